@@ -0,0 +1,226 @@
+use anyhow::{Context, Result, bail};
+use json_patch::jsonptr::PointerBuf;
+use json_patch::{AddOperation, Patch, PatchOperation};
+use k8s_openapi::api::apps::v1::Deployment;
+use kube::core::DynamicObject;
+use kube::core::admission::{AdmissionRequest, AdmissionResponse, AdmissionReview, Operation};
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+
+pub const ANNOTATION_PREFIX: &str = "gitops.operator.";
+const DEFAULT_ADMISSION_PORT: u16 = 8443;
+
+/// Default `gitops.operator.*` annotations for new Deployments in `namespace`
+/// whose labels include every `selector` pair, so app teams only set what
+/// differs (often just `gitops.operator.enabled`). Rules are operator-wide,
+/// loaded from the YAML list at `GITOPS_ADMISSION_DEFAULTS_PATH`.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct AnnotationDefaults {
+    pub namespace: String,
+    #[serde(default)]
+    pub selector: BTreeMap<String, String>,
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl AnnotationDefaults {
+    /// Whether the rule applies to a Deployment in `namespace` with `labels`.
+    pub fn matches(&self, namespace: &str, labels: Option<&BTreeMap<String, String>>) -> bool {
+        self.namespace == namespace
+            && self
+                .selector
+                .iter()
+                .all(|(key, value)| labels.and_then(|labels| labels.get(key)) == Some(value))
+    }
+}
+
+/// Parse a YAML list of defaults. Only `gitops.operator.*` annotations may be
+/// injected.
+pub fn parse_defaults(yaml: &str) -> Result<Vec<AnnotationDefaults>> {
+    let defaults: Vec<AnnotationDefaults> =
+        serde_yaml::from_str(yaml).context("Invalid admission defaults")?;
+
+    for rule in &defaults {
+        if let Some(key) = rule
+            .annotations
+            .keys()
+            .find(|key| !key.starts_with(ANNOTATION_PREFIX))
+        {
+            bail!(
+                "Invalid admission defaults for {}: {} is not a {}* annotation",
+                rule.namespace,
+                key,
+                ANNOTATION_PREFIX
+            );
+        }
+    }
+
+    Ok(defaults)
+}
+
+/// The defaults in the file at `GITOPS_ADMISSION_DEFAULTS_PATH`, if set. A
+/// missing or invalid file is logged and yields no defaults.
+pub fn annotation_defaults() -> Vec<AnnotationDefaults> {
+    let Ok(path) = std::env::var("GITOPS_ADMISSION_DEFAULTS_PATH") else {
+        return vec![];
+    };
+    match std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path))
+        .and_then(|yaml| parse_defaults(&yaml))
+    {
+        Ok(defaults) => defaults,
+        Err(e) => {
+            warn!("{:#}; no annotation defaults apply", e);
+            vec![]
+        }
+    }
+}
+
+/// Serving settings for the mutating admission webhook. The API server only
+/// calls webhooks over HTTPS, so it is enabled by providing a certificate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdmissionSettings {
+    /// `GITOPS_ADMISSION_TLS_CERT`: PEM certificate chain path.
+    pub cert_path: String,
+    /// `GITOPS_ADMISSION_TLS_KEY`: PEM private key path.
+    pub key_path: String,
+    /// `GITOPS_ADMISSION_PORT` (default 8443).
+    pub port: u16,
+    pub defaults: Vec<AnnotationDefaults>,
+}
+
+impl AdmissionSettings {
+    /// Read the settings from the environment, or `None` when the webhook is
+    /// not enabled. Defaults come from the file at
+    /// `GITOPS_ADMISSION_DEFAULTS_PATH`; a missing or invalid file is logged
+    /// and leaves the webhook admitting Deployments unchanged.
+    pub fn from_env() -> Option<Self> {
+        let cert_path = std::env::var("GITOPS_ADMISSION_TLS_CERT").ok()?;
+        let key_path = std::env::var("GITOPS_ADMISSION_TLS_KEY").ok()?;
+        let port = std::env::var("GITOPS_ADMISSION_PORT")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(DEFAULT_ADMISSION_PORT);
+
+        let defaults = annotation_defaults();
+        if !defaults.is_empty() {
+            info!("Loaded {} admission default(s)", defaults.len());
+        }
+
+        Some(Self {
+            cert_path,
+            key_path,
+            port,
+            defaults,
+        })
+    }
+}
+
+/// The annotations to add to a Deployment in `namespace` with `labels` and
+/// `existing` annotations: the matching defaults it does not set itself. When
+/// several rules set the same annotation, the first one wins.
+pub fn missing_annotations(
+    defaults: &[AnnotationDefaults],
+    namespace: &str,
+    labels: Option<&BTreeMap<String, String>>,
+    existing: Option<&BTreeMap<String, String>>,
+) -> BTreeMap<String, String> {
+    let mut missing = BTreeMap::new();
+    for rule in defaults
+        .iter()
+        .filter(|rule| rule.matches(namespace, labels))
+    {
+        for (key, value) in &rule.annotations {
+            if !existing.is_some_and(|existing| existing.contains_key(key)) {
+                missing.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+    }
+    missing
+}
+
+/// JSON patch adding `annotations` to an object whose metadata has
+/// `existing` annotations.
+fn annotations_patch(
+    annotations: BTreeMap<String, String>,
+    existing: Option<&BTreeMap<String, String>>,
+) -> Patch {
+    let operations = if existing.is_none() {
+        vec![PatchOperation::Add(AddOperation {
+            path: PointerBuf::from_tokens(["metadata", "annotations"]),
+            value: serde_json::json!(annotations),
+        })]
+    } else {
+        annotations
+            .into_iter()
+            .map(|(key, value)| {
+                PatchOperation::Add(AddOperation {
+                    path: PointerBuf::from_tokens(["metadata", "annotations", key.as_str()]),
+                    value: serde_json::Value::String(value),
+                })
+            })
+            .collect()
+    };
+    Patch(operations)
+}
+
+fn mutate_request(
+    defaults: &[AnnotationDefaults],
+    request: &AdmissionRequest<Deployment>,
+) -> Result<AdmissionResponse> {
+    let response = AdmissionResponse::from(request);
+    let Some(deployment) = request
+        .object
+        .as_ref()
+        .filter(|_| request.operation == Operation::Create)
+    else {
+        return Ok(response);
+    };
+
+    let namespace = request
+        .namespace
+        .as_deref()
+        .or(deployment.metadata.namespace.as_deref())
+        .unwrap_or_default();
+    let existing = deployment.metadata.annotations.as_ref();
+    let missing = missing_annotations(
+        defaults,
+        namespace,
+        deployment.metadata.labels.as_ref(),
+        existing,
+    );
+    if missing.is_empty() {
+        return Ok(response);
+    }
+
+    info!(
+        "Injecting {} default annotation(s) into {}/{}",
+        missing.len(),
+        namespace,
+        request.name
+    );
+    Ok(response.with_patch(annotations_patch(missing, existing))?)
+}
+
+/// Answer an `AdmissionReview` for a Deployment, adding the matching defaults
+/// to new Deployments. Deployments are always admitted: a webhook failure
+/// must never block a rollout, so errors only skip the mutation.
+pub fn mutate(
+    defaults: &[AnnotationDefaults],
+    review: AdmissionReview<Deployment>,
+) -> AdmissionReview<DynamicObject> {
+    let request: AdmissionRequest<Deployment> = match review.try_into() {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("Invalid admission review: {}", e);
+            return AdmissionResponse::invalid(e.to_string()).into_review();
+        }
+    };
+
+    match mutate_request(defaults, &request) {
+        Ok(response) => response.into_review(),
+        Err(e) => {
+            warn!("Failed to mutate {}: {:#}", request.name, e);
+            AdmissionResponse::from(&request).into_review()
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use crate::history::{enum_name, now_rfc3339};
+use crate::state::{MemoryState, state_store};
+use crate::traits::StateStore;
+use anyhow::{Context, Result, bail};
+use axum::Router;
+use axum::extract::Request;
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::warn;
+use uuid::Uuid;
+
+const APPROVALS_BUCKET: &str = "approvals";
+
+/// Where a pending change stands.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+/// A promotion held back until someone approves it.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct PendingChange {
+    pub id: String,
+    pub namespace: String,
+    pub deployment: String,
+    /// Tag in the manifest when the change was requested.
+    pub from_sha: Option<String>,
+    /// Tag the change promotes to.
+    pub to_sha: String,
+    pub status: ApprovalStatus,
+    pub requested_at: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub decided_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub decided_by: Option<String>,
+}
+
+/// Namespaces whose deployments always need approval, from the
+/// comma-separated `GITOPS_APPROVAL_NAMESPACES` (default none), whatever
+/// their own annotations say.
+pub fn approval_namespaces() -> Vec<String> {
+    std::env::var("GITOPS_APPROVAL_NAMESPACES")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|ns| !ns.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Bearer token the endpoints that change what is deployed (approvals,
+/// rollbacks, pauses, resumes, telemetry reloads) require, from
+/// `GITOPS_APPROVAL_TOKEN`. Without one, they are refused.
+pub fn approval_token() -> Option<String> {
+    std::env::var("GITOPS_APPROVAL_TOKEN")
+        .ok()
+        .filter(|token| !token.trim().is_empty())
+}
+
+/// Whether an `Authorization` header carries `token` as a bearer token. The
+/// comparison takes the same time wherever the first difference is.
+pub fn authorized(header: Option<&str>, token: &str) -> bool {
+    let Some(given) = header.and_then(|h| h.strip_prefix("Bearer ")) else {
+        return false;
+    };
+    let (given, token) = (given.trim().as_bytes(), token.as_bytes());
+    given.len() == token.len()
+        && given
+            .iter()
+            .zip(token)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Middleware refusing requests without the [`approval_token`] as their
+/// bearer token: `401`, or `403` while no token is configured.
+pub async fn require_approval_token(request: Request, next: Next) -> Response {
+    let Some(token) = approval_token() else {
+        return (
+            StatusCode::FORBIDDEN,
+            "GITOPS_APPROVAL_TOKEN is not set".to_string(),
+        )
+            .into_response();
+    };
+    let header = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    if !authorized(header, &token) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            "missing or wrong bearer token".to_string(),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+/// `router` with every route behind [`require_approval_token`].
+pub fn guarded<S: Clone + Send + Sync + 'static>(router: Router<S>) -> Router<S> {
+    router.route_layer(middleware::from_fn(require_approval_token))
+}
+
+/// Promotions awaiting (or granted) approval, one per deployment, kept in a
+/// [`StateStore`] so they survive restarts with a durable backend. Changes
+/// are keyed by `namespace/name`, so a reconcile finds its own without
+/// reading the others.
+pub struct ApprovalQueue {
+    store: Arc<dyn StateStore>,
+    // Serializes read-modify-write cycles over the bucket.
+    updates: Mutex<()>,
+}
+
+impl Default for ApprovalQueue {
+    fn default() -> Self {
+        Self::with_store(Arc::new(MemoryState::new()))
+    }
+}
+
+impl ApprovalQueue {
+    /// A queue kept in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A queue kept in `store`. Changes saved under their id, as earlier
+    /// versions did, are moved under their deployment.
+    pub fn with_store(store: Arc<dyn StateStore>) -> Self {
+        let queue = Self {
+            store,
+            updates: Mutex::new(()),
+        };
+        if let Err(e) = queue.rekey() {
+            warn!("Failed to key approvals by deployment: {:#}", e);
+        }
+        queue
+    }
+
+    fn key(namespace: &str, name: &str) -> String {
+        format!("{}/{}", namespace, name)
+    }
+
+    fn rekey(&self) -> Result<()> {
+        for (key, value) in self.store.entries(APPROVALS_BUCKET)? {
+            let change: PendingChange =
+                serde_json::from_str(&value).context("Failed to read approval")?;
+            if key != Self::key(&change.namespace, &change.deployment) {
+                self.save(&change)?;
+                self.store.remove(APPROVALS_BUCKET, &key)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn save(&self, change: &PendingChange) -> Result<()> {
+        self.store.put(
+            APPROVALS_BUCKET,
+            &Self::key(&change.namespace, &change.deployment),
+            &serde_json::to_string(change)?,
+        )
+    }
+
+    /// Every change, oldest request first.
+    pub fn list(&self) -> Result<Vec<PendingChange>> {
+        let mut changes = self
+            .store
+            .entries(APPROVALS_BUCKET)?
+            .into_iter()
+            .map(|(_, value)| serde_json::from_str(&value).context("Failed to read approval"))
+            .collect::<Result<Vec<PendingChange>>>()?;
+        changes.sort_by(|a, b| a.requested_at.cmp(&b.requested_at));
+        Ok(changes)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<PendingChange>> {
+        Ok(self.list()?.into_iter().find(|c| c.id == id))
+    }
+
+    /// The change of `namespace/name`, if any.
+    pub fn for_deployment(&self, namespace: &str, name: &str) -> Result<Option<PendingChange>> {
+        self.store
+            .get(APPROVALS_BUCKET, &Self::key(namespace, name))?
+            .map(|value| serde_json::from_str(&value).context("Failed to read approval"))
+            .transpose()
+    }
+
+    /// The change promoting `namespace/name` to `to_sha`, queued as pending
+    /// if there is none yet; a change to another tag is replaced. Returns
+    /// the change and whether it was just queued.
+    pub fn request(
+        &self,
+        namespace: &str,
+        name: &str,
+        from_sha: Option<String>,
+        to_sha: &str,
+    ) -> Result<(PendingChange, bool)> {
+        let _guard = self.updates.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(existing) = self.for_deployment(namespace, name)?
+            && existing.to_sha == to_sha
+        {
+            return Ok((existing, false));
+        }
+
+        let change = PendingChange {
+            id: Uuid::new_v4().simple().to_string()[..12].to_string(),
+            namespace: namespace.to_string(),
+            deployment: name.to_string(),
+            from_sha,
+            to_sha: to_sha.to_string(),
+            status: ApprovalStatus::Pending,
+            requested_at: now_rfc3339(),
+            decided_at: None,
+            decided_by: None,
+        };
+        self.save(&change)?;
+        Ok((change, true))
+    }
+
+    /// Approve or reject the pending change `id`. `None` if there is no such
+    /// change; an error if it was already decided.
+    pub fn decide(
+        &self,
+        id: &str,
+        approve: bool,
+        by: Option<&str>,
+    ) -> Result<Option<PendingChange>> {
+        let _guard = self.updates.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(mut change) = self.get(id)? else {
+            return Ok(None);
+        };
+        if change.status != ApprovalStatus::Pending {
+            bail!("Change {} was already {}", id, enum_name(&change.status));
+        }
+
+        change.status = if approve {
+            ApprovalStatus::Approved
+        } else {
+            ApprovalStatus::Rejected
+        };
+        change.decided_at = Some(now_rfc3339());
+        change.decided_by = by.map(String::from);
+        self.save(&change)?;
+        Ok(Some(change))
+    }
+
+    /// Forget the change of `namespace/name` once its promotion went through
+    /// (or is no longer needed).
+    pub fn complete(&self, namespace: &str, name: &str) -> Result<()> {
+        let _guard = self.updates.lock().unwrap_or_else(|e| e.into_inner());
+        self.store
+            .remove(APPROVALS_BUCKET, &Self::key(namespace, name))
+    }
+}
+
+/// Process-wide approval queue, kept in the operator's [`state_store`].
+pub fn approval_queue() -> Arc<ApprovalQueue> {
+    static QUEUE: OnceLock<Arc<ApprovalQueue>> = OnceLock::new();
+    QUEUE
+        .get_or_init(|| Arc::new(ApprovalQueue::with_store(state_store())))
+        .clone()
+}
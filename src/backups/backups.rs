@@ -0,0 +1,170 @@
+use crate::history::now_rfc3339;
+use anyhow::{Context, Result};
+use git2::{ObjectType, Oid};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::{info, warn};
+
+const DEFAULT_BACKUP_DIR: &str = "/tmp/gitops-operator-backups";
+const DEFAULT_BACKUP_MAX_ENTRIES: usize = 500;
+const INDEX_FILE: &str = "index.json";
+
+/// A manifest as it was right before the operator overwrote it.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct BackupRecord {
+    /// The deployment (`namespace/name`) whose pass patched the file.
+    pub deployment: String,
+    /// Path of the file within the manifests repository.
+    pub path: String,
+    /// Git blob id of the original content, which names it in the store
+    /// (`git hash-object` of a restored file prints the same id).
+    pub hash: String,
+    pub size: u64,
+    pub stored_at: String,
+}
+
+/// Content-addressed copies of manifests taken before they are patched, so
+/// a bad patch can be undone by hand without digging through the repository
+/// history. Holds at most `max_entries` records; blobs no record refers to
+/// any more are deleted.
+pub struct BackupStore {
+    dir: PathBuf,
+    max_entries: usize,
+    records: Mutex<VecDeque<BackupRecord>>,
+}
+
+impl BackupStore {
+    /// Open (or create) the store in `dir`, loading its index.
+    pub fn open(dir: impl Into<PathBuf>, max_entries: usize) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create backup directory {}", dir.display()))?;
+
+        let index = dir.join(INDEX_FILE);
+        let records = match fs::read(&index) {
+            Ok(raw) => serde_json::from_slice(&raw)
+                .with_context(|| format!("Failed to parse {}", index.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => VecDeque::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", index.display())),
+        };
+
+        Ok(Self {
+            dir,
+            max_entries,
+            records: Mutex::new(records),
+        })
+    }
+
+    /// The store configured by `GITOPS_BACKUP_DIR` (default
+    /// `/tmp/gitops-operator-backups`) and `GITOPS_BACKUP_MAX_ENTRIES`
+    /// (default 500, 0 disables backups).
+    pub fn from_env() -> Option<Self> {
+        let max_entries = std::env::var("GITOPS_BACKUP_MAX_ENTRIES")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(DEFAULT_BACKUP_MAX_ENTRIES);
+        if max_entries == 0 {
+            info!("Manifest backups disabled");
+            return None;
+        }
+
+        let dir =
+            std::env::var("GITOPS_BACKUP_DIR").unwrap_or_else(|_| DEFAULT_BACKUP_DIR.to_string());
+        match Self::open(&dir, max_entries) {
+            Ok(store) => Some(store),
+            Err(e) => {
+                warn!("{:#}; manifest backups disabled", e);
+                None
+            }
+        }
+    }
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(&hash[..2]).join(&hash[2..])
+    }
+
+    /// Store `content`, the current state of `path` for `deployment`. The
+    /// same content backed up again for the same file adds no record.
+    pub fn backup(&self, deployment: &str, path: &str, content: &[u8]) -> Result<BackupRecord> {
+        let hash = Oid::hash_object(ObjectType::Blob, content)?.to_string();
+        let blob = self.blob_path(&hash);
+        if !blob.exists() {
+            fs::create_dir_all(blob.parent().unwrap_or(&self.dir))?;
+            write_atomically(&blob, content)?;
+        }
+
+        let record = BackupRecord {
+            deployment: deployment.to_string(),
+            path: path.to_string(),
+            hash,
+            size: content.len() as u64,
+            stored_at: now_rfc3339(),
+        };
+
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        let latest = records
+            .iter()
+            .rev()
+            .find(|r| r.deployment == record.deployment && r.path == record.path);
+        if latest.is_some_and(|latest| latest.hash == record.hash) {
+            return Ok(record);
+        }
+
+        records.push_back(record.clone());
+        while records.len() > self.max_entries {
+            if let Some(dropped) = records.pop_front()
+                && !records.iter().any(|r| r.hash == dropped.hash)
+            {
+                let _ = fs::remove_file(self.blob_path(&dropped.hash));
+            }
+        }
+        let index = serde_json::to_vec(&*records)?;
+        write_atomically(&self.dir.join(INDEX_FILE), &index)?;
+
+        Ok(record)
+    }
+
+    /// Backups of `deployment` (every deployment when `None`), newest first.
+    pub fn records(&self, deployment: Option<&str>, limit: usize) -> Vec<BackupRecord> {
+        let records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        records
+            .iter()
+            .rev()
+            .filter(|r| deployment.is_none_or(|d| r.deployment == d))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// The content stored under `hash`, if `deployment` has a backup with it.
+    pub fn content(&self, deployment: &str, hash: &str) -> Result<Option<Vec<u8>>> {
+        let known = self
+            .records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .any(|r| r.deployment == deployment && r.hash == hash);
+        if !known {
+            return Ok(None);
+        }
+
+        Ok(Some(fs::read(self.blob_path(hash))?))
+    }
+}
+
+fn write_atomically(path: &Path, content: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// The operator's backup store, if backups are enabled.
+pub fn backup_store() -> Option<Arc<BackupStore>> {
+    static STORE: OnceLock<Option<Arc<BackupStore>>> = OnceLock::new();
+    STORE
+        .get_or_init(|| BackupStore::from_env().map(Arc::new))
+        .clone()
+}
@@ -0,0 +1,356 @@
+use crate::http_client::http_client;
+use crate::pull_requests::{ChangeRequest, PullRequest, split_repository_url};
+use crate::retry::with_retries;
+use crate::traits::PullRequestOpener;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+/// The `workspace/repository` of a Bitbucket Cloud clone URL, e.g.
+/// `git@bitbucket.org:platform/manifests.git` -> `platform/manifests`.
+pub fn parse_bitbucket_cloud_repository(url: &str) -> Option<String> {
+    let (host, path) = split_repository_url(url)?;
+    if host != "bitbucket.org" || path.matches('/').count() != 1 {
+        return None;
+    }
+    Some(path)
+}
+
+/// Where the REST API of the Bitbucket Server (or Data Center) instance
+/// hosting `url` is served, and the project key and repository slug there.
+/// Both `ssh://git@host:7999/PROJ/repo.git` and
+/// `https://host/scm/PROJ/repo.git` clone URLs are understood.
+pub fn parse_bitbucket_server_repository(url: &str) -> Option<(String, String, String)> {
+    let (host, path) = split_repository_url(url)?;
+    let path = path.strip_prefix("scm/").unwrap_or(&path);
+    let (project, repo) = path.split_once('/')?;
+    if repo.contains('/') {
+        return None;
+    }
+    Some((
+        format!("https://{}/rest/api/1.0", host),
+        project.to_string(),
+        repo.to_string(),
+    ))
+}
+
+/// Authenticate with `token`: an app password (or any password) given as
+/// `username:password` is sent with basic auth, anything else as a bearer
+/// access token.
+fn authorize(request: RequestBuilder, token: &str) -> RequestBuilder {
+    match token.split_once(':') {
+        Some((username, password)) => request.basic_auth(username, Some(password)),
+        None => request.bearer_auth(token),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudPage {
+    values: Vec<CloudPullRequest>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudPullRequest {
+    id: u64,
+    links: CloudLinks,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudLinks {
+    html: Link,
+}
+
+#[derive(Debug, Deserialize)]
+struct Link {
+    href: String,
+}
+
+impl From<CloudPullRequest> for PullRequest {
+    fn from(response: CloudPullRequest) -> Self {
+        PullRequest {
+            number: response.id,
+            url: response.links.html.href,
+        }
+    }
+}
+
+/// Opens pull requests on Bitbucket Cloud through its REST API (2.0).
+#[derive(Debug)]
+pub struct BitbucketCloudPullRequests {
+    client: ClientWithMiddleware,
+    api_base: String,
+}
+
+impl Default for BitbucketCloudPullRequests {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BitbucketCloudPullRequests {
+    pub fn new() -> Self {
+        Self::with_api_base("https://api.bitbucket.org/2.0".to_string())
+    }
+
+    pub fn with_api_base(api_base: String) -> Self {
+        Self {
+            client: with_retries(http_client()),
+            api_base,
+        }
+    }
+
+    fn request(&self, method: reqwest::Method, url: &str, token: &str) -> RequestBuilder {
+        authorize(self.client.request(method, url), token).header("User-Agent", "gitops-operator")
+    }
+}
+
+#[async_trait]
+impl PullRequestOpener for BitbucketCloudPullRequests {
+    #[tracing::instrument(name = "open_bitbucket_pull_request", skip(self, token), fields())]
+    async fn open_pull_request(&self, change: &ChangeRequest, token: &str) -> Result<PullRequest> {
+        let repo = parse_bitbucket_cloud_repository(&change.repository).ok_or_else(|| {
+            anyhow::anyhow!("{} is not a Bitbucket Cloud repository", change.repository)
+        })?;
+        let url = format!("{}/repositories/{}/pullrequests", self.api_base, repo);
+
+        let query = format!(
+            "source.branch.name = \"{}\" AND destination.branch.name = \"{}\"",
+            change.head, change.base
+        );
+        let response = self
+            .request(reqwest::Method::GET, &url, token)
+            .query(&[("state", "OPEN"), ("q", query.as_str())])
+            .send()
+            .await
+            .context("Failed to list Bitbucket pull requests")?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Bitbucket refused to list the pull requests of {}: {}",
+                repo,
+                response.status()
+            );
+        }
+        let open: CloudPage = response
+            .json()
+            .await
+            .context("Failed to parse Bitbucket pull requests")?;
+        if let Some(existing) = open.values.into_iter().next() {
+            info!("Pull request #{} is already open", existing.id);
+            return Ok(existing.into());
+        }
+
+        if !change.labels.is_empty() {
+            warn!("Bitbucket has no pull request labels, not applying them");
+        }
+        // Bitbucket has reviewers rather than assignees, named by account
+        // id or `{uuid}`.
+        let reviewers: Vec<_> = change
+            .assignees
+            .iter()
+            .map(|reviewer| {
+                if reviewer.starts_with('{') {
+                    json!({ "uuid": reviewer })
+                } else {
+                    json!({ "account_id": reviewer })
+                }
+            })
+            .collect();
+        let response = self
+            .request(reqwest::Method::POST, &url, token)
+            .json(&json!({
+                "title": change.title,
+                "description": change.body,
+                "source": { "branch": { "name": change.head } },
+                "destination": { "branch": { "name": change.base } },
+                "reviewers": reviewers,
+                "close_source_branch": true,
+            }))
+            .send()
+            .await
+            .context("Failed to open Bitbucket pull request")?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let detail = response.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "Bitbucket refused to open a pull request on {}: {} {}",
+                repo,
+                status,
+                detail
+            );
+        }
+        let opened: CloudPullRequest = response
+            .json()
+            .await
+            .context("Failed to parse Bitbucket pull request")?;
+        info!(
+            "Opened pull request #{}: {}",
+            opened.id, opened.links.html.href
+        );
+        Ok(opened.into())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ServerPage {
+    values: Vec<ServerPullRequest>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerPullRequest {
+    id: u64,
+    to_ref: ServerRef,
+    links: ServerLinks,
+}
+
+#[derive(Debug, Deserialize)]
+struct ServerRef {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ServerLinks {
+    #[serde(rename = "self")]
+    web: Vec<Link>,
+}
+
+impl From<ServerPullRequest> for PullRequest {
+    fn from(response: ServerPullRequest) -> Self {
+        PullRequest {
+            number: response.id,
+            url: response
+                .links
+                .web
+                .into_iter()
+                .next()
+                .map(|link| link.href)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Opens pull requests on Bitbucket Server and Data Center through their
+/// REST API (1.0).
+#[derive(Debug)]
+pub struct BitbucketServerPullRequests {
+    client: ClientWithMiddleware,
+    /// Overrides the API base derived from each repository's host.
+    api_base: Option<String>,
+}
+
+impl Default for BitbucketServerPullRequests {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BitbucketServerPullRequests {
+    pub fn new() -> Self {
+        Self {
+            client: with_retries(http_client()),
+            api_base: None,
+        }
+    }
+
+    pub fn with_api_base(api_base: String) -> Self {
+        Self {
+            api_base: Some(api_base),
+            ..Self::new()
+        }
+    }
+
+    fn request(&self, method: reqwest::Method, url: &str, token: &str) -> RequestBuilder {
+        authorize(self.client.request(method, url), token).header("User-Agent", "gitops-operator")
+    }
+}
+
+#[async_trait]
+impl PullRequestOpener for BitbucketServerPullRequests {
+    #[tracing::instrument(
+        name = "open_bitbucket_server_pull_request",
+        skip(self, token),
+        fields()
+    )]
+    async fn open_pull_request(&self, change: &ChangeRequest, token: &str) -> Result<PullRequest> {
+        let (api_base, project, repo) = parse_bitbucket_server_repository(&change.repository)
+            .ok_or_else(|| {
+                anyhow::anyhow!("{} is not a Bitbucket Server repository", change.repository)
+            })?;
+        let api_base = self.api_base.clone().unwrap_or(api_base);
+        let url = format!(
+            "{}/projects/{}/repos/{}/pull-requests",
+            api_base, project, repo
+        );
+        let head = format!("refs/heads/{}", change.head);
+        let base = format!("refs/heads/{}", change.base);
+
+        let response = self
+            .request(reqwest::Method::GET, &url, token)
+            .query(&[
+                ("state", "OPEN"),
+                ("direction", "OUTGOING"),
+                ("at", head.as_str()),
+            ])
+            .send()
+            .await
+            .context("Failed to list Bitbucket Server pull requests")?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Bitbucket Server refused to list the pull requests of {}/{}: {}",
+                project,
+                repo,
+                response.status()
+            );
+        }
+        let open: ServerPage = response
+            .json()
+            .await
+            .context("Failed to parse Bitbucket Server pull requests")?;
+        if let Some(existing) = open.values.into_iter().find(|pr| pr.to_ref.id == base) {
+            info!("Pull request #{} is already open", existing.id);
+            return Ok(existing.into());
+        }
+
+        if !change.labels.is_empty() {
+            warn!("Bitbucket Server has no pull request labels, not applying them");
+        }
+        let reviewers: Vec<_> = change
+            .assignees
+            .iter()
+            .map(|reviewer| json!({ "user": { "name": reviewer } }))
+            .collect();
+        let response = self
+            .request(reqwest::Method::POST, &url, token)
+            .json(&json!({
+                "title": change.title,
+                "description": change.body,
+                "fromRef": { "id": head },
+                "toRef": { "id": base },
+                "reviewers": reviewers,
+            }))
+            .send()
+            .await
+            .context("Failed to open Bitbucket Server pull request")?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let detail = response.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "Bitbucket Server refused to open a pull request on {}/{}: {} {}",
+                project,
+                repo,
+                status,
+                detail
+            );
+        }
+        let opened: PullRequest = response
+            .json::<ServerPullRequest>()
+            .await
+            .context("Failed to parse Bitbucket Server pull request")?
+            .into();
+        info!("Opened pull request #{}: {}", opened.number, opened.url);
+        Ok(opened)
+    }
+}
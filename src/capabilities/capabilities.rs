@@ -0,0 +1,146 @@
+use kube::Client;
+use kube::core::GroupVersionKind;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use tracing::{info, warn};
+
+/// The Events API the operator records Deployment events through.
+pub const EVENTS_RESOURCE: &str = "events.k8s.io/v1/Event";
+/// Argo Rollouts, whose manifests `patch_expression` can patch.
+pub const ARGO_ROLLOUT_RESOURCE: &str = "argoproj.io/v1alpha1/Rollout";
+/// Flux, which may be the CD tool rolling out the manifests.
+pub const FLUX_KUSTOMIZATION_RESOURCE: &str = "kustomize.toolkit.fluxcd.io/v1/Kustomization";
+pub const FLUX_HELM_RELEASE_RESOURCE: &str = "helm.toolkit.fluxcd.io/v2/HelmRelease";
+
+/// What the cluster the operator runs in supports, probed once at startup
+/// and served by `/version`.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct Capabilities {
+    pub operator_version: String,
+    /// `gitVersion` of the API server, e.g. `v1.31.2`.
+    pub kubernetes_version: Option<String>,
+    pub platform: Option<String>,
+    /// Optional resources (`group/version/Kind`) and whether they are
+    /// served. Resources that could not be probed are left out.
+    pub resources: BTreeMap<String, bool>,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            operator_version: env!("CARGO_PKG_VERSION").to_string(),
+            kubernetes_version: None,
+            platform: None,
+            resources: BTreeMap::new(),
+        }
+    }
+}
+
+impl Capabilities {
+    /// Whether `resource` is served. Unprobed resources are assumed to be,
+    /// so a failed probe never turns a feature off.
+    pub fn has(&self, resource: &str) -> bool {
+        self.resources.get(resource).copied().unwrap_or(true)
+    }
+
+    pub fn has_kind(&self, gvk: &GroupVersionKind) -> bool {
+        self.has(&resource_name(gvk))
+    }
+}
+
+/// `group/version/Kind` (`version/Kind` for the core group).
+pub fn resource_name(gvk: &GroupVersionKind) -> String {
+    if gvk.group.is_empty() {
+        format!("{}/{}", gvk.version, gvk.kind)
+    } else {
+        format!("{}/{}/{}", gvk.group, gvk.version, gvk.kind)
+    }
+}
+
+fn parse_resource_name(name: &str) -> GroupVersionKind {
+    match name.rsplitn(3, '/').collect::<Vec<_>>()[..] {
+        [kind, version, group] => GroupVersionKind::gvk(group, version, kind),
+        [kind, version] => GroupVersionKind::gvk("", version, kind),
+        _ => GroupVersionKind::gvk("", "", name),
+    }
+}
+
+/// Whether the API server serves `gvk`: `Some(false)` when its group version
+/// or kind is missing, `None` when discovery failed for another reason.
+async fn serves(client: &Client, gvk: &GroupVersionKind) -> Option<bool> {
+    let resources = if gvk.group.is_empty() {
+        client.list_core_api_resources(&gvk.version).await
+    } else {
+        client
+            .list_api_group_resources(&format!("{}/{}", gvk.group, gvk.version))
+            .await
+    };
+
+    match resources {
+        Ok(list) => Some(list.resources.iter().any(|r| r.kind == gvk.kind)),
+        Err(kube::Error::Api(status)) if status.is_not_found() => Some(false),
+        Err(e) => {
+            warn!("Failed to probe {}: {}", resource_name(gvk), e);
+            None
+        }
+    }
+}
+
+/// Probe the API server's version and the optional resources the operator
+/// knows about, plus `extra` (e.g. the configured maintenance window kinds).
+pub async fn probe(client: &Client, extra: &[GroupVersionKind]) -> Capabilities {
+    let mut capabilities = Capabilities::default();
+
+    match client.apiserver_version().await {
+        Ok(info) => {
+            capabilities.kubernetes_version = Some(info.git_version);
+            capabilities.platform = Some(info.platform);
+        }
+        Err(e) => warn!("Failed to read the API server version: {}", e),
+    }
+
+    let known = [
+        EVENTS_RESOURCE,
+        ARGO_ROLLOUT_RESOURCE,
+        FLUX_KUSTOMIZATION_RESOURCE,
+        FLUX_HELM_RELEASE_RESOURCE,
+    ]
+    .into_iter()
+    .map(parse_resource_name);
+    for gvk in known.chain(extra.iter().cloned()) {
+        if let Some(served) = serves(client, &gvk).await {
+            capabilities.resources.insert(resource_name(&gvk), served);
+        }
+    }
+
+    info!(
+        "Kubernetes {} ({} of {} optional resources served)",
+        capabilities
+            .kubernetes_version
+            .as_deref()
+            .unwrap_or("unknown"),
+        capabilities
+            .resources
+            .values()
+            .filter(|&&served| served)
+            .count(),
+        capabilities.resources.len()
+    );
+    capabilities
+}
+
+static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+
+/// Record the startup probe; only the first call has an effect.
+pub fn set_capabilities(capabilities: Capabilities) {
+    let _ = CAPABILITIES.set(capabilities);
+}
+
+/// The capabilities probed at startup (all assumed present before that, and
+/// in tests).
+pub fn capabilities() -> &'static Capabilities {
+    static UNPROBED: OnceLock<Capabilities> = OnceLock::new();
+    CAPABILITIES
+        .get()
+        .unwrap_or_else(|| UNPROBED.get_or_init(Capabilities::default))
+}
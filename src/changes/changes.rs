@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// How long a successful pass vouches for an unchanged deployment, from
+/// `GITOPS_CHANGE_CACHE_TTL_SECS` (default 0: every pass does the full work).
+pub fn change_ttl() -> Duration {
+    Duration::from_secs(
+        std::env::var("GITOPS_CHANGE_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(0),
+    )
+}
+
+/// Fingerprint of what a pass acts on: the deployment's resolved
+/// configuration and the tag it would promote (from the app SHA or the
+/// registry). Only compared within this process.
+pub fn fingerprint(config: &impl serde::Serialize, tag: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(config)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    tag.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Fingerprints of the last successful pass per deployment (`namespace/name`),
+/// so passes over a deployment whose configuration and app haven't moved can
+/// stop before touching manifests or the registry.
+#[derive(Default)]
+pub struct ChangeCache {
+    seen: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl ChangeCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `key` was last reconciled successfully with `fingerprint`,
+    /// less than `ttl` ago.
+    pub fn unchanged(&self, key: &str, fingerprint: &str, ttl: Duration) -> bool {
+        let seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.get(key)
+            .is_some_and(|(seen, at)| seen == fingerprint && at.elapsed() < ttl)
+    }
+
+    /// Remember that `key` was reconciled successfully with `fingerprint`.
+    pub fn record(&self, key: &str, fingerprint: String) {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.insert(key.to_string(), (fingerprint, Instant::now()));
+    }
+
+    /// Make the next pass over `key` do the full work, e.g. after its
+    /// manifest was changed behind the reconcile loop's back.
+    pub fn forget(&self, key: &str) {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.remove(key);
+    }
+}
+
+/// The operator's shared change cache.
+pub fn change_cache() -> Arc<ChangeCache> {
+    static CACHE: OnceLock<Arc<ChangeCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Arc::new(ChangeCache::new())).clone()
+}
@@ -0,0 +1,190 @@
+use crate::metrics::WEBHOOK_EVENTS_COALESCED_TOTAL;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::info;
+
+/// How long a registry webhook waits for further events about the same
+/// repository and branch before reconciling, from
+/// `GITOPS_WEBHOOK_COALESCE_MS` (default 0: reconcile at once).
+pub fn coalesce_window() -> Duration {
+    std::env::var("GITOPS_WEBHOOK_COALESCE_MS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .map(Duration::from_millis)
+        .unwrap_or_default()
+}
+
+/// What one trigger gets back from the batch it joined.
+#[derive(Debug)]
+pub struct Coalesced<O> {
+    pub output: Arc<O>,
+    /// How many triggers the batch ran for, this one included.
+    pub events: usize,
+}
+
+impl<O> Clone for Coalesced<O> {
+    fn clone(&self) -> Self {
+        Self {
+            output: self.output.clone(),
+            events: self.events,
+        }
+    }
+}
+
+struct Batch<I, O> {
+    items: Vec<I>,
+    events: usize,
+    done: watch::Receiver<Option<Coalesced<O>>>,
+}
+
+/// Batches the triggers arriving for the same key within a window, so their
+/// work runs once (with every trigger's items) and each trigger gets the
+/// shared result.
+pub struct Coalescer<I, O> {
+    batches: Mutex<HashMap<String, Batch<I, O>>>,
+}
+
+impl<I, O> Default for Coalescer<I, O> {
+    fn default() -> Self {
+        Self {
+            batches: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<I, O> Coalescer<I, O>
+where
+    I: Send + 'static,
+    O: Send + Sync + 'static,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `items` to the batch for `key`, opening one if there is none. The
+    /// trigger opening a batch has `run` called with the items of the whole
+    /// batch once `window` has passed; later triggers' `run` is dropped. The
+    /// batch runs in its own task, so it completes even if its triggers
+    /// give up waiting. A zero `window` runs at once.
+    pub async fn submit<F, Fut>(
+        self: &Arc<Self>,
+        key: &str,
+        items: Vec<I>,
+        window: Duration,
+        run: F,
+    ) -> Result<Coalesced<O>>
+    where
+        F: FnOnce(Vec<I>) -> Fut + Send + 'static,
+        Fut: Future<Output = O> + Send + 'static,
+    {
+        if window.is_zero() {
+            return Ok(Coalesced {
+                output: Arc::new(run(items).await),
+                events: 1,
+            });
+        }
+
+        let mut done = {
+            let mut batches = self.batches.lock().unwrap_or_else(|e| e.into_inner());
+            match batches.get_mut(key) {
+                Some(batch) => {
+                    batch.items.extend(items);
+                    batch.events += 1;
+                    batch.done.clone()
+                }
+                None => {
+                    let (tx, rx) = watch::channel(None);
+                    batches.insert(
+                        key.to_string(),
+                        Batch {
+                            items,
+                            events: 1,
+                            done: rx.clone(),
+                        },
+                    );
+                    self.spawn_batch(key.to_string(), window, tx, run);
+                    rx
+                }
+            }
+        };
+
+        // The sender is only dropped without a result if the batch panicked.
+        let result = done
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| anyhow::anyhow!("Coalesced batch for {} ended without a result", key))?;
+        Ok(result.clone().expect("waited for a result"))
+    }
+
+    fn spawn_batch<F, Fut>(
+        self: &Arc<Self>,
+        key: String,
+        window: Duration,
+        tx: watch::Sender<Option<Coalesced<O>>>,
+        run: F,
+    ) where
+        F: FnOnce(Vec<I>) -> Fut + Send + 'static,
+        Fut: Future<Output = O> + Send + 'static,
+    {
+        let coalescer = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+            let batch = {
+                let mut batches = coalescer.batches.lock().unwrap_or_else(|e| e.into_inner());
+                batches.remove(&key).expect("a batch is only removed here")
+            };
+
+            if batch.events > 1 {
+                info!("Coalesced {} triggers for {}", batch.events, key);
+                ::metrics::counter!(WEBHOOK_EVENTS_COALESCED_TOTAL)
+                    .increment(batch.events as u64 - 1);
+            }
+            let output = run(batch.items).await;
+            let _ = tx.send(Some(Coalesced {
+                output: Arc::new(output),
+                events: batch.events,
+            }));
+        });
+    }
+}
+
+/// Work done once per key within a reconcile run, e.g. fetching a repository
+/// several deployments track: the first caller runs it, callers arriving
+/// meanwhile wait for it, and all of them get its result.
+pub struct OncePerRun<T> {
+    cells: Mutex<HashMap<String, Arc<tokio::sync::OnceCell<T>>>>,
+}
+
+impl<T> Default for OncePerRun<T> {
+    fn default() -> Self {
+        Self {
+            cells: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T: Clone> OncePerRun<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The result for `key`, from `run` if nobody ran it yet.
+    pub async fn get_or_run<F, Fut>(&self, key: &str, run: F) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let cell = {
+            let mut cells = self.cells.lock().unwrap_or_else(|e| e.into_inner());
+            cells.entry(key.to_string()).or_default().clone()
+        };
+        if cell.initialized() {
+            info!("Reusing {} from earlier in this run", key);
+        }
+        cell.get_or_init(run).await.clone()
+    }
+}
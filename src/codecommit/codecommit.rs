@@ -0,0 +1,406 @@
+use crate::http_client::blocking_client_builder;
+use anyhow::{Context, Result};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use serde::Deserialize;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info};
+
+/// Credentials are refreshed once they are this close to expiring.
+const REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+/// Where the instance metadata service answers.
+const IMDS_ENDPOINT: &str = "http://169.254.169.254";
+
+/// Whether `url` is a CodeCommit repository: a `codecommit::` URL as used by
+/// git-remote-codecommit, or the HTTPS endpoint of a region.
+pub fn is_codecommit_url(url: &str) -> bool {
+    https_url(url).is_some()
+}
+
+/// The HTTPS clone URL of a CodeCommit repository, from either
+/// `codecommit::<region>://[<profile>@]<repo>`, `codecommit://[<profile>@]<repo>`
+/// (in the region of `AWS_REGION`) or the HTTPS URL itself. Profiles are
+/// ignored: credentials always come from the operator's environment.
+pub fn https_url(url: &str) -> Option<String> {
+    let url = url.trim();
+    if let Some(rest) = url.strip_prefix("codecommit:") {
+        let (region, repo) = match rest.strip_prefix(':') {
+            Some(rest) => {
+                let (region, repo) = rest.split_once("://")?;
+                (region.to_string(), repo)
+            }
+            None => (
+                std::env::var("AWS_REGION")
+                    .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+                    .ok()?,
+                rest.strip_prefix("//")?,
+            ),
+        };
+        let repo = repo.rsplit('@').next().unwrap_or(repo);
+        if region.is_empty() || repo.is_empty() || repo.contains('/') {
+            return None;
+        }
+        return Some(format!(
+            "https://git-codecommit.{}.amazonaws.com/v1/repos/{}",
+            region, repo
+        ));
+    }
+    let (region, path) = codecommit_endpoint(url)?;
+    Some(format!(
+        "https://git-codecommit.{}.amazonaws.com{}",
+        region, path
+    ))
+}
+
+/// The region and path of a CodeCommit HTTPS URL.
+fn codecommit_endpoint(url: &str) -> Option<(String, String)> {
+    let rest = url.trim().strip_prefix("https://")?;
+    let (authority, path) = rest.split_once('/')?;
+    let host = authority.rsplit('@').next().unwrap_or(authority);
+    let region = host
+        .strip_prefix("git-codecommit.")?
+        .strip_suffix(".amazonaws.com")?;
+    if region.is_empty() || region.contains('.') {
+        return None;
+    }
+    Some((region.to_string(), format!("/{}", path)))
+}
+
+/// AWS credentials the operator signs CodeCommit requests with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+    /// When temporary credentials stop working.
+    pub expires_at: Option<SystemTime>,
+}
+
+impl AwsCredentials {
+    fn is_fresh(&self, now: SystemTime) -> bool {
+        self.expires_at
+            .is_none_or(|expires_at| now + REFRESH_MARGIN < expires_at)
+    }
+}
+
+/// The username and password git authenticates to the CodeCommit HTTPS
+/// `url` with, signed with SigV4 at `now` the way git-remote-codecommit
+/// does. `None` when `url` isn't a CodeCommit HTTPS URL.
+pub fn git_credentials(
+    url: &str,
+    credentials: &AwsCredentials,
+    now: SystemTime,
+) -> Option<(String, String)> {
+    let (region, path) = codecommit_endpoint(url)?;
+    let host = format!("git-codecommit.{}.amazonaws.com", region);
+    let (date, timestamp) = utc_timestamp(now);
+
+    let canonical_request = format!("GIT\n{}\n\nhost:{}\n\nhost\n", path, host);
+    let scope = format!("{}/{}/codecommit/aws4_request", date, region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        timestamp,
+        scope,
+        hex(&openssl::sha::sha256(canonical_request.as_bytes()))
+    );
+
+    let key = format!("AWS4{}", credentials.secret_access_key);
+    let key = hmac(key.as_bytes(), date.as_bytes());
+    let key = hmac(&key, region.as_bytes());
+    let key = hmac(&key, b"codecommit");
+    let key = hmac(&key, b"aws4_request");
+    let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+
+    let username = match &credentials.session_token {
+        Some(token) => format!("{}%{}", credentials.access_key_id, token),
+        None => credentials.access_key_id.clone(),
+    };
+    Some((username, format!("{}Z{}", timestamp, signature)))
+}
+
+/// The `Authorization` (and `X-Amz-Date`, `X-Amz-Security-Token`) headers
+/// signing an AWS API request with SigV4 at `now`. `query` is the canonical
+/// query string (sorted and encoded, empty for none) and `headers` the other
+/// headers to sign, `host` included, with lowercase names.
+#[allow(clippy::too_many_arguments)]
+pub fn sign_request(
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    method: &str,
+    path: &str,
+    query: &str,
+    headers: &[(&str, &str)],
+    payload: &[u8],
+    now: SystemTime,
+) -> Vec<(String, String)> {
+    let (date, timestamp) = utc_timestamp(now);
+    let timestamp = format!("{}Z", timestamp);
+
+    let mut signed: Vec<(String, String)> = headers
+        .iter()
+        .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+        .collect();
+    let mut added = vec![("x-amz-date".to_string(), timestamp.clone())];
+    if let Some(token) = &credentials.session_token {
+        added.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+    signed.extend(added.iter().cloned());
+    signed.sort();
+
+    let names = signed
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = signed
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .collect();
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        path,
+        query,
+        canonical_headers,
+        names,
+        hex(&openssl::sha::sha256(payload))
+    );
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        timestamp,
+        scope,
+        hex(&openssl::sha::sha256(canonical_request.as_bytes()))
+    );
+
+    let key = format!("AWS4{}", credentials.secret_access_key);
+    let key = hmac(key.as_bytes(), date.as_bytes());
+    let key = hmac(&key, region.as_bytes());
+    let key = hmac(&key, service.as_bytes());
+    let key = hmac(&key, b"aws4_request");
+    let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+
+    added.push((
+        "authorization".to_string(),
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            credentials.access_key_id, scope, names, signature
+        ),
+    ));
+    added
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    // HMAC-SHA256 over in-memory buffers can't fail.
+    let key = PKey::hmac(key).expect("HMAC key");
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).expect("HMAC signer");
+    signer.update(data).expect("HMAC update");
+    signer.sign_to_vec().expect("HMAC sign")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `now` as the date (`20261016`) and timestamp (`20261016T093000`) SigV4
+/// signs with, in UTC.
+fn utc_timestamp(now: SystemTime) -> (String, String) {
+    let secs = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let seconds = secs % 86400;
+    let date = format!("{:04}{:02}{:02}", year, month, day);
+    let timestamp = format!(
+        "{}T{:02}{:02}{:02}",
+        date,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    (date, timestamp)
+}
+
+/// The proleptic Gregorian date `days` days after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Days from 1970-01-01 to the proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Parse an ISO 8601 UTC time such as `2026-10-16T09:30:00Z`, as AWS reports
+/// credential expirations.
+pub fn parse_expiration(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+    let number = |range: std::ops::Range<usize>| value.get(range)?.parse::<u32>().ok();
+    let days = days_from_civil(i64::from(number(0..4)?), number(5..7)?, number(8..10)?);
+    let seconds = i64::from(number(11..13)?) * 3600
+        + i64::from(number(14..16)?) * 60
+        + i64::from(number(17..19)?);
+    let secs = u64::try_from(days * 86400 + seconds).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+static CREDENTIALS: Mutex<Option<AwsCredentials>> = Mutex::new(None);
+
+/// The operator's AWS credentials, looked up in the order the AWS SDKs use:
+/// the `AWS_ACCESS_KEY_ID`/`AWS_SECRET_ACCESS_KEY` environment variables,
+/// a web identity token (IRSA: `AWS_WEB_IDENTITY_TOKEN_FILE` and
+/// `AWS_ROLE_ARN`), then the instance's role from the metadata service.
+/// Temporary credentials are cached until shortly before they expire.
+///
+/// Blocks on network calls; call it off the async runtime.
+pub fn aws_credentials() -> Result<AwsCredentials> {
+    let mut cached = CREDENTIALS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(credentials) = cached.as_ref()
+        && credentials.is_fresh(SystemTime::now())
+    {
+        return Ok(credentials.clone());
+    }
+
+    let credentials = if let Some(credentials) = env_credentials() {
+        credentials
+    } else if let (Ok(token_file), Ok(role_arn)) = (
+        std::env::var("AWS_WEB_IDENTITY_TOKEN_FILE"),
+        std::env::var("AWS_ROLE_ARN"),
+    ) {
+        web_identity_credentials(&token_file, &role_arn)?
+    } else {
+        instance_credentials()?
+    };
+    *cached = Some(credentials.clone());
+    Ok(credentials)
+}
+
+fn env_credentials() -> Option<AwsCredentials> {
+    let access_key_id = std::env::var("AWS_ACCESS_KEY_ID").ok()?;
+    let secret_access_key = std::env::var("AWS_SECRET_ACCESS_KEY").ok()?;
+    debug!("Using AWS credentials from the environment");
+    Some(AwsCredentials {
+        access_key_id,
+        secret_access_key,
+        session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        expires_at: None,
+    })
+}
+
+fn http_client() -> Result<reqwest::blocking::Client> {
+    Ok(blocking_client_builder()
+        .timeout(Duration::from_secs(10))
+        .build()?)
+}
+
+/// The text between `<tag>` and `</tag>` in an STS response.
+fn xml_value(xml: &str, tag: &str) -> Option<String> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(xml[start..end].trim().to_string())
+}
+
+/// Exchange the projected service account token for credentials of
+/// `role_arn` with STS.
+fn web_identity_credentials(token_file: &str, role_arn: &str) -> Result<AwsCredentials> {
+    let token = std::fs::read_to_string(token_file)
+        .with_context(|| format!("Failed to read web identity token {}", token_file))?;
+    let endpoint = match std::env::var("AWS_REGION") {
+        Ok(region) => format!("https://sts.{}.amazonaws.com/", region),
+        Err(_) => "https://sts.amazonaws.com/".to_string(),
+    };
+    let response = http_client()?
+        .get(&endpoint)
+        .query(&[
+            ("Action", "AssumeRoleWithWebIdentity"),
+            ("Version", "2011-06-15"),
+            ("RoleArn", role_arn),
+            ("RoleSessionName", "gitops-operator"),
+            ("WebIdentityToken", token.trim()),
+        ])
+        .send()
+        .context("Failed to call STS")?;
+    let status = response.status();
+    let body = response.text().context("Failed to read the STS response")?;
+    if !status.is_success() {
+        anyhow::bail!("STS refused to assume {}: {} {}", role_arn, status, body);
+    }
+
+    let field = |tag| xml_value(&body, tag).with_context(|| format!("STS response has no {}", tag));
+    info!("Assumed {} with the web identity token", role_arn);
+    Ok(AwsCredentials {
+        access_key_id: field("AccessKeyId")?,
+        secret_access_key: field("SecretAccessKey")?,
+        session_token: Some(field("SessionToken")?),
+        expires_at: xml_value(&body, "Expiration").and_then(|value| parse_expiration(&value)),
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InstanceCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    token: String,
+    expiration: String,
+}
+
+/// The credentials of the instance's role, through IMDSv2.
+fn instance_credentials() -> Result<AwsCredentials> {
+    let client = http_client()?;
+    let token = client
+        .put(format!("{}/latest/api/token", IMDS_ENDPOINT))
+        .header("X-aws-ec2-metadata-token-ttl-seconds", "21600")
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.text())
+        .context("No AWS credentials in the environment and no instance metadata service")?;
+
+    let base = format!(
+        "{}/latest/meta-data/iam/security-credentials/",
+        IMDS_ENDPOINT
+    );
+    let get = |url: &str| {
+        client
+            .get(url)
+            .header("X-aws-ec2-metadata-token", &token)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.text())
+    };
+    let roles = get(&base).context("The instance has no IAM role")?;
+    let role = roles
+        .lines()
+        .next()
+        .context("The instance has no IAM role")?;
+    let credentials: InstanceCredentials = serde_json::from_str(
+        &get(&format!("{}{}", base, role))
+            .with_context(|| format!("Failed to get the credentials of {}", role))?,
+    )
+    .context("Failed to parse the instance credentials")?;
+
+    debug!("Using the credentials of instance role {}", role);
+    Ok(AwsCredentials {
+        access_key_id: credentials.access_key_id,
+        secret_access_key: credentials.secret_access_key,
+        session_token: Some(credentials.token),
+        expires_at: parse_expiration(&credentials.expiration),
+    })
+}
@@ -1,28 +1,110 @@
-use crate::files::{current_image_tag, needs_patching, patch_deployment};
-use crate::git::{clone_repo, commit_changes, get_latest_commit};
-use crate::github::GitHubBuildChecker;
-use crate::notifications::HttpNotificationSender;
-use crate::registry::RegistryCheckerFactory;
+use crate::approvals::{ApprovalQueue, ApprovalStatus, approval_namespaces, approval_queue};
+use crate::backups::{BackupStore, backup_store};
+use crate::capabilities::{EVENTS_RESOURCE, capabilities};
+use crate::changes::{ChangeCache, change_cache, change_ttl, fingerprint};
+use crate::coalesce::OncePerRun;
+use crate::codecommit::is_codecommit_url;
+use crate::dependencies::{DependencyRule, apply_rule, dependency_rules_from_env};
+use crate::drift::{
+    DigestDrift, DriftBoard, DriftUpdate, RunningImage, detect, detect_digests, drift_board,
+    drift_notifications, verify_digests,
+};
+use crate::egress::{EgressDenied, EgressKind, EgressPolicy, egress_policy};
+use crate::files::{
+    ContainerTarget, ImageHost, current_tag, find_manifests, image_matches, images_need_patching,
+    patch_images,
+};
+use crate::git::{
+    CloneStrategy, CommitAuthor, DEFAULT_COMMIT_MESSAGE, DEFAULT_HTTPS_USERNAME,
+    DEPENDENCY_COMMIT_MESSAGE, GitBackend, GitCredentials, HttpsToken, ImageRevision,
+    LibGit2Provider, ROLLBACK_COMMIT_MESSAGE, get_commit_author, get_source_commit, hashed_name,
+    head_commit, image_tag_history, is_ambiguous, newest_tag, push_to_branch, repo_cache_dir,
+    sha_length, sync_repo, take_git_retries, touches_paths,
+};
+use crate::github::{GitHubBuildChecker, parse_github_repo};
+use crate::github_app::{GitHubAppTokens, github_app_tokens};
+use crate::history::{enum_name, now_rfc3339};
+use crate::identity::operator_identity;
+use crate::known_hosts::{KnownHosts, known_hosts_file, strict_host_key_checking};
+use crate::locks::{entry_locks, path_locks};
+use crate::maintenance_windows::{
+    KubeMaintenanceWindows, MaintenanceWindow, active_freeze, blocking_window, parse_freeze_windows,
+};
+use crate::metrics::{ENTRIES_SUSPENDED_TOTAL, ROLLOUTS_TOTAL};
+use crate::namespaces::namespace_policy;
+use crate::notifications::{
+    FallbackNotificationSender, HttpNotificationSender, Notification, NotificationEndpoint,
+    NotificationProvider, NotificationStatus, fallback_webhook_url,
+};
+use crate::pauses::{PAUSED_ANNOTATION, Pause, Pauses, pauses};
+use crate::policy::{KubePolicies, Policy, PolicyInput};
+use crate::pull_requests::{
+    ChangeRequest, DEFAULT_PR_BODY, DEFAULT_PR_TITLE, Forge, Forges, PullRequest, Strategy,
+    pull_request_branch, split_repository_url,
+};
+use crate::registries::{
+    RegistryAuth, RegistryMap, ResolvedRegistry, registry_map, registry_mirrors,
+};
+use crate::registry::{
+    FallbackImageChecker, RateLimited, RegistryCheckerFactory, ecr_auth, registry_concurrency,
+    revision_lookup_limit,
+};
+use crate::rollouts::{
+    DEFAULT_ROLLOUT_TIMEOUT, ROLLOUT_POLL_INTERVAL, Rollout, RolloutStatus, Rollouts,
+    rollout_progress, rollout_timeout, rollouts, started,
+};
 use crate::secrets::K8sSecretProvider;
+use crate::state::blocking;
+use crate::suspension::{
+    DEFAULT_FAILURE_THRESHOLD, FailureCounter, SUSPENDED_AT_ANNOTATION,
+    SUSPENDED_REASON_ANNOTATION, failure_counter, failure_threshold,
+};
+use crate::telemetry::{RECONCILE_ACTION_ATTRIBUTE, RECONCILE_STATUS_ATTRIBUTE};
+use crate::templates::render;
+use crate::timeline::{Timeline, TimelineEvent, TimelineKind, timeline};
 use crate::traits::{
-    BuildStatus, BuildStatusChecker, ImageChecker, ImageCheckerFactory, NotificationSender,
-    SecretProvider,
+    BuildStatus, BuildStatusChecker, ClusterReporter, EventSeverity, GitProvider, ImageChecker,
+    ImageCheckerFactory, MaintenanceWindowSource, NotificationSender, PolicySource,
+    PullRequestOpener, SecretProvider,
 };
+use crate::validation::{validate_file, validate_manifests_default};
+use crate::versions::{
+    GitTagPattern, PatternPolicy, REVISION_LABEL, SemverPolicy, TagPolicy, TagSort, TagSource,
+    natural_cmp, same_revision,
+};
+use anyhow::Context;
+use async_trait::async_trait;
 use axum::Json;
 use axum::extract::State as AxumState;
-use futures::future;
+use futures::{StreamExt, future, stream};
 use k8s_openapi::api::apps::v1::Deployment;
-use k8s_openapi::api::core::v1::Container;
-use kube::ResourceExt;
+use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::jiff::Timestamp;
+use kube::api::{ListParams, Patch, PatchParams};
+use kube::runtime::events::{Event, EventType, Recorder};
 use kube::runtime::reflector;
+use kube::{Api, Client, Resource, ResourceExt};
 use std::collections::BTreeMap;
 use std::fs::remove_dir_all;
 use std::path::Path;
 use std::sync::Arc;
-use tracing::{error, info, warn};
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
 
 type Cache = reflector::Store<Deployment>;
 
+/// Annotation written back to the Deployment with the image tag its manifest
+/// was last synced to.
+pub const LAST_SYNCED_SHA_ANNOTATION: &str = "gitops.operator.last-synced-sha";
+
+/// Annotation written back to the Deployment with when it was last synced
+/// (RFC 3339, UTC).
+pub const LAST_SYNCED_AT_ANNOTATION: &str = "gitops.operator.last-synced-at";
+
+/// Key of the image tag in Helm values files when `gitops.operator.values_path`
+/// is not set.
+const DEFAULT_VALUES_PATH: &str = "image.tag";
+
 /// What the operator did (or could not do) for a deployment in a reconcile pass.
 #[derive(serde::Serialize, Clone, Debug, PartialEq)]
 #[serde(rename_all = "snake_case")]
@@ -35,6 +117,14 @@ pub enum Action {
     Skipped,
     /// Reconciliation failed before completing.
     Failed,
+    /// The manifest was reverted to an earlier image tag on request.
+    RolledBack,
+    /// A newer image is ready but a cluster maintenance window or a policy
+    /// holds it back for now.
+    Deferred,
+    /// The manifest update was pushed to a branch of its own and proposed as
+    /// a pull request.
+    Proposed,
 }
 
 /// Overall outcome of reconciling a single deployment.
@@ -46,6 +136,81 @@ pub enum Status {
     Skipped,
 }
 
+/// Why a deployment was not brought up to date, in terms callers can branch
+/// on. `--once` turns it into the process exit code.
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// A repository or registry refused the operator's credentials, or the
+    /// SSH key could not be read.
+    Auth,
+    /// A remote could not be reached.
+    Network,
+    /// The remote moved on under the operator (a rejected push) or another
+    /// pass is working on the deployment.
+    Conflict,
+    /// The promotion was held back on purpose: a suspension, a maintenance
+    /// window or a policy.
+    Policy,
+    /// Anything else: missing images, manifests that cannot be patched, bad
+    /// configuration.
+    Other,
+}
+
+/// Exit code of a `--once` run in which some deployments failed and others
+/// were reconciled.
+pub const EXIT_PARTIAL_SUCCESS: i32 = 20;
+
+impl ErrorKind {
+    /// Classify a libgit2 error from fetching or pushing.
+    pub fn from_git(e: &git2::Error) -> Self {
+        use git2::{ErrorClass, ErrorCode};
+
+        match (e.code(), e.class()) {
+            (ErrorCode::Auth | ErrorCode::Certificate, _) => ErrorKind::Auth,
+            (
+                ErrorCode::NotFastForward
+                | ErrorCode::MergeConflict
+                | ErrorCode::Locked
+                | ErrorCode::Modified,
+                _,
+            ) => ErrorKind::Conflict,
+            (_, ErrorClass::Ssh) if e.message().to_lowercase().contains("authenticat") => {
+                ErrorKind::Auth
+            }
+            (_, ErrorClass::Net | ErrorClass::Http | ErrorClass::Ssh | ErrorClass::Os) => {
+                ErrorKind::Network
+            }
+            _ => ErrorKind::Other,
+        }
+    }
+
+    /// The documented exit code for a `--once` run that failed this way.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorKind::Auth => 10,
+            ErrorKind::Network => 11,
+            ErrorKind::Conflict => 12,
+            ErrorKind::Policy => 13,
+            ErrorKind::Other => 1,
+        }
+    }
+}
+
+/// Exit code for a `--once` run: 0 when nothing went wrong, 20 when some
+/// deployments were reconciled and others were not, otherwise the code of
+/// the most specific error (auth before network before conflict before
+/// policy).
+pub fn exit_code(results: &[ReconcileResult]) -> i32 {
+    let Some(error) = results.iter().filter_map(|r| r.error).min() else {
+        return 0;
+    };
+    if results.iter().any(|r| r.status == Status::Success) {
+        return EXIT_PARTIAL_SUCCESS;
+    }
+    error.exit_code()
+}
+
 /// Structured, per-deployment result returned by the `/reconcile` endpoint.
 /// Each entry makes it clear which deployment it refers to, what happened,
 /// and the SHA transition, instead of a bare free-text message.
@@ -60,6 +225,20 @@ pub struct ReconcileResult {
     pub to_sha: Option<String>,
     pub status: Status,
     pub message: String,
+    /// `metadata.resourceVersion` of the Deployment object the operator
+    /// acted on, to tell a stale cached object from the current one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_version: Option<String>,
+    /// `metadata.generation` of the Deployment object the operator acted on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation: Option<i64>,
+    /// Category of the failure (or of the policy holding the deployment
+    /// back); absent when the deployment went through or was disabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorKind>,
+    /// How many times cloning, fetching or pushing this deployment's
+    /// checkouts was retried after a transient failure.
+    pub git_retries: u32,
 }
 
 impl ReconcileResult {
@@ -72,11 +251,18 @@ impl ReconcileResult {
             to_sha: None,
             status,
             message,
+            resource_version: entry.resource_version.clone(),
+            generation: entry.generation,
+            error: None,
+            git_retries: 0,
         }
     }
 
-    fn failure(entry: &Entry, message: impl Into<String>) -> Self {
-        Self::for_entry(entry, Action::Failed, Status::Failure, message.into())
+    fn failure(entry: &Entry, error: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            error: Some(error),
+            ..Self::for_entry(entry, Action::Failed, Status::Failure, message.into())
+        }
     }
 
     fn skipped(entry: &Entry, message: impl Into<String>) -> Self {
@@ -105,18 +291,181 @@ pub struct Config {
     pub app_repository: String,
     pub manifest_repository: String,
     pub image_name: String,
+    /// Every image patched for this deployment: `image_name` first, then the
+    /// extra ones from `gitops.operator.image_names` (comma-separated).
+    pub image_names: Vec<String>,
+    /// Patch only the container with this name (`gitops.operator.container_name`)
+    /// instead of every container running one of `image_names`.
+    pub container_name: Option<String>,
+    /// The first of `deployment_paths`, whose history drives rollbacks and
+    /// `/releases`.
     pub deployment_path: String,
+    /// Every manifest patched for this deployment (`deployment_path` may be a
+    /// comma-separated list, e.g. one file per overlay, and each entry may be
+    /// a directory). They are committed together.
+    pub deployment_paths: Vec<String>,
+    /// For `gitops.operator.manifest_format: helm-values`, the dotted key
+    /// holding the image tag in those files (`gitops.operator.values_path`,
+    /// default `image.tag`). `None` for workload manifests.
+    pub values_path: Option<String>,
+    /// yq-style path of the image (or bare tag) to patch in any YAML document,
+    /// e.g. `.spec.template.spec.containers[0].image` in an Argo Rollout
+    /// (`gitops.operator.patch_expression`). Takes precedence over
+    /// `values_path` and the workload container lookup.
+    pub patch_expression: Option<String>,
+    /// Appended to the commit SHA to form the image tag, for pipelines that
+    /// push per-architecture tags (`<sha>-arm64`) next to the manifest list
+    /// (`<sha>`). From `gitops.operator.tag_suffix`, defaulting to the
+    /// operator-wide `GITOPS_TAG_SUFFIX` (empty: the manifest list).
+    pub tag_suffix: String,
     pub observe_branch: String,
+    /// Follow the newest app repository tag matching this glob or semver
+    /// range instead of the head of `observe_branch`, promoting the tag name
+    /// as the image tag (`gitops.operator.observe_tag_pattern`, e.g. `v*`).
+    pub observe_tag_pattern: Option<String>,
+    /// Branch of the manifest repository patched manifests are committed to
+    /// (`gitops.operator.manifest_branch`, default `observe_branch`), for
+    /// apps built from e.g. `develop` whose manifests live on `main`.
+    pub manifest_branch: String,
+    /// How much of both repositories is cloned (`gitops.operator.clone_strategy`:
+    /// `full`, `single_branch` or `shallow`), defaulting to the operator-wide
+    /// `GITOPS_CLONE_STRATEGY`, else `full`.
+    pub clone_strategy: CloneStrategy,
     pub tag_type: String,
+    /// Template of the image tag (`gitops.operator.tag_template`, e.g.
+    /// `main-{sha_short}`), replacing `tag_type`; see [`Entry::tag_for`].
+    pub tag_template: Option<String>,
+    /// How the tag of a new commit is found (`gitops.operator.tag_source`:
+    /// `sha`, the default, or `revision_label`: the registry tag whose image
+    /// carries the commit in its `revision_label` label).
+    pub tag_source: TagSource,
+    /// Label naming the commit an image was built from
+    /// (`gitops.operator.revision_label`, default [`REVISION_LABEL`]).
+    pub revision_label: String,
+    /// Follow registry tags instead of commits: promote the newest tag within
+    /// this semver range (`gitops.operator.semver`, e.g. `~1.4`).
+    pub semver: Option<String>,
+    /// Follow registry tags matching this regular expression instead of
+    /// commits (`gitops.operator.tag_pattern`); `semver` takes precedence.
+    pub tag_pattern: Option<String>,
+    /// Order of the tags matching `tag_pattern` (`gitops.operator.tag_sort`:
+    /// `numeric`, `alphabetical` or `timestamp`, default `alphabetical`).
+    pub tag_sort: TagSort,
+    /// Only promote a new commit when the changes since the last synced one
+    /// touch these paths of the app repository (`gitops.operator.watch_paths`,
+    /// comma-separated git pathspecs, e.g. `services/api,libs/common`).
+    pub watch_paths: Vec<String>,
+    /// Expression a promotion must satisfy (`gitops.operator.policy`), on top
+    /// of the policy ConfigMaps applying to the namespace.
+    pub policy: Option<String>,
+    /// Hold every promotion until it is approved through `/approvals`
+    /// (`gitops.operator.require_approval: "true"`).
+    pub require_approval: bool,
+    /// `;`-separated periods during which promotions are deferred
+    /// (`gitops.operator.freeze_windows`), parsed when a promotion is due.
+    pub freeze_windows: Option<String>,
+    /// Skip every reconcile while set (`gitops.operator.paused: "true"`);
+    /// unlike `enabled: "false"`, the deployment is still reported as paused.
+    pub paused: bool,
+    /// Validate patched manifests and refuse to push invalid ones
+    /// (`gitops.operator.validate_manifests`, defaulting to the operator-wide
+    /// `GITOPS_VALIDATE_MANIFESTS`).
+    pub validate_manifests: bool,
+    /// After pushing a new image, watch the Deployment until it is rolled out
+    /// or `GITOPS_ROLLOUT_TIMEOUT_SECS` elapses, and notify the outcome
+    /// (`gitops.operator.verify_rollout: "true"`).
+    pub verify_rollout: bool,
+    /// When a verified rollout fails, commit the manifest back to the tag it
+    /// declared before and pause the deployment
+    /// (`gitops.operator.auto_revert: "true"`, with `verify_rollout`).
+    pub auto_revert: bool,
+    /// Push patched manifests straight to `manifest_branch`, or to a branch of
+    /// their own proposed as a pull request (`gitops.operator.strategy`:
+    /// `push` or `pull_request`, default `push`).
+    pub strategy: Strategy,
+    /// Force push to `manifest_branch`, replacing whatever it holds, so
+    /// operator-owned branches that get rebased (e.g. preview environments)
+    /// keep reconciling (`gitops.operator.force_push: "true"`). Never applies
+    /// to `main`, `master` or the repository's default branch.
+    pub force_push: bool,
+    /// Template of the message manifest updates are committed with
+    /// (`gitops.operator.commit_message`, defaulting to the operator-wide
+    /// `GITOPS_COMMIT_MESSAGE`, else [`DEFAULT_COMMIT_MESSAGE`]).
+    pub commit_message: Option<String>,
+    /// Title template of the pull requests opened (`gitops.operator.pr_title`,
+    /// default [`DEFAULT_PR_TITLE`]).
+    pub pr_title: Option<String>,
+    /// Body template of the pull requests opened (`gitops.operator.pr_body`,
+    /// default [`DEFAULT_PR_BODY`]).
+    pub pr_body: Option<String>,
+    /// Labels put on those pull requests (`gitops.operator.pr_labels`,
+    /// comma-separated).
+    pub pr_labels: Vec<String>,
+    /// Usernames those pull requests are assigned to
+    /// (`gitops.operator.pr_assignees`, comma-separated).
+    pub pr_assignees: Vec<String>,
+    /// Where `manifest_repository` is hosted (`gitops.operator.forge`:
+    /// `github`, `gitlab`, `gitea`, `forgejo`, `bitbucket` or
+    /// `bitbucket_server`, detected from the repository URL when unset).
+    pub forge: Forge,
+    /// Secret holding the API token (key: `token`) of forges other than
+    /// GitHub, or of GitHub instead of `github_token_secret_name`.
+    pub forge_token_secret_name: Option<String>,
+    pub forge_token_secret_namespace: Option<String>,
+    /// Empty when neither repository is reached over SSH.
     pub ssh_key_name: String,
     pub ssh_key_namespace: String,
+    /// SSH key the manifest repository is cloned and pushed with instead of
+    /// `ssh_key_name`, which then only needs to read the app repository.
+    pub manifest_ssh_key_name: Option<String>,
+    pub manifest_ssh_key_namespace: Option<String>,
+    /// Secret holding the known_hosts (key: `known_hosts`) SSH host keys
+    /// are checked against, instead of `GITOPS_KNOWN_HOSTS_FILE`.
+    pub known_hosts_secret_name: Option<String>,
+    pub known_hosts_secret_namespace: Option<String>,
+    /// Secret holding the token (key: `token`, or `password`) and optional
+    /// `username` git uses for `https://` repositories.
+    pub https_token_secret_name: Option<String>,
+    pub https_token_secret_namespace: Option<String>,
     pub notifications_secret_name: Option<String>,
     pub notifications_secret_namespace: Option<String>,
+    /// Payload format of the notification webhook
+    /// (`gitops.operator.notifications_provider`: `slack`, `discord`, `teams`
+    /// or `webhook`); detected from its URL when unset.
+    pub notifications_provider: Option<NotificationProvider>,
     pub registry_url: Option<String>,
+    /// Registries images are also pushed to, tried in order when the primary
+    /// fails or doesn't have an image (`gitops.operator.fallback_registry_urls`).
+    pub fallback_registry_urls: Vec<String>,
     pub registry_secret_name: Option<String>,
     pub registry_secret_namespace: Option<String>,
     pub github_token_secret_name: Option<String>,
     pub github_token_secret_namespace: Option<String>,
+    /// Secret holding a GitHub App (keys: `app-id`, `private-key`, optional
+    /// `installation-id`) whose installation tokens are used for git over
+    /// HTTPS, pull requests and build checks instead of long-lived tokens.
+    pub github_app_secret_name: Option<String>,
+    pub github_app_secret_namespace: Option<String>,
+    /// Free-form values from `gitops.operator.vars` (a JSON object), exposed to
+    /// commit and notification templates alongside the built-in variables.
+    pub vars: BTreeMap<String, String>,
+    pub notification_template: Option<String>,
+    /// Email domains (from `gitops.operator.author_domains`) whose app commit
+    /// authors are credited as the author of the manifest commit. Empty keeps
+    /// the operator as the author.
+    pub author_domains: Vec<String>,
+    pub image_host: ImageHost,
+}
+
+/// A container of the deployment's pod template, as seen in the cluster.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct ContainerImage {
+    pub name: String,
+    /// Image reference without the tag.
+    pub image: String,
+    pub tag: String,
+    /// Whether the operator patches this container.
+    pub tracked: bool,
 }
 
 #[derive(serde::Serialize, Clone, Debug, PartialEq)]
@@ -124,9 +473,73 @@ pub struct Entry {
     pub container: String,
     pub name: String,
     pub namespace: String,
+    /// `metadata.resourceVersion` of the Deployment this entry was read from.
+    pub resource_version: Option<String>,
+    /// `metadata.generation` of the Deployment this entry was read from.
+    pub generation: Option<i64>,
     pub annotations: BTreeMap<String, String>,
     pub version: String,
+    pub containers: Vec<ContainerImage>,
     pub config: Config,
+    /// Set while the deployment is paused, through the API or its annotation.
+    pub paused: Option<Pause>,
+}
+
+/// `path` relative to the checkout at `root`, as recorded in git.
+fn repo_relative(root: &str, path: &str) -> String {
+    path.strip_prefix(root.trim_end_matches('/'))
+        .unwrap_or(path)
+        .trim_start_matches('/')
+        .to_string()
+}
+
+/// The validation errors of the manifests in `paths`, each prefixed with its
+/// path in the repository at `root`.
+fn manifest_errors(root: &str, paths: &[String]) -> Vec<String> {
+    paths
+        .iter()
+        .flat_map(|path| {
+            let relative = repo_relative(root, path);
+            validate_file(path)
+                .unwrap_or_else(|e| vec![format!("{:#}", e)])
+                .into_iter()
+                .map(move |error| format!("{}: {}", relative, error))
+        })
+        .collect()
+}
+
+/// `message` wrapped in the entry's notification template, if any.
+fn notification_text(entry: &Entry, message: &str) -> String {
+    match &entry.config.notification_template {
+        Some(template) => {
+            let mut vars = entry.template_vars();
+            vars.insert("message".to_string(), message.to_string());
+            render(template, &vars)
+        }
+        None => message.to_string(),
+    }
+}
+
+/// Web page of the app commit `sha`, linked from notifications.
+fn app_commit_url(entry: &Entry, sha: Option<&str>) -> Option<String> {
+    let repository = &entry.config.app_repository;
+    Forge::detect(repository).commit_url(repository, sha?)
+}
+
+/// Patch every manifest in `paths` that is not yet at `sha`. The first error
+/// aborts; the caller discards the checkout so nothing partial is committed.
+fn patch_manifests(
+    paths: &[String],
+    target: &ContainerTarget,
+    sha: &str,
+    host: ImageHost,
+) -> anyhow::Result<()> {
+    for path in paths {
+        if images_need_patching(path, target, sha)? {
+            patch_images(path, target, sha, host)?;
+        }
+    }
+    Ok(())
 }
 
 /// Build the full container image reference from the registry URL and image name.
@@ -161,11 +574,316 @@ pub fn build_container_image(registry_url: &str, image_name: &str) -> String {
     format!("{}/{}", host, image_name)
 }
 
+/// Reduce an image reference to its repository path so references can be
+/// compared regardless of how they were written: the registry host, the tag or
+/// digest, and Docker Hub's implicit `library/` namespace are all dropped
+/// (e.g. `ghcr.io/kainlite/tr:abc` and `kainlite/tr` both become `kainlite/tr`).
+pub fn image_repository(image: &str) -> String {
+    let without_digest = image.split('@').next().unwrap_or(image);
+
+    // A ':' after the last '/' is a tag separator; one before it is a port.
+    let without_tag = match without_digest.rsplit_once(':') {
+        Some((repo, tag)) if !tag.contains('/') => repo,
+        _ => without_digest,
+    };
+
+    // The first component is a registry host when it looks like one.
+    let path = match without_tag.split_once('/') {
+        Some((first, rest))
+            if first.contains('.') || first.contains(':') || first == "localhost" =>
+        {
+            rest
+        }
+        _ => without_tag,
+    };
+
+    path.strip_prefix("library/").unwrap_or(path).to_string()
+}
+
+/// Reports back to the target Deployment through the Kubernetes API: Events
+/// (so `kubectl describe deployment` shows what the operator did to it) and
+/// last-synced annotations.
+#[derive(Clone)]
+pub struct KubeClusterReporter;
+
+impl KubeClusterReporter {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for KubeClusterReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ClusterReporter for KubeClusterReporter {
+    async fn publish_event(
+        &self,
+        namespace: &str,
+        name: &str,
+        severity: EventSeverity,
+        reason: &str,
+        note: &str,
+    ) -> anyhow::Result<()> {
+        if !capabilities().has(EVENTS_RESOURCE) {
+            debug!("The Events API is not served, not recording {}", reason);
+            return Ok(());
+        }
+        let client = Client::try_default().await?;
+
+        // Fetch the live object so the event carries its UID, which is what
+        // `kubectl describe` uses to associate events with it.
+        let deployments: Api<Deployment> = Api::namespaced(client.clone(), namespace);
+        let reference = deployments.get(name).await?.object_ref(&());
+
+        let recorder = Recorder::new(client, "gitops-operator".into());
+        recorder
+            .publish(
+                &Event {
+                    type_: match severity {
+                        EventSeverity::Normal => EventType::Normal,
+                        EventSeverity::Warning => EventType::Warning,
+                    },
+                    reason: reason.to_string(),
+                    note: Some(note.chars().take(1024).collect()),
+                    action: "Reconcile".to_string(),
+                    secondary: None,
+                },
+                &reference,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn annotate(
+        &self,
+        namespace: &str,
+        name: &str,
+        annotations: &BTreeMap<String, String>,
+    ) -> anyhow::Result<()> {
+        let client = Client::try_default().await?;
+        let deployments: Api<Deployment> = Api::namespaced(client, namespace);
+
+        // Only metadata annotations change, so this never triggers a rollout.
+        let patch = serde_json::json!({ "metadata": { "annotations": annotations } });
+        deployments
+            .patch(name, &PatchParams::default(), &Patch::Merge(&patch))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_annotations(
+        &self,
+        namespace: &str,
+        name: &str,
+        keys: &[String],
+    ) -> anyhow::Result<()> {
+        let client = Client::try_default().await?;
+        let deployments: Api<Deployment> = Api::namespaced(client, namespace);
+
+        // A null value deletes the key in a JSON merge patch.
+        let annotations: serde_json::Map<String, serde_json::Value> = keys
+            .iter()
+            .map(|key| (key.clone(), serde_json::Value::Null))
+            .collect();
+        let patch = serde_json::json!({ "metadata": { "annotations": annotations } });
+        deployments
+            .patch(name, &PatchParams::default(), &Patch::Merge(&patch))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn live_deployment(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> anyhow::Result<Option<Deployment>> {
+        let client = Client::try_default().await?;
+        let deployments: Api<Deployment> = Api::namespaced(client, namespace);
+
+        Ok(deployments.get_opt(name).await?)
+    }
+
+    async fn running_images(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> anyhow::Result<Vec<RunningImage>> {
+        let client = Client::try_default().await?;
+        let deployments: Api<Deployment> = Api::namespaced(client.clone(), namespace);
+        let Some(deployment) = deployments.get_opt(name).await? else {
+            return Ok(vec![]);
+        };
+        let selector = deployment
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.selector.match_labels.as_ref())
+            .map(|labels| {
+                labels
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .unwrap_or_default();
+        if selector.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let pods: Api<Pod> = Api::namespaced(client, namespace);
+        let pods = pods.list(&ListParams::default().labels(&selector)).await?;
+        Ok(pods
+            .items
+            .iter()
+            .flat_map(|pod| {
+                let statuses = pod
+                    .status
+                    .as_ref()
+                    .and_then(|status| status.container_statuses.clone())
+                    .unwrap_or_default();
+                statuses.into_iter().map(|status| RunningImage {
+                    pod: pod.name_any(),
+                    container: status.name,
+                    image: status.image,
+                    image_id: status.image_id,
+                })
+            })
+            .collect())
+    }
+}
+
+/// Reporter used when no cluster is wired in (e.g. tests built with `new`).
+struct NoopClusterReporter;
+
+#[async_trait]
+impl ClusterReporter for NoopClusterReporter {
+    async fn publish_event(
+        &self,
+        _namespace: &str,
+        _name: &str,
+        _severity: EventSeverity,
+        _reason: &str,
+        _note: &str,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn annotate(
+        &self,
+        _namespace: &str,
+        _name: &str,
+        _annotations: &BTreeMap<String, String>,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn remove_annotations(
+        &self,
+        _namespace: &str,
+        _name: &str,
+        _keys: &[String],
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn live_deployment(
+        &self,
+        _namespace: &str,
+        _name: &str,
+    ) -> anyhow::Result<Option<Deployment>> {
+        Ok(None)
+    }
+}
+
+/// Source used when no cluster is wired in: no maintenance windows.
+struct NoMaintenanceWindows;
+
+#[async_trait]
+impl MaintenanceWindowSource for NoMaintenanceWindows {
+    async fn windows(&self) -> anyhow::Result<Vec<MaintenanceWindow>> {
+        Ok(vec![])
+    }
+}
+
+/// Source used when no cluster is wired in: no policies.
+struct NoPolicies;
+
+#[async_trait]
+impl PolicySource for NoPolicies {
+    async fn policies(&self) -> anyhow::Result<Vec<Policy>> {
+        Ok(vec![])
+    }
+}
+
+/// Whether each reconcile re-reads the Deployment from the API server
+/// before acting instead of trusting the reflector cache, from
+/// `GITOPS_REFETCH_LIVE_DEPLOYMENT` (default false).
+pub fn refetch_live_deployment() -> bool {
+    std::env::var("GITOPS_REFETCH_LIVE_DEPLOYMENT").is_ok_and(|value| value.trim() == "true")
+}
+
+/// The git credentials of an entry's app repository, which is only read,
+/// and of its manifest repository, which is also pushed to.
+#[derive(Clone)]
+struct RepositoryCredentials {
+    app: GitCredentials,
+    manifest: GitCredentials,
+}
+
 /// Processor for handling deployment reconciliation with injectable dependencies
+#[derive(Clone)]
 pub struct DeploymentProcessor {
     secret_provider: Arc<dyn SecretProvider>,
     image_checker_factory: Arc<dyn ImageCheckerFactory>,
     notification_sender: Arc<dyn NotificationSender>,
+    cluster_reporter: Arc<dyn ClusterReporter>,
+    dependency_rules: Arc<Vec<DependencyRule>>,
+    failures: Arc<FailureCounter>,
+    failure_threshold: u32,
+    refetch: bool,
+    maintenance_windows: Arc<dyn MaintenanceWindowSource>,
+    policies: Arc<dyn PolicySource>,
+    approvals: Arc<ApprovalQueue>,
+    approval_namespaces: Vec<String>,
+    registries: Arc<RegistryMap>,
+    egress: Arc<EgressPolicy>,
+    pauses: Arc<Pauses>,
+    timeline: Arc<Timeline>,
+    drift: Arc<DriftBoard>,
+    notify_drift: bool,
+    verify_digests: bool,
+    rollouts: Arc<Rollouts>,
+    rollout_timeout: Duration,
+    rollout_interval: Duration,
+    pull_requests: Arc<dyn PullRequestOpener>,
+    github_apps: Arc<GitHubAppTokens>,
+    known_hosts_file: Option<String>,
+    strict_host_keys: bool,
+    backups: Option<Arc<BackupStore>>,
+    changes: Arc<ChangeCache>,
+    change_ttl: Duration,
+    force: bool,
+    git: Arc<dyn GitProvider>,
+    /// The app repository heads resolved in the current run, when run by
+    /// [`Entry::reconcile_entries_with`].
+    app_heads: Option<Arc<OncePerRun<AppHead>>>,
+}
+
+/// The app repository checkout a pass read and the head of its observed
+/// branch (a full SHA, or why it couldn't be resolved). Entries of a run
+/// tracking the same repository and branch share one.
+#[derive(Clone, Debug)]
+struct AppHead {
+    path: String,
+    sha: Result<String, (ErrorKind, String)>,
+    /// The tag `sha` was resolved from, for deployments observing tags.
+    tag: Option<String>,
 }
 
 impl DeploymentProcessor {
@@ -179,219 +897,2555 @@ impl DeploymentProcessor {
             secret_provider,
             image_checker_factory,
             notification_sender,
+            cluster_reporter: Arc::new(NoopClusterReporter),
+            dependency_rules: Arc::new(vec![]),
+            failures: Arc::new(FailureCounter::new()),
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            refetch: false,
+            maintenance_windows: Arc::new(NoMaintenanceWindows),
+            policies: Arc::new(NoPolicies),
+            approvals: Arc::new(ApprovalQueue::new()),
+            approval_namespaces: vec![],
+            registries: Arc::new(RegistryMap::default()),
+            egress: Arc::new(EgressPolicy::allow_all()),
+            pauses: Arc::new(Pauses::new()),
+            timeline: Arc::new(Timeline::new()),
+            drift: Arc::new(DriftBoard::new(Duration::ZERO)),
+            notify_drift: false,
+            verify_digests: false,
+            rollouts: Arc::new(Rollouts::new()),
+            rollout_timeout: DEFAULT_ROLLOUT_TIMEOUT,
+            rollout_interval: ROLLOUT_POLL_INTERVAL,
+            pull_requests: Arc::new(Forges::new()),
+            github_apps: Arc::new(GitHubAppTokens::new()),
+            known_hosts_file: None,
+            strict_host_keys: false,
+            backups: None,
+            changes: Arc::new(ChangeCache::new()),
+            change_ttl: Duration::ZERO,
+            force: false,
+            git: Arc::new(LibGit2Provider),
+            app_heads: None,
         }
     }
 
-    /// Create a processor with production implementations
-    pub fn production() -> Self {
-        Self {
-            secret_provider: Arc::new(K8sSecretProvider::new()),
-            image_checker_factory: Arc::new(RegistryCheckerFactory::new()),
-            notification_sender: Arc::new(HttpNotificationSender::new()),
-        }
+    /// Read SSH keys and tokens through `secret_provider` instead of the one
+    /// the processor was built with.
+    pub fn with_secret_provider(mut self, secret_provider: Arc<dyn SecretProvider>) -> Self {
+        self.secret_provider = secret_provider;
+        self
     }
 
-    /// Send a notification, logging (but not failing on) any delivery error.
-    async fn notify(&self, endpoint: &Option<String>, message: &str) {
-        if let Some(ep) = endpoint {
-            match self.notification_sender.send(message, ep).await {
-                Ok(_) => info!("Notification sent successfully"),
-                Err(e) => warn!("Failed to send notification: {:?}", e),
-            }
-        }
+    /// Check registries through checkers from `factory` instead of the one
+    /// the processor was built with.
+    pub fn with_image_checker_factory(mut self, factory: Arc<dyn ImageCheckerFactory>) -> Self {
+        self.image_checker_factory = factory;
+        self
     }
 
-    /// Process a deployment entry
-    #[tracing::instrument(name = "deployment_processor_process", skip(self, entry), fields())]
-    pub async fn process(&self, entry: &Entry) -> ReconcileResult {
-        info!("Processing: {}/{}", &entry.namespace, &entry.name);
+    /// Send notifications through `sender` instead of the one the processor
+    /// was built with.
+    pub fn with_notification_sender(mut self, sender: Arc<dyn NotificationSender>) -> Self {
+        self.notification_sender = sender;
+        self
+    }
 
-        // Get notification endpoint
-        let endpoint = self.get_notifications_endpoint(entry).await;
+    /// Report activity to the cluster through `cluster_reporter` (Kubernetes
+    /// Events in production); processors built with `new` report nothing.
+    pub fn with_cluster_reporter(mut self, cluster_reporter: Arc<dyn ClusterReporter>) -> Self {
+        self.cluster_reporter = cluster_reporter;
+        self
+    }
 
-        // Get SSH key
-        let ssh_key_secret = match self
-            .secret_provider
-            .get_ssh_key(&entry.config.ssh_key_name, &entry.config.ssh_key_namespace)
-            .await
-        {
-            Ok(key) => key,
-            Err(e) => {
-                error!("Failed to get SSH key: {:?}", e);
-                return ReconcileResult::failure(entry, format!("Failed to get SSH key: {:#}", e));
-            }
-        };
+    /// Propagate promotions to dependent services according to `rules`;
+    /// processors built with `new` have none.
+    pub fn with_dependency_rules(mut self, rules: Vec<DependencyRule>) -> Self {
+        self.dependency_rules = Arc::new(rules);
+        self
+    }
 
-        let registry_url = entry
-            .config
-            .registry_url
-            .as_deref()
-            .unwrap_or("https://index.docker.io/v1/");
+    /// Suspend a deployment after `threshold` consecutive push failures (0
+    /// never suspends); processors built with `new` use the default of 5.
+    pub fn with_failure_threshold(mut self, threshold: u32) -> Self {
+        self.failure_threshold = threshold;
+        self
+    }
 
-        // Build full container image reference (e.g. ghcr.io/user/repo)
-        // For Docker Hub the image_name is used as-is, for other registries
-        // the registry host is prepended
-        let container_image = build_container_image(registry_url, &entry.config.image_name);
+    /// Re-read each Deployment through the cluster reporter before acting on
+    /// it; processors built with `new` trust the entry they are given.
+    pub fn with_refetch(mut self, refetch: bool) -> Self {
+        self.refetch = refetch;
+        self
+    }
 
-        // Get registry credentials
-        let registry_credentials = self
-            .secret_provider
-            .get_registry_auth(
-                entry
-                    .config
-                    .registry_secret_name
-                    .as_deref()
-                    .unwrap_or("regcred"),
-                entry
-                    .config
-                    .registry_secret_namespace
-                    .as_deref()
-                    .unwrap_or("gitops-operator"),
-                registry_url,
-            )
+    /// Defer promotions while one of the windows from `source` is active;
+    /// processors built with `new` see none.
+    pub fn with_maintenance_windows(mut self, source: Arc<dyn MaintenanceWindowSource>) -> Self {
+        self.maintenance_windows = source;
+        self
+    }
+
+    /// Hold back promotions that one of the policies from `source` (or the
+    /// deployment's own) refuses; processors built with `new` only apply the
+    /// deployment's own.
+    pub fn with_policies(mut self, source: Arc<dyn PolicySource>) -> Self {
+        self.policies = source;
+        self
+    }
+
+    /// Queue promotions needing approval in `queue`, and require approval in
+    /// `namespaces` whatever the deployments' annotations say; processors
+    /// built with `new` use a queue of their own and no such namespaces.
+    pub fn with_approvals(mut self, queue: Arc<ApprovalQueue>, namespaces: Vec<String>) -> Self {
+        self.approvals = queue;
+        self.approval_namespaces = namespaces;
+        self
+    }
+
+    /// Resolve registries and their credentials for images without registry
+    /// annotations from `registries`; processors built with `new` use the
+    /// operator defaults.
+    pub fn with_registries(mut self, registries: RegistryMap) -> Self {
+        self.registries = Arc::new(registries);
+        self
+    }
+
+    /// Refuse repositories, registries and webhooks on hosts `policy` doesn't
+    /// allow; processors built with `new` call any host.
+    pub fn with_egress(mut self, policy: EgressPolicy) -> Self {
+        self.egress = Arc::new(policy);
+        self
+    }
+
+    /// Keep the pauses set through the API in `pauses`; processors built with
+    /// `new` keep their own.
+    pub fn with_pauses(mut self, pauses: Arc<Pauses>) -> Self {
+        self.pauses = pauses;
+        self
+    }
+
+    /// Record manifest commits and notifications in `timeline`; processors
+    /// built with `new` keep their own.
+    pub fn with_timeline(mut self, timeline: Arc<Timeline>) -> Self {
+        self.timeline = timeline;
+        self
+    }
+
+    /// Track drift between the cluster and the manifests in `board`, and with
+    /// `notify` send a notification when it lasts; processors built with
+    /// `new` keep their own board, with no grace period, and don't notify.
+    pub fn with_drift(mut self, board: Arc<DriftBoard>, notify: bool) -> Self {
+        self.drift = board;
+        self.notify_drift = notify;
+        self
+    }
+
+    /// Also compare the digests the pods run with the one the registry serves
+    /// for the manifest's tag; processors built with `new` only compare tags.
+    pub fn with_digest_verification(mut self, verify: bool) -> Self {
+        self.verify_digests = verify;
+        self
+    }
+
+    /// Record verified rollouts in `rollouts`, reading the live Deployment
+    /// every `interval` for up to `timeout`; processors built with `new` keep
+    /// their own, polling every 10 seconds for up to 10 minutes.
+    pub fn with_rollouts(
+        mut self,
+        rollouts: Arc<Rollouts>,
+        timeout: Duration,
+        interval: Duration,
+    ) -> Self {
+        self.rollouts = rollouts;
+        self.rollout_timeout = timeout;
+        self.rollout_interval = interval;
+        self
+    }
+
+    /// Open the pull requests of entries with `strategy: pull_request`
+    /// through `opener`; processors built with `new` open them on the forge
+    /// hosting the manifest repository.
+    pub fn with_pull_request_opener(mut self, opener: Arc<dyn PullRequestOpener>) -> Self {
+        self.pull_requests = opener;
+        self
+    }
+
+    /// Mint GitHub App installation tokens with `tokens`; processors built
+    /// with `new` keep a cache of their own, `production` shares one.
+    pub fn with_github_app_tokens(mut self, tokens: Arc<GitHubAppTokens>) -> Self {
+        self.github_apps = tokens;
+        self
+    }
+
+    /// Check SSH host keys against the known_hosts file at `path` when
+    /// entries don't name a secret, refusing unlisted hosts when `strict`;
+    /// processors built with `new` check none.
+    pub fn with_known_hosts(mut self, path: Option<String>, strict: bool) -> Self {
+        self.known_hosts_file = path;
+        self.strict_host_keys = strict;
+        self
+    }
+
+    /// Keep the original of every manifest before patching it in `store`;
+    /// processors built with `new` keep none.
+    pub fn with_backups(mut self, store: Arc<BackupStore>) -> Self {
+        self.backups = Some(store);
+        self
+    }
+
+    /// Skip the rest of a pass when the deployment's configuration and the
+    /// tag to promote are the same as in a successful pass less than `ttl`
+    /// ago (zero disables this); processors built with `new` never skip.
+    pub fn with_change_ttl(mut self, ttl: Duration) -> Self {
+        self.change_ttl = ttl;
+        self
+    }
+
+    /// Do the full work even for deployments that haven't changed.
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Clone, fetch, commit and push with `git` instead of libgit2.
+    pub fn with_git_provider(mut self, git: Arc<dyn GitProvider>) -> Self {
+        self.git = git;
+        self
+    }
+
+    /// Fetch each app repository and branch once for all the entries this
+    /// processor handles, sharing the checkout and head SHA of the first
+    /// among them. For the processors of a single run: later runs must fetch
+    /// again.
+    pub fn with_shared_fetches(mut self) -> Self {
+        self.app_heads = Some(Arc::new(OncePerRun::new()));
+        self
+    }
+
+    /// With `validate_manifests`, refuse to push patched manifests that don't
+    /// validate: the checkout is discarded and the failure lists every error.
+    async fn reject_invalid_manifests(
+        &self,
+        entry: &Entry,
+        endpoint: &Option<NotificationEndpoint>,
+        manifest_repo_path: &str,
+        paths: &[String],
+        sha: &str,
+    ) -> Option<ReconcileResult> {
+        if !entry.config.validate_manifests {
+            return None;
+        }
+        let errors = manifest_errors(manifest_repo_path, paths);
+        if errors.is_empty() {
+            return None;
+        }
+
+        let _ = remove_dir_all(manifest_repo_path);
+        let message = format!(
+            "Refusing to push invalid manifests for {} (version {}): {}",
+            &entry.name,
+            sha,
+            errors.join("; ")
+        );
+        self.record_event(entry, EventSeverity::Warning, "InvalidManifest", &message)
             .await;
+        self.notify(entry, endpoint, Notification::failure(&message))
+            .await;
+        error!("{}", message);
+        Some(ReconcileResult::failure(entry, ErrorKind::Other, message))
+    }
 
-        info!("Creating registry checker for: {}", registry_url);
-        let image_checker: Option<Box<dyn ImageChecker>> = match registry_credentials {
-            Ok(credentials) => {
-                match self
-                    .image_checker_factory
-                    .create(registry_url, Some(credentials))
-                    .await
-                {
-                    Ok(checker) => Some(checker),
-                    Err(e) => {
-                        error!("Failed to create image checker: {:?}", e);
-                        None
-                    }
-                }
+    /// Back up the manifests of `entry` about to be patched, logging (but not
+    /// failing on) any error.
+    fn backup_manifests(&self, entry: &Entry, manifest_repo_path: &str, paths: &[String]) {
+        let Some(backups) = &self.backups else {
+            return;
+        };
+        for path in paths {
+            let relative = repo_relative(manifest_repo_path, path);
+            let backed_up = std::fs::read(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|content| backups.backup(&entry.key(), &relative, &content));
+            match backed_up {
+                Ok(record) => debug!("Backed up {} as {}", relative, record.hash),
+                Err(e) => warn!("Failed to back up {}: {:#}", relative, e),
             }
+        }
+    }
+
+    /// The maintenance window currently deferring promotions of `entry`, if
+    /// any. Windows that can't be listed are logged and don't block.
+    async fn blocking_window(&self, entry: &Entry) -> Option<MaintenanceWindow> {
+        match self.maintenance_windows.windows().await {
+            Ok(windows) => blocking_window(&windows, &entry.namespace, Timestamp::now()).cloned(),
             Err(e) => {
-                error!("Failed to get registry credentials: {:?}", e);
+                warn!("Failed to list maintenance windows: {:#}", e);
                 None
             }
-        };
+        }
+    }
 
-        // Start process
-        info!("Performing reconciliation for: {}", &entry.name);
-        let app_repo_path = format!("/tmp/app-{}-{}/", &entry.name, &entry.config.observe_branch);
-        let manifest_repo_path = format!(
-            "/tmp/manifest-{}-{}/",
-            &entry.name, &entry.config.observe_branch
-        );
+    /// The first policy refusing the promotion described by `input`: the
+    /// deployment's own, then those declared in the cluster. A policy that
+    /// can't be listed, parsed or evaluated refuses too, with the error.
+    async fn refusing_policy(
+        &self,
+        entry: &Entry,
+        input: &PolicyInput,
+    ) -> Option<(Policy, Option<anyhow::Error>)> {
+        let mut policies: Vec<Policy> = entry
+            .config
+            .policy
+            .iter()
+            .map(|expression| Policy {
+                object: "annotation gitops.operator.policy".to_string(),
+                expression: expression.clone(),
+                namespaces: vec![],
+            })
+            .collect();
+        match self.policies.policies().await {
+            Ok(declared) => policies.extend(
+                declared
+                    .into_iter()
+                    .filter(|policy| policy.applies_to(&entry.namespace)),
+            ),
+            Err(e) => {
+                let unknown = Policy {
+                    object: "cluster policies".to_string(),
+                    expression: String::new(),
+                    namespaces: vec![],
+                };
+                return Some((unknown, Some(e.context("Failed to list policies"))));
+            }
+        }
 
-        // Create concurrent clone operations
-        info!("Cloning repositories for: {}", &entry.name);
-        let app_clone = {
-            let repo = entry.config.app_repository.clone();
-            let path = app_repo_path.clone();
-            let branch = entry.config.observe_branch.clone();
-            let ssh_key_secret = ssh_key_secret.clone();
-            tokio::task::spawn_blocking(move || clone_repo(&repo, &path, &branch, &ssh_key_secret))
-        };
+        policies
+            .into_iter()
+            .find_map(|policy| match policy.allows(input) {
+                Ok(true) => None,
+                Ok(false) => Some((policy, None)),
+                Err(e) => Some((policy, Some(e))),
+            })
+    }
+
+    /// Refuse to touch the entry while it is suspended or paused: the result
+    /// reporting why, for passes and rollbacks alike.
+    fn check_hold(&self, entry: &Entry) -> Option<ReconcileResult> {
+        if let Some(since) = entry.suspended_at() {
+            let message = format!(
+                "Deployment {} is suspended since {} after repeated push failures (POST /resume/{}/{} to resume)",
+                &entry.name, since, &entry.namespace, &entry.name
+            );
+            warn!("{}", message);
+            return Some(ReconcileResult {
+                error: Some(ErrorKind::Policy),
+                ..ReconcileResult::skipped(entry, message)
+            });
+        }
+
+        let pause = self
+            .pauses
+            .get(&entry.key())
+            .or_else(|| entry.config.paused.then(Pause::annotation));
+        if let Some(pause) = pause {
+            let message = match pause.since {
+                Some(since) => format!(
+                    "Deployment {} is paused since {} (POST /resume/{}/{} to resume)",
+                    &entry.name, since, &entry.namespace, &entry.name
+                ),
+                None => format!(
+                    "Deployment {} is paused ({}: \"true\")",
+                    &entry.name, PAUSED_ANNOTATION
+                ),
+            };
+            info!("{}", message);
+            return Some(ReconcileResult::skipped(entry, message));
+        }
+
+        None
+    }
+
+    /// The gates every change of the entry's manifest from `old_tag` to `tag`
+    /// goes through, whether a pass promotes it or a rollback restores it:
+    /// maintenance windows, freeze windows and policies. `Err` holds the
+    /// result reporting what held it back.
+    async fn check_gates(
+        &self,
+        entry: &Entry,
+        old_tag: Option<String>,
+        tag: &str,
+    ) -> Result<(), ReconcileResult> {
+        if let Some(window) = self.blocking_window(entry).await {
+            let message = format!(
+                "Promotion of {} to {} deferred by maintenance window {}",
+                &entry.name,
+                tag,
+                window.describe()
+            );
+            info!("{}", message);
+            return Err(ReconcileResult {
+                to_sha: Some(tag.to_string()),
+                error: Some(ErrorKind::Policy),
+                ..ReconcileResult::for_entry(entry, Action::Deferred, Status::Skipped, message)
+            });
+        }
+
+        if let Some(raw) = &entry.config.freeze_windows {
+            let windows = match parse_freeze_windows(raw) {
+                Ok(windows) => windows,
+                Err(e) => {
+                    let message = format!(
+                        "Promotion of {} to {} blocked by gitops.operator.freeze_windows: {:#}",
+                        &entry.name, tag, e
+                    );
+                    error!("{}", message);
+                    return Err(ReconcileResult {
+                        to_sha: Some(tag.to_string()),
+                        ..ReconcileResult::failure(entry, ErrorKind::Other, message)
+                    });
+                }
+            };
+            if let Some((window, end)) = active_freeze(&windows, Timestamp::now()) {
+                let message = format!(
+                    "Promotion of {} to {} deferred by freeze window {} (until {})",
+                    &entry.name,
+                    tag,
+                    window.describe(),
+                    end
+                );
+                info!("{}", message);
+                return Err(ReconcileResult {
+                    to_sha: Some(tag.to_string()),
+                    error: Some(ErrorKind::Policy),
+                    ..ReconcileResult::for_entry(entry, Action::Deferred, Status::Skipped, message)
+                });
+            }
+        }
+
+        let input = PolicyInput {
+            namespace: entry.namespace.clone(),
+            name: entry.name.clone(),
+            branch: entry.config.observe_branch.clone(),
+            old_tag,
+            new_tag: tag.to_string(),
+            now: Timestamp::now(),
+        };
+        if let Some((policy, error)) = self.refusing_policy(entry, &input).await {
+            return Err(match error {
+                Some(e) => {
+                    let message = format!(
+                        "Promotion of {} to {} blocked by {}: {:#}",
+                        &entry.name, tag, policy.object, e
+                    );
+                    error!("{}", message);
+                    ReconcileResult {
+                        to_sha: Some(tag.to_string()),
+                        ..ReconcileResult::failure(entry, ErrorKind::Other, message)
+                    }
+                }
+                None => {
+                    let message = format!(
+                        "Promotion of {} to {} refused by {} ({})",
+                        &entry.name, tag, policy.object, policy.expression
+                    );
+                    info!("{}", message);
+                    ReconcileResult {
+                        to_sha: Some(tag.to_string()),
+                        error: Some(ErrorKind::Policy),
+                        ..ReconcileResult::for_entry(
+                            entry,
+                            Action::Deferred,
+                            Status::Skipped,
+                            message,
+                        )
+                    }
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Whether promotions of the entry wait for approval, by its own
+    /// annotation or its namespace.
+    fn requires_approval(&self, entry: &Entry) -> bool {
+        entry.config.require_approval || self.approval_namespaces.contains(&entry.namespace)
+    }
+
+    /// Queue the promotion of the entry to `new_sha` for approval, announcing
+    /// it when first queued. `None` once it is approved; otherwise the result
+    /// holding it back.
+    async fn awaiting_approval(
+        &self,
+        entry: &Entry,
+        endpoint: &Option<NotificationEndpoint>,
+        from_sha: Option<String>,
+        new_sha: &str,
+    ) -> Option<ReconcileResult> {
+        let requested = {
+            let (namespace, name) = (entry.namespace.clone(), entry.name.clone());
+            let to_sha = new_sha.to_string();
+            blocking(&self.approvals, move |approvals| {
+                approvals.request(&namespace, &name, from_sha, &to_sha)
+            })
+            .await
+        };
+        let (change, created) = match requested {
+            Ok(requested) => requested,
+            Err(e) => {
+                let message = format!(
+                    "Failed to queue promotion of {} to {} for approval: {:#}",
+                    &entry.name, new_sha, e
+                );
+                error!("{}", message);
+                return Some(ReconcileResult {
+                    to_sha: Some(new_sha.to_string()),
+                    ..ReconcileResult::failure(entry, ErrorKind::Other, message)
+                });
+            }
+        };
+
+        let message = match change.status {
+            ApprovalStatus::Approved => return None,
+            ApprovalStatus::Pending => format!(
+                "Promotion of {} to {} awaits approval of change {}",
+                &entry.name, new_sha, change.id
+            ),
+            ApprovalStatus::Rejected => format!(
+                "Promotion of {} to {} was rejected in change {}",
+                &entry.name, new_sha, change.id
+            ),
+        };
+        if created {
+            self.notify_approval(
+                entry,
+                endpoint,
+                &format!(
+                    ":raised_hand: {} (POST /approvals/{}/approve)",
+                    message, change.id
+                ),
+                &change.id,
+            )
+            .await;
+        }
+        info!("{}", message);
+        Some(ReconcileResult {
+            to_sha: Some(new_sha.to_string()),
+            error: Some(ErrorKind::Policy),
+            ..ReconcileResult::for_entry(entry, Action::Deferred, Status::Skipped, message)
+        })
+    }
+
+    /// Forget the entry's approval once its promotion is no longer pending,
+    /// logging (but not failing on) any error.
+    async fn complete_approval(&self, entry: &Entry) {
+        let (namespace, name) = (entry.namespace.clone(), entry.name.clone());
+        let completed = blocking(&self.approvals, move |approvals| {
+            approvals.complete(&namespace, &name)
+        })
+        .await;
+        if let Err(e) = completed {
+            warn!("Failed to clear approval of {}: {:?}", &entry.name, e);
+        }
+    }
+
+    /// Start counting the entry's push failures afresh.
+    async fn reset_failures(&self, entry: &Entry) {
+        let key = entry.key();
+        blocking(&self.failures, move |failures| failures.reset(&key)).await;
+    }
+
+    /// Save where the entry's rollout stands, logging (but not failing on)
+    /// any error.
+    async fn record_rollout(&self, entry: &Entry, rollout: &Rollout) {
+        let rollout = rollout.clone();
+        let recorded = blocking(&self.rollouts, move |rollouts| rollouts.record(&rollout)).await;
+        if let Err(e) = recorded {
+            warn!("Failed to record the rollout of {}: {:#}", entry.key(), e);
+        }
+    }
+
+    /// The registry serving the entry's image and the secret to log in with.
+    fn registry_for(&self, entry: &Entry) -> ResolvedRegistry {
+        ResolvedRegistry::resolve(
+            &self.registries,
+            &entry.config.image_name,
+            entry.config.registry_url.as_deref(),
+            entry.config.registry_secret_name.as_deref(),
+            entry.config.registry_secret_namespace.as_deref(),
+        )
+    }
+
+    /// Create a processor with production implementations
+    pub fn production() -> Self {
+        let notification_sender: Arc<dyn NotificationSender> =
+            Arc::new(HttpNotificationSender::new());
+        let notification_sender: Arc<dyn NotificationSender> = match fallback_webhook_url() {
+            Some(fallback) => Arc::new(FallbackNotificationSender::new(
+                notification_sender,
+                &fallback,
+            )),
+            None => notification_sender,
+        };
+        Self {
+            secret_provider: Arc::new(K8sSecretProvider::new()),
+            image_checker_factory: Arc::new(RegistryCheckerFactory::new()),
+            notification_sender,
+            cluster_reporter: Arc::new(KubeClusterReporter::new()),
+            dependency_rules: Arc::new(dependency_rules_from_env()),
+            failures: failure_counter(),
+            failure_threshold: failure_threshold(),
+            refetch: refetch_live_deployment(),
+            maintenance_windows: Arc::new(KubeMaintenanceWindows::from_env()),
+            policies: Arc::new(KubePolicies),
+            approvals: approval_queue(),
+            approval_namespaces: approval_namespaces(),
+            registries: registry_map(),
+            egress: egress_policy(),
+            pauses: pauses(),
+            timeline: timeline(),
+            drift: drift_board(),
+            notify_drift: drift_notifications(),
+            verify_digests: verify_digests(),
+            rollouts: rollouts(),
+            rollout_timeout: rollout_timeout(),
+            rollout_interval: ROLLOUT_POLL_INTERVAL,
+            pull_requests: Arc::new(Forges::new()),
+            github_apps: github_app_tokens(),
+            known_hosts_file: known_hosts_file(),
+            strict_host_keys: strict_host_key_checking(),
+            backups: backup_store(),
+            changes: change_cache(),
+            change_ttl: change_ttl(),
+            force: false,
+            git: GitBackend::from_env().provider(),
+            app_heads: None,
+        }
+    }
+
+    /// Record a Kubernetes Event on the entry's Deployment, logging (but not
+    /// failing on) any error.
+    async fn record_event(&self, entry: &Entry, severity: EventSeverity, reason: &str, note: &str) {
+        if let Err(e) = self
+            .cluster_reporter
+            .publish_event(&entry.namespace, &entry.name, severity, reason, note)
+            .await
+        {
+            warn!("Failed to record {} event: {:?}", reason, e);
+        }
+    }
+
+    /// Stamp the entry's Deployment with the SHA its manifest was just synced
+    /// to and when, logging (but not failing on) any error.
+    async fn record_sync(&self, entry: &Entry, sha: &str) {
+        let annotations = BTreeMap::from([
+            (LAST_SYNCED_SHA_ANNOTATION.to_string(), sha.to_string()),
+            (LAST_SYNCED_AT_ANNOTATION.to_string(), now_rfc3339()),
+        ]);
+        if let Err(e) = self
+            .cluster_reporter
+            .annotate(&entry.namespace, &entry.name, &annotations)
+            .await
+        {
+            warn!("Failed to record last synced SHA: {:?}", e);
+        }
+    }
+
+    /// Send a notification about the entry, logging (but not failing on) any
+    /// delivery error. Its text is wrapped in the entry's notification
+    /// template, if any.
+    async fn notify(
+        &self,
+        entry: &Entry,
+        endpoint: &Option<NotificationEndpoint>,
+        notification: Notification,
+    ) {
+        if let Some(ep) = endpoint {
+            let notification = Notification {
+                text: notification_text(entry, &notification.text),
+                app: entry.name.clone(),
+                namespace: entry.namespace.clone(),
+                ..notification
+            };
+            if self.egress.check(EgressKind::Webhook, &ep.url).is_err() {
+                self.record_notification(entry, &notification.text, false);
+                return;
+            }
+            let result = self
+                .notification_sender
+                .send_notification(&notification, ep)
+                .await;
+            match &result {
+                Ok(_) => info!("Notification sent successfully"),
+                Err(e) => warn!("Failed to send notification: {:?}", e),
+            }
+            self.record_notification(entry, &notification.text, result.is_ok());
+        }
+    }
+
+    /// `primary` followed by checkers for the entry's fallback registries,
+    /// logged in to with the registry secret where it has credentials for
+    /// them. Fallbacks whose checker can't be created are left out.
+    async fn with_fallback_registries(
+        &self,
+        entry: &Entry,
+        registry: &ResolvedRegistry,
+        primary: Box<dyn ImageChecker>,
+    ) -> Box<dyn ImageChecker> {
+        let mut checkers = vec![(registry.url.clone(), primary)];
+        for url in &entry.config.fallback_registry_urls {
+            let credentials = match &registry.secret {
+                Some((name, namespace)) => self
+                    .secret_provider
+                    .get_registry_auth(name, namespace, url)
+                    .await
+                    .inspect_err(|e| info!("No credentials for fallback registry {}: {:#}", url, e))
+                    .ok(),
+                None if registry.auth == RegistryAuth::Ecr => ecr_auth().auth_token(url).await.ok(),
+                None => None,
+            };
+            match self.image_checker_factory.create(url, credentials).await {
+                Ok(checker) => checkers.push((url.clone(), checker)),
+                Err(e) => error!("Failed to create image checker for {}: {:?}", url, e),
+            }
+        }
+        Box::new(FallbackImageChecker::new(checkers))
+    }
+
+    /// Check the entry's repositories and registries against the egress policy.
+    fn check_egress(&self, entry: &Entry) -> Result<(), EgressDenied> {
+        self.egress
+            .check(EgressKind::Git, &entry.config.app_repository)?;
+        self.egress
+            .check(EgressKind::Git, &entry.config.manifest_repository)?;
+        let registry = self.registry_for(entry);
+        let mirrors = registry_mirrors();
+        for url in std::iter::once(&registry.url).chain(&entry.config.fallback_registry_urls) {
+            let url = match mirrors.lookup(url) {
+                Some(mirror) => &mirror.url,
+                None => url,
+            };
+            self.egress.check(EgressKind::Registry, url)?;
+        }
+        Ok(())
+    }
+
+    fn record_notification(&self, entry: &Entry, message: &str, sent: bool) {
+        let status = if sent { "sent" } else { "failed" };
+        self.timeline.record(
+            &entry.key(),
+            TimelineEvent {
+                status: Some(status.to_string()),
+                ..TimelineEvent::new(TimelineKind::Notification, message)
+            },
+        );
+    }
+
+    /// The pods of the entry running `manifest_tag` from a different digest
+    /// than `checker` resolves it to.
+    async fn digest_drift(
+        &self,
+        entry: &Entry,
+        checker: &dyn ImageChecker,
+        manifest_tag: &str,
+    ) -> Vec<DigestDrift> {
+        let containers: Vec<&ContainerImage> = entry
+            .containers
+            .iter()
+            .filter(|c| c.tracked && c.tag == manifest_tag)
+            .collect();
+        if containers.is_empty() {
+            return vec![];
+        }
+        let running = match self
+            .cluster_reporter
+            .running_images(&entry.namespace, &entry.name)
+            .await
+        {
+            Ok(running) => running,
+            Err(e) => {
+                warn!("Failed to list the pods of {}: {:?}", entry.key(), e);
+                return vec![];
+            }
+        };
+
+        let mut digests: BTreeMap<&str, Option<String>> = BTreeMap::new();
+        let mut drift = vec![];
+        for container in containers {
+            let Some(image_name) = entry
+                .config
+                .image_names
+                .iter()
+                .find(|name| image_matches(&container.image, name))
+                .or(entry.config.image_names.first())
+            else {
+                continue;
+            };
+            if !digests.contains_key(image_name.as_str()) {
+                let digest = checker
+                    .image_digest(image_name, manifest_tag)
+                    .await
+                    .inspect_err(|e| {
+                        warn!(
+                            "Failed to resolve the digest of {}:{}: {:?}",
+                            image_name, manifest_tag, e
+                        )
+                    })
+                    .ok()
+                    .flatten();
+                digests.insert(image_name, digest);
+            }
+            if let Some(Some(digest)) = digests.get(image_name.as_str()) {
+                drift.extend(detect_digests(&running, container, digest));
+            }
+        }
+        drift
+    }
+
+    /// Compare the images the entry runs with `manifest_tag`, the tag its
+    /// manifest declared before this pass, and report lasting drift. With
+    /// digest verification, `checker` resolves the digest the pods should run.
+    async fn check_drift(
+        &self,
+        entry: &Entry,
+        endpoint: &Option<NotificationEndpoint>,
+        manifest_tag: &str,
+        checker: Option<&dyn ImageChecker>,
+    ) {
+        let drift = detect(&entry.containers, manifest_tag);
+        let digests = match checker {
+            Some(checker) if self.verify_digests => {
+                self.digest_drift(entry, checker, manifest_tag).await
+            }
+            _ => vec![],
+        };
+        for mismatch in &digests {
+            warn!(
+                "{} runs {}:{} from {} but the registry serves {}",
+                mismatch.pods.join(", "),
+                mismatch.image,
+                mismatch.tag,
+                mismatch.running_digest,
+                mismatch.manifest_digest
+            );
+        }
+        if !drift.is_empty() {
+            warn!(
+                "{} runs {} but its manifest declares {}",
+                entry.key(),
+                drift
+                    .iter()
+                    .map(|d| format!("{}:{}", d.image, d.live_tag))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                manifest_tag
+            );
+        }
+        let notification = match self
+            .drift
+            .update(&entry.namespace, &entry.name, drift, digests)
+        {
+            DriftUpdate::Lasting(report) => {
+                let mut found: Vec<String> = vec![];
+                if !report.containers.is_empty() {
+                    found.push(format!(
+                        "the cluster runs {} instead of {}",
+                        report
+                            .containers
+                            .iter()
+                            .map(|d| format!("{}:{}", d.image, d.live_tag))
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        manifest_tag
+                    ));
+                }
+                found.extend(report.digests.iter().map(|d| {
+                    format!(
+                        "{} run {}:{} from {} while the registry serves {}",
+                        d.pods.join(", "),
+                        d.image,
+                        d.tag,
+                        d.running_digest,
+                        d.manifest_digest
+                    )
+                }));
+                Notification::warning(format!(
+                    ":warning: {} drifted from its manifest since {}: {}",
+                    entry.key(),
+                    report.since,
+                    found.join("; ")
+                ))
+            }
+            DriftUpdate::Resolved(_) => Notification::success(format!(
+                ":white_check_mark: {} matches its manifest again",
+                entry.key()
+            )),
+            DriftUpdate::Unchanged => return,
+        };
+        if self.notify_drift {
+            self.notify(entry, endpoint, notification).await;
+        }
+    }
+
+    /// Verify the rollout of `tag`, pushed over `previous_tag`, in the
+    /// background, so the pass doesn't wait on the cluster.
+    fn spawn_rollout_check(
+        &self,
+        entry: &Entry,
+        tag: &str,
+        previous_tag: Option<&str>,
+        endpoint: &Option<NotificationEndpoint>,
+    ) {
+        let processor = self.clone();
+        let (entry, tag, endpoint) = (entry.clone(), tag.to_string(), endpoint.clone());
+        let previous_tag = previous_tag.map(String::from);
+        tokio::spawn(async move {
+            processor
+                .verify_rollout(&entry, &tag, previous_tag.as_deref(), &endpoint)
+                .await;
+        });
+    }
+
+    /// Watch the entry's live Deployment until its tracked containers run
+    /// `tag` on every replica, it fails, or the rollout timeout elapses, then
+    /// record and notify the outcome. With `auto_revert`, a failed rollout
+    /// puts `previous_tag` back.
+    pub async fn verify_rollout(
+        &self,
+        entry: &Entry,
+        tag: &str,
+        previous_tag: Option<&str>,
+        endpoint: &Option<NotificationEndpoint>,
+    ) -> Rollout {
+        let mut rollout = started(&entry.namespace, &entry.name, tag, previous_tag);
+        self.record_rollout(entry, &rollout).await;
+        let containers: Vec<String> = entry
+            .containers
+            .iter()
+            .filter(|c| c.tracked)
+            .map(|c| c.name.clone())
+            .collect();
+        let deadline = tokio::time::Instant::now() + self.rollout_timeout;
+        loop {
+            match self
+                .cluster_reporter
+                .live_deployment(&entry.namespace, &entry.name)
+                .await
+            {
+                Ok(Some(live)) => {
+                    (rollout.status, rollout.message) = rollout_progress(&live, &containers, tag);
+                }
+                Ok(None) => {
+                    rollout.status = RolloutStatus::Failed;
+                    rollout.message = "the Deployment no longer exists".to_string();
+                }
+                Err(e) => warn!(
+                    "Failed to read {} while verifying its rollout: {:?}",
+                    entry.key(),
+                    e
+                ),
+            }
+            if rollout.status.is_finished() {
+                break;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                rollout.status = RolloutStatus::TimedOut;
+                rollout.message = format!(
+                    "not rolled out after {}s: {}",
+                    self.rollout_timeout.as_secs(),
+                    rollout.message
+                );
+                break;
+            }
+            tokio::time::sleep(self.rollout_interval).await;
+        }
+        rollout.finished_at = Some(now_rfc3339());
+        self.record_rollout(entry, &rollout).await;
+        ::metrics::counter!(ROLLOUTS_TOTAL, "status" => rollout.status.as_str()).increment(1);
+
+        let (severity, reason, message) = match rollout.status {
+            RolloutStatus::Complete => (
+                EventSeverity::Normal,
+                "RolloutComplete",
+                format!(
+                    ":rocket: Deployment {} rolled out version {}: {}",
+                    &entry.name, tag, rollout.message
+                ),
+            ),
+            _ => (
+                EventSeverity::Warning,
+                "RolloutFailed",
+                format!(
+                    ":x: Rollout of deployment {} to version {} failed: {}",
+                    &entry.name, tag, rollout.message
+                ),
+            ),
+        };
+        info!("{}", message);
+        self.record_event(entry, severity, reason, &message).await;
+        let status = match rollout.status {
+            RolloutStatus::Complete => NotificationStatus::Success,
+            _ => NotificationStatus::Failure,
+        };
+        self.notify(
+            entry,
+            endpoint,
+            Notification::new(status, &message).with_tags(previous_tag, tag),
+        )
+        .await;
+
+        if rollout.status != RolloutStatus::Complete && entry.config.auto_revert {
+            rollout.reverted = self.auto_revert(entry, &rollout, endpoint).await;
+            self.record_rollout(entry, &rollout).await;
+        }
+        rollout
+    }
+
+    /// Commit the manifest of a failed `rollout` back to its previous tag and
+    /// pause the entry, so the next pass doesn't promote the same tag again.
+    /// Returns whether the manifest was reverted.
+    async fn auto_revert(
+        &self,
+        entry: &Entry,
+        rollout: &Rollout,
+        endpoint: &Option<NotificationEndpoint>,
+    ) -> bool {
+        let Some(previous_tag) = rollout.previous_tag.as_deref() else {
+            let message = format!(
+                "Not reverting deployment {}: the tag before {} is unknown",
+                &entry.name, rollout.tag
+            );
+            warn!("{}", message);
+            self.notify(entry, endpoint, Notification::warning(&message))
+                .await;
+            return false;
+        };
+
+        // Wait for a pass still holding the entry to finish.
+        let deadline = tokio::time::Instant::now() + self.rollout_timeout;
+        let _guard = loop {
+            if let Some(guard) = entry_locks().try_acquire(&entry.key()) {
+                break guard;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                let message = format!(
+                    "Not reverting deployment {}: it is still being reconciled",
+                    &entry.name
+                );
+                warn!("{}", message);
+                self.notify(entry, endpoint, Notification::warning(&message))
+                    .await;
+                return false;
+            }
+            tokio::time::sleep(self.rollout_interval).await;
+        };
+
+        let result = self.rollback_entry(entry, previous_tag, false).await;
+        if result.status != Status::Success {
+            let message = format!(
+                ":x: Failed to revert deployment {} to version {}: {}",
+                &entry.name, previous_tag, result.message
+            );
+            error!("{}", message);
+            self.notify(entry, endpoint, Notification::failure(&message))
+                .await;
+            return false;
+        }
+
+        let by = format!("auto-revert of {}", rollout.tag);
+        if let Err(e) = self.pause(entry, Some(by), false).await {
+            warn!(
+                "Failed to pause {} after reverting it: {:#}",
+                entry.key(),
+                e
+            );
+        }
+        let reverted = match result.action {
+            Action::Proposed => "Proposed reverting",
+            _ => "Reverted",
+        };
+        let message = format!(
+            ":pause_button: {} deployment {} to version {} after the rollout of {} failed; \
+             it stays paused until resumed",
+            reverted, &entry.name, previous_tag, rollout.tag
+        );
+        info!("{}", message);
+        self.notify(entry, endpoint, Notification::warning(&message))
+            .await;
+        true
+    }
+
+    /// Put the commit just pushed from `manifest_repo_path`, moving the
+    /// entry from `from_sha` to `to_sha`, on its timeline.
+    fn record_commit(
+        &self,
+        entry: &Entry,
+        manifest_repo_path: &str,
+        from_sha: Option<&str>,
+        to_sha: &str,
+    ) {
+        let summary = match head_commit(manifest_repo_path) {
+            Ok(commit) => format!("Pushed manifest commit {}", commit),
+            Err(_) => "Pushed a manifest commit".to_string(),
+        };
+        self.timeline.record(
+            &entry.key(),
+            TimelineEvent {
+                from: from_sha.map(String::from),
+                to: Some(to_sha.to_string()),
+                ..TimelineEvent::new(TimelineKind::ManifestCommit, summary)
+            },
+        );
+    }
+
+    /// Like [`Self::notify`], for a promotion waiting on change `id`: the
+    /// sender may offer buttons to decide it.
+    async fn notify_approval(
+        &self,
+        entry: &Entry,
+        endpoint: &Option<NotificationEndpoint>,
+        message: &str,
+        id: &str,
+    ) {
+        if let Some(ep) = endpoint {
+            let message = notification_text(entry, message);
+            if self.egress.check(EgressKind::Webhook, &ep.url).is_err() {
+                self.record_notification(entry, &message, false);
+                return;
+            }
+            let result = self
+                .notification_sender
+                .send_approval_request(&message, id, ep)
+                .await;
+            match &result {
+                Ok(_) => info!("Approval request sent successfully"),
+                Err(e) => warn!("Failed to send approval request: {:?}", e),
+            }
+            self.record_notification(entry, &message, result.is_ok());
+        }
+    }
+
+    /// Commit the patched manifests in the entry's checkout with
+    /// `commit_message`, push them to its pull request branch and open a
+    /// pull request for it against `manifest_branch`, with the title and body
+    /// templates rendered from `vars`. Failures are reported like those of a
+    /// direct push and returned as the pass's result.
+    async fn propose_change(
+        &self,
+        entry: &Entry,
+        endpoint: &Option<NotificationEndpoint>,
+        vars: &BTreeMap<String, String>,
+        commit_message: &str,
+        credentials: &GitCredentials,
+        author: Option<&CommitAuthor>,
+    ) -> Result<PullRequest, ReconcileResult> {
+        let new_sha = vars.get("new_sha").cloned().unwrap_or_default();
+        let token = match self.forge_token(entry).await {
+            Ok(token) => token,
+            Err(e) => {
+                let message = format!(
+                    "Cannot open a {} for {}: {:#}",
+                    entry.config.forge.request_kind(),
+                    &entry.name,
+                    e
+                );
+                error!("{}", message);
+                return Err(ReconcileResult::failure(entry, ErrorKind::Auth, message));
+            }
+        };
+
+        let head = pull_request_branch(&entry.namespace, &entry.name, &new_sha);
+        let manifest_repo_path = entry.manifest_repo_path();
+        if let Err(e) = push_to_branch(
+            &manifest_repo_path,
+            &head,
+            commit_message,
+            credentials,
+            author,
+        ) {
+            let _ = remove_dir_all(&manifest_repo_path);
+            let mut message = format!(
+                "Failed to push {} (version {}) to {}: {:#}",
+                &entry.name, &new_sha, &head, e
+            );
+            self.record_event(entry, EventSeverity::Warning, "PushFailed", &message)
+                .await;
+            self.notify(entry, endpoint, Notification::failure(&message))
+                .await;
+            error!("{}", message);
+            if let Some(note) = self.record_push_failure(entry, endpoint, &message).await {
+                message.push_str(&format!(" ({})", note));
+            }
+            return Err(ReconcileResult::failure(
+                entry,
+                ErrorKind::from_git(&e),
+                message,
+            ));
+        }
+
+        let change = ChangeRequest {
+            forge: entry.config.forge,
+            repository: entry.config.manifest_repository.clone(),
+            head,
+            base: entry.config.manifest_branch.clone(),
+            title: render(
+                entry.config.pr_title.as_deref().unwrap_or(DEFAULT_PR_TITLE),
+                vars,
+            ),
+            body: render(
+                entry.config.pr_body.as_deref().unwrap_or(DEFAULT_PR_BODY),
+                vars,
+            ),
+            labels: entry.config.pr_labels.clone(),
+            assignees: entry.config.pr_assignees.clone(),
+        };
+        match self.pull_requests.open_pull_request(&change, &token).await {
+            Ok(pull_request) => Ok(pull_request),
+            Err(e) => {
+                let message = format!(
+                    "Failed to open a {} for {} (version {}): {:#}",
+                    entry.config.forge.request_kind(),
+                    &entry.name,
+                    &new_sha,
+                    e
+                );
+                self.record_event(entry, EventSeverity::Warning, "PullRequestFailed", &message)
+                    .await;
+                self.notify(entry, endpoint, Notification::failure(&message))
+                    .await;
+                error!("{}", message);
+                Err(ReconcileResult::failure(entry, ErrorKind::Other, message))
+            }
+        }
+    }
+
+    /// What the entry's repositories are cloned and pushed with: the SSH keys
+    /// and, when `https_token_secret_name` is set, the HTTPS token. Errors
+    /// come with the reason of the Event reporting them.
+    async fn git_credentials(
+        &self,
+        entry: &Entry,
+    ) -> Result<RepositoryCredentials, (&'static str, anyhow::Error)> {
+        let config = &entry.config;
+        // Unset when no repository is reached over SSH.
+        let ssh_key = if config.ssh_key_name.is_empty() {
+            String::new()
+        } else {
+            self.secret_provider
+                .get_ssh_key(&config.ssh_key_name, &config.ssh_key_namespace)
+                .await
+                .context("Failed to get SSH key")
+                .map_err(|e| ("SshKeyUnavailable", e))?
+        };
+        let manifest_ssh_key = match config.manifest_ssh_key_name.as_deref() {
+            Some(name) => {
+                let namespace = config
+                    .manifest_ssh_key_namespace
+                    .as_deref()
+                    .unwrap_or(&config.ssh_key_namespace);
+                self.secret_provider
+                    .get_ssh_key(name, namespace)
+                    .await
+                    .context("Failed to get manifest SSH key")
+                    .map_err(|e| ("SshKeyUnavailable", e))?
+            }
+            None => ssh_key.clone(),
+        };
+        let https_token = match config.https_token_secret_name.as_deref() {
+            Some(name) => {
+                let namespace = config
+                    .https_token_secret_namespace
+                    .as_deref()
+                    .unwrap_or("gitops-operator");
+                Some(
+                    self.secret_provider
+                        .get_https_token(name, namespace)
+                        .await
+                        .context("Failed to get HTTPS token")
+                        .map_err(|e| ("HttpsTokenUnavailable", e))?,
+                )
+            }
+            None => match self.github_app_token(entry).await {
+                Some(token) => Some(HttpsToken {
+                    username: Some(DEFAULT_HTTPS_USERNAME.to_string()),
+                    token: token.map_err(|e| ("GitHubAppTokenUnavailable", e))?,
+                }),
+                None => None,
+            },
+        };
+        let known_hosts = self
+            .known_hosts(entry)
+            .await
+            .map_err(|e| ("KnownHostsUnavailable", e))?;
+        Ok(RepositoryCredentials {
+            app: GitCredentials {
+                ssh_key,
+                https_token: https_token.clone(),
+                known_hosts: known_hosts.clone(),
+                strict_host_keys: self.strict_host_keys,
+            },
+            manifest: GitCredentials {
+                ssh_key: manifest_ssh_key,
+                https_token,
+                known_hosts,
+                strict_host_keys: self.strict_host_keys,
+            },
+        })
+    }
+
+    /// The known_hosts of the entry's `known_hosts_secret_name`, else of the
+    /// operator's known_hosts file, if either is set.
+    async fn known_hosts(&self, entry: &Entry) -> anyhow::Result<Option<KnownHosts>> {
+        let config = &entry.config;
+        let content = match (&config.known_hosts_secret_name, &self.known_hosts_file) {
+            (Some(name), _) => {
+                let namespace = config
+                    .known_hosts_secret_namespace
+                    .as_deref()
+                    .unwrap_or("gitops-operator");
+                self.secret_provider
+                    .get_known_hosts(name, namespace)
+                    .await
+                    .context("Failed to get known_hosts")?
+            }
+            (None, Some(path)) => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read known_hosts from {}", path))?,
+            (None, None) => return Ok(None),
+        };
+        Ok(Some(KnownHosts::parse(&content)))
+    }
+
+    /// An installation token of the entry's GitHub App
+    /// (`github_app_secret_name`), when it has one. The app's installation
+    /// on the manifest repository's account is used.
+    async fn github_app_token(&self, entry: &Entry) -> Option<anyhow::Result<String>> {
+        let config = &entry.config;
+        let name = config.github_app_secret_name.as_deref()?;
+        let namespace = config
+            .github_app_secret_namespace
+            .as_deref()
+            .unwrap_or("gitops-operator");
+        let token = async {
+            let repo = parse_github_repo(&config.manifest_repository).with_context(|| {
+                format!("{} is not a GitHub repository", config.manifest_repository)
+            })?;
+            let app = self
+                .secret_provider
+                .get_github_app(name, namespace)
+                .await
+                .context("Failed to get GitHub App")?;
+            self.github_apps.token(&app, &repo).await
+        };
+        Some(token.await)
+    }
+
+    /// The API token for the forge hosting the entry's manifests: from
+    /// `forge_token_secret_name`, or for GitHub an installation token of
+    /// `github_app_secret_name` or `github_token_secret_name`.
+    async fn forge_token(&self, entry: &Entry) -> anyhow::Result<String> {
+        let config = &entry.config;
+        if let Some(name) = config.forge_token_secret_name.as_deref() {
+            let namespace = config
+                .forge_token_secret_namespace
+                .as_deref()
+                .unwrap_or("gitops-operator");
+            return self.secret_provider.get_forge_token(name, namespace).await;
+        }
+        if config.forge == Forge::GitHub
+            && let Some(token) = self.github_app_token(entry).await
+        {
+            return token;
+        }
+        match (config.forge, config.github_token_secret_name.as_deref()) {
+            (Forge::GitHub, Some(name)) => {
+                let namespace = config
+                    .github_token_secret_namespace
+                    .as_deref()
+                    .unwrap_or("gitops-operator");
+                self.secret_provider.get_github_token(name, namespace).await
+            }
+            _ => anyhow::bail!("gitops.operator.forge_token_secret_name is not set"),
+        }
+    }
+
+    /// Count a failed push for the entry and, once the streak reaches the
+    /// failure threshold, suspend it: the Deployment is annotated so every
+    /// later pass skips it until it is resumed. Returns a note for the result
+    /// message when the entry was suspended.
+    async fn record_push_failure(
+        &self,
+        entry: &Entry,
+        endpoint: &Option<NotificationEndpoint>,
+        error: &str,
+    ) -> Option<String> {
+        let failures = {
+            let key = entry.key();
+            blocking(&self.failures, move |failures| {
+                failures.record_failure(&key)
+            })
+            .await
+        };
+        if self.failure_threshold == 0 || failures < self.failure_threshold {
+            return None;
+        }
+
+        let reason = format!("{} consecutive push failures, last: {}", failures, error);
+        let annotations = BTreeMap::from([
+            (SUSPENDED_AT_ANNOTATION.to_string(), now_rfc3339()),
+            (SUSPENDED_REASON_ANNOTATION.to_string(), reason),
+        ]);
+        if let Err(e) = self
+            .cluster_reporter
+            .annotate(&entry.namespace, &entry.name, &annotations)
+            .await
+        {
+            // Keep counting, so the next failure tries to suspend again.
+            error!("Failed to suspend {}: {:?}", entry.key(), e);
+            return None;
+        }
+        self.reset_failures(entry).await;
+        ::metrics::counter!(ENTRIES_SUSPENDED_TOTAL).increment(1);
+
+        let message = format!(
+            ":rotating_light: Suspended {} after {} consecutive push failures; fix its configuration and resume it with POST /resume/{}/{}",
+            entry.key(),
+            failures,
+            &entry.namespace,
+            &entry.name
+        );
+        self.record_event(entry, EventSeverity::Warning, "Suspended", &message)
+            .await;
+        self.notify(entry, endpoint, Notification::failure(&message))
+            .await;
+        error!("{}", message);
+
+        Some(format!("suspended after {} consecutive failures", failures))
+    }
+
+    /// Lift a suspension or a pause: remove the suspension and pause
+    /// annotations from the entry's Deployment, forget any pause set through
+    /// the API, and start counting failures afresh.
+    pub async fn resume(&self, entry: &Entry) -> anyhow::Result<()> {
+        self.cluster_reporter
+            .remove_annotations(
+                &entry.namespace,
+                &entry.name,
+                &[
+                    SUSPENDED_AT_ANNOTATION.to_string(),
+                    SUSPENDED_REASON_ANNOTATION.to_string(),
+                    PAUSED_ANNOTATION.to_string(),
+                ],
+            )
+            .await
+            .with_context(|| format!("Failed to resume {}", entry.key()))?;
+        self.reset_failures(entry).await;
+        let key = entry.key();
+        blocking(&self.pauses, move |pauses| pauses.resume(&key)).await?;
+
+        let message = format!("Resumed {}", entry.key());
+        self.record_event(entry, EventSeverity::Normal, "Resumed", &message)
+            .await;
+        info!("{}", message);
+
+        Ok(())
+    }
+
+    /// Pause the entry on behalf of `by`, and with `annotate` also mark the
+    /// Deployment with `gitops.operator.paused` so the pause outlives the
+    /// operator's state. Returns false if it was already paused.
+    pub async fn pause(
+        &self,
+        entry: &Entry,
+        by: Option<String>,
+        annotate: bool,
+    ) -> anyhow::Result<bool> {
+        let (_, created) = {
+            let (key, by) = (entry.key(), by.clone());
+            blocking(&self.pauses, move |pauses| pauses.pause(&key, by)).await?
+        };
+        if annotate {
+            let annotations = BTreeMap::from([(PAUSED_ANNOTATION.to_string(), "true".to_string())]);
+            self.cluster_reporter
+                .annotate(&entry.namespace, &entry.name, &annotations)
+                .await
+                .with_context(|| format!("Failed to annotate {}", entry.key()))?;
+        }
+        if created {
+            let message = match &by {
+                Some(by) => format!("Paused {} ({})", entry.key(), by),
+                None => format!("Paused {}", entry.key()),
+            };
+            self.record_event(entry, EventSeverity::Normal, "Paused", &message)
+                .await;
+            info!("{}", message);
+        }
+
+        Ok(created)
+    }
+
+    /// Process a deployment entry
+    #[tracing::instrument(name = "deployment_processor_process", skip(self, entry), fields())]
+    pub async fn process(&self, entry: &Entry) -> ReconcileResult {
+        let result = self.process_entry(entry).await;
+        let git_retries = take_git_retries(Path::new(&entry.app_repo_path()))
+            + take_git_retries(Path::new(&entry.manifest_repo_path()));
+        ReconcileResult {
+            git_retries,
+            ..result
+        }
+    }
+
+    async fn process_entry(&self, entry: &Entry) -> ReconcileResult {
+        info!("Processing: {}/{}", &entry.namespace, &entry.name);
+
+        // The cached object may lag behind the API server; act on the live
+        // one (and report its version) when asked to.
+        let live;
+        let entry = if self.refetch {
+            match self
+                .cluster_reporter
+                .live_deployment(&entry.namespace, &entry.name)
+                .await
+            {
+                Ok(Some(deployment)) => match Entry::new(&deployment) {
+                    Some(fresh) => {
+                        if fresh.resource_version != entry.resource_version {
+                            info!(
+                                "Cached {} was at resourceVersion {:?}, acting on live {:?}",
+                                entry.key(),
+                                entry.resource_version,
+                                fresh.resource_version
+                            );
+                        }
+                        live = fresh;
+                        &live
+                    }
+                    None => {
+                        return ReconcileResult::skipped(
+                            entry,
+                            format!("Deployment {} is no longer tracked", &entry.name),
+                        );
+                    }
+                },
+                Ok(None) => {
+                    return ReconcileResult::skipped(
+                        entry,
+                        format!("Deployment {} no longer exists", &entry.name),
+                    );
+                }
+                Err(e) => {
+                    return ReconcileResult::failure(
+                        entry,
+                        ErrorKind::Network,
+                        format!("Failed to read live Deployment {}: {:#}", entry.key(), e),
+                    );
+                }
+            }
+        } else {
+            entry
+        };
+
+        if let Some(held) = self.check_hold(entry) {
+            return held;
+        }
+
+        // Before any credentials are read for them
+        if let Err(denied) = self.check_egress(entry) {
+            let message = denied.to_string();
+            self.record_event(entry, EventSeverity::Warning, "EgressDenied", &message)
+                .await;
+            return ReconcileResult::failure(entry, ErrorKind::Policy, message);
+        }
+
+        // Get notification endpoint
+        let endpoint = self.get_notifications_endpoint(entry).await;
+
+        // Get SSH key
+        let credentials = match self.git_credentials(entry).await {
+            Ok(credentials) => credentials,
+            Err((reason, e)) => {
+                error!("Failed to get git credentials: {:?}", e);
+                let message = format!("{:#}", e);
+                self.record_event(entry, EventSeverity::Warning, reason, &message)
+                    .await;
+                return ReconcileResult::failure(entry, ErrorKind::Auth, message);
+            }
+        };
+
+        let registry = self.registry_for(entry);
+        let registry_url = registry.url.as_str();
+
+        // Build full container image reference (e.g. ghcr.io/user/repo)
+        // For Docker Hub the image_name is used as-is, for other registries
+        // the registry host is prepended
+        let container_image = build_container_image(registry_url, &entry.config.image_name);
+        let target = entry.container_target(registry_url);
+
+        // Get registry credentials (the mirror's, when checks go to one);
+        // anonymous registries need none
+        let mirrors = registry_mirrors();
+        let credentials_url = mirrors
+            .lookup(registry_url)
+            .map_or(registry_url, |mirror| mirror.url.as_str());
+        let registry_credentials = match &registry.secret {
+            Some((name, namespace)) => self
+                .secret_provider
+                .get_registry_auth(name, namespace, credentials_url)
+                .await
+                .map(Some),
+            None if registry.auth == RegistryAuth::Ecr => {
+                ecr_auth().auth_token(registry_url).await.map(Some)
+            }
+            None => Ok(None),
+        };
+
+        info!("Creating registry checker for: {}", registry_url);
+        let image_checker: Option<Box<dyn ImageChecker>> = match registry_credentials {
+            Ok(credentials) => {
+                match self
+                    .image_checker_factory
+                    .create(registry_url, credentials)
+                    .await
+                {
+                    Ok(checker) => Some(checker),
+                    Err(e) => {
+                        error!("Failed to create image checker: {:?}", e);
+                        None
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to get registry credentials: {:?}", e);
+                None
+            }
+        };
+        let image_checker = match image_checker {
+            Some(primary) if !entry.config.fallback_registry_urls.is_empty() => Some(
+                self.with_fallback_registries(entry, &registry, primary)
+                    .await,
+            ),
+            checker => checker,
+        };
+
+        // Start process
+        info!("Performing reconciliation for: {}", &entry.name);
+        let app_repo_path = entry.app_repo_path();
+        let manifest_repo_path = entry.manifest_repo_path();
+        let _checkouts = path_locks()
+            .lock(&[&app_repo_path, &manifest_repo_path])
+            .await;
+
+        // Create concurrent clone operations
+        info!("Cloning repositories for: {}", &entry.name);
+        let app_head = self.app_head(entry, &app_repo_path, &credentials.app);
+
+        let manifest_clone = {
+            let repo = entry.config.manifest_repository.clone();
+            let path = manifest_repo_path.clone();
+            let branch = entry.config.manifest_branch.clone();
+            let credentials = credentials.manifest.clone();
+            let strategy = entry.config.clone_strategy;
+            let git = self.git.clone();
+            tokio::task::spawn_blocking(move || {
+                sync_repo(git.as_ref(), &repo, &path, &branch, &credentials, strategy)
+            })
+        };
+
+        // Wait for both clones to complete
+        let (app_head, manifest_cloned) = tokio::join!(app_head, manifest_clone);
+        if let Err(e) = manifest_cloned {
+            error!("Failed to clone repositories: {:?}", e);
+        }
+        // Another entry's checkout, when it fetched the same repository first.
+        // It stays locked while it is read, so neither that entry's pass nor
+        // repository maintenance changes it underneath. Only the entry that
+        // fetched owns it, so this waits on no one waiting for us.
+        let _shared_checkout = if app_head.path != app_repo_path {
+            Some(path_locks().lock(&[&app_head.path]).await)
+        } else {
+            None
+        };
+        let app_repo_path = app_head.path;
+
+        // The tag written to the manifests (and checked in the registry):
+        // the newest registry tag the tag policy selects, or one derived from
+        // the latest remote head. Only the latter has a build to wait for.
+        let (commit_sha, new_sha) = match entry.config.tag_policy() {
+            Some(policy) => {
+                match newest_registry_tag(entry, image_checker.as_deref(), policy).await {
+                    Ok(tag) => (None, tag),
+                    Err(e) => {
+                        let message = format!(
+                            "Failed to pick a tag of {}: {:#}",
+                            &entry.config.image_name, e
+                        );
+                        error!("{}", message);
+                        return ReconcileResult::failure(entry, ErrorKind::Other, message);
+                    }
+                }
+            }
+            None => match (app_head.sha, app_head.tag) {
+                (Ok(sha), _) if entry.config.tag_source == TagSource::RevisionLabel => {
+                    match tag_for_revision(entry, image_checker.as_deref(), &sha).await {
+                        Ok(tag) => (Some(sha), tag),
+                        Err(e) => {
+                            let message = format!(
+                                "Failed to find the tag of {} built from {}: {:#}",
+                                &entry.config.image_name, &sha, e
+                            );
+                            error!("{}", message);
+                            return ReconcileResult {
+                                to_sha: Some(sha),
+                                ..ReconcileResult::failure(entry, ErrorKind::Other, message)
+                            };
+                        }
+                    }
+                }
+                // Builds are looked up by commit, whatever the tag looks like.
+                (Ok(sha), tag) => {
+                    warn_if_ambiguous(entry, &app_repo_path, &sha);
+                    let tag = entry.tag_for(&sha, tag.as_deref());
+                    (Some(sha), tag)
+                }
+                (Err((kind, e)), _) => {
+                    error!("Failed to get latest SHA: {}", e);
+                    return ReconcileResult::failure(
+                        entry,
+                        kind,
+                        format!("Failed to get latest SHA: {}", e),
+                    );
+                }
+            },
+        };
+
+        // Before the shortcut for unchanged passes: drift comes from the
+        // cluster, not from the app or the configuration.
+        if let Some(manifest_tag) = entry
+            .manifest_files(&manifest_repo_path, &target)
+            .first()
+            .and_then(|path| current_tag(path, &target).ok().flatten())
+        {
+            self.check_drift(entry, &endpoint, &manifest_tag, image_checker.as_deref())
+                .await;
+        }
+
+        let fingerprint = fingerprint(&entry.config, &new_sha);
+        if !self.force
+            && self
+                .changes
+                .unchanged(&entry.key(), &fingerprint, self.change_ttl)
+        {
+            let message = format!(
+                "Deployment {} is unchanged at {} since its last successful pass",
+                &entry.name, &new_sha
+            );
+            info!("{}", message);
+            return ReconcileResult::success(entry, Action::UpToDate, None, Some(new_sha), message);
+        }
+
+        let author = self.app_commit_author(entry, &app_repo_path, commit_sha.as_deref());
+
+        let deployment_paths = entry.manifest_files(&manifest_repo_path, &target);
+        let Some(deployment_path) = deployment_paths.first() else {
+            let message = format!(
+                "No manifest under {} references image '{}'",
+                &entry.config.deployment_path, &entry.config.image_name
+            );
+            error!("{}", message);
+            return ReconcileResult::failure(entry, ErrorKind::Other, message);
+        };
+
+        if !deployment_paths
+            .iter()
+            .any(|path| images_need_patching(path, &target, &new_sha).unwrap_or(false))
+        {
+            let message = format!("Deployment {} is up to date at {}", &entry.name, &new_sha);
+            info!("{}", message);
+            self.reset_failures(entry).await;
+            self.changes.record(&entry.key(), fingerprint);
+            self.complete_approval(entry).await;
+            return ReconcileResult::success(entry, Action::UpToDate, None, Some(new_sha), message);
+        }
+
+        // In a monorepo, commits to other services don't warrant a new image.
+        if let Some(sha) = &commit_sha
+            && !entry.config.watch_paths.is_empty()
+        {
+            let manifest_tag = current_tag(deployment_path, &target).ok().flatten();
+            if let Some(base) = last_synced_commit(entry, &app_repo_path, manifest_tag.as_deref()) {
+                match touches_paths(
+                    Path::new(&app_repo_path),
+                    &base,
+                    sha,
+                    &entry.config.watch_paths,
+                ) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        let message = format!(
+                            "Deployment {} stays at {}: no changes under {} since {}",
+                            &entry.name,
+                            manifest_tag.as_deref().unwrap_or(&base),
+                            entry.config.watch_paths.join(", "),
+                            &base
+                        );
+                        info!("{}", message);
+                        self.reset_failures(entry).await;
+                        return ReconcileResult::success(
+                            entry,
+                            Action::UpToDate,
+                            None,
+                            manifest_tag,
+                            message,
+                        );
+                    }
+                    Err(e) => warn!(
+                        "Failed to diff {}..{} of {}, promoting: {}",
+                        &base, sha, &entry.config.app_repository, e
+                    ),
+                }
+            }
+        }
+
+        let old_tag = current_tag(deployment_path, &target).ok().flatten();
+        if let Err(held) = self.check_gates(entry, old_tag, &new_sha).await {
+            return held;
+        }
+
+        info!("Checking image: {}", &container_image);
+        if let Some(ref checker) = image_checker {
+            let image_found = match &commit_sha {
+                Some(commit_sha) => {
+                    self.wait_for_image(
+                        entry,
+                        checker.as_ref(),
+                        commit_sha,
+                        &new_sha,
+                        registry_url,
+                        &endpoint,
+                    )
+                    .await
+                }
+                // The tag was just listed by the registry.
+                None => Ok(true),
+            };
+            let image_found = match image_found {
+                Ok(found) => found,
+                Err(limited) => {
+                    let message = format!(
+                        ":x: image {}:{} could not be checked: {}",
+                        &container_image, &new_sha, limited
+                    );
+                    self.notify(entry, &endpoint, Notification::failure(&message))
+                        .await;
+                    error!("{}", message);
+                    return ReconcileResult::failure(entry, ErrorKind::Network, message);
+                }
+            };
+            if !image_found {
+                let message = format!(
+                    ":x: image {}:{} not found in registry after waiting for build",
+                    &container_image, &new_sha
+                );
+                self.notify(entry, &endpoint, Notification::failure(&message))
+                    .await;
+                error!("{}", message);
+                return ReconcileResult::failure(entry, ErrorKind::Other, message);
+            }
+
+            // The other images come from the same build, so once the primary
+            // one is published they are checked without waiting again.
+            for image_name in entry.config.image_names.iter().skip(1) {
+                let (message, kind) =
+                    match image_exists(checker.as_ref(), image_name, &new_sha).await {
+                        Ok(true) => continue,
+                        Ok(false) => (
+                            format!(
+                                ":x: image {}:{} not found in registry",
+                                build_container_image(registry_url, image_name),
+                                &new_sha
+                            ),
+                            ErrorKind::Other,
+                        ),
+                        Err(limited) => (
+                            format!(
+                                ":x: image {}:{} could not be checked: {}",
+                                build_container_image(registry_url, image_name),
+                                &new_sha,
+                                limited
+                            ),
+                            ErrorKind::Network,
+                        ),
+                    };
+                self.notify(entry, &endpoint, Notification::failure(&message))
+                    .await;
+                error!("{}", message);
+                return ReconcileResult::failure(entry, kind, message);
+            }
+        }
+
+        // Capture the SHA currently deployed before we overwrite it, so the
+        // result can report the from -> to transition.
+        let from_sha = current_tag(deployment_path, &target).ok().flatten();
+
+        if self.requires_approval(entry)
+            && let Some(held) = self
+                .awaiting_approval(entry, &endpoint, from_sha.clone(), &new_sha)
+                .await
+        {
+            return held;
+        }
+
+        self.backup_manifests(entry, &manifest_repo_path, &deployment_paths);
+        if let Err(e) = patch_manifests(
+            &deployment_paths,
+            &target,
+            &new_sha,
+            entry.config.image_host,
+        ) {
+            let _ = remove_dir_all(&manifest_repo_path);
+            let message = format!(
+                "Failed to patch deployment {} to version {}: {:#}",
+                &entry.name, &new_sha, e
+            );
+            self.record_event(entry, EventSeverity::Warning, "PatchFailed", &message)
+                .await;
+            self.notify(
+                entry,
+                &endpoint,
+                Notification::failure(&message).with_tags(from_sha.as_deref(), &new_sha),
+            )
+            .await;
+            error!("{}", message);
+            return ReconcileResult::failure(entry, ErrorKind::Other, message);
+        }
+        info!("File patched successfully for: {}", &entry.name);
+        if let Some(rejected) = self
+            .reject_invalid_manifests(
+                entry,
+                &endpoint,
+                &manifest_repo_path,
+                &deployment_paths,
+                &new_sha,
+            )
+            .await
+        {
+            return rejected;
+        }
+
+        let mut vars = entry.template_vars();
+        vars.insert("old_sha".to_string(), from_sha.clone().unwrap_or_default());
+        vars.insert("new_sha".to_string(), new_sha.clone());
+        let mut commit_message = render(
+            entry
+                .config
+                .commit_message
+                .as_deref()
+                .unwrap_or(DEFAULT_COMMIT_MESSAGE),
+            &vars,
+        );
+        if let Some(commit_sha) = &commit_sha
+            && let Some(body) = source_commit_body(entry, &app_repo_path, commit_sha)
+        {
+            commit_message.push_str("\n\n");
+            commit_message.push_str(&body);
+        }
+
+        if entry.config.strategy == Strategy::PullRequest {
+            let pull_request = match self
+                .propose_change(
+                    entry,
+                    &endpoint,
+                    &vars,
+                    &commit_message,
+                    &credentials.manifest,
+                    author.as_ref(),
+                )
+                .await
+            {
+                Ok(pull_request) => pull_request,
+                Err(failed) => return failed,
+            };
+            self.reset_failures(entry).await;
+            self.complete_approval(entry).await;
+
+            let message = format!(
+                "Proposed version {} of {} in {}: {}",
+                &new_sha,
+                &entry.name,
+                entry.config.forge.request_name(pull_request.number),
+                pull_request.url
+            );
+            self.record_event(entry, EventSeverity::Normal, "PullRequestOpened", &message)
+                .await;
+            self.notify(
+                entry,
+                &endpoint,
+                Notification::info(&message)
+                    .with_tags(from_sha.as_deref(), &new_sha)
+                    .with_commit_url(app_commit_url(entry, commit_sha.as_deref())),
+            )
+            .await;
+            info!("{}", message);
+            self.changes.record(&entry.key(), fingerprint);
+            return ReconcileResult::success(
+                entry,
+                Action::Proposed,
+                from_sha,
+                Some(new_sha),
+                message,
+            );
+        }
+
+        if let Err(e) = entry.commit_changes(
+            self.git.as_ref(),
+            &manifest_repo_path,
+            &commit_message,
+            &credentials.manifest,
+            author.as_ref(),
+        ) {
+            let _ = remove_dir_all(&manifest_repo_path);
+            let mut message = format!(
+                "Failed to commit changes for {} (version {}): {:#}",
+                &entry.name, &new_sha, e
+            );
+            self.record_event(entry, EventSeverity::Warning, "PushFailed", &message)
+                .await;
+            self.notify(
+                entry,
+                &endpoint,
+                Notification::failure(&message).with_tags(from_sha.as_deref(), &new_sha),
+            )
+            .await;
+            error!("{}", message);
+            if let Some(note) = self.record_push_failure(entry, &endpoint, &message).await {
+                message.push_str(&format!(" ({})", note));
+            }
+            return ReconcileResult::failure(entry, ErrorKind::from_git(&e), message);
+        }
+        info!("Changes committed successfully");
+        self.record_commit(entry, &manifest_repo_path, from_sha.as_deref(), &new_sha);
+        self.reset_failures(entry).await;
+        self.complete_approval(entry).await;
+        self.record_sync(entry, &new_sha).await;
+
+        let mut message = format!(
+            "Deployment {} patched successfully to version {}",
+            &entry.name, &new_sha
+        );
+        let propagated = self
+            .propagate(
+                entry,
+                &container_image,
+                &new_sha,
+                &credentials.manifest,
+                &endpoint,
+            )
+            .await;
+        if !propagated.is_empty() {
+            message.push_str(&format!(" (also pinned in {})", propagated.join(", ")));
+        }
+        if let Some(fallback) = image_checker
+            .as_ref()
+            .and_then(|checker| checker.answered_by())
+            .filter(|url| url != registry_url)
+        {
+            message.push_str(&format!(" (image found on fallback registry {})", fallback));
+        }
+        self.record_event(entry, EventSeverity::Normal, "ManifestPatched", &message)
+            .await;
+        self.notify(
+            entry,
+            &endpoint,
+            Notification::success(&message)
+                .with_tags(from_sha.as_deref(), &new_sha)
+                .with_commit_url(app_commit_url(entry, commit_sha.as_deref())),
+        )
+        .await;
+        info!("{}", message);
+        self.changes.record(&entry.key(), fingerprint);
+        if entry.config.verify_rollout {
+            self.spawn_rollout_check(entry, &new_sha, from_sha.as_deref(), &endpoint);
+        }
+
+        ReconcileResult::success(entry, Action::Patched, from_sha, Some(new_sha), message)
+    }
+
+    /// Revert the manifest to an earlier image tag and push it, reusing the
+    /// regular clone/patch/commit plumbing. `target` is either an explicit tag
+    /// (SHA) or `"previous"`, which picks the tag the manifest pointed at before
+    /// the current one according to the manifests repository history.
+    ///
+    /// The rollback goes through the same gates as a promotion: a suspended
+    /// or paused entry isn't touched, and windows and policies can hold the
+    /// target back. With `strategy: pull_request` it is proposed in a pull
+    /// request rather than pushed. Either way the entry is paused afterwards
+    /// so the next pass doesn't promote the tag it was rolled back from again.
+    #[tracing::instrument(name = "deployment_processor_rollback", skip(self, entry), fields())]
+    pub async fn rollback(&self, entry: &Entry, target: &str) -> ReconcileResult {
+        let result = self.rollback_entry(entry, target, true).await;
+        ReconcileResult {
+            git_retries: take_git_retries(Path::new(&entry.manifest_repo_path())),
+            ..result
+        }
+    }
+
+    /// Roll the entry back to `target`. `requested` rollbacks (over the API)
+    /// are gated and pause the entry; [`Self::auto_revert`] undoing a failed
+    /// rollout of its own promotion is neither.
+    async fn rollback_entry(
+        &self,
+        entry: &Entry,
+        target: &str,
+        requested: bool,
+    ) -> ReconcileResult {
+        info!(
+            "Rolling back: {}/{} to {}",
+            &entry.namespace, &entry.name, target
+        );
+
+        if !is_valid_tag(target) {
+            return ReconcileResult::failure(
+                entry,
+                ErrorKind::Other,
+                format!("Invalid rollback target '{}'", target),
+            );
+        }
+        if requested && let Some(held) = self.check_hold(entry) {
+            return held;
+        }
+        if let Err(denied) = self
+            .egress
+            .check(EgressKind::Git, &entry.config.manifest_repository)
+        {
+            return ReconcileResult::failure(entry, ErrorKind::Policy, denied.to_string());
+        }
+
+        let endpoint = self.get_notifications_endpoint(entry).await;
+
+        let credentials = match self.git_credentials(entry).await {
+            Ok(credentials) => credentials.manifest,
+            Err((reason, e)) => {
+                error!("Failed to get git credentials: {:?}", e);
+                let message = format!("{:#}", e);
+                self.record_event(entry, EventSeverity::Warning, reason, &message)
+                    .await;
+                return ReconcileResult::failure(entry, ErrorKind::Auth, message);
+            }
+        };
+
+        let containers = entry.container_target(&self.registry_for(entry).url);
+
+        let manifest_repo_path = entry.manifest_repo_path();
+        let _checkout = path_locks().lock(&[&manifest_repo_path]).await;
+        let manifest_clone = {
+            let repo = entry.config.manifest_repository.clone();
+            let path = manifest_repo_path.clone();
+            let branch = entry.config.manifest_branch.clone();
+            let credentials = credentials.clone();
+            let strategy = entry.config.clone_strategy;
+            let git = self.git.clone();
+            tokio::task::spawn_blocking(move || {
+                sync_repo(git.as_ref(), &repo, &path, &branch, &credentials, strategy)
+            })
+        };
+        if let Err(e) = manifest_clone.await {
+            error!("Failed to clone manifest repository: {:?}", e);
+        }
+
+        let deployment_paths = entry.manifest_files(&manifest_repo_path, &containers);
+        let Some(deployment_path) = deployment_paths.first() else {
+            return ReconcileResult::failure(
+                entry,
+                ErrorKind::Other,
+                format!(
+                    "No manifest under {} references image '{}'",
+                    &entry.config.deployment_path, &entry.config.image_name
+                ),
+            );
+        };
+        let from_sha = current_tag(deployment_path, &containers).ok().flatten();
+
+        let to_sha = if target == "previous" {
+            let history = image_tag_history(
+                Path::new(&manifest_repo_path),
+                &repo_relative(&manifest_repo_path, deployment_path),
+                &containers,
+                2,
+            );
+            match history {
+                Ok(revisions) => match revisions
+                    .into_iter()
+                    .find(|r| Some(&r.tag) != from_sha.as_ref())
+                {
+                    Some(revision) => revision.tag,
+                    None => {
+                        return ReconcileResult::failure(
+                            entry,
+                            ErrorKind::Other,
+                            format!(
+                                "No previous image tag found in the history of {}",
+                                &entry.config.deployment_path
+                            ),
+                        );
+                    }
+                },
+                Err(e) => {
+                    error!("Failed to read manifest history: {:?}", e);
+                    return ReconcileResult::failure(
+                        entry,
+                        ErrorKind::Other,
+                        format!("Failed to read manifest history: {:#}", e),
+                    );
+                }
+            }
+        } else {
+            target.to_string()
+        };
+
+        if !deployment_paths
+            .iter()
+            .any(|path| images_need_patching(path, &containers, &to_sha).unwrap_or(false))
+        {
+            let message = format!("Deployment {} is already at {}", &entry.name, &to_sha);
+            info!("{}", message);
+            return ReconcileResult::success(entry, Action::UpToDate, None, Some(to_sha), message);
+        }
+        if requested && let Err(held) = self.check_gates(entry, from_sha.clone(), &to_sha).await {
+            return held;
+        }
+
+        self.backup_manifests(entry, &manifest_repo_path, &deployment_paths);
+        if let Err(e) = patch_manifests(
+            &deployment_paths,
+            &containers,
+            &to_sha,
+            entry.config.image_host,
+        ) {
+            let _ = remove_dir_all(&manifest_repo_path);
+            let message = format!(
+                "Failed to roll back deployment {} to version {}: {:#}",
+                &entry.name, &to_sha, e
+            );
+            self.record_event(entry, EventSeverity::Warning, "PatchFailed", &message)
+                .await;
+            self.notify(entry, &endpoint, Notification::failure(&message))
+                .await;
+            error!("{}", message);
+            return ReconcileResult::failure(entry, ErrorKind::Other, message);
+        }
+        if let Some(rejected) = self
+            .reject_invalid_manifests(
+                entry,
+                &endpoint,
+                &manifest_repo_path,
+                &deployment_paths,
+                &to_sha,
+            )
+            .await
+        {
+            return rejected;
+        }
+
+        let mut vars = entry.template_vars();
+        vars.insert("old_sha".to_string(), from_sha.clone().unwrap_or_default());
+        vars.insert("new_sha".to_string(), to_sha.clone());
+        let commit_message = render(ROLLBACK_COMMIT_MESSAGE, &vars);
+
+        // With `strategy: pull_request`, a rollback is proposed like any other
+        // change instead of going to the observed branch.
+        let (action, mut message) = if entry.config.strategy == Strategy::PullRequest {
+            let pull_request = match self
+                .propose_change(entry, &endpoint, &vars, &commit_message, &credentials, None)
+                .await
+            {
+                Ok(pull_request) => pull_request,
+                Err(failed) => return failed,
+            };
+            let message = format!(
+                ":rewind: Proposed rolling deployment {} back to version {} in {}: {}",
+                &entry.name,
+                &to_sha,
+                entry.config.forge.request_name(pull_request.number),
+                pull_request.url
+            );
+            (Action::Proposed, message)
+        } else {
+            if let Err(e) = entry.commit_changes(
+                self.git.as_ref(),
+                &manifest_repo_path,
+                &commit_message,
+                &credentials,
+                None,
+            ) {
+                let _ = remove_dir_all(&manifest_repo_path);
+                let message = format!(
+                    "Failed to commit rollback for {} (version {}): {:#}",
+                    &entry.name, &to_sha, e
+                );
+                self.record_event(entry, EventSeverity::Warning, "PushFailed", &message)
+                    .await;
+                self.notify(entry, &endpoint, Notification::failure(&message))
+                    .await;
+                error!("{}", message);
+                return ReconcileResult::failure(entry, ErrorKind::from_git(&e), message);
+            }
+
+            self.record_commit(entry, &manifest_repo_path, from_sha.as_deref(), &to_sha);
+            self.record_sync(entry, &to_sha).await;
+            // The manifest no longer matches what the last pass left behind.
+            self.changes.forget(&entry.key());
+
+            let message = format!(
+                ":rewind: Deployment {} rolled back to version {}",
+                &entry.name, &to_sha
+            );
+            (Action::RolledBack, message)
+        };
+        if requested {
+            let by = format!("rollback to {}", &to_sha);
+            match self.pause(entry, Some(by), false).await {
+                Ok(_) => message.push_str("; it stays paused until resumed"),
+                Err(e) => warn!(
+                    "Failed to pause {} after rolling it back: {:#}",
+                    entry.key(),
+                    e
+                ),
+            }
+        }
+        let reason = match action {
+            Action::Proposed => "PullRequestOpened",
+            _ => "RolledBack",
+        };
+        self.record_event(entry, EventSeverity::Normal, reason, &message)
+            .await;
+        self.notify(
+            entry,
+            &endpoint,
+            Notification::success(&message).with_tags(from_sha.as_deref(), &to_sha),
+        )
+        .await;
+        info!("{}", message);
+
+        ReconcileResult::success(entry, action, from_sha, Some(to_sha), message)
+    }
+
+    /// Release history for a deployment: the image tags its manifest has
+    /// pointed at over time, newest first, read from the manifest repository.
+    pub async fn releases(
+        &self,
+        entry: &Entry,
+        limit: usize,
+    ) -> anyhow::Result<Vec<ImageRevision>> {
+        self.egress
+            .check(EgressKind::Git, &entry.config.manifest_repository)?;
+        let credentials = self
+            .git_credentials(entry)
+            .await
+            .map_err(|(_, e)| e)?
+            .manifest;
 
-        let manifest_clone = {
-            let repo = entry.config.manifest_repository.clone();
-            let path = manifest_repo_path.clone();
-            let branch = entry.config.observe_branch.clone();
-            let ssh_key_secret = ssh_key_secret.clone();
-            tokio::task::spawn_blocking(move || clone_repo(&repo, &path, &branch, &ssh_key_secret))
-        };
+        let target = entry.container_target(&self.registry_for(entry).url);
 
-        // Wait for both clones to complete
-        if let Err(e) = tokio::try_join!(app_clone, manifest_clone) {
-            error!("Failed to clone repositories: {:?}", e);
-        }
+        let entry = entry.clone();
+        let git = self.git.clone();
+        let _checkout = path_locks().lock(&[&entry.manifest_repo_path()]).await;
+        tokio::task::spawn_blocking(move || {
+            let path = entry.manifest_repo_path();
+            sync_repo(
+                git.as_ref(),
+                &entry.config.manifest_repository,
+                &path,
+                &entry.config.manifest_branch,
+                &credentials,
+                entry.config.clone_strategy,
+            );
+            let manifest = entry
+                .manifest_files(&path, &target)
+                .first()
+                .map(|file| repo_relative(&path, file))
+                .unwrap_or_else(|| entry.config.deployment_path.clone());
+            image_tag_history(Path::new(&path), &manifest, &target, limit)
+        })
+        .await?
+        .context("Failed to read manifest history")
+    }
 
-        // Find the latest remote head
-        info!("Getting latest commit for: {}", &entry.name);
-        let new_sha = get_latest_commit(
-            Path::new(&app_repo_path),
-            &entry.config.observe_branch,
-            &entry.config.tag_type,
-            &ssh_key_secret,
-        );
+    /// Apply the dependency rules whose source is `entry`, pinning `image` to
+    /// `sha` in each dependent repository. A failing rule is reported but
+    /// does not fail the entry's own (already pushed) promotion. Returns the
+    /// repositories that got a commit.
+    async fn propagate(
+        &self,
+        entry: &Entry,
+        image: &str,
+        sha: &str,
+        credentials: &GitCredentials,
+        endpoint: &Option<NotificationEndpoint>,
+    ) -> Vec<String> {
+        let key = entry.key();
+        let mut propagated = vec![];
 
-        let new_sha = match new_sha {
-            Ok(sha) => sha,
-            Err(e) => {
-                error!("Failed to get latest SHA: {:?}", e);
-                return ReconcileResult::failure(
-                    entry,
-                    format!("Failed to get latest SHA: {:#}", e),
-                );
+        for (index, rule) in self.dependency_rules.iter().enumerate() {
+            if rule.source != key {
+                continue;
             }
-        };
 
-        let deployment_path = format!("{}/{}", &manifest_repo_path, &entry.config.deployment_path);
+            let target = rule.target(image);
+            let mut vars = entry.template_vars();
+            vars.insert("image".to_string(), target.image_names[0].clone());
+            vars.insert("new_sha".to_string(), sha.to_string());
+            let commit_message = render(DEPENDENCY_COMMIT_MESSAGE, &vars);
+            let dir = format!(
+                "dependency-{}-{}-{}-{}",
+                &entry.namespace, &entry.name, index, &rule.branch
+            );
+            let checkout = format!("{}/", repo_cache_dir().join(dir).display());
 
-        if !needs_patching(&deployment_path, &new_sha).unwrap_or(false) {
-            let message = format!("Deployment {} is up to date at {}", &entry.name, &new_sha);
-            info!("{}", message);
-            return ReconcileResult::success(entry, Action::UpToDate, None, Some(new_sha), message);
-        }
+            let applied = {
+                let rule = rule.clone();
+                let sha = sha.to_string();
+                let credentials = credentials.clone();
+                tokio::task::spawn_blocking(move || {
+                    apply_rule(
+                        &rule,
+                        &checkout,
+                        &target,
+                        &sha,
+                        &credentials,
+                        &commit_message,
+                    )
+                })
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|result| result)
+            };
 
-        info!("Checking image: {}", &container_image);
-        if let Some(ref checker) = image_checker {
-            let image_found = self
-                .wait_for_image(entry, checker.as_ref(), &new_sha, registry_url, &endpoint)
-                .await;
-            if !image_found {
-                let message = format!(
-                    ":x: image {}:{} not found in registry after waiting for build",
-                    &container_image, &new_sha
-                );
-                self.notify(&endpoint, &message).await;
-                error!("{}", message);
-                return ReconcileResult::failure(entry, message);
+            match applied {
+                Ok(true) => {
+                    info!("Pinned {} in {}", sha, &rule.manifest_repository);
+                    propagated.push(rule.manifest_repository.clone());
+                }
+                Ok(false) => info!("{} already pinned at {}", &rule.manifest_repository, sha),
+                Err(e) => {
+                    let message = format!(
+                        "Failed to pin {} in dependent repository {}: {:#}",
+                        sha, &rule.manifest_repository, e
+                    );
+                    self.record_event(entry, EventSeverity::Warning, "PropagationFailed", &message)
+                        .await;
+                    self.notify(entry, endpoint, Notification::failure(&message))
+                        .await;
+                    error!("{}", message);
+                }
             }
         }
 
-        // Capture the SHA currently deployed before we overwrite it, so the
-        // result can report the from -> to transition.
-        let from_sha = current_image_tag(&deployment_path, &container_image)
-            .ok()
-            .flatten();
+        propagated
+    }
 
-        if let Err(e) = patch_deployment(&deployment_path, &container_image, &new_sha) {
-            let _ = remove_dir_all(&manifest_repo_path);
-            let message = format!(
-                "Failed to patch deployment {} to version {}: {:#}",
-                &entry.name, &new_sha, e
-            );
-            self.notify(&endpoint, &message).await;
-            error!("{}", message);
-            return ReconcileResult::failure(entry, message);
-        }
-        info!("File patched successfully for: {}", &entry.name);
+    /// Clone or update the app repository of `entry` at `app_repo_path` and
+    /// resolve the head of its observed branch (or its newest tag matching
+    /// `observe_tag_pattern`), unless an entry earlier in the run tracks the
+    /// same repository and branch or pattern, fetched the same way with the
+    /// same credentials: its checkout (read only) and head are reused then.
+    async fn app_head(
+        &self,
+        entry: &Entry,
+        app_repo_path: &str,
+        credentials: &GitCredentials,
+    ) -> AppHead {
+        let repo = entry.config.app_repository.clone();
+        let branch = entry.config.observe_branch.clone();
+        let tag_pattern = entry.config.observe_tag_pattern.clone();
+        // Only full clones fetch tags.
+        let strategy = match tag_pattern {
+            Some(_) => CloneStrategy::Full,
+            None => entry.config.clone_strategy,
+        };
+        let observed = match &tag_pattern {
+            Some(pattern) => format!("tags:{}", pattern),
+            None => branch.clone(),
+        };
+        let key = format!(
+            "{}#{} ({:?}, credentials {})",
+            repo,
+            observed,
+            strategy,
+            credentials.identity()
+        );
+        let fetch = {
+            let path = app_repo_path.to_string();
+            let credentials = credentials.clone();
+            let git = self.git.clone();
+            move || async move {
+                info!("Getting latest commit of {} ({})", &repo, &branch);
+                let fetched = {
+                    let path = path.clone();
+                    tokio::task::spawn_blocking(move || {
+                        let path = Path::new(&path);
+                        git.sync(&repo, path, &branch, &credentials, strategy)
+                            .map_err(|e| (ErrorKind::from_git(&e), format!("{:#}", e)))?;
+                        match tag_pattern {
+                            Some(pattern) => observed_tag(&repo, path, &pattern),
+                            None => git
+                                .resolve(path, &branch)
+                                .map(|sha| (sha, None))
+                                .map_err(|e| (ErrorKind::from_git(&e), format!("{:#}", e))),
+                        }
+                    })
+                    .await
+                };
+                let (sha, tag) = match fetched {
+                    Ok(Ok((sha, tag))) => (Ok(sha), tag),
+                    Ok(Err(e)) => (Err(e), None),
+                    Err(e) => (Err((ErrorKind::Other, e.to_string())), None),
+                };
+                AppHead { path, sha, tag }
+            }
+        };
 
-        if let Err(e) = commit_changes(
-            &manifest_repo_path,
-            &entry.config.observe_branch,
-            &ssh_key_secret,
-        ) {
-            let _ = remove_dir_all(&manifest_repo_path);
-            let message = format!(
-                "Failed to commit changes for {} (version {}): {:#}",
-                &entry.name, &new_sha, e
-            );
-            self.notify(&endpoint, &message).await;
-            error!("{}", message);
-            return ReconcileResult::failure(entry, message);
+        match &self.app_heads {
+            Some(heads) => heads.get_or_run(&key, fetch).await,
+            None => fetch().await,
         }
-        info!("Changes committed successfully");
+    }
 
-        let message = format!(
-            "Deployment {} patched successfully to version {}",
-            &entry.name, &new_sha
-        );
-        self.notify(&endpoint, &message).await;
-        info!("{}", message);
+    /// The app commit's author, if the entry opted into author mapping and the
+    /// author's email domain is allowlisted; otherwise the operator stays the
+    /// author of the manifest commit.
+    fn app_commit_author(
+        &self,
+        entry: &Entry,
+        app_repo_path: &str,
+        commit_sha: Option<&str>,
+    ) -> Option<CommitAuthor> {
+        if entry.config.author_domains.is_empty() {
+            return None;
+        }
 
-        ReconcileResult::success(entry, Action::Patched, from_sha, Some(new_sha), message)
+        let path = Path::new(app_repo_path);
+        let author = match (&entry.config.observe_tag_pattern, commit_sha) {
+            // The tagged commit, which needn't be the head of the branch.
+            (Some(_), Some(sha)) => get_source_commit(path, sha).map(|commit| commit.author),
+            _ => get_commit_author(path, &entry.config.observe_branch),
+        };
+        match author {
+            Ok(author) if entry.config.allows_author(&author.email) => Some(author),
+            Ok(author) => {
+                info!(
+                    "Not crediting {} on the manifest commit for {}: domain not allowlisted",
+                    &author.email, &entry.name
+                );
+                None
+            }
+            Err(e) => {
+                warn!("Failed to read app commit author: {:?}", e);
+                None
+            }
+        }
     }
 
-    async fn get_notifications_endpoint(&self, entry: &Entry) -> Option<String> {
+    async fn get_notifications_endpoint(&self, entry: &Entry) -> Option<NotificationEndpoint> {
         let secret_name = entry
             .config
             .notifications_secret_name
@@ -412,7 +3466,10 @@ impl DeploymentProcessor {
             .get_notification_endpoint(&secret_name, &namespace)
             .await
         {
-            Ok(endpoint) if !endpoint.is_empty() => Some(endpoint),
+            Ok(endpoint) if !endpoint.url.is_empty() => match entry.config.notifications_provider {
+                Some(provider) => Some(endpoint.with_provider(provider)),
+                None => Some(endpoint),
+            },
             Ok(_) => None,
             Err(e) => {
                 warn!("Failed to get notifications secret: {:?}", e);
@@ -421,8 +3478,21 @@ impl DeploymentProcessor {
         }
     }
 
-    /// Optionally create a build status checker if GitHub token annotations are configured
+    /// Optionally create a build status checker if GitHub App or token
+    /// annotations are configured
     async fn get_build_checker(&self, entry: &Entry) -> Option<Box<dyn BuildStatusChecker>> {
+        if let Some(token) = self.github_app_token(entry).await {
+            return match token.and_then(GitHubBuildChecker::new) {
+                Ok(checker) => {
+                    info!("GitHub build status checker configured with the GitHub App");
+                    Some(Box::new(checker))
+                }
+                Err(e) => {
+                    warn!("Failed to create GitHub build checker: {:?}", e);
+                    None
+                }
+            };
+        }
         let secret_name = entry.config.github_token_secret_name.as_deref()?;
         let namespace = entry
             .config
@@ -459,26 +3529,24 @@ impl DeploymentProcessor {
         entry: &Entry,
         checker: &dyn ImageChecker,
         sha: &str,
+        tag: &str,
         registry_url: &str,
-        endpoint: &Option<String>,
-    ) -> bool {
+        endpoint: &Option<NotificationEndpoint>,
+    ) -> Result<bool, RateLimited> {
         const MAX_RETRIES: u32 = 5;
         const INITIAL_DELAY_SECS: u64 = 10;
         const BACKOFF_MULTIPLIER: u64 = 2;
         const MAX_DELAY_SECS: u64 = 60;
 
+        // Builds are looked up by commit, images by the tag we will write.
         // First check: is the image already available?
-        if checker
-            .check_image(&entry.config.image_name, sha)
-            .await
-            .unwrap_or(false)
-        {
-            return true;
+        if image_exists(checker, &entry.config.image_name, tag).await? {
+            return Ok(true);
         }
 
         info!(
-            "Image {}/{} with SHA {} not found, checking build status...",
-            registry_url, &entry.config.image_name, sha
+            "Image {}/{} with tag {} not found, checking build status...",
+            registry_url, &entry.config.image_name, tag
         );
 
         // Try to get a build status checker (only if GitHub annotations are configured)
@@ -488,19 +3556,19 @@ impl DeploymentProcessor {
             None => {
                 // No build checker configured, just report image not found
                 info!("No GitHub build checker configured, skipping retry logic");
-                return false;
+                return Ok(false);
             }
         };
 
         // Parse GitHub repo from app_repository URL
-        let github_repo = match crate::github::parse_github_repo(&entry.config.app_repository) {
+        let github_repo = match parse_github_repo(&entry.config.app_repository) {
             Some(repo) => repo,
             None => {
                 warn!(
                     "Could not parse GitHub repo from app_repository: {}",
                     &entry.config.app_repository
                 );
-                return false;
+                return Ok(false);
             }
         };
 
@@ -512,7 +3580,7 @@ impl DeploymentProcessor {
                 Ok(status) => status,
                 Err(e) => {
                     warn!("Failed to check build status: {:?}", e);
-                    return false;
+                    return Ok(false);
                 }
             };
 
@@ -530,48 +3598,38 @@ impl DeploymentProcessor {
                         status_str, sha, delay_secs, attempt, MAX_RETRIES
                     );
 
-                    if let Some(ep) = endpoint {
-                        let message = format!(
-                            ":hourglass: Build {} for {}/{} (SHA: {}), retrying in {}s (attempt {}/{})",
-                            status_str,
-                            registry_url,
-                            &entry.config.image_name,
-                            sha,
-                            delay_secs,
-                            attempt,
-                            MAX_RETRIES
-                        );
-                        if let Err(e) = self.notification_sender.send(&message, ep).await {
-                            warn!("Failed to send notification: {:?}", e);
-                        }
-                    }
+                    let message = format!(
+                        ":hourglass: Build {} for {}/{} (SHA: {}), retrying in {}s (attempt {}/{})",
+                        status_str,
+                        registry_url,
+                        &entry.config.image_name,
+                        sha,
+                        delay_secs,
+                        attempt,
+                        MAX_RETRIES
+                    );
+                    self.notify(entry, endpoint, Notification::info(&message))
+                        .await;
 
                     tokio::time::sleep(tokio::time::Duration::from_secs(delay_secs)).await;
 
                     // Check registry again after waiting
-                    if checker
-                        .check_image(&entry.config.image_name, sha)
-                        .await
-                        .unwrap_or(false)
-                    {
+                    if image_exists(checker, &entry.config.image_name, tag).await? {
                         info!("Image found after {} retries", attempt);
-                        return true;
+                        return Ok(true);
                     }
 
                     delay_secs = (delay_secs * BACKOFF_MULTIPLIER).min(MAX_DELAY_SECS);
                 }
                 BuildStatus::Failed => {
                     error!("Build failed for SHA {} in repo {}", sha, github_repo);
-                    if let Some(ep) = endpoint {
-                        let message = format!(
-                            ":x: Build failed for {}/{} (SHA: {}), image will not be available",
-                            registry_url, &entry.config.image_name, sha
-                        );
-                        if let Err(e) = self.notification_sender.send(&message, ep).await {
-                            warn!("Failed to send notification: {:?}", e);
-                        }
-                    }
-                    return false;
+                    let message = format!(
+                        ":x: Build failed for {}/{} (SHA: {}), image will not be available",
+                        registry_url, &entry.config.image_name, sha
+                    );
+                    self.notify(entry, endpoint, Notification::failure(&message))
+                        .await;
+                    return Ok(false);
                 }
                 BuildStatus::Completed => {
                     // Build completed but image not in registry: likely wrong image name
@@ -579,11 +3637,11 @@ impl DeploymentProcessor {
                         "Build completed for SHA {} but image not found, possible image name mismatch",
                         sha
                     );
-                    return false;
+                    return Ok(false);
                 }
                 BuildStatus::NotFound => {
                     warn!("No CI build found for SHA {} in repo {}", sha, github_repo);
-                    return false;
+                    return Ok(false);
                 }
             }
         }
@@ -592,11 +3650,130 @@ impl DeploymentProcessor {
             "Image not found after {} retries for SHA {}",
             MAX_RETRIES, sha
         );
-        false
+        Ok(false)
+    }
+}
+
+/// Whether `image:tag` is in the registry. A failed check counts as a missing
+/// image, except a rate limit, which is passed on so the deployment fails with
+/// it rather than waiting for a build.
+async fn image_exists(
+    checker: &dyn ImageChecker,
+    image: &str,
+    tag: &str,
+) -> Result<bool, RateLimited> {
+    match checker.check_image(image, tag).await {
+        Ok(found) => Ok(found),
+        Err(e) => match e.downcast::<RateLimited>() {
+            Ok(limited) => Err(limited),
+            Err(e) => {
+                warn!("Failed to check image {}:{}: {:#}", image, tag, e);
+                Ok(false)
+            }
+        },
+    }
+}
+
+/// Warn when the abbreviated form of `sha` in `entry`'s image tags matches
+/// more than one object of the app checkout at `app_repo_path`: the tag still
+/// has the length CI gives it, but can't be traced back to its commit.
+fn warn_if_ambiguous(entry: &Entry, app_repo_path: &str, sha: &str) {
+    if entry.config.tag_type == "long" && entry.config.tag_template.is_none() {
+        return;
+    }
+    let prefix = sha.get(..entry.short_sha_length()).unwrap_or(sha);
+    match is_ambiguous(Path::new(app_repo_path), prefix) {
+        Ok(false) => {}
+        Ok(true) => warn!(
+            "{} is ambiguous in {}: tags of {} should use a longer SHA (gitops.operator.tag_type: short:N)",
+            prefix, &entry.config.app_repository, &entry.name
+        ),
+        Err(e) => warn!("Failed to check whether {} is ambiguous: {}", prefix, e),
+    }
+}
+
+/// The app commit `entry` was last synced to, as found in the app checkout
+/// at `app_repo_path`: the one its last-synced annotation names, else the one
+/// its manifest's tag (`manifest_tag`) was derived from. Tags rendered from a
+/// `tag_template` are searched for the SHA they hold.
+fn last_synced_commit(
+    entry: &Entry,
+    app_repo_path: &str,
+    manifest_tag: Option<&str>,
+) -> Option<String> {
+    entry
+        .annotations
+        .get(LAST_SYNCED_SHA_ANNOTATION)
+        .map(String::as_str)
+        .into_iter()
+        .chain(manifest_tag)
+        .map(|tag| {
+            tag.strip_suffix(entry.config.tag_suffix.as_str())
+                .unwrap_or(tag)
+        })
+        .flat_map(|tag| {
+            std::iter::once(tag).chain(
+                tag.split(|c: char| !c.is_ascii_hexdigit())
+                    .filter(move |part| part.len() >= 7 && *part != tag),
+            )
+        })
+        .filter(|sha| !sha.is_empty())
+        .find_map(|sha| get_source_commit(Path::new(app_repo_path), sha).ok())
+        .map(|commit| commit.sha)
+}
+
+/// The commit and name of the newest tag of `repo` (checked out at `path`)
+/// matching `pattern`.
+#[allow(clippy::type_complexity)]
+fn observed_tag(
+    repo: &str,
+    path: &Path,
+    pattern: &str,
+) -> Result<(String, Option<String>), (ErrorKind, String)> {
+    let parsed = GitTagPattern::parse(pattern).map_err(|e| {
+        (
+            ErrorKind::Other,
+            format!("Invalid gitops.operator.observe_tag_pattern: {:#}", e),
+        )
+    })?;
+    match newest_tag(path, &parsed) {
+        Ok(Some(tag)) => {
+            info!(
+                "Newest tag of {} matching {} is {}",
+                repo, pattern, &tag.name
+            );
+            Ok((tag.sha, Some(tag.name)))
+        }
+        Ok(None) => Err((
+            ErrorKind::Other,
+            format!("No tag of {} matches {}", repo, pattern),
+        )),
+        Err(e) => Err((ErrorKind::from_git(&e), format!("{:#}", e))),
     }
 }
 
 impl Config {
+    /// How the tag to promote is picked from the registry, for deployments
+    /// following registry tags (`semver` or `tag_pattern`) instead of commits.
+    pub fn tag_policy(&self) -> Option<anyhow::Result<TagPolicy>> {
+        if let Some(range) = &self.semver {
+            return Some(SemverPolicy::parse(range).map(TagPolicy::Semver));
+        }
+        self.tag_pattern
+            .as_ref()
+            .map(|pattern| PatternPolicy::parse(pattern, self.tag_sort).map(TagPolicy::Pattern))
+    }
+
+    /// Whether the app commit author `email` may be credited on manifest
+    /// commits: its domain must be in `author_domains` (exact, case-insensitive).
+    pub fn allows_author(&self, email: &str) -> bool {
+        email.rsplit_once('@').is_some_and(|(_, domain)| {
+            self.author_domains
+                .iter()
+                .any(|d| d.eq_ignore_ascii_case(domain))
+        })
+    }
+
     /// Parse a deployment's `gitops.operator.*` annotations into a `Config`.
     ///
     /// Returns `None` when any *required* annotation is missing (the deployment
@@ -618,29 +3795,89 @@ impl Config {
             .get("gitops.operator.manifest_repository")?
             .to_string();
         let image_name = annotations.get("gitops.operator.image_name")?.to_string();
-        let deployment_path = annotations
+        let mut image_names = vec![image_name.clone()];
+        for extra in annotations
+            .get("gitops.operator.image_names")
+            .map(String::as_str)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            if !image_names.iter().any(|name| name == extra) {
+                image_names.push(extra.to_string());
+            }
+        }
+        let deployment_paths: Vec<String> = annotations
             .get("gitops.operator.deployment_path")?
-            .to_string();
-        let ssh_key_name = annotations.get("gitops.operator.ssh_key_name")?.to_string();
-        let ssh_key_namespace = annotations
-            .get("gitops.operator.ssh_key_namespace")?
-            .to_string();
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(String::from)
+            .collect();
+        let deployment_path = deployment_paths.first()?.clone();
+        // Repositories over HTTPS (CodeCommit included) need no SSH key.
+        let over_https = |url: &str| {
+            url.starts_with("https://") || url.starts_with("http://") || is_codecommit_url(url)
+        };
+        let manifest_ssh_key = annotations.contains_key("gitops.operator.manifest_ssh_key_name");
+        let ssh_key_optional =
+            over_https(&app_repository) && (manifest_ssh_key || over_https(&manifest_repository));
+        let ssh_key_annotation = |key: &str| match annotations.get(key) {
+            Some(value) => Some(value.to_string()),
+            None if ssh_key_optional => Some(String::new()),
+            None => None,
+        };
+        let ssh_key_name = ssh_key_annotation("gitops.operator.ssh_key_name")?;
+        let ssh_key_namespace = ssh_key_annotation("gitops.operator.ssh_key_namespace")?;
 
         let observe_branch = annotations
             .get("gitops.operator.observe_branch")
             .map(String::as_str)
             .unwrap_or("master")
             .to_string();
+        let manifest_branch = annotations
+            .get("gitops.operator.manifest_branch")
+            .map(|branch| branch.trim())
+            .filter(|branch| !branch.is_empty())
+            .map(String::from)
+            .unwrap_or_else(|| observe_branch.clone());
         let tag_type = match annotations
             .get("gitops.operator.tag_type")
-            .map(String::as_str)
+            .map(|tag_type| tag_type.trim())
         {
-            Some("short") => "short",
+            Some(tag_type) if tag_type != "long" && sha_length(tag_type).is_some() => tag_type,
             _ => "long",
         }
         .to_string();
 
         let optional = |key: &str| annotations.get(key).map(String::to_string);
+        let list = |key: &str| -> Vec<String> {
+            annotations
+                .get(key)
+                .map(String::as_str)
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(String::from)
+                .collect()
+        };
+
+        let vars = annotations
+            .get("gitops.operator.vars")
+            .map(|raw| parse_vars(raw))
+            .unwrap_or_default();
+
+        let forge = match annotations.get("gitops.operator.forge").map(|s| s.parse()) {
+            Some(Ok(forge)) => forge,
+            Some(Err(e)) => {
+                let forge = Forge::detect(&manifest_repository);
+                warn!("{:#}, using {:?}", e, forge);
+                forge
+            }
+            None => Forge::detect(&manifest_repository),
+        };
 
         Some(Config {
             enabled,
@@ -648,45 +3885,313 @@ impl Config {
             app_repository,
             manifest_repository,
             image_name,
+            image_names,
+            container_name: optional("gitops.operator.container_name"),
             deployment_path,
+            deployment_paths,
+            values_path: match annotations
+                .get("gitops.operator.manifest_format")
+                .map(|format| format.trim())
+            {
+                Some("helm-values") => Some(
+                    optional("gitops.operator.values_path")
+                        .unwrap_or_else(|| DEFAULT_VALUES_PATH.to_string()),
+                ),
+                _ => None,
+            },
+            patch_expression: optional("gitops.operator.patch_expression"),
+            tag_suffix: annotations
+                .get("gitops.operator.tag_suffix")
+                .cloned()
+                .or_else(|| std::env::var("GITOPS_TAG_SUFFIX").ok())
+                .map(|suffix| parse_tag_suffix(&suffix))
+                .unwrap_or_default(),
             observe_branch,
+            observe_tag_pattern: optional("gitops.operator.observe_tag_pattern"),
+            manifest_branch,
+            clone_strategy: match annotations
+                .get("gitops.operator.clone_strategy")
+                .map(|s| s.parse())
+            {
+                Some(Ok(strategy)) => strategy,
+                Some(Err(e)) => {
+                    let strategy = CloneStrategy::from_env();
+                    warn!("{:#}, using {:?}", e, strategy);
+                    strategy
+                }
+                None => CloneStrategy::from_env(),
+            },
             tag_type,
+            tag_template: optional("gitops.operator.tag_template"),
+            tag_source: match annotations
+                .get("gitops.operator.tag_source")
+                .map(|s| s.parse())
+            {
+                Some(Ok(source)) => source,
+                Some(Err(e)) => {
+                    warn!("{:#}, using the commit SHA", e);
+                    TagSource::default()
+                }
+                None => TagSource::default(),
+            },
+            revision_label: optional("gitops.operator.revision_label")
+                .map(|label| label.trim().to_string())
+                .filter(|label| !label.is_empty())
+                .unwrap_or_else(|| REVISION_LABEL.to_string()),
+            semver: optional("gitops.operator.semver"),
+            tag_pattern: optional("gitops.operator.tag_pattern"),
+            tag_sort: match annotations
+                .get("gitops.operator.tag_sort")
+                .map(|s| s.parse())
+            {
+                Some(Ok(sort)) => sort,
+                Some(Err(e)) => {
+                    warn!("{:#}, sorting alphabetically", e);
+                    TagSort::default()
+                }
+                None => TagSort::default(),
+            },
+            watch_paths: list("gitops.operator.watch_paths"),
+            policy: optional("gitops.operator.policy"),
+            require_approval: annotations
+                .get("gitops.operator.require_approval")
+                .is_some_and(|value| value.trim() == "true"),
+            freeze_windows: optional("gitops.operator.freeze_windows"),
+            paused: annotations
+                .get(PAUSED_ANNOTATION)
+                .is_some_and(|value| value.trim() == "true"),
+            validate_manifests: match annotations.get("gitops.operator.validate_manifests") {
+                Some(value) => value.trim() == "true",
+                None => validate_manifests_default(),
+            },
+            verify_rollout: annotations
+                .get("gitops.operator.verify_rollout")
+                .is_some_and(|value| value.trim() == "true"),
+            auto_revert: annotations
+                .get("gitops.operator.auto_revert")
+                .is_some_and(|value| value.trim() == "true"),
+            strategy: match annotations
+                .get("gitops.operator.strategy")
+                .map(|s| s.parse())
+            {
+                Some(Ok(strategy)) => strategy,
+                Some(Err(e)) => {
+                    warn!("{:#}, pushing directly", e);
+                    Strategy::default()
+                }
+                None => Strategy::default(),
+            },
+            force_push: annotations
+                .get("gitops.operator.force_push")
+                .is_some_and(|value| value.trim() == "true"),
+            commit_message: optional("gitops.operator.commit_message").or_else(|| {
+                std::env::var("GITOPS_COMMIT_MESSAGE")
+                    .ok()
+                    .filter(|template| !template.trim().is_empty())
+            }),
+            pr_title: optional("gitops.operator.pr_title"),
+            pr_body: optional("gitops.operator.pr_body"),
+            pr_labels: list("gitops.operator.pr_labels"),
+            pr_assignees: list("gitops.operator.pr_assignees"),
+            forge,
+            forge_token_secret_name: optional("gitops.operator.forge_token_secret_name"),
+            forge_token_secret_namespace: optional("gitops.operator.forge_token_secret_namespace"),
             ssh_key_name,
             ssh_key_namespace,
+            manifest_ssh_key_name: optional("gitops.operator.manifest_ssh_key_name"),
+            manifest_ssh_key_namespace: optional("gitops.operator.manifest_ssh_key_namespace"),
+            known_hosts_secret_name: optional("gitops.operator.known_hosts_secret_name"),
+            known_hosts_secret_namespace: optional("gitops.operator.known_hosts_secret_namespace"),
+            https_token_secret_name: optional("gitops.operator.https_token_secret_name"),
+            https_token_secret_namespace: optional("gitops.operator.https_token_secret_namespace"),
             notifications_secret_name: optional("gitops.operator.notifications_secret_name"),
             notifications_secret_namespace: optional(
                 "gitops.operator.notifications_secret_namespace",
             ),
+            notifications_provider: annotations
+                .get("gitops.operator.notifications_provider")
+                .and_then(|provider| match provider.parse() {
+                    Ok(provider) => Some(provider),
+                    Err(e) => {
+                        warn!("{:#}, detecting it from the webhook URL", e);
+                        None
+                    }
+                }),
             registry_url: optional("gitops.operator.registry_secret_url"),
+            fallback_registry_urls: list("gitops.operator.fallback_registry_urls"),
             registry_secret_name: optional("gitops.operator.registry_secret_name"),
             registry_secret_namespace: optional("gitops.operator.registry_secret_namespace"),
             github_token_secret_name: optional("gitops.operator.github_token_secret_name"),
             github_token_secret_namespace: optional(
                 "gitops.operator.github_token_secret_namespace",
             ),
+            github_app_secret_name: optional("gitops.operator.github_app_secret_name"),
+            github_app_secret_namespace: optional("gitops.operator.github_app_secret_namespace"),
+            vars,
+            notification_template: optional("gitops.operator.notification_template"),
+            author_domains: annotations
+                .get("gitops.operator.author_domains")
+                .map(|raw| parse_domains(raw))
+                .unwrap_or_default(),
+            image_host: match annotations
+                .get("gitops.operator.image_host")
+                .map(String::as_str)
+            {
+                Some("rewrite") => ImageHost::Rewrite,
+                _ => ImageHost::Preserve,
+            },
         })
     }
 }
 
-/// Pick the container the operator should track in a (possibly multi-container)
-/// pod: the first container whose image reference contains `image_name` (the
-/// same match the patcher uses), falling back to the first container. Returns
-/// its image reference without the tag, and the tag (`latest` if untagged).
-fn select_container(containers: &[Container], image_name: &str) -> Option<(String, String)> {
-    let target = containers
-        .iter()
-        .find(|c| {
-            c.image
-                .as_deref()
-                .is_some_and(|img| img.contains(image_name))
+/// Whether `tag` is a valid OCI image tag (`[A-Za-z0-9_][A-Za-z0-9_.-]{0,127}`),
+/// which also rules out anything that could escape the `image:tag` reference.
+fn is_valid_tag(tag: &str) -> bool {
+    let mut chars = tag.chars();
+    tag.len() <= 128
+        && chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+/// Validate a tag suffix: appended to a SHA it must still be a valid tag. An
+/// invalid suffix is logged and ignored, falling back to the bare SHA.
+fn parse_tag_suffix(raw: &str) -> String {
+    let suffix = raw.trim();
+    if is_valid_tag(&format!("0{}", suffix)) {
+        suffix.to_string()
+    } else {
+        warn!("Ignoring invalid tag suffix '{}'", suffix);
+        String::new()
+    }
+}
+
+/// The manifest commit body crediting the app commit `sha` it ships, e.g.
+/// `Promotes org/app@abc1234: fix login bug` followed by the commit's author
+/// and web page, so `git blame` in the manifests leads back to the change.
+fn source_commit_body(entry: &Entry, app_repo_path: &str, sha: &str) -> Option<String> {
+    let commit = match get_source_commit(Path::new(app_repo_path), sha) {
+        Ok(commit) => commit,
+        Err(e) => {
+            warn!("Failed to read app commit {}: {:?}", sha, e);
+            return None;
+        }
+    };
+    let repository = &entry.config.app_repository;
+    let name = split_repository_url(repository)
+        .map(|(_, path)| path)
+        .unwrap_or_else(|| repository.clone());
+    let short = &commit.sha[..commit.sha.len().min(7)];
+
+    let mut body = format!("Promotes {}@{}: {}\n", name, short, commit.summary);
+    body.push_str(&format!(
+        "\nAuthor: {} <{}>",
+        commit.author.name, commit.author.email
+    ));
+    if let Some(url) = Forge::detect(repository).commit_url(repository, &commit.sha) {
+        body.push_str(&format!("\nCommit: {}", url));
+    }
+    Some(body)
+}
+
+/// The newest tag of the entry's image that `policy` selects, as listed by
+/// the registry.
+async fn newest_registry_tag(
+    entry: &Entry,
+    checker: Option<&dyn ImageChecker>,
+    policy: anyhow::Result<TagPolicy>,
+) -> anyhow::Result<String> {
+    let policy = policy?;
+    let checker = checker.context("Registry credentials are unavailable")?;
+    let tags = checker.list_tags(&entry.config.image_name).await?;
+
+    policy
+        .newest(tags.iter().map(String::as_str))
+        .map(str::to_string)
+        .with_context(|| {
+            format!(
+                "None of its {} tags matches {}",
+                tags.len(),
+                policy.describe()
+            )
+        })
+}
+
+/// The tag of the entry's image whose revision label names the app commit
+/// `sha`, among the [`revision_lookup_limit`] most recent ones the registry
+/// lists (in [`natural_cmp`] order, as build numbers and dates sort).
+async fn tag_for_revision(
+    entry: &Entry,
+    checker: Option<&dyn ImageChecker>,
+    sha: &str,
+) -> anyhow::Result<String> {
+    let checker = checker.context("Registry credentials are unavailable")?;
+    let image = &entry.config.image_name;
+    let label = &entry.config.revision_label;
+    let mut tags = checker.list_tags(image).await?;
+    tags.sort_by(|a, b| natural_cmp(b, a));
+    tags.truncate(revision_lookup_limit());
+
+    let count = tags.len();
+    let mut labelled = stream::iter(tags)
+        .map(|tag| async move {
+            let labels = checker.image_labels(image, &tag).await;
+            (tag, labels)
         })
-        .or_else(|| containers.first())?;
+        .buffered(registry_concurrency());
+    while let Some((tag, labels)) = labelled.next().await {
+        match labels {
+            Ok(labels) if labels.get(label).is_some_and(|rev| same_revision(rev, sha)) => {
+                info!("{}:{} was built from {}", image, tag, sha);
+                return Ok(tag);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to read the labels of {}:{}: {:#}", image, tag, e),
+        }
+    }
+    anyhow::bail!(
+        "none of its {} most recent tags has {}={}",
+        count,
+        label,
+        sha
+    )
+}
+
+/// Parse the `gitops.operator.vars` annotation: a flat JSON object whose values
+/// are strings (other scalars are stringified). Invalid JSON is logged and
+/// ignored rather than disabling the whole deployment.
+fn parse_vars(raw: &str) -> BTreeMap<String, String> {
+    match serde_json::from_str::<BTreeMap<String, serde_json::Value>>(raw) {
+        Ok(map) => map
+            .into_iter()
+            .map(|(k, v)| match v {
+                serde_json::Value::String(s) => (k, s),
+                other => (k, other.to_string()),
+            })
+            .collect(),
+        Err(e) => {
+            warn!("Ignoring invalid gitops.operator.vars annotation: {}", e);
+            BTreeMap::new()
+        }
+    }
+}
+
+/// Parse a comma-separated list of email domains, lowercased, ignoring blanks
+/// and a leading `@`.
+fn parse_domains(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|d| d.trim().trim_start_matches('@').to_ascii_lowercase())
+        .filter(|d| !d.is_empty())
+        .collect()
+}
 
-    let img = target.image.as_ref()?;
-    match *img.splitn(2, ':').collect::<Vec<_>>().as_slice() {
-        [c, v] => Some((c.to_owned(), v.to_owned())),
-        [c] => Some((c.to_owned(), "latest".to_owned())),
-        _ => None,
+/// Split an image reference into the reference without its tag and the tag
+/// (`latest` if untagged). A `:` before the last `/` is a registry port.
+fn split_image(image: &str) -> (String, String) {
+    match image.rsplit_once(':') {
+        Some((repo, tag)) if !tag.contains('/') => (repo.to_owned(), tag.to_owned()),
+        _ => (image.to_owned(), "latest".to_owned()),
     }
 }
 
@@ -694,32 +4199,256 @@ impl Entry {
     pub fn new(d: &Deployment) -> Option<Entry> {
         let name = d.name_any();
         let namespace = d.namespace()?;
+        if !namespace_policy().allows(&namespace) {
+            debug!("Skipping {}/{}: namespace not allowed", &namespace, &name);
+            return None;
+        }
+        if operator_identity().is_self(d) {
+            debug!(
+                "Skipping {}/{}: the operator's own Deployment",
+                &namespace, &name
+            );
+            return None;
+        }
         let annotations = d.metadata.annotations.as_ref()?;
 
         let config = Config::from_annotations(annotations, &namespace)?;
 
-        // Select the tracked container by image_name so multi-container pods are
-        // handled correctly, then derive its current image reference and tag.
+        // Every container is listed, marking the ones the patcher targets. The
+        // primary container is the first targeted one, falling back to the
+        // first container.
+        let target = ContainerTarget {
+            image_names: config.image_names.clone(),
+            container_name: config.container_name.clone(),
+            key_path: None,
+        };
         let tpl = d.spec.as_ref()?.template.spec.as_ref()?;
-        let (container, version) = select_container(&tpl.containers, &config.image_name)?;
+        let containers: Vec<ContainerImage> = tpl
+            .containers
+            .iter()
+            .filter_map(|c| {
+                let image = c.image.as_deref()?;
+                let (reference, tag) = split_image(image);
+                Some(ContainerImage {
+                    name: c.name.clone(),
+                    image: reference,
+                    tag,
+                    tracked: target.image_for(c).is_some(),
+                })
+            })
+            .collect();
+        let primary = tpl
+            .containers
+            .iter()
+            .find(|c| target.image_for(c).is_some())
+            .or_else(|| tpl.containers.first())?
+            .image
+            .as_deref()?;
+        let (container, version) = split_image(primary);
 
         info!("Processing: {}/{}", &namespace, &name);
 
+        let paused = pauses()
+            .get(&format!("{}/{}", &namespace, &name))
+            .or_else(|| config.paused.then(Pause::annotation));
         Some(Entry {
             name,
             namespace,
+            resource_version: d.metadata.resource_version.clone(),
+            generation: d.metadata.generation,
             annotations: annotations.clone(),
             container,
             version,
+            containers,
             config,
+            paused,
         })
     }
 
+    /// The manifest containers patched for this entry, with image names
+    /// qualified for `registry_url`.
+    pub fn container_target(&self, registry_url: &str) -> ContainerTarget {
+        ContainerTarget {
+            image_names: self
+                .config
+                .image_names
+                .iter()
+                .map(|name| build_container_image(registry_url, name))
+                .collect(),
+            container_name: self.config.container_name.clone(),
+            key_path: self
+                .config
+                .patch_expression
+                .clone()
+                .or_else(|| self.config.values_path.clone()),
+        }
+    }
+
+    /// The image tag published for the app commit `sha`.
+    pub fn image_tag(&self, sha: &str) -> String {
+        format!("{}{}", sha, &self.config.tag_suffix)
+    }
+
+    /// The image tag published for the app commit `sha` (full), tagged
+    /// `git_tag` when the deployment observes tags: `tag_template` rendered
+    /// with `sha`, `sha_short` and `tag` on top of [`Entry::template_vars`],
+    /// else the git tag, else the SHA in its `tag_type` form. `sha_short` is
+    /// as long as a `short:N` tag type says (7 otherwise). `tag_suffix` is
+    /// appended either way.
+    pub fn tag_for(&self, sha: &str, git_tag: Option<&str>) -> String {
+        let sha_short = sha.get(..self.short_sha_length()).unwrap_or(sha);
+        let tag = match (&self.config.tag_template, git_tag) {
+            (Some(template), _) => {
+                let mut vars = self.template_vars();
+                vars.insert("sha".to_string(), sha.to_string());
+                vars.insert("sha_short".to_string(), sha_short.to_string());
+                vars.insert("tag".to_string(), git_tag.unwrap_or_default().to_string());
+                render(template, &vars)
+            }
+            (None, Some(tag)) => tag.to_string(),
+            (None, None) => match self.config.tag_type.as_str() {
+                "long" => sha.to_string(),
+                _ => sha_short.to_string(),
+            },
+        };
+        self.image_tag(&tag)
+    }
+
+    /// How many characters of the commit SHA a short tag has.
+    pub fn short_sha_length(&self) -> usize {
+        match sha_length(&self.config.tag_type) {
+            Some(length) if self.config.tag_type != "long" => length,
+            _ => 7,
+        }
+    }
+
+    /// `namespace/name`, identifying the deployment across operator state.
+    pub fn key(&self) -> String {
+        format!("{}/{}", &self.namespace, &self.name)
+    }
+
+    /// When the operator suspended this deployment after repeated push
+    /// failures, if it is suspended.
+    pub fn suspended_at(&self) -> Option<&str> {
+        self.annotations
+            .get(SUSPENDED_AT_ANNOTATION)
+            .map(String::as_str)
+    }
+
+    /// Absolute paths of the manifests patched for this entry inside the
+    /// checkout at `manifest_repo_path`, primary manifest first. A configured
+    /// path that is a directory expands to the workload manifests under it
+    /// with a container in `target` (see [`find_manifests`]).
+    pub fn manifest_files(
+        &self,
+        manifest_repo_path: &str,
+        target: &ContainerTarget,
+    ) -> Vec<String> {
+        let mut files = vec![];
+        for path in &self.config.deployment_paths {
+            let path = format!("{}/{}", manifest_repo_path.trim_end_matches('/'), path);
+            if Path::new(&path).is_dir() {
+                match find_manifests(&path, target) {
+                    Ok(found) => files.extend(found),
+                    Err(e) => warn!("Failed to scan {}: {:#}", path, e),
+                }
+            } else {
+                files.push(path);
+            }
+        }
+        files.dedup();
+        files
+    }
+
+    /// Local checkout of the app repository for this entry, named after its
+    /// namespace, name and branch plus a hash of the repository URL, so
+    /// deployments of the same name in other namespaces or moved to another
+    /// repository get their own.
+    pub fn app_repo_path(&self) -> String {
+        let dir = hashed_name(
+            &format!(
+                "app-{}-{}-{}",
+                &self.namespace, &self.name, &self.config.observe_branch
+            ),
+            &self.config.app_repository,
+        );
+        format!("{}/", repo_cache_dir().join(dir).display())
+    }
+
+    /// Local checkout of the manifests repository for this entry, named as
+    /// [`Entry::app_repo_path`] is.
+    pub fn manifest_repo_path(&self) -> String {
+        let dir = hashed_name(
+            &format!(
+                "manifest-{}-{}-{}",
+                &self.namespace, &self.name, &self.config.manifest_branch
+            ),
+            &self.config.manifest_repository,
+        );
+        format!("{}/", repo_cache_dir().join(dir).display())
+    }
+
+    /// Commit the changes in `manifest_repo_path` with `git` and push them to
+    /// `manifest_branch`, forcing the push when the entry opted into it.
+    fn commit_changes(
+        &self,
+        git: &dyn GitProvider,
+        manifest_repo_path: &str,
+        commit_message: &str,
+        credentials: &GitCredentials,
+        author: Option<&CommitAuthor>,
+    ) -> Result<(), git2::Error> {
+        let path = Path::new(manifest_repo_path);
+        if git.commit(path, commit_message, author)? {
+            git.push(
+                path,
+                &self.config.manifest_branch,
+                credentials,
+                self.config.force_push,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Variables available to commit and notification templates: the custom
+    /// `gitops.operator.vars` plus the built-ins `app`, `namespace`, `branch`,
+    /// `manifest_branch`, and `image`. Built-ins win on conflicts so they
+    /// can't be spoofed.
+    pub fn template_vars(&self) -> BTreeMap<String, String> {
+        let mut vars = self.config.vars.clone();
+        vars.insert("app".to_string(), self.name.clone());
+        vars.insert("namespace".to_string(), self.namespace.clone());
+        vars.insert("branch".to_string(), self.config.observe_branch.clone());
+        vars.insert(
+            "manifest_branch".to_string(),
+            self.config.manifest_branch.clone(),
+        );
+        vars.insert("image".to_string(), self.config.image_name.clone());
+        vars
+    }
+
     /// Process deployment using the production dependencies
-    #[tracing::instrument(name = "process_deployment", skip(self), fields())]
-    pub async fn process_deployment(self) -> ReconcileResult {
-        let processor = DeploymentProcessor::production();
-        processor.process(&self).await
+    pub async fn process_deployment(self, force: bool) -> ReconcileResult {
+        let processor = DeploymentProcessor::production().with_force(force);
+        self.process_traced(&processor).await
+    }
+
+    /// Process the deployment with `processor`, recording the outcome on the
+    /// span the trace sampler reads.
+    #[tracing::instrument(
+        name = "process_deployment",
+        skip(self, processor),
+        fields(reconcile.action = tracing::field::Empty, reconcile.status = tracing::field::Empty)
+    )]
+    async fn process_traced(&self, processor: &DeploymentProcessor) -> ReconcileResult {
+        let result = processor.process(self).await;
+
+        // Read by the trace sampler (see telemetry::SamplingRules).
+        let span = tracing::Span::current();
+        span.record(RECONCILE_ACTION_ATTRIBUTE, enum_name(&result.action));
+        span.record(RECONCILE_STATUS_ATTRIBUTE, enum_name(&result.status));
+
+        result
     }
 
     /// Process deployment with a custom processor (for testing)
@@ -730,11 +4459,39 @@ impl Entry {
         processor.process(self).await
     }
 
+    /// Look up the tracked entry for a single deployment in the reflector store.
+    pub fn find(store: &Cache, namespace: &str, name: &str) -> Option<Entry> {
+        store
+            .state()
+            .iter()
+            .find(|d| d.name_any() == name && d.namespace().as_deref() == Some(namespace))
+            .and_then(|d| Entry::new(d))
+    }
+
     pub async fn reconcile(AxumState(store): AxumState<Cache>) -> Json<Vec<ReconcileResult>> {
         tracing::info!("Starting reconciliation");
 
         let data: Vec<_> = store.state().iter().filter_map(|d| Entry::new(d)).collect();
 
+        Json(Entry::reconcile_entries(data, false).await)
+    }
+
+    /// Reconcile an explicit set of entries concurrently. Disabled entries are
+    /// reported as skipped. Used by `/reconcile` for every tracked deployment and
+    /// by the webhook endpoints for the subset affected by an event. With
+    /// `force`, deployments that haven't changed are reconciled all the same.
+    pub async fn reconcile_entries(data: Vec<Entry>, force: bool) -> Vec<ReconcileResult> {
+        let processor = DeploymentProcessor::production().with_force(force);
+        Entry::reconcile_entries_with(data, &processor).await
+    }
+
+    /// Like [`Entry::reconcile_entries`], with `processor` doing the work.
+    /// Entries tracking the same app repository and branch share one fetch.
+    pub async fn reconcile_entries_with(
+        data: Vec<Entry>,
+        processor: &DeploymentProcessor,
+    ) -> Vec<ReconcileResult> {
+        let processor = &processor.clone().with_shared_fetches();
         let mut handles: Vec<_> = vec![];
         let mut skipped: Vec<ReconcileResult> = vec![];
 
@@ -751,13 +4508,29 @@ impl Entry {
                 continue;
             }
 
-            handles.push(entry.process_deployment());
+            // Another trigger is already working on this deployment; don't race it.
+            let Some(guard) = entry_locks().try_acquire(&entry.key()) else {
+                warn!("Reconcile already in progress for: {}", entry.key());
+                skipped.push(ReconcileResult {
+                    error: Some(ErrorKind::Conflict),
+                    ..ReconcileResult::skipped(
+                        &entry,
+                        format!("Deployment {} is already being reconciled", &entry.name),
+                    )
+                });
+                continue;
+            };
+
+            handles.push(async move {
+                let _guard = guard;
+                entry.process_traced(processor).await
+            });
         }
 
         let mut results = future::join_all(handles).await;
         results.extend(skipped);
 
-        Json(results)
+        results
     }
 }
 
@@ -780,11 +4553,15 @@ pub fn status_report(entries: &[Entry]) -> String {
     out.push_str(&header);
 
     for entry in entries {
+        let enabled = match &entry.paused {
+            Some(_) => "paused".to_string(),
+            None => entry.config.enabled.to_string(),
+        };
         out.push_str(&format!(
             "{:<12} {:<24} {:<8} {:<8} {}:{}\n",
             entry.namespace,
             entry.name,
-            entry.config.enabled,
+            enabled,
             entry.config.observe_branch,
             entry.container,
             entry.version,
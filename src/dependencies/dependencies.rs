@@ -0,0 +1,109 @@
+use crate::files::{ContainerTarget, ImageHost, images_need_patching, patch_images};
+use crate::git::{GitCredentials, clone_repo, commit_changes};
+use anyhow::{Context, Result};
+use std::fs::remove_dir_all;
+use tracing::{info, warn};
+
+/// When the `source` deployment (`namespace/name`) is promoted to a new tag,
+/// pin the same tag in another service's manifests, e.g. a shared base image
+/// or sidecar version. Rules are operator-wide, loaded from the YAML list at
+/// `GITOPS_DEPENDENCY_RULES_PATH`.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct DependencyRule {
+    pub source: String,
+    /// Manifests repository of the dependent service.
+    pub manifest_repository: String,
+    #[serde(default = "default_branch")]
+    pub branch: String,
+    /// Manifests to update inside that repository, committed together.
+    pub paths: Vec<String>,
+    /// Image to pin in those manifests (default: the source's `image_name`).
+    #[serde(default)]
+    pub image: Option<String>,
+    /// Only update the container with this name.
+    #[serde(default)]
+    pub container_name: Option<String>,
+}
+
+fn default_branch() -> String {
+    "master".to_string()
+}
+
+impl DependencyRule {
+    /// The manifest containers this rule updates, given the source's image.
+    pub fn target(&self, source_image: &str) -> ContainerTarget {
+        ContainerTarget {
+            image_names: vec![self.image.as_deref().unwrap_or(source_image).to_string()],
+            container_name: self.container_name.clone(),
+            key_path: None,
+        }
+    }
+}
+
+/// Parse a YAML list of rules.
+pub fn parse_rules(yaml: &str) -> Result<Vec<DependencyRule>> {
+    serde_yaml::from_str(yaml).context("Invalid dependency rules")
+}
+
+/// Rules from the file at `GITOPS_DEPENDENCY_RULES_PATH`, if set. A missing or
+/// invalid file is logged and disables propagation rather than the operator.
+pub fn dependency_rules_from_env() -> Vec<DependencyRule> {
+    let Ok(path) = std::env::var("GITOPS_DEPENDENCY_RULES_PATH") else {
+        return vec![];
+    };
+
+    match std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path))
+        .and_then(|yaml| parse_rules(&yaml))
+    {
+        Ok(rules) => {
+            info!("Loaded {} dependency rule(s) from {}", rules.len(), path);
+            rules
+        }
+        Err(e) => {
+            warn!("{:#}; dependency propagation disabled", e);
+            vec![]
+        }
+    }
+}
+
+/// Update the rule's repository checked out at `checkout`: pin `target` to
+/// `sha` in every path that is not there yet and push it all as one commit.
+/// On any error the checkout is discarded so nothing partial is pushed.
+/// Returns false when every path was already pinned.
+pub fn apply_rule(
+    rule: &DependencyRule,
+    checkout: &str,
+    target: &ContainerTarget,
+    sha: &str,
+    credentials: &GitCredentials,
+    commit_message: &str,
+) -> Result<bool> {
+    clone_repo(
+        &rule.manifest_repository,
+        checkout,
+        &rule.branch,
+        credentials,
+    );
+
+    let result = (|| {
+        let mut patched = false;
+        for path in &rule.paths {
+            let path = format!("{}/{}", checkout, path);
+            if images_need_patching(&path, target, sha)? {
+                patch_images(&path, target, sha, ImageHost::Preserve)?;
+                patched = true;
+            }
+        }
+
+        if patched {
+            commit_changes(checkout, &rule.branch, commit_message, credentials, None)?;
+        }
+        Ok(patched)
+    })();
+
+    if result.is_err() {
+        let _ = remove_dir_all(checkout);
+    }
+    result
+}
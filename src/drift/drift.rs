@@ -0,0 +1,227 @@
+use crate::configuration::ContainerImage;
+use crate::history::now_rfc3339;
+use crate::metrics::{DEPLOYMENTS_DRIFTED, IMAGE_DIGEST_MISMATCHES};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_DRIFT_GRACE: Duration = Duration::from_secs(600);
+
+/// How long drift must last before it is notified, from
+/// `GITOPS_DRIFT_GRACE_SECS` (default 600). Right after a promotion the
+/// cluster lags the manifest until the CD tool syncs it.
+pub fn drift_grace() -> Duration {
+    std::env::var("GITOPS_DRIFT_GRACE_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_DRIFT_GRACE)
+}
+
+/// Whether lasting drift is notified, from `GITOPS_DRIFT_NOTIFICATIONS`
+/// (default false).
+pub fn drift_notifications() -> bool {
+    std::env::var("GITOPS_DRIFT_NOTIFICATIONS").is_ok_and(|value| value.trim() == "true")
+}
+
+/// Whether the digests the pods run are compared with the one the registry
+/// serves for the manifest's tag, from `GITOPS_VERIFY_DIGESTS` (default
+/// false). Costs a pod list and a registry request per deployment and pass.
+pub fn verify_digests() -> bool {
+    std::env::var("GITOPS_VERIFY_DIGESTS").is_ok_and(|value| value.trim() == "true")
+}
+
+/// A container of a running pod, as its status reports it.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct RunningImage {
+    pub pod: String,
+    pub container: String,
+    /// The image reference the pod was created with.
+    pub image: String,
+    /// The runtime's `imageID`, naming the digest it actually pulled.
+    pub image_id: String,
+}
+
+/// The digest in a container status `imageID`: `docker-pullable://repo@sha256:...`,
+/// `repo@sha256:...` or a bare `sha256:...`. `None` for runtimes reporting
+/// something else (e.g. a local image ID).
+pub fn digest_of(image_id: &str) -> Option<&str> {
+    let digest = image_id
+        .rsplit_once('@')
+        .map_or(image_id, |(_, digest)| digest);
+    let (algorithm, hex) = digest.split_once(':')?;
+    (matches!(algorithm, "sha256" | "sha512")
+        && !hex.is_empty()
+        && hex.chars().all(|c| c.is_ascii_hexdigit()))
+    .then_some(digest)
+}
+
+/// Pods running `tag` in `container` with a different digest than the
+/// registry serves for it: the tag was pushed again after they pulled it.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct DigestDrift {
+    pub container: String,
+    pub image: String,
+    pub tag: String,
+    pub manifest_digest: String,
+    pub running_digest: String,
+    pub pods: Vec<String>,
+}
+
+/// The pods whose `container` runs `tag` from a digest other than
+/// `manifest_digest`, grouped by digest. Pods on another tag are left to
+/// [`detect`].
+pub fn detect_digests(
+    running: &[RunningImage],
+    container: &ContainerImage,
+    manifest_digest: &str,
+) -> Vec<DigestDrift> {
+    let mut pods: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for image in running.iter().filter(|r| r.container == container.name) {
+        let same_tag = image
+            .image
+            .split('@')
+            .next()
+            .and_then(|reference| reference.rsplit_once(':'))
+            .is_some_and(|(_, tag)| tag == container.tag);
+        match digest_of(&image.image_id) {
+            Some(digest) if same_tag && digest != manifest_digest => {
+                pods.entry(digest).or_default().push(image.pod.clone())
+            }
+            _ => {}
+        }
+    }
+    pods.into_iter()
+        .map(|(digest, pods)| DigestDrift {
+            container: container.name.clone(),
+            image: container.image.clone(),
+            tag: container.tag.clone(),
+            manifest_digest: manifest_digest.to_string(),
+            running_digest: digest.to_string(),
+            pods,
+        })
+        .collect()
+}
+
+/// A container running a different tag than its manifest declares.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct ContainerDrift {
+    pub container: String,
+    pub image: String,
+    pub live_tag: String,
+    pub manifest_tag: String,
+}
+
+/// The patched containers of a Deployment whose live tag isn't
+/// `manifest_tag`.
+pub fn detect(containers: &[ContainerImage], manifest_tag: &str) -> Vec<ContainerDrift> {
+    containers
+        .iter()
+        .filter(|c| c.tracked && c.tag != manifest_tag)
+        .map(|c| ContainerDrift {
+            container: c.name.clone(),
+            image: c.image.clone(),
+            live_tag: c.tag.clone(),
+            manifest_tag: manifest_tag.to_string(),
+        })
+        .collect()
+}
+
+/// A deployment drifting from its manifest.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct DriftReport {
+    pub namespace: String,
+    pub deployment: String,
+    pub containers: Vec<ContainerDrift>,
+    /// Pods running the manifest's tag from a digest the registry no longer
+    /// serves for it.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub digests: Vec<DigestDrift>,
+    /// When the drift was first seen (RFC 3339, UTC).
+    pub since: String,
+    #[serde(skip)]
+    first_seen: Instant,
+    #[serde(skip)]
+    notified: bool,
+}
+
+/// What a new observation changed.
+#[derive(Debug, PartialEq)]
+pub enum DriftUpdate {
+    /// Drift has lasted past the grace period and was not notified yet.
+    Lasting(DriftReport),
+    /// Notified drift is gone.
+    Resolved(DriftReport),
+    /// Nothing to tell.
+    Unchanged,
+}
+
+/// The deployments currently drifting, as of their last reconcile.
+pub struct DriftBoard {
+    grace: Duration,
+    reports: Mutex<BTreeMap<String, DriftReport>>,
+}
+
+impl DriftBoard {
+    pub fn new(grace: Duration) -> Self {
+        Self {
+            grace,
+            reports: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Record the drift found for `namespace/deployment` (none when
+    /// `containers` and `digests` are empty).
+    pub fn update(
+        &self,
+        namespace: &str,
+        deployment: &str,
+        containers: Vec<ContainerDrift>,
+        digests: Vec<DigestDrift>,
+    ) -> DriftUpdate {
+        let key = format!("{}/{}", namespace, deployment);
+        let mut reports = self.reports.lock().unwrap_or_else(|e| e.into_inner());
+        let update = if containers.is_empty() && digests.is_empty() {
+            match reports.remove(&key) {
+                Some(report) if report.notified => DriftUpdate::Resolved(report),
+                _ => DriftUpdate::Unchanged,
+            }
+        } else {
+            let report = reports.entry(key).or_insert_with(|| DriftReport {
+                namespace: namespace.to_string(),
+                deployment: deployment.to_string(),
+                containers: vec![],
+                digests: vec![],
+                since: now_rfc3339(),
+                first_seen: Instant::now(),
+                notified: false,
+            });
+            report.containers = containers;
+            report.digests = digests;
+            if !report.notified && report.first_seen.elapsed() >= self.grace {
+                report.notified = true;
+                DriftUpdate::Lasting(report.clone())
+            } else {
+                DriftUpdate::Unchanged
+            }
+        };
+        ::metrics::gauge!(DEPLOYMENTS_DRIFTED).set(reports.len() as f64);
+        ::metrics::gauge!(IMAGE_DIGEST_MISMATCHES)
+            .set(reports.values().map(|r| r.digests.len()).sum::<usize>() as f64);
+        update
+    }
+
+    /// Every drifting deployment, by namespace and name.
+    pub fn list(&self) -> Vec<DriftReport> {
+        let reports = self.reports.lock().unwrap_or_else(|e| e.into_inner());
+        reports.values().cloned().collect()
+    }
+}
+
+/// Process-wide drift board, filled in by the reconcile passes.
+pub fn drift_board() -> Arc<DriftBoard> {
+    static BOARD: OnceLock<Arc<DriftBoard>> = OnceLock::new();
+    BOARD
+        .get_or_init(|| Arc::new(DriftBoard::new(drift_grace())))
+        .clone()
+}
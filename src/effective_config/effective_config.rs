@@ -0,0 +1,271 @@
+use crate::admission::{ANNOTATION_PREFIX, AnnotationDefaults};
+use crate::configuration::{LAST_SYNCED_AT_ANNOTATION, LAST_SYNCED_SHA_ANNOTATION};
+use crate::suspension::{SUSPENDED_AT_ANNOTATION, SUSPENDED_REASON_ANNOTATION};
+use std::collections::BTreeMap;
+
+/// Annotations a deployment must set; there is no default to fall back to.
+const REQUIRED: &[&str] = &[
+    "enabled",
+    "app_repository",
+    "manifest_repository",
+    "image_name",
+    "deployment_path",
+    "ssh_key_name",
+    "ssh_key_namespace",
+];
+
+/// Every other annotation the operator reads, with its built-in default.
+const OPTIONAL: &[(&str, Option<&str>)] = &[
+    ("observe_branch", Some("master")),
+    ("observe_tag_pattern", None),
+    ("manifest_branch", None),
+    ("clone_strategy", Some("full")),
+    ("tag_type", Some("long")),
+    ("tag_template", None),
+    ("tag_source", Some("sha")),
+    ("revision_label", Some("org.opencontainers.image.revision")),
+    ("semver", None),
+    ("tag_pattern", None),
+    ("tag_sort", Some("alphabetical")),
+    ("watch_paths", None),
+    ("policy", None),
+    ("require_approval", Some("false")),
+    ("freeze_windows", None),
+    ("paused", Some("false")),
+    ("validate_manifests", Some("false")),
+    ("verify_rollout", Some("false")),
+    ("auto_revert", Some("false")),
+    ("strategy", Some("push")),
+    ("force_push", Some("false")),
+    ("commit_message", None),
+    ("pr_title", None),
+    ("pr_body", None),
+    ("pr_labels", None),
+    ("pr_assignees", None),
+    ("forge", None),
+    ("forge_token_secret_name", None),
+    ("forge_token_secret_namespace", Some("gitops-operator")),
+    ("manifest_ssh_key_name", None),
+    ("manifest_ssh_key_namespace", None),
+    ("known_hosts_secret_name", None),
+    ("known_hosts_secret_namespace", Some("gitops-operator")),
+    ("https_token_secret_name", None),
+    ("https_token_secret_namespace", Some("gitops-operator")),
+    ("tag_suffix", None),
+    ("notifications_secret_name", None),
+    ("notifications_secret_namespace", Some("gitops-operator")),
+    ("notifications_provider", None),
+    ("registry_secret_url", Some("https://index.docker.io/v1/")),
+    ("fallback_registry_urls", None),
+    ("registry_secret_name", Some("regcred")),
+    ("registry_secret_namespace", Some("gitops-operator")),
+    ("github_token_secret_name", None),
+    ("github_token_secret_namespace", Some("gitops-operator")),
+    ("github_app_secret_name", None),
+    ("github_app_secret_namespace", Some("gitops-operator")),
+    ("vars", None),
+    ("notification_template", None),
+    ("image_host", Some("preserve")),
+    ("author_domains", None),
+    ("image_names", None),
+    ("container_name", None),
+    ("manifest_format", None),
+    ("values_path", Some("image.tag")),
+    ("patch_expression", None),
+];
+
+/// Annotations the operator writes itself to record status.
+const STATUS: &[&str] = &[
+    LAST_SYNCED_SHA_ANNOTATION,
+    LAST_SYNCED_AT_ANNOTATION,
+    SUSPENDED_AT_ANNOTATION,
+    SUSPENDED_REASON_ANNOTATION,
+];
+
+/// Which layer a setting's effective value comes from.
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigSource {
+    /// Set on the deployment itself.
+    Annotation,
+    /// Set on the deployment to the value a namespace default (an admission
+    /// default rule) injects.
+    NamespaceDefault,
+    /// Not set; the operator's default applies.
+    OperatorDefault,
+    /// Not set, and there is no default.
+    Unset,
+}
+
+/// One `gitops.operator.*` setting of a deployment, with the value each layer
+/// gives it.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct EffectiveValue {
+    pub key: String,
+    pub value: Option<String>,
+    pub source: ConfigSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace_default: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operator_default: Option<String>,
+    /// Overrides, conflicts and other surprises worth a look.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+}
+
+/// The settings of a deployment and where each comes from.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct EffectiveConfig {
+    pub namespace: String,
+    pub name: String,
+    /// Whether every required annotation is set, so the operator tracks it.
+    pub tracked: bool,
+    pub values: Vec<EffectiveValue>,
+}
+
+impl EffectiveConfig {
+    /// Keep only the settings a layer above the operator defaults sets, or
+    /// with notes.
+    pub fn diff(mut self) -> Self {
+        self.values.retain(|value| {
+            matches!(
+                value.source,
+                ConfigSource::Annotation | ConfigSource::NamespaceDefault
+            ) || !value.notes.is_empty()
+        });
+        self
+    }
+}
+
+/// The operator's default for an optional setting; `tag_suffix` defaults to
+/// `GITOPS_TAG_SUFFIX`, `commit_message` to `GITOPS_COMMIT_MESSAGE`,
+/// `clone_strategy` to `GITOPS_CLONE_STRATEGY`, and `validate_manifests` to
+/// `GITOPS_VALIDATE_MANIFESTS`.
+fn operator_default(setting: &str, default: Option<&str>) -> Option<String> {
+    match setting {
+        "tag_suffix" => std::env::var("GITOPS_TAG_SUFFIX").ok(),
+        "commit_message" => std::env::var("GITOPS_COMMIT_MESSAGE").ok(),
+        "clone_strategy" => std::env::var("GITOPS_CLONE_STRATEGY")
+            .ok()
+            .or_else(|| default.map(String::from)),
+        "validate_manifests" => std::env::var("GITOPS_VALIDATE_MANIFESTS")
+            .ok()
+            .or_else(|| default.map(String::from)),
+        _ => default.map(String::from),
+    }
+}
+
+/// Lay out the settings of a deployment in `namespace` with `labels` and
+/// `annotations` against the namespace `defaults` and the operator's own.
+/// Values are reported as written; the parsing the operator applies on top
+/// (e.g. an unknown `tag_type` meaning `long`) is not.
+pub fn effective_config(
+    namespace: &str,
+    name: &str,
+    labels: Option<&BTreeMap<String, String>>,
+    annotations: Option<&BTreeMap<String, String>>,
+    defaults: &[AnnotationDefaults],
+) -> EffectiveConfig {
+    let rules: Vec<&AnnotationDefaults> = defaults
+        .iter()
+        .filter(|rule| rule.matches(namespace, labels))
+        .collect();
+    let settings = REQUIRED.iter().map(|setting| (*setting, None, true)).chain(
+        OPTIONAL
+            .iter()
+            .map(|(setting, default)| (*setting, operator_default(setting, *default), false)),
+    );
+
+    let mut values: Vec<EffectiveValue> = settings
+        .map(|(setting, operator_default, required)| {
+            let key = format!("{}{}", ANNOTATION_PREFIX, setting);
+            let annotation = annotations.and_then(|a| a.get(&key)).cloned();
+            let mut injected: Vec<&String> = vec![];
+            for value in rules.iter().filter_map(|rule| rule.annotations.get(&key)) {
+                if !injected.contains(&value) {
+                    injected.push(value);
+                }
+            }
+            let namespace_default = injected.first().map(|value| value.to_string());
+
+            let mut notes = vec![];
+            if injected.len() > 1 {
+                notes.push(format!(
+                    "namespace defaults conflict ({}); the first matching rule wins",
+                    injected
+                        .iter()
+                        .map(|value| format!("'{}'", value))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+            let source = match (&annotation, &namespace_default) {
+                (Some(value), Some(default)) if value == default => ConfigSource::NamespaceDefault,
+                (Some(_), default) => {
+                    if default.is_some() {
+                        notes.push("overrides the namespace default".to_string());
+                    } else if operator_default.is_some() && annotation != operator_default {
+                        notes.push("overrides the operator default".to_string());
+                    }
+                    ConfigSource::Annotation
+                }
+                (None, default) => {
+                    if default.is_some() {
+                        // Defaults are only injected when a Deployment is created.
+                        notes.push(
+                            "namespace default not applied; the deployment predates it".to_string(),
+                        );
+                    }
+                    if required {
+                        notes.push("required, the deployment is not tracked".to_string());
+                    }
+                    if operator_default.is_some() {
+                        ConfigSource::OperatorDefault
+                    } else {
+                        ConfigSource::Unset
+                    }
+                }
+            };
+
+            EffectiveValue {
+                key,
+                value: annotation.clone().or_else(|| operator_default.clone()),
+                source,
+                annotation,
+                namespace_default,
+                operator_default,
+                notes,
+            }
+        })
+        .collect();
+    let tracked = values
+        .iter()
+        .take(REQUIRED.len())
+        .all(|value| value.annotation.is_some());
+
+    for (key, value) in annotations.into_iter().flatten() {
+        if key.starts_with(ANNOTATION_PREFIX)
+            && !STATUS.contains(&key.as_str())
+            && !values.iter().any(|known| &known.key == key)
+        {
+            values.push(EffectiveValue {
+                key: key.clone(),
+                value: Some(value.clone()),
+                source: ConfigSource::Annotation,
+                annotation: Some(value.clone()),
+                namespace_default: None,
+                operator_default: None,
+                notes: vec!["unknown setting, ignored".to_string()],
+            });
+        }
+    }
+
+    EffectiveConfig {
+        namespace: namespace.to_string(),
+        name: name.to_string(),
+        tracked,
+        values,
+    }
+}
@@ -0,0 +1,3 @@
+#[allow(clippy::module_inception)]
+mod effective_config;
+pub use effective_config::*;
@@ -0,0 +1,188 @@
+use crate::codecommit::https_url;
+use crate::metrics::EGRESS_DENIED_TOTAL;
+use anyhow::{Context, Result};
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+use tracing::{error, info, warn};
+
+/// What an outbound call is for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EgressKind {
+    /// Cloning, fetching and pushing repositories.
+    Git,
+    /// Registry API and token requests.
+    Registry,
+    /// Notification webhooks.
+    Webhook,
+    /// OTLP collectors traces and metrics are exported to.
+    Telemetry,
+}
+
+impl EgressKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EgressKind::Git => "git",
+            EgressKind::Registry => "registry",
+            EgressKind::Webhook => "webhook",
+            EgressKind::Telemetry => "telemetry",
+        }
+    }
+}
+
+/// An outbound call refused by the [`EgressPolicy`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EgressDenied {
+    pub kind: EgressKind,
+    pub host: String,
+}
+
+impl fmt::Display for EgressDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Egress policy refused the {} call to {}: host not in the allowlist",
+            self.kind.as_str(),
+            self.host
+        )
+    }
+}
+
+impl std::error::Error for EgressDenied {}
+
+/// The host `url` connects to, lowercased: `https://host:443/...`,
+/// `ssh://git@host/...` and scp-like `git@host:org/repo.git` all give `host`.
+/// `None` for local paths and `file://` URLs, which make no network call.
+pub fn host_of(url: &str) -> Option<String> {
+    let authority = match url.split_once("://") {
+        Some((_, rest)) => rest.split(['/', '?', '#']).next().unwrap_or_default(),
+        None if url.starts_with('/') || url.starts_with('.') => return None,
+        None => url.split_once(':')?.0,
+    };
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = match host.strip_prefix('[') {
+        // IPv6 literal: [::1]:5000
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    (!host.is_empty()).then(|| host.to_ascii_lowercase())
+}
+
+/// Whether `host` matches `pattern`: the exact host, or any subdomain for
+/// `*.example.com`.
+fn matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim().to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+        None => host == pattern,
+    }
+}
+
+/// Hosts the operator may call, per kind of call. A kind without a list is
+/// unrestricted; an empty list refuses every call of that kind.
+#[derive(serde::Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct EgressPolicy {
+    #[serde(default)]
+    pub git: Option<Vec<String>>,
+    #[serde(default)]
+    pub registries: Option<Vec<String>>,
+    #[serde(default)]
+    pub webhooks: Option<Vec<String>>,
+    #[serde(default)]
+    pub telemetry: Option<Vec<String>>,
+}
+
+impl EgressPolicy {
+    /// No restriction at all.
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// Every kind of call refused.
+    pub fn deny_all() -> Self {
+        Self {
+            git: Some(vec![]),
+            registries: Some(vec![]),
+            webhooks: Some(vec![]),
+            telemetry: Some(vec![]),
+        }
+    }
+
+    /// Parse a policy from YAML:
+    ///
+    /// ```yaml
+    /// git: [github.com]
+    /// registries: [ghcr.io, "*.docker.io"]
+    /// webhooks: [hooks.slack.com]
+    /// telemetry: [otel-collector.monitoring]
+    /// ```
+    pub fn parse(yaml: &str) -> Result<Self> {
+        serde_yaml::from_str(yaml).context("Failed to parse egress policy")
+    }
+
+    fn allowlist(&self, kind: EgressKind) -> Option<&[String]> {
+        match kind {
+            EgressKind::Git => self.git.as_deref(),
+            EgressKind::Registry => self.registries.as_deref(),
+            EgressKind::Webhook => self.webhooks.as_deref(),
+            EgressKind::Telemetry => self.telemetry.as_deref(),
+        }
+    }
+
+    /// Refuse a `kind` call to `url` unless its host is allowed. Calls that
+    /// stay on the machine are always allowed, and URLs without a host
+    /// are refused when the kind is restricted.
+    pub fn check(&self, kind: EgressKind, url: &str) -> Result<(), EgressDenied> {
+        let Some(allowed) = self.allowlist(kind) else {
+            return Ok(());
+        };
+        if url.starts_with('/') || url.starts_with('.') || url.starts_with("file://") {
+            return Ok(());
+        }
+        // `codecommit::` URLs name a region, not a host.
+        let url = https_url(url).unwrap_or_else(|| url.to_string());
+        let host = host_of(&url).unwrap_or_else(|| url.to_string());
+        if allowed.iter().any(|pattern| matches(pattern, &host)) {
+            return Ok(());
+        }
+
+        ::metrics::counter!(EGRESS_DENIED_TOTAL, "kind" => kind.as_str()).increment(1);
+        let denied = EgressDenied { kind, host };
+        warn!("{}", denied);
+        Err(denied)
+    }
+}
+
+/// The operator's egress policy, read from the YAML file at
+/// `GITOPS_EGRESS_POLICY_PATH`. Unrestricted when unset; a file that can't be
+/// read or parsed refuses every call rather than silently opening up.
+pub fn egress_policy_from_env() -> EgressPolicy {
+    let Ok(path) = std::env::var("GITOPS_EGRESS_POLICY_PATH") else {
+        return EgressPolicy::allow_all();
+    };
+    match std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path))
+        .and_then(|yaml| EgressPolicy::parse(&yaml))
+    {
+        Ok(policy) => {
+            info!("Loaded egress policy from {}", path);
+            policy
+        }
+        Err(e) => {
+            error!("{:#}; refusing every outbound call", e);
+            EgressPolicy::deny_all()
+        }
+    }
+}
+
+/// Process-wide egress policy.
+pub fn egress_policy() -> Arc<EgressPolicy> {
+    static POLICY: OnceLock<Arc<EgressPolicy>> = OnceLock::new();
+    POLICY
+        .get_or_init(|| Arc::new(egress_policy_from_env()))
+        .clone()
+}
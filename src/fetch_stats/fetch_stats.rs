@@ -0,0 +1,139 @@
+use crate::history::now_rfc3339;
+use crate::metrics::{GIT_FETCH_DURATION_SECONDS, GIT_FETCH_RECEIVED_BYTES_TOTAL};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Fetches remembered per repository; older ones are dropped.
+pub const MAX_SAMPLES: usize = 200;
+
+/// One clone or fetch of a repository.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct FetchSample {
+    /// When the transfer finished, RFC 3339 (UTC).
+    pub at: String,
+    pub duration_ms: u64,
+    pub received_bytes: u64,
+    pub received_objects: u64,
+    /// Whether this was a full clone rather than a fetch into a checkout.
+    pub clone: bool,
+    pub ok: bool,
+}
+
+impl FetchSample {
+    pub fn new(
+        duration: Duration,
+        received_bytes: u64,
+        received_objects: u64,
+        clone: bool,
+        ok: bool,
+    ) -> Self {
+        Self {
+            at: now_rfc3339(),
+            duration_ms: duration.as_millis() as u64,
+            received_bytes,
+            received_objects,
+            clone,
+            ok,
+        }
+    }
+}
+
+/// What fetching a repository has cost over its remembered samples.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct RepoCost {
+    pub repository: String,
+    pub fetches: usize,
+    pub clones: usize,
+    pub failures: usize,
+    pub total_ms: u64,
+    pub mean_ms: u64,
+    pub max_ms: u64,
+    pub received_bytes: u64,
+    pub mean_bytes: u64,
+    /// Largest single transfer, usually a clone: what shallowing or
+    /// shrinking the repository would save.
+    pub max_bytes: u64,
+    pub last_fetch_at: String,
+}
+
+impl RepoCost {
+    fn from_samples(repository: &str, samples: &VecDeque<FetchSample>) -> Self {
+        let fetches = samples.len();
+        let total_ms = samples.iter().map(|s| s.duration_ms).sum();
+        let received_bytes = samples.iter().map(|s| s.received_bytes).sum();
+        Self {
+            repository: repository.to_string(),
+            fetches,
+            clones: samples.iter().filter(|s| s.clone).count(),
+            failures: samples.iter().filter(|s| !s.ok).count(),
+            total_ms,
+            mean_ms: total_ms / fetches.max(1) as u64,
+            max_ms: samples.iter().map(|s| s.duration_ms).max().unwrap_or(0),
+            received_bytes,
+            mean_bytes: received_bytes / fetches.max(1) as u64,
+            max_bytes: samples.iter().map(|s| s.received_bytes).max().unwrap_or(0),
+            last_fetch_at: samples.back().map(|s| s.at.clone()).unwrap_or_default(),
+        }
+    }
+}
+
+/// Recent clone and fetch timings and transfer sizes, per repository URL.
+#[derive(Default)]
+pub struct FetchStats {
+    samples: Mutex<HashMap<String, VecDeque<FetchSample>>>,
+}
+
+impl FetchStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember a transfer of `repository` and export it as metrics.
+    pub fn record(&self, repository: &str, sample: FetchSample) {
+        ::metrics::histogram!(GIT_FETCH_DURATION_SECONDS, "repository" => repository.to_string())
+            .record(sample.duration_ms as f64 / 1000.0);
+        ::metrics::counter!(GIT_FETCH_RECEIVED_BYTES_TOTAL, "repository" => repository.to_string())
+            .increment(sample.received_bytes);
+
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        let repo = samples.entry(repository.to_string()).or_default();
+        if repo.len() == MAX_SAMPLES {
+            repo.pop_front();
+        }
+        repo.push_back(sample);
+    }
+
+    /// The remembered transfers of `repository`, oldest first.
+    pub fn samples(&self, repository: &str) -> Vec<FetchSample> {
+        let samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        samples
+            .get(repository)
+            .map(|repo| repo.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The `limit` repositories that took longest to fetch in total, most
+    /// expensive first.
+    pub fn slow_repos(&self, limit: usize) -> Vec<RepoCost> {
+        let samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        let mut costs: Vec<RepoCost> = samples
+            .iter()
+            .map(|(repository, repo)| RepoCost::from_samples(repository, repo))
+            .collect();
+        costs.sort_by(|a, b| {
+            b.total_ms
+                .cmp(&a.total_ms)
+                .then_with(|| b.received_bytes.cmp(&a.received_bytes))
+                .then_with(|| a.repository.cmp(&b.repository))
+        });
+        costs.truncate(limit);
+        costs
+    }
+}
+
+/// Process-wide fetch statistics, filled in by the git operations.
+pub fn fetch_stats() -> &'static FetchStats {
+    static STATS: OnceLock<FetchStats> = OnceLock::new();
+    STATS.get_or_init(FetchStats::new)
+}
@@ -0,0 +1,3 @@
+#[allow(clippy::module_inception)]
+mod fetch_stats;
+pub use fetch_stats::*;
@@ -1,28 +1,380 @@
+use crate::configuration::image_repository;
 use anyhow::Context;
 use anyhow::Error;
-use k8s_openapi::api::apps::v1::Deployment;
+use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, StatefulSet};
+use k8s_openapi::api::batch::v1::CronJob;
+use k8s_openapi::api::core::v1::{Container, PodSpec};
 use serde_yaml;
 use std::fs;
+use std::path::PathBuf;
 
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
-fn get_deployment_from_file(file_path: &str) -> Result<Deployment, Error> {
+/// A workload manifest the operator knows how to patch, selected by its `kind`.
+#[derive(Clone, Debug)]
+pub enum Workload {
+    Deployment(Deployment),
+    StatefulSet(StatefulSet),
+    DaemonSet(DaemonSet),
+    CronJob(Box<CronJob>),
+}
+
+impl Workload {
+    /// The pod spec holding the containers to inspect and patch (for CronJobs,
+    /// the one nested under `jobTemplate`).
+    pub fn pod_spec(&self) -> Option<&PodSpec> {
+        match self {
+            Workload::Deployment(d) => d.spec.as_ref()?.template.spec.as_ref(),
+            Workload::StatefulSet(s) => s.spec.as_ref()?.template.spec.as_ref(),
+            Workload::DaemonSet(d) => d.spec.as_ref()?.template.spec.as_ref(),
+            Workload::CronJob(c) => c.spec.job_template.spec.as_ref()?.template.spec.as_ref(),
+        }
+    }
+
+    fn pod_spec_mut(&mut self) -> Option<&mut PodSpec> {
+        match self {
+            Workload::Deployment(d) => d.spec.as_mut()?.template.spec.as_mut(),
+            Workload::StatefulSet(s) => s.spec.as_mut()?.template.spec.as_mut(),
+            Workload::DaemonSet(d) => d.spec.as_mut()?.template.spec.as_mut(),
+            Workload::CronJob(c) => c.spec.job_template.spec.as_mut()?.template.spec.as_mut(),
+        }
+    }
+
+    fn to_yaml(&self) -> Result<String, serde_yaml::Error> {
+        match self {
+            Workload::Deployment(d) => serde_yaml::to_string(d),
+            Workload::StatefulSet(s) => serde_yaml::to_string(s),
+            Workload::DaemonSet(d) => serde_yaml::to_string(d),
+            Workload::CronJob(c) => serde_yaml::to_string(c),
+        }
+    }
+}
+
+/// What happens to the rest of a matched image reference (registry host and
+/// path) when its tag is patched, from `gitops.operator.image_host`.
+#[derive(serde::Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageHost {
+    /// Keep the reference as written in the manifest; only the tag changes.
+    #[default]
+    Preserve,
+    /// Replace the whole reference with the configured image (host included).
+    Rewrite,
+}
+
+/// Whether the image reference `reference` (as found in a manifest) points at
+/// `image_name`, comparing repository paths so a registry host present on one
+/// side only doesn't matter (`harbor.example.com/team/app:v1` matches
+/// `team/app`, but `team/myapp` does not).
+pub fn image_matches(reference: &str, image_name: &str) -> bool {
+    image_repository(reference) == image_repository(image_name)
+}
+
+/// The containers of a manifest the operator manages: those whose image
+/// matches one of `image_names`, or, when `container_name` is set
+/// (`gitops.operator.container_name`), only the container with that name.
+/// With `key_path` set the files can be any YAML document (Helm values via
+/// `gitops.operator.values_path`, CRDs via `gitops.operator.patch_expression`)
+/// and the tag is the value at that path, see [`parse_key_path`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ContainerTarget {
+    pub image_names: Vec<String>,
+    pub container_name: Option<String>,
+    pub key_path: Option<String>,
+}
+
+impl ContainerTarget {
+    /// Target the containers running `image_name`.
+    pub fn image(image_name: &str) -> Self {
+        Self {
+            image_names: vec![image_name.to_string()],
+            ..Self::default()
+        }
+    }
+
+    /// The image name `container` is managed under, or `None` if it is not
+    /// targeted. A container selected by name that runs none of the images
+    /// is managed under the first one.
+    pub fn image_for(&self, container: &Container) -> Option<&str> {
+        let image = container.image.as_deref()?;
+        let matching = self
+            .image_names
+            .iter()
+            .find(|name| image_matches(image, name));
+
+        match &self.container_name {
+            Some(name) if *name != container.name => None,
+            Some(_) => matching.or(self.image_names.first()).map(String::as_str),
+            None => matching.map(String::as_str),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match &self.container_name {
+            Some(name) => format!("container '{}'; check gitops.operator.container_name", name),
+            None => format!(
+                "image '{}'; check gitops.operator.image_name",
+                self.image_names.join("', '")
+            ),
+        }
+    }
+}
+
+/// `reference` without its tag or digest; the registry host (and port) stay.
+fn strip_tag(reference: &str) -> &str {
+    let without_digest = reference.split('@').next().unwrap_or(reference);
+    match without_digest.rsplit_once(':') {
+        Some((repo, tag)) if !tag.contains('/') => repo,
+        _ => without_digest,
+    }
+}
+
+/// One step of a key path: a mapping key or a sequence index.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Parse a key path, either dotted (`image.tag`, where numeric segments
+/// index sequences) or yq-style (`.spec.template.spec.containers[0].image`).
+pub fn parse_key_path(path: &str) -> Result<Vec<PathSegment>, Error> {
+    let invalid = || anyhow::anyhow!("Invalid key path {:?}", path);
+    let mut segments = vec![];
+    for part in path.strip_prefix('.').unwrap_or(path).split('.') {
+        let (key, mut indexes) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if key.is_empty() && indexes.is_empty() {
+            return Err(invalid());
+        }
+        if !key.is_empty() {
+            segments.push(PathSegment::Key(key.to_string()));
+        }
+        while !indexes.is_empty() {
+            let (index, rest) = indexes
+                .strip_prefix('[')
+                .and_then(|rest| rest.split_once(']'))
+                .ok_or_else(invalid)?;
+            segments.push(PathSegment::Index(index.parse().map_err(|_| invalid())?));
+            indexes = rest;
+        }
+    }
+    Ok(segments)
+}
+
+/// The node at `path`. A key on a sequence is read as an index, so dotted
+/// paths can index lists too.
+fn value_at<'a>(
+    root: &'a serde_yaml::Value,
+    path: &[PathSegment],
+) -> Option<&'a serde_yaml::Value> {
+    path.iter()
+        .try_fold(root, |node, segment| match (node, segment) {
+            (serde_yaml::Value::Sequence(items), PathSegment::Key(key)) => {
+                items.get(key.parse::<usize>().ok()?)
+            }
+            (serde_yaml::Value::Sequence(items), PathSegment::Index(index)) => items.get(*index),
+            (_, PathSegment::Key(key)) => node.get(key),
+            (_, PathSegment::Index(_)) => None,
+        })
+}
+
+fn value_at_mut<'a>(
+    root: &'a mut serde_yaml::Value,
+    path: &[PathSegment],
+) -> Option<&'a mut serde_yaml::Value> {
+    path.iter()
+        .try_fold(root, |node, segment| match (node, segment) {
+            (serde_yaml::Value::Sequence(items), PathSegment::Key(key)) => {
+                items.get_mut(key.parse::<usize>().ok()?)
+            }
+            (serde_yaml::Value::Sequence(items), PathSegment::Index(index)) => {
+                items.get_mut(*index)
+            }
+            (node, PathSegment::Key(key)) => node.get_mut(key),
+            (_, PathSegment::Index(_)) => None,
+        })
+}
+
+/// Whether a value found at a key path is a whole image reference
+/// (`ghcr.io/org/app:v1`) rather than a bare tag, which can contain none of
+/// `/`, `:` or `@`.
+fn is_image_reference(value: &str) -> bool {
+    value.contains(['/', ':', '@'])
+}
+
+/// The tag held by a value found at a key path: the value itself, or the tag
+/// of an image reference (`latest` if it has none).
+fn tag_of_value(value: &str) -> String {
+    if !is_image_reference(value) {
+        return value.to_string();
+    }
+    let without_digest = value.split('@').next().unwrap_or(value);
+    match without_digest.rsplit_once(':') {
+        Some((_, tag)) if !tag.contains('/') => tag.to_string(),
+        _ => "latest".to_string(),
+    }
+}
+
+/// The tag at `key_path` in a YAML document. Numbers are read as tags too,
+/// since an unquoted all-digit tag parses as one.
+fn key_path_tag_from_str(yaml_content: &str, key_path: &str) -> Result<Option<String>, Error> {
+    let path = parse_key_path(key_path)?;
+    let document: serde_yaml::Value =
+        serde_yaml::from_str(yaml_content).context("Failed to parse YAML")?;
+
+    Ok(match value_at(&document, &path) {
+        Some(serde_yaml::Value::String(value)) => Some(tag_of_value(value)),
+        Some(serde_yaml::Value::Number(tag)) => Some(tag.to_string()),
+        _ => None,
+    })
+}
+
+/// Set the tag at `key_path` in the YAML file to `new_sha`: the whole value,
+/// or only the tag of an image reference. The key must already exist, so a
+/// typo in the path fails instead of adding a value nothing reads.
+fn patch_key_path(file_path: &str, key_path: &str, new_sha: &str) -> Result<usize, Error> {
+    info!("Patching {} in file: {}", key_path, file_path);
+    let path = parse_key_path(key_path)?;
+    let yaml_content = fs::read_to_string(file_path).context("Failed to read YAML file")?;
+    let mut document: serde_yaml::Value =
+        serde_yaml::from_str(&yaml_content).context("Failed to parse YAML")?;
+
+    let Some(value) = value_at_mut(&mut document, &path) else {
+        return Err(anyhow::anyhow!(
+            "No key {} in {}; check gitops.operator.values_path or gitops.operator.patch_expression",
+            key_path,
+            file_path
+        ));
+    };
+    let current = match value {
+        serde_yaml::Value::String(current) => current.clone(),
+        serde_yaml::Value::Number(tag) => tag.to_string(),
+        _ => String::new(),
+    };
+    if tag_of_value(&current) == new_sha {
+        warn!("Image tag already updated... Aborting mission!");
+        return Err(anyhow::anyhow!(
+            "Image tag {} is already up to date",
+            new_sha
+        ));
+    }
+    let updated = if is_image_reference(&current) {
+        format!("{}:{}", strip_tag(&current), new_sha)
+    } else {
+        new_sha.to_string()
+    };
+    *value = serde_yaml::Value::String(updated);
+
+    let updated_yaml = serde_yaml::to_string(&document).context("Failed to serialize YAML")?;
+    fs::write(file_path, updated_yaml).context("Failed to write updated YAML back to file")?;
+
+    Ok(1)
+}
+
+fn get_workload_from_file(file_path: &str) -> Result<Workload, Error> {
     let yaml_content =
         fs::read_to_string(file_path).context("Failed to read deployment YAML file")?;
 
-    let deployment: Deployment = serde_yaml::from_str(&yaml_content)
-        .context("Failed to parse YAML into Kubernetes Deployment")?;
+    get_workload_from_str(&yaml_content)
+}
+
+/// Directory mode: every `*.yaml`/`*.yml` file under `dir` (recursively, in
+/// path order) holding a workload with a container in `target`, or for Helm
+/// values and other YAML, a value at its `key_path`. Anything else (`kustomization.yaml`,
+/// Services, multi-document files, ...) is skipped.
+pub fn find_manifests(dir: &str, target: &ContainerTarget) -> Result<Vec<String>, Error> {
+    let mut pending = vec![PathBuf::from(dir)];
+    let mut files = vec![];
+    while let Some(dir) = pending.pop() {
+        for entry in
+            fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))?
+        {
+            let path = entry?.path();
+            if path.is_dir() {
+                // Skip .git and other hidden directories.
+                if !path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+                {
+                    pending.push(path);
+                }
+            } else if path
+                .extension()
+                .is_some_and(|ext| ext == "yaml" || ext == "yml")
+            {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
 
-    Ok(deployment)
+    Ok(files
+        .into_iter()
+        .filter_map(|path| {
+            let path = path.to_str()?.to_string();
+            if target.key_path.is_some() {
+                return current_tag(&path, target)
+                    .ok()
+                    .flatten()
+                    .is_some()
+                    .then_some(path);
+            }
+            match get_workload_from_file(&path) {
+                Ok(workload) => workload
+                    .pod_spec()?
+                    .containers
+                    .iter()
+                    .any(|c| target.image_for(c).is_some())
+                    .then_some(path),
+                Err(e) => {
+                    debug!("Skipping {}: {:#}", path, e);
+                    None
+                }
+            }
+        })
+        .collect())
+}
+
+/// Parse a manifest into the workload type named by its `kind`. Manifests
+/// without a `kind` are treated as Deployments.
+pub fn get_workload_from_str(yaml_content: &str) -> Result<Workload, Error> {
+    let value: serde_yaml::Value =
+        serde_yaml::from_str(yaml_content).context("Failed to parse manifest YAML")?;
+    let kind = value
+        .get("kind")
+        .and_then(serde_yaml::Value::as_str)
+        .unwrap_or("Deployment")
+        .to_string();
+
+    match kind.as_str() {
+        "Deployment" => Ok(Workload::Deployment(
+            serde_yaml::from_value(value)
+                .context("Failed to parse YAML into Kubernetes Deployment")?,
+        )),
+        "StatefulSet" => Ok(Workload::StatefulSet(
+            serde_yaml::from_value(value)
+                .context("Failed to parse YAML into Kubernetes StatefulSet")?,
+        )),
+        "DaemonSet" => Ok(Workload::DaemonSet(
+            serde_yaml::from_value(value)
+                .context("Failed to parse YAML into Kubernetes DaemonSet")?,
+        )),
+        "CronJob" => Ok(Workload::CronJob(
+            serde_yaml::from_value(value)
+                .context("Failed to parse YAML into Kubernetes CronJob")?,
+        )),
+        other => Err(anyhow::anyhow!(
+            "Unsupported manifest kind '{}'; expected Deployment, StatefulSet, DaemonSet or CronJob",
+            other
+        )),
+    }
 }
 
 pub fn needs_patching(file_path: &str, new_sha: &str) -> Result<bool, Error> {
     info!("Comparing deployment file: {}", file_path);
-    let deployment = get_deployment_from_file(file_path)?;
+    let workload = get_workload_from_file(file_path)?;
 
-    if let Some(spec) = deployment.spec
-        && let Some(template) = spec.template.spec
-    {
+    if let Some(template) = workload.pod_spec() {
         for container in &template.containers {
             if let Some(image) = container.image.as_ref()
                 && image.contains(new_sha)
@@ -37,17 +389,38 @@ pub fn needs_patching(file_path: &str, new_sha: &str) -> Result<bool, Error> {
 }
 
 /// Return the image tag currently set on the first container whose image
-/// reference contains `image_name`, if any. Used to report the previous SHA
-/// (`from_sha`) when reconciling.
+/// matches `image_name`, if any. Used to report the previous SHA (`from_sha`)
+/// when reconciling.
 pub fn current_image_tag(file_path: &str, image_name: &str) -> Result<Option<String>, Error> {
-    let deployment = get_deployment_from_file(file_path)?;
+    current_tag(file_path, &ContainerTarget::image(image_name))
+}
+
+/// Same as [`current_image_tag`] for the first container in `target`.
+pub fn current_tag(file_path: &str, target: &ContainerTarget) -> Result<Option<String>, Error> {
+    let yaml_content =
+        fs::read_to_string(file_path).context("Failed to read deployment YAML file")?;
+
+    tag_from_str(&yaml_content, target)
+}
+
+/// Same as [`current_image_tag`] but for manifest content already in memory,
+/// e.g. a blob read from an older commit of the manifests repository.
+pub fn image_tag_from_str(yaml_content: &str, image_name: &str) -> Result<Option<String>, Error> {
+    tag_from_str(yaml_content, &ContainerTarget::image(image_name))
+}
 
-    if let Some(spec) = deployment.spec
-        && let Some(template) = spec.template.spec
-    {
+/// Same as [`current_tag`] but for manifest content already in memory.
+pub fn tag_from_str(yaml_content: &str, target: &ContainerTarget) -> Result<Option<String>, Error> {
+    if let Some(key_path) = &target.key_path {
+        return key_path_tag_from_str(yaml_content, key_path);
+    }
+
+    let workload = get_workload_from_str(yaml_content)?;
+
+    if let Some(template) = workload.pod_spec() {
         for container in &template.containers {
-            if let Some(image) = container.image.as_ref()
-                && image.contains(image_name)
+            if target.image_for(container).is_some()
+                && let Some(image) = container.image.as_ref()
             {
                 return Ok(image.rsplit_once(':').map(|(_, tag)| tag.to_string()));
             }
@@ -57,48 +430,122 @@ pub fn current_image_tag(file_path: &str, image_name: &str) -> Result<Option<Str
     Ok(None)
 }
 
-#[tracing::instrument(name = "clone_or_update_repo", skip(), fields())]
 pub fn patch_deployment(file_path: &str, image_name: &str, new_sha: &str) -> Result<(), Error> {
+    patch_deployment_with(file_path, image_name, new_sha, ImageHost::default())
+}
+
+/// Set the tag of every container whose image matches `image_name` (see
+/// [`image_matches`]) to `new_sha`, handling the rest of the reference as
+/// `host` says.
+pub fn patch_deployment_with(
+    file_path: &str,
+    image_name: &str,
+    new_sha: &str,
+    host: ImageHost,
+) -> Result<(), Error> {
+    patch_images(
+        file_path,
+        &ContainerTarget::image(image_name),
+        new_sha,
+        host,
+    )
+    .map(|_| ())
+}
+
+/// Whether any container matching one of `image_names` is not yet at
+/// `new_sha`. Also true when nothing matches, so the patch step can report it.
+pub fn images_need_patching(
+    file_path: &str,
+    target: &ContainerTarget,
+    new_sha: &str,
+) -> Result<bool, Error> {
+    info!("Comparing deployment file: {}", file_path);
+    if target.key_path.is_some() {
+        return Ok(current_tag(file_path, target)?.as_deref() != Some(new_sha));
+    }
+    let workload = get_workload_from_file(file_path)?;
+
+    let tags: Vec<Option<&str>> = workload
+        .pod_spec()
+        .map(|template| template.containers.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter(|c| target.image_for(c).is_some())
+        .filter_map(|c| c.image.as_deref())
+        .map(|image| image.rsplit_once(':').map(|(_, tag)| tag))
+        .collect();
+
+    Ok(tags.is_empty() || tags.iter().any(|tag| *tag != Some(new_sha)))
+}
+
+/// Set the tag of every container in `target` to `new_sha`, leaving containers
+/// already at `new_sha` alone. Returns how many containers were changed;
+/// errors if none matched or all were up to date.
+#[tracing::instrument(name = "patch_images", skip(), fields())]
+pub fn patch_images(
+    file_path: &str,
+    target: &ContainerTarget,
+    new_sha: &str,
+    host: ImageHost,
+) -> Result<usize, Error> {
+    if let Some(key_path) = &target.key_path {
+        return patch_key_path(file_path, key_path, new_sha);
+    }
+
     info!("Patching image tag in deployment file: {}", file_path);
-    let mut deployment = get_deployment_from_file(file_path)?;
+    let mut workload = get_workload_from_file(file_path)?;
 
-    let mut patched = false;
+    let mut matched = 0;
+    let mut patched = 0;
 
-    // Modify deployment specifics
-    if let Some(spec) = deployment.spec.as_mut()
-        && let Some(template) = spec.template.spec.as_mut()
-    {
+    // Modify workload specifics
+    if let Some(template) = workload.pod_spec_mut() {
         for container in &mut template.containers {
+            let Some(image_name) = target.image_for(container) else {
+                continue;
+            };
             let Some(image) = container.image.as_ref() else {
                 continue;
             };
-            if image.contains(new_sha) {
-                warn!("Image tag already updated... Aborting mission!");
-                return Err(anyhow::anyhow!(
-                    "Image tag {} is already up to date",
-                    new_sha
-                ));
-            }
-            if image.contains(image_name) {
-                container.image = Some(format!("{}:{}", &image_name, &new_sha));
-                patched = true;
+            matched += 1;
+
+            if image.rsplit_once(':').map(|(_, tag)| tag) == Some(new_sha) {
+                continue;
             }
+
+            let repository = match host {
+                ImageHost::Preserve => strip_tag(image),
+                ImageHost::Rewrite => strip_tag(image_name),
+            };
+            container.image = Some(format!("{}:{}", repository, &new_sha));
+            patched += 1;
         }
     }
 
-    // If no container referenced the configured image, writing the file back
-    // would be a silent no-op. Surface it as an error so the reconcile result
-    // reports a failure instead of a misleading success.
-    if !patched {
+    // If no container was targeted, writing the file back would be a silent
+    // no-op. Surface it as an error so the reconcile result reports a failure
+    // instead of a misleading success.
+    if matched == 0 {
         return Err(anyhow::anyhow!(
-            "No container in {} references image '{}'; check gitops.operator.image_name",
+            "No container in {} matches {}",
             file_path,
-            image_name
+            target.describe()
         ));
     }
 
-    let updated_yaml =
-        serde_yaml::to_string(&deployment).context("Failed to serialize updated deployment")?;
+    if patched == 0 {
+        warn!("Image tag already updated... Aborting mission!");
+        return Err(anyhow::anyhow!(
+            "Image tag {} is already up to date",
+            new_sha
+        ));
+    }
+
+    let updated_yaml = workload
+        .to_yaml()
+        .context("Failed to serialize updated deployment")?;
+
+    fs::write(file_path, updated_yaml).context("Failed to write updated YAML back to file")?;
 
-    fs::write(file_path, updated_yaml).context("Failed to write updated YAML back to file")
+    Ok(patched)
 }
@@ -0,0 +1,371 @@
+use crate::fetch_stats::{FetchSample, fetch_stats};
+use crate::git::utils::{SshCommand, create_signature, https_login, refspec, remote_url};
+use crate::git::{
+    CloneStrategy, CommitAuthor, GitCredentials, GitRetryPolicy, PROTECTED_BRANCHES, push_retries,
+    retry_transient,
+};
+use crate::traits::GitProvider;
+use git2::{Error as GitError, ErrorClass, ErrorCode};
+
+use std::path::Path;
+use std::process::Command;
+use std::time::Instant;
+
+use tracing::{info, warn};
+
+/// Credential helper answering with the login in the environment of the
+/// `git` process, so the token never shows up in its arguments.
+const CREDENTIAL_HELPER: &str = "!f() { test \"$1\" = get || exit 0; \
+     echo \"username=$GITOPS_GIT_USERNAME\"; echo \"password=$GITOPS_GIT_PASSWORD\"; }; f";
+
+/// [`GitProvider`] running the `git` binary, for when libgit2 falls short
+/// (an SSH setup it can't handle, say). The system's git configuration,
+/// credential helpers and `ssh` all apply; the deployment's SSH key and
+/// HTTPS token, when it has them, are used first. Checkouts are clones of
+/// their own with a detached HEAD.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GitCliProvider;
+
+impl GitProvider for GitCliProvider {
+    fn name(&self) -> &'static str {
+        "git"
+    }
+
+    fn clone_repo(
+        &self,
+        url: &str,
+        path: &Path,
+        branch: &str,
+        credentials: &GitCredentials,
+        strategy: CloneStrategy,
+    ) -> Result<(), GitError> {
+        info!(
+            "Cloning {} into {} with git ({:?})",
+            url,
+            path.display(),
+            strategy
+        );
+        let session = Session::new(url, credentials)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| GitError::from_str(&e.to_string()))?;
+        }
+        let remote = remote_url(url);
+        let target = path.display().to_string();
+        let mut args = vec!["clone", "--quiet", "--branch", branch];
+        match strategy {
+            CloneStrategy::Full => {}
+            CloneStrategy::SingleBranch => args.extend(["--single-branch", "--no-tags"]),
+            CloneStrategy::Shallow => {
+                args.extend(["--depth", "1", "--single-branch", "--no-tags"]);
+            }
+        }
+        args.extend(["--", &remote, &target]);
+
+        retry_transient(&GitRetryPolicy::from_env(), "clone", path, || {
+            let started = Instant::now();
+            let cloned = git(None, &args, &session.env);
+            record_fetch(url, started, true, cloned.is_ok());
+            cloned
+        })?;
+        git(Some(path), &["checkout", "--quiet", "--detach"], &[]).map(|_| ())
+    }
+
+    fn fetch(
+        &self,
+        url: &str,
+        path: &Path,
+        branch: &str,
+        credentials: &GitCredentials,
+        strategy: CloneStrategy,
+    ) -> Result<(), GitError> {
+        info!("Fetching {} into {} with git", url, path.display());
+        let session = Session::new(url, credentials)?;
+        let refspec = refspec(branch, strategy);
+        let mut args = vec!["fetch", "--quiet"];
+        match strategy {
+            CloneStrategy::Full => args.push("--tags"),
+            CloneStrategy::SingleBranch => args.push("--no-tags"),
+            CloneStrategy::Shallow => args.extend(["--no-tags", "--depth", "1"]),
+        }
+        args.extend(["origin", &refspec]);
+
+        retry_transient(&GitRetryPolicy::from_env(), "fetch", path, || {
+            let started = Instant::now();
+            let fetched = git(Some(path), &args, &session.env);
+            record_fetch(url, started, false, fetched.is_ok());
+            fetched
+        })?;
+        // Like the other backends, what wasn't pushed is dropped.
+        let remote_branch = format!("refs/remotes/origin/{}", branch);
+        git(
+            Some(path),
+            &["checkout", "--quiet", "--force", "--detach", &remote_branch],
+            &[],
+        )
+        .map(|_| ())
+    }
+
+    fn resolve(&self, path: &Path, branch: &str) -> Result<String, GitError> {
+        git(
+            Some(path),
+            &[
+                "rev-parse",
+                "--verify",
+                &format!("refs/remotes/origin/{}^{{commit}}", branch),
+            ],
+            &[],
+        )
+    }
+
+    fn commit(
+        &self,
+        path: &Path,
+        message: &str,
+        author: Option<&CommitAuthor>,
+    ) -> Result<bool, GitError> {
+        info!("Staging changes for: {}", path.display());
+        if !git(Some(path), &["ls-files", "--unmerged"], &[])?.is_empty() {
+            warn!("Merge conflicts detected for {}", path.display());
+            return Ok(false);
+        }
+        git(Some(path), &["add", "--all"], &[])?;
+        if git(Some(path), &["diff", "--cached", "--name-only"], &[])?.is_empty() {
+            info!(
+                "No changes detected against HEAD in {}; skipping commit and push",
+                path.display()
+            );
+            return Ok(false);
+        }
+
+        let mut env = Vec::new();
+        if let Some(author) = author {
+            env.push(("GIT_AUTHOR_NAME", author.name.clone()));
+            env.push(("GIT_AUTHOR_EMAIL", author.email.clone()));
+        }
+        git(
+            Some(path),
+            &["commit", "--quiet", "--no-verify", "--message", message],
+            &env,
+        )?;
+        info!(
+            "New commit: {}",
+            git(Some(path), &["rev-parse", "HEAD"], &[])?
+        );
+        Ok(true)
+    }
+
+    fn push(
+        &self,
+        path: &Path,
+        branch: &str,
+        credentials: &GitCredentials,
+        force: bool,
+    ) -> Result<(), GitError> {
+        let url = git(Some(path), &["remote", "get-url", "origin"], &[])?;
+        let session = Session::new(&url, credentials)?;
+        if force {
+            if !is_protected_branch(path, branch) {
+                return push_once(path, &format!("+HEAD:refs/heads/{}", branch), &session);
+            }
+            warn!("Not force pushing to protected branch {}", branch);
+        }
+
+        let refspec = format!("HEAD:refs/heads/{}", branch);
+        let mut pushed = push_once(path, &refspec, &session);
+        let retries = push_retries();
+        for attempt in 1..=retries {
+            match &pushed {
+                Err(e) if e.code() == ErrorCode::NotFastForward => {}
+                _ => break,
+            }
+            warn!(
+                "Push to {} rejected as non-fast-forward, rebasing and retrying ({}/{})",
+                branch, attempt, retries
+            );
+            rebase_onto_remote(path, branch, &session)?;
+            pushed = push_once(path, &refspec, &session);
+        }
+        pushed
+    }
+}
+
+/// What `git` authenticates to a remote with: the deployment's SSH key
+/// through `GIT_SSH_COMMAND` and its HTTPS login through
+/// [`CREDENTIAL_HELPER`]. Without them, git's own configuration decides.
+struct Session {
+    env: Vec<(&'static str, String)>,
+    /// Keeps the key file until the session is done with.
+    _ssh: SshCommand,
+}
+
+impl Session {
+    fn new(url: &str, credentials: &GitCredentials) -> Result<Self, GitError> {
+        let ssh = SshCommand::new(url, credentials)?;
+        let mut env = Vec::new();
+        if let Some(command) = ssh.command() {
+            env.push(("GIT_SSH_COMMAND", command.to_string()));
+        }
+        let remote = remote_url(url);
+        if (remote.starts_with("https://") || remote.starts_with("http://"))
+            && let Ok((username, password)) = https_login(
+                &remote,
+                credentials.https_token.as_ref(),
+                username_from_url(&remote),
+            )
+        {
+            env.extend([
+                ("GIT_CONFIG_COUNT", "1".to_string()),
+                ("GIT_CONFIG_KEY_0", "credential.helper".to_string()),
+                ("GIT_CONFIG_VALUE_0", CREDENTIAL_HELPER.to_string()),
+                ("GITOPS_GIT_USERNAME", username),
+                ("GITOPS_GIT_PASSWORD", password),
+            ]);
+        }
+        Ok(Self { env, _ssh: ssh })
+    }
+}
+
+/// The user in an `http(s)://user@host/...` URL.
+fn username_from_url(url: &str) -> Option<&str> {
+    let rest = url.split_once("://")?.1;
+    let authority = rest.split('/').next()?;
+    let (user, _) = authority.rsplit_once('@')?;
+    user.split(':').next().filter(|user| !user.is_empty())
+}
+
+/// Run `git` with `args` (in `dir`, when given) and `env` on top of the
+/// operator's identity, returning what it printed.
+fn git(dir: Option<&Path>, args: &[&str], env: &[(&str, String)]) -> Result<String, GitError> {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    command.args(args).env("GIT_TERMINAL_PROMPT", "0");
+    if let Ok(signature) = create_signature() {
+        let name = String::from_utf8_lossy(signature.name_bytes()).into_owned();
+        let email = String::from_utf8_lossy(signature.email_bytes()).into_owned();
+        command
+            .env("GIT_AUTHOR_NAME", &name)
+            .env("GIT_AUTHOR_EMAIL", &email)
+            .env("GIT_COMMITTER_NAME", name)
+            .env("GIT_COMMITTER_EMAIL", email);
+    }
+    command.envs(env.iter().map(|(key, value)| (key, value)));
+
+    let output = command
+        .output()
+        .map_err(|e| GitError::from_str(&format!("Could not run git: {}", e)))?;
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
+    }
+    let printed = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stderr),
+        String::from_utf8_lossy(&output.stdout)
+    );
+    Err(to_git_error(args.first().unwrap_or(&"git"), printed.trim()))
+}
+
+/// A failed `git operation` as a libgit2 error, classified from what git
+/// printed so [`crate::git::is_transient`] and
+/// [`crate::configuration::ErrorKind`] tell it apart the same way.
+fn to_git_error(operation: &str, printed: &str) -> GitError {
+    let lower = printed.to_lowercase();
+    let any = |needles: &[&str]| needles.iter().any(|needle| lower.contains(needle));
+    let (code, class) = if any(&["[rejected]", "non-fast-forward", "fetch first"]) {
+        (ErrorCode::NotFastForward, ErrorClass::Reference)
+    } else if any(&[
+        "host key verification failed",
+        "remote host identification has changed",
+    ]) {
+        (ErrorCode::Certificate, ErrorClass::Ssh)
+    } else if any(&[
+        "authentication failed",
+        "permission denied",
+        "could not read username",
+        "could not read password",
+        "invalid username or password",
+    ]) {
+        (ErrorCode::Auth, ErrorClass::Net)
+    } else if any(&[
+        "could not resolve host",
+        "connection timed out",
+        "connection refused",
+        "connection reset",
+        "operation timed out",
+        "early eof",
+        "the remote end hung up",
+    ]) {
+        (ErrorCode::GenericError, ErrorClass::Net)
+    } else if any(&[
+        "not found",
+        "does not appear to be a git repository",
+        "couldn't find remote ref",
+        "needed a single revision",
+    ]) {
+        (ErrorCode::NotFound, ErrorClass::Reference)
+    } else {
+        (ErrorCode::GenericError, ErrorClass::None)
+    };
+    GitError::new(
+        code,
+        class,
+        format!("git {} failed: {}", operation, printed),
+    )
+}
+
+fn record_fetch(url: &str, started: Instant, clone: bool, ok: bool) {
+    fetch_stats().record(url, FetchSample::new(started.elapsed(), 0, 0, clone, ok));
+}
+
+fn push_once(path: &Path, refspec: &str, session: &Session) -> Result<(), GitError> {
+    info!("Pushing {} from {}", refspec, path.display());
+    retry_transient(&GitRetryPolicy::from_env(), "push", path, || {
+        git(
+            Some(path),
+            &["push", "--quiet", "origin", refspec],
+            &session.env,
+        )
+        .map(|_| ())
+    })
+}
+
+/// Whether `branch` is one of [`PROTECTED_BRANCHES`] or the default branch
+/// of origin, like [`crate::git::is_protected_branch`].
+fn is_protected_branch(path: &Path, branch: &str) -> bool {
+    PROTECTED_BRANCHES.contains(&branch)
+        || git(
+            Some(path),
+            &["symbolic-ref", "--quiet", "refs/remotes/origin/HEAD"],
+            &[],
+        )
+        .is_ok_and(|target| target == format!("refs/remotes/origin/{}", branch))
+}
+
+/// Fetch `branch` from origin and replay the commit at HEAD on top of it.
+/// Fails when the remote changed the same lines.
+fn rebase_onto_remote(path: &Path, branch: &str, session: &Session) -> Result<(), GitError> {
+    let refspec = refspec(branch, CloneStrategy::SingleBranch);
+    retry_transient(&GitRetryPolicy::from_env(), "fetch", path, || {
+        git(
+            Some(path),
+            &["fetch", "--quiet", "--no-tags", "origin", &refspec],
+            &session.env,
+        )
+    })?;
+
+    let upstream = format!("refs/remotes/origin/{}", branch);
+    if let Err(e) = git(Some(path), &["rebase", "--quiet", &upstream], &[]) {
+        let _ = git(Some(path), &["rebase", "--abort"], &[]);
+        return Err(GitError::new(
+            ErrorCode::MergeConflict,
+            ErrorClass::Merge,
+            format!(
+                "Cannot rebase onto origin/{}: conflicting changes ({})",
+                branch,
+                e.message()
+            ),
+        ));
+    }
+    Ok(())
+}
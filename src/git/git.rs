@@ -1,25 +1,245 @@
+use crate::codecommit::{aws_credentials, git_credentials, https_url, is_codecommit_url};
+use crate::fetch_stats::{FetchSample, fetch_stats};
+use crate::files::{ContainerTarget, tag_from_str};
 use crate::git::utils::create_signature;
+use crate::git::{GitCliProvider, GitoxideProvider};
+use crate::known_hosts::{HostKeyStatus, KnownHosts};
+use crate::metrics::GIT_RETRIES_TOTAL;
+use crate::traits::GitProvider;
+use crate::versions::GitTagPattern;
 use git2::{
-    Cred, Error as GitError, FetchOptions, RemoteCallbacks, Repository, build::RepoBuilder,
+    CertificateCheckStatus, Cred, CredentialType, Error as GitError, FetchOptions, RemoteCallbacks,
+    Repository, Signature,
 };
 
+use std::cell::Cell;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 use tracing::{debug, error, info, warn};
 
+/// Commit message used for manifest updates when no template is configured.
+pub const DEFAULT_COMMIT_MESSAGE: &str = "chore(refs): gitops-operator updating {app} to {new_sha}";
+
+/// Commit message used when a dependency rule pins a promoted image tag in
+/// another service's manifests.
+pub const DEPENDENCY_COMMIT_MESSAGE: &str =
+    "chore(refs): gitops-operator pinning {image} to {new_sha} after {app}";
+
+/// Commit message used when reverting a manifest through the rollback endpoint.
+pub const ROLLBACK_COMMIT_MESSAGE: &str =
+    "chore(refs): gitops-operator rolling back {app} to {new_sha}";
+
+const DEFAULT_PUSH_RETRIES: u32 = 3;
+
+const DEFAULT_GIT_MAX_RETRIES: u32 = 3;
+const DEFAULT_GIT_RETRY_BASE_DELAY_MS: u64 = 500;
+const DEFAULT_GIT_RETRY_JITTER_MS: u64 = 250;
+
+/// Where the bare mirrors checkouts are made from live, under
+/// [`repo_cache_dir`].
+pub const MIRRORS_DIR: &str = "gitops-operator-mirrors";
+
+/// Username sent with an HTTPS token when neither the secret nor the URL
+/// names one. GitHub accepts it for tokens; other forges accept any.
+pub const DEFAULT_HTTPS_USERNAME: &str = "x-access-token";
+
+/// A personal access (or app, or deploy) token for git over HTTPS.
+#[derive(Clone, Default, PartialEq)]
+pub struct HttpsToken {
+    /// Forges such as Bitbucket want the token owner's username.
+    pub username: Option<String>,
+    pub token: String,
+}
+
+/// What git authenticates to remotes with: the SSH key for `ssh://` and
+/// scp-like URLs, the token (if any) for `https://` ones. CodeCommit HTTPS
+/// URLs are signed with the operator's AWS credentials instead. SSH host
+/// keys are checked against `known_hosts`, when there is one.
+#[derive(Clone, Default, PartialEq)]
+pub struct GitCredentials {
+    pub ssh_key: String,
+    pub https_token: Option<HttpsToken>,
+    pub known_hosts: Option<KnownHosts>,
+    /// Refuse SSH hosts `known_hosts` doesn't list (all of them without
+    /// one) instead of leaving them to libgit2.
+    pub strict_host_keys: bool,
+}
+
+impl GitCredentials {
+    /// Credentials with only an SSH key.
+    pub fn ssh(ssh_key: &str) -> Self {
+        Self {
+            ssh_key: ssh_key.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// A hash telling these credentials apart from others without revealing
+    /// them, to key what was fetched with them.
+    pub fn identity(&self) -> String {
+        let token = self.https_token.as_ref();
+        let material = [
+            self.ssh_key.as_str(),
+            token
+                .and_then(|t| t.username.as_deref())
+                .unwrap_or_default(),
+            token.map(|t| t.token.as_str()).unwrap_or_default(),
+            &self
+                .known_hosts
+                .as_ref()
+                .map(|known_hosts| known_hosts.to_openssh())
+                .unwrap_or_default(),
+            if self.strict_host_keys { "strict" } else { "" },
+        ]
+        .join("\0");
+        git2::Oid::hash_object(git2::ObjectType::Blob, material.as_bytes())
+            .map(|oid| oid.to_string()[..12].to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Whether the SSH host key in `cert`, presented by `host`, may be trusted.
+/// TLS certificates are left to libgit2's own verification.
+fn check_host_key(
+    cert: &git2::cert::Cert<'_>,
+    host: &str,
+    known_hosts: Option<&KnownHosts>,
+    strict: bool,
+) -> Result<CertificateCheckStatus, GitError> {
+    let Some(hostkey) = cert.as_hostkey() else {
+        return Ok(CertificateCheckStatus::CertificatePassthrough);
+    };
+    let status = match known_hosts {
+        None => HostKeyStatus::Unknown,
+        Some(known_hosts) => match (hostkey.hostkey_type(), hostkey.hostkey()) {
+            (Some(key_type), Some(key)) => known_hosts.check(host, key_type.name(), key),
+            _ => match hostkey.hash_sha256() {
+                Some(digest) => known_hosts.check_sha256(host, digest),
+                None => HostKeyStatus::Unknown,
+            },
+        },
+    };
+    match status {
+        HostKeyStatus::Match => Ok(CertificateCheckStatus::CertificateOk),
+        HostKeyStatus::Mismatch => Err(GitError::from_str(&format!(
+            "The host key of {} doesn't match known_hosts",
+            host
+        ))),
+        HostKeyStatus::Revoked => Err(GitError::from_str(&format!(
+            "The host key of {} is revoked in known_hosts",
+            host
+        ))),
+        HostKeyStatus::Unknown if strict => Err(GitError::from_str(&format!(
+            "{} isn't in known_hosts and strict host key checking is on",
+            host
+        ))),
+        HostKeyStatus::Unknown => {
+            if known_hosts.is_some() {
+                warn!("{} isn't in known_hosts, trusting its host key", host);
+            }
+            Ok(CertificateCheckStatus::CertificatePassthrough)
+        }
+    }
+}
+
 pub trait DefaultCallbacks<'a> {
-    fn prepare_callbacks(&mut self, ssh_key: String) -> &Self;
+    fn prepare_callbacks(&mut self, credentials: GitCredentials) -> &Self;
 }
 
 impl<'a> DefaultCallbacks<'a> for RemoteCallbacks<'a> {
-    fn prepare_callbacks(&mut self, ssh_key: String) -> &Self {
-        self.credentials(move |_url, username_from_url, _allowed_types| {
-            Cred::ssh_key_from_memory(username_from_url.unwrap_or("git"), None, &ssh_key, None)
+    fn prepare_callbacks(&mut self, credentials: GitCredentials) -> &Self {
+        let known_hosts = credentials.known_hosts.clone();
+        let strict = credentials.strict_host_keys;
+        self.certificate_check(move |cert, host| {
+            check_host_key(cert, host, known_hosts.as_ref(), strict)
+        });
+
+        // libgit2 asks again when the remote refuses what it got.
+        let mut asked = false;
+        self.credentials(move |url, username_from_url, allowed_types| {
+            if !allowed_types.contains(CredentialType::USER_PASS_PLAINTEXT) {
+                return Cred::ssh_key_from_memory(
+                    username_from_url.unwrap_or("git"),
+                    None,
+                    &credentials.ssh_key,
+                    None,
+                );
+            }
+            if asked {
+                return Err(GitError::from_str(&format!(
+                    "{} refused the HTTPS credentials",
+                    url
+                )));
+            }
+            asked = true;
+
+            // CodeCommit over HTTPS: sign with the operator's AWS credentials.
+            if is_codecommit_url(url) {
+                let aws = aws_credentials().map_err(|e| GitError::from_str(&format!("{:#}", e)))?;
+                let (username, password) = git_credentials(url, &aws, std::time::SystemTime::now())
+                    .ok_or_else(|| GitError::from_str("Not a CodeCommit HTTPS URL"))?;
+                return Cred::userpass_plaintext(&username, &password);
+            }
+            let Some(https) = &credentials.https_token else {
+                return Err(GitError::from_str(&format!(
+                    "{} needs an HTTPS token (gitops.operator.https_token_secret_name)",
+                    url
+                )));
+            };
+            let username = https
+                .username
+                .as_deref()
+                .or(username_from_url)
+                .unwrap_or(DEFAULT_HTTPS_USERNAME);
+            Cred::userpass_plaintext(username, &https.token)
         });
         self
     }
 }
 
+/// Bytes and objects received by a transfer so far.
+#[derive(Clone, Copy, Default)]
+struct Transfer {
+    bytes: usize,
+    objects: usize,
+}
+
+/// Callbacks authenticating with `credentials` and counting what a transfer
+/// receives into `transfer`.
+fn fetch_callbacks<'a>(
+    credentials: &GitCredentials,
+    transfer: &'a Cell<Transfer>,
+) -> RemoteCallbacks<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.prepare_callbacks(credentials.clone());
+    callbacks.transfer_progress(move |progress| {
+        transfer.set(Transfer {
+            bytes: progress.received_bytes(),
+            objects: progress.received_objects(),
+        });
+        true
+    });
+    callbacks
+}
+
+/// Add a clone or fetch of `url` that started at `started` to the fetch stats.
+fn record_fetch(url: &str, started: Instant, transfer: Transfer, clone: bool, ok: bool) {
+    fetch_stats().record(
+        url,
+        FetchSample::new(
+            started.elapsed(),
+            transfer.bytes as u64,
+            transfer.objects as u64,
+            clone,
+            ok,
+        ),
+    );
+}
+
 fn normal_merge(
     repo: &Repository,
     local: &git2::AnnotatedCommit,
@@ -57,21 +277,94 @@ fn normal_merge(
     Ok(())
 }
 
+/// How much of a repository is cloned and fetched
+/// (`gitops.operator.clone_strategy`). libgit2 has no partial clone, so
+/// blobs can't be left out; the history and the other branches can.
+#[derive(serde::Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CloneStrategy {
+    /// Every branch and tag with their whole history.
+    #[default]
+    Full,
+    /// Only the tracked branch, without tags.
+    SingleBranch,
+    /// Only the tip of the tracked branch, without tags. The manifest
+    /// history (and the rollbacks picking from it) then only reaches back
+    /// to the first commit fetched. Needs an SSH or HTTPS remote: libgit2
+    /// can't fetch shallow from a local path.
+    Shallow,
+}
+
+impl FromStr for CloneStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim() {
+            "full" => Ok(CloneStrategy::Full),
+            "single_branch" => Ok(CloneStrategy::SingleBranch),
+            "shallow" => Ok(CloneStrategy::Shallow),
+            other => anyhow::bail!(
+                "Invalid clone strategy '{}'. Must be 'full', 'single_branch' or 'shallow'",
+                other
+            ),
+        }
+    }
+}
+
+impl CloneStrategy {
+    /// The operator-wide strategy (`GITOPS_CLONE_STRATEGY`), for deployments
+    /// that don't set one.
+    pub fn from_env() -> Self {
+        match std::env::var("GITOPS_CLONE_STRATEGY").map(|value| value.parse()) {
+            Ok(Ok(strategy)) => strategy,
+            Ok(Err(e)) => {
+                warn!("{:#}, cloning in full", e);
+                CloneStrategy::default()
+            }
+            Err(_) => CloneStrategy::default(),
+        }
+    }
+
+    /// Apply the strategy's tag and depth limits to `fetch_options`.
+    fn configure(&self, fetch_options: &mut FetchOptions) {
+        match self {
+            CloneStrategy::Full => {
+                fetch_options.download_tags(git2::AutotagOption::All);
+            }
+            CloneStrategy::SingleBranch => {
+                fetch_options.download_tags(git2::AutotagOption::None);
+            }
+            CloneStrategy::Shallow => {
+                fetch_options.download_tags(git2::AutotagOption::None);
+                fetch_options.depth(1);
+            }
+        }
+    }
+}
+
 pub fn clone_or_update_repo(
     url: &str,
     repo_path: PathBuf,
     branch: &str,
-    ssh_key: &str,
+    credentials: &GitCredentials,
 ) -> Result<Repository, GitError> {
-    info!("Cloning or updating repository from: {}", &url);
-
-    let mut callbacks = RemoteCallbacks::new();
-    callbacks.prepare_callbacks(ssh_key.to_string());
+    clone_or_update_repo_with(url, repo_path, branch, credentials, CloneStrategy::Full)
+}
 
-    // Prepare fetch options
-    let mut fetch_options = FetchOptions::new();
-    fetch_options.remote_callbacks(callbacks);
-    fetch_options.download_tags(git2::AutotagOption::All);
+/// Like [`clone_or_update_repo`], cloning and fetching as little as
+/// `strategy` asks for.
+pub fn clone_or_update_repo_with(
+    url: &str,
+    repo_path: PathBuf,
+    branch: &str,
+    credentials: &GitCredentials,
+    strategy: CloneStrategy,
+) -> Result<Repository, GitError> {
+    info!(
+        "Cloning or updating repository from: {} ({:?})",
+        &url, strategy
+    );
+    let policy = GitRetryPolicy::from_env();
 
     // Check if repository already exists
     if repo_path.exists() {
@@ -84,7 +377,16 @@ pub fn clone_or_update_repo(
         let repo = Repository::open(&repo_path)?;
 
         // Fetch changes
-        fetch_existing_repo(&repo, &mut fetch_options, branch)?;
+        retry_transient(&policy, "fetch", &repo_path, || {
+            let transfer = Cell::new(Transfer::default());
+            let mut fetch_options = FetchOptions::new();
+            fetch_options.remote_callbacks(fetch_callbacks(credentials, &transfer));
+            strategy.configure(&mut fetch_options);
+            let started = Instant::now();
+            let fetched = fetch_existing_repo(&repo, &mut fetch_options, branch);
+            record_fetch(url, started, transfer.get(), false, fetched.is_ok());
+            fetched
+        })?;
         pull_repo(&repo, branch)?;
 
         // Pull changes (merge)
@@ -96,7 +398,16 @@ pub fn clone_or_update_repo(
         );
 
         // Clone new repository
-        clone_new_repo(url, &repo_path, fetch_options)
+        retry_transient(&policy, "clone", &repo_path, || {
+            let transfer = Cell::new(Transfer::default());
+            let mut fetch_options = FetchOptions::new();
+            fetch_options.remote_callbacks(fetch_callbacks(credentials, &transfer));
+            strategy.configure(&mut fetch_options);
+            let started = Instant::now();
+            let cloned = clone_new_repo(url, &repo_path, branch, strategy, fetch_options);
+            record_fetch(url, started, transfer.get(), true, cloned.is_ok());
+            cloned
+        })
     }
 }
 
@@ -113,6 +424,8 @@ fn fetch_existing_repo(
 
     // Find the origin remote
     let mut remote = repo.find_remote("origin")?;
+    let refs = refs_lock(repo.commondir());
+    let _refs = refs.lock().unwrap_or_else(|e| e.into_inner());
 
     // Fetch all branches
     let refs = &[format!(
@@ -125,19 +438,158 @@ fn fetch_existing_repo(
     Ok(())
 }
 
-/// Clone a new repository
+/// Directory the mirrors and checkouts are kept in: `GITOPS_REPO_CACHE_DIR`
+/// (e.g. a mounted volume, so they survive restarts) or the system's
+/// temporary directory.
+pub fn repo_cache_dir() -> PathBuf {
+    std::env::var("GITOPS_REPO_CACHE_DIR")
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// Where the bare mirror of `url` is kept. Every checkout of a remote
+/// shares its mirror, so its objects are stored and fetched once; the name
+/// ends with a hash of the URL so remotes sharing a name don't collide.
+pub fn mirror_path(url: &str) -> PathBuf {
+    let url = url.trim().trim_end_matches('/');
+    let name = url
+        .trim_end_matches(".git")
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or_default();
+    repo_cache_dir()
+        .join(MIRRORS_DIR)
+        .join(format!("{}.git", hashed_name(name, url)))
+}
+
+/// The lock serializing fetches and pushes into the repository whose
+/// common directory (a mirror, for its worktrees) is `common_dir`: libgit2
+/// fails instead of waiting on a ref another fetch has locked, and only one
+/// clone may create a mirror. Held too while a mirror is pruned, so it
+/// isn't deleted under a clone adding a worktree to it.
+pub fn refs_lock(common_dir: &Path) -> Arc<Mutex<()>> {
+    static LOCKS: OnceLock<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> = OnceLock::new();
+    let mut locks = LOCKS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    locks.entry(common_dir.to_path_buf()).or_default().clone()
+}
+
+/// `name` made safe for a file or ref name, followed by a short hash of
+/// `key`.
+pub fn hashed_name(name: &str, key: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let hash = git2::Oid::hash_object(git2::ObjectType::Blob, key.as_bytes())
+        .map(|oid| oid.to_string()[..12].to_string())
+        .unwrap_or_default();
+    format!("{}-{}", name, hash)
+}
+
+/// Fetch `branch` (every branch and tag for [`CloneStrategy::Full`]) into
+/// the bare mirror of `url`, creating the mirror on first use. The mirror
+/// keeps what any of its checkouts asked for.
+fn update_mirror(
+    url: &str,
+    branch: &str,
+    strategy: CloneStrategy,
+    mut fetch_options: FetchOptions,
+) -> Result<Repository, GitError> {
+    let path = mirror_path(url);
+    let mirror = match Repository::open_bare(&path) {
+        Ok(mirror) => mirror,
+        Err(_) => {
+            info!("Creating mirror of {} in {}", url, path.display());
+            let mirror = Repository::init_bare(&path)?;
+            let url = https_url(url).unwrap_or_else(|| url.to_string());
+            mirror.remote_with_fetch("origin", &url, "+refs/heads/*:refs/remotes/origin/*")?;
+            mirror
+        }
+    };
+
+    {
+        let mut remote = mirror.find_remote("origin")?;
+        let refspec = match strategy {
+            CloneStrategy::Full => "+refs/heads/*:refs/remotes/origin/*".to_string(),
+            _ => format!("+refs/heads/{}:refs/remotes/origin/{}", branch, branch),
+        };
+        remote.fetch(&[&refspec], Some(&mut fetch_options), None)?;
+
+        // What `refs/remotes/origin/HEAD` points at marks the default branch
+        // (see `is_protected_branch`).
+        if strategy == CloneStrategy::Full
+            && let Ok(default) = remote.default_branch()
+            && let Some(name) = default
+                .as_str()
+                .ok()
+                .and_then(|name| name.strip_prefix("refs/heads/"))
+        {
+            mirror.reference_symbolic(
+                "refs/remotes/origin/HEAD",
+                &format!("refs/remotes/origin/{}", name),
+                true,
+                "mirror",
+            )?;
+        }
+    }
+    Ok(mirror)
+}
+
+/// Check out `branch` of `url` at `local_path`, as a worktree of the
+/// remote's mirror with a detached HEAD (a branch can only be checked out
+/// in one worktree, and several deployments may track the same one).
 fn clone_new_repo(
     url: &str,
     local_path: &Path,
+    branch: &str,
+    strategy: CloneStrategy,
     fetch_options: FetchOptions,
 ) -> Result<Repository, GitError> {
     info!("Cloning repository from: {}", &url);
-    // Prepare repository builder
-    let mut repo_builder = RepoBuilder::new();
-    repo_builder.fetch_options(fetch_options);
+    let refs = refs_lock(&mirror_path(url));
+    let _refs = refs.lock().unwrap_or_else(|e| e.into_inner());
+    let mirror = update_mirror(url, branch, strategy, fetch_options)?;
+
+    let name = hashed_name(
+        &local_path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default(),
+        &local_path.display().to_string(),
+    );
+    // The checkout was deleted since; its worktree is stale.
+    if let Ok(stale) = mirror.find_worktree(&name) {
+        stale.prune(Some(git2::WorktreePruneOptions::new().valid(true)))?;
+    }
+    if let Some(parent) = local_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| GitError::from_str(&e.to_string()))?;
+    }
 
-    // Clone the repository
-    repo_builder.clone(url, local_path)
+    let commit = mirror
+        .find_reference(&format!("refs/remotes/origin/{}", branch))?
+        .peel_to_commit()?;
+    // Worktrees start on a branch, so check out a throwaway one and detach.
+    let mut checkout = mirror.branch(&name, &commit, true)?;
+    {
+        let mut options = git2::WorktreeAddOptions::new();
+        options.reference(Some(checkout.get()));
+        mirror.worktree(&name, local_path, Some(&options))?;
+    }
+    let repo = Repository::open(local_path)?;
+    repo.set_head_detached(commit.id())?;
+    checkout.delete()?;
+    Ok(repo)
 }
 
 /// Pull (merge) changes into the current branch
@@ -174,7 +626,9 @@ fn pull_repo(repo: &Repository, branch: &str) -> Result<(), GitError> {
         let refname = format!("refs/remotes/origin/{}", branch);
         let mut reference = repo.find_reference(&refname)?;
         reference.set_target(fetch_commit.id(), "Fast-Forward")?;
-        repo.set_head(&refname)?;
+        // Checkouts stay detached: HEAD attached to the remote-tracking ref
+        // would move with every fetch into the mirror its worktrees share.
+        repo.set_head_detached(fetch_commit.id())?;
         repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
 
         Ok(())
@@ -191,24 +645,300 @@ fn pull_repo(repo: &Repository, branch: &str) -> Result<(), GitError> {
     }
 }
 
-#[tracing::instrument(name = "stage_and_push_changes", skip(repo, ssh_key), fields())]
+#[tracing::instrument(name = "stage_and_push_changes", skip(repo, credentials), fields())]
 pub fn stage_and_push_changes(
     repo: &Repository,
     commit_message: &str,
     branch: &str,
-    ssh_key: &str,
+    credentials: &GitCredentials,
+) -> Result<(), GitError> {
+    stage_and_push_changes_as(repo, commit_message, branch, credentials, None)
+}
+
+/// How many times a push the remote refuses as non-fast-forward is rebased
+/// onto the remote branch and retried (`GITOPS_PUSH_RETRIES`, default 3,
+/// 0 disables retries).
+pub fn push_retries() -> u32 {
+    std::env::var("GITOPS_PUSH_RETRIES")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(DEFAULT_PUSH_RETRIES)
+}
+
+/// How clones, fetches and pushes are retried after a transient failure
+/// (see [`is_transient`]).
+#[derive(Clone, Debug, PartialEq)]
+pub struct GitRetryPolicy {
+    /// `GITOPS_GIT_MAX_RETRIES` (default 3, 0 disables retries).
+    pub max_retries: u32,
+    /// `GITOPS_GIT_RETRY_BASE_DELAY_MS` (default 500): the first backoff,
+    /// doubled on every further attempt.
+    pub base_delay: Duration,
+    /// `GITOPS_GIT_RETRY_JITTER_MS` (default 250): up to this much is added
+    /// at random to every wait, so checkouts that failed together don't
+    /// retry together.
+    pub jitter: Duration,
+}
+
+impl Default for GitRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_GIT_MAX_RETRIES,
+            base_delay: Duration::from_millis(DEFAULT_GIT_RETRY_BASE_DELAY_MS),
+            jitter: Duration::from_millis(DEFAULT_GIT_RETRY_JITTER_MS),
+        }
+    }
+}
+
+impl GitRetryPolicy {
+    pub fn from_env() -> Self {
+        fn env<T: FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok().and_then(|v| v.trim().parse().ok())
+        }
+
+        let default = Self::default();
+        Self {
+            max_retries: env("GITOPS_GIT_MAX_RETRIES").unwrap_or(default.max_retries),
+            base_delay: env("GITOPS_GIT_RETRY_BASE_DELAY_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(default.base_delay),
+            jitter: env("GITOPS_GIT_RETRY_JITTER_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(default.jitter),
+        }
+    }
+
+    /// How long to wait before retry number `retry` (0-based): exponential
+    /// backoff plus up to `jitter`.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry.min(16)));
+        let jitter = match self.jitter.as_millis() as u64 {
+            0 => 0,
+            max => (uuid::Uuid::new_v4().as_u128() % u128::from(max)) as u64,
+        };
+        backoff.saturating_add(Duration::from_millis(jitter))
+    }
+}
+
+/// Whether `e` is a network blip worth retrying: a timeout, or a transport
+/// error other than a refused login or a missing repository.
+pub fn is_transient(e: &GitError) -> bool {
+    use git2::{ErrorClass, ErrorCode};
+
+    let message = e.message().to_lowercase();
+    match (e.code(), e.class()) {
+        (ErrorCode::Timeout, _) => true,
+        (ErrorCode::Auth | ErrorCode::Certificate | ErrorCode::NotFastForward, _) => false,
+        (_, ErrorClass::Net) => true,
+        (_, ErrorClass::Http | ErrorClass::Ssh) => {
+            !message.contains("authenticat") && !message.contains("status code: 4")
+        }
+        _ => false,
+    }
+}
+
+/// Retries made per checkout, until taken by [`take_git_retries`].
+fn git_retries() -> &'static Mutex<HashMap<PathBuf, u32>> {
+    static RETRIES: OnceLock<Mutex<HashMap<PathBuf, u32>>> = OnceLock::new();
+    RETRIES.get_or_init(Default::default)
+}
+
+/// `path` without a trailing separator, so both spellings count together.
+fn checkout_key(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+/// How many times git operations on the checkout at `path` were retried
+/// since the last call.
+pub fn take_git_retries(path: &Path) -> u32 {
+    git_retries()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&checkout_key(path))
+        .unwrap_or_default()
+}
+
+/// Run `operation` (a clone, fetch or push) on the checkout at `checkout`,
+/// retrying it as `policy` says while it fails transiently. Retries are
+/// counted by `gitops_operator_git_retries_total{operation}` and against
+/// the checkout (see [`take_git_retries`]).
+pub fn retry_transient<T>(
+    policy: &GitRetryPolicy,
+    operation: &str,
+    checkout: &Path,
+    mut run: impl FnMut() -> Result<T, GitError>,
+) -> Result<T, GitError> {
+    let mut retry = 0;
+    loop {
+        match run() {
+            Err(e) if retry < policy.max_retries && is_transient(&e) => {
+                let delay = policy.delay(retry);
+                warn!(
+                    "{} of {} failed ({}), retrying in {:?} ({}/{})",
+                    operation,
+                    checkout.display(),
+                    e,
+                    delay,
+                    retry + 1,
+                    policy.max_retries
+                );
+                ::metrics::counter!(GIT_RETRIES_TOTAL, "operation" => operation.to_string())
+                    .increment(1);
+                *git_retries()
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .entry(checkout_key(checkout))
+                    .or_default() += 1;
+                std::thread::sleep(delay);
+                retry += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Like [`stage_and_push_changes`], but records `author` as the commit author
+/// when given, keeping the operator as the committer.
+///
+/// When someone else pushed to `branch` in the meantime (e.g. another
+/// deployment sharing the manifest repository), the commit is rebased onto
+/// what they pushed and pushed again, up to [`push_retries`] times.
+pub fn stage_and_push_changes_as(
+    repo: &Repository,
+    commit_message: &str,
+    branch: &str,
+    credentials: &GitCredentials,
+    author: Option<&CommitAuthor>,
+) -> Result<(), GitError> {
+    if commit_all(repo, commit_message, author)? {
+        push_rebasing(repo, branch, credentials)?;
+    }
+    Ok(())
+}
+
+/// Push HEAD to `branch`, rebasing it onto what others pushed there in the
+/// meantime and pushing again, up to [`push_retries`] times.
+fn push_rebasing(
+    repo: &Repository,
+    branch: &str,
+    credentials: &GitCredentials,
+) -> Result<(), GitError> {
+    let refspec = format!("HEAD:refs/heads/{}", branch);
+    let mut pushed = push(repo, &refspec, credentials);
+
+    let retries = push_retries();
+    for attempt in 1..=retries {
+        match &pushed {
+            Err(e) if e.code() == git2::ErrorCode::NotFastForward => {}
+            _ => break,
+        }
+        warn!(
+            "Push to {} rejected as non-fast-forward, rebasing and retrying ({}/{})",
+            branch, attempt, retries
+        );
+        rebase_onto_remote(repo, branch, credentials)?;
+        pushed = push(repo, &refspec, credentials);
+    }
+    pushed
+}
+
+/// Fetch `branch` from origin and replay the commit at HEAD on top of it,
+/// keeping its author and message. Fails when the remote changed the same
+/// lines.
+fn rebase_onto_remote(
+    repo: &Repository,
+    branch: &str,
+    credentials: &GitCredentials,
 ) -> Result<(), GitError> {
+    retry_transient(
+        &GitRetryPolicy::from_env(),
+        "fetch",
+        repo.workdir().unwrap_or(repo.path()),
+        || {
+            let transfer = Cell::new(Transfer::default());
+            let mut fetch_options = FetchOptions::new();
+            fetch_options.remote_callbacks(fetch_callbacks(credentials, &transfer));
+            fetch_existing_repo(repo, &mut fetch_options, branch)
+        },
+    )?;
+
+    let upstream = repo
+        .find_reference(&format!("refs/remotes/origin/{}", branch))?
+        .peel_to_commit()?;
+    let local = repo.head()?.peel_to_commit()?;
+    if repo.graph_descendant_of(local.id(), upstream.id())? {
+        return Ok(());
+    }
+
+    let mut index = repo.cherrypick_commit(&local, &upstream, 0, None)?;
+    if index.has_conflicts() {
+        return Err(GitError::new(
+            git2::ErrorCode::MergeConflict,
+            git2::ErrorClass::Merge,
+            format!(
+                "Cannot rebase {} onto origin/{} ({}): conflicting changes",
+                local.id(),
+                branch,
+                upstream.id()
+            ),
+        ));
+    }
+    let tree = repo.find_tree(index.write_tree_to(repo)?)?;
+    let rebased = repo.commit(
+        None,
+        &local.author(),
+        &create_signature()?,
+        &String::from_utf8_lossy(local.message_bytes()),
+        &tree,
+        &[&upstream],
+    )?;
     info!(
-        "Staging and pushing changes for: {}",
-        &repo.path().display()
+        "Rebased {} onto {} as {}",
+        local.id(),
+        upstream.id(),
+        rebased
     );
 
+    repo.reset(
+        &repo.find_object(rebased, None)?,
+        git2::ResetType::Hard,
+        None,
+    )
+}
+
+/// Commit every change in `repo` on top of HEAD and push it with `refspec`.
+/// Nothing is committed or pushed when the tree matches HEAD.
+fn commit_and_push(
+    repo: &Repository,
+    commit_message: &str,
+    refspec: &str,
+    credentials: &GitCredentials,
+    author: Option<&CommitAuthor>,
+) -> Result<(), GitError> {
+    if commit_all(repo, commit_message, author)? {
+        push(repo, refspec, credentials)?;
+    }
+    Ok(())
+}
+
+/// Commit every change in `repo` on top of HEAD, returning whether there was
+/// any. A tree matching HEAD's is not committed, nor is an index with
+/// conflicts (those are reset instead).
+fn commit_all(
+    repo: &Repository,
+    commit_message: &str,
+    author: Option<&CommitAuthor>,
+) -> Result<bool, GitError> {
+    info!("Staging changes for: {}", &repo.path().display());
+
     // Stage all changes (equivalent to git add .)
     let mut index = repo.index()?;
     if index.has_conflicts() {
         warn!("Merge conflicts detected for {}", &repo.path().display());
         repo.checkout_index(Some(&mut index), None)?;
-        return Ok(());
+        return Ok(false);
     }
 
     index.add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)?;
@@ -228,20 +958,24 @@ pub fn stage_and_push_changes(
             "No changes detected against HEAD ({}); skipping commit and push",
             parent_commit.id()
         );
-        return Ok(());
+        return Ok(false);
     }
 
     info!("Parent commit: {}", parent_commit.id());
 
     // Prepare signature (author and committer)
     let signature = create_signature()?;
+    let author_signature = match author {
+        Some(author) => Signature::new(&author.name, &author.email, &signature.when())?,
+        None => signature.clone(),
+    };
 
-    info!("Author: {}", signature.name().unwrap_or("<unknown>"));
+    info!("Author: {}", author_signature.name().unwrap_or("<unknown>"));
 
     // Create the commit
     let commit_oid = repo.commit(
         Some("HEAD"),      // Update HEAD reference
-        &signature,        // Author
+        &author_signature, // Author
         &signature,        // Committer
         commit_message,    // Commit message
         &tree,             // Tree to commit
@@ -249,57 +983,460 @@ pub fn stage_and_push_changes(
     )?;
 
     info!("New commit: {}", commit_oid);
+    Ok(true)
+}
 
-    // Prepare push credentials
-    let mut callbacks = RemoteCallbacks::new();
-    callbacks.prepare_callbacks(ssh_key.to_string());
-
-    // Prepare push options
-    let mut push_options = git2::PushOptions::new();
-    push_options.remote_callbacks(callbacks);
+/// Push `refspec` to origin, failing with [`git2::ErrorCode::NotFastForward`]
+/// when the remote refuses the update.
+fn push(repo: &Repository, refspec: &str, credentials: &GitCredentials) -> Result<(), GitError> {
+    retry_transient(
+        &GitRetryPolicy::from_env(),
+        "push",
+        repo.workdir().unwrap_or(repo.path()),
+        || push_once(repo, refspec, credentials),
+    )
+}
 
+fn push_once(
+    repo: &Repository,
+    refspec: &str,
+    credentials: &GitCredentials,
+) -> Result<(), GitError> {
     // Find the origin remote
     let mut remote = repo.find_remote("origin")?;
+    let refs = refs_lock(repo.commondir());
+    let _refs = refs.lock().unwrap_or_else(|e| e.into_inner());
 
     info!("Pushing to remote: {}", remote.url().unwrap_or("<unknown>"));
 
-    // Push the local HEAD to the configured branch on the remote.
-    let refspec = format!("HEAD:refs/heads/{}", branch);
+    info!("Pushing to remote branch: {}", refspec);
+
+    // A remote that refuses the update (e.g. a non-fast-forward after someone
+    // else pushed) still completes the push; it only reports the ref status.
+    let mut rejection = None;
+    {
+        // Prepare push credentials
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.prepare_callbacks(credentials.clone());
+        callbacks.push_update_reference(|refname, status| {
+            if let Some(status) = status {
+                rejection = Some(format!("{} rejected by remote: {}", refname, status));
+            }
+            Ok(())
+        });
+
+        // Prepare push options
+        let mut push_options = git2::PushOptions::new();
+        push_options.remote_callbacks(callbacks);
+
+        // Push changes
+        remote.push(&[refspec], Some(&mut push_options))?;
+    }
+
+    match rejection {
+        Some(rejection) => Err(GitError::new(
+            git2::ErrorCode::NotFastForward,
+            git2::ErrorClass::Reference,
+            rejection,
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Which implementation of [`GitProvider`] reconciles work with
+/// (`GITOPS_GIT_BACKEND`).
+#[derive(serde::Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GitBackend {
+    /// libgit2, through the `git2` crate.
+    #[default]
+    LibGit2,
+    /// gitoxide, in pure Rust (see [`GitoxideProvider`]).
+    Gitoxide,
+    /// The `git` binary (see [`GitCliProvider`]).
+    #[serde(rename = "git")]
+    Cli,
+}
+
+impl FromStr for GitBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim() {
+            "libgit2" => Ok(GitBackend::LibGit2),
+            "gitoxide" => Ok(GitBackend::Gitoxide),
+            "git" => Ok(GitBackend::Cli),
+            other => anyhow::bail!(
+                "Invalid git backend '{}'. Must be 'libgit2', 'gitoxide' or 'git'",
+                other
+            ),
+        }
+    }
+}
+
+impl GitBackend {
+    /// The backend set with `GITOPS_GIT_BACKEND`, libgit2 when unset.
+    pub fn from_env() -> Self {
+        match std::env::var("GITOPS_GIT_BACKEND").map(|value| value.parse()) {
+            Ok(Ok(backend)) => backend,
+            Ok(Err(e)) => {
+                warn!("{:#}, using libgit2", e);
+                GitBackend::default()
+            }
+            Err(_) => GitBackend::default(),
+        }
+    }
+
+    pub fn provider(&self) -> Arc<dyn GitProvider> {
+        match self {
+            GitBackend::LibGit2 => Arc::new(LibGit2Provider),
+            GitBackend::Gitoxide => Arc::new(GitoxideProvider),
+            GitBackend::Cli => Arc::new(GitCliProvider),
+        }
+    }
+}
+
+/// [`GitProvider`] on libgit2: checkouts are worktrees of a mirror per
+/// remote (see [`mirror_path`]).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LibGit2Provider;
+
+impl GitProvider for LibGit2Provider {
+    fn name(&self) -> &'static str {
+        "libgit2"
+    }
+
+    fn clone_repo(
+        &self,
+        url: &str,
+        path: &Path,
+        branch: &str,
+        credentials: &GitCredentials,
+        strategy: CloneStrategy,
+    ) -> Result<(), GitError> {
+        clone_or_update_repo_with(url, path.to_path_buf(), branch, credentials, strategy)
+            .map(|_| ())
+    }
+
+    fn fetch(
+        &self,
+        url: &str,
+        path: &Path,
+        branch: &str,
+        credentials: &GitCredentials,
+        strategy: CloneStrategy,
+    ) -> Result<(), GitError> {
+        clone_or_update_repo_with(url, path.to_path_buf(), branch, credentials, strategy)
+            .map(|_| ())
+    }
 
-    info!("Pushing to remote branch: {}", &refspec);
+    fn resolve(&self, path: &Path, branch: &str) -> Result<String, GitError> {
+        let repo = Repository::open(path)?;
+        let commit = repo
+            .find_reference(&format!("refs/remotes/origin/{}", branch))?
+            .peel_to_commit()?;
+        Ok(commit.id().to_string())
+    }
+
+    fn commit(
+        &self,
+        path: &Path,
+        message: &str,
+        author: Option<&CommitAuthor>,
+    ) -> Result<bool, GitError> {
+        commit_all(&Repository::open(path)?, message, author)
+    }
 
-    // Push changes
-    remote.push(&[&refspec], Some(&mut push_options))
+    fn push(
+        &self,
+        path: &Path,
+        branch: &str,
+        credentials: &GitCredentials,
+        force: bool,
+    ) -> Result<(), GitError> {
+        let repo = Repository::open(path)?;
+        if !force {
+            return push_rebasing(&repo, branch, credentials);
+        }
+        if is_protected_branch(&repo, branch) {
+            warn!("Not force pushing to protected branch {}", branch);
+            return push_rebasing(&repo, branch, credentials);
+        }
+        push(&repo, &format!("+HEAD:refs/heads/{}", branch), credentials)
+    }
+}
+
+pub fn clone_repo(url: &str, local_path: &str, branch: &str, credentials: &GitCredentials) {
+    clone_repo_with(url, local_path, branch, credentials, CloneStrategy::Full)
 }
 
-#[tracing::instrument(name = "clone_repo", skip(ssh_key), fields())]
-pub fn clone_repo(url: &str, local_path: &str, branch: &str, ssh_key: &str) {
-    let repo_path = PathBuf::from(local_path);
+/// Like [`clone_repo`], cloning and fetching as little as `strategy` asks for.
+#[tracing::instrument(name = "clone_repo", skip(credentials), fields())]
+pub fn clone_repo_with(
+    url: &str,
+    local_path: &str,
+    branch: &str,
+    credentials: &GitCredentials,
+    strategy: CloneStrategy,
+) {
+    sync_repo(
+        &LibGit2Provider,
+        url,
+        local_path,
+        branch,
+        credentials,
+        strategy,
+    )
+}
 
-    match clone_or_update_repo(url, repo_path, branch, ssh_key) {
-        Ok(_) => info!("Repository successfully updated: {}", &local_path),
-        Err(e) => error!("Error updating repository: {}", e),
+/// Like [`clone_repo_with`], cloning or fetching with `git`.
+pub fn sync_repo(
+    git: &dyn GitProvider,
+    url: &str,
+    local_path: &str,
+    branch: &str,
+    credentials: &GitCredentials,
+    strategy: CloneStrategy,
+) {
+    match git.sync(url, Path::new(local_path), branch, credentials, strategy) {
+        Ok(()) => info!("Repository successfully updated: {}", &local_path),
+        Err(e) => error!("Error updating repository with {}: {}", git.name(), e),
     }
 }
 
-#[tracing::instrument(name = "commit_changes", skip(ssh_key), fields())]
+#[tracing::instrument(name = "commit_changes", skip(credentials), fields())]
 pub fn commit_changes(
     manifest_repo_path: &str,
     branch: &str,
-    ssh_key: &str,
+    commit_message: &str,
+    credentials: &GitCredentials,
+    author: Option<&CommitAuthor>,
 ) -> Result<(), GitError> {
-    let commit_message = "chore(refs): gitops-operator updating image tags";
     let manifest_repo = Repository::open(manifest_repo_path)?;
 
-    stage_and_push_changes(&manifest_repo, commit_message, branch, ssh_key)
+    stage_and_push_changes_as(&manifest_repo, commit_message, branch, credentials, author)
+}
+
+/// Branches never force pushed to, on top of the remote's default branch.
+pub const PROTECTED_BRANCHES: &[&str] = &["main", "master"];
+
+/// Whether `branch` is one of [`PROTECTED_BRANCHES`] or the default branch
+/// of `repo`'s origin (what `refs/remotes/origin/HEAD` points at).
+pub fn is_protected_branch(repo: &Repository, branch: &str) -> bool {
+    if PROTECTED_BRANCHES.contains(&branch) {
+        return true;
+    }
+    repo.find_reference("refs/remotes/origin/HEAD")
+        .ok()
+        .and_then(|head| head.symbolic_target().ok().flatten().map(str::to_string))
+        .is_some_and(|target| target == format!("refs/remotes/origin/{}", branch))
 }
 
-#[tracing::instrument(name = "get_latest_commit", skip(ssh_key), fields())]
+/// Like [`commit_changes`], but replaces whatever `branch` holds on the
+/// remote instead of failing when it was rewritten (e.g. a rebased preview
+/// branch). Protected branches (see [`is_protected_branch`]) are pushed to
+/// normally.
+#[tracing::instrument(name = "force_commit_changes", skip(credentials), fields())]
+pub fn force_commit_changes(
+    manifest_repo_path: &str,
+    branch: &str,
+    commit_message: &str,
+    credentials: &GitCredentials,
+    author: Option<&CommitAuthor>,
+) -> Result<(), GitError> {
+    let manifest_repo = Repository::open(manifest_repo_path)?;
+    if is_protected_branch(&manifest_repo, branch) {
+        warn!("Not force pushing to protected branch {}", branch);
+        return stage_and_push_changes_as(
+            &manifest_repo,
+            commit_message,
+            branch,
+            credentials,
+            author,
+        );
+    }
+
+    let refspec = format!("+HEAD:refs/heads/{}", branch);
+    commit_and_push(
+        &manifest_repo,
+        commit_message,
+        &refspec,
+        credentials,
+        author,
+    )
+}
+
+/// Commit the changes in the repository at `manifest_repo_path` and push them
+/// to the branch `head` rather than the one checked out, replacing whatever
+/// an earlier pass pushed there. The checkout is then reset to where it was,
+/// so it keeps following its branch until the change is merged. Returns
+/// whether anything was committed.
+pub fn push_to_branch(
+    manifest_repo_path: &str,
+    head: &str,
+    commit_message: &str,
+    credentials: &GitCredentials,
+    author: Option<&CommitAuthor>,
+) -> Result<bool, GitError> {
+    let repo = Repository::open(manifest_repo_path)?;
+    let base = repo.head()?.peel_to_commit()?;
+
+    let refspec = format!("+HEAD:refs/heads/{}", head);
+    let pushed = commit_and_push(&repo, commit_message, &refspec, credentials, author);
+    let committed = repo.head()?.peel_to_commit()?.id() != base.id();
+    if committed {
+        repo.reset(base.as_object(), git2::ResetType::Hard, None)?;
+    }
+    pushed.map(|_| committed)
+}
+
+/// Id of the commit checked out in the repository at `repo_path`.
+pub fn head_commit(repo_path: &str) -> Result<String, GitError> {
+    let repo = Repository::open(repo_path)?;
+    let commit = repo.head()?.peel_to_commit()?;
+    Ok(commit.id().to_string())
+}
+
+/// Name and email of the person who authored a commit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommitAuthor {
+    pub name: String,
+    pub email: String,
+}
+
+/// Author of the latest commit on `origin/{branch}`, as last fetched by
+/// [`get_latest_commit`].
+#[tracing::instrument(name = "get_commit_author", skip(), fields())]
+pub fn get_commit_author(repo_path: &Path, branch: &str) -> Result<CommitAuthor, GitError> {
+    let repo = Repository::open(repo_path)?;
+    let commit = repo
+        .find_reference(&format!("refs/remotes/origin/{}", branch))?
+        .peel_to_commit()?;
+    let author = commit.author();
+
+    Ok(CommitAuthor {
+        name: String::from_utf8_lossy(author.name_bytes()).into_owned(),
+        email: String::from_utf8_lossy(author.email_bytes()).into_owned(),
+    })
+}
+
+/// Whether the changes from commit `from` to commit `to` of the repository at
+/// `repo_path` touch any of `paths` (git pathspecs: files, directories or
+/// globs such as `services/*/src`).
+pub fn touches_paths(
+    repo_path: &Path,
+    from: &str,
+    to: &str,
+    paths: &[String],
+) -> Result<bool, GitError> {
+    let repo = Repository::open(repo_path)?;
+    let from = repo.revparse_single(from)?.peel_to_tree()?;
+    let to = repo.revparse_single(to)?.peel_to_tree()?;
+    let mut options = git2::DiffOptions::new();
+    for path in paths {
+        options.pathspec(path);
+    }
+    let diff = repo.diff_tree_to_tree(Some(&from), Some(&to), Some(&mut options))?;
+    Ok(diff.deltas().len() > 0)
+}
+
+/// A tag of the app repository and the commit it points to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObservedTag {
+    pub name: String,
+    pub sha: String,
+}
+
+/// The newest tag `pattern` matches among those fetched into the repository
+/// at `repo_path` (see [`GitTagPattern::newest`]), if any.
+pub fn newest_tag(
+    repo_path: &Path,
+    pattern: &GitTagPattern,
+) -> Result<Option<ObservedTag>, GitError> {
+    let repo = Repository::open(repo_path)?;
+    let mut commits = HashMap::new();
+    for name in repo.tag_names(None)?.iter().flatten().flatten() {
+        // Annotated tags are peeled to their commit; tags of trees are skipped.
+        let Ok(commit) = repo
+            .find_reference(&format!("refs/tags/{}", name))
+            .and_then(|tag| tag.peel_to_commit())
+        else {
+            continue;
+        };
+        commits.insert(name.to_string(), (commit.id(), commit.time().seconds()));
+    }
+
+    let newest = pattern.newest(
+        commits
+            .iter()
+            .map(|(name, (_, time))| (name.as_str(), *time)),
+    );
+    Ok(newest.map(|name| ObservedTag {
+        name: name.to_string(),
+        sha: commits[name].0.to_string(),
+    }))
+}
+
+/// The app commit a manifest update ships: its SHA, the first line of its
+/// message, and its author.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceCommit {
+    pub sha: String,
+    pub summary: String,
+    pub author: CommitAuthor,
+}
+
+/// The commit `sha` of the repository at `repo_path`.
+#[tracing::instrument(name = "get_source_commit", skip(), fields())]
+pub fn get_source_commit(repo_path: &Path, sha: &str) -> Result<SourceCommit, GitError> {
+    let repo = Repository::open(repo_path)?;
+    let commit = repo.revparse_single(sha)?.peel_to_commit()?;
+    let author = commit.author();
+
+    Ok(SourceCommit {
+        sha: commit.id().to_string(),
+        summary: String::from_utf8_lossy(commit.summary_bytes().unwrap_or_default()).into_owned(),
+        author: CommitAuthor {
+            name: String::from_utf8_lossy(author.name_bytes()).into_owned(),
+            email: String::from_utf8_lossy(author.email_bytes()).into_owned(),
+        },
+    })
+}
+
+/// How many characters of the commit SHA an image tag has for `tag_type`:
+/// `long` (40), `short` (7) or `short:N` (N, from 4 to 40). `None` for
+/// anything else.
+pub fn sha_length(tag_type: &str) -> Option<usize> {
+    match tag_type.trim() {
+        "long" => Some(40),
+        "short" => Some(7),
+        other => other
+            .strip_prefix("short:")?
+            .parse()
+            .ok()
+            .filter(|length| (4..=40).contains(length)),
+    }
+}
+
+/// Whether the abbreviated SHA `prefix` matches more than one object of the
+/// repository at `repo_path`, so a tag holding it doesn't tell which commit
+/// it was built from.
+pub fn is_ambiguous(repo_path: &Path, prefix: &str) -> Result<bool, GitError> {
+    let repo = Repository::open(repo_path)?;
+    let odb = repo.odb()?;
+    match odb.exists_prefix(git2::Oid::from_str(prefix)?, prefix.len()) {
+        Ok(_) => Ok(false),
+        Err(e) if e.code() == git2::ErrorCode::Ambiguous => Ok(true),
+        Err(e) => Err(e),
+    }
+}
+
+#[tracing::instrument(name = "get_latest_commit", skip(credentials), fields())]
 pub fn get_latest_commit(
     repo_path: &Path,
     branch: &str,
     tag_type: &str,
-    ssh_key: &str,
+    credentials: &GitCredentials,
 ) -> Result<String, git2::Error> {
     let repo = Repository::open(repo_path)?;
 
@@ -318,14 +1455,6 @@ pub fn get_latest_commit(
         debug!("{}", remote_name.ok().flatten().unwrap_or("invalid utf-8"));
     }
 
-    // Create fetch options with verbose progress
-    let mut fetch_opts = FetchOptions::new();
-
-    let mut callbacks = RemoteCallbacks::new();
-    callbacks.prepare_callbacks(ssh_key.to_string());
-
-    fetch_opts.remote_callbacks(callbacks);
-
     // Get the remote, with explicit error handling
     let mut remote = repo.find_remote("origin").map_err(|e| {
         error!("Error finding remote 'origin': {}", e);
@@ -334,16 +1463,33 @@ pub fn get_latest_commit(
 
     // Fetch the latest changes, including all branches
     info!("Fetching updates for: {}", &repo_path.display());
-    remote
-        .fetch(
+    let refs = refs_lock(repo.commondir());
+    let _refs = refs.lock().unwrap_or_else(|e| e.into_inner());
+    retry_transient(&GitRetryPolicy::from_env(), "fetch", repo_path, || {
+        // Create fetch options with verbose progress
+        let transfer = Cell::new(Transfer::default());
+        let mut fetch_opts = FetchOptions::new();
+        fetch_opts.remote_callbacks(fetch_callbacks(credentials, &transfer));
+
+        let started = Instant::now();
+        let fetched = remote.fetch(
             &[format!("refs/remotes/origin/{}", &branch)],
             Some(&mut fetch_opts),
             None,
-        )
-        .map_err(|e| {
-            error!("Error during fetch: {}", e);
-            e
-        })?;
+        );
+        record_fetch(
+            remote.url().unwrap_or_default(),
+            started,
+            transfer.get(),
+            false,
+            fetched.is_ok(),
+        );
+        fetched
+    })
+    .map_err(|e| {
+        error!("Error during fetch: {}", e);
+        e
+    })?;
 
     // Try different branch name variations
     let branch_names = [format!("refs/remotes/origin/{}", &branch)];
@@ -358,13 +1504,12 @@ pub fn get_latest_commit(
 
                 // Convert the commit ID to the appropriate format
                 info!("Found commit: {} in branch {}", commit_id, branch_name);
-                match tag_type {
-                    "short" => return Ok(commit_id.to_string()[..7].to_string()),
-                    "long" => return Ok(commit_id.to_string()),
-                    _ => Err(git2::Error::from_str(
-                        "Invalid tag_type. Must be 'short' or 'long'",
+                return match sha_length(tag_type) {
+                    Some(length) => Ok(commit_id.to_string()[..length].to_string()),
+                    None => Err(git2::Error::from_str(
+                        "Invalid tag_type. Must be 'short', 'short:N' (4 to 40) or 'long'",
                     )),
-                }?;
+                };
             }
             Err(e) => error!("Could not find reference {}: {}", branch_name, e),
         }
@@ -375,3 +1520,78 @@ pub fn get_latest_commit(
         format!("Could not find {} branch in any expected location", branch).as_str(),
     ))
 }
+
+/// An image tag as recorded in the manifests repository at a given commit.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct ImageRevision {
+    pub tag: String,
+    /// Commit that introduced this tag into the manifest.
+    pub commit: String,
+    /// Commit time, RFC 3339 (UTC).
+    pub date: String,
+}
+
+/// Walk the first-parent history of HEAD and return the image tags that the
+/// `target` containers of `file_path` pointed at over time, newest first.
+/// Consecutive commits that kept the same tag are collapsed into the commit
+/// that introduced it. At most `limit` revisions are returned.
+#[tracing::instrument(name = "image_tag_history", skip(), fields())]
+pub fn image_tag_history(
+    repo_path: &Path,
+    file_path: &str,
+    target: &ContainerTarget,
+    limit: usize,
+) -> Result<Vec<ImageRevision>, GitError> {
+    let repo = Repository::open(repo_path)?;
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_head()?;
+    revwalk.simplify_first_parent()?;
+
+    // (commit, tag) pairs newest first, including unchanged consecutive tags.
+    let mut observed: Vec<ImageRevision> = vec![];
+    for oid in revwalk {
+        let oid = oid?;
+        let commit = repo.find_commit(oid)?;
+        let Ok(entry) = commit.tree()?.get_path(Path::new(file_path)) else {
+            // The file did not exist yet; nothing older can be relevant.
+            break;
+        };
+        let blob = repo.find_blob(entry.id())?;
+        let content = String::from_utf8_lossy(blob.content());
+
+        let tag = match tag_from_str(&content, target) {
+            Ok(Some(tag)) => tag,
+            Ok(None) => continue,
+            Err(e) => {
+                debug!("Skipping unparsable {} at {}: {:#}", file_path, oid, e);
+                continue;
+            }
+        };
+
+        let date = k8s_openapi::jiff::Timestamp::from_second(commit.time().seconds())
+            .map(|t| t.to_string())
+            .unwrap_or_default();
+
+        // Walking backwards: an older commit with the same tag replaces the
+        // newer one, so each run ends up attributed to the commit introducing it.
+        match observed.last_mut() {
+            Some(last) if last.tag == tag => {
+                last.commit = commit.id().to_string();
+                last.date = date;
+            }
+            _ => {
+                if observed.len() == limit {
+                    break;
+                }
+                observed.push(ImageRevision {
+                    tag,
+                    commit: commit.id().to_string(),
+                    date,
+                });
+            }
+        }
+    }
+
+    Ok(observed)
+}
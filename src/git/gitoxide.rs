@@ -0,0 +1,440 @@
+use crate::fetch_stats::{FetchSample, fetch_stats};
+use crate::git::utils::{SshCommand, create_signature, https_login, refspec, remote_url};
+use crate::git::{
+    CloneStrategy, CommitAuthor, GitCredentials, GitRetryPolicy, LibGit2Provider, retry_transient,
+};
+use crate::traits::GitProvider;
+use git2::Error as GitError;
+use gix::bstr::{BString, ByteSlice};
+use gix::credentials::helper::Action;
+use gix::credentials::protocol::Outcome;
+use gix::remote::Direction;
+use gix::remote::fetch::{Shallow, Tags};
+
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::time::Instant;
+
+use tracing::{info, warn};
+
+/// [`GitProvider`] on gitoxide, in pure Rust. Checkouts are clones of their
+/// own with a detached HEAD rather than worktrees of a shared mirror, and
+/// SSH remotes are reached through the system's `ssh`, so any key type it
+/// supports works. gitoxide can't push yet: pushes go through libgit2.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GitoxideProvider;
+
+impl GitProvider for GitoxideProvider {
+    fn name(&self) -> &'static str {
+        "gitoxide"
+    }
+
+    fn clone_repo(
+        &self,
+        url: &str,
+        path: &Path,
+        branch: &str,
+        credentials: &GitCredentials,
+        strategy: CloneStrategy,
+    ) -> Result<(), GitError> {
+        info!(
+            "Cloning {} into {} with gitoxide ({:?})",
+            url,
+            path.display(),
+            strategy
+        );
+        let ssh = SshCommand::new(url, credentials)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| GitError::from_str(&e.to_string()))?;
+        }
+        retry_transient(&GitRetryPolicy::from_env(), "clone", path, || {
+            let started = Instant::now();
+            let cloned = clone_once(url, path, branch, credentials, strategy, &ssh);
+            record_fetch(url, started, true, cloned.is_ok());
+            cloned.map_err(to_git_error)
+        })
+    }
+
+    fn fetch(
+        &self,
+        url: &str,
+        path: &Path,
+        branch: &str,
+        credentials: &GitCredentials,
+        strategy: CloneStrategy,
+    ) -> Result<(), GitError> {
+        info!("Fetching {} into {} with gitoxide", url, path.display());
+        let ssh = SshCommand::new(url, credentials)?;
+        let repo = open(path, &ssh_overrides(&ssh)).map_err(to_git_error)?;
+        retry_transient(&GitRetryPolicy::from_env(), "fetch", path, || {
+            let started = Instant::now();
+            let fetched = fetch_once(&repo, url, branch, credentials, strategy);
+            record_fetch(url, started, false, fetched.is_ok());
+            fetched.map_err(to_git_error)
+        })?;
+        let id = remote_branch(&repo, branch).map_err(to_git_error)?;
+        check_out(&repo, id).map_err(to_git_error)
+    }
+
+    fn resolve(&self, path: &Path, branch: &str) -> Result<String, GitError> {
+        let repo = open(path, &[]).map_err(to_git_error)?;
+        remote_branch(&repo, branch)
+            .map(|id| id.to_string())
+            .map_err(to_git_error)
+    }
+
+    fn commit(
+        &self,
+        path: &Path,
+        message: &str,
+        author: Option<&CommitAuthor>,
+    ) -> Result<bool, GitError> {
+        let repo = open(path, &[]).map_err(to_git_error)?;
+        commit_all(&repo, message, author).map_err(to_git_error)
+    }
+
+    fn push(
+        &self,
+        path: &Path,
+        branch: &str,
+        credentials: &GitCredentials,
+        force: bool,
+    ) -> Result<(), GitError> {
+        LibGit2Provider.push(path, branch, credentials, force)
+    }
+}
+
+/// The in-memory configuration running `ssh` as `ssh` says.
+fn ssh_overrides(ssh: &SshCommand) -> Vec<BString> {
+    ssh.command()
+        .map(|command| format!("core.sshCommand={}", command).into())
+        .into_iter()
+        .collect()
+}
+
+/// Open the checkout at `path` with `overrides` on top of its
+/// configuration, and the operator as the identity recorded in reflogs.
+fn open(path: &Path, overrides: &[BString]) -> gix::Result<gix::Repository> {
+    gix::open_opts(
+        path,
+        gix::open::Options::default()
+            .config_overrides(identity().into_iter().chain(overrides.iter().cloned())),
+    )
+}
+
+/// The operator's name and email as configuration (see [`create_signature`]).
+fn identity() -> Vec<BString> {
+    let Ok(signature) = create_signature() else {
+        return vec![];
+    };
+    vec![
+        format!(
+            "user.name={}",
+            String::from_utf8_lossy(signature.name_bytes())
+        )
+        .into(),
+        format!(
+            "user.email={}",
+            String::from_utf8_lossy(signature.email_bytes())
+        )
+        .into(),
+    ]
+}
+
+/// Answers gitoxide's requests for HTTPS credentials like libgit2's
+/// callbacks do (see [`crate::git::DefaultCallbacks`]).
+fn authenticate(
+    url: &str,
+    credentials: &GitCredentials,
+) -> impl FnMut(Action) -> gix::Result<Option<Outcome>> + 'static {
+    let url = url.to_string();
+    let https = credentials.https_token.clone();
+    move |action| {
+        let Action::Get(context) = action else {
+            return Ok(None);
+        };
+        let (username, password) =
+            https_login(&url, https.as_ref(), context.username.as_deref()).map_err(failure)?;
+        Ok(Some(Outcome {
+            identity: gix::sec::identity::Account {
+                username,
+                password,
+                oauth_refresh_token: None,
+            },
+            next: context.into(),
+        }))
+    }
+}
+
+fn failure(message: String) -> gix::Error {
+    gix::Error::from_error(gix::error::Message::new(message))
+}
+
+fn tags(strategy: CloneStrategy) -> Tags {
+    match strategy {
+        CloneStrategy::Full => Tags::All,
+        _ => Tags::None,
+    }
+}
+
+fn clone_once(
+    url: &str,
+    path: &Path,
+    branch: &str,
+    credentials: &GitCredentials,
+    strategy: CloneStrategy,
+    ssh: &SshCommand,
+) -> gix::Result<()> {
+    let remote = remote_url(url);
+    let spec = refspec(branch, strategy);
+    let mut authenticated = Some(authenticate(&remote, credentials));
+    let mut prepare = gix::prepare_clone(remote.as_str(), path)?
+        .with_ref_name(Some(branch))?
+        .with_in_memory_config_overrides(identity().into_iter().chain(ssh_overrides(ssh)))
+        .configure_remote(move |mut remote| {
+            remote.replace_refspecs([spec.as_str()], Direction::Fetch)?;
+            Ok(remote.with_fetch_tags(tags(strategy)))
+        })
+        .configure_connection(move |connection| {
+            if let Some(authenticate) = authenticated.take() {
+                connection.set_credentials(authenticate);
+            }
+            Ok(())
+        });
+    if strategy == CloneStrategy::Shallow {
+        prepare = prepare.with_shallow(Shallow::DepthAtRemote(NonZeroU32::MIN));
+    }
+
+    let interrupt = AtomicBool::new(false);
+    let (mut checkout, _) = prepare.fetch_then_checkout(gix::progress::Discard, &interrupt)?;
+    let (repo, _) = checkout.main_worktree(gix::progress::Discard, &interrupt)?;
+    let id = remote_branch(&repo, branch)?;
+    detach_head(&repo, id)
+}
+
+fn fetch_once(
+    repo: &gix::Repository,
+    url: &str,
+    branch: &str,
+    credentials: &GitCredentials,
+    strategy: CloneStrategy,
+) -> gix::Result<()> {
+    let mut remote = repo.find_remote("origin")?;
+    remote.replace_refspecs([refspec(branch, strategy).as_str()], Direction::Fetch)?;
+    let remote = remote.with_fetch_tags(tags(strategy));
+    let mut connection = remote.connect(Direction::Fetch)?;
+    connection.set_credentials(authenticate(&remote_url(url), credentials));
+    let mut prepare = connection.prepare_fetch(gix::progress::Discard, Default::default())?;
+    if strategy == CloneStrategy::Shallow {
+        prepare = prepare.with_shallow(Shallow::DepthAtRemote(NonZeroU32::MIN));
+    }
+    prepare.receive(gix::progress::Discard, &AtomicBool::new(false))?;
+    Ok(())
+}
+
+fn record_fetch(url: &str, started: Instant, clone: bool, ok: bool) {
+    fetch_stats().record(url, FetchSample::new(started.elapsed(), 0, 0, clone, ok));
+}
+
+fn remote_branch(repo: &gix::Repository, branch: &str) -> gix::Result<gix::ObjectId> {
+    Ok(repo
+        .find_reference(format!("refs/remotes/origin/{}", branch).as_str())?
+        .peel_to_id()?
+        .detach())
+}
+
+fn detach_head(repo: &gix::Repository, id: gix::ObjectId) -> gix::Result<()> {
+    use gix::refs::transaction::{Change, LogChange, PreviousValue, RefEdit};
+
+    repo.edit_reference(RefEdit {
+        change: Change::Update {
+            log: LogChange::default(),
+            expected: PreviousValue::Any,
+            new: gix::refs::Target::Object(id),
+        },
+        name: "HEAD".try_into().map_err(gix::Error::from_error)?,
+        deref: false,
+    })?;
+    Ok(())
+}
+
+/// Move the checkout to commit `id`, dropping what changed in tracked files
+/// and any commit that wasn't pushed. Untracked files are left alone.
+fn check_out(repo: &gix::Repository, id: gix::ObjectId) -> gix::Result<()> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| failure(format!("{} has no worktree", repo.git_dir().display())))?
+        .to_path_buf();
+    let tree = repo.find_commit(id)?.tree_id()?.detach();
+    let previous = repo.index_or_empty()?;
+    let mut index = repo.index_from_tree(&tree)?;
+
+    // Files the new commit doesn't have anymore.
+    for entry in previous.entries() {
+        let path = entry.path(&previous);
+        if index.entry_by_path(path).is_none()
+            && let Ok(path) = gix::path::from_bstr(path)
+        {
+            let _ = std::fs::remove_file(workdir.join(path));
+        }
+    }
+
+    let mut options =
+        repo.checkout_options(gix::worktree::stack::state::attributes::Source::IdMapping)?;
+    options.overwrite_existing = true;
+    options.destination_is_initially_empty = false;
+    gix::worktree::state::checkout(
+        &mut index,
+        &workdir,
+        repo.objects
+            .clone()
+            .into_arc()
+            .map_err(gix::Error::from_error)?,
+        &gix::progress::Discard,
+        &gix::progress::Discard,
+        &AtomicBool::new(false),
+        options,
+    )?;
+    index.write(Default::default())?;
+    detach_head(repo, id)
+}
+
+/// Commit every change in the worktree of `repo` on top of HEAD, returning
+/// whether there was any.
+fn commit_all(
+    repo: &gix::Repository,
+    message: &str,
+    author: Option<&CommitAuthor>,
+) -> gix::Result<bool> {
+    use gix::status::index_worktree::iter::Summary;
+
+    info!("Staging changes for: {}", repo.git_dir().display());
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| failure(format!("{} has no worktree", repo.git_dir().display())))?
+        .to_path_buf();
+    let parent = repo.head_commit()?;
+    let parent_tree = parent.tree_id()?.detach();
+    let mut editor = repo.edit_tree(parent_tree)?;
+
+    let status = repo
+        .status(gix::progress::Discard)?
+        .untracked_files(gix::status::UntrackedFiles::Files)
+        .index_worktree_rewrites(None)
+        .into_index_worktree_iter(Vec::<BString>::new())?;
+    for item in status {
+        let item = item?;
+        let path = item.rela_path().to_owned();
+        match item.summary() {
+            None => {}
+            Some(Summary::Conflict) => {
+                warn!("Merge conflicts detected for {}", workdir.display());
+                return Ok(false);
+            }
+            Some(Summary::Removed) => {
+                editor.remove(path.as_bstr())?;
+            }
+            Some(_) => {
+                let file = workdir
+                    .join(gix::path::from_bstr(path.as_bstr()).map_err(gix::Error::from_error)?);
+                let (kind, content) = read_entry(&file).map_err(gix::Error::from_error)?;
+                let blob = repo.write_blob(content)?;
+                editor.upsert(path.as_bstr(), kind, blob)?;
+            }
+        }
+    }
+
+    let tree = editor.write()?.detach();
+    if tree == parent_tree {
+        info!(
+            "No changes detected against HEAD ({}); skipping commit and push",
+            parent.id
+        );
+        return Ok(false);
+    }
+
+    let committer = create_signature().map_err(gix::Error::from_error)?;
+    let when = gix::date::Time::new(committer.when().seconds(), 0);
+    let committer = gix::actor::Signature {
+        name: committer.name_bytes().into(),
+        email: committer.email_bytes().into(),
+        time: when,
+    };
+    let author = match author {
+        Some(author) => gix::actor::Signature {
+            name: author.name.as_str().into(),
+            email: author.email.as_str().into(),
+            time: when,
+        },
+        None => committer.clone(),
+    };
+    let (mut committer_time, mut author_time) = Default::default();
+    let id = repo
+        .commit_as(
+            committer.to_ref(&mut committer_time),
+            author.to_ref(&mut author_time),
+            "HEAD",
+            message,
+            tree,
+            [parent.id],
+        )?
+        .detach();
+    info!("New commit: {}", id);
+
+    let mut index = repo.index_from_tree(&tree)?;
+    index.write(Default::default())?;
+    Ok(true)
+}
+
+/// What a worktree file is committed as: a symlink's target or the
+/// content of a (possibly executable) file.
+fn read_entry(file: &Path) -> std::io::Result<(gix::object::tree::EntryKind, Vec<u8>)> {
+    use gix::object::tree::EntryKind;
+
+    let metadata = std::fs::symlink_metadata(file)?;
+    if metadata.file_type().is_symlink() {
+        let target = std::fs::read_link(file)?;
+        return Ok((
+            EntryKind::Link,
+            target.to_string_lossy().into_owned().into_bytes(),
+        ));
+    }
+    #[cfg(unix)]
+    let executable = {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o111 != 0
+    };
+    #[cfg(not(unix))]
+    let executable = false;
+    let kind = if executable {
+        EntryKind::BlobExecutable
+    } else {
+        EntryKind::Blob
+    };
+    Ok((kind, std::fs::read(file)?))
+}
+
+/// `e` as a libgit2 error, classified so [`crate::git::is_transient`] and
+/// [`crate::error::ErrorKind`] tell it apart the same way.
+fn to_git_error(e: gix::Error) -> GitError {
+    use git2::{ErrorClass, ErrorCode};
+
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(&e);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    let (code, class) = if e.classify().is_unauthenticated() || e.classify().is_permission_denied()
+    {
+        (ErrorCode::Auth, ErrorClass::Net)
+    } else if e.classify().can_retry() {
+        (ErrorCode::GenericError, ErrorClass::Net)
+    } else if e.classify().is_not_found() {
+        (ErrorCode::NotFound, ErrorClass::Reference)
+    } else {
+        (ErrorCode::GenericError, ErrorClass::None)
+    };
+    GitError::new(code, class, message)
+}
@@ -1,6 +1,12 @@
 mod utils;
 pub use utils::*;
 
+mod cli;
+pub use cli::*;
+
+mod gitoxide;
+pub use gitoxide::*;
+
 #[allow(clippy::module_inception)]
 mod git;
 pub use git::*;
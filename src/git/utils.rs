@@ -1,7 +1,11 @@
+use crate::codecommit::{aws_credentials, git_credentials, https_url, is_codecommit_url};
+use crate::git::{CloneStrategy, DEFAULT_HTTPS_USERNAME, GitCredentials, HttpsToken};
 use git2::Error as GitError;
 use git2::Signature;
 use std::env;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
 
 pub fn create_signature<'a>() -> Result<Signature<'a>, GitError> {
     let name = env::var("DEFAULT_FROM_NAME").unwrap_or("GitOps Operator".to_owned());
@@ -17,3 +21,153 @@ pub fn create_signature<'a>() -> Result<Signature<'a>, GitError> {
     // Create signature with current timestamp
     Signature::new(&name, &email, &git2::Time::new(time as i64, 0))
 }
+
+/// The username and password to answer a request for HTTPS credentials
+/// to `url` with, the way libgit2's callbacks do (see
+/// [`crate::git::DefaultCallbacks`]): CodeCommit is signed with the
+/// operator's AWS credentials, other remotes get the token.
+pub(crate) fn https_login(
+    url: &str,
+    https: Option<&HttpsToken>,
+    username_from_url: Option<&str>,
+) -> Result<(String, String), String> {
+    if is_codecommit_url(url) {
+        let aws = aws_credentials().map_err(|e| format!("{:#}", e))?;
+        return git_credentials(url, &aws, SystemTime::now())
+            .ok_or_else(|| "Not a CodeCommit HTTPS URL".to_string());
+    }
+    let Some(https) = https else {
+        return Err(format!(
+            "{} needs an HTTPS token (gitops.operator.https_token_secret_name)",
+            url
+        ));
+    };
+    let username = https
+        .username
+        .as_deref()
+        .or(username_from_url)
+        .unwrap_or(DEFAULT_HTTPS_USERNAME);
+    Ok((username.to_string(), https.token.clone()))
+}
+
+/// Where `url` is fetched from: CodeCommit's `codecommit::` URLs become
+/// their HTTPS endpoint.
+pub(crate) fn remote_url(url: &str) -> String {
+    https_url(url).unwrap_or_else(|| url.to_string())
+}
+
+/// The refspec fetching what `strategy` asks for of `branch`.
+pub(crate) fn refspec(branch: &str, strategy: CloneStrategy) -> String {
+    match strategy {
+        CloneStrategy::Full => "+refs/heads/*:refs/remotes/origin/*".to_string(),
+        _ => format!("+refs/heads/{}:refs/remotes/origin/{}", branch, branch),
+    }
+}
+
+/// The `ssh` command line authenticating with the SSH key of `credentials`
+/// and checking host keys against their known_hosts (the system's when they
+/// have none), written to a directory of their own that is removed on drop.
+/// For backends running the system's `ssh` (the command goes through a
+/// shell).
+pub(crate) struct SshCommand {
+    dir: Option<PathBuf>,
+    command: Option<String>,
+}
+
+impl SshCommand {
+    /// No command for remotes that aren't reached over SSH, or without a key.
+    pub(crate) fn new(url: &str, credentials: &GitCredentials) -> Result<Self, GitError> {
+        if credentials.ssh_key.trim().is_empty() || !is_ssh_url(url) {
+            return Ok(Self {
+                dir: None,
+                command: None,
+            });
+        }
+
+        let dir =
+            std::env::temp_dir().join(format!("gitops-operator-ssh-{}", uuid::Uuid::new_v4()));
+        let mut ssh = Self {
+            dir: Some(dir.clone()),
+            command: None,
+        };
+        let io = |e: std::io::Error| GitError::from_str(&format!("Writing the SSH key: {}", e));
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        // Neither the directory nor the key is ever readable by others.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+            builder.mode(0o700);
+            options.mode(0o600);
+        }
+        builder.create(&dir).map_err(io)?;
+        let key = dir.join("id");
+        let mut content = credentials.ssh_key.trim_end().to_string();
+        content.push('\n');
+        options
+            .open(&key)
+            .and_then(|mut file| std::io::Write::write_all(&mut file, content.as_bytes()))
+            .map_err(io)?;
+
+        // Without known_hosts of their own, ssh checks against the system's
+        // (`~/.ssh/known_hosts` and ssh config), like libgit2 does.
+        let host_keys = match &credentials.known_hosts {
+            Some(known_hosts) => {
+                let file = dir.join("known_hosts");
+                std::fs::write(&file, known_hosts.to_openssh()).map_err(io)?;
+                let checking = if credentials.strict_host_keys {
+                    "yes"
+                } else {
+                    "accept-new"
+                };
+                format!(
+                    " -o UserKnownHostsFile={} -o StrictHostKeyChecking={}",
+                    quote(&file),
+                    checking
+                )
+            }
+            None if credentials.strict_host_keys => " -o StrictHostKeyChecking=yes".to_string(),
+            None => String::new(),
+        };
+        ssh.command = Some(format!(
+            "ssh -i {} -o IdentitiesOnly=yes{}",
+            quote(&key),
+            host_keys
+        ));
+        Ok(ssh)
+    }
+
+    pub(crate) fn command(&self) -> Option<&str> {
+        self.command.as_deref()
+    }
+}
+
+impl Drop for SshCommand {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.dir
+            && let Err(e) = std::fs::remove_dir_all(dir)
+        {
+            warn!("Could not remove {}: {}", dir.display(), e);
+        }
+    }
+}
+
+/// Whether `url` is an `ssh://` or scp-like (`git@host:repo.git`) URL.
+pub(crate) fn is_ssh_url(url: &str) -> bool {
+    let url = url.trim();
+    if url.starts_with("ssh://") {
+        return true;
+    }
+    !url.contains("://")
+        && !url.starts_with("codecommit:")
+        && url
+            .split_once(':')
+            .is_some_and(|(host, _)| !host.is_empty() && !host.contains('/'))
+}
+
+/// `path` single-quoted for the shell `core.sshCommand` runs in.
+fn quote(path: &Path) -> String {
+    format!("'{}'", path.display().to_string().replace('\'', r"'\''"))
+}
@@ -0,0 +1,205 @@
+use crate::http_client::http_client;
+use crate::pull_requests::{ChangeRequest, PullRequest, split_repository_url};
+use crate::retry::with_retries;
+use crate::traits::PullRequestOpener;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest_middleware::ClientWithMiddleware;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+/// Where the API of the Gitea or Forgejo instance hosting `url` is served and
+/// the repository's `owner/name` there, e.g.
+/// `("https://codeberg.org/api/v1", "platform/manifests")`.
+pub fn parse_gitea_repository(url: &str) -> Option<(String, String)> {
+    let (host, path) = split_repository_url(url)?;
+    // Gitea has no nested groups: owner and name only.
+    if path.matches('/').count() != 1 {
+        return None;
+    }
+    Some((format!("https://{}/api/v1", host), path))
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequestResponse {
+    number: u64,
+    html_url: String,
+    head: BranchResponse,
+    base: BranchResponse,
+}
+
+#[derive(Debug, Deserialize)]
+struct BranchResponse {
+    #[serde(rename = "ref")]
+    name: String,
+}
+
+impl From<PullRequestResponse> for PullRequest {
+    fn from(response: PullRequestResponse) -> Self {
+        PullRequest {
+            number: response.number,
+            url: response.html_url,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LabelResponse {
+    id: u64,
+    name: String,
+}
+
+/// Opens pull requests through the Gitea REST API (v1), which Forgejo serves
+/// too.
+#[derive(Debug)]
+pub struct GiteaPullRequests {
+    client: ClientWithMiddleware,
+    /// Overrides the API base derived from each repository's host.
+    api_base: Option<String>,
+}
+
+impl Default for GiteaPullRequests {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GiteaPullRequests {
+    pub fn new() -> Self {
+        Self {
+            client: with_retries(http_client()),
+            api_base: None,
+        }
+    }
+
+    pub fn with_api_base(api_base: String) -> Self {
+        Self {
+            api_base: Some(api_base),
+            ..Self::new()
+        }
+    }
+
+    fn request(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        token: &str,
+    ) -> reqwest_middleware::RequestBuilder {
+        self.client
+            .request(method, url)
+            .header("Authorization", format!("token {}", token))
+            .header("User-Agent", "gitops-operator")
+    }
+
+    /// The ids of the repository's labels named in `names`; labels are set
+    /// by id, and unknown ones are logged and left out rather than failing
+    /// the pull request.
+    async fn label_ids(&self, repo_url: &str, names: &[String], token: &str) -> Vec<u64> {
+        if names.is_empty() {
+            return vec![];
+        }
+        let labels = async {
+            let response = self
+                .request(reqwest::Method::GET, &format!("{}/labels", repo_url), token)
+                .query(&[("limit", "100")])
+                .send()
+                .await?
+                .error_for_status()?;
+            anyhow::Ok(response.json::<Vec<LabelResponse>>().await?)
+        }
+        .await;
+        let labels = match labels {
+            Ok(labels) => labels,
+            Err(e) => {
+                warn!("Failed to list the repository's labels: {:#}", e);
+                return vec![];
+            }
+        };
+        names
+            .iter()
+            .filter_map(|name| {
+                let id = labels
+                    .iter()
+                    .find(|label| &label.name == name)
+                    .map(|label| label.id);
+                if id.is_none() {
+                    warn!("No label named {}, not applying it", name);
+                }
+                id
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl PullRequestOpener for GiteaPullRequests {
+    #[tracing::instrument(name = "open_gitea_pull_request", skip(self, token), fields())]
+    async fn open_pull_request(&self, change: &ChangeRequest, token: &str) -> Result<PullRequest> {
+        let (api_base, repo) = parse_gitea_repository(&change.repository)
+            .ok_or_else(|| anyhow::anyhow!("{} is not a Gitea repository", change.repository))?;
+        let api_base = self.api_base.clone().unwrap_or(api_base);
+        let repo_url = format!("{}/repos/{}", api_base, repo);
+        let url = format!("{}/pulls", repo_url);
+
+        // The list can't be filtered by branch, so the match is made here.
+        let response = self
+            .request(reqwest::Method::GET, &url, token)
+            .query(&[("state", "open"), ("limit", "50")])
+            .send()
+            .await
+            .context("Failed to list Gitea pull requests")?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Gitea refused to list the pull requests of {}: {}",
+                repo,
+                response.status()
+            );
+        }
+        let open: Vec<PullRequestResponse> = response
+            .json()
+            .await
+            .context("Failed to parse Gitea pull requests")?;
+        if let Some(existing) = open
+            .into_iter()
+            .find(|pr| pr.head.name == change.head && pr.base.name == change.base)
+        {
+            info!("Pull request #{} is already open", existing.number);
+            return Ok(existing.into());
+        }
+
+        let labels = self.label_ids(&repo_url, &change.labels, token).await;
+        let response = self
+            .request(reqwest::Method::POST, &url, token)
+            .json(&json!({
+                "head": change.head,
+                "base": change.base,
+                "title": change.title,
+                "body": change.body,
+                "labels": labels,
+                "assignees": change.assignees,
+            }))
+            .send()
+            .await
+            .context("Failed to open Gitea pull request")?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let detail = response.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "Gitea refused to open a pull request on {}: {} {}",
+                repo,
+                status,
+                detail
+            );
+        }
+        let opened: PullRequestResponse = response
+            .json()
+            .await
+            .context("Failed to parse Gitea pull request")?;
+        info!(
+            "Opened pull request #{}: {}",
+            opened.number, opened.html_url
+        );
+        Ok(opened.into())
+    }
+}
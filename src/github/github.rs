@@ -1,8 +1,12 @@
-use crate::traits::{BuildStatus, BuildStatusChecker};
+use crate::http_client::{client_builder, http_client};
+use crate::pull_requests::{ChangeRequest, PullRequest};
+use crate::retry::with_retries;
+use crate::traits::{BuildStatus, BuildStatusChecker, PullRequestOpener};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
-use reqwest::Client;
+use reqwest_middleware::ClientWithMiddleware;
 use serde::Deserialize;
+use serde_json::json;
 use tracing::{info, warn};
 
 #[derive(Debug, Deserialize)]
@@ -46,7 +50,7 @@ pub fn parse_github_repo(url: &str) -> Option<String> {
 
 #[derive(Debug)]
 pub struct GitHubBuildChecker {
-    client: Client,
+    client: ClientWithMiddleware,
     token: String,
     api_base: String,
 }
@@ -57,9 +61,11 @@ impl GitHubBuildChecker {
     }
 
     pub fn with_api_base(token: String, api_base: String) -> Result<Self> {
-        let client = Client::builder()
-            .build()
-            .context("Failed to create HTTP client for GitHub API")?;
+        let client = with_retries(
+            client_builder()
+                .build()
+                .context("Failed to create HTTP client for GitHub API")?,
+        );
 
         Ok(Self {
             client,
@@ -161,3 +167,151 @@ impl Default for GitHubBuildCheckerFactory {
         Self::new()
     }
 }
+
+#[derive(Debug, Deserialize)]
+struct PullRequestResponse {
+    number: u64,
+    html_url: String,
+}
+
+impl From<PullRequestResponse> for PullRequest {
+    fn from(response: PullRequestResponse) -> Self {
+        PullRequest {
+            number: response.number,
+            url: response.html_url,
+        }
+    }
+}
+
+/// Opens pull requests on GitHub repositories through the REST API.
+#[derive(Debug)]
+pub struct GitHubPullRequests {
+    client: ClientWithMiddleware,
+    api_base: String,
+}
+
+impl Default for GitHubPullRequests {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GitHubPullRequests {
+    pub fn new() -> Self {
+        Self::with_api_base("https://api.github.com".to_string())
+    }
+
+    pub fn with_api_base(api_base: String) -> Self {
+        Self {
+            client: with_retries(http_client()),
+            api_base,
+        }
+    }
+
+    fn request(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        token: &str,
+    ) -> reqwest_middleware::RequestBuilder {
+        self.client
+            .request(method, url)
+            .header("Accept", "application/vnd.github+json")
+            .header("Authorization", format!("Bearer {}", token))
+            .header("User-Agent", "gitops-operator")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+    }
+}
+
+#[async_trait]
+impl PullRequestOpener for GitHubPullRequests {
+    #[tracing::instrument(name = "open_pull_request", skip(self, token), fields())]
+    async fn open_pull_request(&self, change: &ChangeRequest, token: &str) -> Result<PullRequest> {
+        let repo = parse_github_repo(&change.repository)
+            .ok_or_else(|| anyhow::anyhow!("{} is not a GitHub repository", change.repository))?;
+        let owner = repo.split('/').next().unwrap_or_default();
+        let url = format!("{}/repos/{}/pulls", self.api_base, repo);
+
+        let response = self
+            .request(reqwest::Method::GET, &url, token)
+            .query(&[
+                ("head", format!("{}:{}", owner, change.head)),
+                ("base", change.base.clone()),
+                ("state", "open".to_string()),
+            ])
+            .send()
+            .await
+            .context("Failed to list GitHub pull requests")?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "GitHub refused to list the pull requests of {}: {}",
+                repo,
+                response.status()
+            );
+        }
+        let open: Vec<PullRequestResponse> = response
+            .json()
+            .await
+            .context("Failed to parse GitHub pull requests")?;
+        if let Some(existing) = open.into_iter().next() {
+            info!("Pull request #{} is already open", existing.number);
+            return Ok(existing.into());
+        }
+
+        let response = self
+            .request(reqwest::Method::POST, &url, token)
+            .json(&json!({
+                "title": change.title,
+                "body": change.body,
+                "head": change.head,
+                "base": change.base,
+            }))
+            .send()
+            .await
+            .context("Failed to open GitHub pull request")?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let detail = response.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "GitHub refused to open a pull request on {}: {} {}",
+                repo,
+                status,
+                detail
+            );
+        }
+        let opened: PullRequestResponse = response
+            .json()
+            .await
+            .context("Failed to parse GitHub pull request")?;
+        info!(
+            "Opened pull request #{}: {}",
+            opened.number, opened.html_url
+        );
+
+        // Pull requests are labelled and assigned through their issue.
+        if !change.labels.is_empty() || !change.assignees.is_empty() {
+            let issue = format!("{}/repos/{}/issues/{}", self.api_base, repo, opened.number);
+            let updated = self
+                .request(reqwest::Method::PATCH, &issue, token)
+                .json(&json!({
+                    "labels": change.labels,
+                    "assignees": change.assignees,
+                }))
+                .send()
+                .await;
+            match updated {
+                Ok(response) if response.status().is_success() => {}
+                Ok(response) => warn!(
+                    "GitHub refused to label or assign pull request #{}: {}",
+                    opened.number,
+                    response.status()
+                ),
+                Err(e) => warn!(
+                    "Failed to label or assign pull request #{}: {:?}",
+                    opened.number, e
+                ),
+            }
+        }
+        Ok(opened.into())
+    }
+}
@@ -0,0 +1,220 @@
+use crate::codecommit::parse_expiration;
+use crate::http_client::http_client;
+use crate::retry::with_retries;
+use anyhow::{Context, Result};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use reqwest_middleware::ClientWithMiddleware;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+/// Installation tokens are minted again once they are this close to
+/// expiring (GitHub issues them for an hour).
+const REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+/// A GitHub App the operator authenticates as, from the secret named by
+/// `gitops.operator.github_app_secret_name`.
+#[derive(Clone, PartialEq)]
+pub struct GitHubApp {
+    pub app_id: String,
+    /// The app's private key, PEM encoded.
+    pub private_key: String,
+    /// The installation to mint tokens for; looked up from the repository
+    /// when unset.
+    pub installation_id: Option<u64>,
+}
+
+/// A JSON Web Token authenticating as the app itself, valid for nine minutes
+/// from `now` (backdated a minute for clock drift), signed with RS256.
+pub fn app_jwt(app: &GitHubApp, now: SystemTime) -> Result<String> {
+    let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let header = URL_SAFE_NO_PAD.encode(json!({"alg": "RS256", "typ": "JWT"}).to_string());
+    let claims = URL_SAFE_NO_PAD.encode(
+        json!({
+            "iat": now.saturating_sub(60),
+            "exp": now + 540,
+            "iss": app.app_id,
+        })
+        .to_string(),
+    );
+    let signing_input = format!("{}.{}", header, claims);
+
+    let key = PKey::private_key_from_pem(app.private_key.as_bytes())
+        .context("Failed to parse the GitHub App private key")?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(signing_input.as_bytes())?;
+    let signature = URL_SAFE_NO_PAD.encode(signer.sign_to_vec()?);
+    Ok(format!("{}.{}", signing_input, signature))
+}
+
+#[derive(Debug, Deserialize)]
+struct InstallationResponse {
+    id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccessTokenResponse {
+    token: String,
+    expires_at: String,
+}
+
+/// Mints installation access tokens for GitHub Apps, reusing each until
+/// shortly before it expires.
+#[derive(Debug)]
+pub struct GitHubAppTokens {
+    client: ClientWithMiddleware,
+    api_base: String,
+    /// Installation of each `app_id/owner/repo`.
+    installations: Mutex<HashMap<String, u64>>,
+    /// Token of each `app_id/installation`, with when it expires.
+    tokens: Mutex<HashMap<String, (String, SystemTime)>>,
+}
+
+impl Default for GitHubAppTokens {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GitHubAppTokens {
+    pub fn new() -> Self {
+        Self::with_api_base("https://api.github.com".to_string())
+    }
+
+    pub fn with_api_base(api_base: String) -> Self {
+        Self {
+            client: with_retries(http_client()),
+            api_base,
+            installations: Mutex::new(HashMap::new()),
+            tokens: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn request(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        jwt: &str,
+    ) -> reqwest_middleware::RequestBuilder {
+        self.client
+            .request(method, url)
+            .header("Accept", "application/vnd.github+json")
+            .header("Authorization", format!("Bearer {}", jwt))
+            .header("User-Agent", "gitops-operator")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+    }
+
+    /// An installation token of `app` that can reach `repo` (`owner/repo`),
+    /// for both git over HTTPS and the REST API.
+    #[tracing::instrument(name = "github_app_token", skip(self, app), fields(app_id = %app.app_id))]
+    pub async fn token(&self, app: &GitHubApp, repo: &str) -> Result<String> {
+        let installation = match app.installation_id {
+            Some(id) => id,
+            None => self.installation(app, repo).await?,
+        };
+
+        let key = format!("{}/{}", app.app_id, installation);
+        if let Some((token, expires_at)) = self
+            .tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+            && SystemTime::now() + REFRESH_MARGIN < *expires_at
+        {
+            return Ok(token.clone());
+        }
+
+        let jwt = app_jwt(app, SystemTime::now())?;
+        let url = format!(
+            "{}/app/installations/{}/access_tokens",
+            self.api_base, installation
+        );
+        let response = self
+            .request(reqwest::Method::POST, &url, &jwt)
+            .send()
+            .await
+            .context("Failed to request a GitHub App installation token")?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let detail = response.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "GitHub refused an installation token for app {} (installation {}): {} {}",
+                app.app_id,
+                installation,
+                status,
+                detail
+            );
+        }
+        let minted: AccessTokenResponse = response
+            .json()
+            .await
+            .context("Failed to parse the GitHub App installation token")?;
+        let expires_at = parse_expiration(&minted.expires_at)
+            .unwrap_or_else(|| SystemTime::now() + Duration::from_secs(3600));
+        info!(
+            "Minted an installation token for GitHub App {} (installation {})",
+            app.app_id, installation
+        );
+
+        self.tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, (minted.token.clone(), expires_at));
+        Ok(minted.token)
+    }
+
+    /// The installation of `app` on the account owning `repo`.
+    async fn installation(&self, app: &GitHubApp, repo: &str) -> Result<u64> {
+        let key = format!("{}/{}", app.app_id, repo);
+        if let Some(id) = self
+            .installations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+        {
+            return Ok(*id);
+        }
+
+        let jwt = app_jwt(app, SystemTime::now())?;
+        let url = format!("{}/repos/{}/installation", self.api_base, repo);
+        let response = self
+            .request(reqwest::Method::GET, &url, &jwt)
+            .send()
+            .await
+            .context("Failed to look up the GitHub App installation")?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "GitHub App {} is not installed on {}: {}",
+                app.app_id,
+                repo,
+                response.status()
+            );
+        }
+        let installation: InstallationResponse = response
+            .json()
+            .await
+            .context("Failed to parse the GitHub App installation")?;
+
+        self.installations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, installation.id);
+        Ok(installation.id)
+    }
+}
+
+static TOKENS: OnceLock<Arc<GitHubAppTokens>> = OnceLock::new();
+
+/// The operator's installation tokens, shared by every pass so each is
+/// minted once an hour rather than once per deployment.
+pub fn github_app_tokens() -> Arc<GitHubAppTokens> {
+    TOKENS
+        .get_or_init(|| Arc::new(GitHubAppTokens::new()))
+        .clone()
+}
@@ -0,0 +1,3 @@
+#[allow(clippy::module_inception)]
+mod github_app;
+pub use github_app::*;
@@ -0,0 +1,178 @@
+use crate::http_client::http_client;
+use crate::pull_requests::{ChangeRequest, PullRequest, split_repository_url};
+use crate::retry::with_retries;
+use crate::traits::PullRequestOpener;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest_middleware::ClientWithMiddleware;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+/// Where the API of the GitLab instance hosting `url` is served and the
+/// project's path there, e.g. `("https://gitlab.example.com/api/v4",
+/// "platform/manifests")`.
+pub fn parse_gitlab_project(url: &str) -> Option<(String, String)> {
+    let (host, path) = split_repository_url(url)?;
+    Some((format!("https://{}/api/v4", host), path))
+}
+
+#[derive(Debug, Deserialize)]
+struct MergeRequestResponse {
+    iid: u64,
+    web_url: String,
+}
+
+impl From<MergeRequestResponse> for PullRequest {
+    fn from(response: MergeRequestResponse) -> Self {
+        PullRequest {
+            number: response.iid,
+            url: response.web_url,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UserResponse {
+    id: u64,
+}
+
+/// Opens merge requests through the GitLab REST API (v4).
+#[derive(Debug)]
+pub struct GitLabMergeRequests {
+    client: ClientWithMiddleware,
+    /// Overrides the API base derived from each repository's host.
+    api_base: Option<String>,
+}
+
+impl Default for GitLabMergeRequests {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GitLabMergeRequests {
+    pub fn new() -> Self {
+        Self {
+            client: with_retries(http_client()),
+            api_base: None,
+        }
+    }
+
+    pub fn with_api_base(api_base: String) -> Self {
+        Self {
+            api_base: Some(api_base),
+            ..Self::new()
+        }
+    }
+
+    fn request(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        token: &str,
+    ) -> reqwest_middleware::RequestBuilder {
+        self.client
+            .request(method, url)
+            .header("PRIVATE-TOKEN", token)
+            .header("User-Agent", "gitops-operator")
+    }
+
+    /// The ids of the `usernames` that exist; unknown ones are logged and
+    /// left out rather than failing the merge request.
+    async fn user_ids(&self, api_base: &str, usernames: &[String], token: &str) -> Vec<u64> {
+        let mut ids = vec![];
+        for username in usernames {
+            let found = async {
+                let response = self
+                    .request(reqwest::Method::GET, &format!("{}/users", api_base), token)
+                    .query(&[("username", username)])
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                let users: Vec<UserResponse> = response.json().await?;
+                anyhow::Ok(users.first().map(|user| user.id))
+            }
+            .await;
+            match found {
+                Ok(Some(id)) => ids.push(id),
+                Ok(None) => warn!("No GitLab user named {}, not assigning them", username),
+                Err(e) => warn!("Failed to look up GitLab user {}: {:#}", username, e),
+            }
+        }
+        ids
+    }
+}
+
+#[async_trait]
+impl PullRequestOpener for GitLabMergeRequests {
+    #[tracing::instrument(name = "open_merge_request", skip(self, token), fields())]
+    async fn open_pull_request(&self, change: &ChangeRequest, token: &str) -> Result<PullRequest> {
+        let (api_base, project) = parse_gitlab_project(&change.repository)
+            .ok_or_else(|| anyhow::anyhow!("{} is not a GitLab repository", change.repository))?;
+        let api_base = self.api_base.clone().unwrap_or(api_base);
+        let url = format!(
+            "{}/projects/{}/merge_requests",
+            api_base,
+            project.replace('/', "%2F")
+        );
+
+        let response = self
+            .request(reqwest::Method::GET, &url, token)
+            .query(&[
+                ("source_branch", change.head.as_str()),
+                ("target_branch", change.base.as_str()),
+                ("state", "opened"),
+            ])
+            .send()
+            .await
+            .context("Failed to list GitLab merge requests")?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "GitLab refused to list the merge requests of {}: {}",
+                project,
+                response.status()
+            );
+        }
+        let open: Vec<MergeRequestResponse> = response
+            .json()
+            .await
+            .context("Failed to parse GitLab merge requests")?;
+        if let Some(existing) = open.into_iter().next() {
+            info!("Merge request !{} is already open", existing.iid);
+            return Ok(existing.into());
+        }
+
+        let assignee_ids = self.user_ids(&api_base, &change.assignees, token).await;
+        let response = self
+            .request(reqwest::Method::POST, &url, token)
+            .json(&json!({
+                "source_branch": change.head,
+                "target_branch": change.base,
+                "title": change.title,
+                "description": change.body,
+                "labels": change.labels.join(","),
+                "assignee_ids": assignee_ids,
+                "remove_source_branch": true,
+            }))
+            .send()
+            .await
+            .context("Failed to open GitLab merge request")?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let detail = response.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "GitLab refused to open a merge request on {}: {} {}",
+                project,
+                status,
+                detail
+            );
+        }
+        let opened: MergeRequestResponse = response
+            .json()
+            .await
+            .context("Failed to parse GitLab merge request")?;
+        info!("Opened merge request !{}: {}", opened.iid, opened.web_url);
+        Ok(opened.into())
+    }
+}
@@ -0,0 +1,537 @@
+use crate::configuration::ReconcileResult;
+use crate::traits::StateStore;
+use anyhow::{Context, Result};
+use rusqlite::{Connection, params};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_HISTORY_PATH: &str = "/tmp/gitops-operator-history.db";
+const HISTORY_BUCKET: &str = "history";
+const DEFAULT_RETENTION_DAYS: u64 = 90;
+const DEFAULT_RETENTION_ROWS: usize = 100_000;
+
+/// How many records a [`StateStore`]-backed history keeps; a ConfigMap can't
+/// hold an unbounded audit trail.
+pub const STATE_HISTORY_LIMIT: usize = 500;
+
+/// Location of the history database, from `GITOPS_HISTORY_PATH`. Point it at a
+/// mounted volume to keep the audit trail across pod restarts.
+pub fn history_path() -> String {
+    std::env::var("GITOPS_HISTORY_PATH").unwrap_or_else(|_| DEFAULT_HISTORY_PATH.to_string())
+}
+
+/// How much history is kept; older records are pruned whenever a pass is
+/// recorded. The default keeps everything.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HistoryRetention {
+    /// `GITOPS_HISTORY_RETENTION_DAYS` (default 90, 0 keeps records of any
+    /// age).
+    pub max_age: Option<Duration>,
+    /// `GITOPS_HISTORY_RETENTION_ROWS` (default 100000, 0 keeps any number).
+    pub max_rows: Option<usize>,
+}
+
+impl HistoryRetention {
+    pub fn from_env() -> Self {
+        fn env<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok().and_then(|v| v.trim().parse().ok())
+        }
+
+        Self {
+            max_age: Some(env("GITOPS_HISTORY_RETENTION_DAYS").unwrap_or(DEFAULT_RETENTION_DAYS))
+                .filter(|days| *days > 0)
+                .map(|days| Duration::from_secs(days * 24 * 3600)),
+            max_rows: Some(env("GITOPS_HISTORY_RETENTION_ROWS").unwrap_or(DEFAULT_RETENTION_ROWS))
+                .filter(|rows| *rows > 0),
+        }
+    }
+
+    /// The `finished_at` before which records are too old, if any are.
+    fn cutoff(&self) -> Option<String> {
+        self.max_age.map(|age| rfc3339_at(SystemTime::now() - age))
+    }
+}
+
+/// Current time as an RFC 3339 UTC timestamp (second precision).
+pub fn now_rfc3339() -> String {
+    rfc3339_at(SystemTime::now())
+}
+
+fn rfc3339_at(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+
+    k8s_openapi::jiff::Timestamp::from_second(secs)
+        .map(|t| t.to_string())
+        .unwrap_or_default()
+}
+
+/// One persisted reconcile outcome for a single deployment.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct HistoryRecord {
+    pub id: i64,
+    /// Identifies the reconcile pass (all records of one pass share it).
+    pub run_id: String,
+    pub namespace: String,
+    pub deployment: String,
+    pub action: String,
+    pub status: String,
+    pub from_sha: Option<String>,
+    pub to_sha: Option<String>,
+    pub message: String,
+    pub started_at: String,
+    pub finished_at: String,
+    /// `metadata.resourceVersion` of the Deployment object acted on.
+    pub resource_version: Option<String>,
+    /// `metadata.generation` of the Deployment object acted on.
+    pub generation: Option<i64>,
+}
+
+enum Backend {
+    Sqlite(Mutex<Connection>),
+    State {
+        store: Arc<dyn StateStore>,
+        max_records: usize,
+        // Serializes id allocation and eviction.
+        writes: Mutex<()>,
+    },
+}
+
+/// Audit trail of reconcile results, served by `/history`. Kept in its own
+/// SQLite database, or in the operator's [`StateStore`] when that isn't SQLite.
+///
+/// Its methods block on I/O; async callers go through [`Self::blocking`].
+pub struct HistoryStore {
+    backend: Backend,
+    retention: HistoryRetention,
+}
+
+impl HistoryStore {
+    /// Open (creating if needed) the database at `path`.
+    pub fn open(path: &str) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open history database at {}", path))?;
+        Self::init(conn)
+    }
+
+    /// A throwaway in-memory store (for tests, or when persistence is unwanted).
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    /// A store keeping the newest `max_records` records in `store`.
+    pub fn on_state(store: Arc<dyn StateStore>, max_records: usize) -> Self {
+        Self {
+            backend: Backend::State {
+                store,
+                max_records,
+                writes: Mutex::new(()),
+            },
+            retention: HistoryRetention::default(),
+        }
+    }
+
+    /// Prune records beyond `retention` from now on.
+    pub fn with_retention(mut self, retention: HistoryRetention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Run `f` against the store on tokio's blocking pool, so neither the
+    /// I/O nor the connection lock holds up the runtime's workers.
+    pub async fn blocking<T, F>(self: &Arc<Self>, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&HistoryStore) -> Result<T> + Send + 'static,
+    {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || f(&store))
+            .await
+            .context("History task panicked")?
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS reconcile_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                run_id TEXT NOT NULL,
+                namespace TEXT NOT NULL,
+                deployment TEXT NOT NULL,
+                action TEXT NOT NULL,
+                status TEXT NOT NULL,
+                from_sha TEXT,
+                to_sha TEXT,
+                message TEXT NOT NULL,
+                started_at TEXT NOT NULL,
+                finished_at TEXT NOT NULL,
+                resource_version TEXT,
+                generation INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS reconcile_history_deployment
+                ON reconcile_history (namespace, deployment);",
+        )
+        .context("Failed to initialise history schema")?;
+
+        // Databases created before a column existed get it added (NULL for
+        // the rows already there).
+        let columns: Vec<String> = conn
+            .prepare("SELECT name FROM pragma_table_info('reconcile_history')")?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        for (column, kind) in [("resource_version", "TEXT"), ("generation", "INTEGER")] {
+            if !columns.iter().any(|c| c == column) {
+                conn.execute_batch(&format!(
+                    "ALTER TABLE reconcile_history ADD COLUMN {} {}",
+                    column, kind
+                ))
+                .context("Failed to migrate history schema")?;
+            }
+        }
+
+        Ok(Self {
+            backend: Backend::Sqlite(Mutex::new(conn)),
+            retention: HistoryRetention::default(),
+        })
+    }
+
+    /// Persist the results of one reconcile pass that started at `started_at`,
+    /// then prune what the retention no longer keeps.
+    pub fn record(
+        &self,
+        run_id: &str,
+        started_at: &str,
+        results: &[ReconcileResult],
+    ) -> Result<()> {
+        let finished_at = now_rfc3339();
+        let conn = match &self.backend {
+            Backend::Sqlite(conn) => conn,
+            Backend::State {
+                store,
+                max_records,
+                writes,
+            } => {
+                let _guard = writes.lock().unwrap_or_else(|e| e.into_inner());
+                let max_records = self
+                    .retention
+                    .max_rows
+                    .map_or(*max_records, |rows| rows.min(*max_records));
+                return record_on_state(
+                    store.as_ref(),
+                    max_records,
+                    self.retention.cutoff(),
+                    run_id,
+                    started_at,
+                    &finished_at,
+                    results,
+                );
+            }
+        };
+        let mut conn = conn
+            .lock()
+            .map_err(|_| anyhow::anyhow!("history store lock poisoned"))?;
+        let tx = conn.transaction()?;
+
+        for result in results {
+            tx.execute(
+                "INSERT INTO reconcile_history
+                    (run_id, namespace, deployment, action, status, from_sha, to_sha,
+                     message, started_at, finished_at, resource_version, generation)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    run_id,
+                    result.namespace,
+                    result.deployment,
+                    enum_name(&result.action),
+                    enum_name(&result.status),
+                    result.from_sha,
+                    result.to_sha,
+                    result.message,
+                    started_at,
+                    finished_at,
+                    result.resource_version,
+                    result.generation,
+                ],
+            )?;
+        }
+
+        if let Some(cutoff) = self.retention.cutoff() {
+            tx.execute(
+                "DELETE FROM reconcile_history WHERE finished_at < ?1",
+                params![cutoff],
+            )?;
+        }
+        if let Some(rows) = self.retention.max_rows {
+            tx.execute(
+                "DELETE FROM reconcile_history WHERE id <= (
+                    SELECT id FROM reconcile_history ORDER BY id DESC LIMIT 1 OFFSET ?1
+                 )",
+                params![rows as i64],
+            )?;
+        }
+
+        tx.commit().context("Failed to write history")
+    }
+
+    /// Most recent records across all deployments, newest first.
+    pub fn list(&self, limit: usize) -> Result<Vec<HistoryRecord>> {
+        self.query(None, None, limit)
+    }
+
+    /// Every record of one reconcile pass.
+    pub fn for_run(&self, run_id: &str) -> Result<Vec<HistoryRecord>> {
+        self.query(None, Some(run_id), i64::MAX as usize)
+    }
+
+    /// Most recent records for one deployment, newest first.
+    pub fn for_deployment(
+        &self,
+        namespace: &str,
+        name: &str,
+        limit: usize,
+    ) -> Result<Vec<HistoryRecord>> {
+        self.query(Some((namespace, name)), None, limit)
+    }
+
+    fn query(
+        &self,
+        deployment: Option<(&str, &str)>,
+        run_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<HistoryRecord>> {
+        let conn = match &self.backend {
+            Backend::Sqlite(conn) => conn,
+            Backend::State { store, .. } => {
+                return query_state(store.as_ref(), deployment, run_id, limit);
+            }
+        };
+        let conn = conn
+            .lock()
+            .map_err(|_| anyhow::anyhow!("history store lock poisoned"))?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, run_id, namespace, deployment, action, status, from_sha, to_sha,
+                    message, started_at, finished_at, resource_version, generation
+             FROM reconcile_history
+             WHERE (?1 IS NULL OR namespace = ?1) AND (?2 IS NULL OR deployment = ?2)
+               AND (?3 IS NULL OR run_id = ?3)
+             ORDER BY id DESC
+             LIMIT ?4",
+        )?;
+
+        let (namespace, name) = deployment.unzip();
+        let rows = stmt.query_map(params![namespace, name, run_id, limit as i64], |row| {
+            Ok(HistoryRecord {
+                id: row.get(0)?,
+                run_id: row.get(1)?,
+                namespace: row.get(2)?,
+                deployment: row.get(3)?,
+                action: row.get(4)?,
+                status: row.get(5)?,
+                from_sha: row.get(6)?,
+                to_sha: row.get(7)?,
+                message: row.get(8)?,
+                started_at: row.get(9)?,
+                finished_at: row.get(10)?,
+                resource_version: row.get(11)?,
+                generation: row.get(12)?,
+            })
+        })?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+            .context("Failed to read history")
+    }
+}
+
+/// What a run did for one deployment.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct RunOutcome {
+    pub action: String,
+    pub status: String,
+    pub to_sha: Option<String>,
+    pub message: String,
+}
+
+impl From<&HistoryRecord> for RunOutcome {
+    fn from(record: &HistoryRecord) -> Self {
+        Self {
+            action: record.action.clone(),
+            status: record.status.clone(),
+            to_sha: record.to_sha.clone(),
+            message: record.message.clone(),
+        }
+    }
+}
+
+/// A deployment whose outcome differs between two runs. `before` or `after`
+/// is `None` when the deployment is missing from that run.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct OutcomeChange {
+    pub namespace: String,
+    pub deployment: String,
+    pub before: Option<RunOutcome>,
+    pub after: Option<RunOutcome>,
+}
+
+/// The differences between two reconcile runs, served by `/compare`.
+#[derive(serde::Serialize, Clone, Debug, Default, PartialEq)]
+pub struct RunComparison {
+    pub run_a: String,
+    pub run_b: String,
+    /// Deployments whose action or status changed (including those in only
+    /// one of the runs).
+    pub changed: Vec<OutcomeChange>,
+    /// Deployments failing in `run_b` that did not fail in `run_a`.
+    pub new_failures: Vec<OutcomeChange>,
+    /// Deployments failing in `run_a` that did not fail in `run_b`.
+    pub resolved_failures: Vec<OutcomeChange>,
+    /// How many deployments had the same action and status in both runs.
+    pub unchanged: usize,
+}
+
+/// Compare the records of run `a` with those of a later run `b`.
+pub fn compare_runs(
+    run_a: &str,
+    a: &[HistoryRecord],
+    run_b: &str,
+    b: &[HistoryRecord],
+) -> RunComparison {
+    let by_deployment = |records: &[HistoryRecord]| -> BTreeMap<(String, String), RunOutcome> {
+        records
+            .iter()
+            .map(|r| {
+                (
+                    (r.namespace.clone(), r.deployment.clone()),
+                    RunOutcome::from(r),
+                )
+            })
+            .collect()
+    };
+    let (before, after) = (by_deployment(a), by_deployment(b));
+    let failed = |outcome: Option<&RunOutcome>| outcome.is_some_and(|o| o.status == "failure");
+
+    let mut comparison = RunComparison {
+        run_a: run_a.to_string(),
+        run_b: run_b.to_string(),
+        ..RunComparison::default()
+    };
+    let keys: BTreeSet<_> = before.keys().chain(after.keys()).collect();
+    for key in keys {
+        let (old, new) = (before.get(key), after.get(key));
+        let same = matches!((old, new), (Some(o), Some(n)) if o.action == n.action && o.status == n.status);
+        if same {
+            comparison.unchanged += 1;
+            continue;
+        }
+
+        let change = OutcomeChange {
+            namespace: key.0.clone(),
+            deployment: key.1.clone(),
+            before: old.cloned(),
+            after: new.cloned(),
+        };
+        if failed(new) && !failed(old) {
+            comparison.new_failures.push(change.clone());
+        }
+        if failed(old) && new.is_some() && !failed(new) {
+            comparison.resolved_failures.push(change.clone());
+        }
+        comparison.changed.push(change);
+    }
+
+    comparison
+}
+
+/// Zero-padded so the store's key order is id order.
+fn state_key(id: i64) -> String {
+    format!("{:020}", id)
+}
+
+/// Append `results` to a [`StateStore`]-backed history, then drop the records
+/// that finished before `cutoff` and the oldest beyond `max_records`.
+fn record_on_state(
+    store: &dyn StateStore,
+    max_records: usize,
+    cutoff: Option<String>,
+    run_id: &str,
+    started_at: &str,
+    finished_at: &str,
+    results: &[ReconcileResult],
+) -> Result<()> {
+    let existing = store.entries(HISTORY_BUCKET)?;
+    let mut id = existing
+        .last()
+        .and_then(|(key, _)| key.parse::<i64>().ok())
+        .unwrap_or(0);
+
+    for result in results {
+        id += 1;
+        let record = HistoryRecord {
+            id,
+            run_id: run_id.to_string(),
+            namespace: result.namespace.clone(),
+            deployment: result.deployment.clone(),
+            action: enum_name(&result.action),
+            status: enum_name(&result.status),
+            from_sha: result.from_sha.clone(),
+            to_sha: result.to_sha.clone(),
+            message: result.message.clone(),
+            started_at: started_at.to_string(),
+            finished_at: finished_at.to_string(),
+            resource_version: result.resource_version.clone(),
+            generation: result.generation,
+        };
+        store.put(
+            HISTORY_BUCKET,
+            &state_key(id),
+            &serde_json::to_string(&record)?,
+        )?;
+    }
+
+    // Keys sort by id, so the oldest come first.
+    let excess = (existing.len() + results.len()).saturating_sub(max_records);
+    for (index, (key, value)) in existing.iter().enumerate() {
+        let expired = cutoff.as_deref().is_some_and(|cutoff| {
+            serde_json::from_str::<HistoryRecord>(value)
+                .is_ok_and(|record| record.finished_at.as_str() < cutoff)
+        });
+        if index < excess || expired {
+            store.remove(HISTORY_BUCKET, key)?;
+        }
+    }
+    Ok(())
+}
+
+/// The newest records of a [`StateStore`]-backed history matching the filters.
+fn query_state(
+    store: &dyn StateStore,
+    deployment: Option<(&str, &str)>,
+    run_id: Option<&str>,
+    limit: usize,
+) -> Result<Vec<HistoryRecord>> {
+    let mut records = Vec::new();
+    for (_, value) in store.entries(HISTORY_BUCKET)?.into_iter().rev() {
+        if records.len() >= limit {
+            break;
+        }
+        let record: HistoryRecord =
+            serde_json::from_str(&value).context("Failed to read history")?;
+        let matches = deployment
+            .is_none_or(|(ns, name)| record.namespace == ns && record.deployment == name)
+            && run_id.is_none_or(|id| record.run_id == id);
+        if matches {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+/// The snake_case name an enum serializes to (e.g. `Action::UpToDate` -> `up_to_date`).
+pub(crate) fn enum_name<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_default()
+}
@@ -0,0 +1,108 @@
+use anyhow::{Context, Result};
+use reqwest::{Certificate, Client, ClientBuilder, NoProxy, Proxy};
+use std::sync::OnceLock;
+use tracing::{info, warn};
+
+/// Extra root certificates in the PEM bundle at `path`, e.g. the CA of a
+/// proxy intercepting TLS.
+pub fn load_ca_bundle(path: &str) -> Result<Vec<Certificate>> {
+    let pem = std::fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+    let certificates = Certificate::from_pem_bundle(&pem)
+        .with_context(|| format!("Invalid CA bundle {}", path))?;
+    if certificates.is_empty() {
+        anyhow::bail!("Invalid CA bundle {}: no certificates", path);
+    }
+    Ok(certificates)
+}
+
+/// The certificates of `GITOPS_CA_BUNDLE`, trusted by every HTTP client on
+/// top of the system's. Loaded once; an unreadable bundle is logged and
+/// ignored.
+pub fn ca_certificates() -> &'static [Certificate] {
+    static CERTIFICATES: OnceLock<Vec<Certificate>> = OnceLock::new();
+    CERTIFICATES.get_or_init(|| {
+        let Ok(path) = std::env::var("GITOPS_CA_BUNDLE") else {
+            return vec![];
+        };
+        match load_ca_bundle(&path) {
+            Ok(certificates) => {
+                info!("Trusting {} extra CA certificates", certificates.len());
+                certificates
+            }
+            Err(e) => {
+                warn!("{:#}; trusting the system CAs only", e);
+                vec![]
+            }
+        }
+    })
+}
+
+/// The proxies `var` configures: `HTTPS_PROXY` for `https://` URLs,
+/// `HTTP_PROXY` for `http://` ones and `ALL_PROXY` for both (lowercase names
+/// too), except for the hosts in `NO_PROXY`.
+pub fn proxies_from(var: impl Fn(&str) -> Option<String>) -> Result<Vec<Proxy>> {
+    let var = |name: &str| {
+        var(name)
+            .or_else(|| var(&name.to_lowercase()))
+            .filter(|value| !value.trim().is_empty())
+    };
+    let no_proxy = var("NO_PROXY").and_then(|hosts| NoProxy::from_string(&hosts));
+
+    let mut proxies = vec![];
+    for name in ["HTTPS_PROXY", "HTTP_PROXY", "ALL_PROXY"] {
+        let Some(url) = var(name) else {
+            continue;
+        };
+        let proxy = match name {
+            "HTTPS_PROXY" => Proxy::https(url.trim()),
+            "HTTP_PROXY" => Proxy::http(url.trim()),
+            _ => Proxy::all(url.trim()),
+        }
+        .with_context(|| format!("Invalid {}", name))?;
+        proxies.push(proxy.no_proxy(no_proxy.clone()));
+    }
+    Ok(proxies)
+}
+
+/// The proxies of the operator's environment, see [`proxies_from`]. Invalid
+/// ones are logged and requests go out directly.
+pub fn proxies() -> Vec<Proxy> {
+    proxies_from(|name| std::env::var(name).ok()).unwrap_or_else(|e| {
+        warn!("{:#}; connecting directly", e);
+        vec![]
+    })
+}
+
+/// A client builder trusting [`ca_certificates`] and going through
+/// [`proxies`], for every outgoing HTTP client.
+pub fn client_builder() -> ClientBuilder {
+    let mut builder = Client::builder();
+    for certificate in ca_certificates() {
+        builder = builder.add_root_certificate(certificate.clone());
+    }
+    for proxy in proxies() {
+        builder = builder.proxy(proxy);
+    }
+    builder
+}
+
+/// A client from [`client_builder`], or a default one if that can't be
+/// built.
+pub fn http_client() -> Client {
+    client_builder().build().unwrap_or_else(|e| {
+        warn!("Failed to create HTTP client, using the defaults: {:?}", e);
+        Client::new()
+    })
+}
+
+/// [`client_builder`] for the blocking client.
+pub fn blocking_client_builder() -> reqwest::blocking::ClientBuilder {
+    let mut builder = reqwest::blocking::Client::builder();
+    for certificate in ca_certificates() {
+        builder = builder.add_root_certificate(certificate.clone());
+    }
+    for proxy in proxies() {
+        builder = builder.proxy(proxy);
+    }
+    builder
+}
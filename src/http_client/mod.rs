@@ -0,0 +1,3 @@
+#[allow(clippy::module_inception)]
+mod http_client;
+pub use http_client::*;
@@ -0,0 +1,131 @@
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use k8s_openapi::api::apps::v1::Deployment;
+use kube::ResourceExt;
+use std::sync::OnceLock;
+use tracing::info;
+
+const SERVICE_ACCOUNT_DIR: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// The Deployment a pod named `pod` belongs to: pods of a Deployment are
+/// named `<deployment>-<replicaset hash>-<pod suffix>`.
+pub fn deployment_from_pod_name(pod: &str) -> Option<String> {
+    let (rest, _suffix) = pod.rsplit_once('-')?;
+    let (deployment, _hash) = rest.rsplit_once('-')?;
+    (!deployment.is_empty()).then(|| deployment.to_string())
+}
+
+/// The namespace and name of the ServiceAccount a token was issued to, read
+/// from its `sub` claim (`system:serviceaccount:<namespace>:<name>`). The
+/// signature isn't checked: the token is the operator's own.
+pub fn service_account_from_token(token: &str) -> Option<(String, String)> {
+    let payload = token.trim().split('.').nth(1)?;
+    let claims: serde_json::Value =
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?)
+            .ok()?;
+    let subject = claims.get("sub")?.as_str()?;
+    let (namespace, name) = subject
+        .strip_prefix("system:serviceaccount:")?
+        .split_once(':')?;
+    Some((namespace.to_string(), name.to_string()))
+}
+
+/// Who the operator runs as, so it never reconciles its own Deployment even
+/// if someone copies the `gitops.operator.*` annotations onto it: an image
+/// update would restart the operator mid-pass, over and over.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OperatorIdentity {
+    pub namespace: Option<String>,
+    pub deployment: Option<String>,
+    pub service_account: Option<String>,
+}
+
+impl OperatorIdentity {
+    /// An identity that matches no Deployment, turning the protection off.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Work out the identity from the pod the operator runs in: the namespace
+    /// and ServiceAccount from the mounted token, the Deployment from the pod
+    /// name (`POD_NAME`, else `HOSTNAME`). `GITOPS_OPERATOR_NAMESPACE`,
+    /// `GITOPS_OPERATOR_DEPLOYMENT` and `GITOPS_OPERATOR_SERVICE_ACCOUNT`
+    /// override each part, and `GITOPS_SKIP_SELF=false` turns it all off.
+    pub fn from_env() -> Self {
+        if std::env::var("GITOPS_SKIP_SELF").is_ok_and(|value| value.trim() == "false") {
+            return Self::none();
+        }
+        let env = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let token = std::fs::read_to_string(format!("{}/token", SERVICE_ACCOUNT_DIR))
+            .ok()
+            .and_then(|token| service_account_from_token(&token));
+
+        Self {
+            namespace: env("GITOPS_OPERATOR_NAMESPACE")
+                .or_else(|| token.as_ref().map(|(namespace, _)| namespace.clone()))
+                .or_else(|| {
+                    std::fs::read_to_string(format!("{}/namespace", SERVICE_ACCOUNT_DIR))
+                        .ok()
+                        .map(|namespace| namespace.trim().to_string())
+                }),
+            deployment: env("GITOPS_OPERATOR_DEPLOYMENT").or_else(|| {
+                env("POD_NAME")
+                    .or_else(|| env("HOSTNAME"))
+                    .and_then(|pod| deployment_from_pod_name(&pod))
+            }),
+            service_account: env("GITOPS_OPERATOR_SERVICE_ACCOUNT")
+                .or_else(|| token.map(|(_, name)| name)),
+        }
+    }
+
+    /// Whether `deployment` is the operator's own: in the operator's
+    /// namespace, and either named like it or running its pods as the
+    /// operator's ServiceAccount (unless that is the namespace's `default`,
+    /// which unrelated Deployments share).
+    pub fn is_self(&self, deployment: &Deployment) -> bool {
+        let Some(namespace) = &self.namespace else {
+            return false;
+        };
+        if deployment.namespace().as_ref() != Some(namespace) {
+            return false;
+        }
+        if self
+            .deployment
+            .as_ref()
+            .is_some_and(|name| *name == deployment.name_any())
+        {
+            return true;
+        }
+
+        let pod_service_account = deployment
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.template.spec.as_ref())
+            .and_then(|pod| pod.service_account_name.as_deref())
+            .unwrap_or("default");
+        self.service_account
+            .as_deref()
+            .is_some_and(|sa| sa != "default" && sa == pod_service_account)
+    }
+}
+
+/// The process-wide identity, worked out on first use.
+pub fn operator_identity() -> &'static OperatorIdentity {
+    static IDENTITY: OnceLock<OperatorIdentity> = OnceLock::new();
+    IDENTITY.get_or_init(|| {
+        let identity = OperatorIdentity::from_env();
+        if let Some(namespace) = &identity.namespace {
+            info!(
+                "Never reconciling the operator's own Deployment ({}/{}, service account {})",
+                namespace,
+                identity.deployment.as_deref().unwrap_or("?"),
+                identity.service_account.as_deref().unwrap_or("?")
+            );
+        }
+        identity
+    })
+}
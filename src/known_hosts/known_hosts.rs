@@ -0,0 +1,211 @@
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use openssl::hash::{MessageDigest, hash};
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+
+/// Whether `GITOPS_STRICT_HOST_KEY_CHECKING` is `true`: SSH hosts missing
+/// from known_hosts (or all hosts, when there is none) are refused instead
+/// of trusted with a warning.
+pub fn strict_host_key_checking() -> bool {
+    std::env::var("GITOPS_STRICT_HOST_KEY_CHECKING").is_ok_and(|value| value.trim() == "true")
+}
+
+/// The known_hosts file mounted into the operator (`GITOPS_KNOWN_HOSTS_FILE`),
+/// used for deployments without `known_hosts_secret_name`.
+pub fn known_hosts_file() -> Option<String> {
+    std::env::var("GITOPS_KNOWN_HOSTS_FILE")
+        .ok()
+        .filter(|path| !path.trim().is_empty())
+}
+
+/// What a known_hosts file says about a host's key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostKeyStatus {
+    /// A line for the host lists this key.
+    Match,
+    /// The host is listed, with other keys only.
+    Mismatch,
+    /// The key is marked `@revoked`.
+    Revoked,
+    /// No line is for the host.
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum HostPattern {
+    Plain(String),
+    /// `|1|salt|hash`: HMAC-SHA1 of the host name keyed with the salt.
+    Hashed {
+        salt: Vec<u8>,
+        hash: Vec<u8>,
+    },
+}
+
+impl HostPattern {
+    fn matches(&self, host: &str) -> bool {
+        match self {
+            HostPattern::Plain(pattern) => {
+                // libgit2 doesn't say which port the host was reached on.
+                let pattern = match pattern.strip_prefix('[').and_then(|p| p.split_once("]:")) {
+                    Some((name, _port)) => name,
+                    None => pattern,
+                };
+                pattern.eq_ignore_ascii_case(host)
+            }
+            HostPattern::Hashed { salt, hash } => hmac_sha1(salt, host)
+                .map(|digest| digest == *hash)
+                .unwrap_or(false),
+        }
+    }
+}
+
+fn hmac_sha1(key: &[u8], data: &str) -> Option<Vec<u8>> {
+    let key = PKey::hmac(key).ok()?;
+    let mut signer = Signer::new(MessageDigest::sha1(), &key).ok()?;
+    signer.update(data.as_bytes()).ok()?;
+    signer.sign_to_vec().ok()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct KnownHost {
+    patterns: Vec<HostPattern>,
+    key_type: String,
+    key: Vec<u8>,
+    revoked: bool,
+}
+
+/// The host keys of an OpenSSH known_hosts file. Plain and hashed host
+/// names are understood (`[host]:port` ones for any port of the host, as
+/// libgit2 doesn't tell which); wildcard patterns and `@cert-authority`
+/// lines are ignored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KnownHosts {
+    hosts: Vec<KnownHost>,
+}
+
+impl KnownHosts {
+    pub fn parse(content: &str) -> Self {
+        let hosts = content.lines().filter_map(parse_line).collect();
+        Self { hosts }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty()
+    }
+
+    /// Check the `key_type` key `key` that `host` presented.
+    pub fn check(&self, host: &str, key_type: &str, key: &[u8]) -> HostKeyStatus {
+        self.check_with(host, |known| known.key_type == key_type && known.key == key)
+    }
+
+    /// Like [`KnownHosts::check`] for when only the key's SHA-256 digest is
+    /// known.
+    pub fn check_sha256(&self, host: &str, digest: &[u8]) -> HostKeyStatus {
+        self.check_with(host, |known| {
+            hash(MessageDigest::sha256(), &known.key)
+                .map(|known| known.as_ref() == digest)
+                .unwrap_or(false)
+        })
+    }
+
+    /// The keys as OpenSSH known_hosts lines, for handing to `ssh`. Lines
+    /// whose patterns were all unsupported are left out.
+    pub fn to_openssh(&self) -> String {
+        let mut content = String::new();
+        for known in &self.hosts {
+            let patterns: Vec<String> = known
+                .patterns
+                .iter()
+                .map(|pattern| match pattern {
+                    HostPattern::Plain(pattern) => pattern.clone(),
+                    HostPattern::Hashed { salt, hash } => {
+                        format!("|1|{}|{}", STANDARD.encode(salt), STANDARD.encode(hash))
+                    }
+                })
+                .collect();
+            let hosts = match (known.revoked, patterns.is_empty()) {
+                (false, true) => continue,
+                (true, true) => "*".to_string(),
+                _ => patterns.join(","),
+            };
+            if known.revoked {
+                content.push_str("@revoked ");
+            }
+            content.push_str(&format!(
+                "{} {} {}\n",
+                hosts,
+                known.key_type,
+                STANDARD.encode(&known.key)
+            ));
+        }
+        content
+    }
+
+    fn check_with(&self, name: &str, same_key: impl Fn(&KnownHost) -> bool) -> HostKeyStatus {
+        let mut listed = false;
+        let mut matched = false;
+        for known in &self.hosts {
+            if known.revoked {
+                // Revocations apply whatever the host.
+                if same_key(known) {
+                    return HostKeyStatus::Revoked;
+                }
+                continue;
+            }
+            if !known.patterns.iter().any(|pattern| pattern.matches(name)) {
+                continue;
+            }
+            listed = true;
+            matched |= same_key(known);
+        }
+        match (listed, matched) {
+            (_, true) => HostKeyStatus::Match,
+            (true, false) => HostKeyStatus::Mismatch,
+            (false, false) => HostKeyStatus::Unknown,
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Option<KnownHost> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let mut fields = line.split_whitespace();
+    let mut hosts = fields.next()?;
+    let mut revoked = false;
+    if let Some(marker) = hosts.strip_prefix('@') {
+        match marker {
+            "revoked" => revoked = true,
+            _ => return None,
+        }
+        hosts = fields.next()?;
+    }
+    let key_type = fields.next()?.to_string();
+    let key = STANDARD.decode(fields.next()?).ok()?;
+
+    let patterns = hosts
+        .split(',')
+        .filter_map(|pattern| {
+            if let Some(hashed) = pattern.strip_prefix("|1|") {
+                let (salt, hash) = hashed.split_once('|')?;
+                return Some(HostPattern::Hashed {
+                    salt: STANDARD.decode(salt).ok()?,
+                    hash: STANDARD.decode(hash).ok()?,
+                });
+            }
+            // Negations and wildcards aren't supported: never match them.
+            if pattern.contains(['*', '?', '!']) {
+                return None;
+            }
+            Some(HostPattern::Plain(pattern.to_string()))
+        })
+        .collect();
+
+    Some(KnownHost {
+        patterns,
+        key_type,
+        key,
+        revoked,
+    })
+}
@@ -0,0 +1,3 @@
+#[allow(clippy::module_inception)]
+mod known_hosts;
+pub use known_hosts::*;
@@ -9,26 +9,138 @@
 //!
 //! See the project README for the full annotation reference and HTTP API.
 //!
+//! ## Embedding
+//!
+//! The binary wraps the engine in an HTTP server, a schedule and webhooks.
+//! Other services can run it directly through [`operator::Operator`],
+//! swapping any of the providers in [`traits`]. The engine's other state is
+//! process-wide, so run one `Operator` per process:
+//!
+//! ```no_run
+//! use gitops_operator::operator::Operator;
+//! use std::time::Duration;
+//!
+//! # async fn embed() -> anyhow::Result<()> {
+//! let operator = Operator::builder()
+//!     .interval(Duration::from_secs(60))
+//!     .build();
+//!
+//! // Reconcile every annotated Deployment each minute...
+//! operator.run().await?;
+//!
+//! // ...or only the ones at hand, once.
+//! let results = operator.reconcile_once(&[]).await;
+//! # Ok(())
+//! # }
+//! ```
+//!
 //! ## Modules
 //!
+//! - [`admission`]: the mutating admission webhook injecting default annotations.
+//! - [`approvals`]: promotions held back until someone approves them.
+//! - [`backups`]: content-addressed copies of manifests taken before patching.
+//! - [`bitbucket`]: opening pull requests on Bitbucket Cloud and Bitbucket Server.
+//! - [`capabilities`]: the API server version and optional resources probed at startup.
+//! - [`changes`]: skipping passes over deployments that haven't changed.
+//! - [`codecommit`]: signing git over HTTPS to AWS CodeCommit with the operator's AWS credentials.
+//! - [`coalesce`]: running bursts of webhook triggers for one repository once,
+//!   and the fetches deployments of one run share.
 //! - [`configuration`]: deployment annotation parsing ([`configuration::Entry`]),
 //!   the reconcile engine ([`configuration::DeploymentProcessor`]), and the
 //!   structured per-deployment result ([`configuration::ReconcileResult`]).
+//! - [`dependencies`]: rules pinning a promoted tag in dependent services' manifests.
+//! - [`drift`]: live images that differ from what the manifests declare.
+//! - [`effective_config`]: where each of a deployment's settings comes from.
+//! - [`egress`]: optional allowlist of the hosts the operator may call.
+//! - [`fetch_stats`]: per-repository fetch timings behind the slow-repo report.
 //! - [`files`]: reading and patching the image tag in deployment manifests.
 //! - [`git`]: cloning, updating, committing, and pushing repositories over SSH.
-//! - [`github`]: querying GitHub Actions build status for a commit.
+//! - [`gitea`]: opening pull requests on Gitea and Forgejo.
+//! - [`github`]: querying GitHub Actions build status for a commit, and opening pull requests.
+//! - [`github_app`]: minting and caching GitHub App installation tokens.
+//! - [`gitlab`]: opening merge requests on GitLab, hosted or self-managed.
+//! - [`history`]: the audit trail of reconcile results behind `/history` and `/compare`.
+//! - [`http_client`]: extra root CAs and proxies for every outgoing HTTP client.
+//! - [`identity`]: recognising the operator's own Deployment so it is never reconciled.
+//! - [`pauses`]: deployments paused through the API or their annotation.
+//! - [`policy`]: expressions a promotion must satisfy before it is committed.
+//! - [`pull_requests`]: proposing manifest changes as pull requests instead of pushing them.
+//! - [`registries`]: the operator-level registry map resolving credentials per image prefix.
 //! - [`registry`]: checking whether an image tag exists in a container registry.
+//! - [`results`]: the latest result per deployment, long-polled by `/watch`.
+//! - [`retry`]: retry middleware shared by every outgoing HTTP client.
+//! - [`rollouts`]: verifying that pushed images actually roll out in the cluster.
+//! - [`schedule`]: the internal reconcile scheduler and its mirror `CronJob`.
 //! - [`secrets`]: fetching SSH keys, registry, notification, and token secrets.
+//! - [`known_hosts`]: checking SSH host keys against a known_hosts file.
+//! - [`listeners`]: the addresses the HTTP servers bind to.
+//! - [`locks`]: per-deployment reconcile locks and the stale lock reaper.
+//! - [`maintenance`]: repacking cached checkouts and reporting their size.
+//! - [`maintenance_windows`]: deferring promotions during cluster maintenance.
+//! - [`namespaces`]: the namespace deny-list and opt-in label policy.
+//! - [`metrics`]: names and descriptions of the Prometheus metrics we record.
 //! - [`notifications`]: sending Slack-compatible webhook notifications.
+//! - [`operator`]: the reconcile engine as a library ([`operator::Operator`]).
+//! - [`slack`]: Approve/Reject buttons on approval notifications and their signed callbacks.
+//! - [`state`]: the pluggable storage (memory, SQLite, ConfigMap) behind operator state.
+//! - [`suspension`]: suspending deployments after repeated push failures.
 //! - [`telemetry`]: tracing, metrics, and OpenTelemetry export setup.
+//! - [`templates`]: `{placeholder}` rendering for commit and notification messages.
+//! - [`timeline`]: per-deployment image changes, commits and notifications behind `/timeline`.
 //! - [`traits`]: the dependency-injection interfaces used to test the above.
+//! - [`validation`]: schema checks run on patched manifests before they are pushed.
+//! - [`versions`]: picking the newest registry tag within a semver range.
+//! - [`webhooks`]: parsing registry push events into reconcile triggers.
 
+pub mod admission;
+pub mod approvals;
+pub mod backups;
+pub mod bitbucket;
+pub mod capabilities;
+pub mod changes;
+pub mod coalesce;
+pub mod codecommit;
 pub mod configuration;
+pub mod dependencies;
+pub mod drift;
+pub mod effective_config;
+pub mod egress;
+pub mod fetch_stats;
 pub mod files;
 pub mod git;
+pub mod gitea;
 pub mod github;
+pub mod github_app;
+pub mod gitlab;
+pub mod history;
+pub mod http_client;
+pub mod identity;
+pub mod known_hosts;
+pub mod listeners;
+pub mod locks;
+pub mod maintenance;
+pub mod maintenance_windows;
+pub mod metrics;
+pub mod namespaces;
 pub mod notifications;
+pub mod operator;
+pub mod pauses;
+pub mod policy;
+pub mod pull_requests;
+pub mod registries;
 pub mod registry;
+pub mod results;
+pub mod retry;
+pub mod rollouts;
+pub mod schedule;
 pub mod secrets;
+pub mod slack;
+pub mod state;
+pub mod suspension;
 pub mod telemetry;
+pub mod templates;
+pub mod timeline;
 pub mod traits;
+pub mod validation;
+pub mod versions;
+pub mod webhooks;
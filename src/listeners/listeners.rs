@@ -0,0 +1,80 @@
+use anyhow::{Context, Result};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{SocketAddr, TcpListener};
+
+/// Address the HTTP API listens on unless `GITOPS_LISTEN_ADDRS` says
+/// otherwise.
+pub const DEFAULT_LISTEN_ADDR: &str = "0.0.0.0:8000";
+
+const LISTEN_BACKLOG: i32 = 1024;
+
+/// Parse a comma or whitespace separated list of socket addresses, e.g.
+/// `0.0.0.0:8000, [::]:8000`. Duplicates are dropped.
+pub fn parse_listen_addrs(value: &str) -> Result<Vec<SocketAddr>> {
+    let mut addrs = vec![];
+    for part in value.split([',', ' ']).filter(|p| !p.is_empty()) {
+        let addr: SocketAddr = part
+            .parse()
+            .with_context(|| format!("Invalid listen address {:?}", part))?;
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    if addrs.is_empty() {
+        anyhow::bail!("No listen address in {:?}", value);
+    }
+    Ok(addrs)
+}
+
+/// Addresses to serve the HTTP API on, from `GITOPS_LISTEN_ADDRS` (default
+/// `0.0.0.0:8000`). Use `[::]:8000` on IPv6-only clusters, or list both
+/// families to listen on each explicitly.
+pub fn listen_addrs() -> Result<Vec<SocketAddr>> {
+    match std::env::var("GITOPS_LISTEN_ADDRS") {
+        Ok(value) => parse_listen_addrs(&value).context("Invalid GITOPS_LISTEN_ADDRS"),
+        Err(_) => parse_listen_addrs(DEFAULT_LISTEN_ADDR),
+    }
+}
+
+/// The same hosts as `addrs` on another `port`, so secondary servers (the
+/// admission webhook) follow the API's address families.
+pub fn with_port(addrs: &[SocketAddr], port: u16) -> Vec<SocketAddr> {
+    let mut ported: Vec<SocketAddr> = vec![];
+    for addr in addrs {
+        let addr = SocketAddr::new(addr.ip(), port);
+        if !ported.contains(&addr) {
+            ported.push(addr);
+        }
+    }
+    ported
+}
+
+/// Bind a non-blocking listener to each address. An IPv6 wildcard normally
+/// accepts IPv4 connections too, which would clash with an IPv4 address on
+/// the same port, so IPv6 sockets are restricted to IPv6 whenever the list
+/// also holds an IPv4 address with their port.
+pub fn bind_all(addrs: &[SocketAddr]) -> Result<Vec<TcpListener>> {
+    addrs
+        .iter()
+        .map(|addr| bind(addr, addrs).with_context(|| format!("Failed to listen on {}", addr)))
+        .collect()
+}
+
+fn bind(addr: &SocketAddr, all: &[SocketAddr]) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(
+        Domain::for_address(*addr),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    socket.set_reuse_address(true)?;
+    if addr.is_ipv6() {
+        let shared = all
+            .iter()
+            .any(|other| other.is_ipv4() && other.port() == addr.port());
+        socket.set_only_v6(shared)?;
+    }
+    socket.bind(&(*addr).into())?;
+    socket.listen(LISTEN_BACKLOG)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket.into())
+}
@@ -0,0 +1,204 @@
+use crate::changes::change_cache;
+use crate::history::now_rfc3339;
+use crate::metrics::STALE_LOCKS_REAPED_TOTAL;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+const DEFAULT_LOCK_DEADLINE_SECS: u64 = 900;
+
+/// How long a reconcile may hold an entry lock before the reaper considers it
+/// orphaned, from `GITOPS_LOCK_DEADLINE_SECS` (default 900).
+pub fn lock_deadline() -> Duration {
+    let secs = std::env::var("GITOPS_LOCK_DEADLINE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_LOCK_DEADLINE_SECS);
+    Duration::from_secs(secs)
+}
+
+struct Held {
+    token: u64,
+    acquired: Instant,
+    since: String,
+}
+
+/// A lock the reaper released because it outlived the deadline.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct StaleLock {
+    pub key: String,
+    /// When the lock was acquired, RFC 3339.
+    pub since: String,
+    pub held_for_secs: u64,
+}
+
+/// Per-deployment reconcile locks, so two passes (e.g. `/reconcile` and a
+/// webhook) never work on the same deployment at once.
+#[derive(Default)]
+pub struct EntryLocks {
+    held: Mutex<HashMap<String, Held>>,
+    next_token: AtomicU64,
+}
+
+/// Releases its lock when dropped, unless the reaper already took it away and
+/// handed the key to someone else in the meantime.
+pub struct EntryLockGuard<'a> {
+    locks: &'a EntryLocks,
+    key: String,
+    token: u64,
+}
+
+impl Drop for EntryLockGuard<'_> {
+    fn drop(&mut self) {
+        let mut held = self.locks.held.lock().unwrap_or_else(|e| e.into_inner());
+        if held.get(&self.key).is_some_and(|h| h.token == self.token) {
+            held.remove(&self.key);
+        }
+    }
+}
+
+impl EntryLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take the lock for `key`, or `None` if another reconcile holds it.
+    pub fn try_acquire(&self, key: &str) -> Option<EntryLockGuard<'_>> {
+        let mut held = self.held.lock().unwrap_or_else(|e| e.into_inner());
+        if held.contains_key(key) {
+            return None;
+        }
+
+        let token = self.next_token.fetch_add(1, Ordering::Relaxed);
+        held.insert(
+            key.to_string(),
+            Held {
+                token,
+                acquired: Instant::now(),
+                since: now_rfc3339(),
+            },
+        );
+
+        Some(EntryLockGuard {
+            locks: self,
+            key: key.to_string(),
+            token,
+        })
+    }
+
+    /// Keys currently locked.
+    pub fn held(&self) -> Vec<String> {
+        let held = self.held.lock().unwrap_or_else(|e| e.into_inner());
+        held.keys().cloned().collect()
+    }
+
+    /// Forcibly release every lock held for longer than `deadline`, e.g. by a
+    /// task that panicked or was cancelled mid-reconcile without unwinding.
+    pub fn reap(&self, deadline: Duration) -> Vec<StaleLock> {
+        let mut held = self.held.lock().unwrap_or_else(|e| e.into_inner());
+
+        let stale: Vec<StaleLock> = held
+            .iter()
+            .filter(|(_, h)| h.acquired.elapsed() >= deadline)
+            .map(|(key, h)| StaleLock {
+                key: key.clone(),
+                since: h.since.clone(),
+                held_for_secs: h.acquired.elapsed().as_secs(),
+            })
+            .collect();
+
+        for lock in &stale {
+            held.remove(&lock.key);
+            warn!(
+                "Reaped stale reconcile lock for {} (held since {}, {}s > deadline {}s)",
+                lock.key,
+                lock.since,
+                lock.held_for_secs,
+                deadline.as_secs()
+            );
+        }
+
+        if !stale.is_empty() {
+            ::metrics::counter!(STALE_LOCKS_REAPED_TOTAL).increment(stale.len() as u64);
+        }
+
+        stale
+    }
+}
+
+/// Process-wide entry locks shared by every reconcile trigger.
+pub fn entry_locks() -> &'static EntryLocks {
+    static LOCKS: OnceLock<EntryLocks> = OnceLock::new();
+    LOCKS.get_or_init(EntryLocks::new)
+}
+
+/// Locks on the checkouts git works in. Entry locks don't cover them: two
+/// deployments can share one (the same name in two namespaces), and a
+/// rollback or a rollout check may run beside a pass of its deployment.
+/// Unlike entry locks, these wait for the holder.
+#[derive(Default)]
+pub struct PathLocks {
+    paths: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl PathLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lock every checkout in `paths` (trailing slashes aside), waiting for
+    /// whoever holds them. They are taken in a fixed order, so callers
+    /// locking overlapping sets can't deadlock.
+    pub async fn lock(&self, paths: &[&str]) -> Vec<tokio::sync::OwnedMutexGuard<()>> {
+        let mut keys: Vec<String> = paths
+            .iter()
+            .map(|path| path.trim_end_matches('/').to_string())
+            .collect();
+        keys.sort();
+        keys.dedup();
+
+        let mut guards = Vec::with_capacity(keys.len());
+        for key in keys {
+            let lock = {
+                let mut locks = self.paths.lock().unwrap_or_else(|e| e.into_inner());
+                locks.entry(key).or_default().clone()
+            };
+            guards.push(lock.lock_owned().await);
+        }
+        guards
+    }
+}
+
+/// Process-wide checkout locks.
+pub fn path_locks() -> &'static PathLocks {
+    static LOCKS: OnceLock<PathLocks> = OnceLock::new();
+    LOCKS.get_or_init(PathLocks::new)
+}
+
+/// Clear what a reaped pass of deployment `key` left in progress: the
+/// fingerprint of its last pass, which the stuck one may still overwrite, so
+/// the next pass does the full work instead of trusting it.
+pub fn reset_in_progress(key: &str) {
+    change_cache().forget(key);
+}
+
+/// Periodically reap locks older than `deadline`, resetting the in-progress
+/// state of their deployments. Runs forever.
+pub async fn run_reaper(locks: &'static EntryLocks, deadline: Duration) {
+    let period = (deadline / 4).max(Duration::from_secs(1));
+    info!(
+        "Starting stale lock reaper (deadline {}s, every {}s)",
+        deadline.as_secs(),
+        period.as_secs()
+    );
+
+    let mut interval = tokio::time::interval(period);
+    loop {
+        interval.tick().await;
+        for lock in locks.reap(deadline) {
+            reset_in_progress(&lock.key);
+        }
+    }
+}
@@ -1,16 +1,60 @@
-use axum::extract::State;
+use axum::extract::{FromRef, Path, Query, State};
 use axum::http;
 use axum::response::IntoResponse;
 use axum::routing::get;
 use axum::{Json, Router, routing};
 use axum_prometheus::PrometheusMetricLayer;
 use futures::{StreamExt, future};
-use gitops_operator::configuration::{Entry, ReconcileResult, status_report};
-use gitops_operator::telemetry::init_subscriber;
+use gitops_operator::admission::{
+    AdmissionSettings, AnnotationDefaults, annotation_defaults, mutate,
+};
+use gitops_operator::approvals::{PendingChange, approval_queue, guarded};
+use gitops_operator::backups::{BackupRecord, BackupStore, backup_store};
+use gitops_operator::capabilities::{
+    Capabilities, EVENTS_RESOURCE, capabilities, probe, resource_name, set_capabilities,
+};
+use gitops_operator::coalesce::{Coalescer, coalesce_window};
+use gitops_operator::configuration::{
+    DeploymentProcessor, Entry, ReconcileResult, exit_code, status_report,
+};
+use gitops_operator::drift::{DriftReport, drift_board};
+use gitops_operator::effective_config::{EffectiveConfig, effective_config};
+use gitops_operator::egress::{EgressKind, egress_policy};
+use gitops_operator::fetch_stats::{RepoCost, fetch_stats};
+use gitops_operator::git::ImageRevision;
+use gitops_operator::history::{
+    HistoryRecord, HistoryRetention, HistoryStore, RunComparison, STATE_HISTORY_LIMIT,
+    compare_runs, history_path, now_rfc3339,
+};
+use gitops_operator::listeners::{bind_all, listen_addrs, with_port};
+use gitops_operator::locks::{entry_locks, lock_deadline, run_reaper};
+use gitops_operator::maintenance::{GcSettings, run_repo_maintenance};
+use gitops_operator::maintenance_windows::maintenance_resources;
+use gitops_operator::metrics::{describe_metrics, grafana_dashboard, prometheus_rules};
+use gitops_operator::namespaces::namespace_policy;
+use gitops_operator::notifications::HttpNotificationSender;
+use gitops_operator::results::{WatchedResult, result_board};
+use gitops_operator::rollouts::{Rollout, rollouts};
+use gitops_operator::schedule::{ScheduleSettings, run_cronjob_sync, run_scheduler};
+use gitops_operator::slack::{
+    ApprovalClick, SIGNATURE_HEADER, TIMESTAMP_HEADER, parse_interaction, replacement_payload,
+    slack_signing_secret, verify_signature,
+};
+use gitops_operator::state::{
+    MemoryState, StateBackend, StateSettings, blocking, open_state_store, set_state_store,
+};
+use gitops_operator::telemetry::{TelemetrySettings, init_subscriber, telemetry};
+use gitops_operator::timeline::{TimelineEvent, merge, timeline};
+use gitops_operator::webhooks::parse_registry_event;
 use k8s_openapi::api::apps::v1::Deployment;
+use k8s_openapi::api::core::v1::Namespace;
+use kube::core::DynamicObject;
+use kube::core::admission::AdmissionReview;
 use kube::runtime::{WatchStreamExt, reflector, watcher};
 use kube::{Api, Client, ResourceExt};
 use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use tower_http::trace::TraceLayer;
 use tracing::Level;
 use tracing::{debug, info, instrument, warn};
@@ -21,16 +65,803 @@ type Cache = reflector::Store<Deployment>;
 #[global_allocator]
 static GLOBAL: jemallocator::Jemalloc = jemallocator::Jemalloc;
 
-// - GET /reconcile
+/// Registry webhook triggers (each deployment with the trigger's `force`),
+/// batched per app repository and branch.
+type WebhookCoalescer = Coalescer<(Entry, bool), Vec<ReconcileResult>>;
+
+/// Shared state for the HTTP handlers. Handlers that only need the reflector
+/// store keep extracting `State<Cache>` through `FromRef`.
+#[derive(Clone)]
+struct AppState {
+    store: Cache,
+    history: Arc<HistoryStore>,
+    webhooks: Arc<WebhookCoalescer>,
+}
+
+impl FromRef<AppState> for Cache {
+    fn from_ref(state: &AppState) -> Cache {
+        state.store.clone()
+    }
+}
+
+/// Persist the results of a pass to the history store, logging rather than
+/// failing the request when the write does not succeed, and wake `/watch`
+/// callers waiting on them.
+async fn record_history(
+    history: &Arc<HistoryStore>,
+    started_at: &str,
+    results: &[ReconcileResult],
+) {
+    let (started_at, records) = (started_at.to_string(), results.to_vec());
+    let recorded = history
+        .blocking(move |history| history.record(&Uuid::new_v4().to_string(), &started_at, &records))
+        .await;
+    if let Err(e) = recorded {
+        warn!("Failed to record reconcile history: {:?}", e);
+    }
+    result_board().publish(results);
+}
+
+#[derive(serde::Deserialize)]
+struct ReconcileParams {
+    /// Reconcile deployments even if nothing changed since their last pass.
+    #[serde(default)]
+    force: bool,
+}
+
+// - GET /reconcile?force=true
 #[tracing::instrument(
     name = "reconcile",
-    skip(store),
+    skip(state, params),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+async fn reconcile(
+    State(state): State<AppState>,
+    Query(params): Query<ReconcileParams>,
+) -> Json<Vec<ReconcileResult>> {
+    let started_at = now_rfc3339();
+    let entries: Vec<Entry> = state
+        .store
+        .state()
+        .iter()
+        .filter_map(|d| Entry::new(d))
+        .collect();
+    let results = Entry::reconcile_entries(entries, params.force).await;
+    record_history(&state.history, &started_at, &results).await;
+    Json(results)
+}
+
+/// Reconcile a batch of webhook-triggered deployments once each, forcing the
+/// pass if any trigger asked for it.
+async fn reconcile_batch(
+    history: Arc<HistoryStore>,
+    items: Vec<(Entry, bool)>,
+) -> Vec<ReconcileResult> {
+    let started_at = now_rfc3339();
+    let force = items.iter().any(|(_, force)| *force);
+    // Several events may name the same deployment; keep the newest view of it.
+    let mut entries = BTreeMap::new();
+    for (entry, _) in items {
+        entries.insert(entry.key(), entry);
+    }
+
+    let results = Entry::reconcile_entries(entries.into_values().collect(), force).await;
+    record_history(&history, &started_at, &results).await;
+    results
+}
+
+/// Header telling a registry webhook caller how many events its reconcile
+/// served (more than 1 when bursts were coalesced).
+const COALESCED_EVENTS_HEADER: &str = "x-coalesced-events";
+
+type WebhookResponse =
+    Result<([(&'static str, String); 1], Json<Vec<ReconcileResult>>), (http::StatusCode, String)>;
+
+// - POST /webhook/registry: reconcile only the deployments whose image was
+//   just pushed, according to a Harbor, Docker Hub, or ECR (EventBridge) event.
+//   Events for the same app repository and branch arriving within
+//   GITOPS_WEBHOOK_COALESCE_MS of each other share one reconcile.
+#[tracing::instrument(
+    name = "registry_webhook",
+    skip(state, params, payload),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+async fn registry_webhook(
+    State(state): State<AppState>,
+    Query(params): Query<ReconcileParams>,
+    Json(payload): Json<serde_json::Value>,
+) -> WebhookResponse {
+    let events = parse_registry_event(&payload).ok_or((
+        http::StatusCode::BAD_REQUEST,
+        "unrecognised registry webhook payload".to_string(),
+    ))?;
+
+    let entries: Vec<Entry> = state
+        .store
+        .state()
+        .iter()
+        .filter_map(|d| Entry::new(d))
+        .filter(|e| events.iter().any(|ev| ev.matches(e)))
+        .collect();
+
+    info!(
+        "Registry webhook: {} push event(s), {} matching deployment(s)",
+        events.len(),
+        entries.len()
+    );
+
+    let mut batches: BTreeMap<String, Vec<(Entry, bool)>> = BTreeMap::new();
+    for entry in entries {
+        let key = format!(
+            "{}@{}",
+            &entry.config.app_repository, &entry.config.observe_branch
+        );
+        batches.entry(key).or_default().push((entry, params.force));
+    }
+
+    let window = coalesce_window();
+    let batches = future::try_join_all(batches.into_iter().map(|(key, items)| {
+        let history = state.history.clone();
+        let webhooks = state.webhooks.clone();
+        async move {
+            webhooks
+                .submit(&key, items, window, move |items| {
+                    reconcile_batch(history, items)
+                })
+                .await
+        }
+    }))
+    .await
+    .map_err(|e| (http::StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))?;
+
+    let coalesced = batches.iter().map(|b| b.events).max().unwrap_or(1);
+    let results = batches
+        .iter()
+        .flat_map(|b| b.output.iter().cloned())
+        .collect();
+
+    Ok((
+        [(COALESCED_EVENTS_HEADER, coalesced.to_string())],
+        Json(results),
+    ))
+}
+
+#[derive(serde::Deserialize)]
+struct RollbackParams {
+    /// Image tag (SHA) to restore, or `previous` (the default).
+    target: Option<String>,
+}
+
+// - POST /rollback/{namespace}/{name}?target=<sha|previous>: commit the
+//   manifest back to an earlier image tag.
+#[tracing::instrument(
+    name = "rollback",
+    skip(state, params),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+async fn rollback(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+    Query(params): Query<RollbackParams>,
+) -> Result<Json<ReconcileResult>, (http::StatusCode, String)> {
+    let started_at = now_rfc3339();
+    let entry = Entry::find(&state.store, &namespace, &name).ok_or((
+        http::StatusCode::NOT_FOUND,
+        format!("no tracked deployment {}/{}", namespace, name),
+    ))?;
+
+    let _guard = entry_locks().try_acquire(&entry.key()).ok_or((
+        http::StatusCode::CONFLICT,
+        format!("{} is already being reconciled", entry.key()),
+    ))?;
+
+    let target = params.target.unwrap_or_else(|| "previous".to_string());
+    let processor = DeploymentProcessor::production();
+
+    let result = processor.rollback(&entry, &target).await;
+    record_history(&state.history, &started_at, std::slice::from_ref(&result)).await;
+
+    Ok(Json(result))
+}
+
+#[derive(serde::Deserialize)]
+struct PauseParams {
+    /// Who paused the deployment, recorded with the pause.
+    by: Option<String>,
+    /// Also annotate the Deployment, so the pause survives the operator's
+    /// state.
+    #[serde(default)]
+    annotate: bool,
+}
+
+// - POST /pause/{namespace}/{name}?by=<who>&annotate=true: stop reconciling a
+//   deployment until it is resumed, without disabling it.
+#[tracing::instrument(
+    name = "pause",
+    skip(state, params),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+async fn pause(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+    Query(params): Query<PauseParams>,
+) -> Result<String, (http::StatusCode, String)> {
+    let entry = Entry::find(&state.store, &namespace, &name).ok_or((
+        http::StatusCode::NOT_FOUND,
+        format!("no tracked deployment {}/{}", namespace, name),
+    ))?;
+    if entry.paused.is_some() && !params.annotate {
+        return Err((
+            http::StatusCode::CONFLICT,
+            format!("{} is already paused", entry.key()),
+        ));
+    }
+
+    DeploymentProcessor::production()
+        .pause(&entry, params.by, params.annotate)
+        .await
+        .map(|_| format!("{} paused\n", entry.key()))
+        .map_err(|e| (http::StatusCode::BAD_GATEWAY, format!("{:#}", e)))
+}
+
+// - POST /resume/{namespace}/{name}: lift a pause, or a suspension after
+//   repeated push failures, so the deployment is reconciled again.
+#[tracing::instrument(
+    name = "resume",
+    skip(state),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+async fn resume(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> Result<String, (http::StatusCode, String)> {
+    let entry = Entry::find(&state.store, &namespace, &name).ok_or((
+        http::StatusCode::NOT_FOUND,
+        format!("no tracked deployment {}/{}", namespace, name),
+    ))?;
+    if entry.suspended_at().is_none() && entry.paused.is_none() {
+        return Err((
+            http::StatusCode::CONFLICT,
+            format!("{} is not suspended or paused", entry.key()),
+        ));
+    }
+
+    DeploymentProcessor::production()
+        .resume(&entry)
+        .await
+        .map(|_| format!("{} resumed\n", entry.key()))
+        .map_err(|e| (http::StatusCode::BAD_GATEWAY, format!("{:#}", e)))
+}
+
+// - POST /mutate (admission port): add the default `gitops.operator.*`
+//   annotations to new Deployments.
+#[tracing::instrument(name = "mutate", skip(defaults, review), fields())]
+async fn mutate_deployment(
+    State(defaults): State<Arc<Vec<AnnotationDefaults>>>,
+    Json(review): Json<AdmissionReview<Deployment>>,
+) -> Json<AdmissionReview<DynamicObject>> {
+    Json(mutate(&defaults, review))
+}
+
+#[derive(serde::Deserialize)]
+struct EffectiveConfigParams {
+    #[serde(default)]
+    diff: bool,
+}
+
+// - GET /effective-config/{namespace}/{name}?diff=true: each setting of a
+//   deployment and whether it comes from its annotations, a namespace default
+//   or the operator's defaults; `diff` keeps only what differs from the
+//   operator's defaults, and anything flagged.
+#[tracing::instrument(name = "effective_config", skip(store, params), fields())]
+async fn deployment_effective_config(
+    State(store): State<Cache>,
+    Path((namespace, name)): Path<(String, String)>,
+    Query(params): Query<EffectiveConfigParams>,
+) -> Result<Json<EffectiveConfig>, (http::StatusCode, String)> {
+    let deployment = store
+        .state()
+        .into_iter()
+        .find(|d| d.name_any() == name && d.namespace().as_deref() == Some(namespace.as_str()))
+        .ok_or((
+            http::StatusCode::NOT_FOUND,
+            format!("no deployment {}/{}", namespace, name),
+        ))?;
+
+    let config = effective_config(
+        &namespace,
+        &name,
+        deployment.metadata.labels.as_ref(),
+        deployment.metadata.annotations.as_ref(),
+        &annotation_defaults(),
+    );
+    Ok(Json(if params.diff { config.diff() } else { config }))
+}
+
+#[derive(serde::Deserialize)]
+struct ReleasesParams {
+    limit: Option<usize>,
+}
+
+const DEFAULT_RELEASES_LIMIT: usize = 20;
+
+// - GET /releases/{namespace}/{name}: image tags the deployment's manifest has
+//   pointed at over time, from the manifest repository history.
+#[tracing::instrument(
+    name = "releases",
+    skip(state, params),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+async fn releases(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+    Query(params): Query<ReleasesParams>,
+) -> Result<Json<Vec<ImageRevision>>, (http::StatusCode, String)> {
+    let entry = Entry::find(&state.store, &namespace, &name).ok_or((
+        http::StatusCode::NOT_FOUND,
+        format!("no tracked deployment {}/{}", namespace, name),
+    ))?;
+
+    // The manifest checkout is shared with reconcile, so don't fetch into it
+    // while a pass for this deployment is running.
+    let _guard = entry_locks().try_acquire(&entry.key()).ok_or((
+        http::StatusCode::CONFLICT,
+        format!("{} is already being reconciled", entry.key()),
+    ))?;
+
+    DeploymentProcessor::production()
+        .releases(&entry, params.limit.unwrap_or(DEFAULT_RELEASES_LIMIT))
+        .await
+        .map(Json)
+        .map_err(|e| (http::StatusCode::BAD_GATEWAY, format!("{:#}", e)))
+}
+
+#[derive(serde::Deserialize)]
+struct WatchParams {
+    /// Seconds to hold the connection (default 30, at most 300).
+    timeout: Option<u64>,
+    /// Revision the caller already has; a newer result is returned at once.
+    revision: Option<u64>,
+}
+
+const DEFAULT_WATCH_TIMEOUT_SECS: u64 = 30;
+const MAX_WATCH_TIMEOUT_SECS: u64 = 300;
+
+// - GET /watch/{namespace}/{name}?timeout=<secs>&revision=<n>: hold the
+//   connection until the deployment's result changes, answering 204 if it
+//   doesn't within the timeout.
+#[tracing::instrument(name = "watch", skip(state, params), fields())]
+async fn watch_result(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+    Query(params): Query<WatchParams>,
+) -> Result<Json<WatchedResult>, (http::StatusCode, String)> {
+    let entry = Entry::find(&state.store, &namespace, &name).ok_or((
+        http::StatusCode::NOT_FOUND,
+        format!("no tracked deployment {}/{}", namespace, name),
+    ))?;
+
+    let timeout = params
+        .timeout
+        .unwrap_or(DEFAULT_WATCH_TIMEOUT_SECS)
+        .min(MAX_WATCH_TIMEOUT_SECS);
+    result_board()
+        .wait_for_change(
+            &entry.key(),
+            params.revision,
+            std::time::Duration::from_secs(timeout),
+        )
+        .await
+        .map(Json)
+        .ok_or((http::StatusCode::NO_CONTENT, String::new()))
+}
+
+#[derive(serde::Deserialize)]
+struct HistoryParams {
+    limit: Option<usize>,
+}
+
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+// - GET /history: most recent reconcile results across all deployments.
+#[tracing::instrument(name = "history", skip(state, params), fields())]
+async fn history(
+    State(state): State<AppState>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Vec<HistoryRecord>>, (http::StatusCode, String)> {
+    let limit = params.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    state
+        .history
+        .blocking(move |history| history.list(limit))
+        .await
+        .map(Json)
+        .map_err(|e| (http::StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))
+}
+
+// - GET /history/{namespace}/{name}: reconcile results for one deployment.
+#[tracing::instrument(name = "deployment_history", skip(state, params), fields())]
+async fn deployment_history(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Vec<HistoryRecord>>, (http::StatusCode, String)> {
+    let limit = params.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    state
+        .history
+        .blocking(move |history| history.for_deployment(&namespace, &name, limit))
+        .await
+        .map(Json)
+        .map_err(|e| (http::StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))
+}
+
+// - GET /timeline/{namespace}/{name}?limit=: image changes seen in the
+//   cluster, reconcile attempts, manifest commits and notifications for one
+//   deployment, oldest first.
+#[tracing::instrument(name = "timeline", skip(state, params), fields())]
+async fn deployment_timeline(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Vec<TimelineEvent>>, (http::StatusCode, String)> {
+    let limit = params.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    let key = format!("{}/{}", namespace, name);
+    let history = state
+        .history
+        .blocking(move |history| history.for_deployment(&namespace, &name, limit))
+        .await
+        .map_err(|e| (http::StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))?;
+    let events = timeline().events(&key);
+
+    Ok(Json(merge(&history, events, limit)))
+}
+
+// - GET /drift: deployments whose live image differs from the one in their
+//   manifest, as of their last reconcile.
+#[tracing::instrument(name = "drift", fields())]
+async fn drift() -> Json<Vec<DriftReport>> {
+    Json(drift_board().list())
+}
+
+// - GET /rollouts: the latest verified rollout of each deployment with
+//   `gitops.operator.verify_rollout`, in progress or finished.
+#[tracing::instrument(name = "rollouts", fields())]
+async fn rollout_list() -> Result<Json<Vec<Rollout>>, (http::StatusCode, String)> {
+    blocking(&rollouts(), |rollouts| rollouts.list())
+        .await
+        .map(Json)
+        .map_err(|e| (http::StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))
+}
+
+// - GET /telemetry: where traces and metrics are exported, and which ones.
+#[tracing::instrument(name = "telemetry_settings", fields())]
+async fn telemetry_settings() -> Result<Json<TelemetrySettings>, (http::StatusCode, String)> {
+    telemetry()
+        .map(|telemetry| Json(telemetry.settings()))
+        .ok_or((
+            http::StatusCode::SERVICE_UNAVAILABLE,
+            "telemetry is not initialized".to_string(),
+        ))
+}
+
+// - PUT /telemetry: switch OTLP endpoints or turn exporters on and off
+//   without restarting, which would drop the reflector cache and any
+//   reconcile in flight. Traces carry repository URLs and error text, so
+//   the endpoint must pass the egress policy.
+#[tracing::instrument(name = "telemetry_reload", skip(settings), fields())]
+async fn telemetry_reload(
+    Json(settings): Json<TelemetrySettings>,
+) -> Result<Json<TelemetrySettings>, (http::StatusCode, String)> {
+    let telemetry = telemetry().ok_or((
+        http::StatusCode::SERVICE_UNAVAILABLE,
+        "telemetry is not initialized".to_string(),
+    ))?;
+    egress_policy()
+        .check(EgressKind::Telemetry, settings.endpoint.trim())
+        .map_err(|e| (http::StatusCode::FORBIDDEN, e.to_string()))?;
+    telemetry
+        .reload(settings)
+        .map_err(|e| (http::StatusCode::BAD_REQUEST, format!("{:#}", e)))?;
+    info!("Telemetry reloaded: {:?}", telemetry.settings());
+    Ok(Json(telemetry.settings()))
+}
+
+#[derive(serde::Deserialize)]
+struct CompareParams {
+    run_a: Option<String>,
+    run_b: Option<String>,
+}
+
+// - GET /compare?run_a=<id>&run_b=<id>: what changed between two reconcile
+//   runs (run ids are in the history records).
+#[tracing::instrument(name = "compare", skip(state, params), fields())]
+async fn compare(
+    State(state): State<AppState>,
+    Query(params): Query<CompareParams>,
+) -> Result<Json<RunComparison>, (http::StatusCode, String)> {
+    let (Some(run_a), Some(run_b)) = (params.run_a, params.run_b) else {
+        return Err((
+            http::StatusCode::BAD_REQUEST,
+            "both run_a and run_b are required".to_string(),
+        ));
+    };
+
+    let runs = (run_a.clone(), run_b.clone());
+    let (a, b) = state
+        .history
+        .blocking(move |history| Ok((history.for_run(&runs.0)?, history.for_run(&runs.1)?)))
+        .await
+        .map_err(|e| (http::StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))?;
+    for (run_id, records) in [(&run_a, &a), (&run_b, &b)] {
+        if records.is_empty() {
+            return Err((
+                http::StatusCode::NOT_FOUND,
+                format!("no reconcile run {}", run_id),
+            ));
+        }
+    }
+    Ok(Json(compare_runs(&run_a, &a, &run_b, &b)))
+}
+
+// - GET /approvals: promotions awaiting (or granted) approval.
+#[tracing::instrument(name = "approvals", skip(), fields())]
+async fn approvals() -> Result<Json<Vec<PendingChange>>, (http::StatusCode, String)> {
+    blocking(&approval_queue(), |approvals| approvals.list())
+        .await
+        .map(Json)
+        .map_err(|e| (http::StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))
+}
+
+#[derive(serde::Deserialize)]
+struct DecisionParams {
+    /// Who decided, recorded with the change.
+    by: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct Decision {
+    change: PendingChange,
+    /// The reconcile an approval triggers, if the deployment is still tracked.
+    results: Vec<ReconcileResult>,
+}
+
+/// Approve or reject change `id`; an approved change is promoted at once.
+async fn decide_change(
+    state: &AppState,
+    id: &str,
+    approve: bool,
+    by: Option<String>,
+) -> Result<Decision, (http::StatusCode, String)> {
+    let decided = {
+        let id = id.to_string();
+        blocking(&approval_queue(), move |approvals| {
+            approvals.decide(&id, approve, by.as_deref())
+        })
+        .await
+    };
+    let change = decided
+        .map_err(|e| (http::StatusCode::CONFLICT, format!("{:#}", e)))?
+        .ok_or((http::StatusCode::NOT_FOUND, format!("no change {}", id)))?;
+    info!(
+        "Change {} for {}/{} {}",
+        change.id,
+        change.namespace,
+        change.deployment,
+        if approve { "approved" } else { "rejected" }
+    );
+
+    let mut results = vec![];
+    if approve && let Some(entry) = Entry::find(&state.store, &change.namespace, &change.deployment)
+    {
+        let started_at = now_rfc3339();
+        results = Entry::reconcile_entries(vec![entry], false).await;
+        record_history(&state.history, &started_at, &results).await;
+    }
+    Ok(Decision { change, results })
+}
+
+/// What a Slack click on change `id` came to, replacing the buttons.
+fn click_outcome(
+    click: &ApprovalClick,
+    outcome: Result<Decision, (http::StatusCode, String)>,
+) -> String {
+    match outcome {
+        Ok(Decision { change, results }) => {
+            let mut text = format!(
+                "{} Promotion of {}/{} to {} {} by {}",
+                if click.approve {
+                    ":white_check_mark:"
+                } else {
+                    ":no_entry:"
+                },
+                change.namespace,
+                change.deployment,
+                change.to_sha,
+                if click.approve {
+                    "approved"
+                } else {
+                    "rejected"
+                },
+                click.user
+            );
+            for result in results {
+                text.push_str(&format!("\n{}", result.message));
+            }
+            text
+        }
+        Err((_, message)) => format!(":warning: Change {}: {}", click.change_id, message),
+    }
+}
+
+// - POST /slack/interactions: Approve/Reject button clicks on approval
+//   notifications, signed with the Slack app's signing secret.
+#[tracing::instrument(
+    name = "slack_interactions",
+    skip(state, headers, body),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+async fn slack_interactions(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+    body: axum::body::Bytes,
+) -> Result<http::StatusCode, (http::StatusCode, String)> {
+    let secret = slack_signing_secret().ok_or((
+        http::StatusCode::NOT_FOUND,
+        "GITOPS_SLACK_SIGNING_SECRET is not set".to_string(),
+    ))?;
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default();
+    verify_signature(
+        &secret,
+        &header(TIMESTAMP_HEADER),
+        &body,
+        &header(SIGNATURE_HEADER),
+        now,
+    )
+    .map_err(|e| (http::StatusCode::UNAUTHORIZED, format!("{:#}", e)))?;
+
+    let Some(click) = parse_interaction(&body)
+        .map_err(|e| (http::StatusCode::BAD_REQUEST, format!("{:#}", e)))?
+    else {
+        return Ok(http::StatusCode::OK);
+    };
+
+    // Slack expects an answer within three seconds, while an approval
+    // reconciles the deployment; the outcome goes to the response URL.
+    tokio::spawn(async move {
+        let by = format!("slack:{}", click.user);
+        let outcome = decide_change(&state, &click.change_id, click.approve, Some(by)).await;
+        let text = click_outcome(&click, outcome);
+        info!("{}", text);
+        if let Some(url) = &click.response_url
+            && let Err(e) = HttpNotificationSender::new()
+                .send_payload(&replacement_payload(&text), url)
+                .await
+        {
+            warn!("Failed to answer Slack interaction: {:?}", e);
+        }
+    });
+    Ok(http::StatusCode::OK)
+}
+
+// - POST /approvals/{id}/approve?by=<who>: let a held promotion through and
+//   reconcile its deployment.
+#[tracing::instrument(
+    name = "approve",
+    skip(state, params),
+    fields(
+        request_id = %Uuid::new_v4(),
+    )
+)]
+async fn approve(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<DecisionParams>,
+) -> Result<Json<Decision>, (http::StatusCode, String)> {
+    decide_change(&state, &id, true, params.by).await.map(Json)
+}
+
+// - POST /approvals/{id}/reject?by=<who>: keep a held promotion out until a
+//   newer tag replaces it.
+#[tracing::instrument(
+    name = "reject",
+    skip(state, params),
     fields(
         request_id = %Uuid::new_v4(),
     )
 )]
-async fn reconcile(State(store): State<Cache>) -> Json<Vec<ReconcileResult>> {
-    Entry::reconcile(State(store)).await
+async fn reject(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<DecisionParams>,
+) -> Result<Json<Decision>, (http::StatusCode, String)> {
+    decide_change(&state, &id, false, params.by).await.map(Json)
+}
+
+const DEFAULT_SLOW_REPOS_LIMIT: usize = 20;
+
+// - GET /reports/slow-repos?limit=<n>: repositories ranked by the time spent
+//   cloning and fetching them recently, with what they transferred.
+#[tracing::instrument(name = "slow_repos", skip(params), fields())]
+async fn slow_repos(Query(params): Query<HistoryParams>) -> Json<Vec<RepoCost>> {
+    Json(fetch_stats().slow_repos(params.limit.unwrap_or(DEFAULT_SLOW_REPOS_LIMIT)))
+}
+
+type BackupsResponse = Result<Json<Vec<BackupRecord>>, (http::StatusCode, String)>;
+
+fn enabled_backups() -> Result<Arc<BackupStore>, (http::StatusCode, String)> {
+    backup_store().ok_or((
+        http::StatusCode::NOT_FOUND,
+        "manifest backups are disabled".to_string(),
+    ))
+}
+
+// - GET /backups: originals of recently patched manifests, newest first.
+#[tracing::instrument(name = "backups", skip(params), fields())]
+async fn backups(Query(params): Query<HistoryParams>) -> BackupsResponse {
+    let store = enabled_backups()?;
+    Ok(Json(store.records(
+        None,
+        params.limit.unwrap_or(DEFAULT_HISTORY_LIMIT),
+    )))
+}
+
+// - GET /backups/{namespace}/{name}: backups taken for one deployment.
+#[tracing::instrument(name = "deployment_backups", skip(params), fields())]
+async fn deployment_backups(
+    Path((namespace, name)): Path<(String, String)>,
+    Query(params): Query<HistoryParams>,
+) -> BackupsResponse {
+    let store = enabled_backups()?;
+    Ok(Json(store.records(
+        Some(&format!("{}/{}", namespace, name)),
+        params.limit.unwrap_or(DEFAULT_HISTORY_LIMIT),
+    )))
+}
+
+// - GET /backups/{namespace}/{name}/{hash}: the original content, ready to be
+//   written back over the manifest.
+#[tracing::instrument(name = "backup_content", skip(), fields())]
+async fn backup_content(
+    Path((namespace, name, hash)): Path<(String, String, String)>,
+) -> Result<Vec<u8>, (http::StatusCode, String)> {
+    let store = enabled_backups()?;
+    store
+        .content(&format!("{}/{}", namespace, name), &hash)
+        .map_err(|e| (http::StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))?
+        .ok_or((
+            http::StatusCode::NOT_FOUND,
+            format!("no backup {} for {}/{}", hash, namespace, name),
+        ))
+}
+
+// - GET /version: operator version and the cluster capabilities probed at
+//   startup.
+#[tracing::instrument(name = "version", skip(), fields())]
+async fn version() -> Json<Capabilities> {
+    Json(capabilities().clone())
 }
 
 // - GET /debug
@@ -51,6 +882,20 @@ async fn status(State(store): State<Cache>) -> impl IntoResponse {
     )
 }
 
+// - GET /assets/grafana-dashboard.json: dashboard generated from the metrics
+//   the operator registers.
+async fn dashboard_asset() -> Json<serde_json::Value> {
+    Json(grafana_dashboard())
+}
+
+// - GET /assets/prometheus-rules.yaml: alert rules for the same metrics.
+async fn rules_asset() -> impl IntoResponse {
+    (
+        [(http::header::CONTENT_TYPE, "application/yaml")],
+        prometheus_rules(),
+    )
+}
+
 // - GET /health: liveness/readiness with a count of tracked deployments,
 //   which also confirms the reflector store is readable.
 #[tracing::instrument(name = "health", skip(store), fields())]
@@ -75,7 +920,23 @@ async fn main() -> anyhow::Result<()> {
     info!("Starting gitops-operator");
 
     let client = Client::try_default().await?;
-    let api: Api<Deployment> = Api::all(client);
+
+    // Find out up front which optional APIs the cluster serves, so features
+    // built on missing ones are turned off instead of failing mid-reconcile.
+    let maintenance_kinds = maintenance_resources();
+    let probed = probe(&client, &maintenance_kinds).await;
+    for gvk in maintenance_kinds.iter().filter(|gvk| !probed.has_kind(gvk)) {
+        warn!(
+            "{} is not served; its maintenance windows are ignored",
+            resource_name(gvk)
+        );
+    }
+    if !probed.has(EVENTS_RESOURCE) {
+        warn!("The Events API is not served; Deployment events are not recorded");
+    }
+    set_capabilities(probed);
+
+    let api: Api<Deployment> = Api::all(client.clone());
 
     let (reader, writer) = reflector::store();
     let watch = reflector(writer, watcher(api, Default::default()))
@@ -83,24 +944,161 @@ async fn main() -> anyhow::Result<()> {
         .touched_objects()
         .for_each(|r| {
             match r {
-                Ok(o) => debug!(
-                    "Saw {} in {}",
-                    o.name_any(),
-                    o.namespace().unwrap_or_else(|| "<cluster-scoped>".into())
-                ),
+                Ok(o) => {
+                    debug!(
+                        "Saw {} in {}",
+                        o.name_any(),
+                        o.namespace().unwrap_or_else(|| "<cluster-scoped>".into())
+                    );
+                    timeline().observe(&o);
+                }
                 Err(e) => warn!("watcher error: {e}"),
             };
             future::ready(())
         });
     tokio::spawn(watch); // poll forever
 
+    // With the opt-in label required, keep namespace labels in a second
+    // reflector so the policy can be checked without an API call per pass.
+    let mut ns_ready = None;
+    if namespace_policy().requires_label() {
+        let (ns_reader, ns_writer) = reflector::store();
+        let namespaces: Api<Namespace> = Api::all(client.clone());
+        let ns_watch = reflector(ns_writer, watcher(namespaces, Default::default()))
+            .default_backoff()
+            .touched_objects()
+            .for_each(|r| {
+                if let Err(e) = r {
+                    warn!("namespace watcher error: {e}");
+                }
+                future::ready(())
+            });
+        tokio::spawn(ns_watch);
+        ns_ready = Some(ns_reader.clone());
+        namespace_policy().watch_namespaces(ns_reader);
+    }
+
+    let state_settings = StateSettings::from_env();
+    let state = match open_state_store(&state_settings, client.clone()).await {
+        Ok(state) => state,
+        Err(e) => {
+            warn!("{:#}; keeping operator state in memory only", e);
+            Arc::new(MemoryState::new())
+        }
+    };
+    set_state_store(state.clone());
+
+    let history_store = match state_settings.backend {
+        StateBackend::Sqlite => match HistoryStore::open(&history_path()) {
+            Ok(store) => store,
+            Err(e) => {
+                warn!("{:#}; keeping reconcile history in memory only", e);
+                HistoryStore::in_memory()?
+            }
+        },
+        StateBackend::Memory => HistoryStore::in_memory()?,
+        StateBackend::Kubernetes => HistoryStore::on_state(state, STATE_HISTORY_LIMIT),
+    };
+    let history_store = Arc::new(history_store.with_retention(HistoryRetention::from_env()));
+
+    // One-shot mode for CI jobs: reconcile everything once, print the results
+    // as the last line of output and exit with a code for the failure category.
+    if std::env::args().skip(1).any(|arg| arg == "--once") {
+        reader.wait_until_ready().await?;
+        if let Some(ns_reader) = ns_ready {
+            ns_reader.wait_until_ready().await?;
+        }
+
+        let started_at = now_rfc3339();
+        let Json(results) = Entry::reconcile(State(reader)).await;
+        record_history(&history_store, &started_at, &results).await;
+        println!("{}", serde_json::to_string(&results)?);
+        std::process::exit(exit_code(&results));
+    }
+
+    let state = AppState {
+        store: reader,
+        history: history_store,
+        webhooks: Arc::new(Coalescer::new()),
+    };
+
+    tokio::spawn(run_reaper(entry_locks(), lock_deadline()));
+    tokio::spawn(run_repo_maintenance(
+        state.store.clone(),
+        entry_locks(),
+        GcSettings::from_env(),
+    ));
+
+    // Optional internal reconcile schedule, mirrored into a CronJob so it can
+    // be inspected and edited with kubectl.
+    if let Some(settings) = ScheduleSettings::from_env() {
+        let (tx, rx) = tokio::sync::watch::channel(settings.schedule.clone());
+        tokio::spawn(run_cronjob_sync(client, settings, tx));
+
+        let state = state.clone();
+        tokio::spawn(run_scheduler(rx, move || {
+            let state = state.clone();
+            async move {
+                let started_at = now_rfc3339();
+                let Json(results) = Entry::reconcile(State(state.store)).await;
+                record_history(&state.history, &started_at, &results).await;
+            }
+        }));
+    }
+
     let (prometheus_layer, metric_handle) = PrometheusMetricLayer::pair();
+    describe_metrics();
+    // Whatever changes what is deployed, or lets a held change through,
+    // needs the approval token.
+    let guarded_routes = guarded(
+        Router::new()
+            .route("/rollback/{namespace}/{name}", routing::post(rollback))
+            .route("/pause/{namespace}/{name}", routing::post(pause))
+            .route("/resume/{namespace}/{name}", routing::post(resume))
+            .route("/approvals/{id}/approve", routing::post(approve))
+            .route("/approvals/{id}/reject", routing::post(reject))
+            .route("/telemetry", routing::put(telemetry_reload)),
+    );
     let app = Router::new()
+        .merge(guarded_routes)
         .route("/health", routing::get(health))
+        .route("/version", routing::get(version))
         .route("/status", routing::get(status))
         .route("/debug", routing::get(debug))
         .route("/reconcile", routing::get(reconcile))
-        .with_state(reader)
+        .route("/webhook/registry", routing::post(registry_webhook))
+        .route("/approvals", routing::get(approvals))
+        .route("/slack/interactions", routing::post(slack_interactions))
+        .route("/releases/{namespace}/{name}", routing::get(releases))
+        .route(
+            "/effective-config/{namespace}/{name}",
+            routing::get(deployment_effective_config),
+        )
+        .route("/watch/{namespace}/{name}", routing::get(watch_result))
+        .route("/backups", routing::get(backups))
+        .route(
+            "/backups/{namespace}/{name}",
+            routing::get(deployment_backups),
+        )
+        .route(
+            "/backups/{namespace}/{name}/{hash}",
+            routing::get(backup_content),
+        )
+        .route("/history", routing::get(history))
+        .route("/compare", routing::get(compare))
+        .route("/drift", routing::get(drift))
+        .route("/rollouts", routing::get(rollout_list))
+        .route("/telemetry", routing::get(telemetry_settings))
+        .route("/reports/slow-repos", routing::get(slow_repos))
+        .route(
+            "/history/{namespace}/{name}",
+            routing::get(deployment_history),
+        )
+        .route(
+            "/timeline/{namespace}/{name}",
+            routing::get(deployment_timeline),
+        )
+        .with_state(state)
         .layer(
             TraceLayer::new_for_http().make_span_with(|request: &http::Request<_>| {
                 tracing::span!(
@@ -113,10 +1111,46 @@ async fn main() -> anyhow::Result<()> {
             }),
         )
         .route("/metrics", get(|| async move { metric_handle.render() }))
+        .route("/assets/grafana-dashboard.json", get(dashboard_asset))
+        .route("/assets/prometheus-rules.yaml", get(rules_asset))
         .layer(prometheus_layer);
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await?;
-    axum::serve(listener, app.into_make_service()).await?;
+    let addrs = listen_addrs()?;
+
+    // Optional mutating admission webhook, served over TLS on its own port as
+    // the API server requires.
+    if let Some(settings) = AdmissionSettings::from_env() {
+        let tls = axum_server::tls_openssl::OpenSSLConfig::from_pem_chain_file(
+            &settings.cert_path,
+            &settings.key_path,
+        )?;
+        let admission = Router::new()
+            .route("/mutate", routing::post(mutate_deployment))
+            .with_state(Arc::new(settings.defaults));
+
+        let admission_addrs = with_port(&addrs, settings.port);
+        for (addr, listener) in admission_addrs.iter().zip(bind_all(&admission_addrs)?) {
+            info!("Serving the admission webhook on {}", addr);
+            let server = axum_server::from_tcp(listener)?
+                .acceptor(axum_server::tls_openssl::OpenSSLAcceptor::new(tls.clone()));
+            let admission = admission.clone();
+            tokio::spawn(async move {
+                if let Err(e) = server.serve(admission.into_make_service()).await {
+                    warn!("Admission webhook server failed: {:?}", e);
+                }
+            });
+        }
+    }
+
+    let servers = addrs.iter().zip(bind_all(&addrs)?).map(|(addr, listener)| {
+        info!("Listening on {}", addr);
+        let app = app.clone();
+        async move {
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+            axum::serve(listener, app.into_make_service()).await
+        }
+    });
+    future::try_join_all(servers).await?;
 
     Ok(())
 }
@@ -0,0 +1,598 @@
+use crate::configuration::Entry;
+use crate::git::{MIRRORS_DIR, refs_lock, repo_cache_dir};
+use crate::locks::{EntryLocks, path_locks};
+use crate::metrics::{
+    REPO_CACHE_SIZE_BYTES, REPO_CHECKOUTS_REMOVED_TOTAL, REPO_GC_RECLAIMED_BYTES_TOTAL,
+    REPO_GC_TOTAL, REPO_SIZE_BYTES,
+};
+use anyhow::{Context, Result};
+use git2::Repository;
+use k8s_openapi::api::apps::v1::Deployment;
+use kube::runtime::reflector;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, warn};
+
+const DEFAULT_GC_INTERVAL_SECS: u64 = 3600;
+const DEFAULT_GC_MAX_PACKS: usize = 50;
+const DEFAULT_GC_MAX_LOOSE_OBJECTS: usize = 1000;
+const DEFAULT_MAX_IDLE_HOURS: u64 = 24;
+
+/// Name prefixes of the checkouts in the cache directory
+/// ([`Entry::app_repo_path`], [`Entry::manifest_repo_path`] and the
+/// dependency rules' checkouts).
+const CHECKOUT_PREFIXES: &[&str] = &["app-", "manifest-", "dependency-"];
+
+/// When cached checkouts get repacked. Every fetch leaves a new pack (and
+/// commits leave loose objects), so long-lived checkouts grow without it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GcSettings {
+    /// `GITOPS_REPO_GC_INTERVAL_SECS` (default 3600, 0 disables maintenance).
+    pub interval: Duration,
+    /// `GITOPS_REPO_GC_MAX_PACKS` (default 50).
+    pub max_packs: usize,
+    /// `GITOPS_REPO_GC_MAX_LOOSE_OBJECTS` (default 1000).
+    pub max_loose_objects: usize,
+    /// `GITOPS_REPO_GC_MAX_SIZE_MB`, converted to bytes (default unset).
+    pub max_size_bytes: Option<u64>,
+    /// How long a checkout may go unused before it is deleted:
+    /// `GITOPS_REPO_MAX_IDLE_HOURS` (default 24, 0 keeps them).
+    pub max_idle: Option<Duration>,
+    /// `GITOPS_REPO_CACHE_MAX_SIZE_MB`, converted to bytes (default unset):
+    /// beyond it, the least recently used checkouts are evicted.
+    pub max_cache_bytes: Option<u64>,
+}
+
+impl GcSettings {
+    pub fn from_env() -> Self {
+        fn env<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok().and_then(|v| v.trim().parse().ok())
+        }
+
+        Self {
+            interval: Duration::from_secs(
+                env("GITOPS_REPO_GC_INTERVAL_SECS").unwrap_or(DEFAULT_GC_INTERVAL_SECS),
+            ),
+            max_packs: env("GITOPS_REPO_GC_MAX_PACKS").unwrap_or(DEFAULT_GC_MAX_PACKS),
+            max_loose_objects: env("GITOPS_REPO_GC_MAX_LOOSE_OBJECTS")
+                .unwrap_or(DEFAULT_GC_MAX_LOOSE_OBJECTS),
+            max_size_bytes: env::<u64>("GITOPS_REPO_GC_MAX_SIZE_MB").map(|mb| mb * 1024 * 1024),
+            max_idle: Some(env("GITOPS_REPO_MAX_IDLE_HOURS").unwrap_or(DEFAULT_MAX_IDLE_HOURS))
+                .filter(|hours| *hours > 0)
+                .map(|hours| Duration::from_secs(hours * 3600)),
+            max_cache_bytes: env::<u64>("GITOPS_REPO_CACHE_MAX_SIZE_MB").map(|mb| mb * 1024 * 1024),
+        }
+    }
+
+    /// Whether a repository with `stats` is due for a repack.
+    pub fn needs_gc(&self, stats: &ObjectStats) -> bool {
+        stats.packs > self.max_packs
+            || stats.loose_objects > self.max_loose_objects
+            || self
+                .max_size_bytes
+                .is_some_and(|max| stats.size_bytes > max)
+    }
+}
+
+/// Size and layout of a repository's object database.
+#[derive(serde::Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct ObjectStats {
+    pub size_bytes: u64,
+    pub packs: usize,
+    pub loose_objects: usize,
+}
+
+fn is_loose_object_dir(name: &str) -> bool {
+    name.len() == 2 && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Measure the object database of the repository at `repo_path` (the
+/// mirror's, for a worktree of one).
+pub fn object_stats(repo_path: &Path) -> Result<ObjectStats> {
+    let repo = Repository::open(repo_path)?;
+    let objects = repo.commondir().join("objects");
+    let mut stats = ObjectStats::default();
+
+    for dir in fs::read_dir(&objects)? {
+        let dir = dir?;
+        let name = dir.file_name().to_string_lossy().into_owned();
+        if !dir.file_type()?.is_dir() {
+            continue;
+        }
+
+        for file in fs::read_dir(dir.path())? {
+            let file = file?;
+            stats.size_bytes += file.metadata()?.len();
+            if is_loose_object_dir(&name) {
+                stats.loose_objects += 1;
+            } else if name == "pack" && file.path().extension().is_some_and(|ext| ext == "pack") {
+                stats.packs += 1;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Repack every object reachable from the refs, HEAD and the index into a
+/// single pack, then drop the loose objects and older packs. Unreachable
+/// objects are discarded: a cached checkout can always be fetched again.
+/// For a worktree, the mirror is repacked with the HEAD and index of each
+/// of its worktrees. Returns the stats before and after.
+pub fn repack(repo_path: &Path) -> Result<(ObjectStats, ObjectStats)> {
+    let before = object_stats(repo_path)?;
+    let repo = Repository::open(repo_path)?;
+    let pack_dir = repo.commondir().join("objects").join("pack");
+    let mut checkouts = vec![];
+    if repo.is_worktree() {
+        let mirror = Repository::open(repo.commondir())?;
+        for name in mirror.worktrees()?.iter().flatten().flatten() {
+            if let Ok(worktree) = mirror.find_worktree(name)
+                && worktree.validate().is_ok()
+            {
+                checkouts.push(Repository::open_from_worktree(&worktree)?);
+            }
+        }
+    } else {
+        checkouts.push(Repository::open(repo_path)?);
+    }
+
+    let mut builder = repo.packbuilder()?;
+    let mut walk = repo.revwalk()?;
+    walk.push_glob("*")?;
+    // An unborn HEAD has nothing to add.
+    for checkout in &checkouts {
+        if let Some(head) = checkout.head().ok().and_then(|head| head.target()) {
+            walk.push(head)?;
+        }
+    }
+    builder.insert_walk(&mut walk)?;
+    // Ref targets that are not commits (annotated tags) and staged blobs.
+    for reference in repo.references()? {
+        if let Some(oid) = reference?.target() {
+            builder.insert_recursive(oid, None)?;
+        }
+    }
+    for checkout in &checkouts {
+        for entry in checkout.index()?.iter() {
+            builder.insert_object(entry.id, None)?;
+        }
+    }
+
+    builder.write(&pack_dir, 0)?;
+    let pack_name = builder
+        .name()?
+        .map(|name| format!("pack-{}", name))
+        .context("Pack builder produced no pack")?;
+    debug!(
+        "Wrote {} ({} objects) in {}",
+        pack_name,
+        builder.object_count(),
+        repo_path.display()
+    );
+
+    let objects = repo.commondir().join("objects");
+    for dir in fs::read_dir(&objects)? {
+        let dir = dir?;
+        let name = dir.file_name().to_string_lossy().into_owned();
+        if is_loose_object_dir(&name) {
+            fs::remove_dir_all(dir.path())?;
+        }
+    }
+    for file in fs::read_dir(&pack_dir)? {
+        let path = file?.path();
+        let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned());
+        if stem.is_some_and(|stem| stem.starts_with("pack-") && stem != pack_name) {
+            fs::remove_file(&path)?;
+        }
+    }
+
+    let after = object_stats(repo_path)?;
+    Ok((before, after))
+}
+
+/// Check the checkout at `repo_path` and repack it if `settings` say so,
+/// recording its size. A checkout the repack breaks is deleted so the next
+/// reconcile clones it afresh.
+pub fn maintain(
+    repo_path: &Path,
+    settings: &GcSettings,
+) -> Result<Option<(ObjectStats, ObjectStats)>> {
+    let repo = repo_path.display().to_string();
+    let stats = object_stats(repo_path)?;
+    ::metrics::gauge!(REPO_SIZE_BYTES, "repo" => repo.clone()).set(stats.size_bytes as f64);
+
+    if !settings.needs_gc(&stats) {
+        return Ok(None);
+    }
+
+    let (before, after) = match repack(repo_path) {
+        Ok(result) => result,
+        Err(e) => {
+            warn!("Repacking {} failed, removing it: {:#}", repo, e);
+            let _ = fs::remove_dir_all(repo_path);
+            ::metrics::gauge!(REPO_SIZE_BYTES, "repo" => repo).set(0.0);
+            return Err(e);
+        }
+    };
+
+    info!(
+        "Repacked {}: {} -> {} bytes, {} -> {} packs, {} -> {} loose objects",
+        repo,
+        before.size_bytes,
+        after.size_bytes,
+        before.packs,
+        after.packs,
+        before.loose_objects,
+        after.loose_objects
+    );
+    ::metrics::counter!(REPO_GC_TOTAL).increment(1);
+    ::metrics::counter!(REPO_GC_RECLAIMED_BYTES_TOTAL)
+        .increment(before.size_bytes.saturating_sub(after.size_bytes));
+    ::metrics::gauge!(REPO_SIZE_BYTES, "repo" => repo).set(after.size_bytes as f64);
+
+    Ok(Some((before, after)))
+}
+
+/// When the checkout at `repo_path` was last fetched into or committed to:
+/// the modification time of its git directory, where both rewrite a file
+/// (`FETCH_HEAD`, the index).
+pub fn last_used(repo_path: &Path) -> Option<SystemTime> {
+    let repo = Repository::open(repo_path).ok()?;
+    fs::metadata(repo.path()).and_then(|m| m.modified()).ok()
+}
+
+/// Whether the checkout at `repo_path` has gone unused for longer than
+/// `max_idle`.
+pub fn is_idle(repo_path: &Path, max_idle: Option<Duration>) -> bool {
+    max_idle.is_some_and(|max| {
+        last_used(repo_path)
+            .and_then(|used| used.elapsed().ok())
+            .is_some_and(|idle| idle > max)
+    })
+}
+
+/// Delete the checkout at `repo_path` and, for a worktree, its entry in the
+/// mirror, which goes too once no checkout of it is left.
+pub fn remove_checkout(repo_path: &Path) -> Result<()> {
+    let mirror = Repository::open(repo_path)
+        .ok()
+        .filter(|repo| repo.is_worktree())
+        .map(|repo| repo.commondir().to_path_buf());
+    fs::remove_dir_all(repo_path)
+        .with_context(|| format!("Failed to remove {}", repo_path.display()))?;
+    ::metrics::gauge!(REPO_SIZE_BYTES, "repo" => repo_path.display().to_string()).set(0.0);
+    if let Some(mirror) = mirror {
+        prune_mirror(&mirror)?;
+    }
+    Ok(())
+}
+
+/// Drop the worktrees of the mirror at `mirror_path` whose checkout is gone,
+/// and the mirror itself when none is left. Returns whether it was deleted.
+/// Holds the mirror's [`refs_lock`], so a clone adding a checkout to it
+/// either finishes first (and keeps it) or creates it afresh afterwards.
+pub fn prune_mirror(mirror_path: &Path) -> Result<bool> {
+    let refs = refs_lock(mirror_path);
+    let _refs = refs.lock().unwrap_or_else(|e| e.into_inner());
+    if !mirror_path.exists() {
+        return Ok(false);
+    }
+    let mirror = Repository::open_bare(mirror_path)?;
+    let mut checkouts = 0;
+    for name in mirror.worktrees()?.iter().flatten().flatten() {
+        let worktree = mirror.find_worktree(name)?;
+        if worktree.validate().is_ok() {
+            checkouts += 1;
+        } else {
+            worktree.prune(None)?;
+        }
+    }
+    if checkouts > 0 {
+        return Ok(false);
+    }
+    fs::remove_dir_all(mirror_path)
+        .with_context(|| format!("Failed to remove {}", mirror_path.display()))?;
+    Ok(true)
+}
+
+/// The checkouts in `cache_dir` that aren't in `live`: those of deployments
+/// deleted or no longer managed.
+pub fn orphaned_checkouts(cache_dir: &Path, live: &HashSet<PathBuf>) -> Vec<PathBuf> {
+    let Ok(dirs) = fs::read_dir(cache_dir) else {
+        return vec![];
+    };
+    dirs.filter_map(|dir| dir.ok())
+        .filter(|dir| {
+            let name = dir.file_name().to_string_lossy().into_owned();
+            CHECKOUT_PREFIXES
+                .iter()
+                .any(|prefix| name.starts_with(prefix))
+        })
+        .map(|dir| dir.path())
+        .filter(|path| !live.contains(path) && Repository::open(path).is_ok())
+        .collect()
+}
+
+/// Delete the [`orphaned_checkouts`] of `cache_dir`, then the mirrors no
+/// checkout is left of. Each checkout's path lock is held while it is
+/// deleted, and `live` is asked again under it: a deployment created since
+/// keeps the checkout its first reconcile is cloning. Returns the checkouts
+/// deleted.
+pub async fn remove_orphaned_checkouts(
+    cache_dir: &Path,
+    live: impl Fn() -> HashSet<PathBuf>,
+) -> Vec<PathBuf> {
+    let mut removed = vec![];
+    for path in orphaned_checkouts(cache_dir, &live()) {
+        let _checkout = path_locks().lock(&[&path.display().to_string()]).await;
+        if live().contains(&path) {
+            continue;
+        }
+        let result = {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || remove_checkout(&path)).await
+        };
+        match result {
+            Ok(Ok(())) => removed.push(path),
+            Ok(Err(e)) => warn!("Removing orphaned {} failed: {:#}", path.display(), e),
+            Err(e) => warn!("Removing orphaned {} failed: {:?}", path.display(), e),
+        }
+    }
+
+    let mirrors = cache_dir.join(MIRRORS_DIR);
+    let pruned = tokio::task::spawn_blocking(move || {
+        let Ok(dirs) = fs::read_dir(mirrors) else {
+            return;
+        };
+        for dir in dirs.filter_map(|dir| dir.ok()) {
+            match prune_mirror(&dir.path()) {
+                Ok(true) => info!("Removed unused mirror {}", dir.path().display()),
+                Ok(false) => {}
+                Err(e) => warn!("Pruning {} failed: {:#}", dir.path().display(), e),
+            }
+        }
+    })
+    .await;
+    if let Err(e) = pruned {
+        warn!("Pruning mirrors failed: {:?}", e);
+    }
+    removed
+}
+
+/// Total size of the files under `path`.
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .map(|dirs| {
+            dirs.filter_map(|dir| dir.ok())
+                .map(|dir| dir_size(&dir.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Size of what the operator keeps in `cache_dir`: its checkouts and
+/// mirrors, leaving out anything else stored there (e.g. in `/tmp`).
+pub fn cache_size(cache_dir: &Path) -> u64 {
+    let Ok(dirs) = fs::read_dir(cache_dir) else {
+        return 0;
+    };
+    dirs.filter_map(|dir| dir.ok())
+        .filter(|dir| {
+            let name = dir.file_name().to_string_lossy().into_owned();
+            name == MIRRORS_DIR
+                || CHECKOUT_PREFIXES
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+        })
+        .map(|dir| dir_size(&dir.path()))
+        .sum()
+}
+
+/// Delete `checkouts`, least recently used first, until [`cache_size`] of
+/// `cache_dir` is at most `max_bytes`. A checkout's objects live in its
+/// mirror, so this mostly frees space once every checkout of a mirror is
+/// gone. Returns the checkouts deleted.
+pub fn evict_lru(cache_dir: &Path, checkouts: &[PathBuf], max_bytes: u64) -> Vec<PathBuf> {
+    let mut by_use: Vec<_> = checkouts
+        .iter()
+        .filter_map(|path| Some((last_used(path)?, path)))
+        .collect();
+    by_use.sort();
+
+    let mut evicted = vec![];
+    let mut size = cache_size(cache_dir);
+    for (_, path) in by_use {
+        if size <= max_bytes {
+            break;
+        }
+        match remove_checkout(path) {
+            Ok(()) => {
+                evicted.push(path.clone());
+                size = cache_size(cache_dir);
+            }
+            Err(e) => warn!("Evicting {} failed: {:#}", path.display(), e),
+        }
+    }
+    evicted
+}
+
+/// Cached checkouts belonging to `entry`: its app and manifests repositories
+/// and the dependent repositories it propagates to.
+fn checkouts(entry: &Entry) -> Vec<String> {
+    let mut paths = vec![entry.app_repo_path(), entry.manifest_repo_path()];
+    let prefix = format!("dependency-{}-{}-", &entry.namespace, &entry.name);
+    if let Ok(dirs) = fs::read_dir(repo_cache_dir()) {
+        paths.extend(
+            dirs.filter_map(|dir| dir.ok())
+                .filter(|dir| dir.file_name().to_string_lossy().starts_with(&prefix))
+                .map(|dir| dir.path().display().to_string()),
+        );
+    }
+    paths
+}
+
+/// The checkouts of every deployment in `store` as it stands now.
+fn live_checkouts(store: &reflector::Store<Deployment>) -> HashSet<PathBuf> {
+    store
+        .state()
+        .iter()
+        .filter_map(|d| Entry::new(d))
+        .flat_map(|entry| checkouts(&entry))
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Periodically maintain the checkouts of every tracked deployment, deleting
+/// those unused for longer than `settings.max_idle`, then delete the
+/// checkouts of deployments no longer tracked. Each deployment's lock and
+/// its checkouts' are held meanwhile so no reconcile fetches into a checkout
+/// being repacked;
+/// busy deployments wait for the next round. Runs forever (returns at once
+/// if maintenance is disabled).
+pub async fn run_repo_maintenance(
+    store: reflector::Store<Deployment>,
+    locks: &'static EntryLocks,
+    settings: GcSettings,
+) {
+    if settings.interval.is_zero() {
+        info!("Repository maintenance disabled");
+        return;
+    }
+    info!(
+        "Starting repository maintenance (every {}s)",
+        settings.interval.as_secs()
+    );
+
+    // Until the store is filled, every checkout would look orphaned.
+    if let Err(e) = store.wait_until_ready().await {
+        warn!("Repository maintenance stopped: {}", e);
+        return;
+    }
+
+    let mut interval = tokio::time::interval(settings.interval);
+    loop {
+        interval.tick().await;
+
+        let entries: Vec<Entry> = store.state().iter().filter_map(|d| Entry::new(d)).collect();
+        let owners: Vec<(String, Vec<PathBuf>)> = entries
+            .iter()
+            .map(|entry| {
+                let paths = checkouts(entry).into_iter().map(PathBuf::from);
+                (entry.key(), paths.collect())
+            })
+            .collect();
+        for entry in entries {
+            let Some(_guard) = locks.try_acquire(&entry.key()) else {
+                debug!("Skipping maintenance of busy {}", entry.key());
+                continue;
+            };
+
+            let paths = checkouts(&entry);
+            let _checkouts = path_locks()
+                .lock(&paths.iter().map(String::as_str).collect::<Vec<_>>())
+                .await;
+            let settings = settings.clone();
+            let maintained = tokio::task::spawn_blocking(move || {
+                for path in paths {
+                    let path = Path::new(&path);
+                    if !path.exists() {
+                        continue;
+                    }
+                    if is_idle(path, settings.max_idle) {
+                        info!("Removing {}, unused for too long", path.display());
+                        match remove_checkout(path) {
+                            Ok(()) => {
+                                ::metrics::counter!(REPO_CHECKOUTS_REMOVED_TOTAL, "reason" => "idle")
+                                    .increment(1)
+                            }
+                            Err(e) => warn!("Removing {} failed: {:#}", path.display(), e),
+                        }
+                    } else if let Err(e) = maintain(path, &settings) {
+                        warn!("Maintenance of {} failed: {:#}", path.display(), e);
+                    }
+                }
+            })
+            .await;
+            if let Err(e) = maintained {
+                warn!("Repository maintenance task failed: {:?}", e);
+            }
+        }
+
+        let removed = remove_orphaned_checkouts(&repo_cache_dir(), || live_checkouts(&store)).await;
+        for path in &removed {
+            info!("Removed orphaned checkout {}", path.display());
+        }
+        ::metrics::counter!(REPO_CHECKOUTS_REMOVED_TOTAL, "reason" => "orphaned")
+            .increment(removed.len() as u64);
+
+        enforce_cache_size(&owners, locks, settings.max_cache_bytes).await;
+    }
+}
+
+/// Record the size of the cache directory and, when it exceeds `max_bytes`,
+/// evict the least recently used checkouts of the deployments not being
+/// reconciled until it fits.
+async fn enforce_cache_size(
+    owners: &[(String, Vec<PathBuf>)],
+    locks: &'static EntryLocks,
+    max_bytes: Option<u64>,
+) {
+    let cache_dir = repo_cache_dir();
+    let size = {
+        let cache_dir = cache_dir.clone();
+        tokio::task::spawn_blocking(move || cache_size(&cache_dir)).await
+    };
+    let Ok(size) = size else {
+        return;
+    };
+    ::metrics::gauge!(REPO_CACHE_SIZE_BYTES).set(size as f64);
+    let Some(max_bytes) = max_bytes.filter(|max| size > *max) else {
+        return;
+    };
+
+    let mut guards = vec![];
+    let mut candidates = vec![];
+    for (key, paths) in owners {
+        if let Some(guard) = locks.try_acquire(key) {
+            guards.push(guard);
+            candidates.extend(paths.iter().cloned());
+        }
+    }
+    let paths: Vec<String> = candidates
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    let _checkouts = path_locks()
+        .lock(&paths.iter().map(String::as_str).collect::<Vec<_>>())
+        .await;
+    let evicted = tokio::task::spawn_blocking(move || {
+        let evicted = evict_lru(&cache_dir, &candidates, max_bytes);
+        (evicted, cache_size(&cache_dir))
+    })
+    .await;
+    drop(guards);
+
+    match evicted {
+        Ok((evicted, size)) => {
+            warn!(
+                "Repository cache over {} bytes, evicted {} checkouts: {:?}",
+                max_bytes,
+                evicted.len(),
+                evicted
+            );
+            ::metrics::counter!(REPO_CHECKOUTS_REMOVED_TOTAL, "reason" => "evicted")
+                .increment(evicted.len() as u64);
+            ::metrics::gauge!(REPO_CACHE_SIZE_BYTES).set(size as f64);
+        }
+        Err(e) => warn!("Repository eviction task failed: {:?}", e),
+    }
+}
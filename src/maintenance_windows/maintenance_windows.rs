@@ -0,0 +1,332 @@
+use crate::capabilities::capabilities;
+use crate::schedule::CronSchedule;
+use crate::traits::MaintenanceWindowSource;
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use k8s_openapi::api::core::v1::ConfigMap;
+use k8s_openapi::jiff::{SignedDuration, Timestamp};
+use kube::api::{Api, ListParams};
+use kube::core::{ApiResource, DynamicObject, GroupVersionKind};
+use kube::{Client, ResourceExt};
+use tracing::warn;
+
+/// Label marking a ConfigMap as a maintenance window.
+pub const MAINTENANCE_WINDOW_LABEL: &str = "gitops.operator/maintenance-window";
+
+/// A period during which promotions are deferred, declared by a ConfigMap
+/// labelled [`MAINTENANCE_WINDOW_LABEL`]` = "true"` or by an object of one of
+/// the kinds in `GITOPS_MAINTENANCE_RESOURCES`. Without `start`/`end` it is
+/// active for as long as the object exists.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaintenanceWindow {
+    /// `Kind namespace/name` of the declaring object.
+    pub object: String,
+    pub start: Option<Timestamp>,
+    pub end: Option<Timestamp>,
+    /// Namespaces whose deployments it defers; empty defers all of them.
+    pub namespaces: Vec<String>,
+}
+
+impl MaintenanceWindow {
+    /// Build a window from the `start`, `end` (RFC 3339) and `namespaces`
+    /// (comma-separated) fields of `object`.
+    pub fn from_fields(
+        object: String,
+        start: Option<&str>,
+        end: Option<&str>,
+        namespaces: Option<&str>,
+    ) -> Result<Self> {
+        let timestamp = |field: &str, value: Option<&str>| -> Result<Option<Timestamp>> {
+            value
+                .map(|v| v.trim().parse::<Timestamp>())
+                .transpose()
+                .with_context(|| format!("Invalid {} in {}", field, object))
+        };
+
+        Ok(Self {
+            start: timestamp("start", start)?,
+            end: timestamp("end", end)?,
+            namespaces: namespaces
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|ns| !ns.is_empty())
+                .map(String::from)
+                .collect(),
+            object,
+        })
+    }
+
+    /// Whether the window defers promotions in `namespace` at `now`.
+    pub fn blocks(&self, namespace: &str, now: Timestamp) -> bool {
+        (self.namespaces.is_empty() || self.namespaces.iter().any(|ns| ns == namespace))
+            && self.start.is_none_or(|start| start <= now)
+            && self.end.is_none_or(|end| now < end)
+    }
+
+    /// `Kind namespace/name`, with the end of the window if it has one.
+    pub fn describe(&self) -> String {
+        match self.end {
+            Some(end) => format!("{} (until {})", self.object, end),
+            None => self.object.clone(),
+        }
+    }
+}
+
+/// The first of `windows` deferring promotions in `namespace` at `now`.
+pub fn blocking_window<'a>(
+    windows: &'a [MaintenanceWindow],
+    namespace: &str,
+    now: Timestamp,
+) -> Option<&'a MaintenanceWindow> {
+    windows.iter().find(|window| window.blocks(namespace, now))
+}
+
+/// A period during which a deployment's promotions are deferred, from its
+/// `gitops.operator.freeze_windows` annotation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FreezeWindow {
+    /// `<start>/<end>`, two RFC 3339 or RFC 9557 timestamps.
+    Interval { start: Timestamp, end: Timestamp },
+    /// `<cron expression> for <duration>`: starts whenever the schedule
+    /// fires (UTC) and lasts `duration`, e.g. `0 0 * * 6 for 2d`.
+    Recurring {
+        schedule: CronSchedule,
+        duration: SignedDuration,
+    },
+}
+
+/// Parse a duration such as `2d`, `48h` or `1h30m`.
+fn parse_freeze_duration(value: &str) -> Result<SignedDuration> {
+    let value = value.trim();
+    let mut total = SignedDuration::ZERO;
+    let mut digits = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit: i64 = match c {
+            'd' => 86400,
+            'h' => 3600,
+            'm' => 60,
+            _ => bail!("Invalid duration '{}': unknown unit '{}'", value, c),
+        };
+        let amount: i64 = digits
+            .parse()
+            .with_context(|| format!("Invalid duration '{}'", value))?;
+        total += SignedDuration::from_secs(amount * unit);
+        digits.clear();
+    }
+    if !digits.is_empty() || total.is_zero() {
+        bail!(
+            "Invalid duration '{}': expected e.g. 2d, 48h or 1h30m",
+            value
+        );
+    }
+    Ok(total)
+}
+
+impl FreezeWindow {
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if let Some((schedule, duration)) = value.split_once(" for ") {
+            return Ok(Self::Recurring {
+                schedule: CronSchedule::parse(schedule)?,
+                duration: parse_freeze_duration(duration)?,
+            });
+        }
+
+        let Some((start, end)) = value.split_once('/') else {
+            bail!(
+                "Invalid freeze window '{}': expected '<start>/<end>' or '<cron> for <duration>'",
+                value
+            );
+        };
+        let timestamp = |field: &str| {
+            field
+                .trim()
+                .parse::<Timestamp>()
+                .with_context(|| format!("Invalid freeze window '{}'", value))
+        };
+        let (start, end) = (timestamp(start)?, timestamp(end)?);
+        if end <= start {
+            bail!(
+                "Invalid freeze window '{}': it ends before it starts",
+                value
+            );
+        }
+        Ok(Self::Interval { start, end })
+    }
+
+    /// When the window covering `now` ends, or `None` if it doesn't cover it.
+    pub fn active_until(&self, now: Timestamp) -> Option<Timestamp> {
+        match self {
+            Self::Interval { start, end } => (*start <= now && now < *end).then_some(*end),
+            Self::Recurring { schedule, duration } => {
+                // The first start within the last `duration` is still running.
+                let started = schedule.next_after(now.checked_sub(*duration).ok()?)?;
+                (started <= now).then(|| started.checked_add(*duration).ok())?
+            }
+        }
+    }
+
+    /// The window as written in the annotation.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Interval { start, end } => format!("{}/{}", start, end),
+            Self::Recurring { schedule, duration } => {
+                format!("{} for {:#}", schedule, duration)
+            }
+        }
+    }
+}
+
+/// Parse the `;`-separated windows of a `gitops.operator.freeze_windows`
+/// annotation.
+pub fn parse_freeze_windows(value: &str) -> Result<Vec<FreezeWindow>> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|window| !window.is_empty())
+        .map(FreezeWindow::parse)
+        .collect()
+}
+
+/// The first of `windows` covering `now`, with when it ends.
+pub fn active_freeze(
+    windows: &[FreezeWindow],
+    now: Timestamp,
+) -> Option<(&FreezeWindow, Timestamp)> {
+    windows
+        .iter()
+        .find_map(|window| window.active_until(now).map(|end| (window, end)))
+}
+
+/// Parse a comma-separated list of `group/version/Kind` (or `version/Kind`
+/// for the core group), e.g. `maintenance.example.com/v1/MaintenanceWindow`.
+pub fn parse_resources(value: &str) -> Result<Vec<GroupVersionKind>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|resource| !resource.is_empty())
+        .map(
+            |resource| match resource.split('/').collect::<Vec<_>>()[..] {
+                [group, version, kind] => Ok(GroupVersionKind::gvk(group, version, kind)),
+                [version, kind] => Ok(GroupVersionKind::gvk("", version, kind)),
+                _ => anyhow::bail!("Invalid maintenance resource {:?}", resource),
+            },
+        )
+        .collect()
+}
+
+/// Reads maintenance windows from labelled ConfigMaps and from objects of
+/// the kinds in `GITOPS_MAINTENANCE_RESOURCES`, whose optional `spec.start`,
+/// `spec.end` and `spec.namespaces` bound them like the ConfigMap fields.
+pub struct KubeMaintenanceWindows {
+    resources: Vec<GroupVersionKind>,
+}
+
+/// The kinds listed in `GITOPS_MAINTENANCE_RESOURCES`.
+pub fn maintenance_resources() -> Vec<GroupVersionKind> {
+    match std::env::var("GITOPS_MAINTENANCE_RESOURCES") {
+        Ok(value) => parse_resources(&value).unwrap_or_else(|e| {
+            warn!("{:#}; only ConfigMap maintenance windows are used", e);
+            vec![]
+        }),
+        Err(_) => vec![],
+    }
+}
+
+impl KubeMaintenanceWindows {
+    /// Uses the kinds from `GITOPS_MAINTENANCE_RESOURCES` the cluster serves,
+    /// according to the startup probe.
+    pub fn from_env() -> Self {
+        let resources = maintenance_resources()
+            .into_iter()
+            .filter(|gvk| capabilities().has_kind(gvk))
+            .collect();
+        Self { resources }
+    }
+
+    async fn config_map_windows(&self, client: Client) -> Result<Vec<MaintenanceWindow>> {
+        let config_maps: Api<ConfigMap> = Api::all(client);
+        let params = ListParams::default().labels(&format!("{}=true", MAINTENANCE_WINDOW_LABEL));
+
+        Ok(config_maps
+            .list(&params)
+            .await?
+            .into_iter()
+            .filter_map(|cm| {
+                let object = format!(
+                    "ConfigMap {}/{}",
+                    cm.namespace().unwrap_or_default(),
+                    cm.name_any()
+                );
+                let data = cm.data.unwrap_or_default();
+                let field = |key: &str| data.get(key).map(String::as_str);
+                MaintenanceWindow::from_fields(
+                    object,
+                    field("start"),
+                    field("end"),
+                    field("namespaces"),
+                )
+                .inspect_err(|e| warn!("Ignoring maintenance window: {:#}", e))
+                .ok()
+            })
+            .collect())
+    }
+
+    async fn resource_windows(
+        &self,
+        client: Client,
+        gvk: &GroupVersionKind,
+    ) -> Result<Vec<MaintenanceWindow>> {
+        let api: Api<DynamicObject> = Api::all_with(client, &ApiResource::from_gvk(gvk));
+
+        Ok(api
+            .list(&ListParams::default())
+            .await?
+            .into_iter()
+            .filter_map(|obj| {
+                let object = match obj.namespace() {
+                    Some(ns) => format!("{} {}/{}", gvk.kind, ns, obj.name_any()),
+                    None => format!("{} {}", gvk.kind, obj.name_any()),
+                };
+                let spec = obj.data.get("spec");
+                let field = |key: &str| spec.and_then(|s| s.get(key));
+                let namespaces = match field("namespaces") {
+                    Some(serde_json::Value::Array(items)) => Some(
+                        items
+                            .iter()
+                            .filter_map(|ns| ns.as_str())
+                            .collect::<Vec<_>>()
+                            .join(","),
+                    ),
+                    Some(value) => value.as_str().map(String::from),
+                    None => None,
+                };
+                MaintenanceWindow::from_fields(
+                    object,
+                    field("start").and_then(|v| v.as_str()),
+                    field("end").and_then(|v| v.as_str()),
+                    namespaces.as_deref(),
+                )
+                .inspect_err(|e| warn!("Ignoring maintenance window: {:#}", e))
+                .ok()
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl MaintenanceWindowSource for KubeMaintenanceWindows {
+    async fn windows(&self) -> Result<Vec<MaintenanceWindow>> {
+        let client = Client::try_default().await?;
+        let mut windows = self.config_map_windows(client.clone()).await?;
+        for gvk in &self.resources {
+            windows.extend(self.resource_windows(client.clone(), gvk).await?);
+        }
+        Ok(windows)
+    }
+}
@@ -0,0 +1,296 @@
+/// Kind of a Prometheus metric exported by the operator.
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+/// Prometheus alerting rule attached to a metric.
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct AlertRule {
+    pub name: &'static str,
+    /// PromQL expression; must reference the metric it is attached to.
+    pub expr: &'static str,
+    /// How long `expr` must hold before the alert fires (e.g. `5m`).
+    pub for_duration: &'static str,
+    pub severity: &'static str,
+    pub summary: &'static str,
+}
+
+/// Name, kind, and help text of an operator metric. Every metric the operator
+/// records is listed in [`METRICS`] so it is described once at startup, and
+/// so the Grafana dashboard and alert rules served under `/assets` cover it.
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct MetricDef {
+    pub name: &'static str,
+    pub kind: MetricKind,
+    pub help: &'static str,
+    pub alert: Option<AlertRule>,
+}
+
+pub const STALE_LOCKS_REAPED_TOTAL: &str = "gitops_operator_stale_locks_reaped_total";
+pub const ENTRIES_SUSPENDED_TOTAL: &str = "gitops_operator_entries_suspended_total";
+pub const REPO_SIZE_BYTES: &str = "gitops_operator_repo_size_bytes";
+pub const REPO_GC_TOTAL: &str = "gitops_operator_repo_gc_total";
+pub const REPO_GC_RECLAIMED_BYTES_TOTAL: &str = "gitops_operator_repo_gc_reclaimed_bytes_total";
+pub const REPO_CHECKOUTS_REMOVED_TOTAL: &str = "gitops_operator_repo_checkouts_removed_total";
+pub const REPO_CACHE_SIZE_BYTES: &str = "gitops_operator_repo_cache_size_bytes";
+pub const HTTP_RETRIES_TOTAL: &str = "gitops_operator_http_retries_total";
+pub const WEBHOOK_EVENTS_COALESCED_TOTAL: &str = "gitops_operator_webhook_events_coalesced_total";
+pub const GIT_FETCH_DURATION_SECONDS: &str = "gitops_operator_git_fetch_duration_seconds";
+pub const GIT_FETCH_RECEIVED_BYTES_TOTAL: &str = "gitops_operator_git_fetch_received_bytes_total";
+pub const GIT_RETRIES_TOTAL: &str = "gitops_operator_git_retries_total";
+pub const NOTIFICATION_FALLBACKS_TOTAL: &str = "gitops_operator_notification_fallbacks_total";
+pub const DEPLOYMENTS_DRIFTED: &str = "gitops_operator_deployments_drifted";
+pub const EGRESS_DENIED_TOTAL: &str = "gitops_operator_egress_denied_total";
+pub const ROLLOUTS_TOTAL: &str = "gitops_operator_rollouts_total";
+pub const IMAGE_DIGEST_MISMATCHES: &str = "gitops_operator_image_digest_mismatches";
+pub const REGISTRY_RATE_LIMIT_REMAINING: &str = "gitops_operator_registry_rate_limit_remaining";
+
+pub const METRICS: &[MetricDef] = &[
+    MetricDef {
+        name: STALE_LOCKS_REAPED_TOTAL,
+        kind: MetricKind::Counter,
+        help: "Per-deployment reconcile locks forcibly released after exceeding the lock deadline",
+        alert: Some(AlertRule {
+            name: "GitopsOperatorStaleLocksReaped",
+            expr: "increase(gitops_operator_stale_locks_reaped_total[15m]) > 0",
+            for_duration: "0m",
+            severity: "warning",
+            summary: "A reconcile task held its lock past the deadline and was reaped",
+        }),
+    },
+    MetricDef {
+        name: ENTRIES_SUSPENDED_TOTAL,
+        kind: MetricKind::Counter,
+        help: "Deployments suspended after reaching the consecutive push failure threshold",
+        alert: Some(AlertRule {
+            name: "GitopsOperatorDeploymentSuspended",
+            expr: "increase(gitops_operator_entries_suspended_total[15m]) > 0",
+            for_duration: "0m",
+            severity: "critical",
+            summary: "A deployment kept failing to push and was suspended until resumed",
+        }),
+    },
+    MetricDef {
+        name: REPO_SIZE_BYTES,
+        kind: MetricKind::Gauge,
+        help: "Size of the object database of each cached repository checkout, by repo path",
+        alert: None,
+    },
+    MetricDef {
+        name: REPO_GC_TOTAL,
+        kind: MetricKind::Counter,
+        help: "Cached repository checkouts repacked by repository maintenance",
+        alert: None,
+    },
+    MetricDef {
+        name: REPO_GC_RECLAIMED_BYTES_TOTAL,
+        kind: MetricKind::Counter,
+        help: "Bytes of object database reclaimed by repacking cached repository checkouts",
+        alert: None,
+    },
+    MetricDef {
+        name: REPO_CHECKOUTS_REMOVED_TOTAL,
+        kind: MetricKind::Counter,
+        help: "Cached repository checkouts deleted by repository maintenance, by reason (idle, orphaned or evicted)",
+        alert: None,
+    },
+    MetricDef {
+        name: REPO_CACHE_SIZE_BYTES,
+        kind: MetricKind::Gauge,
+        help: "Disk space taken by the cached repository checkouts and mirrors",
+        alert: None,
+    },
+    MetricDef {
+        name: HTTP_RETRIES_TOTAL,
+        kind: MetricKind::Counter,
+        help: "Outgoing HTTP requests retried after a transient failure, by host",
+        alert: None,
+    },
+    MetricDef {
+        name: WEBHOOK_EVENTS_COALESCED_TOTAL,
+        kind: MetricKind::Counter,
+        help: "Registry webhook events folded into a reconcile already queued for the same repository and branch",
+        alert: None,
+    },
+    MetricDef {
+        name: GIT_FETCH_DURATION_SECONDS,
+        kind: MetricKind::Histogram,
+        help: "Time taken to clone or fetch a repository, by repository",
+        alert: None,
+    },
+    MetricDef {
+        name: GIT_FETCH_RECEIVED_BYTES_TOTAL,
+        kind: MetricKind::Counter,
+        help: "Bytes received cloning or fetching repositories, by repository",
+        alert: None,
+    },
+    MetricDef {
+        name: GIT_RETRIES_TOTAL,
+        kind: MetricKind::Counter,
+        help: "Clones, fetches and pushes retried after a transient failure, by operation",
+        alert: None,
+    },
+    MetricDef {
+        name: NOTIFICATION_FALLBACKS_TOTAL,
+        kind: MetricKind::Counter,
+        help: "Notifications re-sent to the fallback webhook after a deployment's webhook failed",
+        alert: Some(AlertRule {
+            name: "GitopsOperatorNotificationWebhookFailing",
+            expr: "increase(gitops_operator_notification_fallbacks_total[1h]) > 0",
+            for_duration: "0m",
+            severity: "warning",
+            summary: "A deployment's notification webhook is failing; alerts go to the fallback channel",
+        }),
+    },
+    MetricDef {
+        name: DEPLOYMENTS_DRIFTED,
+        kind: MetricKind::Gauge,
+        help: "Deployments whose live image tag differs from the one in their manifest",
+        alert: Some(AlertRule {
+            name: "GitopsOperatorDeploymentDrifted",
+            expr: "gitops_operator_deployments_drifted > 0",
+            for_duration: "30m",
+            severity: "warning",
+            summary: "A deployment runs a different image than its manifest declares; see /drift",
+        }),
+    },
+    MetricDef {
+        name: EGRESS_DENIED_TOTAL,
+        kind: MetricKind::Counter,
+        help: "Outbound calls refused by the egress policy, by kind (git, registry, webhook)",
+        alert: Some(AlertRule {
+            name: "GitopsOperatorEgressDenied",
+            expr: "increase(gitops_operator_egress_denied_total[15m]) > 0",
+            for_duration: "0m",
+            severity: "warning",
+            summary: "An annotation points the operator at a host outside its egress allowlist",
+        }),
+    },
+    MetricDef {
+        name: ROLLOUTS_TOTAL,
+        kind: MetricKind::Counter,
+        help: "Verified rollouts of pushed images, by outcome (complete, failed, timed_out)",
+        alert: Some(AlertRule {
+            name: "GitopsOperatorRolloutFailed",
+            expr: "increase(gitops_operator_rollouts_total{status!=\"complete\"}[30m]) > 0",
+            for_duration: "0m",
+            severity: "warning",
+            summary: "A pushed image failed to roll out in the cluster; see /rollouts",
+        }),
+    },
+    MetricDef {
+        name: IMAGE_DIGEST_MISMATCHES,
+        kind: MetricKind::Gauge,
+        help: "Containers running their manifest's tag from a different digest than the registry serves for it",
+        alert: Some(AlertRule {
+            name: "GitopsOperatorImageDigestMismatch",
+            expr: "gitops_operator_image_digest_mismatches > 0",
+            for_duration: "15m",
+            severity: "warning",
+            summary: "An image tag was pushed again after it was promoted; see /drift",
+        }),
+    },
+    MetricDef {
+        name: REGISTRY_RATE_LIMIT_REMAINING,
+        kind: MetricKind::Gauge,
+        help: "Requests left in the registry's rate-limit window (Docker Hub's ratelimit-remaining), by registry",
+        alert: Some(AlertRule {
+            name: "GitopsOperatorRegistryRateLimited",
+            expr: "gitops_operator_registry_rate_limit_remaining == 0",
+            for_duration: "0m",
+            severity: "warning",
+            summary: "A registry is rate limiting image checks; authenticate or use a mirror",
+        }),
+    },
+];
+
+/// Register help text for every metric in [`METRICS`] with the installed
+/// recorder. Call once after the Prometheus recorder is set up.
+pub fn describe_metrics() {
+    for metric in METRICS {
+        match metric.kind {
+            MetricKind::Counter => ::metrics::describe_counter!(metric.name, metric.help),
+            MetricKind::Gauge => ::metrics::describe_gauge!(metric.name, metric.help),
+            MetricKind::Histogram => ::metrics::describe_histogram!(metric.name, metric.help),
+        }
+    }
+}
+
+/// PromQL used to chart a metric: per-second rate for counters, the value for
+/// gauges, and the p95 for histograms.
+fn panel_expr(metric: &MetricDef) -> String {
+    match metric.kind {
+        MetricKind::Counter => format!("sum(rate({}[5m]))", metric.name),
+        MetricKind::Gauge => format!("sum({})", metric.name),
+        MetricKind::Histogram => format!(
+            "histogram_quantile(0.95, sum by (le) (rate({}_bucket[5m])))",
+            metric.name
+        ),
+    }
+}
+
+/// Grafana dashboard with one panel per metric in [`METRICS`], served at
+/// `/assets/grafana-dashboard.json`. Panels query a `datasource` variable so
+/// the dashboard can be imported against any Prometheus data source.
+pub fn grafana_dashboard() -> serde_json::Value {
+    let panels: Vec<serde_json::Value> = METRICS
+        .iter()
+        .enumerate()
+        .map(|(i, metric)| {
+            serde_json::json!({
+                "id": i + 1,
+                "type": "timeseries",
+                "title": metric.name,
+                "description": metric.help,
+                "datasource": { "type": "prometheus", "uid": "${datasource}" },
+                "gridPos": { "h": 8, "w": 12, "x": (i % 2) * 12, "y": (i / 2) * 8 },
+                "targets": [{ "refId": "A", "expr": panel_expr(metric) }],
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "uid": "gitops-operator",
+        "title": "gitops-operator",
+        "tags": ["gitops-operator"],
+        "schemaVersion": 39,
+        "version": 1,
+        "time": { "from": "now-6h", "to": "now" },
+        "templating": {
+            "list": [{
+                "name": "datasource",
+                "type": "datasource",
+                "query": "prometheus",
+            }]
+        },
+        "panels": panels,
+    })
+}
+
+/// Prometheus rule file with the alert of every metric in [`METRICS`] that
+/// has one, served at `/assets/prometheus-rules.yaml`.
+pub fn prometheus_rules() -> String {
+    let rules: Vec<serde_json::Value> = METRICS
+        .iter()
+        .filter_map(|metric| metric.alert)
+        .map(|alert| {
+            serde_json::json!({
+                "alert": alert.name,
+                "expr": alert.expr,
+                "for": alert.for_duration,
+                "labels": { "severity": alert.severity },
+                "annotations": { "summary": alert.summary },
+            })
+        })
+        .collect();
+
+    let file = serde_json::json!({
+        "groups": [{ "name": "gitops-operator", "rules": rules }]
+    });
+
+    serde_yaml::to_string(&file).unwrap_or_default()
+}
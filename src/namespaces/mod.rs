@@ -0,0 +1,3 @@
+#[allow(clippy::module_inception)]
+mod namespaces;
+pub use namespaces::*;
@@ -0,0 +1,104 @@
+use k8s_openapi::api::core::v1::Namespace;
+use kube::runtime::reflector::{ObjectRef, Store};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use tracing::info;
+
+/// Namespace label that opts a namespace in when `GITOPS_REQUIRE_NAMESPACE_LABEL`
+/// is enabled.
+pub const NAMESPACE_OPT_IN_LABEL: &str = "gitops.operator/enabled";
+
+const DEFAULT_DENIED_NAMESPACES: &str = "kube-system,kube-public,kube-node-lease";
+
+/// Cluster-wide control over which namespaces the operator looks at, applied
+/// before any of a namespace's Deployments are considered.
+pub struct NamespacePolicy {
+    denied: Vec<String>,
+    require_label: bool,
+    namespaces: OnceLock<Store<Namespace>>,
+}
+
+impl NamespacePolicy {
+    pub fn new(denied: Vec<String>, require_label: bool) -> Self {
+        Self {
+            denied,
+            require_label,
+            namespaces: OnceLock::new(),
+        }
+    }
+
+    /// Read the policy from `GITOPS_DENIED_NAMESPACES` (comma-separated,
+    /// default `kube-system,kube-public,kube-node-lease`; set it empty to deny
+    /// nothing) and `GITOPS_REQUIRE_NAMESPACE_LABEL` (default false).
+    pub fn from_env() -> Self {
+        let denied = std::env::var("GITOPS_DENIED_NAMESPACES")
+            .unwrap_or_else(|_| DEFAULT_DENIED_NAMESPACES.to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|ns| !ns.is_empty())
+            .map(String::from)
+            .collect();
+        let require_label = std::env::var("GITOPS_REQUIRE_NAMESPACE_LABEL")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(false);
+
+        Self::new(denied, require_label)
+    }
+
+    /// Whether namespaces must carry [`NAMESPACE_OPT_IN_LABEL`], in which case
+    /// a Namespace store has to be provided with [`Self::watch_namespaces`].
+    pub fn requires_label(&self) -> bool {
+        self.require_label
+    }
+
+    /// Provide the reflector store used to look up namespace labels. Only the
+    /// first store is kept.
+    pub fn watch_namespaces(&self, store: Store<Namespace>) {
+        if self.namespaces.set(store).is_ok() {
+            info!(
+                "Only namespaces labelled {}=true are reconciled",
+                NAMESPACE_OPT_IN_LABEL
+            );
+        }
+    }
+
+    /// Whether Deployments in `namespace` may be processed. With the opt-in
+    /// label required, namespaces are denied until the store knows about them.
+    pub fn allows(&self, namespace: &str) -> bool {
+        if !self.require_label {
+            return self.allows_labels(namespace, None);
+        }
+
+        let namespace_obj = self
+            .namespaces
+            .get()
+            .and_then(|store| store.get(&ObjectRef::new(namespace)));
+        let labels = namespace_obj
+            .as_ref()
+            .and_then(|ns| ns.metadata.labels.as_ref());
+        self.allows_labels(namespace, labels)
+    }
+
+    /// [`Self::allows`] for a namespace whose labels are already known.
+    pub fn allows_labels(
+        &self,
+        namespace: &str,
+        labels: Option<&BTreeMap<String, String>>,
+    ) -> bool {
+        if self.denied.iter().any(|denied| denied == namespace) {
+            return false;
+        }
+
+        !self.require_label
+            || labels
+                .and_then(|labels| labels.get(NAMESPACE_OPT_IN_LABEL))
+                .is_some_and(|value| value.trim() == "true")
+    }
+}
+
+/// The process-wide policy, read from the environment on first use.
+pub fn namespace_policy() -> &'static NamespacePolicy {
+    static POLICY: OnceLock<NamespacePolicy> = OnceLock::new();
+    POLICY.get_or_init(NamespacePolicy::from_env)
+}
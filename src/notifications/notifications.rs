@@ -1,9 +1,367 @@
+use crate::http_client::http_client;
+use crate::metrics::NOTIFICATION_FALLBACKS_TOTAL;
+use crate::retry::{IDEMPOTENCY_KEY, with_retries};
+use crate::slack::{approval_payload, notification_payload, slack_signing_secret};
 use crate::traits::NotificationSender;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use reqwest;
+use reqwest_middleware::ClientWithMiddleware;
 use serde_json;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use tracing::warn;
+use uuid::Uuid;
+
+/// The payload format a notification webhook expects
+/// (`gitops.operator.notifications_provider`, else detected from the URL).
+#[derive(serde::Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationProvider {
+    /// `{"text": ...}`, which generic webhooks take
+    #[default]
+    Webhook,
+    /// Block Kit messages for Slack incoming webhooks
+    Slack,
+    /// Embeds for Discord webhooks, which refuse Slack payloads
+    Discord,
+    /// Adaptive Cards for Microsoft Teams incoming webhooks and workflows
+    Teams,
+}
+
+impl NotificationProvider {
+    /// The provider of webhook `url`: Slack, Discord or Teams for their own
+    /// webhook hosts, a generic webhook otherwise.
+    pub fn detect(url: &str) -> Self {
+        let host = url
+            .split_once("://")
+            .map_or(url, |(_, rest)| rest)
+            .split(['/', '?'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match host.as_str() {
+            "hooks.slack.com" => NotificationProvider::Slack,
+            "discord.com" | "discordapp.com" | "ptb.discord.com" | "canary.discord.com" => {
+                NotificationProvider::Discord
+            }
+            host if host.ends_with(".webhook.office.com") => NotificationProvider::Teams,
+            _ => NotificationProvider::Webhook,
+        }
+    }
+
+    /// Whether the provider takes Slack's block payloads, e.g. approval
+    /// buttons.
+    pub fn takes_slack_blocks(&self) -> bool {
+        matches!(
+            self,
+            NotificationProvider::Webhook | NotificationProvider::Slack
+        )
+    }
+}
+
+impl FromStr for NotificationProvider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "webhook" => Ok(NotificationProvider::Webhook),
+            "slack" => Ok(NotificationProvider::Slack),
+            "discord" => Ok(NotificationProvider::Discord),
+            "teams" => Ok(NotificationProvider::Teams),
+            other => anyhow::bail!(
+                "Invalid notifications provider '{}'. Must be 'webhook', 'slack', 'discord' or 'teams'",
+                other
+            ),
+        }
+    }
+}
+
+/// What a notification reports, shown as its color by the providers that
+/// have one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NotificationStatus {
+    #[default]
+    Info,
+    Success,
+    Warning,
+    Failure,
+}
+
+impl NotificationStatus {
+    /// The color as a 24-bit RGB value.
+    pub fn rgb(&self) -> u32 {
+        match self {
+            NotificationStatus::Info => 0x439FE0,
+            NotificationStatus::Success => 0x2EB67D,
+            NotificationStatus::Warning => 0xECB22E,
+            NotificationStatus::Failure => 0xE01E5A,
+        }
+    }
+
+    /// The color as a hex string, e.g. `#2EB67D`.
+    pub fn color(&self) -> String {
+        format!("#{:06X}", self.rgb())
+    }
+
+    /// The Adaptive Card container style of the status.
+    pub fn card_style(&self) -> &'static str {
+        match self {
+            NotificationStatus::Info => "accent",
+            NotificationStatus::Success => "good",
+            NotificationStatus::Warning => "warning",
+            NotificationStatus::Failure => "attention",
+        }
+    }
+}
+
+/// A notification about a deployment: its message, plus the details rich
+/// providers lay out next to it. Generic webhooks only get the text.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Notification {
+    pub text: String,
+    pub status: NotificationStatus,
+    pub app: String,
+    pub namespace: String,
+    /// Tag the deployment moved from, if known.
+    pub from_tag: Option<String>,
+    /// Tag the deployment moved (or is moving) to.
+    pub to_tag: Option<String>,
+    /// Web page of the app commit `to_tag` was built from.
+    pub commit_url: Option<String>,
+}
+
+impl Notification {
+    pub fn new(status: NotificationStatus, text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            status,
+            ..Default::default()
+        }
+    }
+
+    pub fn info(text: impl Into<String>) -> Self {
+        Self::new(NotificationStatus::Info, text)
+    }
+
+    pub fn success(text: impl Into<String>) -> Self {
+        Self::new(NotificationStatus::Success, text)
+    }
+
+    pub fn warning(text: impl Into<String>) -> Self {
+        Self::new(NotificationStatus::Warning, text)
+    }
+
+    pub fn failure(text: impl Into<String>) -> Self {
+        Self::new(NotificationStatus::Failure, text)
+    }
+
+    pub fn with_tags(mut self, from_tag: Option<&str>, to_tag: &str) -> Self {
+        self.from_tag = from_tag.map(String::from);
+        self.to_tag = Some(to_tag.to_string());
+        self
+    }
+
+    pub fn with_commit_url(mut self, commit_url: Option<String>) -> Self {
+        self.commit_url = commit_url;
+        self
+    }
+}
+
+/// Where a deployment's notifications go: the webhook URL from its
+/// notifications secret, and the headers some receivers need on top of it.
+#[derive(Clone, Default, PartialEq)]
+pub struct NotificationEndpoint {
+    pub url: String,
+    /// Sent with every request, e.g. `Authorization: Bearer <auth-token>`.
+    pub headers: BTreeMap<String, String>,
+    /// How the messages are formatted; detected from the URL unless the
+    /// deployment names one.
+    pub provider: NotificationProvider,
+}
+
+// Header values are credentials; keep them out of the logs.
+impl fmt::Debug for NotificationEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NotificationEndpoint")
+            .field("url", &self.url)
+            .field("headers", &self.headers.keys().collect::<Vec<_>>())
+            .field("provider", &self.provider)
+            .finish()
+    }
+}
+
+impl NotificationEndpoint {
+    pub fn new(url: impl Into<String>) -> Self {
+        let url = url.into();
+        Self {
+            provider: NotificationProvider::detect(&url),
+            url,
+            headers: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_provider(mut self, provider: NotificationProvider) -> Self {
+        self.provider = provider;
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Read an endpoint from the fields of a notifications secret:
+    /// `webhook-url`, and optionally `auth-token` (sent as a bearer token)
+    /// and `headers` (one `Name: value` per line, which win over the token).
+    pub fn from_secret_data(data: &BTreeMap<String, Vec<u8>>) -> Result<Self> {
+        let field = |key: &str| {
+            data.get(key)
+                .map(|bytes| {
+                    String::from_utf8(bytes.clone())
+                        .with_context(|| format!("Failed to convert {} to string", key))
+                })
+                .transpose()
+        };
+
+        let url = field("webhook-url")?.context("Failed to read field: webhook-url in data, consider recreating the secret with kubectl create secret generic webhook-secret-name -n your_namespace --from-literal=webhook-url=https://hooks.sl...")?;
+        let mut endpoint = Self::new(url);
+        if let Some(token) = field("auth-token")?.filter(|token| !token.trim().is_empty()) {
+            endpoint = endpoint.with_header("Authorization", &format!("Bearer {}", token.trim()));
+        }
+        for line in field("headers")?.unwrap_or_default().lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .with_context(|| format!("Invalid header '{}', expected 'Name: value'", line))?;
+            let name = name.trim();
+            reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("Invalid header name '{}'", name))?;
+            endpoint = endpoint.with_header(name, value.trim());
+        }
+        Ok(endpoint)
+    }
+}
+
+/// Discord webhook payload for `notification`: an embed with its text, then
+/// the app, namespace, tag change and commit link as fields, colored by its
+/// status.
+pub fn discord_payload(notification: &Notification) -> serde_json::Value {
+    let mut fields = vec![];
+    let mut field = |name: &str, value: String| {
+        fields.push(serde_json::json!({ "name": name, "value": value, "inline": true }));
+    };
+    if !notification.app.is_empty() {
+        field("App", notification.app.clone());
+    }
+    if !notification.namespace.is_empty() {
+        field("Namespace", notification.namespace.clone());
+    }
+    if let Some(to_tag) = &notification.to_tag {
+        let tag = match &notification.from_tag {
+            Some(from_tag) if from_tag != to_tag => format!("`{}` → `{}`", from_tag, to_tag),
+            _ => format!("`{}`", to_tag),
+        };
+        field("Tag", tag);
+    }
+    if let Some(url) = &notification.commit_url {
+        field("Commit", format!("[View commit]({})", url));
+    }
+
+    serde_json::json!({
+        "embeds": [
+            {
+                "description": notification.text,
+                "color": notification.status.rgb(),
+                "fields": fields,
+            },
+        ],
+    })
+}
+
+/// Teams webhook payload for `notification`: an Adaptive Card with its text
+/// in a container styled by its status, the app, namespace and tag change as
+/// facts, and a button opening the commit.
+pub fn teams_payload(notification: &Notification) -> serde_json::Value {
+    let mut facts = vec![];
+    let mut fact = |title: &str, value: String| {
+        facts.push(serde_json::json!({ "title": title, "value": value }));
+    };
+    if !notification.app.is_empty() {
+        fact("App", notification.app.clone());
+    }
+    if !notification.namespace.is_empty() {
+        fact("Namespace", notification.namespace.clone());
+    }
+    if let Some(to_tag) = &notification.to_tag {
+        let tag = match &notification.from_tag {
+            Some(from_tag) if from_tag != to_tag => format!("{} → {}", from_tag, to_tag),
+            _ => to_tag.clone(),
+        };
+        fact("Tag", tag);
+    }
+
+    let mut body = vec![serde_json::json!({
+        "type": "Container",
+        "style": notification.status.card_style(),
+        "items": [{ "type": "TextBlock", "text": notification.text, "wrap": true }],
+    })];
+    if !facts.is_empty() {
+        body.push(serde_json::json!({ "type": "FactSet", "facts": facts }));
+    }
+    let mut card = serde_json::json!({
+        "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+        "type": "AdaptiveCard",
+        "version": "1.4",
+        "body": body,
+    });
+    if let Some(url) = &notification.commit_url {
+        card["actions"] = serde_json::json!([
+            { "type": "Action.OpenUrl", "title": "View commit", "url": url },
+        ]);
+    }
+    serde_json::json!({
+        "type": "message",
+        "attachments": [
+            {
+                "contentType": "application/vnd.microsoft.card.adaptive",
+                "content": card,
+            },
+        ],
+    })
+}
+
+/// Webhook receiving the notifications a deployment's own webhook failed to
+/// deliver, from `GITOPS_FALLBACK_WEBHOOK_URL`.
+pub fn fallback_webhook_url() -> Option<String> {
+    std::env::var("GITOPS_FALLBACK_WEBHOOK_URL")
+        .ok()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+}
+
+/// POST `payload` to `endpoint`. Every attempt carries the same
+/// `Idempotency-Key`, so the post can be retried without the receiver
+/// delivering it twice.
+async fn post(
+    client: &ClientWithMiddleware,
+    endpoint: &str,
+    headers: &BTreeMap<String, String>,
+    payload: &serde_json::Value,
+) -> reqwest_middleware::Result<reqwest::Response> {
+    let mut request = client
+        .post(endpoint)
+        .header("Content-Type", "application/json")
+        .header(IDEMPOTENCY_KEY, Uuid::new_v4().to_string());
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    request.json(payload).send().await
+}
 
 #[tracing::instrument(name = "send", skip(endpoint), fields())]
 pub async fn send(
@@ -15,26 +373,47 @@ pub async fn send(
         return Err("No notification endpoint configured".into());
     };
 
-    let client = reqwest::Client::new();
+    let client = with_retries(http_client());
     let payload = serde_json::json!({
         "text": message
     });
 
-    Ok(client
-        .post(endpoint)
-        .header("Content-Type", "application/json")
-        .json(&payload)
-        .send()
-        .await?)
+    Ok(post(&client, endpoint, &BTreeMap::new(), &payload).await?)
 }
 
 /// HTTP-based implementation of NotificationSender
 #[derive(Clone)]
-pub struct HttpNotificationSender;
+pub struct HttpNotificationSender {
+    client: ClientWithMiddleware,
+    /// Whether approval requests carry Slack buttons, i.e. a signing secret
+    /// is configured to verify the clicks.
+    interactive: bool,
+}
 
 impl HttpNotificationSender {
     pub fn new() -> Self {
-        Self
+        Self {
+            client: with_retries(http_client()),
+            interactive: slack_signing_secret().is_some(),
+        }
+    }
+
+    /// POST an arbitrary JSON `payload` to `endpoint`, e.g. a Slack
+    /// `response_url`.
+    pub async fn send_payload(&self, payload: &serde_json::Value, endpoint: &str) -> Result<()> {
+        self.deliver(payload, &NotificationEndpoint::new(endpoint))
+            .await
+    }
+
+    async fn deliver(
+        &self,
+        payload: &serde_json::Value,
+        endpoint: &NotificationEndpoint,
+    ) -> Result<()> {
+        post(&self.client, &endpoint.url, &endpoint.headers, payload)
+            .await?
+            .error_for_status()?;
+        Ok(())
     }
 }
 
@@ -46,19 +425,160 @@ impl Default for HttpNotificationSender {
 
 #[async_trait]
 impl NotificationSender for HttpNotificationSender {
-    async fn send(&self, message: &str, endpoint: &str) -> Result<()> {
-        let client = reqwest::Client::new();
-        let payload = serde_json::json!({
-            "text": message
-        });
-
-        client
-            .post(endpoint)
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await?;
+    async fn send(&self, message: &str, endpoint: &NotificationEndpoint) -> Result<()> {
+        let payload = match endpoint.provider {
+            NotificationProvider::Discord => serde_json::json!({
+                "content": message
+            }),
+            // Teams workflows only post cards.
+            NotificationProvider::Teams => teams_payload(&Notification::info(message)),
+            _ => serde_json::json!({
+                "text": message
+            }),
+        };
 
-        Ok(())
+        // A rotated or revoked webhook answers with an error status rather
+        // than failing to connect.
+        self.deliver(&payload, endpoint).await
+    }
+
+    async fn send_notification(
+        &self,
+        notification: &Notification,
+        endpoint: &NotificationEndpoint,
+    ) -> Result<()> {
+        match endpoint.provider {
+            NotificationProvider::Webhook => self.send(&notification.text, endpoint).await,
+            NotificationProvider::Slack => {
+                self.deliver(&notification_payload(notification), endpoint)
+                    .await
+            }
+            NotificationProvider::Discord => {
+                self.deliver(&discord_payload(notification), endpoint).await
+            }
+            NotificationProvider::Teams => {
+                self.deliver(&teams_payload(notification), endpoint).await
+            }
+        }
+    }
+
+    async fn send_approval_request(
+        &self,
+        message: &str,
+        id: &str,
+        endpoint: &NotificationEndpoint,
+    ) -> Result<()> {
+        if !self.interactive || !endpoint.provider.takes_slack_blocks() {
+            return self.send(message, endpoint).await;
+        }
+        self.deliver(&approval_payload(message, id), endpoint).await
+    }
+}
+
+/// Delivers through `primary`, and re-sends to the `fallback` endpoint
+/// whatever the primary endpoint still fails to take after its retries, so
+/// alerts aren't lost when a deployment's webhook is rotated or down.
+pub struct FallbackNotificationSender {
+    primary: Arc<dyn NotificationSender>,
+    fallback: NotificationEndpoint,
+    /// Consecutive delivery failures per primary endpoint.
+    failures: Mutex<HashMap<String, u32>>,
+}
+
+impl FallbackNotificationSender {
+    pub fn new(primary: Arc<dyn NotificationSender>, fallback: &str) -> Self {
+        Self {
+            primary,
+            fallback: NotificationEndpoint::new(fallback),
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// How many notifications in a row `endpoint` has failed to deliver.
+    pub fn consecutive_failures(&self, endpoint: &str) -> u32 {
+        let failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        failures.get(endpoint).copied().unwrap_or(0)
+    }
+
+    /// Note the outcome of a delivery to `endpoint`; on failure, the message
+    /// to send to the fallback instead.
+    fn record(&self, endpoint: &str, result: &Result<()>, message: &str) -> Option<String> {
+        let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        let Err(e) = result else {
+            failures.remove(endpoint);
+            return None;
+        };
+        if endpoint == self.fallback.url {
+            return None;
+        }
+
+        let count = failures.entry(endpoint.to_string()).or_insert(0);
+        *count += 1;
+        warn!(
+            "Notification webhook failed ({} in a row), using the fallback: {:#}",
+            count, e
+        );
+        ::metrics::counter!(NOTIFICATION_FALLBACKS_TOTAL).increment(1);
+        Some(format!(
+            "{}\n(sent to the fallback channel: the deployment's notification webhook failed {} time(s) in a row)",
+            message, count
+        ))
+    }
+}
+
+#[async_trait]
+impl NotificationSender for FallbackNotificationSender {
+    async fn send(&self, message: &str, endpoint: &NotificationEndpoint) -> Result<()> {
+        let result = self.primary.send(message, endpoint).await;
+        match self.record(&endpoint.url, &result, message) {
+            Some(message) => self
+                .primary
+                .send(&message, &self.fallback)
+                .await
+                .context("Fallback notification webhook failed too"),
+            None => result,
+        }
+    }
+
+    async fn send_notification(
+        &self,
+        notification: &Notification,
+        endpoint: &NotificationEndpoint,
+    ) -> Result<()> {
+        let result = self.primary.send_notification(notification, endpoint).await;
+        match self.record(&endpoint.url, &result, &notification.text) {
+            Some(text) => self
+                .primary
+                .send_notification(
+                    &Notification {
+                        text,
+                        ..notification.clone()
+                    },
+                    &self.fallback,
+                )
+                .await
+                .context("Fallback notification webhook failed too"),
+            None => result,
+        }
+    }
+
+    async fn send_approval_request(
+        &self,
+        message: &str,
+        id: &str,
+        endpoint: &NotificationEndpoint,
+    ) -> Result<()> {
+        let result = self
+            .primary
+            .send_approval_request(message, id, endpoint)
+            .await;
+        match self.record(&endpoint.url, &result, message) {
+            Some(message) => self
+                .primary
+                .send_approval_request(&message, id, &self.fallback)
+                .await
+                .context("Fallback notification webhook failed too"),
+            None => result,
+        }
     }
 }
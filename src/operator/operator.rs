@@ -0,0 +1,231 @@
+use crate::configuration::{DeploymentProcessor, Entry, ReconcileResult};
+use crate::traits::{
+    ClusterReporter, GitProvider, ImageCheckerFactory, MaintenanceWindowSource, NotificationSender,
+    PolicySource, PullRequestOpener, SecretProvider,
+};
+use futures::{StreamExt, future};
+use k8s_openapi::api::apps::v1::Deployment;
+use kube::runtime::{WatchStreamExt, reflector, watcher};
+use kube::{Api, Client};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How often [`Operator::run`] reconciles when no interval is set.
+pub const DEFAULT_RECONCILE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// The reconcile engine on its own, for embedding in other services: no HTTP
+/// server, schedule or webhooks, just passes over annotated Deployments.
+///
+/// Built with [`Operator::builder`]; every provider left unset is the one the
+/// operator binary uses.
+///
+/// Only one `Operator` per process is supported. The builder swaps the
+/// providers, but the rest of the engine's state is process-wide, read from
+/// the environment once: the entry and checkout locks, the pause, approval,
+/// change and suspension state, the registry map and mirrors, and the ECR,
+/// CodeCommit and GitHub App credential caches. Checkouts live under
+/// `GITOPS_REPO_CACHE_DIR` too, at paths named after the deployment. A second
+/// `Operator` would share all of it, so it can't be pointed at other settings,
+/// and its passes count against the first one's locks.
+#[derive(Clone)]
+pub struct Operator {
+    processor: DeploymentProcessor,
+    client: Option<Client>,
+    interval: Duration,
+}
+
+impl Operator {
+    pub fn builder() -> OperatorBuilder {
+        OperatorBuilder::default()
+    }
+
+    /// How often [`Operator::run`] reconciles.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Reconcile `deployments` once, concurrently. Deployments without the
+    /// required annotations are left out; disabled ones, and those another
+    /// pass is already working on, are reported as skipped.
+    pub async fn reconcile_once(&self, deployments: &[Deployment]) -> Vec<ReconcileResult> {
+        let entries = deployments.iter().filter_map(Entry::new).collect();
+        self.reconcile_entries(entries).await
+    }
+
+    /// Reconcile already parsed `entries` once, concurrently.
+    pub async fn reconcile_entries(&self, entries: Vec<Entry>) -> Vec<ReconcileResult> {
+        Entry::reconcile_entries_with(entries, &self.processor).await
+    }
+
+    /// Watch the Deployments of every namespace and reconcile them every
+    /// interval, starting once the watch has listed them. Runs until the
+    /// watch can't be started or the returned future is dropped.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let client = match &self.client {
+            Some(client) => client.clone(),
+            None => Client::try_default().await?,
+        };
+        let api: Api<Deployment> = Api::all(client);
+
+        let (reader, writer) = reflector::store();
+        let watch = reflector(writer, watcher(api, Default::default()))
+            .default_backoff()
+            .touched_objects()
+            .for_each(|r| {
+                if let Err(e) = r {
+                    warn!("watcher error: {e}");
+                }
+                future::ready(())
+            });
+        let watch = tokio::spawn(watch);
+        // Stop watching when the caller drops this future.
+        let _watch = AbortOnDrop(watch);
+
+        reader.wait_until_ready().await?;
+        let mut ticks = tokio::time::interval(self.interval);
+        loop {
+            ticks.tick().await;
+            let deployments: Vec<Deployment> = reader
+                .state()
+                .iter()
+                .map(|deployment| deployment.as_ref().clone())
+                .collect();
+            let results = self.reconcile_once(&deployments).await;
+            info!("Reconciled {} deployments", results.len());
+        }
+    }
+}
+
+struct AbortOnDrop(tokio::task::JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Builder for an [`Operator`], replacing the providers the operator binary
+/// uses one by one.
+#[derive(Default)]
+pub struct OperatorBuilder {
+    secret_provider: Option<Arc<dyn SecretProvider>>,
+    image_checker_factory: Option<Arc<dyn ImageCheckerFactory>>,
+    notification_sender: Option<Arc<dyn NotificationSender>>,
+    cluster_reporter: Option<Arc<dyn ClusterReporter>>,
+    pull_request_opener: Option<Arc<dyn PullRequestOpener>>,
+    maintenance_windows: Option<Arc<dyn MaintenanceWindowSource>>,
+    policies: Option<Arc<dyn PolicySource>>,
+    git_provider: Option<Arc<dyn GitProvider>>,
+    client: Option<Client>,
+    interval: Option<Duration>,
+    force: bool,
+}
+
+impl OperatorBuilder {
+    /// Where SSH keys, webhook URLs and tokens are read (Kubernetes Secrets
+    /// by default).
+    pub fn secret_provider(mut self, provider: Arc<dyn SecretProvider>) -> Self {
+        self.secret_provider = Some(provider);
+        self
+    }
+
+    /// What checks registries for images (the registry's v2 API by default).
+    pub fn image_checker_factory(mut self, factory: Arc<dyn ImageCheckerFactory>) -> Self {
+        self.image_checker_factory = Some(factory);
+        self
+    }
+
+    /// What delivers notifications (Slack-compatible webhooks by default).
+    pub fn notification_sender(mut self, sender: Arc<dyn NotificationSender>) -> Self {
+        self.notification_sender = Some(sender);
+        self
+    }
+
+    /// What records events and reads the live state of Deployments (the
+    /// Kubernetes API by default).
+    pub fn cluster_reporter(mut self, reporter: Arc<dyn ClusterReporter>) -> Self {
+        self.cluster_reporter = Some(reporter);
+        self
+    }
+
+    /// What opens pull requests for `strategy: pull_request` (the forge
+    /// hosting the manifests by default).
+    pub fn pull_request_opener(mut self, opener: Arc<dyn PullRequestOpener>) -> Self {
+        self.pull_request_opener = Some(opener);
+        self
+    }
+
+    /// Where maintenance windows are declared (the cluster's by default).
+    pub fn maintenance_windows(mut self, source: Arc<dyn MaintenanceWindowSource>) -> Self {
+        self.maintenance_windows = Some(source);
+        self
+    }
+
+    /// Where promotion policies are declared (the cluster's by default).
+    pub fn policies(mut self, source: Arc<dyn PolicySource>) -> Self {
+        self.policies = Some(source);
+        self
+    }
+
+    /// What clones, fetches, commits and pushes (the `GITOPS_GIT_BACKEND`
+    /// one, libgit2 when unset, by default).
+    pub fn git_provider(mut self, provider: Arc<dyn GitProvider>) -> Self {
+        self.git_provider = Some(provider);
+        self
+    }
+
+    /// The cluster [`Operator::run`] watches (the inferred one by default).
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// How often [`Operator::run`] reconciles (default 5 minutes).
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Reconcile deployments that haven't changed since their last pass too.
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Build the operator on top of [`DeploymentProcessor::production`]; see
+    /// [`Operator`] for the state every operator in the process shares.
+    pub fn build(self) -> Operator {
+        let mut processor = DeploymentProcessor::production().with_force(self.force);
+        if let Some(provider) = self.secret_provider {
+            processor = processor.with_secret_provider(provider);
+        }
+        if let Some(factory) = self.image_checker_factory {
+            processor = processor.with_image_checker_factory(factory);
+        }
+        if let Some(sender) = self.notification_sender {
+            processor = processor.with_notification_sender(sender);
+        }
+        if let Some(reporter) = self.cluster_reporter {
+            processor = processor.with_cluster_reporter(reporter);
+        }
+        if let Some(opener) = self.pull_request_opener {
+            processor = processor.with_pull_request_opener(opener);
+        }
+        if let Some(source) = self.maintenance_windows {
+            processor = processor.with_maintenance_windows(source);
+        }
+        if let Some(source) = self.policies {
+            processor = processor.with_policies(source);
+        }
+        if let Some(provider) = self.git_provider {
+            processor = processor.with_git_provider(provider);
+        }
+
+        Operator {
+            processor,
+            client: self.client,
+            interval: self.interval.unwrap_or(DEFAULT_RECONCILE_INTERVAL),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use crate::history::now_rfc3339;
+use crate::state::{MemoryState, state_store};
+use crate::traits::StateStore;
+use anyhow::{Context, Result};
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::warn;
+
+/// Annotation pausing a Deployment: `"true"` keeps it tracked (and listed)
+/// but skips every reconcile until it is removed or `POST
+/// /resume/{namespace}/{name}` is called.
+pub const PAUSED_ANNOTATION: &str = "gitops.operator.paused";
+
+const PAUSES_BUCKET: &str = "pauses";
+
+/// How a deployment was paused.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseSource {
+    /// `gitops.operator.paused: "true"` on the Deployment.
+    Annotation,
+    /// `POST /pause/{namespace}/{name}`.
+    Api,
+}
+
+/// Why and since when a deployment is paused.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct Pause {
+    pub source: PauseSource,
+    /// When it was paused through the API (RFC 3339, UTC); unknown for
+    /// annotations.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub since: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub by: Option<String>,
+}
+
+impl Pause {
+    pub fn annotation() -> Self {
+        Self {
+            source: PauseSource::Annotation,
+            since: None,
+            by: None,
+        }
+    }
+}
+
+/// Deployments (`namespace/name`) paused through the API, kept in a
+/// [`StateStore`].
+pub struct Pauses {
+    store: Arc<dyn StateStore>,
+    // Serializes pausing against resuming.
+    updates: Mutex<()>,
+}
+
+impl Default for Pauses {
+    fn default() -> Self {
+        Self::with_store(Arc::new(MemoryState::new()))
+    }
+}
+
+impl Pauses {
+    /// Pauses kept in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pauses kept in `store`.
+    pub fn with_store(store: Arc<dyn StateStore>) -> Self {
+        Self {
+            store,
+            updates: Mutex::new(()),
+        }
+    }
+
+    /// Pause `key` on behalf of `by`. Returns the pause in effect and whether
+    /// it is new; pausing again keeps the original.
+    pub fn pause(&self, key: &str, by: Option<String>) -> Result<(Pause, bool)> {
+        let _guard = self.updates.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pause) = self.read(key)? {
+            return Ok((pause, false));
+        }
+        let pause = Pause {
+            source: PauseSource::Api,
+            since: Some(now_rfc3339()),
+            by,
+        };
+        self.store
+            .put(PAUSES_BUCKET, key, &serde_json::to_string(&pause)?)?;
+        Ok((pause, true))
+    }
+
+    /// Lift the pause of `key`, returning it if there was one.
+    pub fn resume(&self, key: &str) -> Result<Option<Pause>> {
+        let _guard = self.updates.lock().unwrap_or_else(|e| e.into_inner());
+        let pause = self.read(key)?;
+        if pause.is_some() {
+            self.store.remove(PAUSES_BUCKET, key)?;
+        }
+        Ok(pause)
+    }
+
+    /// The API pause of `key`, if paused. Unreadable state is logged and
+    /// counts as not paused.
+    pub fn get(&self, key: &str) -> Option<Pause> {
+        self.read(key).unwrap_or_else(|e| {
+            warn!("Failed to read the pause of {}: {:#}", key, e);
+            None
+        })
+    }
+
+    fn read(&self, key: &str) -> Result<Option<Pause>> {
+        self.store
+            .get(PAUSES_BUCKET, key)?
+            .map(|value| serde_json::from_str(&value).context("Failed to read pause"))
+            .transpose()
+    }
+}
+
+/// Process-wide pauses shared by every reconcile trigger and the API, kept in
+/// the operator's [`state_store`].
+pub fn pauses() -> Arc<Pauses> {
+    static PAUSES: OnceLock<Arc<Pauses>> = OnceLock::new();
+    PAUSES
+        .get_or_init(|| Arc::new(Pauses::with_store(state_store())))
+        .clone()
+}
@@ -0,0 +1,643 @@
+use crate::traits::PolicySource;
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use k8s_openapi::api::core::v1::ConfigMap;
+use k8s_openapi::jiff::Timestamp;
+use k8s_openapi::jiff::tz::TimeZone;
+use kube::api::{Api, ListParams};
+use kube::{Client, ResourceExt};
+use regex::Regex;
+use std::collections::BTreeMap;
+use tracing::warn;
+
+/// Label marking a ConfigMap as a promotion policy.
+pub const POLICY_LABEL: &str = "gitops.operator/policy";
+
+/// A value a policy expression works with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    String(String),
+    List(Vec<Value>),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::Int(_) => "int",
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Int(i64),
+    Str(String),
+    Ident(String),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 20] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "+", "-", "(", ")", "[", "]", ",", ".", "?",
+    ":", "%",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            tokens.push(Token::Int(
+                rest[..end].parse().context("Integer too large")?,
+            ));
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if c == '"' || c == '\'' {
+            let mut value = String::new();
+            let mut chars = rest[1..].char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((i, q)) if q == c => break i + 2,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, 'n')) => value.push('\n'),
+                        Some((_, 't')) => value.push('\t'),
+                        Some((_, escaped)) => value.push(escaped),
+                        None => bail!("Unterminated string in {:?}", source),
+                    },
+                    Some((_, ch)) => value.push(ch),
+                    None => bail!("Unterminated string in {:?}", source),
+                }
+            };
+            tokens.push(Token::Str(value));
+            rest = &rest[end..];
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            bail!("Unexpected character {:?} in {:?}", c, source);
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinaryOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+    Add,
+    Sub,
+    Rem,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Literal(Value),
+    Ident(String),
+    List(Vec<Expr>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    Call {
+        target: Option<Box<Expr>>,
+        function: String,
+        args: Vec<Expr>,
+    },
+}
+
+/// How deeply an expression may nest (operands of operators, parentheses,
+/// lists, calls and conditionals) before it is refused, so neither parsing
+/// nor evaluating it can exhaust the stack.
+const MAX_DEPTH: usize = 100;
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    /// Go one level deeper into the expression, failing past [`MAX_DEPTH`].
+    fn descend(&mut self) -> Result<()> {
+        if self.depth >= MAX_DEPTH {
+            bail!(
+                "Expression nested more than {} deep at token {}",
+                MAX_DEPTH,
+                self.pos + 1
+            );
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Op(o)) if *o == op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<()> {
+        if !self.eat(op) {
+            bail!("Expected '{}' at token {}", op, self.pos + 1);
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self) -> Result<Expr> {
+        let condition = self.or()?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+        let depth = self.depth;
+        self.descend()?;
+        let then = self.expression()?;
+        self.expect(":")?;
+        let otherwise = self.expression()?;
+        self.depth = depth;
+        Ok(Expr::Conditional(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let depth = self.depth;
+        let mut left = self.and()?;
+        while self.eat("||") {
+            self.descend()?;
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let depth = self.depth;
+        let mut left = self.relation()?;
+        while self.eat("&&") {
+            self.descend()?;
+            left = Expr::And(Box::new(left), Box::new(self.relation()?));
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn relation(&mut self) -> Result<Expr> {
+        let left = self.sum()?;
+        let op = match self.peek() {
+            Some(Token::Op("==")) => BinaryOp::Eq,
+            Some(Token::Op("!=")) => BinaryOp::Ne,
+            Some(Token::Op("<")) => BinaryOp::Lt,
+            Some(Token::Op("<=")) => BinaryOp::Le,
+            Some(Token::Op(">")) => BinaryOp::Gt,
+            Some(Token::Op(">=")) => BinaryOp::Ge,
+            Some(Token::Ident(word)) if word == "in" => BinaryOp::In,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        Ok(Expr::Binary(op, Box::new(left), Box::new(self.sum()?)))
+    }
+
+    fn sum(&mut self) -> Result<Expr> {
+        let depth = self.depth;
+        let mut left = self.product()?;
+        loop {
+            let op = if self.eat("+") {
+                BinaryOp::Add
+            } else if self.eat("-") {
+                BinaryOp::Sub
+            } else {
+                self.depth = depth;
+                return Ok(left);
+            };
+            self.descend()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr> {
+        let depth = self.depth;
+        let mut left = self.unary()?;
+        while self.eat("%") {
+            self.descend()?;
+            left = Expr::Binary(BinaryOp::Rem, Box::new(left), Box::new(self.unary()?));
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        let depth = self.depth;
+        self.descend()?;
+        let expr = if self.eat("!") {
+            Expr::Not(Box::new(self.unary()?))
+        } else if self.eat("-") {
+            Expr::Neg(Box::new(self.unary()?))
+        } else {
+            self.member()?
+        };
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn member(&mut self) -> Result<Expr> {
+        let depth = self.depth;
+        let mut target = self.primary()?;
+        while self.eat(".") {
+            self.descend()?;
+            let Some(Token::Ident(function)) = self.peek().cloned() else {
+                bail!("Expected a method name at token {}", self.pos + 1);
+            };
+            self.pos += 1;
+            self.expect("(")?;
+            let args = self.arguments(")")?;
+            target = Expr::Call {
+                target: Some(Box::new(target)),
+                function,
+                args,
+            };
+        }
+        self.depth = depth;
+        Ok(target)
+    }
+
+    fn arguments(&mut self, close: &str) -> Result<Vec<Expr>> {
+        let mut args = Vec::new();
+        if self.eat(close) {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expression()?);
+            if self.eat(close) {
+                return Ok(args);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        let Some(token) = self.peek().cloned() else {
+            bail!("Unexpected end of expression");
+        };
+        self.pos += 1;
+
+        match token {
+            Token::Int(n) => Ok(Expr::Literal(Value::Int(n))),
+            Token::Str(s) => Ok(Expr::Literal(Value::String(s))),
+            Token::Ident(word) => match word.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                _ if self.eat("(") => Ok(Expr::Call {
+                    target: None,
+                    function: word,
+                    args: self.arguments(")")?,
+                }),
+                _ => Ok(Expr::Ident(word)),
+            },
+            Token::Op("(") => {
+                let inner = self.expression()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Token::Op("[") => Ok(Expr::List(self.arguments("]")?)),
+            Token::Op(op) => bail!("Unexpected '{}' at token {}", op, self.pos),
+        }
+    }
+}
+
+fn evaluate(expr: &Expr, vars: &BTreeMap<String, Value>) -> Result<Value> {
+    let boolean = |expr: &Expr| -> Result<bool> {
+        match evaluate(expr, vars)? {
+            Value::Bool(b) => Ok(b),
+            other => bail!("Expected a bool, got {}", other.type_name()),
+        }
+    };
+
+    match expr {
+        Expr::Literal(value) => Ok(value.clone()),
+        Expr::Ident(name) => vars
+            .get(name)
+            .cloned()
+            .with_context(|| format!("Unknown variable '{}'", name)),
+        Expr::List(items) => Ok(Value::List(
+            items
+                .iter()
+                .map(|item| evaluate(item, vars))
+                .collect::<Result<_>>()?,
+        )),
+        Expr::Not(inner) => Ok(Value::Bool(!boolean(inner)?)),
+        Expr::Neg(inner) => match evaluate(inner, vars)? {
+            Value::Int(n) => Ok(Value::Int(n.checked_neg().context("Integer overflow")?)),
+            other => bail!("Cannot negate a {}", other.type_name()),
+        },
+        Expr::And(left, right) => Ok(Value::Bool(boolean(left)? && boolean(right)?)),
+        Expr::Or(left, right) => Ok(Value::Bool(boolean(left)? || boolean(right)?)),
+        Expr::Conditional(condition, then, otherwise) => {
+            if boolean(condition)? {
+                evaluate(then, vars)
+            } else {
+                evaluate(otherwise, vars)
+            }
+        }
+        Expr::Binary(op, left, right) => binary(*op, evaluate(left, vars)?, evaluate(right, vars)?),
+        Expr::Call {
+            target,
+            function,
+            args,
+        } => {
+            let target = target
+                .as_ref()
+                .map(|target| evaluate(target, vars))
+                .transpose()?;
+            let args = args
+                .iter()
+                .map(|arg| evaluate(arg, vars))
+                .collect::<Result<Vec<_>>>()?;
+            call(target, function, args)
+        }
+    }
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value) -> Result<Value> {
+    use Value::{Bool, Int, List, String};
+
+    let ordering = |left: &Value, right: &Value| match (left, right) {
+        (Int(a), Int(b)) => Ok(a.cmp(b)),
+        (String(a), String(b)) => Ok(a.cmp(b)),
+        _ => bail!(
+            "Cannot compare {} with {}",
+            left.type_name(),
+            right.type_name()
+        ),
+    };
+
+    Ok(match op {
+        BinaryOp::Eq => Bool(left == right),
+        BinaryOp::Ne => Bool(left != right),
+        BinaryOp::Lt => Bool(ordering(&left, &right)?.is_lt()),
+        BinaryOp::Le => Bool(ordering(&left, &right)?.is_le()),
+        BinaryOp::Gt => Bool(ordering(&left, &right)?.is_gt()),
+        BinaryOp::Ge => Bool(ordering(&left, &right)?.is_ge()),
+        BinaryOp::In => match right {
+            List(items) => Bool(items.contains(&left)),
+            other => bail!("'in' needs a list, got {}", other.type_name()),
+        },
+        BinaryOp::Add => match (left, right) {
+            (Int(a), Int(b)) => Int(a.checked_add(b).context("Integer overflow")?),
+            (String(a), String(b)) => String(a + &b),
+            (List(mut a), List(b)) => {
+                a.extend(b);
+                List(a)
+            }
+            (a, b) => bail!("Cannot add {} and {}", a.type_name(), b.type_name()),
+        },
+        BinaryOp::Sub => match (left, right) {
+            (Int(a), Int(b)) => Int(a.checked_sub(b).context("Integer overflow")?),
+            (a, b) => bail!("Cannot subtract {} from {}", b.type_name(), a.type_name()),
+        },
+        BinaryOp::Rem => match (left, right) {
+            (Int(a), Int(b)) => Int(a.checked_rem(b).context("Division by zero")?),
+            (a, b) => bail!("Cannot take {} modulo {}", a.type_name(), b.type_name()),
+        },
+    })
+}
+
+fn call(target: Option<Value>, function: &str, args: Vec<Value>) -> Result<Value> {
+    use Value::{Bool, Int, List, String};
+
+    // `size(x)` and `x.size()` are the same function in CEL.
+    let (receiver, args) = match target {
+        Some(target) => (target, args),
+        None => {
+            let mut args = args.into_iter();
+            let Some(first) = args.next() else {
+                bail!("{}() needs an argument", function);
+            };
+            (first, args.collect())
+        }
+    };
+
+    Ok(match (function, receiver, args.as_slice()) {
+        ("size", String(s), []) => Int(s.chars().count() as i64),
+        ("size", List(items), []) => Int(items.len() as i64),
+        ("startsWith", String(s), [String(prefix)]) => Bool(s.starts_with(prefix.as_str())),
+        ("endsWith", String(s), [String(suffix)]) => Bool(s.ends_with(suffix.as_str())),
+        ("contains", String(s), [String(part)]) => Bool(s.contains(part.as_str())),
+        ("matches", String(s), [String(pattern)]) => Bool(
+            Regex::new(pattern)
+                .with_context(|| format!("Invalid pattern {:?}", pattern))?
+                .is_match(&s),
+        ),
+        ("lowerAscii", String(s), []) => String(s.to_ascii_lowercase()),
+        ("int", Int(n), []) => Int(n),
+        ("int", String(s), []) => Int(s
+            .trim()
+            .parse()
+            .with_context(|| format!("Cannot convert {:?} to int", s))?),
+        ("string", String(s), []) => String(s),
+        ("string", Int(n), []) => String(n.to_string()),
+        ("string", Bool(b), []) => String(b.to_string()),
+        (function, receiver, args) => bail!(
+            "Unknown function {}({}{})",
+            function,
+            receiver.type_name(),
+            args.iter()
+                .map(|arg| format!(", {}", arg.type_name()))
+                .collect::<std::string::String>()
+        ),
+    })
+}
+
+/// A parsed policy expression: a subset of CEL (literals, lists, `!`, `-`,
+/// `+`, `%`, comparisons, `in`, `&&`, `||`, `?:`, and the `size`,
+/// `startsWith`, `endsWith`, `contains`, `matches`, `lowerAscii`, `int` and
+/// `string` functions).
+#[derive(Clone, Debug, PartialEq)]
+pub struct PolicyExpression {
+    source: String,
+    expr: Expr,
+}
+
+impl PolicyExpression {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+            depth: 0,
+        };
+        let expr = parser
+            .expression()
+            .with_context(|| format!("Invalid policy {:?}", source))?;
+        if parser.pos < parser.tokens.len() {
+            bail!(
+                "Invalid policy {:?}: unexpected input at token {}",
+                source,
+                parser.pos + 1
+            );
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            expr,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Evaluate the expression against `vars`; anything but a bool is an error.
+    pub fn allows(&self, vars: &BTreeMap<String, Value>) -> Result<bool> {
+        match evaluate(&self.expr, vars)
+            .with_context(|| format!("Failed to evaluate policy {:?}", self.source))?
+        {
+            Value::Bool(allowed) => Ok(allowed),
+            other => bail!(
+                "Policy {:?} returned a {}, not a bool",
+                self.source,
+                other.type_name()
+            ),
+        }
+    }
+}
+
+/// What a policy gets to decide on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PolicyInput {
+    pub namespace: String,
+    pub name: String,
+    pub branch: String,
+    /// Tag in the manifest, if it has one.
+    pub old_tag: Option<String>,
+    pub new_tag: String,
+    pub now: Timestamp,
+}
+
+impl PolicyInput {
+    /// The variables an expression sees: `namespace`, `name`, `branch`,
+    /// `old_tag` (empty when unknown), `new_tag`, and the UTC `hour`,
+    /// `minute` and `weekday` (0 is Sunday).
+    pub fn variables(&self) -> BTreeMap<String, Value> {
+        let now = self.now.to_zoned(TimeZone::UTC);
+        let string = |s: &str| Value::String(s.to_string());
+
+        BTreeMap::from([
+            ("namespace".to_string(), string(&self.namespace)),
+            ("name".to_string(), string(&self.name)),
+            ("branch".to_string(), string(&self.branch)),
+            (
+                "old_tag".to_string(),
+                string(self.old_tag.as_deref().unwrap_or_default()),
+            ),
+            ("new_tag".to_string(), string(&self.new_tag)),
+            ("hour".to_string(), Value::Int(now.hour().into())),
+            ("minute".to_string(), Value::Int(now.minute().into())),
+            (
+                "weekday".to_string(),
+                Value::Int(now.weekday().to_sunday_zero_offset().into()),
+            ),
+        ])
+    }
+}
+
+/// A policy every promotion it applies to must pass, declared by the
+/// `gitops.operator.policy` annotation or by a ConfigMap labelled
+/// [`POLICY_LABEL`]` = "true"`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Policy {
+    /// Where it was declared, e.g. `ConfigMap platform/business-hours`.
+    pub object: String,
+    pub expression: String,
+    /// Namespaces whose deployments it applies to; empty applies to all.
+    pub namespaces: Vec<String>,
+}
+
+impl Policy {
+    pub fn applies_to(&self, namespace: &str) -> bool {
+        self.namespaces.is_empty() || self.namespaces.iter().any(|ns| ns == namespace)
+    }
+
+    /// Whether the policy lets the promotion described by `input` through.
+    pub fn allows(&self, input: &PolicyInput) -> Result<bool> {
+        PolicyExpression::parse(&self.expression)?.allows(&input.variables())
+    }
+}
+
+/// Reads policies from ConfigMaps labelled [`POLICY_LABEL`]` = "true"`, with
+/// the expression in `expression` and an optional comma-separated
+/// `namespaces`.
+pub struct KubePolicies;
+
+#[async_trait]
+impl PolicySource for KubePolicies {
+    async fn policies(&self) -> Result<Vec<Policy>> {
+        let client = Client::try_default().await?;
+        let config_maps: Api<ConfigMap> = Api::all(client);
+        let params = ListParams::default().labels(&format!("{}=true", POLICY_LABEL));
+
+        Ok(config_maps
+            .list(&params)
+            .await?
+            .into_iter()
+            .filter_map(|cm| {
+                let object = format!(
+                    "ConfigMap {}/{}",
+                    cm.namespace().unwrap_or_default(),
+                    cm.name_any()
+                );
+                let data = cm.data.unwrap_or_default();
+                let Some(expression) = data.get("expression") else {
+                    warn!("Ignoring policy {} without an expression", object);
+                    return None;
+                };
+                Some(Policy {
+                    object,
+                    expression: expression.clone(),
+                    namespaces: data
+                        .get("namespaces")
+                        .map(String::as_str)
+                        .unwrap_or_default()
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|ns| !ns.is_empty())
+                        .map(String::from)
+                        .collect(),
+                })
+            })
+            .collect())
+    }
+}
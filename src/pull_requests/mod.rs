@@ -0,0 +1,3 @@
+#[allow(clippy::module_inception)]
+mod pull_requests;
+pub use pull_requests::*;
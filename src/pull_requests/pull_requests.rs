@@ -0,0 +1,245 @@
+use crate::bitbucket::{BitbucketCloudPullRequests, BitbucketServerPullRequests};
+use crate::gitea::GiteaPullRequests;
+use crate::github::GitHubPullRequests;
+use crate::gitlab::GitLabMergeRequests;
+use crate::traits::PullRequestOpener;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Title of the pull requests opened when `gitops.operator.pr_title` is not
+/// set.
+pub const DEFAULT_PR_TITLE: &str = "chore(refs): update {app} to {new_sha}";
+
+/// Body of the pull requests opened when `gitops.operator.pr_body` is not set.
+pub const DEFAULT_PR_BODY: &str = "Updates the image of `{app}` in `{namespace}` \
+     from `{old_sha}` to `{new_sha}`.\n\nOpened by gitops-operator; merge it to deploy.";
+
+/// How a patched manifest reaches its repository
+/// (`gitops.operator.strategy`).
+#[derive(serde::Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Commit and push to the observed branch.
+    #[default]
+    Push,
+    /// Commit to a branch of its own and open a pull request (a merge
+    /// request on GitLab) against the observed branch.
+    PullRequest,
+}
+
+impl FromStr for Strategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "push" => Ok(Strategy::Push),
+            "pull_request" => Ok(Strategy::PullRequest),
+            other => anyhow::bail!(
+                "Invalid strategy '{}'. Must be 'push' or 'pull_request'",
+                other
+            ),
+        }
+    }
+}
+
+/// The service hosting a manifest repository, whose API opens the pull
+/// requests (`gitops.operator.forge`, detected from the repository URL when
+/// unset).
+#[derive(serde::Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Forge {
+    #[default]
+    GitHub,
+    GitLab,
+    /// Gitea or Forgejo, which serve the same API.
+    Gitea,
+    /// Bitbucket Cloud (`bitbucket.org`).
+    Bitbucket,
+    /// Bitbucket Server or Data Center.
+    #[serde(rename = "bitbucket_server")]
+    BitbucketServer,
+}
+
+impl FromStr for Forge {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "github" => Ok(Forge::GitHub),
+            "gitlab" => Ok(Forge::GitLab),
+            "gitea" | "forgejo" => Ok(Forge::Gitea),
+            "bitbucket" => Ok(Forge::Bitbucket),
+            "bitbucket_server" => Ok(Forge::BitbucketServer),
+            other => anyhow::bail!(
+                "Invalid forge '{}'. Must be 'github', 'gitlab', 'gitea', 'forgejo', \
+                 'bitbucket' or 'bitbucket_server'",
+                other
+            ),
+        }
+    }
+}
+
+impl Forge {
+    /// The forge hosting `repository`, going by its host name: GitLab when it
+    /// mentions `gitlab`, Gitea when it mentions `gitea`, `forgejo` or
+    /// `codeberg`, Bitbucket Cloud for `bitbucket.org` and Bitbucket Server
+    /// when it otherwise mentions `bitbucket`, GitHub otherwise. Self-hosted
+    /// forges on other names are set with `gitops.operator.forge`.
+    pub fn detect(repository: &str) -> Self {
+        match split_repository_url(repository) {
+            Some((host, _)) if host.contains("gitlab") => Forge::GitLab,
+            Some((host, _)) if host == "bitbucket.org" => Forge::Bitbucket,
+            Some((host, _)) if host.contains("bitbucket") => Forge::BitbucketServer,
+            Some((host, _))
+                if ["gitea", "forgejo", "codeberg"]
+                    .iter()
+                    .any(|name| host.contains(name)) =>
+            {
+                Forge::Gitea
+            }
+            _ => Forge::GitHub,
+        }
+    }
+
+    /// What the forge calls a change request.
+    pub fn request_kind(&self) -> &'static str {
+        match self {
+            Forge::GitHub | Forge::Gitea | Forge::Bitbucket | Forge::BitbucketServer => {
+                "pull request"
+            }
+            Forge::GitLab => "merge request",
+        }
+    }
+
+    /// How the forge refers to change request `number`, e.g. `pull request #4`.
+    pub fn request_name(&self, number: u64) -> String {
+        match self {
+            Forge::GitLab => format!("{} !{}", self.request_kind(), number),
+            _ => format!("{} #{}", self.request_kind(), number),
+        }
+    }
+
+    /// Web page of commit `sha` in the repository cloned from `repository`,
+    /// assuming the forge serves its UI over HTTPS on the clone host.
+    pub fn commit_url(&self, repository: &str, sha: &str) -> Option<String> {
+        let (host, path) = split_repository_url(repository)?;
+        Some(match self {
+            Forge::GitHub | Forge::Gitea => format!("https://{}/{}/commit/{}", host, path, sha),
+            Forge::GitLab => format!("https://{}/{}/-/commit/{}", host, path, sha),
+            Forge::Bitbucket => format!("https://{}/{}/commits/{}", host, path, sha),
+            Forge::BitbucketServer => {
+                let path = path.strip_prefix("scm/").unwrap_or(&path);
+                let (project, repo) = path.split_once('/')?;
+                format!(
+                    "https://{}/projects/{}/repos/{}/commits/{}",
+                    host, project, repo, sha
+                )
+            }
+        })
+    }
+}
+
+/// The host and the path (without `.git`) of a clone URL, in the scp-like
+/// (`git@host:group/repo.git`), `ssh://` or `http(s)://` forms. The host of
+/// an http(s) URL keeps its port, since the forge's API is served there too.
+pub fn split_repository_url(url: &str) -> Option<(String, String)> {
+    let url = url.trim();
+    let (host, path) = if let Some(rest) = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    {
+        let (authority, path) = rest.split_once('/')?;
+        let host = authority.rsplit('@').next().unwrap_or(authority);
+        (host.to_string(), path)
+    } else if let Some(rest) = url.strip_prefix("ssh://") {
+        let (authority, path) = rest.split_once('/')?;
+        let host = authority.rsplit('@').next().unwrap_or(authority);
+        let host = host.split(':').next().unwrap_or(host);
+        (host.to_string(), path)
+    } else if !url.contains("://") {
+        let (authority, path) = url.split_once(':')?;
+        let host = authority.rsplit('@').next().unwrap_or(authority);
+        (host.to_string(), path)
+    } else {
+        return None;
+    };
+
+    let path = path.trim_matches('/').trim_end_matches(".git");
+    if host.is_empty() || !path.contains('/') {
+        return None;
+    }
+    Some((host, path.to_string()))
+}
+
+/// A change to propose: merge the branch `head` into `base` of the
+/// repository at `repository` (its clone URL), hosted on `forge`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangeRequest {
+    pub forge: Forge,
+    pub repository: String,
+    pub head: String,
+    pub base: String,
+    pub title: String,
+    pub body: String,
+    /// Labels to put on the request (`gitops.operator.pr_labels`).
+    pub labels: Vec<String>,
+    /// Usernames to assign it to (`gitops.operator.pr_assignees`).
+    pub assignees: Vec<String>,
+}
+
+/// A pull request opened for a [`ChangeRequest`].
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct PullRequest {
+    pub number: u64,
+    /// Where people review it.
+    pub url: String,
+}
+
+/// The branch proposing `tag` for the deployment `namespace/name`. The same
+/// promotion always gets the same branch, so later passes update the pull
+/// request they already opened instead of opening another.
+pub fn pull_request_branch(namespace: &str, name: &str, tag: &str) -> String {
+    format!("gitops-operator/{}/{}-{}", namespace, name, tag)
+}
+
+/// Opens each change on the forge it names.
+pub struct Forges {
+    github: Arc<dyn PullRequestOpener>,
+    gitlab: Arc<dyn PullRequestOpener>,
+    gitea: Arc<dyn PullRequestOpener>,
+    bitbucket: Arc<dyn PullRequestOpener>,
+    bitbucket_server: Arc<dyn PullRequestOpener>,
+}
+
+impl Default for Forges {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Forges {
+    pub fn new() -> Self {
+        Self {
+            github: Arc::new(GitHubPullRequests::new()),
+            gitlab: Arc::new(GitLabMergeRequests::new()),
+            gitea: Arc::new(GiteaPullRequests::new()),
+            bitbucket: Arc::new(BitbucketCloudPullRequests::new()),
+            bitbucket_server: Arc::new(BitbucketServerPullRequests::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl PullRequestOpener for Forges {
+    async fn open_pull_request(&self, change: &ChangeRequest, token: &str) -> Result<PullRequest> {
+        match change.forge {
+            Forge::GitHub => self.github.open_pull_request(change, token).await,
+            Forge::GitLab => self.gitlab.open_pull_request(change, token).await,
+            Forge::Gitea => self.gitea.open_pull_request(change, token).await,
+            Forge::Bitbucket => self.bitbucket.open_pull_request(change, token).await,
+            Forge::BitbucketServer => self.bitbucket_server.open_pull_request(change, token).await,
+        }
+    }
+}
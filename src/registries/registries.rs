@@ -0,0 +1,294 @@
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
+use tracing::{info, warn};
+
+pub const DEFAULT_REGISTRY_URL: &str = "https://index.docker.io/v1/";
+pub const DEFAULT_REGISTRY_SECRET_NAME: &str = "regcred";
+pub const DEFAULT_REGISTRY_SECRET_NAMESPACE: &str = "gitops-operator";
+
+/// How the operator authenticates against a registry.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistryAuth {
+    /// Credentials from a `kubernetes.io/dockerconfigjson` secret.
+    #[default]
+    Dockerconfig,
+    /// No credentials; for public registries.
+    Anonymous,
+    /// Tokens from ECR for the operator's AWS credentials, refreshed before
+    /// they expire.
+    Ecr,
+}
+
+/// Registry settings for the images whose reference starts with `prefix`.
+/// Unset fields fall back to the operator defaults.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct RegistryConfig {
+    /// Start of the image reference, registry host included; Docker Hub images
+    /// are matched as `docker.io/<user>/<name>` (`docker.io/library/<name>`
+    /// for official images).
+    pub prefix: String,
+    /// Registry API URL; defaults to `https://` plus the prefix's host.
+    pub url: Option<String>,
+    pub secret_name: Option<String>,
+    pub secret_namespace: Option<String>,
+    #[serde(default)]
+    pub auth: RegistryAuth,
+}
+
+impl RegistryConfig {
+    /// `url`, or the registry host of `prefix` (Docker Hub's index for
+    /// `docker.io`).
+    pub fn registry_url(&self) -> String {
+        if let Some(url) = &self.url {
+            return url.clone();
+        }
+        match qualified_image(&self.prefix).split('/').next() {
+            Some("docker.io") | None => DEFAULT_REGISTRY_URL.to_string(),
+            Some(host) => format!("https://{}", host),
+        }
+    }
+}
+
+/// `image` with its registry host, expanding Docker Hub references the way
+/// Docker does (`nginx` is `docker.io/library/nginx`).
+pub fn qualified_image(image: &str) -> String {
+    match image.split_once('/') {
+        Some((host, _)) if host.contains('.') || host.contains(':') || host == "localhost" => {
+            image.to_string()
+        }
+        Some(_) => format!("docker.io/{}", image),
+        None => format!("docker.io/library/{}", image),
+    }
+}
+
+/// The operator-level registry map: which registry, secret and auth mode
+/// serve each image prefix.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RegistryMap {
+    registries: Vec<RegistryConfig>,
+}
+
+impl RegistryMap {
+    pub fn new(registries: Vec<RegistryConfig>) -> Self {
+        Self { registries }
+    }
+
+    /// Parse a YAML list of [`RegistryConfig`]s.
+    pub fn parse(yaml: &str) -> Result<Self> {
+        let registries: Vec<RegistryConfig> =
+            serde_yaml::from_str(yaml).context("Invalid registry map")?;
+        if let Some(registry) = registries.iter().find(|r| r.prefix.trim().is_empty()) {
+            bail!("Invalid registry map: empty prefix for {:?}", registry.url);
+        }
+        Ok(Self::new(registries))
+    }
+
+    /// The entry with the longest prefix matching `image`.
+    pub fn lookup(&self, image: &str) -> Option<&RegistryConfig> {
+        let image = qualified_image(image);
+        self.registries
+            .iter()
+            .filter(|registry| image.starts_with(&registry.prefix))
+            .max_by_key(|registry| registry.prefix.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registries.is_empty()
+    }
+}
+
+/// Where and how to check an image: the deployment's registry annotations
+/// first, then its entry in the registry map, then the operator defaults.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedRegistry {
+    pub url: String,
+    pub auth: RegistryAuth,
+    /// Name and namespace of the docker-registry secret with
+    /// [`RegistryAuth::Dockerconfig`].
+    pub secret: Option<(String, String)>,
+}
+
+impl ResolvedRegistry {
+    pub fn resolve(
+        map: &RegistryMap,
+        image: &str,
+        url: Option<&str>,
+        secret_name: Option<&str>,
+        secret_namespace: Option<&str>,
+    ) -> Self {
+        let mapped = map.lookup(image);
+        let url = url
+            .map(String::from)
+            .or(mapped.map(RegistryConfig::registry_url))
+            .unwrap_or_else(|| DEFAULT_REGISTRY_URL.to_string());
+
+        // A secret named on the deployment means it authenticates with it,
+        // whatever the map says.
+        let auth = match secret_name {
+            Some(_) => RegistryAuth::Dockerconfig,
+            None => mapped.map(|r| r.auth).unwrap_or_default(),
+        };
+        let secret = (auth == RegistryAuth::Dockerconfig).then(|| {
+            (
+                secret_name
+                    .or(mapped.and_then(|r| r.secret_name.as_deref()))
+                    .unwrap_or(DEFAULT_REGISTRY_SECRET_NAME)
+                    .to_string(),
+                secret_namespace
+                    .or(mapped.and_then(|r| r.secret_namespace.as_deref()))
+                    .unwrap_or(DEFAULT_REGISTRY_SECRET_NAMESPACE)
+                    .to_string(),
+            )
+        });
+
+        Self { url, auth, secret }
+    }
+}
+
+/// The registry map in the file at `GITOPS_REGISTRIES_PATH`, if set. A missing
+/// or invalid file is logged and yields an empty map.
+pub fn registry_map_from_env() -> RegistryMap {
+    let Ok(path) = std::env::var("GITOPS_REGISTRIES_PATH") else {
+        return RegistryMap::default();
+    };
+    match std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path))
+        .and_then(|yaml| RegistryMap::parse(&yaml))
+    {
+        Ok(map) => map,
+        Err(e) => {
+            warn!("{:#}; registries come from annotations only", e);
+            RegistryMap::default()
+        }
+    }
+}
+
+/// Process-wide registry map, loaded on first use.
+pub fn registry_map() -> Arc<RegistryMap> {
+    static MAP: OnceLock<Arc<RegistryMap>> = OnceLock::new();
+    MAP.get_or_init(|| {
+        let map = registry_map_from_env();
+        if !map.is_empty() {
+            info!("Loaded {} registry map entries", map.registries.len());
+        }
+        Arc::new(map)
+    })
+    .clone()
+}
+
+/// A mirror or pull-through cache answering for a registry: its URL, and the
+/// path its copies of the registry's repositories live under, if any
+/// (`dockerhub` for `https://harbor.internal/dockerhub`, as Harbor proxy
+/// projects name them).
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegistryMirror {
+    pub url: String,
+    pub prefix: Option<String>,
+}
+
+impl RegistryMirror {
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        let Some((scheme, rest)) = value.split_once("://") else {
+            bail!("Invalid mirror {:?}: expected an http(s) URL", value);
+        };
+        if scheme != "http" && scheme != "https" {
+            bail!("Invalid mirror {:?}: expected an http(s) URL", value);
+        }
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        if authority.is_empty() {
+            bail!("Invalid mirror {:?}: no host", value);
+        }
+        let prefix = path.trim_matches('/');
+        Ok(Self {
+            url: format!("{}://{}", scheme, authority),
+            prefix: (!prefix.is_empty()).then(|| prefix.to_string()),
+        })
+    }
+
+    /// `image` (without its registry host) as the mirror names it.
+    pub fn repository(&self, image: &str) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}/{}", prefix, image),
+            None => image.to_string(),
+        }
+    }
+}
+
+/// The registry host of `url`, Docker Hub's API and index hosts all being
+/// `docker.io`.
+fn registry_host(url: &str) -> String {
+    let host = url
+        .trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    match host.as_str() {
+        "index.docker.io" | "registry-1.docker.io" | "registry.hub.docker.com" => {
+            "docker.io".to_string()
+        }
+        _ => host,
+    }
+}
+
+/// Which mirror image checks go to instead of each registry, so clusters
+/// that can only reach internal mirrors still see the images they serve.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RegistryMirrors {
+    mirrors: BTreeMap<String, RegistryMirror>,
+}
+
+impl RegistryMirrors {
+    /// Parse a comma-separated list of `registry=mirror` pairs, e.g.
+    /// `docker.io=https://harbor.internal/dockerhub,ghcr.io=https://ghcr.internal`.
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut mirrors = BTreeMap::new();
+        for pair in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let Some((registry, mirror)) = pair.split_once('=') else {
+                bail!("Invalid registry mirror {:?}: expected registry=url", pair);
+            };
+            let registry = registry_host(registry);
+            if registry.is_empty() {
+                bail!("Invalid registry mirror {:?}: no registry", pair);
+            }
+            mirrors.insert(registry, RegistryMirror::parse(mirror)?);
+        }
+        Ok(Self { mirrors })
+    }
+
+    /// The mirror of the registry at `registry_url`.
+    pub fn lookup(&self, registry_url: &str) -> Option<&RegistryMirror> {
+        self.mirrors.get(&registry_host(registry_url))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mirrors.is_empty()
+    }
+}
+
+/// Process-wide registry mirrors, from `GITOPS_REGISTRY_MIRRORS`. An invalid
+/// value is logged and no mirror is used.
+pub fn registry_mirrors() -> Arc<RegistryMirrors> {
+    static MIRRORS: OnceLock<Arc<RegistryMirrors>> = OnceLock::new();
+    MIRRORS
+        .get_or_init(|| {
+            let value = std::env::var("GITOPS_REGISTRY_MIRRORS").unwrap_or_default();
+            let mirrors = RegistryMirrors::parse(&value).unwrap_or_else(|e| {
+                warn!("{:#}; checking images on the registries themselves", e);
+                RegistryMirrors::default()
+            });
+            if !mirrors.is_empty() {
+                info!(
+                    "Checking images through {} registry mirrors",
+                    mirrors.mirrors.len()
+                );
+            }
+            Arc::new(mirrors)
+        })
+        .clone()
+}
@@ -1,21 +1,161 @@
+use crate::codecommit::{aws_credentials, sign_request};
+use crate::egress::{EgressKind, egress_policy};
+use crate::http_client::{client_builder, http_client};
+use crate::metrics::REGISTRY_RATE_LIMIT_REMAINING;
+use crate::registries::{RegistryMirror, registry_mirrors};
+use crate::retry::{retry_after, with_retries};
 use crate::traits::{ImageChecker, ImageCheckerFactory};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use futures::{StreamExt, stream};
 use k8s_openapi::api::core::v1::Secret;
 use kube::{Client as K8sClient, api::Api};
 use reqwest::{
-    Client,
-    header::{ACCEPT, AUTHORIZATION, HeaderMap, HeaderValue, WWW_AUTHENTICATE},
+    Method,
+    header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue, LINK, WWW_AUTHENTICATE},
 };
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
 use serde::Deserialize;
 use serde_json::Value;
-use tracing::{error, info};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{error, info, warn};
+
+/// Lifetime assumed for bearer tokens whose response omits `expires_in`, per
+/// the Docker token spec.
+const DEFAULT_TOKEN_TTL_SECS: u64 = 60;
+
+/// Cached tokens are dropped this long before they expire, so a token is never
+/// handed out just as the registry stops accepting it.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(10);
+
+/// How long a registry is left alone after a 429 without `Retry-After`.
+const DEFAULT_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(60);
+
+/// ECR tokens are exchanged for new ones this long before they expire.
+const ECR_REFRESH_MARGIN: Duration = Duration::from_secs(30 * 60);
+
+/// How long ECR tokens last when the response doesn't say.
+const ECR_TOKEN_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// The ECR API action handing out registry tokens.
+const ECR_GET_AUTHORIZATION_TOKEN: &str =
+    "AmazonEC2ContainerRegistry_V20150921.GetAuthorizationToken";
+
+/// Manifest media types asked for when resolving a tag's digest, so the
+/// registry answers for the index (or manifest) a container runtime pulls.
+const MANIFEST_MEDIA_TYPES: &str = "application/vnd.oci.image.index.v1+json, \
+     application/vnd.docker.distribution.manifest.list.v2+json, \
+     application/vnd.oci.image.manifest.v1+json, \
+     application/vnd.docker.distribution.manifest.v2+json";
+
+/// Maximum number of manifest HEAD requests in flight for one batch, from
+/// `GITOPS_REGISTRY_CONCURRENCY` (default 8).
+pub fn registry_concurrency() -> usize {
+    env::var("GITOPS_REGISTRY_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(8)
+}
+
+/// How many of an image's most recent tags are inspected for the one built
+/// from a commit (`gitops.operator.tag_source: revision_label`), from
+/// `GITOPS_REVISION_LOOKUP_LIMIT` (default 20).
+pub fn revision_lookup_limit() -> usize {
+    env::var("GITOPS_REVISION_LOOKUP_LIMIT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(20)
+}
 
 #[derive(Debug, Deserialize)]
 struct TokenResponse {
     token: String,
     access_token: Option<String>,
+    expires_in: Option<u64>,
+}
+
+/// (registry URL, credentials, scope) a bearer token was issued for.
+type TokenKey = (String, String, String);
+
+/// Bearer tokens by (registry URL, credentials, scope), shared by every
+/// checker in the process so deployments on the same registry reuse them.
+#[derive(Default)]
+struct TokenCache {
+    tokens: Mutex<HashMap<TokenKey, (String, Instant)>>,
+}
+
+impl TokenCache {
+    fn get(&self, key: &TokenKey) -> Option<String> {
+        let tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        tokens
+            .get(key)
+            .filter(|(_, expires)| Instant::now() < *expires)
+            .map(|(token, _)| token.clone())
+    }
+
+    fn insert(&self, key: TokenKey, token: String, ttl: Duration) {
+        let mut tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        tokens.retain(|_, (_, expires)| now < *expires);
+        tokens.insert(key, (token, now + ttl.saturating_sub(TOKEN_EXPIRY_MARGIN)));
+    }
+
+    fn remove(&self, key: &TokenKey) {
+        let mut tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        tokens.remove(key);
+    }
+}
+
+fn token_cache() -> &'static TokenCache {
+    static CACHE: OnceLock<TokenCache> = OnceLock::new();
+    CACHE.get_or_init(TokenCache::default)
+}
+
+/// A registry answered 429 Too Many Requests: it isn't asked again until
+/// `retry_after` has passed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimited {
+    pub registry: String,
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} rate limit reached, checks resume in {}s",
+            self.registry,
+            self.retry_after.as_secs()
+        )
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/// When each rate-limited registry may be asked again, shared by every
+/// checker in the process.
+fn rate_limits() -> &'static Mutex<HashMap<String, Instant>> {
+    static LIMITS: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+    LIMITS.get_or_init(Mutex::default)
+}
+
+/// The requests left in a registry's rate-limit window, from Docker Hub's
+/// `ratelimit-remaining` header (`76;w=21600`).
+pub fn rate_limit_remaining(headers: &HeaderMap) -> Option<u64> {
+    let value = headers.get("ratelimit-remaining")?.to_str().ok()?;
+    value.split(';').next()?.trim().parse().ok()
+}
+
+/// Pull scope for a repository, as requested from the token endpoint.
+fn pull_scope(image: &str) -> String {
+    format!("repository:{}:pull", image)
 }
 
 #[derive(Debug)]
@@ -59,11 +199,13 @@ impl AuthChallenge {
 
 #[derive(Debug)]
 pub struct RegistryChecker {
-    pub client: Client,
+    pub client: ClientWithMiddleware,
     pub registry_url: String,
     pub auth_token: Option<String>,
     pub username: Option<String>,
     pub password: Option<String>,
+    /// Where requests go instead of `registry_url`.
+    pub mirror: Option<RegistryMirror>,
 }
 
 impl RegistryChecker {
@@ -72,10 +214,12 @@ impl RegistryChecker {
         headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
 
         info!("Creating HTTP client for registry checks");
-        let client = Client::builder()
-            .default_headers(headers)
-            .build()
-            .context("Failed to create HTTP client")?;
+        let client = with_retries(
+            client_builder()
+                .default_headers(headers)
+                .build()
+                .context("Failed to create HTTP client")?,
+        );
 
         // If we have a Basic auth token, extract username and password
         let (username, password) = if let Some(token) = &auth_token {
@@ -106,21 +250,113 @@ impl RegistryChecker {
             auth_token,
             username,
             password,
+            mirror: None,
         })
     }
 
+    /// Send every request to `mirror` instead of the registry.
+    pub fn with_mirror(mut self, mirror: Option<RegistryMirror>) -> Self {
+        self.mirror = mirror;
+        self
+    }
+
+    /// The URL requests go to: the mirror's, else the registry's.
+    fn base_url(&self) -> &str {
+        match &self.mirror {
+            Some(mirror) => &mirror.url,
+            None => &self.registry_url,
+        }
+    }
+
+    /// `image` as named where requests go.
+    fn repository(&self, image: &str) -> String {
+        match &self.mirror {
+            Some(mirror) => mirror.repository(image),
+            None => image.to_string(),
+        }
+    }
+
+    /// The registry's host (and port), which rate limits are tracked by.
+    fn host(&self) -> String {
+        reqwest::Url::parse(self.base_url())
+            .ok()
+            .and_then(|url| {
+                let host = url.host_str()?.to_string();
+                Some(match url.port() {
+                    Some(port) => format!("{}:{}", host, port),
+                    None => host,
+                })
+            })
+            .unwrap_or_else(|| self.base_url().to_string())
+    }
+
+    /// Send `request` unless the registry is still rate limiting the
+    /// operator. Records the quota it reports, and a 429 (once the retries
+    /// are spent) as [`RateLimited`] until its `Retry-After`.
+    async fn send(&self, request: RequestBuilder) -> Result<reqwest::Response> {
+        let host = self.host();
+        {
+            let mut limits = rate_limits().lock().unwrap_or_else(|e| e.into_inner());
+            match limits.get(&host) {
+                Some(until) if Instant::now() < *until => {
+                    return Err(RateLimited {
+                        registry: host,
+                        retry_after: until.saturating_duration_since(Instant::now()),
+                    }
+                    .into());
+                }
+                Some(_) => {
+                    limits.remove(&host);
+                }
+                None => {}
+            }
+        }
+
+        let response = request.send().await?;
+        if let Some(remaining) = rate_limit_remaining(response.headers()) {
+            ::metrics::gauge!(REGISTRY_RATE_LIMIT_REMAINING, "registry" => host.clone())
+                .set(remaining as f64);
+        }
+        if response.status().as_u16() == 429 {
+            let wait = retry_after(response.headers()).unwrap_or(DEFAULT_RATE_LIMIT_BACKOFF);
+            warn!("{} is rate limiting the operator for {:?}", host, wait);
+            let mut limits = rate_limits().lock().unwrap_or_else(|e| e.into_inner());
+            limits.insert(host.clone(), Instant::now() + wait);
+            return Err(RateLimited {
+                registry: host,
+                retry_after: wait,
+            }
+            .into());
+        }
+        Ok(response)
+    }
+
     pub async fn get_bearer_token(&self, challenge: &AuthChallenge) -> Result<String> {
-        let mut request = self
-            .client
-            .get(&challenge.realm)
-            .query(&[("service", &challenge.service), ("scope", &challenge.scope)]);
+        self.get_bearer_token_for_scopes(challenge, std::slice::from_ref(&challenge.scope))
+            .await
+    }
+
+    /// Request a single bearer token covering every scope in `scopes` (the
+    /// token endpoint accepts repeated `scope` parameters) and cache it under
+    /// each of them.
+    pub async fn get_bearer_token_for_scopes(
+        &self,
+        challenge: &AuthChallenge,
+        scopes: &[String],
+    ) -> Result<String> {
+        // The realm comes from the registry's response: don't hand the
+        // credentials to a host the egress policy doesn't allow.
+        egress_policy().check(EgressKind::Registry, &challenge.realm)?;
+        let mut query = vec![("service", challenge.service.as_str())];
+        query.extend(scopes.iter().map(|scope| ("scope", scope.as_str())));
+        let mut request = self.client.get(&challenge.realm).query(&query);
 
         // Add basic auth if credentials are available
         if let (Some(username), Some(password)) = (&self.username, &self.password) {
             request = request.basic_auth(username, Some(password));
         }
 
-        let response = request.send().await?;
+        let response = self.send(request).await?;
 
         if !response.status().is_success() {
             error!("Failed to get bearer token: {}", response.status());
@@ -128,29 +364,314 @@ impl RegistryChecker {
         }
 
         let token_response: TokenResponse = response.json().await?;
-        Ok(token_response.access_token.unwrap_or(token_response.token))
+        let ttl = Duration::from_secs(token_response.expires_in.unwrap_or(DEFAULT_TOKEN_TTL_SECS));
+        let token = token_response.access_token.unwrap_or(token_response.token);
+
+        for scope in scopes {
+            token_cache().insert(self.cache_key(scope), token.clone(), ttl);
+        }
+
+        Ok(token)
     }
 
-    #[tracing::instrument(name = "check_image", skip(self), fields())]
-    pub async fn check_image(&self, image: &str, tag: &str) -> Result<bool> {
-        let registry_url = match self.registry_url.as_str() {
+    fn cache_key(&self, scope: &str) -> TokenKey {
+        (
+            self.base_url().to_string(),
+            self.auth_token.clone().unwrap_or_default(),
+            scope.to_string(),
+        )
+    }
+
+    fn api_url(&self) -> String {
+        match self.base_url() {
             url if url.ends_with("/v1/") => url.replace("/v1", "/v2"),
             url if url.ends_with("/v2/") => url.to_string(),
             url => format!("{}/v2", url.trim_end_matches('/')),
-        };
+        }
+    }
+
+    fn manifest_url(&self, image: &str, tag: &str) -> String {
+        format!(
+            "{}/{}/manifests/{}",
+            self.api_url(),
+            self.repository(image),
+            tag
+        )
+    }
 
-        let url = format!("{}/{}/manifests/{}", registry_url, image, tag);
+    /// Every tag of `image`, following the registry's `Link` pagination.
+    #[tracing::instrument(name = "list_tags", skip(self), fields())]
+    pub async fn list_tags(&self, image: &str) -> Result<Vec<String>> {
+        let mut url = Some(format!(
+            "{}/{}/tags/list",
+            self.api_url(),
+            self.repository(image)
+        ));
+        let mut tags = vec![];
+
+        while let Some(page_url) = url.take() {
+            let response = self.get_authorized(&page_url, image).await?;
+            if !response.status().is_success() {
+                anyhow::bail!("Failed to list tags of {}: {}", image, response.status());
+            }
+
+            url = response
+                .headers()
+                .get(LINK)
+                .and_then(|h| h.to_str().ok())
+                .and_then(|link| next_page_url(&page_url, link));
+            let page: TagList = response.json().await?;
+            tags.extend(page.tags.unwrap_or_default());
+        }
+
+        info!("Found {} tags for {}", tags.len(), image);
+        Ok(tags)
+    }
+
+    /// The digest the registry serves for `image:tag` (its
+    /// `Docker-Content-Digest`), or `None` if the tag doesn't exist.
+    #[tracing::instrument(name = "image_digest", skip(self), fields())]
+    pub async fn image_digest(&self, image: &str, tag: &str) -> Result<Option<String>> {
+        let response = self
+            .send_authorized(
+                Method::HEAD,
+                &self.manifest_url(image, tag),
+                image,
+                MANIFEST_MEDIA_TYPES,
+            )
+            .await?;
+        if response.status().as_u16() == 404 {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Failed to resolve the digest of {}:{}: {}",
+                image,
+                tag,
+                response.status()
+            );
+        }
+        Ok(response
+            .headers()
+            .get("docker-content-digest")
+            .and_then(|h| h.to_str().ok())
+            .map(String::from))
+    }
+
+    /// The labels in the configuration of `image:tag` (empty when it has
+    /// none). For a multi-platform image, those of its `linux/amd64` image,
+    /// else of the first one built for a platform.
+    #[tracing::instrument(name = "image_labels", skip(self), fields())]
+    pub async fn image_labels(&self, image: &str, tag: &str) -> Result<BTreeMap<String, String>> {
+        let mut manifest = self.manifest(image, tag).await?;
+        if let Some(manifests) = manifest.manifests.take() {
+            let on = |m: &&Descriptor, os: &str, architecture: Option<&str>| {
+                m.platform
+                    .as_ref()
+                    .is_some_and(|p| p.os == os && architecture.is_none_or(|a| p.architecture == a))
+            };
+            // Attestations are listed with an `unknown` platform.
+            let chosen = manifests
+                .iter()
+                .find(|m| on(m, "linux", Some("amd64")))
+                .or_else(|| {
+                    manifests
+                        .iter()
+                        .find(|m| m.platform.is_none() || !on(m, "unknown", None))
+                })
+                .with_context(|| format!("{}:{} lists no image", image, tag))?;
+            manifest = self.manifest(image, &chosen.digest).await?;
+        }
+
+        let config = manifest
+            .config
+            .with_context(|| format!("{}:{} has no configuration", image, tag))?;
+        let url = format!(
+            "{}/{}/blobs/{}",
+            self.api_url(),
+            self.repository(image),
+            config.digest
+        );
+        let response = self.get_authorized(&url, image).await?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Failed to get the configuration of {}:{}: {}",
+                image,
+                tag,
+                response.status()
+            );
+        }
+        let blob: ImageConfig = response.json().await?;
+        Ok(blob.config.and_then(|c| c.labels).unwrap_or_default())
+    }
+
+    /// The manifest (or index) of `image` at `reference`, a tag or digest.
+    async fn manifest(&self, image: &str, reference: &str) -> Result<Manifest> {
+        let response = self
+            .send_authorized(
+                Method::GET,
+                &self.manifest_url(image, reference),
+                image,
+                MANIFEST_MEDIA_TYPES,
+            )
+            .await?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Failed to get the manifest of {}:{}: {}",
+                image,
+                reference,
+                response.status()
+            );
+        }
+        Ok(response.json().await?)
+    }
+
+    /// GET `url` with the same credentials [`Self::check_image`] would use:
+    /// a cached token, then the configured token, then a bearer token from
+    /// the registry's challenge.
+    async fn get_authorized(&self, url: &str, image: &str) -> Result<reqwest::Response> {
+        self.send_authorized(Method::GET, url, image, "application/json")
+            .await
+    }
+
+    /// Send a `method` request for `url`, accepting `accept`, with the
+    /// credentials described in [`Self::get_authorized`].
+    async fn send_authorized(
+        &self,
+        method: Method,
+        url: &str,
+        image: &str,
+        accept: &str,
+    ) -> Result<reqwest::Response> {
+        let cache_key = self.cache_key(&pull_scope(&self.repository(image)));
+        if let Some(token) = token_cache().get(&cache_key) {
+            let response = self
+                .send(
+                    self.client
+                        .request(method.clone(), url)
+                        .header(ACCEPT, accept)
+                        .header(AUTHORIZATION, format!("Bearer {}", token)),
+                )
+                .await?;
+            if response.status().as_u16() != 401 {
+                return Ok(response);
+            }
+            token_cache().remove(&cache_key);
+        }
+
+        let response = self
+            .send(
+                self.client
+                    .request(method.clone(), url)
+                    .header(ACCEPT, accept)
+                    .header(
+                        AUTHORIZATION,
+                        self.auth_token.as_ref().unwrap_or(&String::new()),
+                    ),
+            )
+            .await?;
+
+        if response.status().as_u16() == 401
+            && let Some(auth_header) = response.headers().get(WWW_AUTHENTICATE)
+            && let Some(challenge) =
+                AuthChallenge::from_header(auth_header.to_str().unwrap_or_default())
+        {
+            let token = self.get_bearer_token(&challenge).await?;
+            return self
+                .send(
+                    self.client
+                        .request(method, url)
+                        .header(ACCEPT, accept)
+                        .header(AUTHORIZATION, format!("Bearer {}", token)),
+                )
+                .await;
+        }
+
+        Ok(response)
+    }
+
+    /// Check many images on this registry at once: a single token is
+    /// requested for every repository that has no cached one, then the
+    /// manifest HEAD requests run concurrently, at most
+    /// [`registry_concurrency`] at a time. Results are in input order.
+    #[tracing::instrument(name = "check_images", skip(self, images), fields(count = images.len()))]
+    pub async fn check_images(&self, images: &[(String, String)]) -> Vec<Result<bool>> {
+        let uncached: BTreeSet<String> = images
+            .iter()
+            .map(|(image, _)| pull_scope(&self.repository(image)))
+            .filter(|scope| token_cache().get(&self.cache_key(scope)).is_none())
+            .collect();
+
+        if let Some((image, tag)) = images
+            .iter()
+            .find(|(image, _)| uncached.contains(&pull_scope(&self.repository(image))))
+        {
+            match self.auth_challenge(&self.manifest_url(image, tag)).await {
+                Ok(Some(challenge)) => {
+                    let scopes: Vec<String> = uncached.into_iter().collect();
+                    if let Err(e) = self.get_bearer_token_for_scopes(&challenge, &scopes).await {
+                        warn!(
+                            "Failed to get a multi-scope token, falling back per image: {:?}",
+                            e
+                        );
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Failed to get registry auth challenge: {:?}", e),
+            }
+        }
+
+        stream::iter(images)
+            .map(|(image, tag)| self.check_image(image, tag))
+            .buffered(registry_concurrency())
+            .collect()
+            .await
+    }
+
+    /// The bearer challenge the registry answers an unauthenticated HEAD of
+    /// `url` with, if any.
+    async fn auth_challenge(&self, url: &str) -> Result<Option<AuthChallenge>> {
+        let response = self.send(self.client.head(url)).await?;
+
+        Ok(response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|h| h.to_str().ok())
+            .and_then(AuthChallenge::from_header))
+    }
+
+    #[tracing::instrument(name = "check_image", skip(self), fields())]
+    pub async fn check_image(&self, image: &str, tag: &str) -> Result<bool> {
+        let url = self.manifest_url(image, tag);
         info!("Checking image: {}", url);
 
+        // Reuse a cached token for this repository when there is one; if the
+        // registry rejects it, drop it and go through the challenge again.
+        let cache_key = self.cache_key(&pull_scope(&self.repository(image)));
+        if let Some(token) = token_cache().get(&cache_key) {
+            let response = self
+                .send(
+                    self.client
+                        .head(&url)
+                        .header(AUTHORIZATION, format!("Bearer {}", token)),
+                )
+                .await?;
+            if response.status().as_u16() != 401 {
+                info!(
+                    "registry checker status (cached token): {}",
+                    response.status()
+                );
+                return Ok(response.status().is_success());
+            }
+            token_cache().remove(&cache_key);
+        }
+
         // First request - might result in 401 with auth challenge
         let response = self
-            .client
-            .head(&url)
-            .header(
+            .send(self.client.head(&url).header(
                 AUTHORIZATION,
                 self.auth_token.as_ref().unwrap_or(&String::new()),
-            )
-            .send()
+            ))
             .await?;
 
         if response.status().as_u16() == 401
@@ -163,10 +684,7 @@ impl RegistryChecker {
             let auth_value = format!("Bearer {}", token);
 
             let response = self
-                .client
-                .head(&url)
-                .header(AUTHORIZATION, auth_value)
-                .send()
+                .send(self.client.head(&url).header(AUTHORIZATION, auth_value))
                 .await?;
             info!("registry checker status: {}", response.status());
 
@@ -177,6 +695,62 @@ impl RegistryChecker {
     }
 }
 
+#[derive(Debug, Deserialize)]
+struct TagList {
+    tags: Option<Vec<String>>,
+}
+
+/// An image manifest (`config`) or index (`manifests`).
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    config: Option<Descriptor>,
+    manifests: Option<Vec<Descriptor>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Descriptor {
+    digest: String,
+    platform: Option<Platform>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Platform {
+    os: String,
+    architecture: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageConfig {
+    config: Option<ContainerConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContainerConfig {
+    #[serde(rename = "Labels")]
+    labels: Option<BTreeMap<String, String>>,
+}
+
+/// The next page of a paginated registry listing, from the `Link` header
+/// (`</v2/app/tags/list?last=v1.2&n=100>; rel="next"`) of the response to
+/// `current`. Relative links are resolved against `current`.
+pub fn next_page_url(current: &str, link: &str) -> Option<String> {
+    link.split(',').find_map(|part| {
+        let (target, params) = part.split_once(';')?;
+        if !params
+            .split(';')
+            .any(|param| param.trim().replace('"', "") == "rel=next")
+        {
+            return None;
+        }
+        let target = target.trim().strip_prefix('<')?.strip_suffix('>')?;
+        reqwest::Url::parse(current)
+            .ok()?
+            .join(target)
+            .ok()
+            .map(|url| url.to_string())
+    })
+}
+
 /// Extract the basic-auth token for a given registry from a parsed
 /// `.dockerconfigjson` payload.
 ///
@@ -238,6 +812,194 @@ pub async fn get_registry_auth_from_secret(
     extract_auth_from_dockerconfig(&str_data, registry_url)
 }
 
+/// An ECR registry, from its host `<account>.dkr.ecr.<region>.amazonaws.com`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EcrRegistry {
+    pub account: String,
+    pub region: String,
+}
+
+impl EcrRegistry {
+    pub fn from_url(registry_url: &str) -> Option<Self> {
+        let host = registry_url
+            .trim()
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .split('/')
+            .next()?;
+        let (account, rest) = host.split_once(".dkr.ecr.")?;
+        let region = rest
+            .strip_suffix(".amazonaws.com")
+            .or_else(|| rest.strip_suffix(".amazonaws.com.cn"))?;
+        if account.is_empty()
+            || !account.bytes().all(|b| b.is_ascii_digit())
+            || region.is_empty()
+            || region.contains('.')
+        {
+            return None;
+        }
+        Some(Self {
+            account: account.to_string(),
+            region: region.to_string(),
+        })
+    }
+
+    /// The ECR API endpoint of the registry's region.
+    fn api_endpoint(&self) -> String {
+        let suffix = if self.region.starts_with("cn-") {
+            "amazonaws.com.cn"
+        } else {
+            "amazonaws.com"
+        };
+        format!("https://api.ecr.{}.{}/", self.region, suffix)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EcrAuthorization {
+    authorization_data: Vec<EcrAuthorizationData>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EcrAuthorizationData {
+    /// Base64 of `AWS:<password>`.
+    authorization_token: String,
+    /// Seconds since the epoch.
+    expires_at: Option<f64>,
+}
+
+/// Registry credentials for ECR, exchanged for the operator's AWS
+/// credentials (IRSA or the instance role, see
+/// [`crate::codecommit::aws_credentials`]) with `GetAuthorizationToken`.
+/// Tokens are cached per registry and exchanged again shortly before their
+/// 12 hours are up, so checkers always get a working one.
+#[derive(Default)]
+pub struct EcrAuth {
+    endpoint: Option<String>,
+    tokens: tokio::sync::Mutex<HashMap<String, (String, SystemTime)>>,
+}
+
+impl EcrAuth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask `endpoint` for tokens instead of the ECR API of each registry's
+    /// region.
+    pub fn with_endpoint(endpoint: String) -> Self {
+        Self {
+            endpoint: Some(endpoint),
+            ..Self::default()
+        }
+    }
+
+    /// The `Basic` authorization for the ECR registry at `registry_url`.
+    #[tracing::instrument(name = "ecr_auth_token", skip(self), fields())]
+    pub async fn auth_token(&self, registry_url: &str) -> Result<String> {
+        let registry = EcrRegistry::from_url(registry_url)
+            .with_context(|| format!("{} is not an ECR registry", registry_url))?;
+
+        // Held while exchanging, so concurrent checks wait for one token.
+        let mut tokens = self.tokens.lock().await;
+        if let Some((token, expires_at)) = tokens.get(registry_url)
+            && SystemTime::now() + ECR_REFRESH_MARGIN < *expires_at
+        {
+            return Ok(token.clone());
+        }
+
+        let (token, expires_at) = self.exchange(&registry).await?;
+        info!(
+            "Got an ECR token for {}, valid for {}s",
+            registry_url,
+            expires_at
+                .duration_since(SystemTime::now())
+                .unwrap_or_default()
+                .as_secs()
+        );
+        tokens.insert(registry_url.to_string(), (token.clone(), expires_at));
+        Ok(token)
+    }
+
+    async fn exchange(&self, registry: &EcrRegistry) -> Result<(String, SystemTime)> {
+        let credentials = tokio::task::spawn_blocking(aws_credentials)
+            .await
+            .context("Failed to look up AWS credentials")??;
+        let endpoint = self
+            .endpoint
+            .clone()
+            .unwrap_or_else(|| registry.api_endpoint());
+        let url = reqwest::Url::parse(&endpoint)
+            .with_context(|| format!("Invalid ECR endpoint {}", endpoint))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => anyhow::bail!("Invalid ECR endpoint {}", endpoint),
+        };
+
+        let body = serde_json::json!({ "registryIds": [registry.account] }).to_string();
+        let content_type = "application/x-amz-json-1.1";
+        let signature = sign_request(
+            &credentials,
+            &registry.region,
+            "ecr",
+            "POST",
+            url.path(),
+            "",
+            &[
+                ("content-type", content_type),
+                ("host", &host),
+                ("x-amz-target", ECR_GET_AUTHORIZATION_TOKEN),
+            ],
+            body.as_bytes(),
+            SystemTime::now(),
+        );
+
+        let mut request = with_retries(http_client())
+            .post(url)
+            .header(CONTENT_TYPE, content_type)
+            .header("x-amz-target", ECR_GET_AUTHORIZATION_TOKEN)
+            .body(body);
+        for (name, value) in signature {
+            request = request.header(name, value);
+        }
+        let response = request.send().await.context("Failed to call ECR")?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "ECR refused a token for {}: {} {}",
+                registry.account,
+                status,
+                body
+            );
+        }
+
+        let authorization: EcrAuthorization = response
+            .json()
+            .await
+            .context("Failed to parse the ECR token")?;
+        let data = authorization
+            .authorization_data
+            .into_iter()
+            .next()
+            .context("ECR returned no token")?;
+        let expires_at = data
+            .expires_at
+            .filter(|secs| secs.is_finite() && *secs > 0.0)
+            .map(|secs| UNIX_EPOCH + Duration::from_secs_f64(secs))
+            .unwrap_or_else(|| SystemTime::now() + ECR_TOKEN_TTL);
+        Ok((format!("Basic {}", data.authorization_token), expires_at))
+    }
+}
+
+/// Process-wide ECR credentials, shared by every deployment.
+pub fn ecr_auth() -> &'static EcrAuth {
+    static AUTH: OnceLock<EcrAuth> = OnceLock::new();
+    AUTH.get_or_init(EcrAuth::new)
+}
+
 /// Implement the ImageChecker trait for RegistryChecker
 #[async_trait]
 impl ImageChecker for RegistryChecker {
@@ -245,6 +1007,106 @@ impl ImageChecker for RegistryChecker {
         // Delegate to the existing method
         RegistryChecker::check_image(self, image, tag).await
     }
+
+    async fn list_tags(&self, image: &str) -> Result<Vec<String>> {
+        RegistryChecker::list_tags(self, image).await
+    }
+
+    async fn image_digest(&self, image: &str, tag: &str) -> Result<Option<String>> {
+        RegistryChecker::image_digest(self, image, tag).await
+    }
+
+    async fn image_labels(&self, image: &str, tag: &str) -> Result<BTreeMap<String, String>> {
+        RegistryChecker::image_labels(self, image, tag).await
+    }
+}
+
+/// Checks against an ordered list of registries, a primary and its
+/// fallbacks: each lookup is answered by the first registry that has the
+/// image, so promotions go on while the primary is down. Registries that fail
+/// are logged and skipped.
+pub struct FallbackImageChecker {
+    checkers: Vec<(String, Box<dyn ImageChecker>)>,
+    answered: Mutex<Option<String>>,
+}
+
+impl FallbackImageChecker {
+    /// `checkers` by registry URL, in the order to try them.
+    pub fn new(checkers: Vec<(String, Box<dyn ImageChecker>)>) -> Self {
+        Self {
+            checkers,
+            answered: Mutex::new(None),
+        }
+    }
+
+    /// The first answer of `lookup` that is `found`, trying the registries in
+    /// order. Without one, the first answer a registry gave, else the last
+    /// error.
+    async fn first<'a, T, F, Fut>(&'a self, lookup: F, found: impl Fn(&T) -> bool) -> Result<T>
+    where
+        F: Fn(&'a dyn ImageChecker) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut answer = None;
+        let mut error = None;
+        for (url, checker) in &self.checkers {
+            match lookup(checker.as_ref()).await {
+                Ok(value) if found(&value) => {
+                    let mut answered = self.answered.lock().unwrap_or_else(|e| e.into_inner());
+                    *answered = Some(url.clone());
+                    return Ok(value);
+                }
+                Ok(value) => {
+                    answer.get_or_insert(value);
+                }
+                Err(e) => {
+                    warn!("Registry {} failed, trying the next one: {:#}", url, e);
+                    error = Some(e);
+                }
+            }
+        }
+        match (answer, error) {
+            (Some(value), _) => Ok(value),
+            (None, Some(e)) => Err(e),
+            (None, None) => anyhow::bail!("No registry to check"),
+        }
+    }
+}
+
+#[async_trait]
+impl ImageChecker for FallbackImageChecker {
+    async fn check_image(&self, image: &str, tag: &str) -> Result<bool> {
+        self.first(|checker| checker.check_image(image, tag), |found| *found)
+            .await
+    }
+
+    async fn list_tags(&self, image: &str) -> Result<Vec<String>> {
+        self.first(|checker| checker.list_tags(image), |_| true)
+            .await
+    }
+
+    async fn image_digest(&self, image: &str, tag: &str) -> Result<Option<String>> {
+        self.first(
+            |checker| checker.image_digest(image, tag),
+            |digest| digest.is_some(),
+        )
+        .await
+    }
+
+    async fn image_labels(&self, image: &str, tag: &str) -> Result<BTreeMap<String, String>> {
+        self.first(
+            |checker| checker.image_labels(image, tag),
+            |labels| !labels.is_empty(),
+        )
+        .await
+    }
+
+    fn answered_by(&self) -> Option<String> {
+        self.answered
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
 }
 
 /// Factory for creating RegistryChecker instances
@@ -270,7 +1132,9 @@ impl ImageCheckerFactory for RegistryCheckerFactory {
         registry_url: &str,
         auth_token: Option<String>,
     ) -> Result<Box<dyn ImageChecker>> {
-        let checker = RegistryChecker::new(registry_url.to_string(), auth_token).await?;
+        let checker = RegistryChecker::new(registry_url.to_string(), auth_token)
+            .await?
+            .with_mirror(registry_mirrors().lookup(registry_url).cloned());
         Ok(Box::new(checker))
     }
 }
@@ -0,0 +1,91 @@
+use crate::configuration::ReconcileResult;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// The latest result of a deployment, numbered so a watcher can tell whether
+/// it has already seen it.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct WatchedResult {
+    /// Increases by one every time the deployment's result changes.
+    pub revision: u64,
+    pub result: ReconcileResult,
+}
+
+/// Latest reconcile result per deployment (`namespace/name`), which `/watch`
+/// long-polls. Only results that differ from the previous one count as a
+/// change, so periodic `up_to_date` passes don't wake watchers.
+#[derive(Default)]
+pub struct ResultBoard {
+    channels: Mutex<HashMap<String, watch::Sender<Option<WatchedResult>>>>,
+}
+
+impl ResultBoard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn channel(&self, key: &str) -> watch::Sender<Option<WatchedResult>> {
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        channels
+            .entry(key.to_string())
+            .or_insert_with(|| watch::channel(None).0)
+            .clone()
+    }
+
+    /// Record the outcome of a pass, waking the watchers of every deployment
+    /// whose result changed.
+    pub fn publish(&self, results: &[ReconcileResult]) {
+        for result in results {
+            let key = format!("{}/{}", &result.namespace, &result.deployment);
+            self.channel(&key).send_if_modified(|latest| match latest {
+                Some(latest) if latest.result == *result => false,
+                _ => {
+                    let revision = latest.as_ref().map_or(1, |l| l.revision + 1);
+                    *latest = Some(WatchedResult {
+                        revision,
+                        result: result.clone(),
+                    });
+                    true
+                }
+            });
+        }
+    }
+
+    /// The latest result of `key`, if it has one.
+    pub fn latest(&self, key: &str) -> Option<WatchedResult> {
+        self.channel(key).borrow().clone()
+    }
+
+    /// Wait up to `timeout` for the result of `key` to change. With `seen`
+    /// (the revision the caller already has), a newer one is returned at
+    /// once; without it, only a change after the call counts. `None` on
+    /// timeout.
+    pub async fn wait_for_change(
+        &self,
+        key: &str,
+        seen: Option<u64>,
+        timeout: Duration,
+    ) -> Option<WatchedResult> {
+        let mut receiver = self.channel(key).subscribe();
+        let current = receiver.borrow_and_update().clone();
+        if let (Some(current), Some(seen)) = (&current, seen)
+            && current.revision != seen
+        {
+            return Some(current.clone());
+        }
+
+        tokio::time::timeout(timeout, receiver.changed())
+            .await
+            .ok()?
+            .ok()?;
+        receiver.borrow().clone()
+    }
+}
+
+/// Process-wide result board fed by every reconcile trigger.
+pub fn result_board() -> Arc<ResultBoard> {
+    static BOARD: OnceLock<Arc<ResultBoard>> = OnceLock::new();
+    BOARD.get_or_init(|| Arc::new(ResultBoard::new())).clone()
+}
@@ -0,0 +1,162 @@
+use crate::metrics::HTTP_RETRIES_TOTAL;
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Client, Request, Response, StatusCode};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, Middleware, Next};
+use std::time::{Duration, SystemTime};
+use tracing::warn;
+
+/// Header carrying the key that lets a receiver drop a retried POST it has
+/// already processed. Requests without it are only retried if their method
+/// is idempotent.
+pub const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_BASE_DELAY_MS: u64 = 250;
+const DEFAULT_MAX_DELAY_SECS: u64 = 30;
+
+/// How outgoing HTTP requests (notifications, registry and GitHub API calls)
+/// are retried on connection errors, timeouts, 408, 429 and 5xx responses.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// `GITOPS_HTTP_MAX_RETRIES` (default 3, 0 disables retries).
+    pub max_retries: u32,
+    /// `GITOPS_HTTP_RETRY_BASE_DELAY_MS` (default 250): the first backoff,
+    /// doubled on every further attempt.
+    pub base_delay: Duration,
+    /// `GITOPS_HTTP_RETRY_MAX_DELAY_SECS` (default 30): cap on any single
+    /// wait, including one asked for by `Retry-After`.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            base_delay: Duration::from_millis(DEFAULT_BASE_DELAY_MS),
+            max_delay: Duration::from_secs(DEFAULT_MAX_DELAY_SECS),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_env() -> Self {
+        fn env<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok().and_then(|v| v.trim().parse().ok())
+        }
+
+        let default = Self::default();
+        Self {
+            max_retries: env("GITOPS_HTTP_MAX_RETRIES").unwrap_or(default.max_retries),
+            base_delay: env("GITOPS_HTTP_RETRY_BASE_DELAY_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(default.base_delay),
+            max_delay: env("GITOPS_HTTP_RETRY_MAX_DELAY_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(default.max_delay),
+        }
+    }
+
+    /// How long to wait before retry number `retry` (0-based): the server's
+    /// `Retry-After` if it sent one, exponential backoff otherwise, capped at
+    /// `max_delay` either way.
+    pub fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry.min(16)));
+        retry_after.unwrap_or(backoff).min(self.max_delay)
+    }
+}
+
+/// Whether a response with `status` is worth retrying.
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status.is_server_error()
+}
+
+/// The wait a `Retry-After` header asks for, in either of its forms
+/// (delay in seconds or HTTP date).
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+/// Middleware retrying requests according to a [`RetryPolicy`].
+pub struct RetryMiddleware {
+    policy: RetryPolicy,
+}
+
+impl RetryMiddleware {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self { policy }
+    }
+
+    fn can_retry(req: &Request) -> bool {
+        req.method().is_idempotent() || req.headers().contains_key(IDEMPOTENCY_KEY)
+    }
+}
+
+#[async_trait]
+impl Middleware for RetryMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut http::Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let mut retry = 0;
+        loop {
+            // Streaming bodies cannot be replayed; send those once.
+            let replay = (retry < self.policy.max_retries && Self::can_retry(&req))
+                .then(|| req.try_clone())
+                .flatten();
+            let Some(replay) = replay else {
+                return next.run(req, extensions).await;
+            };
+
+            let result = next.clone().run(replay, extensions).await;
+            let (reason, wait_hint) = match &result {
+                Ok(response) if is_retryable_status(response.status()) => (
+                    response.status().to_string(),
+                    retry_after(response.headers()),
+                ),
+                Err(reqwest_middleware::Error::Reqwest(e)) if e.is_connect() || e.is_timeout() => {
+                    (e.to_string(), None)
+                }
+                _ => return result,
+            };
+
+            let delay = self.policy.delay(retry, wait_hint);
+            warn!(
+                "{} {} failed ({}), retrying in {:?} ({}/{})",
+                req.method(),
+                req.url(),
+                reason,
+                delay,
+                retry + 1,
+                self.policy.max_retries
+            );
+            ::metrics::counter!(HTTP_RETRIES_TOTAL, "host" => req.url().host_str().unwrap_or_default().to_string())
+                .increment(1);
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        }
+    }
+}
+
+/// Wrap `client` in the operator's retry policy, read from the environment.
+pub fn with_retries(client: Client) -> ClientWithMiddleware {
+    with_policy(client, RetryPolicy::from_env())
+}
+
+/// Wrap `client` in retry middleware following `policy`.
+pub fn with_policy(client: Client, policy: RetryPolicy) -> ClientWithMiddleware {
+    ClientBuilder::new(client)
+        .with(RetryMiddleware::new(policy))
+        .build()
+}
@@ -0,0 +1,3 @@
+#[allow(clippy::module_inception)]
+mod rollouts;
+pub use rollouts::*;
@@ -0,0 +1,241 @@
+use crate::history::now_rfc3339;
+use crate::state::{MemoryState, state_store};
+use crate::traits::StateStore;
+use anyhow::{Context, Result};
+use k8s_openapi::api::apps::v1::Deployment;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::warn;
+
+const ROLLOUTS_BUCKET: &str = "rollouts";
+
+pub const DEFAULT_ROLLOUT_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How often the live Deployment is read while a rollout is verified.
+pub const ROLLOUT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long a rollout may take before it counts as failed, from
+/// `GITOPS_ROLLOUT_TIMEOUT_SECS` (default 600).
+pub fn rollout_timeout() -> Duration {
+    std::env::var("GITOPS_ROLLOUT_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_ROLLOUT_TIMEOUT)
+}
+
+/// Where the rollout of a promoted image stands.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RolloutStatus {
+    /// The cluster hasn't picked the new image up yet, or is rolling it out.
+    Progressing,
+    /// Every replica runs the new image and is available.
+    Complete,
+    /// The Deployment exceeded its progress deadline, or disappeared.
+    Failed,
+    /// The rollout didn't finish within the operator's timeout.
+    TimedOut,
+}
+
+impl RolloutStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RolloutStatus::Progressing => "progressing",
+            RolloutStatus::Complete => "complete",
+            RolloutStatus::Failed => "failed",
+            RolloutStatus::TimedOut => "timed_out",
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        *self != RolloutStatus::Progressing
+    }
+}
+
+/// The rollout of `tag` to a deployment after its manifest was pushed.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct Rollout {
+    pub namespace: String,
+    pub deployment: String,
+    pub tag: String,
+    /// The tag the manifest declared before the push, which an automatic
+    /// revert restores.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub previous_tag: Option<String>,
+    pub status: RolloutStatus,
+    pub message: String,
+    /// RFC 3339 (UTC).
+    pub started_at: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub finished_at: Option<String>,
+    /// Whether the manifest was reverted to `previous_tag` after the rollout
+    /// failed.
+    #[serde(default)]
+    pub reverted: bool,
+}
+
+/// The tag of an image reference (`latest` if untagged).
+fn tag_of(image: &str) -> &str {
+    let image = image.split('@').next().unwrap_or_default();
+    match image.rsplit_once(':') {
+        Some((_, tag)) if !tag.contains('/') => tag,
+        _ => "latest",
+    }
+}
+
+/// Where the rollout of `tag` to the `containers` of `deployment` stands,
+/// the way `kubectl rollout status` tells, with a line saying why.
+pub fn rollout_progress(
+    deployment: &Deployment,
+    containers: &[String],
+    tag: &str,
+) -> (RolloutStatus, String) {
+    let Some(spec) = deployment.spec.as_ref() else {
+        return (RolloutStatus::Failed, "Deployment has no spec".to_string());
+    };
+    let stale: Vec<&str> = spec
+        .template
+        .spec
+        .as_ref()
+        .map(|pod| pod.containers.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter(|c| containers.contains(&c.name))
+        .filter(|c| tag_of(c.image.as_deref().unwrap_or_default()) != tag)
+        .map(|c| c.name.as_str())
+        .collect();
+    if !stale.is_empty() {
+        return (
+            RolloutStatus::Progressing,
+            format!("waiting for {} to be updated to {}", stale.join(", "), tag),
+        );
+    }
+
+    let status = deployment.status.clone().unwrap_or_default();
+    let generation = deployment.metadata.generation.unwrap_or_default();
+    if status.observed_generation.unwrap_or_default() < generation {
+        return (
+            RolloutStatus::Progressing,
+            "waiting for the new spec to be observed".to_string(),
+        );
+    }
+    if let Some(stuck) = status.conditions.iter().flatten().find(|c| {
+        c.type_ == "Progressing" && c.reason.as_deref() == Some("ProgressDeadlineExceeded")
+    }) {
+        return (
+            RolloutStatus::Failed,
+            stuck
+                .message
+                .clone()
+                .unwrap_or_else(|| "progress deadline exceeded".to_string()),
+        );
+    }
+
+    let replicas = spec.replicas.unwrap_or(1);
+    let updated = status.updated_replicas.unwrap_or_default();
+    let total = status.replicas.unwrap_or_default();
+    let available = status.available_replicas.unwrap_or_default();
+    if updated < replicas {
+        return (
+            RolloutStatus::Progressing,
+            format!("{} of {} replicas updated", updated, replicas),
+        );
+    }
+    if total > updated {
+        return (
+            RolloutStatus::Progressing,
+            format!("{} old replicas pending termination", total - updated),
+        );
+    }
+    if available < updated {
+        return (
+            RolloutStatus::Progressing,
+            format!("{} of {} updated replicas available", available, updated),
+        );
+    }
+    (
+        RolloutStatus::Complete,
+        format!("{} replica(s) running {}", replicas, tag),
+    )
+}
+
+/// The latest rollout of each deployment (`namespace/name`), kept in a
+/// [`StateStore`].
+pub struct Rollouts {
+    store: Arc<dyn StateStore>,
+}
+
+impl Default for Rollouts {
+    fn default() -> Self {
+        Self::with_store(Arc::new(MemoryState::new()))
+    }
+}
+
+impl Rollouts {
+    /// Rollouts kept in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rollouts kept in `store`.
+    pub fn with_store(store: Arc<dyn StateStore>) -> Self {
+        Self { store }
+    }
+
+    pub fn record(&self, rollout: &Rollout) -> Result<()> {
+        let key = format!("{}/{}", rollout.namespace, rollout.deployment);
+        self.store
+            .put(ROLLOUTS_BUCKET, &key, &serde_json::to_string(rollout)?)
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<Rollout>> {
+        self.store
+            .get(ROLLOUTS_BUCKET, key)?
+            .map(|value| serde_json::from_str(&value).context("Failed to read rollout"))
+            .transpose()
+    }
+
+    /// The latest rollout of every deployment, by namespace and name.
+    /// Unreadable entries are logged and left out.
+    pub fn list(&self) -> Result<Vec<Rollout>> {
+        Ok(self
+            .store
+            .entries(ROLLOUTS_BUCKET)?
+            .into_iter()
+            .filter_map(|(key, value)| {
+                serde_json::from_str(&value)
+                    .inspect_err(|e| warn!("Failed to read the rollout of {}: {}", key, e))
+                    .ok()
+            })
+            .collect())
+    }
+}
+
+/// A rollout of `tag`, replacing `previous_tag`, just started.
+pub fn started(
+    namespace: &str,
+    deployment: &str,
+    tag: &str,
+    previous_tag: Option<&str>,
+) -> Rollout {
+    Rollout {
+        namespace: namespace.to_string(),
+        deployment: deployment.to_string(),
+        tag: tag.to_string(),
+        previous_tag: previous_tag.map(String::from),
+        status: RolloutStatus::Progressing,
+        message: "waiting for the cluster to pick the new image up".to_string(),
+        started_at: now_rfc3339(),
+        finished_at: None,
+        reverted: false,
+    }
+}
+
+/// Process-wide rollouts, kept in the operator's [`state_store`].
+pub fn rollouts() -> Arc<Rollouts> {
+    static ROLLOUTS: OnceLock<Arc<Rollouts>> = OnceLock::new();
+    ROLLOUTS
+        .get_or_init(|| Arc::new(Rollouts::with_store(state_store())))
+        .clone()
+}
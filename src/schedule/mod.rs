@@ -0,0 +1,3 @@
+#[allow(clippy::module_inception)]
+mod schedule;
+pub use schedule::*;
@@ -0,0 +1,402 @@
+use anyhow::{Context, Result, anyhow, bail};
+use futures::StreamExt;
+use k8s_openapi::api::batch::v1::{CronJob, CronJobSpec, JobSpec, JobTemplateSpec};
+use k8s_openapi::api::core::v1::{Container, PodSpec, PodTemplateSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use k8s_openapi::jiff::Timestamp;
+use k8s_openapi::jiff::tz::TimeZone;
+use kube::api::{Api, PostParams};
+use kube::runtime::{WatchStreamExt, watcher};
+use kube::{Client, ResourceExt};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+const DEFAULT_SCHEDULE_CRONJOB: &str = "gitops-operator-reconcile";
+const DEFAULT_SCHEDULE_NAMESPACE: &str = "gitops-operator";
+/// Image of the CronJob's job template, used for manual runs
+/// (`kubectl create job --from=cronjob/...`).
+const SCHEDULE_JOB_IMAGE: &str = "busybox:1.36";
+/// How far ahead to look for the next run before declaring a schedule
+/// impossible (e.g. `0 0 30 2 *`).
+const MAX_LOOKAHEAD_DAYS: i64 = 5 * 366;
+
+const MINUTE: i64 = 60;
+const HOUR: i64 = 60 * MINUTE;
+const DAY: i64 = 24 * HOUR;
+
+/// A standard five-field cron expression (minute, hour, day of month, month,
+/// day of week), evaluated in UTC like a `CronJob` without `timeZone`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// When both day fields are restricted a day matching either one runs,
+    /// as in cron.
+    any_day: bool,
+}
+
+impl CronSchedule {
+    /// Parse a five-field expression or one of the `@hourly`, `@daily`,
+    /// `@midnight`, `@weekly`, `@monthly`, `@yearly` and `@annually` macros.
+    pub fn parse(expression: &str) -> Result<Self> {
+        let expression = expression.trim();
+        let expanded = match expression {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            bail!(
+                "Invalid cron expression '{}': expected 5 fields, found {}",
+                expression,
+                fields.len()
+            );
+        };
+
+        let parsed = || -> Result<Self> {
+            let mut weekdays = parse_field(weekday, 0, 7, WEEKDAY_NAMES)?;
+            // Both 0 and 7 are Sunday.
+            if weekdays & (1 << 7) != 0 {
+                weekdays = (weekdays | 1) & !(1 << 7);
+            }
+
+            Ok(Self {
+                expression: expression.to_string(),
+                minutes: parse_field(minute, 0, 59, &[])?,
+                hours: parse_field(hour, 0, 23, &[])?,
+                days: parse_field(day, 1, 31, &[])?,
+                months: parse_field(month, 1, 12, MONTH_NAMES)?,
+                weekdays,
+                any_day: !day.starts_with('*') && !weekday.starts_with('*'),
+            })
+        };
+
+        parsed().with_context(|| format!("Invalid cron expression '{}'", expression))
+    }
+
+    /// The expression as written (macros are not expanded).
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// The first run strictly after `after`, or `None` if the schedule never
+    /// fires within the next few years.
+    pub fn next_after(&self, after: Timestamp) -> Option<Timestamp> {
+        let start = after.as_second().div_euclid(MINUTE) * MINUTE + MINUTE;
+        let limit = start + MAX_LOOKAHEAD_DAYS * DAY;
+        let mut secs = start;
+
+        while secs < limit {
+            let now = Timestamp::from_second(secs).ok()?.to_zoned(TimeZone::UTC);
+
+            if !has(self.months, now.month()) || !self.day_matches(now.day(), now.weekday()) {
+                secs = secs - secs.rem_euclid(DAY) + DAY;
+            } else if !has(self.hours, now.hour()) {
+                secs = secs - secs.rem_euclid(HOUR) + HOUR;
+            } else if !has(self.minutes, now.minute()) {
+                secs += MINUTE;
+            } else {
+                return Some(now.timestamp());
+            }
+        }
+
+        None
+    }
+
+    fn day_matches(&self, day: i8, weekday: k8s_openapi::jiff::civil::Weekday) -> bool {
+        let by_day = has(self.days, day);
+        let by_weekday = has(self.weekdays, weekday.to_sunday_zero_offset());
+        if self.any_day {
+            by_day || by_weekday
+        } else {
+            by_day && by_weekday
+        }
+    }
+}
+
+impl std::fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+const MONTH_NAMES: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+fn has(set: u64, value: i8) -> bool {
+    (0..64).contains(&value) && set & (1 << value) != 0
+}
+
+/// Parse one cron field (lists of `*`, `n`, `a-b`, each with an optional
+/// `/step`) into a bit set of the allowed values. `names` map to `min`,
+/// `min + 1`, ... (months start at 1, weekdays at 0).
+fn parse_field(field: &str, min: u8, max: u8, names: &[&str]) -> Result<u64> {
+    let value = |s: &str| -> Result<u8> {
+        let lower = s.to_ascii_lowercase();
+        let n = match names.iter().position(|name| *name == lower) {
+            Some(i) => i as u8 + min,
+            None => s.parse().map_err(|_| anyhow!("'{}' is not a number", s))?,
+        };
+        if !(min..=max).contains(&n) {
+            bail!("{} is out of range {}-{}", n, min, max);
+        }
+        Ok(n)
+    };
+
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u8 = step
+                    .parse()
+                    .map_err(|_| anyhow!("'{}' is not a valid step", step))?;
+                if step == 0 {
+                    bail!("step must be positive in '{}'", part);
+                }
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (value(from)?, value(to)?),
+                // `n/step` runs from n to the end of the range.
+                None if step.is_some() => (value(range)?, max),
+                None => {
+                    let n = value(range)?;
+                    (n, n)
+                }
+            },
+        };
+        if from > to {
+            bail!("range '{}' is reversed", range);
+        }
+
+        for n in (from..=to).step_by(step.unwrap_or(1) as usize) {
+            set |= 1 << n;
+        }
+    }
+
+    Ok(set)
+}
+
+/// Self-registered reconcile schedule, enabled by `GITOPS_RECONCILE_SCHEDULE`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScheduleSettings {
+    /// Schedule used when the operator creates the `CronJob`. Once it exists,
+    /// the `CronJob`'s `spec.schedule` wins.
+    pub schedule: CronSchedule,
+    /// `GITOPS_SCHEDULE_CRONJOB` (default `gitops-operator-reconcile`).
+    pub cronjob_name: String,
+    /// `GITOPS_SCHEDULE_NAMESPACE` (default `gitops-operator`).
+    pub namespace: String,
+    /// `GITOPS_SCHEDULE_RECONCILE_URL`, called by manual runs of the `CronJob`
+    /// (default `http://gitops-operator.<namespace>.svc:8000/reconcile`).
+    pub reconcile_url: String,
+}
+
+impl ScheduleSettings {
+    /// Read the settings from the environment. `None` when no schedule is set
+    /// (reconcile passes are then only triggered externally) or it is invalid.
+    pub fn from_env() -> Option<Self> {
+        let expression = std::env::var("GITOPS_RECONCILE_SCHEDULE").ok()?;
+        let schedule = match CronSchedule::parse(&expression) {
+            Ok(schedule) => schedule,
+            Err(e) => {
+                warn!("{:#}; reconcile schedule disabled", e);
+                return None;
+            }
+        };
+
+        let namespace = std::env::var("GITOPS_SCHEDULE_NAMESPACE")
+            .unwrap_or_else(|_| DEFAULT_SCHEDULE_NAMESPACE.to_string());
+        let reconcile_url = std::env::var("GITOPS_SCHEDULE_RECONCILE_URL")
+            .unwrap_or_else(|_| format!("http://gitops-operator.{}.svc:8000/reconcile", namespace));
+
+        Some(Self {
+            schedule,
+            cronjob_name: std::env::var("GITOPS_SCHEDULE_CRONJOB")
+                .unwrap_or_else(|_| DEFAULT_SCHEDULE_CRONJOB.to_string()),
+            namespace,
+            reconcile_url,
+        })
+    }
+}
+
+/// The `CronJob` representing the reconcile schedule. It is created suspended
+/// because the operator runs the passes itself; the job template only serves
+/// manual runs.
+pub fn reconcile_cronjob(settings: &ScheduleSettings, schedule: &CronSchedule) -> CronJob {
+    let labels = BTreeMap::from([(
+        "app.kubernetes.io/managed-by".to_string(),
+        "gitops-operator".to_string(),
+    )]);
+
+    CronJob {
+        metadata: ObjectMeta {
+            name: Some(settings.cronjob_name.clone()),
+            namespace: Some(settings.namespace.clone()),
+            labels: Some(labels),
+            ..ObjectMeta::default()
+        },
+        spec: CronJobSpec {
+            schedule: schedule.expression().to_string(),
+            suspend: Some(true),
+            concurrency_policy: Some("Forbid".to_string()),
+            job_template: JobTemplateSpec {
+                spec: Some(JobSpec {
+                    backoff_limit: Some(0),
+                    template: PodTemplateSpec {
+                        spec: Some(PodSpec {
+                            restart_policy: Some("Never".to_string()),
+                            containers: vec![Container {
+                                name: "reconcile".to_string(),
+                                image: Some(SCHEDULE_JOB_IMAGE.to_string()),
+                                command: Some(vec![
+                                    "wget".to_string(),
+                                    "-qO-".to_string(),
+                                    settings.reconcile_url.clone(),
+                                ]),
+                                ..Container::default()
+                            }],
+                            ..PodSpec::default()
+                        }),
+                        ..PodTemplateSpec::default()
+                    },
+                    ..JobSpec::default()
+                }),
+                ..JobTemplateSpec::default()
+            },
+            ..CronJobSpec::default()
+        },
+        ..CronJob::default()
+    }
+}
+
+/// Publish the `CronJob`'s schedule to the scheduler if it changed. Invalid
+/// edits are logged and the previous schedule is kept.
+fn adopt_schedule(cronjob: &CronJob, tx: &watch::Sender<CronSchedule>) {
+    let expression = cronjob.spec.schedule.as_str();
+    if tx.borrow().expression() == expression.trim() {
+        return;
+    }
+
+    match CronSchedule::parse(expression) {
+        Ok(schedule) => {
+            info!(
+                "Reconcile schedule changed to '{}' by CronJob {}",
+                schedule,
+                cronjob.name_any()
+            );
+            tx.send_replace(schedule);
+        }
+        Err(e) => warn!(
+            "{:#} in CronJob {}; keeping '{}'",
+            e,
+            cronjob.name_any(),
+            *tx.borrow()
+        ),
+    }
+}
+
+/// Create the schedule `CronJob` if it is missing, then watch it and feed
+/// every change of `spec.schedule` into `tx`. A deleted `CronJob` is recreated
+/// with the schedule in effect. Runs forever.
+pub async fn run_cronjob_sync(
+    client: Client,
+    settings: ScheduleSettings,
+    tx: watch::Sender<CronSchedule>,
+) {
+    let api: Api<CronJob> = Api::namespaced(client, &settings.namespace);
+
+    let ensure = |schedule: CronSchedule| {
+        let api = api.clone();
+        let cronjob = reconcile_cronjob(&settings, &schedule);
+        async move {
+            match api.create(&PostParams::default(), &cronjob).await {
+                Ok(_) => info!(
+                    "Created CronJob {} for reconcile schedule '{}'",
+                    cronjob.name_any(),
+                    schedule
+                ),
+                Err(kube::Error::Api(e)) if e.code == 409 => {}
+                Err(e) => warn!(
+                    "Failed to create reconcile schedule CronJob {}: {}",
+                    cronjob.name_any(),
+                    e
+                ),
+            }
+        }
+    };
+
+    let current = tx.borrow().clone();
+    ensure(current).await;
+
+    let config =
+        watcher::Config::default().fields(&format!("metadata.name={}", settings.cronjob_name));
+    let mut events = watcher(api.clone(), config).default_backoff().boxed();
+    while let Some(event) = events.next().await {
+        match event {
+            Ok(watcher::Event::Apply(cronjob) | watcher::Event::InitApply(cronjob)) => {
+                adopt_schedule(&cronjob, &tx);
+            }
+            Ok(watcher::Event::Delete(cronjob)) => {
+                warn!("CronJob {} was deleted; recreating it", cronjob.name_any());
+                let current = tx.borrow().clone();
+                ensure(current).await;
+            }
+            Ok(_) => {}
+            Err(e) => warn!("schedule watcher error: {e}"),
+        }
+    }
+}
+
+/// Run `pass` at every tick of the schedule in `rx`, picking up schedule
+/// changes as they are published. Runs forever.
+pub async fn run_scheduler<F, Fut>(mut rx: watch::Receiver<CronSchedule>, mut pass: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut publisher_alive = true;
+    // Never fire the same tick twice if the wall clock lags the timer.
+    let mut last_tick: Option<Timestamp> = None;
+    loop {
+        let schedule = rx.borrow_and_update().clone();
+        let now = Timestamp::now();
+        let next = schedule.next_after(last_tick.map_or(now, |last| last.max(now)));
+        match next {
+            Some(next) => info!("Next scheduled reconcile at {} ('{}')", next, schedule),
+            None => warn!("Reconcile schedule '{}' never fires", schedule),
+        }
+        let wait = next
+            .and_then(|next| Duration::try_from(next.duration_since(now)).ok())
+            .unwrap_or(Duration::MAX);
+
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {
+                last_tick = next;
+                pass().await;
+            }
+            changed = rx.changed(), if publisher_alive => {
+                publisher_alive = changed.is_ok();
+            }
+        }
+    }
+}
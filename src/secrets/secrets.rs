@@ -1,3 +1,6 @@
+use crate::git::HttpsToken;
+use crate::github_app::GitHubApp;
+use crate::notifications::NotificationEndpoint;
 use crate::registry::get_registry_auth_from_secret;
 use crate::traits::SecretProvider;
 use anyhow::{Context, Result};
@@ -39,9 +42,13 @@ impl SecretProvider for K8sSecretProvider {
         String::from_utf8(key_bytes).context("Failed to convert key to string")
     }
 
-    async fn get_notification_endpoint(&self, name: &str, namespace: &str) -> Result<String> {
+    async fn get_notification_endpoint(
+        &self,
+        name: &str,
+        namespace: &str,
+    ) -> Result<NotificationEndpoint> {
         if name.is_empty() {
-            return Ok(String::new());
+            return Ok(NotificationEndpoint::default());
         }
 
         let client = Client::try_default().await?;
@@ -50,13 +57,12 @@ impl SecretProvider for K8sSecretProvider {
 
         let secret_data = secret.data.context("Failed to read the data section")?;
 
-        let encoded_url = secret_data
-            .get("webhook-url")
-            .context("Failed to read field: webhook-url in data, consider recreating the secret with kubectl create secret generic webhook-secret-name -n your_namespace --from-literal=webhook-url=https://hooks.sl...")?;
-
-        let bytes = encoded_url.0.clone();
+        let fields = secret_data
+            .into_iter()
+            .map(|(key, value)| (key, value.0))
+            .collect();
 
-        String::from_utf8(bytes).context("Failed to convert key to string")
+        NotificationEndpoint::from_secret_data(&fields)
     }
 
     async fn get_github_token(&self, name: &str, namespace: &str) -> Result<String> {
@@ -75,6 +81,93 @@ impl SecretProvider for K8sSecretProvider {
         String::from_utf8(bytes).context("Failed to convert token to string")
     }
 
+    async fn get_forge_token(&self, name: &str, namespace: &str) -> Result<String> {
+        let client = Client::try_default().await?;
+        let secrets: Api<Secret> = Api::namespaced(client, namespace);
+        let secret = secrets.get(name).await?;
+
+        let secret_data = secret.data.context("Failed to read the data section")?;
+
+        let encoded_token = secret_data
+            .get("token")
+            .context("Failed to read field: token in data, consider recreating the secret with kubectl create secret generic name --from-literal=token=glpat-...")?;
+
+        let bytes = encoded_token.0.clone();
+
+        String::from_utf8(bytes).context("Failed to convert token to string")
+    }
+
+    async fn get_https_token(&self, name: &str, namespace: &str) -> Result<HttpsToken> {
+        let client = Client::try_default().await?;
+        let secrets: Api<Secret> = Api::namespaced(client, namespace);
+        let secret = secrets.get(name).await?;
+
+        let secret_data = secret.data.context("Failed to read the data section")?;
+
+        // `password` lets kubernetes.io/basic-auth secrets be used as they are.
+        let encoded_token = secret_data
+            .get("token")
+            .or_else(|| secret_data.get("password"))
+            .context("Failed to read field: token in data, consider recreating the secret with kubectl create secret generic name --from-literal=token=ghp_...")?;
+        let token = String::from_utf8(encoded_token.0.clone())
+            .context("Failed to convert token to string")?;
+        let username = match secret_data.get("username") {
+            Some(encoded) => Some(
+                String::from_utf8(encoded.0.clone())
+                    .context("Failed to convert username to string")?,
+            ),
+            None => None,
+        };
+
+        Ok(HttpsToken { username, token })
+    }
+
+    async fn get_github_app(&self, name: &str, namespace: &str) -> Result<GitHubApp> {
+        let client = Client::try_default().await?;
+        let secrets: Api<Secret> = Api::namespaced(client, namespace);
+        let secret = secrets.get(name).await?;
+
+        let secret_data = secret.data.context("Failed to read the data section")?;
+        let field = |key: &str| {
+            secret_data
+                .get(key)
+                .map(|value| String::from_utf8(value.0.clone()))
+                .transpose()
+                .with_context(|| format!("Failed to convert {} to string", key))
+        };
+
+        let app_id = field("app-id")?
+            .context("Failed to read field: app-id in data, consider recreating the secret with kubectl create secret generic name --from-literal=app-id=123456 --from-file=private-key=/path/to/app.pem")?;
+        let private_key = field("private-key")?
+            .context("Failed to read field: private-key in data, consider recreating the secret with kubectl create secret generic name --from-literal=app-id=123456 --from-file=private-key=/path/to/app.pem")?;
+        let installation_id = field("installation-id")?
+            .map(|id| id.trim().parse())
+            .transpose()
+            .context("Failed to parse installation-id")?;
+
+        Ok(GitHubApp {
+            app_id: app_id.trim().to_string(),
+            private_key,
+            installation_id,
+        })
+    }
+
+    async fn get_known_hosts(&self, name: &str, namespace: &str) -> Result<String> {
+        let client = Client::try_default().await?;
+        let secrets: Api<Secret> = Api::namespaced(client, namespace);
+        let secret = secrets.get(name).await?;
+
+        let secret_data = secret.data.context("Failed to read the data section")?;
+
+        // `ssh_known_hosts` is what Argo CD and Flux name it.
+        let encoded = secret_data
+            .get("known_hosts")
+            .or_else(|| secret_data.get("ssh_known_hosts"))
+            .context("Failed to read field: known_hosts in data, consider recreating the secret with kubectl create secret generic name --from-file=known_hosts=...")?;
+
+        String::from_utf8(encoded.0.clone()).context("Failed to convert known_hosts to string")
+    }
+
     async fn get_registry_auth(
         &self,
         secret_name: &str,
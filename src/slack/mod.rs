@@ -0,0 +1,3 @@
+#[allow(clippy::module_inception)]
+mod slack;
+pub use slack::*;
@@ -0,0 +1,221 @@
+use crate::notifications::Notification;
+use anyhow::{Context, Result, bail};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+
+/// How far a request's `X-Slack-Request-Timestamp` may be from our clock
+/// before it is refused as a possible replay.
+pub const MAX_SIGNATURE_AGE_SECS: i64 = 300;
+
+pub const SIGNATURE_HEADER: &str = "x-slack-signature";
+pub const TIMESTAMP_HEADER: &str = "x-slack-request-timestamp";
+
+const APPROVE_ACTION: &str = "approve";
+const REJECT_ACTION: &str = "reject";
+
+/// Signing secret of the Slack app whose interactivity points at
+/// `/slack/interactions`, from `GITOPS_SLACK_SIGNING_SECRET`. Approval
+/// notifications only carry buttons when it is set.
+pub fn slack_signing_secret() -> Option<String> {
+    std::env::var("GITOPS_SLACK_SIGNING_SECRET")
+        .ok()
+        .filter(|secret| !secret.trim().is_empty())
+}
+
+/// Webhook payload announcing pending change `id` with `message`, plus
+/// Approve and Reject buttons. `text` stays as the fallback for clients and
+/// Slack-compatible receivers that don't render blocks.
+pub fn approval_payload(message: &str, id: &str) -> serde_json::Value {
+    serde_json::json!({
+        "text": message,
+        "blocks": [
+            {
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": message },
+            },
+            {
+                "type": "actions",
+                "block_id": format!("approval-{}", id),
+                "elements": [
+                    {
+                        "type": "button",
+                        "action_id": APPROVE_ACTION,
+                        "style": "primary",
+                        "text": { "type": "plain_text", "text": "Approve" },
+                        "value": id,
+                    },
+                    {
+                        "type": "button",
+                        "action_id": REJECT_ACTION,
+                        "style": "danger",
+                        "text": { "type": "plain_text", "text": "Reject" },
+                        "value": id,
+                    },
+                ],
+            },
+        ],
+    })
+}
+
+/// Block Kit payload for `notification`: its text, then the app, namespace,
+/// tag change and commit link as fields, in an attachment colored by its
+/// status. `text` stays as the fallback for clients that don't render blocks.
+pub fn notification_payload(notification: &Notification) -> serde_json::Value {
+    let mut fields = vec![];
+    let mut field = |name: &str, value: String| {
+        fields.push(serde_json::json!({
+            "type": "mrkdwn",
+            "text": format!("*{}*\n{}", name, value),
+        }));
+    };
+    if !notification.app.is_empty() {
+        field("App", notification.app.clone());
+    }
+    if !notification.namespace.is_empty() {
+        field("Namespace", notification.namespace.clone());
+    }
+    if let Some(to_tag) = &notification.to_tag {
+        let tag = match &notification.from_tag {
+            Some(from_tag) if from_tag != to_tag => format!("`{}` → `{}`", from_tag, to_tag),
+            _ => format!("`{}`", to_tag),
+        };
+        field("Tag", tag);
+    }
+    if let Some(url) = &notification.commit_url {
+        field("Commit", format!("<{}|View commit>", url));
+    }
+
+    let mut blocks = vec![serde_json::json!({
+        "type": "section",
+        "text": { "type": "mrkdwn", "text": notification.text },
+    })];
+    if !fields.is_empty() {
+        blocks.push(serde_json::json!({ "type": "section", "fields": fields }));
+    }
+    serde_json::json!({
+        "text": notification.text,
+        "attachments": [
+            {
+                "color": notification.status.color(),
+                "blocks": blocks,
+            },
+        ],
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Check a request against Slack's signing scheme: `signature` must be
+/// `v0=` followed by the hex HMAC-SHA256, keyed with `secret`, of
+/// `v0:{timestamp}:{body}`, and `timestamp` within
+/// [`MAX_SIGNATURE_AGE_SECS`] of `now` (Unix seconds).
+pub fn verify_signature(
+    secret: &str,
+    timestamp: &str,
+    body: &[u8],
+    signature: &str,
+    now: i64,
+) -> Result<()> {
+    let sent: i64 = timestamp
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid Slack request timestamp '{}'", timestamp))?;
+    if (now - sent).abs() > MAX_SIGNATURE_AGE_SECS {
+        bail!("Slack request timestamp {} is too old", sent);
+    }
+
+    let key = PKey::hmac(secret.as_bytes())?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(format!("v0:{}:", timestamp.trim()).as_bytes())?;
+    signer.update(body)?;
+    let expected = format!("v0={}", hex(&signer.sign_to_vec()?));
+
+    if expected.len() != signature.len()
+        || !openssl::memcmp::eq(expected.as_bytes(), signature.as_bytes())
+    {
+        bail!("Slack request signature does not match");
+    }
+    Ok(())
+}
+
+/// A click on one of the buttons of [`approval_payload`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApprovalClick {
+    pub change_id: String,
+    pub approve: bool,
+    /// Slack user name (or id) of whoever clicked.
+    pub user: String,
+    /// Where to post the outcome, replacing the original message.
+    pub response_url: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct InteractionForm {
+    payload: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Interaction {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    user: InteractionUser,
+    #[serde(default)]
+    actions: Vec<InteractionAction>,
+    response_url: Option<String>,
+}
+
+#[derive(serde::Deserialize, Default)]
+struct InteractionUser {
+    #[serde(default)]
+    id: String,
+    username: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct InteractionAction {
+    action_id: String,
+    #[serde(default)]
+    value: String,
+}
+
+/// Parse the form-encoded body Slack posts when someone clicks a button.
+/// `None` for other interactions, which the operator doesn't handle.
+pub fn parse_interaction(body: &[u8]) -> Result<Option<ApprovalClick>> {
+    let form: InteractionForm =
+        serde_urlencoded::from_bytes(body).context("Invalid Slack interaction form")?;
+    let interaction: Interaction =
+        serde_json::from_str(&form.payload).context("Invalid Slack interaction payload")?;
+    if interaction.kind != "block_actions" {
+        return Ok(None);
+    }
+
+    let Some(action) = interaction.actions.iter().find(|action| {
+        matches!(action.action_id.as_str(), APPROVE_ACTION | REJECT_ACTION)
+            && !action.value.is_empty()
+    }) else {
+        return Ok(None);
+    };
+    Ok(Some(ApprovalClick {
+        change_id: action.value.clone(),
+        approve: action.action_id == APPROVE_ACTION,
+        user: interaction
+            .user
+            .username
+            .filter(|name| !name.is_empty())
+            .unwrap_or(interaction.user.id),
+        response_url: interaction.response_url,
+    }))
+}
+
+/// Payload replacing the message the buttons were on with `text`, so they
+/// can't be clicked twice.
+pub fn replacement_payload(text: &str) -> serde_json::Value {
+    serde_json::json!({
+        "replace_original": true,
+        "text": text,
+    })
+}
@@ -0,0 +1,383 @@
+use crate::history::history_path;
+use crate::traits::StateStore;
+use anyhow::{Context, Result, bail};
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::api::{Api, ObjectMeta, Patch, PatchParams};
+use rusqlite::{Connection, OptionalExtension, params};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::{info, warn};
+
+const DEFAULT_STATE_NAMESPACE: &str = "gitops-operator";
+const DEFAULT_STATE_CONFIGMAP: &str = "gitops-operator-state";
+
+/// Where the operator keeps its state.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StateBackend {
+    /// Lost on restart; nothing to provision.
+    Memory,
+    /// A SQLite file, durable if it lives on a persistent volume.
+    #[default]
+    Sqlite,
+    /// A ConfigMap in the operator's namespace, durable without a volume.
+    Kubernetes,
+}
+
+impl FromStr for StateBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "memory" => Ok(Self::Memory),
+            "sqlite" => Ok(Self::Sqlite),
+            "kubernetes" | "configmap" => Ok(Self::Kubernetes),
+            other => bail!(
+                "Unknown state backend '{}' (expected memory, sqlite or kubernetes)",
+                other
+            ),
+        }
+    }
+}
+
+/// Which [`StateBackend`] to use and where it keeps its data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateSettings {
+    /// `GITOPS_STATE_BACKEND` (default `sqlite`).
+    pub backend: StateBackend,
+    /// `GITOPS_STATE_PATH`, the SQLite file (default: the history database).
+    pub path: String,
+    /// `GITOPS_STATE_NAMESPACE` (default `gitops-operator`).
+    pub namespace: String,
+    /// `GITOPS_STATE_CONFIGMAP` (default `gitops-operator-state`).
+    pub configmap: String,
+}
+
+impl StateSettings {
+    pub fn from_env() -> Self {
+        let backend = match std::env::var("GITOPS_STATE_BACKEND") {
+            Ok(value) => value.parse().unwrap_or_else(|e| {
+                warn!("{:#}; using the default", e);
+                StateBackend::default()
+            }),
+            Err(_) => StateBackend::default(),
+        };
+
+        Self {
+            backend,
+            path: std::env::var("GITOPS_STATE_PATH").unwrap_or_else(|_| history_path()),
+            namespace: std::env::var("GITOPS_STATE_NAMESPACE")
+                .unwrap_or_else(|_| DEFAULT_STATE_NAMESPACE.to_string()),
+            configmap: std::env::var("GITOPS_STATE_CONFIGMAP")
+                .unwrap_or_else(|_| DEFAULT_STATE_CONFIGMAP.to_string()),
+        }
+    }
+}
+
+type Buckets = BTreeMap<String, BTreeMap<String, String>>;
+
+fn sorted_entries(buckets: &Buckets, bucket: &str) -> Vec<(String, String)> {
+    buckets
+        .get(bucket)
+        .map(|entries| {
+            entries
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// State kept in process memory.
+#[derive(Default)]
+pub struct MemoryState {
+    buckets: Mutex<Buckets>,
+}
+
+impl MemoryState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StateStore for MemoryState {
+    fn get(&self, bucket: &str, key: &str) -> Result<Option<String>> {
+        let buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(buckets.get(bucket).and_then(|b| b.get(key)).cloned())
+    }
+
+    fn put(&self, bucket: &str, key: &str, value: &str) -> Result<()> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        buckets
+            .entry(bucket.to_string())
+            .or_default()
+            .insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn remove(&self, bucket: &str, key: &str) -> Result<()> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entries) = buckets.get_mut(bucket) {
+            entries.remove(key);
+        }
+        Ok(())
+    }
+
+    fn entries(&self, bucket: &str) -> Result<Vec<(String, String)>> {
+        let buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(sorted_entries(&buckets, bucket))
+    }
+}
+
+/// State kept in a SQLite table (alongside the history by default).
+pub struct SqliteState {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteState {
+    /// Open (creating if needed) the database at `path`.
+    pub fn open(path: &str) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open state database at {}", path))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS operator_state (
+                bucket TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (bucket, key)
+            );",
+        )
+        .context("Failed to initialise state schema")?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>> {
+        self.conn
+            .lock()
+            .map_err(|_| anyhow::anyhow!("state store lock poisoned"))
+    }
+}
+
+impl StateStore for SqliteState {
+    fn get(&self, bucket: &str, key: &str) -> Result<Option<String>> {
+        self.conn()?
+            .query_row(
+                "SELECT value FROM operator_state WHERE bucket = ?1 AND key = ?2",
+                params![bucket, key],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Failed to read state")
+    }
+
+    fn put(&self, bucket: &str, key: &str, value: &str) -> Result<()> {
+        self.conn()?
+            .execute(
+                "INSERT INTO operator_state (bucket, key, value) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (bucket, key) DO UPDATE SET value = excluded.value",
+                params![bucket, key, value],
+            )
+            .context("Failed to write state")?;
+        Ok(())
+    }
+
+    fn remove(&self, bucket: &str, key: &str) -> Result<()> {
+        self.conn()?
+            .execute(
+                "DELETE FROM operator_state WHERE bucket = ?1 AND key = ?2",
+                params![bucket, key],
+            )
+            .context("Failed to write state")?;
+        Ok(())
+    }
+
+    fn entries(&self, bucket: &str) -> Result<Vec<(String, String)>> {
+        let conn = self.conn()?;
+        let mut stmt =
+            conn.prepare("SELECT key, value FROM operator_state WHERE bucket = ?1 ORDER BY key")?;
+        let rows = stmt.query_map(params![bucket], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .context("Failed to read state")
+    }
+}
+
+/// The ConfigMap `data` holding `buckets`: one key per bucket, its entries
+/// as a JSON object (ConfigMap keys can't hold the `/` of `namespace/name`).
+pub fn encode_buckets(buckets: &Buckets) -> BTreeMap<String, String> {
+    buckets
+        .iter()
+        .filter(|(_, entries)| !entries.is_empty())
+        .map(|(bucket, entries)| {
+            let json = serde_json::to_string(entries).unwrap_or_else(|_| "{}".to_string());
+            (bucket.clone(), json)
+        })
+        .collect()
+}
+
+/// The inverse of [`encode_buckets`]; buckets that don't parse are dropped.
+pub fn decode_buckets(data: &BTreeMap<String, String>) -> Buckets {
+    data.iter()
+        .filter_map(|(bucket, json)| match serde_json::from_str(json) {
+            Ok(entries) => Some((bucket.clone(), entries)),
+            Err(e) => {
+                warn!("Ignoring unreadable state bucket {}: {}", bucket, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// State kept in a ConfigMap. Reads are served from a copy loaded at
+/// startup; every write updates the copy and then applies the whole
+/// ConfigMap in the background, so callers never wait on the API server.
+/// Meant for small state: a ConfigMap holds at most 1 MiB.
+pub struct ConfigMapState {
+    api: Api<ConfigMap>,
+    name: String,
+    buckets: Arc<Mutex<Buckets>>,
+    writes: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl ConfigMapState {
+    /// Load the ConfigMap `namespace/name` (it is created on the first write).
+    pub async fn load(client: kube::Client, namespace: &str, name: &str) -> Result<Self> {
+        let api: Api<ConfigMap> = Api::namespaced(client, namespace);
+        let existing = api
+            .get_opt(name)
+            .await
+            .with_context(|| format!("Failed to read state ConfigMap {}/{}", namespace, name))?;
+        let buckets = existing
+            .and_then(|cm| cm.data)
+            .map(|data| decode_buckets(&data))
+            .unwrap_or_default();
+
+        Ok(Self {
+            api,
+            name: name.to_string(),
+            buckets: Arc::new(Mutex::new(buckets)),
+            writes: Arc::new(tokio::sync::Mutex::new(())),
+        })
+    }
+
+    /// Apply the current copy to the cluster. Writes are serialized and each
+    /// takes its snapshot once it holds the lock, so the last one applied
+    /// always carries the latest state.
+    fn persist(&self) {
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            warn!("No runtime to persist state ConfigMap {}", self.name);
+            return;
+        };
+        let (api, name) = (self.api.clone(), self.name.clone());
+        let (buckets, writes) = (self.buckets.clone(), self.writes.clone());
+
+        handle.spawn(async move {
+            let _guard = writes.lock().await;
+            let data = {
+                let buckets = buckets.lock().unwrap_or_else(|e| e.into_inner());
+                encode_buckets(&buckets)
+            };
+            let configmap = ConfigMap {
+                metadata: ObjectMeta {
+                    name: Some(name.clone()),
+                    ..Default::default()
+                },
+                data: Some(data),
+                ..Default::default()
+            };
+            let params = PatchParams::apply("gitops-operator").force();
+            if let Err(e) = api.patch(&name, &params, &Patch::Apply(&configmap)).await {
+                warn!("Failed to persist state ConfigMap {}: {}", name, e);
+            }
+        });
+    }
+}
+
+impl StateStore for ConfigMapState {
+    fn get(&self, bucket: &str, key: &str) -> Result<Option<String>> {
+        let buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(buckets.get(bucket).and_then(|b| b.get(key)).cloned())
+    }
+
+    fn put(&self, bucket: &str, key: &str, value: &str) -> Result<()> {
+        {
+            let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+            buckets
+                .entry(bucket.to_string())
+                .or_default()
+                .insert(key.to_string(), value.to_string());
+        }
+        self.persist();
+        Ok(())
+    }
+
+    fn remove(&self, bucket: &str, key: &str) -> Result<()> {
+        let removed = {
+            let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+            buckets
+                .get_mut(bucket)
+                .and_then(|entries| entries.remove(key))
+                .is_some()
+        };
+        if removed {
+            self.persist();
+        }
+        Ok(())
+    }
+
+    fn entries(&self, bucket: &str) -> Result<Vec<(String, String)>> {
+        let buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(sorted_entries(&buckets, bucket))
+    }
+}
+
+/// Open the store `settings` select.
+pub async fn open_state_store(
+    settings: &StateSettings,
+    client: kube::Client,
+) -> Result<Arc<dyn StateStore>> {
+    let store: Arc<dyn StateStore> = match settings.backend {
+        StateBackend::Memory => Arc::new(MemoryState::new()),
+        StateBackend::Sqlite => Arc::new(SqliteState::open(&settings.path)?),
+        StateBackend::Kubernetes => {
+            Arc::new(ConfigMapState::load(client, &settings.namespace, &settings.configmap).await?)
+        }
+    };
+    info!("Keeping operator state in {:?} storage", settings.backend);
+    Ok(store)
+}
+
+/// Run `f` against `state` on tokio's blocking pool. The SQLite store blocks
+/// on disk I/O and on its connection lock, neither of which may hold up a
+/// runtime worker, so async code reaches the state through here.
+pub async fn blocking<S, T, F>(state: &Arc<S>, f: F) -> T
+where
+    S: ?Sized + Send + Sync + 'static,
+    T: Send + 'static,
+    F: FnOnce(&S) -> T + Send + 'static,
+{
+    let state = state.clone();
+    match tokio::task::spawn_blocking(move || f(&state)).await {
+        Ok(value) => value,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+static STATE: OnceLock<Arc<dyn StateStore>> = OnceLock::new();
+
+/// Install the store opened at startup; only the first call has an effect.
+pub fn set_state_store(store: Arc<dyn StateStore>) {
+    let _ = STATE.set(store);
+}
+
+/// The process-wide state store (in memory until one is installed, and in
+/// tests).
+pub fn state_store() -> Arc<dyn StateStore> {
+    static UNSET: OnceLock<Arc<dyn StateStore>> = OnceLock::new();
+    STATE
+        .get()
+        .unwrap_or_else(|| UNSET.get_or_init(|| Arc::new(MemoryState::new())))
+        .clone()
+}
@@ -0,0 +1,92 @@
+use crate::state::{MemoryState, state_store};
+use crate::traits::StateStore;
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::warn;
+
+/// Annotation the operator sets on a Deployment it suspended, with when
+/// (RFC 3339, UTC). Suspended deployments are skipped until it is removed,
+/// either by `POST /resume/{namespace}/{name}` or by hand.
+pub const SUSPENDED_AT_ANNOTATION: &str = "gitops.operator.suspended-at";
+
+/// Annotation with why the operator suspended the Deployment.
+pub const SUSPENDED_REASON_ANNOTATION: &str = "gitops.operator.suspended-reason";
+
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+
+/// How many consecutive push failures suspend a deployment, from
+/// `GITOPS_SUSPEND_AFTER_FAILURES` (default 5, 0 never suspends).
+pub fn failure_threshold() -> u32 {
+    std::env::var("GITOPS_SUSPEND_AFTER_FAILURES")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_FAILURE_THRESHOLD)
+}
+
+const FAILURES_BUCKET: &str = "failures";
+
+/// Consecutive push failures per deployment (`namespace/name`), kept in a
+/// [`StateStore`]; the suspension itself is persisted as annotations.
+pub struct FailureCounter {
+    store: Arc<dyn StateStore>,
+    // Serializes the read-modify-write of a streak.
+    updates: Mutex<()>,
+}
+
+impl Default for FailureCounter {
+    fn default() -> Self {
+        Self::with_store(Arc::new(MemoryState::new()))
+    }
+}
+
+impl FailureCounter {
+    /// A counter keeping its streaks in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A counter keeping its streaks in `store`.
+    pub fn with_store(store: Arc<dyn StateStore>) -> Self {
+        Self {
+            store,
+            updates: Mutex::new(()),
+        }
+    }
+
+    /// Count another failure for `key`, returning the new streak length.
+    pub fn record_failure(&self, key: &str) -> u32 {
+        let _guard = self.updates.lock().unwrap_or_else(|e| e.into_inner());
+        let count = self.count(key) + 1;
+        if let Err(e) = self.store.put(FAILURES_BUCKET, key, &count.to_string()) {
+            warn!("Failed to store the failure streak of {}: {:#}", key, e);
+        }
+        count
+    }
+
+    /// End the failure streak for `key`.
+    pub fn reset(&self, key: &str) {
+        let _guard = self.updates.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = self.store.remove(FAILURES_BUCKET, key) {
+            warn!("Failed to clear the failure streak of {}: {:#}", key, e);
+        }
+    }
+
+    /// Current failure streak for `key`.
+    pub fn count(&self, key: &str) -> u32 {
+        match self.store.get(FAILURES_BUCKET, key) {
+            Ok(count) => count.and_then(|c| c.parse().ok()).unwrap_or(0),
+            Err(e) => {
+                warn!("Failed to read the failure streak of {}: {:#}", key, e);
+                0
+            }
+        }
+    }
+}
+
+/// Process-wide failure counts shared by every reconcile trigger, kept in the
+/// operator's [`state_store`].
+pub fn failure_counter() -> Arc<FailureCounter> {
+    static COUNTER: OnceLock<Arc<FailureCounter>> = OnceLock::new();
+    COUNTER
+        .get_or_init(|| Arc::new(FailureCounter::with_store(state_store())))
+        .clone()
+}
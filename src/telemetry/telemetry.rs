@@ -1,15 +1,20 @@
+use arc_swap::{ArcSwap, ArcSwapOption};
 use opentelemetry_otlp::{MetricExporter, WithExportConfig};
 
 use opentelemetry::global;
-use opentelemetry::trace::TracerProvider;
+use opentelemetry::trace::{SpanId, Status, TraceId, TracerProvider};
 use opentelemetry_otlp::SpanExporter;
 use opentelemetry_sdk::{
     Resource,
-    metrics::{MeterProviderBuilder, PeriodicReader},
-    trace::Sampler,
+    error::OTelSdkResult,
+    metrics::{MeterProviderBuilder, PeriodicReader, SdkMeterProvider},
+    trace::{BatchSpanProcessor, Sampler, Span, SpanData, SpanProcessor},
 };
 
-use opentelemetry::KeyValue;
+use opentelemetry::{Context, KeyValue};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 use tracing_bunyan_formatter::BunyanFormattingLayer;
 use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -22,6 +27,36 @@ pub fn otlp_endpoint() -> String {
         .unwrap_or_else(|_| DEFAULT_OTLP_ENDPOINT.to_string())
 }
 
+/// What the operator exports over OTLP and where. Read from the environment
+/// at startup and replaceable at runtime with `PUT /telemetry`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct TelemetrySettings {
+    /// OTLP (gRPC) endpoint receiving traces and metrics.
+    pub endpoint: String,
+    /// Export traces (`GITOPS_OTEL_TRACES`, default true).
+    #[serde(default = "enabled")]
+    pub traces: bool,
+    /// Export metrics (`GITOPS_OTEL_METRICS`, default true).
+    #[serde(default = "enabled")]
+    pub metrics: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl TelemetrySettings {
+    pub fn from_env() -> Self {
+        let toggle = |key: &str| std::env::var(key).map_or(true, |v| v.trim() != "false");
+
+        Self {
+            endpoint: otlp_endpoint(),
+            traces: toggle("GITOPS_OTEL_TRACES"),
+            metrics: toggle("GITOPS_OTEL_METRICS"),
+        }
+    }
+}
+
 pub fn resource(name: String) -> Resource {
     Resource::builder()
         .with_service_name(name)
@@ -30,6 +65,363 @@ pub fn resource(name: String) -> Resource {
         .build()
 }
 
+/// Span attribute carrying a reconcile's status (`success`, `failure`, ...).
+pub const RECONCILE_STATUS_ATTRIBUTE: &str = "reconcile.status";
+
+/// Span attribute carrying a reconcile's action (`patched`, `up_to_date`, ...).
+pub const RECONCILE_ACTION_ATTRIBUTE: &str = "reconcile.action";
+
+/// Traces still waiting for their root span are capped; past this the oldest
+/// is exported without a decision rather than held indefinitely.
+const MAX_PENDING_TRACES: usize = 1024;
+
+/// Decisions are remembered for spans that end after their root (e.g. from
+/// spawned tasks), up to this many traces.
+const MAX_DECIDED_TRACES: usize = 4096;
+
+/// Which traces to keep, from `GITOPS_TRACE_SAMPLE_RATIO` and
+/// `GITOPS_TRACE_UP_TO_DATE_RATIO` (both default to `1.0`, keep everything).
+/// Traces with a failed reconcile (or an error span) are always kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplingRules {
+    /// Share of traces kept when no more specific rule applies.
+    pub ratio: f64,
+    /// Share of traces kept when every reconcile in them was up to date.
+    pub up_to_date_ratio: f64,
+}
+
+impl SamplingRules {
+    pub fn from_env() -> Self {
+        let ratio = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .map(|r| r.clamp(0.0, 1.0))
+                .unwrap_or(1.0)
+        };
+
+        Self {
+            ratio: ratio("GITOPS_TRACE_SAMPLE_RATIO"),
+            up_to_date_ratio: ratio("GITOPS_TRACE_UP_TO_DATE_RATIO"),
+        }
+    }
+
+    /// Decide whether to export a finished trace from all of its spans. Ratio
+    /// decisions are derived from the trace id, so they are stable per trace.
+    pub fn keep(&self, trace_id: TraceId, spans: &[SpanData]) -> bool {
+        let attribute = |span: &SpanData, key: &str| {
+            span.attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.as_str().into_owned())
+        };
+
+        let failed = spans.iter().any(|span| {
+            matches!(span.status, Status::Error { .. })
+                || attribute(span, RECONCILE_STATUS_ATTRIBUTE).as_deref() == Some("failure")
+        });
+        if failed {
+            return true;
+        }
+
+        let actions: Vec<String> = spans
+            .iter()
+            .filter_map(|span| attribute(span, RECONCILE_ACTION_ATTRIBUTE))
+            .collect();
+        let ratio = if !actions.is_empty() && actions.iter().all(|a| a == "up_to_date") {
+            self.up_to_date_ratio
+        } else {
+            self.ratio
+        };
+
+        ratio_keeps(trace_id, ratio)
+    }
+}
+
+/// Same rule as the SDK's `TraceIdRatioBased` sampler: keep the trace when
+/// the low 63 bits of its id fall under `ratio` of the range.
+fn ratio_keeps(trace_id: TraceId, ratio: f64) -> bool {
+    if ratio >= 1.0 {
+        return true;
+    }
+    if ratio <= 0.0 {
+        return false;
+    }
+
+    let bytes = trace_id.to_bytes();
+    let low = u64::from_be_bytes(bytes[8..16].try_into().unwrap_or_default()) >> 1;
+    low < (ratio * (1u64 << 63) as f64) as u64
+}
+
+#[derive(Debug, Default)]
+struct TailState {
+    pending: HashMap<TraceId, Vec<SpanData>>,
+    pending_order: VecDeque<TraceId>,
+    decided: HashMap<TraceId, bool>,
+    decided_order: VecDeque<TraceId>,
+}
+
+impl TailState {
+    fn remember(&mut self, trace_id: TraceId, keep: bool) {
+        self.decided.insert(trace_id, keep);
+        self.decided_order.push_back(trace_id);
+        while self.decided_order.len() > MAX_DECIDED_TRACES {
+            if let Some(old) = self.decided_order.pop_front() {
+                self.decided.remove(&old);
+            }
+        }
+    }
+}
+
+/// Tail-like sampling: spans are held per trace until the local root span
+/// ends, then the whole trace is passed to `inner` or dropped according to
+/// [`SamplingRules`], so child spans always follow their root's decision.
+#[derive(Debug)]
+pub struct TailSamplingProcessor<P> {
+    rules: SamplingRules,
+    inner: P,
+    state: Mutex<TailState>,
+}
+
+impl<P: SpanProcessor> TailSamplingProcessor<P> {
+    pub fn new(rules: SamplingRules, inner: P) -> Self {
+        Self {
+            rules,
+            inner,
+            state: Mutex::new(TailState::default()),
+        }
+    }
+
+    fn decide(&self, span: SpanData) -> Vec<SpanData> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let trace_id = span.span_context.trace_id();
+
+        if let Some(&keep) = state.decided.get(&trace_id) {
+            return if keep { vec![span] } else { vec![] };
+        }
+
+        if span.parent_span_id == SpanId::INVALID || span.parent_span_is_remote {
+            let mut spans = state.pending.remove(&trace_id).unwrap_or_default();
+            state.pending_order.retain(|id| *id != trace_id);
+            spans.push(span);
+
+            let keep = self.rules.keep(trace_id, &spans);
+            state.remember(trace_id, keep);
+            return if keep { spans } else { vec![] };
+        }
+
+        if !state.pending.contains_key(&trace_id) {
+            state.pending_order.push_back(trace_id);
+        }
+        state.pending.entry(trace_id).or_default().push(span);
+
+        if state.pending_order.len() > MAX_PENDING_TRACES
+            && let Some(oldest) = state.pending_order.pop_front()
+        {
+            state.remember(oldest, true);
+            return state.pending.remove(&oldest).unwrap_or_default();
+        }
+
+        vec![]
+    }
+}
+
+impl<P: SpanProcessor> SpanProcessor for TailSamplingProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.inner.on_start(span, cx);
+    }
+
+    fn on_end(&self, span: SpanData) {
+        for span in self.decide(span) {
+            self.inner.on_end(span);
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        // Traces whose root never ended are exported as-is rather than lost.
+        let pending: Vec<SpanData> = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.pending_order.clear();
+            state.pending.drain().flat_map(|(_, spans)| spans).collect()
+        };
+        for span in pending {
+            self.inner.on_end(span);
+        }
+
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+/// Span processor handing spans to whichever export pipeline is current.
+/// The tracer provider and the tracing layer are built once around it, so
+/// pipelines can be swapped underneath them without dropping a span.
+#[derive(Clone, Debug, Default)]
+pub struct ReloadableSpanProcessor {
+    current: Arc<ArcSwapOption<Box<dyn SpanProcessor>>>,
+    resource: Arc<Mutex<Option<Resource>>>,
+}
+
+impl ReloadableSpanProcessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send spans ending from now on to `processor` (or nowhere), returning
+    /// the one replaced so it can flush what it still holds.
+    pub fn replace(
+        &self,
+        processor: Option<Box<dyn SpanProcessor>>,
+    ) -> Option<Arc<Box<dyn SpanProcessor>>> {
+        let processor = processor.map(|mut processor| {
+            let resource = self.resource.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(resource) = resource.as_ref() {
+                processor.set_resource(resource);
+            }
+            Arc::new(processor)
+        });
+        self.current.swap(processor)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.current.load().is_some()
+    }
+}
+
+impl SpanProcessor for ReloadableSpanProcessor {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        if let Some(processor) = self.current.load().as_ref() {
+            processor.on_start(span, cx);
+        }
+    }
+
+    fn on_end(&self, span: SpanData) {
+        if let Some(processor) = self.current.load().as_ref() {
+            processor.on_end(span);
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        match self.current.load().as_ref() {
+            Some(processor) => processor.force_flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        match self.replace(None) {
+            Some(processor) => processor.shutdown_with_timeout(timeout),
+            None => Ok(()),
+        }
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        *self.resource.lock().unwrap_or_else(|e| e.into_inner()) = Some(resource.clone());
+    }
+}
+
+/// The export pipelines behind the operator's tracing subscriber. They are
+/// rebuilt by [`Telemetry::reload`] while the subscriber, the reflector
+/// cache and any reconcile in flight carry on.
+#[derive(Debug)]
+pub struct Telemetry {
+    name: String,
+    settings: ArcSwap<TelemetrySettings>,
+    spans: ReloadableSpanProcessor,
+    meters: Mutex<Option<SdkMeterProvider>>,
+}
+
+impl Telemetry {
+    /// Pipelines feeding `spans`, exporting nothing until the first reload.
+    pub fn new(name: String, spans: ReloadableSpanProcessor) -> Self {
+        Self {
+            name,
+            settings: ArcSwap::from_pointee(TelemetrySettings {
+                endpoint: otlp_endpoint(),
+                traces: false,
+                metrics: false,
+            }),
+            spans,
+            meters: Mutex::new(None),
+        }
+    }
+
+    pub fn settings(&self) -> TelemetrySettings {
+        self.settings.load().as_ref().clone()
+    }
+
+    /// Build pipelines for `settings` and switch to them. The replaced ones
+    /// flush what they hold in the background; when the new ones can't be
+    /// built, the current ones stay.
+    pub fn reload(&self, settings: TelemetrySettings) -> anyhow::Result<()> {
+        // Also keeps concurrent reloads from interleaving.
+        let mut meters = self.meters.lock().unwrap_or_else(|e| e.into_inner());
+
+        let endpoint = settings.endpoint.trim();
+        if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+            anyhow::bail!(
+                "Invalid OTLP endpoint '{}': must be an http(s) URL",
+                endpoint
+            );
+        }
+
+        let span_processor: Option<Box<dyn SpanProcessor>> = if settings.traces {
+            let span_exporter = SpanExporter::builder()
+                .with_tonic()
+                .with_endpoint(endpoint)
+                .build()?;
+            Some(Box::new(BatchSpanProcessor::builder(span_exporter).build()))
+        } else {
+            None
+        };
+
+        let mut meter_provider =
+            MeterProviderBuilder::default().with_resource(resource(self.name.clone()));
+        if settings.metrics {
+            let metrics_exporter = MetricExporter::builder()
+                .with_tonic()
+                .with_endpoint(endpoint)
+                .with_temporality(opentelemetry_sdk::metrics::Temporality::default())
+                .build()?;
+            let reader = PeriodicReader::builder(metrics_exporter)
+                .with_interval(std::time::Duration::from_secs(30))
+                .build();
+            meter_provider = meter_provider.with_reader(reader);
+        }
+        let meter_provider = meter_provider.build();
+
+        let old_spans = self.spans.replace(span_processor);
+        global::set_meter_provider(meter_provider.clone());
+        let old_meters = meters.replace(meter_provider);
+        self.settings.store(Arc::new(settings));
+
+        // Exporters may wait on the old endpoint, so not on the caller.
+        std::thread::spawn(move || {
+            if let Some(Err(e)) = old_spans.map(|spans| spans.shutdown()) {
+                tracing::warn!("Failed to flush the previous span exporter: {}", e);
+            }
+            if let Some(Err(e)) = old_meters.map(|meters| meters.shutdown()) {
+                tracing::warn!("Failed to flush the previous metric exporter: {}", e);
+            }
+        });
+        Ok(())
+    }
+}
+
+static TELEMETRY: OnceLock<Telemetry> = OnceLock::new();
+
+/// The operator's telemetry pipelines, once [`init_subscriber`] has run.
+pub fn telemetry() -> Option<&'static Telemetry> {
+    TELEMETRY.get()
+}
+
 pub fn init_subscriber(name: String, env_filter: String) {
     // Parse the env filter string
     let env_filter =
@@ -38,18 +430,17 @@ pub fn init_subscriber(name: String, env_filter: String) {
     // Formatting layer
     let formatting_layer = BunyanFormattingLayer::new(name.clone(), std::io::stdout);
 
-    // Set up OpenTelemetry tracer
-    let endpoint = otlp_endpoint();
-    let span_exporter = SpanExporter::builder()
-        .with_tonic()
-        .with_endpoint(&endpoint)
-        .build()
-        .unwrap();
-
+    // Set up OpenTelemetry tracer. Every span is recorded (respecting an
+    // incoming parent's decision) and the keep/drop decision is made once the
+    // whole trace has finished; the exporter behind it can be reloaded.
+    let spans = ReloadableSpanProcessor::new();
     let tracer_provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
-        .with_batch_exporter(span_exporter)
+        .with_span_processor(TailSamplingProcessor::new(
+            SamplingRules::from_env(),
+            spans.clone(),
+        ))
         .with_resource(resource(name.clone()))
-        .with_sampler(Sampler::AlwaysOn)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::AlwaysOn)))
         .build();
 
     // Get a tracer from the provider
@@ -62,24 +453,10 @@ pub fn init_subscriber(name: String, env_filter: String) {
     // Create the OpenTelemetry layer
     let telemetry_layer = tracing_opentelemetry::layer().with_tracer(tracer);
 
-    // Metrics setup
-    let metrics_exporter = MetricExporter::builder()
-        .with_tonic()
-        .with_endpoint(&endpoint)
-        .with_temporality(opentelemetry_sdk::metrics::Temporality::default())
-        .build()
-        .unwrap();
-
-    let reader = PeriodicReader::builder(metrics_exporter)
-        .with_interval(std::time::Duration::from_secs(30))
-        .build();
-
-    let meter_provider = MeterProviderBuilder::default()
-        .with_resource(resource(name.clone()))
-        .with_reader(reader)
-        .build();
-
-    global::set_meter_provider(meter_provider);
+    // Span and metric exporters
+    let pipelines = Telemetry::new(name, spans);
+    pipelines.reload(TelemetrySettings::from_env()).unwrap();
+    let _ = TELEMETRY.set(pipelines);
 
     // Create a tracing-subscriber registry with layers
     let registry = tracing_subscriber::registry()
@@ -0,0 +1,35 @@
+use std::collections::BTreeMap;
+
+/// Render `{name}` placeholders in `template` from `vars`.
+///
+/// Unknown placeholders are left untouched so a typo shows up verbatim in the
+/// output instead of silently disappearing. `{{` and `}}` escape literal braces.
+pub fn render(template: &str, vars: &BTreeMap<String, String>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find(['{', '}']) {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+
+        if let Some(after) = tail.strip_prefix("{{") {
+            out.push('{');
+            rest = after;
+        } else if let Some(after) = tail.strip_prefix("}}") {
+            out.push('}');
+            rest = after;
+        } else if let Some(after) = tail.strip_prefix('{')
+            && let Some(end) = after.find('}')
+            && let Some(value) = vars.get(&after[..end])
+        {
+            out.push_str(value);
+            rest = &after[end + 1..];
+        } else {
+            out.push_str(&tail[..1]);
+            rest = &tail[1..];
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
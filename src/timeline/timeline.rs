@@ -0,0 +1,196 @@
+use crate::admission::ANNOTATION_PREFIX;
+use crate::history::{HistoryRecord, now_rfc3339};
+use k8s_openapi::api::apps::v1::Deployment;
+use kube::ResourceExt;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Events remembered per deployment; older ones are dropped.
+pub const MAX_EVENTS: usize = 200;
+
+/// What happened to a deployment.
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineKind {
+    /// The Deployment's images changed in the cluster, as seen by the watch.
+    ImageObserved,
+    /// A reconcile attempt, from the history.
+    Reconcile,
+    /// A manifest commit was pushed.
+    ManifestCommit,
+    /// A notification was sent (or failed to be).
+    Notification,
+}
+
+/// One entry of a deployment's timeline.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct TimelineEvent {
+    /// RFC 3339 (UTC).
+    pub at: String,
+    pub kind: TimelineKind,
+    pub summary: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Outcome, for reconciles (`success`, `failure`, `skipped`) and
+    /// notifications (`sent`, `failed`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+impl TimelineEvent {
+    pub fn new(kind: TimelineKind, summary: impl Into<String>) -> Self {
+        Self {
+            at: now_rfc3339(),
+            kind,
+            summary: summary.into(),
+            from: None,
+            to: None,
+            status: None,
+        }
+    }
+
+    /// A reconcile attempt recorded in the history.
+    pub fn from_history(record: &HistoryRecord) -> Self {
+        Self {
+            at: record.finished_at.clone(),
+            kind: TimelineKind::Reconcile,
+            summary: format!("{}: {}", record.action, record.message),
+            from: record.from_sha.clone(),
+            to: record.to_sha.clone(),
+            status: Some(record.status.clone()),
+        }
+    }
+}
+
+/// `name=image` for each container of `deployment`'s pod template.
+fn images(deployment: &Deployment) -> Vec<String> {
+    deployment
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.template.spec.as_ref())
+        .map(|pod| {
+            pod.containers
+                .iter()
+                .map(|c| format!("{}={}", c.name, c.image.as_deref().unwrap_or_default()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[derive(Default)]
+struct Journal {
+    events: HashMap<String, VecDeque<TimelineEvent>>,
+    /// Images last seen per deployment, to tell changes apart.
+    images: HashMap<String, Vec<String>>,
+}
+
+/// Recent in-cluster image changes, manifest commits and notifications, per
+/// deployment (`namespace/name`). Reconcile attempts come from the history
+/// instead; [`merge`] puts both together.
+#[derive(Default)]
+pub struct Timeline {
+    journal: Mutex<Journal>,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, key: &str, event: TimelineEvent) {
+        let mut journal = self.journal.lock().unwrap_or_else(|e| e.into_inner());
+        let events = journal.events.entry(key.to_string()).or_default();
+        if events.len() == MAX_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    /// Note a Deployment seen by the watch, recording its images when they
+    /// changed since the last time. The first sighting only sets the
+    /// baseline, and deployments without `gitops.operator.*` annotations are
+    /// ignored.
+    pub fn observe(&self, deployment: &Deployment) {
+        let tracked = deployment
+            .annotations()
+            .keys()
+            .any(|key| key.starts_with(ANNOTATION_PREFIX));
+        let Some(namespace) = deployment.namespace().filter(|_| tracked) else {
+            return;
+        };
+        let key = format!("{}/{}", namespace, deployment.name_any());
+        let images = images(deployment);
+
+        let previous = {
+            let mut journal = self.journal.lock().unwrap_or_else(|e| e.into_inner());
+            match journal.images.insert(key.clone(), images.clone()) {
+                Some(previous) if previous != images => previous,
+                _ => return,
+            }
+        };
+        let changed: Vec<&String> = images.iter().filter(|i| !previous.contains(i)).collect();
+        let summary = format!(
+            "Images changed in the cluster: {}",
+            changed
+                .iter()
+                .map(|image| image.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        self.record(
+            &key,
+            TimelineEvent {
+                from: Some(previous.join(",")),
+                to: Some(images.join(",")),
+                ..TimelineEvent::new(TimelineKind::ImageObserved, summary)
+            },
+        );
+    }
+
+    /// The remembered events of `key`, oldest first.
+    pub fn events(&self, key: &str) -> Vec<TimelineEvent> {
+        let journal = self.journal.lock().unwrap_or_else(|e| e.into_inner());
+        journal
+            .events
+            .get(key)
+            .map(|events| events.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// `events` and the reconcile attempts in `history` in chronological order,
+/// keeping the `limit` most recent. Events at the same second keep the order
+/// they happened in, reconciles (which finish last) after the rest.
+pub fn merge(
+    history: &[HistoryRecord],
+    events: Vec<TimelineEvent>,
+    limit: usize,
+) -> Vec<TimelineEvent> {
+    let mut by_time: BTreeMap<(String, bool), Vec<TimelineEvent>> = BTreeMap::new();
+    for event in events {
+        by_time
+            .entry((event.at.clone(), false))
+            .or_default()
+            .push(event);
+    }
+    // The history comes newest first.
+    for record in history.iter().rev() {
+        let event = TimelineEvent::from_history(record);
+        by_time
+            .entry((event.at.clone(), true))
+            .or_default()
+            .push(event);
+    }
+
+    let merged: Vec<TimelineEvent> = by_time.into_values().flatten().collect();
+    let skip = merged.len().saturating_sub(limit);
+    merged.into_iter().skip(skip).collect()
+}
+
+/// Process-wide timeline, filled in by the watch and the processors.
+pub fn timeline() -> Arc<Timeline> {
+    static TIMELINE: OnceLock<Arc<Timeline>> = OnceLock::new();
+    TIMELINE.get_or_init(|| Arc::new(Timeline::new())).clone()
+}
@@ -1,5 +1,15 @@
+use crate::drift::RunningImage;
+use crate::git::{CloneStrategy, CommitAuthor, GitCredentials, HttpsToken};
+use crate::github_app::GitHubApp;
+use crate::maintenance_windows::MaintenanceWindow;
+use crate::notifications::{Notification, NotificationEndpoint};
+use crate::policy::Policy;
+use crate::pull_requests::{ChangeRequest, PullRequest};
 use anyhow::Result;
 use async_trait::async_trait;
+use k8s_openapi::api::apps::v1::Deployment;
+use std::collections::BTreeMap;
+use std::path::Path;
 
 #[cfg(test)]
 use mockall::automock;
@@ -11,12 +21,28 @@ pub trait SecretProvider: Send + Sync {
     /// Get the SSH key for git operations
     async fn get_ssh_key(&self, name: &str, namespace: &str) -> Result<String>;
 
-    /// Get the notification webhook URL
-    async fn get_notification_endpoint(&self, name: &str, namespace: &str) -> Result<String>;
+    /// Get the notification webhook URL and the headers to send it
+    async fn get_notification_endpoint(
+        &self,
+        name: &str,
+        namespace: &str,
+    ) -> Result<NotificationEndpoint>;
 
     /// Get a GitHub API token from a Kubernetes secret
     async fn get_github_token(&self, name: &str, namespace: &str) -> Result<String>;
 
+    /// Get the API token of a forge (GitLab, ...) from a Kubernetes secret
+    async fn get_forge_token(&self, name: &str, namespace: &str) -> Result<String>;
+
+    /// Get the token (and username) git uses over HTTPS
+    async fn get_https_token(&self, name: &str, namespace: &str) -> Result<HttpsToken>;
+
+    /// Get the ID and private key of a GitHub App
+    async fn get_github_app(&self, name: &str, namespace: &str) -> Result<GitHubApp>;
+
+    /// Get the known_hosts file SSH host keys are checked against
+    async fn get_known_hosts(&self, name: &str, namespace: &str) -> Result<String>;
+
     /// Get registry authentication credentials
     async fn get_registry_auth(
         &self,
@@ -32,6 +58,26 @@ pub trait SecretProvider: Send + Sync {
 pub trait ImageChecker: Send + Sync {
     /// Check if an image with the given tag exists
     async fn check_image(&self, image: &str, tag: &str) -> Result<bool>;
+
+    /// List every tag of the given image
+    async fn list_tags(&self, image: &str) -> Result<Vec<String>>;
+
+    /// The digest the registry serves for the given tag, `None` if it doesn't
+    /// exist or the registry can't tell
+    async fn image_digest(&self, _image: &str, _tag: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// The labels of the given image (`org.opencontainers.image.revision`,
+    /// ...), empty if it has none or the registry can't tell
+    async fn image_labels(&self, _image: &str, _tag: &str) -> Result<BTreeMap<String, String>> {
+        Ok(BTreeMap::new())
+    }
+    /// The registry that answered the last lookup, for checkers that try
+    /// several; `None` otherwise
+    fn answered_by(&self) -> Option<String> {
+        None
+    }
 }
 
 /// Factory trait for creating ImageChecker instances
@@ -51,7 +97,89 @@ pub trait ImageCheckerFactory: Send + Sync {
 #[async_trait]
 pub trait NotificationSender: Send + Sync {
     /// Send a notification message to the given endpoint
-    async fn send(&self, message: &str, endpoint: &str) -> Result<()>;
+    async fn send(&self, message: &str, endpoint: &NotificationEndpoint) -> Result<()>;
+
+    /// Send `notification`, formatted for the endpoint's provider where the
+    /// sender supports it; its text alone by default.
+    async fn send_notification(
+        &self,
+        notification: &Notification,
+        endpoint: &NotificationEndpoint,
+    ) -> Result<()> {
+        self.send(&notification.text, endpoint).await
+    }
+
+    /// Ask for a decision on pending change `id`, with Approve/Reject buttons
+    /// where the endpoint supports them; a plain message by default.
+    async fn send_approval_request(
+        &self,
+        message: &str,
+        _id: &str,
+        endpoint: &NotificationEndpoint,
+    ) -> Result<()> {
+        self.send(message, endpoint).await
+    }
+}
+
+/// Severity of a Kubernetes Event recorded by the operator
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventSeverity {
+    /// Something the operator did as expected (e.g. patched a manifest)
+    Normal,
+    /// Something went wrong and may need attention
+    Warning,
+}
+
+/// Trait for reporting operator activity back to the cluster
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait ClusterReporter: Send + Sync {
+    /// Record a Kubernetes Event on the Deployment `namespace/name`
+    async fn publish_event(
+        &self,
+        namespace: &str,
+        name: &str,
+        severity: EventSeverity,
+        reason: &str,
+        note: &str,
+    ) -> Result<()>;
+
+    /// Merge `annotations` into the metadata of the Deployment `namespace/name`
+    async fn annotate(
+        &self,
+        namespace: &str,
+        name: &str,
+        annotations: &BTreeMap<String, String>,
+    ) -> Result<()>;
+
+    /// Remove the annotations named in `keys` from the Deployment `namespace/name`
+    async fn remove_annotations(&self, namespace: &str, name: &str, keys: &[String]) -> Result<()>;
+
+    /// Read the live Deployment `namespace/name` from the API server,
+    /// bypassing the reflector cache; `None` if it no longer exists
+    async fn live_deployment(&self, namespace: &str, name: &str) -> Result<Option<Deployment>>;
+
+    /// The images the pods of the Deployment `namespace/name` run, from
+    /// their container statuses; none by default
+    async fn running_images(&self, _namespace: &str, _name: &str) -> Result<Vec<RunningImage>> {
+        Ok(vec![])
+    }
+}
+
+/// Trait for looking up the maintenance windows declared in the cluster
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait MaintenanceWindowSource: Send + Sync {
+    /// Every maintenance window currently declared, active or not
+    async fn windows(&self) -> Result<Vec<MaintenanceWindow>>;
+}
+
+/// Trait for looking up the promotion policies declared in the cluster
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait PolicySource: Send + Sync {
+    /// Every policy currently declared, whichever namespaces it applies to
+    async fn policies(&self) -> Result<Vec<Policy>>;
 }
 
 /// Status of a CI build for a given commit SHA
@@ -76,3 +204,103 @@ pub trait BuildStatusChecker: Send + Sync {
     /// Check if there is a CI build running for the given repository and commit SHA
     async fn check_build_status(&self, repo: &str, sha: &str) -> Result<BuildStatus>;
 }
+
+/// Trait for proposing manifest changes for review
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait PullRequestOpener: Send + Sync {
+    /// Open a pull request for `change` with the API `token`, or return the
+    /// one already open for its branch
+    async fn open_pull_request(&self, change: &ChangeRequest, token: &str) -> Result<PullRequest>;
+}
+
+/// Trait for the key/value storage behind the operator's state (failure
+/// streaks, history, pending approvals, pauses). Keys are grouped in buckets.
+#[cfg_attr(test, automock)]
+pub trait StateStore: Send + Sync {
+    /// The value stored under `key` in `bucket`, if any
+    fn get(&self, bucket: &str, key: &str) -> Result<Option<String>>;
+
+    /// Store `value` under `key` in `bucket`, replacing any previous value
+    fn put(&self, bucket: &str, key: &str, value: &str) -> Result<()>;
+
+    /// Remove `key` from `bucket` (a missing key is not an error)
+    fn remove(&self, bucket: &str, key: &str) -> Result<()>;
+
+    /// Every key and value in `bucket`, sorted by key
+    fn entries(&self, bucket: &str) -> Result<Vec<(String, String)>>;
+}
+
+/// Trait for the git operations a reconcile runs on its checkouts, so
+/// another implementation can stand in for libgit2. Errors are libgit2's,
+/// whatever the backend, so they classify the same way.
+#[cfg_attr(test, automock)]
+pub trait GitProvider: Send + Sync {
+    /// Name of the backend, for logs
+    fn name(&self) -> &'static str;
+
+    /// Check out `branch` of `url` at `path`, which doesn't exist yet
+    fn clone_repo(
+        &self,
+        url: &str,
+        path: &Path,
+        branch: &str,
+        credentials: &GitCredentials,
+        strategy: CloneStrategy,
+    ) -> Result<(), git2::Error>;
+
+    /// Fetch `branch` of `url` into the checkout at `path` and move the
+    /// checkout to it
+    fn fetch(
+        &self,
+        url: &str,
+        path: &Path,
+        branch: &str,
+        credentials: &GitCredentials,
+        strategy: CloneStrategy,
+    ) -> Result<(), git2::Error>;
+
+    /// Full id of the commit `branch` was at on origin when last fetched
+    /// into the checkout at `path`
+    fn resolve(&self, path: &Path, branch: &str) -> Result<String, git2::Error>;
+
+    /// Commit every change in the checkout at `path` on top of HEAD,
+    /// returning whether there was any
+    // mockall can't mock the elided lifetime.
+    #[allow(clippy::needless_lifetimes)]
+    fn commit<'a>(
+        &self,
+        path: &Path,
+        message: &str,
+        author: Option<&'a CommitAuthor>,
+    ) -> Result<bool, git2::Error>;
+
+    /// Push HEAD of the checkout at `path` to `branch` on origin. With
+    /// `force` whatever the branch holds is replaced (but protected branches
+    /// are pushed to normally); otherwise HEAD is rebased onto what others
+    /// pushed in the meantime and pushed again, up to `GITOPS_PUSH_RETRIES`
+    /// times.
+    fn push(
+        &self,
+        path: &Path,
+        branch: &str,
+        credentials: &GitCredentials,
+        force: bool,
+    ) -> Result<(), git2::Error>;
+
+    /// Clone `url` at `path`, or fetch into the checkout already there
+    fn sync(
+        &self,
+        url: &str,
+        path: &Path,
+        branch: &str,
+        credentials: &GitCredentials,
+        strategy: CloneStrategy,
+    ) -> Result<(), git2::Error> {
+        if path.exists() {
+            self.fetch(url, path, branch, credentials, strategy)
+        } else {
+            self.clone_repo(url, path, branch, credentials, strategy)
+        }
+    }
+}
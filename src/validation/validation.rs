@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, StatefulSet};
+use k8s_openapi::api::batch::v1::{CronJob, Job};
+use k8s_openapi::api::core::v1::{ConfigMap, Container, Pod, PodSpec, Secret, Service};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_yaml::Value;
+use std::fs;
+
+/// Whether manifests are validated before they are pushed when a deployment
+/// doesn't say, from `GITOPS_VALIDATE_MANIFESTS` (default false).
+pub fn validate_manifests_default() -> bool {
+    std::env::var("GITOPS_VALIDATE_MANIFESTS").is_ok_and(|value| value.trim() == "true")
+}
+
+/// Parse `value` as a `T`, the way the API server would decode it.
+fn decode<T: DeserializeOwned>(value: &Value) -> Result<T, String> {
+    serde_yaml::from_value(value.clone()).map_err(|e| e.to_string())
+}
+
+fn check_containers(field: &str, containers: &[Container], errors: &mut Vec<String>) {
+    for (i, container) in containers.iter().enumerate() {
+        let at = format!("{}[{}]", field, i);
+        if container.name.trim().is_empty() {
+            errors.push(format!("{}.name: must not be empty", at));
+        }
+        match container.image.as_deref().map(str::trim) {
+            None | Some("") => errors.push(format!("{}.image: must not be empty", at)),
+            Some(image) if image.contains(char::is_whitespace) => {
+                errors.push(format!("{}.image: invalid reference '{}'", at, image))
+            }
+            Some(image) if image.ends_with(':') || image.ends_with('@') => {
+                errors.push(format!("{}.image: missing tag in '{}'", at, image))
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+fn check_pod_spec(prefix: &str, spec: Option<&PodSpec>, errors: &mut Vec<String>) {
+    let Some(spec) = spec else {
+        errors.push(format!("{}: missing pod spec", prefix));
+        return;
+    };
+    if spec.containers.is_empty() {
+        errors.push(format!("{}.containers: must not be empty", prefix));
+    }
+    check_containers(&format!("{}.containers", prefix), &spec.containers, errors);
+    if let Some(init) = &spec.init_containers {
+        check_containers(&format!("{}.initContainers", prefix), init, errors);
+    }
+}
+
+/// Check one Kubernetes object against its schema: the type metadata and
+/// name every object needs, the fields of the kinds the operator knows
+/// decoding into their API types, and the containers of workloads.
+fn check_object(kind: &str, value: &Value, errors: &mut Vec<String>) {
+    let result = match kind {
+        "Deployment" => decode::<Deployment>(value).map(|d| {
+            let spec = d.spec.as_ref().and_then(|s| s.template.spec.as_ref());
+            check_pod_spec("spec.template.spec", spec, errors)
+        }),
+        "StatefulSet" => decode::<StatefulSet>(value).map(|s| {
+            let spec = s.spec.as_ref().and_then(|s| s.template.spec.as_ref());
+            check_pod_spec("spec.template.spec", spec, errors)
+        }),
+        "DaemonSet" => decode::<DaemonSet>(value).map(|d| {
+            let spec = d.spec.as_ref().and_then(|s| s.template.spec.as_ref());
+            check_pod_spec("spec.template.spec", spec, errors)
+        }),
+        "Job" => decode::<Job>(value).map(|j| {
+            let spec = j.spec.as_ref().and_then(|s| s.template.spec.as_ref());
+            check_pod_spec("spec.template.spec", spec, errors)
+        }),
+        "CronJob" => decode::<CronJob>(value).map(|c| {
+            let spec = c
+                .spec
+                .job_template
+                .spec
+                .as_ref()
+                .and_then(|s| s.template.spec.as_ref());
+            check_pod_spec("spec.jobTemplate.spec.template.spec", spec, errors)
+        }),
+        "Pod" => decode::<Pod>(value).map(|p| check_pod_spec("spec", p.spec.as_ref(), errors)),
+        "Service" => decode::<Service>(value).map(drop),
+        "ConfigMap" => decode::<ConfigMap>(value).map(drop),
+        "Secret" => decode::<Secret>(value).map(drop),
+        // Custom resources and other kinds only get the common checks.
+        _ => Ok(()),
+    };
+    if let Err(e) = result {
+        errors.push(e);
+    }
+}
+
+/// Validate every document of a manifest file's `content`, returning one
+/// message per problem (none when it is valid). Documents with `apiVersion`
+/// or `kind` are checked as Kubernetes objects; any other YAML (e.g. Helm
+/// values) only has to parse.
+pub fn validate_manifest(content: &str) -> Vec<String> {
+    let mut errors = vec![];
+    for (i, document) in serde_yaml::Deserializer::from_str(content).enumerate() {
+        let at = format!("document {}", i + 1);
+        let value = match Value::deserialize(document) {
+            Ok(value) => value,
+            Err(e) => {
+                // The parser can't resume after a syntax error.
+                errors.push(format!("{}: invalid YAML: {}", at, e));
+                break;
+            }
+        };
+        let Some(object) = value.as_mapping() else {
+            if !value.is_null() {
+                errors.push(format!("{}: expected a mapping", at));
+            }
+            continue;
+        };
+        if !object.contains_key("apiVersion") && !object.contains_key("kind") {
+            continue;
+        }
+
+        let field = |name: &str| value.get(name).and_then(Value::as_str);
+        let kind = field("kind").unwrap_or_default();
+        let name = value
+            .get("metadata")
+            .and_then(|m| m.get("name").or_else(|| m.get("generateName")))
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let at = match (kind, name) {
+            ("", _) | (_, "") => at,
+            _ => format!("{} ({} {})", at, kind, name),
+        };
+
+        let mut problems = vec![];
+        if field("apiVersion").is_none_or(str::is_empty) {
+            problems.push("apiVersion: required".to_string());
+        }
+        if kind.is_empty() {
+            problems.push("kind: required".to_string());
+        }
+        if name.is_empty() {
+            problems.push("metadata.name: required".to_string());
+        }
+        check_object(kind, &value, &mut problems);
+        errors.extend(problems.into_iter().map(|p| format!("{}: {}", at, p)));
+    }
+    errors
+}
+
+/// [`validate_manifest`] for the file at `path`.
+pub fn validate_file(path: &str) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+    Ok(validate_manifest(&content))
+}
@@ -0,0 +1,300 @@
+use anyhow::{Context, Result};
+use k8s_openapi::jiff::Timestamp;
+use k8s_openapi::jiff::civil::DateTime;
+use regex::Regex;
+use semver::{Version, VersionReq};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// The semver range a deployment follows (`gitops.operator.semver`), e.g.
+/// `~1.4` or `>=2.0, <3`. Prereleases only match a range that names one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SemverPolicy {
+    range: VersionReq,
+}
+
+impl SemverPolicy {
+    pub fn parse(range: &str) -> Result<Self> {
+        let range = VersionReq::parse(range.trim())
+            .with_context(|| format!("Invalid semver range '{}'", range))?;
+        Ok(Self { range })
+    }
+
+    /// Whether `tag` is a version within the range.
+    pub fn matches(&self, tag: &str) -> bool {
+        parse_tag(tag).is_some_and(|version| self.range.matches(&version))
+    }
+
+    /// The highest version among `tags` within the range, as the tag was
+    /// written in the registry (so `v1.4.2` keeps its `v`).
+    pub fn newest<'a>(&self, tags: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+        tags.into_iter()
+            .filter_map(|tag| Some((parse_tag(tag)?, tag)))
+            .filter(|(version, _)| self.range.matches(version))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, tag)| tag)
+    }
+}
+
+/// The version an image tag names, accepting a leading `v`. Tags that are
+/// not full `major.minor.patch` versions (`latest`, commit SHAs) are `None`.
+pub fn parse_tag(tag: &str) -> Option<Version> {
+    Version::parse(tag.strip_prefix('v').unwrap_or(tag)).ok()
+}
+
+/// How the tags selected by a [`PatternPolicy`] are ordered.
+#[derive(serde::Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TagSort {
+    /// As unsigned integers (build numbers).
+    Numeric,
+    /// Lexicographically.
+    #[default]
+    Alphabetical,
+    /// As points in time: Unix seconds, `YYYYMMDD`, `YYYYMMDDHHMMSS` or
+    /// `YYYYMMDDTHHMMSSZ` (UTC).
+    Timestamp,
+}
+
+impl FromStr for TagSort {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "numeric" => Ok(TagSort::Numeric),
+            "alphabetical" => Ok(TagSort::Alphabetical),
+            "timestamp" => Ok(TagSort::Timestamp),
+            other => anyhow::bail!(
+                "Invalid tag sort '{}'. Must be 'numeric', 'alphabetical' or 'timestamp'",
+                other
+            ),
+        }
+    }
+}
+
+/// A sort key extracted from a tag.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey<'a> {
+    Number(u64),
+    Text(&'a str),
+    Time(Timestamp),
+}
+
+/// Tags matching a regular expression (`gitops.operator.tag_pattern`),
+/// ordered by `sort` (`gitops.operator.tag_sort`). The key compared is the
+/// capture group named `sort`, else the first capture group, else the whole
+/// tag, so `^main-[0-9a-f]+-(\d+)$` orders `main-<sha>-<build>` tags by build.
+#[derive(Clone, Debug)]
+pub struct PatternPolicy {
+    pattern: Regex,
+    sort: TagSort,
+}
+
+impl PatternPolicy {
+    pub fn parse(pattern: &str, sort: TagSort) -> Result<Self> {
+        let pattern =
+            Regex::new(pattern).with_context(|| format!("Invalid tag pattern '{}'", pattern))?;
+        Ok(Self { pattern, sort })
+    }
+
+    fn sort_key<'a>(&self, tag: &'a str) -> Option<SortKey<'a>> {
+        let captures = self.pattern.captures(tag)?;
+        let key = captures
+            .name("sort")
+            .or_else(|| captures.get(1))
+            .map_or(tag, |m| m.as_str());
+
+        match self.sort {
+            TagSort::Numeric => key.parse().ok().map(SortKey::Number),
+            TagSort::Alphabetical => Some(SortKey::Text(key)),
+            TagSort::Timestamp => parse_timestamp(key).map(SortKey::Time),
+        }
+    }
+
+    /// The last of `tags` matching the pattern in `sort` order. Tags whose
+    /// key doesn't parse as a number or timestamp are left out.
+    pub fn newest<'a>(&self, tags: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+        tags.into_iter()
+            .filter_map(|tag| Some((self.sort_key(tag)?, tag)))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, tag)| tag)
+    }
+}
+
+/// A timestamp written in an image tag (which can't contain `:`).
+pub fn parse_timestamp(key: &str) -> Option<Timestamp> {
+    let civil = |format: &str, value: &str| {
+        DateTime::strptime(format, value)
+            .ok()?
+            .to_zoned(k8s_openapi::jiff::tz::TimeZone::UTC)
+            .ok()
+            .map(|zoned| zoned.timestamp())
+    };
+
+    if !key.bytes().all(|b| b.is_ascii_digit()) {
+        return civil("%Y%m%dT%H%M%S", key.trim_end_matches('Z'));
+    }
+    match key.len() {
+        8 => civil("%Y%m%d%H%M%S", &format!("{}000000", key)),
+        14 => civil("%Y%m%d%H%M%S", key),
+        _ => Timestamp::from_second(key.parse().ok()?).ok(),
+    }
+}
+
+/// How a deployment picks the tag to promote from the registry's tag list.
+#[derive(Clone, Debug)]
+pub enum TagPolicy {
+    Semver(SemverPolicy),
+    Pattern(PatternPolicy),
+}
+
+impl TagPolicy {
+    pub fn newest<'a>(&self, tags: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+        match self {
+            TagPolicy::Semver(policy) => policy.newest(tags),
+            TagPolicy::Pattern(policy) => policy.newest(tags),
+        }
+    }
+
+    /// What the policy selects, for messages.
+    pub fn describe(&self) -> String {
+        match self {
+            TagPolicy::Semver(policy) => format!("semver {}", policy.range),
+            TagPolicy::Pattern(policy) => format!("/{}/", policy.pattern.as_str()),
+        }
+    }
+}
+
+/// The git tags a deployment follows instead of a branch
+/// (`gitops.operator.observe_tag_pattern`): a glob such as `v*` or
+/// `release-*` (`*` and `?` wildcards), else a semver range such as `^1.4`,
+/// else the name of a single tag.
+#[derive(Clone, Debug)]
+pub enum GitTagPattern {
+    Glob(Regex),
+    Semver(SemverPolicy),
+}
+
+impl GitTagPattern {
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let pattern = pattern.trim();
+        if pattern.is_empty() {
+            anyhow::bail!("Empty tag pattern");
+        }
+        if !pattern.contains(['*', '?'])
+            && let Ok(policy) = SemverPolicy::parse(pattern)
+        {
+            return Ok(GitTagPattern::Semver(policy));
+        }
+        let glob = pattern
+            .split('*')
+            .map(|part| {
+                part.split('?')
+                    .map(regex::escape)
+                    .collect::<Vec<_>>()
+                    .join(".")
+            })
+            .collect::<Vec<_>>()
+            .join(".*");
+        let glob = Regex::new(&format!("^{}$", glob))
+            .with_context(|| format!("Invalid tag pattern '{}'", pattern))?;
+        Ok(GitTagPattern::Glob(glob))
+    }
+
+    pub fn matches(&self, tag: &str) -> bool {
+        match self {
+            GitTagPattern::Glob(glob) => glob.is_match(tag),
+            GitTagPattern::Semver(policy) => policy.matches(tag),
+        }
+    }
+
+    /// The newest of `tags` (name and commit time) the pattern matches: the
+    /// highest version when they all are versions, else the one whose commit
+    /// is the most recent.
+    pub fn newest<'a>(&self, tags: impl IntoIterator<Item = (&'a str, i64)>) -> Option<&'a str> {
+        let matching: Vec<_> = tags
+            .into_iter()
+            .filter(|(tag, _)| self.matches(tag))
+            .collect();
+        let versions: Option<Vec<_>> = matching
+            .iter()
+            .map(|(tag, _)| Some((parse_tag(tag)?, *tag)))
+            .collect();
+        match versions {
+            Some(versions) => versions.into_iter().max().map(|(_, tag)| tag),
+            None => matching
+                .into_iter()
+                .max_by_key(|(tag, time)| (*time, *tag))
+                .map(|(tag, _)| tag),
+        }
+    }
+}
+
+/// Label of an image naming the commit it was built from, per the OCI image
+/// spec annotations.
+pub const REVISION_LABEL: &str = "org.opencontainers.image.revision";
+
+/// How the tag written for an app commit is found
+/// (`gitops.operator.tag_source`).
+#[derive(serde::Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TagSource {
+    /// Derived from the commit SHA (`tag_type`, `tag_template`).
+    #[default]
+    Sha,
+    /// The registry tag whose image's revision label is the commit, for
+    /// pipelines tagging with build numbers.
+    RevisionLabel,
+}
+
+impl FromStr for TagSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "sha" => Ok(TagSource::Sha),
+            "revision_label" => Ok(TagSource::RevisionLabel),
+            other => anyhow::bail!(
+                "Invalid tag source '{}'. Must be 'sha' or 'revision_label'",
+                other
+            ),
+        }
+    }
+}
+
+/// Whether the revision label `label` names commit `sha`: the same SHA, or
+/// an abbreviation of at least 7 characters.
+pub fn same_revision(label: &str, sha: &str) -> bool {
+    let label = label.trim().to_ascii_lowercase();
+    label.len() >= 7 && sha.to_ascii_lowercase().starts_with(&label)
+}
+
+/// Compare tags the way their numbers read: digit runs as numbers, the
+/// rest as text, so `build-100` comes after `build-99`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(x), Some(y)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        let ordering = if x.is_ascii_digit() && y.is_ascii_digit() {
+            let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            let (x_end, y_end) = (digits(a), digits(b));
+            let (x_run, y_run) = (
+                a[..x_end].trim_start_matches('0'),
+                b[..y_end].trim_start_matches('0'),
+            );
+            let ordering = x_run.len().cmp(&y_run.len()).then_with(|| x_run.cmp(y_run));
+            a = &a[x_end..];
+            b = &b[y_end..];
+            ordering
+        } else {
+            a = &a[x.len_utf8()..];
+            b = &b[y.len_utf8()..];
+            x.cmp(&y)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
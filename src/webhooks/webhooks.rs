@@ -0,0 +1,110 @@
+use crate::configuration::{Entry, image_repository};
+use serde_json::Value;
+
+/// Registry that emitted a push event.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrySource {
+    Harbor,
+    DockerHub,
+    Ecr,
+}
+
+/// A single image push extracted from a registry webhook payload.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct RegistryEvent {
+    pub source: RegistrySource,
+    /// Repository path without registry host or tag (e.g. `kainlite/blog`).
+    pub repository: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+impl RegistryEvent {
+    /// Whether this push concerns the image tracked by `entry`.
+    pub fn matches(&self, entry: &Entry) -> bool {
+        image_repository(&entry.config.image_name) == self.repository
+    }
+}
+
+fn str_at<'a>(value: &'a Value, pointer: &str) -> Option<&'a str> {
+    value.pointer(pointer).and_then(Value::as_str)
+}
+
+/// Harbor (v2 webhook format): one event can carry several pushed artifacts.
+fn parse_harbor(payload: &Value) -> Option<Vec<RegistryEvent>> {
+    let event_data = payload.get("event_data")?;
+    let kind = str_at(payload, "/type").unwrap_or_default();
+    if !kind.eq_ignore_ascii_case("PUSH_ARTIFACT") && !kind.eq_ignore_ascii_case("pushImage") {
+        return Some(vec![]);
+    }
+
+    let repository = str_at(event_data, "/repository/repo_full_name")?;
+    let resources = event_data
+        .get("resources")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    let events = if resources.is_empty() {
+        vec![RegistryEvent {
+            source: RegistrySource::Harbor,
+            repository: image_repository(repository),
+            tag: None,
+        }]
+    } else {
+        resources
+            .iter()
+            .map(|r| RegistryEvent {
+                source: RegistrySource::Harbor,
+                repository: image_repository(repository),
+                tag: str_at(r, "/tag").map(String::from),
+            })
+            .collect()
+    };
+
+    Some(events)
+}
+
+/// Docker Hub: `repository.repo_name` plus `push_data.tag`.
+fn parse_docker_hub(payload: &Value) -> Option<Vec<RegistryEvent>> {
+    let repository = str_at(payload, "/repository/repo_name")?;
+    payload.get("push_data")?;
+
+    Some(vec![RegistryEvent {
+        source: RegistrySource::DockerHub,
+        repository: image_repository(repository),
+        tag: str_at(payload, "/push_data/tag").map(String::from),
+    }])
+}
+
+/// ECR "ECR Image Action" events, as forwarded by an EventBridge API destination.
+fn parse_ecr(payload: &Value) -> Option<Vec<RegistryEvent>> {
+    if str_at(payload, "/detail-type")? != "ECR Image Action" {
+        return None;
+    }
+
+    let detail = payload.get("detail")?;
+    let pushed = str_at(detail, "/action-type") == Some("PUSH")
+        && str_at(detail, "/result").is_none_or(|r| r == "SUCCESS");
+    if !pushed {
+        return Some(vec![]);
+    }
+
+    Some(vec![RegistryEvent {
+        source: RegistrySource::Ecr,
+        repository: image_repository(str_at(detail, "/repository-name")?),
+        tag: str_at(detail, "/image-tag").map(String::from),
+    }])
+}
+
+/// Extract the image pushes described by a registry webhook payload.
+///
+/// Returns `None` when the payload is not recognised as Harbor, Docker Hub, or
+/// ECR (EventBridge), and an empty list for recognised events that are not
+/// pushes (e.g. Harbor deletions or failed ECR pushes).
+pub fn parse_registry_event(payload: &Value) -> Option<Vec<RegistryEvent>> {
+    parse_ecr(payload)
+        .or_else(|| parse_harbor(payload))
+        .or_else(|| parse_docker_hub(payload))
+}
@@ -0,0 +1,112 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::admission::{mutate, parse_defaults};
+    use k8s_openapi::api::apps::v1::Deployment;
+    use kube::core::admission::AdmissionReview;
+    use serde_json::{Value, json};
+
+    const DEFAULTS: &str = r#"
+- namespace: default
+  selector: {team: web}
+  annotations:
+    gitops.operator.manifest_repository: git@github.com:org/manifests.git
+    gitops.operator.ssh_key_name: ssh-key
+    gitops.operator.ssh_key_namespace: gitops-operator
+- namespace: default
+  annotations:
+    gitops.operator.ssh_key_name: shared-key
+    gitops.operator.observe_branch: main
+"#;
+
+    fn review(operation: &str, metadata: Value) -> AdmissionReview<Deployment> {
+        serde_json::from_value(json!({
+            "apiVersion": "admission.k8s.io/v1",
+            "kind": "AdmissionReview",
+            "request": {
+                "uid": "705ab4f5-6393-11e8-b7cc-42010a800002",
+                "kind": {"group": "apps", "version": "v1", "kind": "Deployment"},
+                "resource": {"group": "apps", "version": "v1", "resource": "deployments"},
+                "name": "blog",
+                "namespace": "default",
+                "operation": operation,
+                "userInfo": {"username": "admin"},
+                "object": {
+                    "apiVersion": "apps/v1",
+                    "kind": "Deployment",
+                    "metadata": metadata,
+                },
+                "dryRun": false,
+            }
+        }))
+        .unwrap()
+    }
+
+    /// Apply the response patch (if any) to `metadata` and return the result.
+    fn admitted_metadata(operation: &str, metadata: Value) -> Value {
+        let defaults = parse_defaults(DEFAULTS).unwrap();
+        let response = mutate(&defaults, review(operation, metadata.clone()))
+            .response
+            .unwrap();
+        assert!(response.allowed);
+
+        let mut object = json!({ "metadata": metadata });
+        if let Some(patch) = response.patch {
+            let patch: json_patch::Patch = serde_json::from_slice(&patch).unwrap();
+            json_patch::patch(&mut object, &patch).unwrap();
+        }
+        object["metadata"].clone()
+    }
+
+    #[test]
+    fn test_mutate_fills_in_missing_defaults() {
+        let metadata = admitted_metadata(
+            "CREATE",
+            json!({
+                "name": "blog",
+                "labels": {"team": "web"},
+                "annotations": {
+                    "gitops.operator.enabled": "true",
+                    "gitops.operator.ssh_key_namespace": "blog",
+                },
+            }),
+        );
+
+        assert_eq!(
+            metadata["annotations"],
+            json!({
+                "gitops.operator.enabled": "true",
+                "gitops.operator.manifest_repository": "git@github.com:org/manifests.git",
+                "gitops.operator.observe_branch": "main",
+                // The Deployment's own value and the first matching rule win.
+                "gitops.operator.ssh_key_name": "ssh-key",
+                "gitops.operator.ssh_key_namespace": "blog",
+            })
+        );
+    }
+
+    #[test]
+    fn test_mutate_respects_selector_and_operation() {
+        // No annotations at all: the whole map is added.
+        let metadata = admitted_metadata("CREATE", json!({ "name": "blog" }));
+        assert_eq!(
+            metadata["annotations"],
+            json!({
+                "gitops.operator.observe_branch": "main",
+                "gitops.operator.ssh_key_name": "shared-key",
+            })
+        );
+
+        // Existing Deployments are left alone.
+        let metadata = admitted_metadata("UPDATE", json!({ "name": "blog" }));
+        assert_eq!(metadata.get("annotations"), None);
+    }
+
+    #[test]
+    fn test_parse_defaults_rejects_foreign_annotations() {
+        let err = parse_defaults(
+            "- namespace: default\n  annotations: {kubernetes.io/change-cause: oops}\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("kubernetes.io/change-cause"));
+    }
+}
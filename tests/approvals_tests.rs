@@ -0,0 +1,219 @@
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::{Router, routing};
+    use gitops_operator::approvals::{
+        ApprovalQueue, ApprovalStatus, approval_namespaces, approval_token, authorized, guarded,
+    };
+    use gitops_operator::state::MemoryState;
+    use gitops_operator::traits::StateStore;
+    use serial_test::serial;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_request_reuses_the_change_for_the_same_tag() {
+        let queue = ApprovalQueue::new();
+
+        let (change, created) = queue
+            .request("default", "blog", Some("abc".to_string()), "def")
+            .unwrap();
+        assert!(created);
+        assert_eq!(change.status, ApprovalStatus::Pending);
+        assert_eq!(change.from_sha.as_deref(), Some("abc"));
+        assert_eq!(change.id.len(), 12);
+
+        let (again, created) = queue.request("default", "blog", None, "def").unwrap();
+        assert!(!created);
+        assert_eq!(again, change);
+
+        // A newer tag replaces the change.
+        let (newer, created) = queue.request("default", "blog", None, "fed").unwrap();
+        assert!(created);
+        assert_ne!(newer.id, change.id);
+        assert_eq!(queue.get(&change.id).unwrap(), None);
+        assert_eq!(queue.list().unwrap(), [newer]);
+    }
+
+    #[test]
+    fn test_decide_records_the_decision_once() {
+        let queue = ApprovalQueue::new();
+        let (change, _) = queue.request("default", "blog", None, "def").unwrap();
+
+        assert_eq!(queue.decide("missing", true, None).unwrap(), None);
+
+        let approved = queue
+            .decide(&change.id, true, Some("alice"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(approved.status, ApprovalStatus::Approved);
+        assert_eq!(approved.decided_by.as_deref(), Some("alice"));
+        assert!(approved.decided_at.is_some());
+
+        let err = queue.decide(&change.id, false, None).unwrap_err();
+        assert!(err.to_string().contains("already approved"), "{}", err);
+
+        let (same, created) = queue.request("default", "blog", None, "def").unwrap();
+        assert!(!created);
+        assert_eq!(same.status, ApprovalStatus::Approved);
+
+        queue.complete("default", "blog").unwrap();
+        assert!(queue.list().unwrap().is_empty());
+        queue.complete("default", "blog").unwrap();
+    }
+
+    #[test]
+    fn test_queue_lives_in_its_store() {
+        let store = Arc::new(MemoryState::new());
+        let (change, _) = ApprovalQueue::with_store(store.clone())
+            .request("default", "blog", None, "def")
+            .unwrap();
+
+        let reopened = ApprovalQueue::with_store(store);
+        assert_eq!(reopened.get(&change.id).unwrap(), Some(change));
+    }
+
+    #[test]
+    fn test_changes_are_keyed_by_deployment() {
+        let store = Arc::new(MemoryState::new());
+        let queue = ApprovalQueue::with_store(store.clone());
+        let (change, _) = queue.request("default", "blog", None, "def").unwrap();
+        queue.request("other", "blog", None, "def").unwrap();
+
+        assert!(store.get("approvals", "default/blog").unwrap().is_some());
+        assert_eq!(
+            queue.for_deployment("default", "blog").unwrap(),
+            Some(change)
+        );
+        assert_eq!(queue.for_deployment("default", "shop").unwrap(), None);
+    }
+
+    #[test]
+    fn test_changes_keyed_by_id_move_under_their_deployment() {
+        let store = Arc::new(MemoryState::new());
+        let (change, _) = ApprovalQueue::new()
+            .request("default", "blog", None, "def")
+            .unwrap();
+        store
+            .put(
+                "approvals",
+                &change.id,
+                &serde_json::to_string(&change).unwrap(),
+            )
+            .unwrap();
+
+        let queue = ApprovalQueue::with_store(store.clone());
+        assert_eq!(store.get("approvals", &change.id).unwrap(), None);
+        assert_eq!(
+            queue.for_deployment("default", "blog").unwrap(),
+            Some(change.clone())
+        );
+        assert_eq!(queue.get(&change.id).unwrap(), Some(change));
+    }
+
+    #[test]
+    fn test_authorized() {
+        assert!(authorized(Some("Bearer s3cret"), "s3cret"));
+        assert!(!authorized(Some("Bearer s3cre"), "s3cret"));
+        assert!(!authorized(Some("Bearer s3cres"), "s3cret"));
+        assert!(!authorized(Some("s3cret"), "s3cret"));
+        assert!(!authorized(None, "s3cret"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_approval_settings_from_env() {
+        unsafe {
+            std::env::set_var("GITOPS_APPROVAL_NAMESPACES", " payments, ,billing ");
+            std::env::set_var("GITOPS_APPROVAL_TOKEN", " ");
+        }
+        assert_eq!(approval_namespaces(), ["payments", "billing"]);
+        assert_eq!(approval_token(), None);
+
+        unsafe {
+            std::env::remove_var("GITOPS_APPROVAL_NAMESPACES");
+            std::env::set_var("GITOPS_APPROVAL_TOKEN", "s3cret");
+        }
+        assert!(approval_namespaces().is_empty());
+        assert_eq!(approval_token().as_deref(), Some("s3cret"));
+
+        unsafe {
+            std::env::remove_var("GITOPS_APPROVAL_TOKEN");
+        }
+    }
+
+    async fn status_of(router: &Router, uri: &str, token: Option<&str>) -> StatusCode {
+        let mut request = Request::post(uri);
+        if let Some(token) = token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+        router
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_mutating_routes_need_the_approval_token() {
+        let router = guarded(
+            Router::new()
+                .route(
+                    "/rollback/{namespace}/{name}",
+                    routing::post(|| async { "rolled back" }),
+                )
+                .route(
+                    "/resume/{namespace}/{name}",
+                    routing::post(|| async { "resumed" }),
+                ),
+        );
+
+        unsafe { std::env::remove_var("GITOPS_APPROVAL_TOKEN") };
+        for uri in ["/rollback/default/blog", "/resume/default/blog"] {
+            assert_eq!(status_of(&router, uri, None).await, StatusCode::FORBIDDEN);
+        }
+
+        unsafe { std::env::set_var("GITOPS_APPROVAL_TOKEN", "s3cret") };
+        for uri in ["/rollback/default/blog", "/resume/default/blog"] {
+            assert_eq!(
+                status_of(&router, uri, None).await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(
+                status_of(&router, uri, Some("guess")).await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(
+                status_of(&router, uri, Some("s3cret")).await,
+                StatusCode::OK
+            );
+        }
+        unsafe { std::env::remove_var("GITOPS_APPROVAL_TOKEN") };
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_guarded_methods_can_share_a_path_with_public_ones() {
+        let router = Router::new()
+            .merge(guarded(
+                Router::new().route("/telemetry", routing::put(|| async { "reloaded" })),
+            ))
+            .route("/telemetry", routing::get(|| async { "settings" }));
+
+        unsafe { std::env::set_var("GITOPS_APPROVAL_TOKEN", "s3cret") };
+        let get = Request::get("/telemetry").body(Body::empty()).unwrap();
+        assert_eq!(
+            router.clone().oneshot(get).await.unwrap().status(),
+            StatusCode::OK
+        );
+        let put = Request::put("/telemetry").body(Body::empty()).unwrap();
+        assert_eq!(
+            router.clone().oneshot(put).await.unwrap().status(),
+            StatusCode::UNAUTHORIZED
+        );
+        unsafe { std::env::remove_var("GITOPS_APPROVAL_TOKEN") };
+    }
+}
@@ -0,0 +1,74 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::backups::BackupStore;
+    use std::fs;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("gitops-backups-{}-{}", name, uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_backups_are_content_addressed_and_deduplicated() {
+        let dir = temp_dir("dedupe");
+        let store = BackupStore::open(&dir, 10).unwrap();
+
+        let record = store
+            .backup("default/app", "deployments/app.yaml", b"image: app:v1\n")
+            .unwrap();
+        // `git hash-object` of the same content.
+        assert_eq!(record.hash, "b1633b2a6ca924f63202dfd3a018245dfd1a9ebe");
+        assert_eq!(record.size, 14);
+
+        // Backing up the unchanged file again adds nothing.
+        store
+            .backup("default/app", "deployments/app.yaml", b"image: app:v1\n")
+            .unwrap();
+        store
+            .backup("default/app", "deployments/app.yaml", b"image: app:v2\n")
+            .unwrap();
+        store
+            .backup("default/api", "deployments/api.yaml", b"image: app:v1\n")
+            .unwrap();
+
+        let all = store.records(None, 100);
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].deployment, "default/api");
+        let app = store.records(Some("default/app"), 100);
+        assert_eq!(app.len(), 2);
+        assert_eq!(app[1].hash, record.hash);
+
+        assert_eq!(
+            store
+                .content("default/app", &record.hash)
+                .unwrap()
+                .as_deref(),
+            Some(&b"image: app:v1\n"[..])
+        );
+        // Content is only served to a deployment that backed it up.
+        assert_eq!(store.content("other/app", &record.hash).unwrap(), None);
+
+        // The index survives a restart.
+        drop(store);
+        let reopened = BackupStore::open(&dir, 10).unwrap();
+        assert_eq!(reopened.records(None, 100), all);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_oldest_backups_are_evicted() {
+        let dir = temp_dir("evict");
+        let store = BackupStore::open(&dir, 2).unwrap();
+
+        let first = store.backup("default/app", "app.yaml", b"v1").unwrap();
+        store.backup("default/app", "app.yaml", b"v2").unwrap();
+        store.backup("default/app", "app.yaml", b"v3").unwrap();
+
+        let records = store.records(None, 100);
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.hash != first.hash));
+        assert!(!dir.join(&first.hash[..2]).join(&first.hash[2..]).exists());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,194 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::bitbucket::*;
+    use gitops_operator::pull_requests::{ChangeRequest, Forge};
+    use gitops_operator::traits::PullRequestOpener;
+
+    use serde_json::json;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_partial_json, header, method, path, query_param},
+    };
+
+    const CLOUD_PULLS: &str = "/repositories/platform/manifests/pullrequests";
+    const SERVER_PULLS: &str = "/projects/PLAT/repos/manifests/pull-requests";
+
+    fn change_request(forge: Forge, repository: &str) -> ChangeRequest {
+        ChangeRequest {
+            forge,
+            repository: repository.to_string(),
+            head: "gitops-operator/default/blog-abc123".to_string(),
+            base: "main".to_string(),
+            title: "chore(refs): update blog to abc123".to_string(),
+            body: "Opened by gitops-operator".to_string(),
+            labels: vec![],
+            assignees: vec!["{b7c1}".to_string(), "557058:42".to_string()],
+        }
+    }
+
+    fn cloud_change() -> ChangeRequest {
+        change_request(Forge::Bitbucket, "git@bitbucket.org:platform/manifests.git")
+    }
+
+    fn server_change() -> ChangeRequest {
+        let mut change = change_request(
+            Forge::BitbucketServer,
+            "https://bitbucket.corp/scm/PLAT/manifests.git",
+        );
+        change.assignees = vec!["alice".to_string()];
+        change
+    }
+
+    #[test]
+    fn test_parse_bitbucket_repositories() {
+        assert_eq!(
+            parse_bitbucket_cloud_repository("https://bitbucket.org/platform/manifests.git"),
+            Some("platform/manifests".to_string())
+        );
+        assert_eq!(
+            parse_bitbucket_cloud_repository("git@github.com:platform/manifests.git"),
+            None
+        );
+
+        let expected = Some((
+            "https://bitbucket.corp/rest/api/1.0".to_string(),
+            "PLAT".to_string(),
+            "manifests".to_string(),
+        ));
+        assert_eq!(
+            parse_bitbucket_server_repository("ssh://git@bitbucket.corp:7999/PLAT/manifests.git"),
+            expected
+        );
+        assert_eq!(
+            parse_bitbucket_server_repository("https://bitbucket.corp/scm/PLAT/manifests.git"),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cloud_pull_request_uses_app_passwords() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(CLOUD_PULLS))
+            .and(query_param("state", "OPEN"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"values": []})))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(CLOUD_PULLS))
+            // deploy-bot:app-password
+            .and(header(
+                "authorization",
+                "Basic ZGVwbG95LWJvdDphcHAtcGFzc3dvcmQ=",
+            ))
+            .and(body_partial_json(json!({
+                "title": "chore(refs): update blog to abc123",
+                "source": {"branch": {"name": "gitops-operator/default/blog-abc123"}},
+                "destination": {"branch": {"name": "main"}},
+                "reviewers": [{"uuid": "{b7c1}"}, {"account_id": "557058:42"}],
+                "close_source_branch": true
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "id": 14,
+                "links": {"html": {"href": "https://bitbucket.org/platform/manifests/pull-requests/14"}}
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let opener = BitbucketCloudPullRequests::with_api_base(mock_server.uri());
+        let pull_request = opener
+            .open_pull_request(&cloud_change(), "deploy-bot:app-password")
+            .await
+            .unwrap();
+        assert_eq!(pull_request.number, 14);
+        assert!(pull_request.url.ends_with("/pull-requests/14"));
+    }
+
+    #[tokio::test]
+    async fn test_cloud_pull_request_reuses_an_open_one() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(CLOUD_PULLS))
+            .and(header("authorization", "Bearer access-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"values": [{
+                "id": 3,
+                "links": {"html": {"href": "https://bitbucket.org/platform/manifests/pull-requests/3"}}
+            }]})))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let opener = BitbucketCloudPullRequests::with_api_base(mock_server.uri());
+        let pull_request = opener
+            .open_pull_request(&cloud_change(), "access-token")
+            .await
+            .unwrap();
+        assert_eq!(pull_request.number, 3);
+    }
+
+    #[tokio::test]
+    async fn test_server_pull_request_creates_one() {
+        let mock_server = MockServer::start().await;
+
+        // Open from the same branch, but into another one.
+        Mock::given(method("GET"))
+            .and(path(SERVER_PULLS))
+            .and(query_param("at", "refs/heads/gitops-operator/default/blog-abc123"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"values": [{
+                "id": 2,
+                "toRef": {"id": "refs/heads/release"},
+                "links": {"self": [{"href": "https://bitbucket.corp/projects/PLAT/repos/manifests/pull-requests/2"}]}
+            }]})))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(SERVER_PULLS))
+            .and(header("authorization", "Bearer server-token"))
+            .and(body_partial_json(json!({
+                "fromRef": {"id": "refs/heads/gitops-operator/default/blog-abc123"},
+                "toRef": {"id": "refs/heads/main"},
+                "reviewers": [{"user": {"name": "alice"}}]
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "id": 5,
+                "toRef": {"id": "refs/heads/main"},
+                "links": {"self": [{"href": "https://bitbucket.corp/projects/PLAT/repos/manifests/pull-requests/5"}]}
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let opener = BitbucketServerPullRequests::with_api_base(mock_server.uri());
+        let pull_request = opener
+            .open_pull_request(&server_change(), "server-token")
+            .await
+            .unwrap();
+        assert_eq!(pull_request.number, 5);
+        assert!(pull_request.url.ends_with("/pull-requests/5"));
+    }
+
+    #[tokio::test]
+    async fn test_server_pull_request_reports_refusals() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(SERVER_PULLS))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&mock_server)
+            .await;
+
+        let opener = BitbucketServerPullRequests::with_api_base(mock_server.uri());
+        let error = opener
+            .open_pull_request(&server_change(), "bad-token")
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("401"));
+    }
+}
@@ -0,0 +1,37 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::capabilities::{
+        ARGO_ROLLOUT_RESOURCE, Capabilities, EVENTS_RESOURCE, capabilities, resource_name,
+    };
+    use kube::core::GroupVersionKind;
+
+    #[test]
+    fn test_resource_names() {
+        assert_eq!(
+            resource_name(&GroupVersionKind::gvk("argoproj.io", "v1alpha1", "Rollout")),
+            ARGO_ROLLOUT_RESOURCE
+        );
+        assert_eq!(
+            resource_name(&GroupVersionKind::gvk("", "v1", "ConfigMap")),
+            "v1/ConfigMap"
+        );
+    }
+
+    #[test]
+    fn test_unprobed_resources_are_assumed_served() {
+        let mut probed = Capabilities::default();
+        probed
+            .resources
+            .insert(ARGO_ROLLOUT_RESOURCE.to_string(), false);
+        probed.resources.insert(EVENTS_RESOURCE.to_string(), true);
+
+        assert!(!probed.has(ARGO_ROLLOUT_RESOURCE));
+        assert!(!probed.has_kind(&GroupVersionKind::gvk("argoproj.io", "v1alpha1", "Rollout")));
+        assert!(probed.has(EVENTS_RESOURCE));
+        assert!(probed.has("example.com/v1/Freeze"));
+
+        // Before the startup probe everything is.
+        assert!(capabilities().has(EVENTS_RESOURCE));
+        assert_eq!(capabilities().operator_version, env!("CARGO_PKG_VERSION"));
+    }
+}
@@ -0,0 +1,33 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::changes::{ChangeCache, fingerprint};
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    #[test]
+    fn test_fingerprint_covers_config_and_tag() {
+        let config = BTreeMap::from([("observe_branch", "master")]);
+        let other = BTreeMap::from([("observe_branch", "main")]);
+
+        assert_eq!(fingerprint(&config, "abc"), fingerprint(&config, "abc"));
+        assert_ne!(fingerprint(&config, "abc"), fingerprint(&config, "def"));
+        assert_ne!(fingerprint(&config, "abc"), fingerprint(&other, "abc"));
+    }
+
+    #[test]
+    fn test_change_cache_honours_fingerprint_and_ttl() {
+        let cache = ChangeCache::new();
+        let ttl = Duration::from_secs(60);
+        assert!(!cache.unchanged("default/app", "f1", ttl));
+
+        cache.record("default/app", "f1".to_string());
+        assert!(cache.unchanged("default/app", "f1", ttl));
+        assert!(!cache.unchanged("default/app", "f2", ttl));
+        assert!(!cache.unchanged("default/api", "f1", ttl));
+        // A zero TTL disables skipping.
+        assert!(!cache.unchanged("default/app", "f1", Duration::ZERO));
+
+        cache.forget("default/app");
+        assert!(!cache.unchanged("default/app", "f1", ttl));
+    }
+}
@@ -0,0 +1,140 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::coalesce::{Coalescer, OncePerRun, coalesce_window};
+    use serial_test::serial;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    type Runs = Arc<Mutex<Vec<Vec<&'static str>>>>;
+
+    async fn trigger(
+        coalescer: &Arc<Coalescer<&'static str, usize>>,
+        runs: &Runs,
+        key: &str,
+        item: &'static str,
+        window: Duration,
+    ) -> (usize, usize) {
+        let runs = runs.clone();
+        let result = coalescer
+            .submit(key, vec![item], window, move |items| async move {
+                let count = items.len();
+                runs.lock().unwrap().push(items);
+                count
+            })
+            .await
+            .unwrap();
+        (*result.output, result.events)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_triggers_within_the_window_run_once() {
+        let coalescer = Arc::new(Coalescer::new());
+        let runs: Runs = Arc::default();
+        let window = Duration::from_secs(5);
+
+        let (a, b, c, other) = tokio::join!(
+            trigger(&coalescer, &runs, "repo@main", "a", window),
+            trigger(&coalescer, &runs, "repo@main", "b", window),
+            trigger(&coalescer, &runs, "repo@main", "c", window),
+            trigger(&coalescer, &runs, "repo@dev", "d", window),
+        );
+
+        assert_eq!(a, (3, 3));
+        assert_eq!(b, (3, 3));
+        assert_eq!(c, (3, 3));
+        assert_eq!(other, (1, 1));
+        let mut runs = runs.lock().unwrap().clone();
+        runs.sort();
+        assert_eq!(runs, [vec!["a", "b", "c"], vec!["d"]]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_a_trigger_after_the_window_opens_a_new_batch() {
+        let coalescer = Arc::new(Coalescer::new());
+        let runs: Runs = Arc::default();
+        let window = Duration::from_secs(5);
+
+        let (first, second) = tokio::join!(
+            trigger(&coalescer, &runs, "repo@main", "a", window),
+            async {
+                tokio::time::sleep(Duration::from_secs(6)).await;
+                trigger(&coalescer, &runs, "repo@main", "b", window).await
+            },
+        );
+
+        assert_eq!(first, (1, 1));
+        assert_eq!(second, (1, 1));
+        assert_eq!(runs.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_a_zero_window_runs_each_trigger() {
+        let coalescer = Arc::new(Coalescer::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..2 {
+            let calls = calls.clone();
+            let result = coalescer
+                .submit(
+                    "repo@main",
+                    vec![1],
+                    Duration::ZERO,
+                    move |items| async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        items.len()
+                    },
+                )
+                .await
+                .unwrap();
+            assert_eq!(result.events, 1);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_once_per_run_runs_each_key_once() {
+        let heads = OncePerRun::new();
+        let calls = AtomicUsize::new(0);
+        let fetch = |sha: &'static str| {
+            let calls = &calls;
+            move || async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                sha.to_string()
+            }
+        };
+
+        let (a, b, other) = tokio::join!(
+            heads.get_or_run("app#main", fetch("abc")),
+            heads.get_or_run("app#main", fetch("def")),
+            heads.get_or_run("app#dev", fetch("123")),
+        );
+        assert_eq!(a, "abc");
+        assert_eq!(b, "abc");
+        assert_eq!(other, "123");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let later = heads.get_or_run("app#main", fetch("456")).await;
+        assert_eq!(later, "abc");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    #[serial]
+    fn test_coalesce_window_from_env() {
+        unsafe {
+            std::env::remove_var("GITOPS_WEBHOOK_COALESCE_MS");
+        }
+        assert_eq!(coalesce_window(), Duration::ZERO);
+
+        unsafe {
+            std::env::set_var("GITOPS_WEBHOOK_COALESCE_MS", "1500");
+        }
+        assert_eq!(coalesce_window(), Duration::from_millis(1500));
+
+        unsafe {
+            std::env::remove_var("GITOPS_WEBHOOK_COALESCE_MS");
+        }
+    }
+}
@@ -0,0 +1,147 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::codecommit::*;
+    use serial_test::serial;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn credentials(session_token: Option<&str>) -> AwsCredentials {
+        AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: session_token.map(String::from),
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_https_url_understands_codecommit_urls() {
+        let expected =
+            Some("https://git-codecommit.eu-west-1.amazonaws.com/v1/repos/manifests".to_string());
+        assert_eq!(https_url("codecommit::eu-west-1://manifests"), expected);
+        assert_eq!(https_url("codecommit::eu-west-1://ops@manifests"), expected);
+        assert_eq!(
+            https_url("https://git-codecommit.eu-west-1.amazonaws.com/v1/repos/manifests"),
+            expected
+        );
+
+        unsafe { std::env::set_var("AWS_REGION", "eu-west-1") };
+        assert_eq!(https_url("codecommit://manifests"), expected);
+        unsafe { std::env::remove_var("AWS_REGION") };
+
+        assert_eq!(https_url("git@github.com:org/manifests.git"), None);
+        assert_eq!(https_url("https://github.com/org/manifests.git"), None);
+        assert!(is_codecommit_url("codecommit::us-east-1://app"));
+        assert!(!is_codecommit_url("/tmp/manifests"));
+    }
+
+    #[test]
+    fn test_git_credentials_are_signed_with_sigv4() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_792_143_000); // 2026-10-16T09:30:00Z
+        let url = "https://git-codecommit.us-east-1.amazonaws.com/v1/repos/manifests";
+
+        let (username, password) = git_credentials(url, &credentials(None), now).unwrap();
+        assert_eq!(username, "AKIDEXAMPLE");
+        assert_eq!(
+            password,
+            "20261016T093000Zf7b8542a656cbaac63555aa270a15f3a4e4c925905aaab3074b91cbdb8cf5fbf"
+        );
+
+        // Temporary credentials carry their session token in the username.
+        let (username, _) = git_credentials(url, &credentials(Some("token")), now).unwrap();
+        assert_eq!(username, "AKIDEXAMPLE%token");
+
+        assert!(
+            git_credentials("https://github.com/org/manifests", &credentials(None), now).is_none()
+        );
+    }
+
+    #[test]
+    fn test_requests_are_signed_with_sigv4() {
+        // The example request of the AWS Signature Version 4 documentation.
+        let now = UNIX_EPOCH + Duration::from_secs(1_440_938_160); // 2015-08-30T12:36:00Z
+        let headers = sign_request(
+            &credentials(None),
+            "us-east-1",
+            "iam",
+            "GET",
+            "/",
+            "Action=ListUsers&Version=2010-05-08",
+            &[
+                (
+                    "Content-Type",
+                    "application/x-www-form-urlencoded; charset=utf-8",
+                ),
+                ("Host", "iam.amazonaws.com"),
+            ],
+            b"",
+            now,
+        );
+        assert_eq!(
+            headers,
+            vec![
+                ("x-amz-date".to_string(), "20150830T123600Z".to_string()),
+                (
+                    "authorization".to_string(),
+                    "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, \
+                     SignedHeaders=content-type;host;x-amz-date, \
+                     Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+                        .to_string()
+                ),
+            ]
+        );
+
+        let headers = sign_request(
+            &credentials(Some("token")),
+            "us-east-1",
+            "iam",
+            "GET",
+            "/",
+            "",
+            &[("Host", "iam.amazonaws.com")],
+            b"",
+            now,
+        );
+        assert_eq!(
+            headers[1],
+            ("x-amz-security-token".to_string(), "token".to_string())
+        );
+        assert!(
+            headers[2]
+                .1
+                .contains("SignedHeaders=host;x-amz-date;x-amz-security-token,")
+        );
+    }
+
+    #[test]
+    fn test_parse_expiration() {
+        assert_eq!(
+            parse_expiration("2026-10-16T09:30:00Z"),
+            Some(UNIX_EPOCH + Duration::from_secs(1_792_143_000))
+        );
+        assert_eq!(parse_expiration("1970-01-01T00:00:00Z"), Some(UNIX_EPOCH));
+        assert_eq!(parse_expiration("soon"), None);
+    }
+
+    #[test]
+    #[serial]
+    fn test_aws_credentials_from_the_environment() {
+        unsafe {
+            std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDEXAMPLE");
+            std::env::set_var("AWS_SECRET_ACCESS_KEY", "secret");
+            std::env::set_var("AWS_SESSION_TOKEN", "session");
+        }
+        let found = aws_credentials();
+        unsafe {
+            std::env::remove_var("AWS_ACCESS_KEY_ID");
+            std::env::remove_var("AWS_SECRET_ACCESS_KEY");
+            std::env::remove_var("AWS_SESSION_TOKEN");
+        }
+
+        let found = found.unwrap();
+        assert_eq!(found.access_key_id, "AKIDEXAMPLE");
+        assert_eq!(found.secret_access_key, "secret");
+        assert_eq!(found.session_token.as_deref(), Some("session"));
+        assert_eq!(found.expires_at, None);
+    }
+}
@@ -1,9 +1,15 @@
 #[cfg(test)]
 mod tests {
     use gitops_operator::configuration::{
-        Action, Config, Entry, Status, build_container_image, status_report,
+        Action, Config, EXIT_PARTIAL_SUCCESS, Entry, ErrorKind, ReconcileResult, Status,
+        build_container_image, exit_code, image_repository, status_report,
     };
+    use gitops_operator::files::ImageHost;
+    use gitops_operator::git::CloneStrategy;
+    use gitops_operator::notifications::NotificationProvider;
+    use gitops_operator::versions::TagSort;
     use k8s_openapi::api::apps::v1::Deployment;
+    use serial_test::serial;
     use std::collections::BTreeMap;
 
     use axum::extract::State as AxumState;
@@ -112,6 +118,156 @@ mod tests {
         assert_eq!(entry.config.ssh_key_namespace, "myns");
     }
 
+    #[test]
+    #[serial]
+    fn test_checkouts_are_named_per_namespace_and_repository() {
+        let annotations = |app: &str| {
+            BTreeMap::from(
+                [
+                    ("gitops.operator.enabled", "true"),
+                    ("gitops.operator.app_repository", app),
+                    (
+                        "gitops.operator.manifest_repository",
+                        "https://github.com/org/manifests",
+                    ),
+                    ("gitops.operator.image_name", "my-app"),
+                    ("gitops.operator.deployment_path", "deployments/app.yaml"),
+                    ("gitops.operator.observe_branch", "release/1.x"),
+                ]
+                .map(|(k, v)| (k.to_string(), v.to_string())),
+            )
+        };
+        let entry = |namespace: &str, app: &str| {
+            Entry::new(&create_test_deployment(
+                "test-app",
+                namespace,
+                "my-container:1.0.0",
+                annotations(app),
+            ))
+            .unwrap()
+        };
+
+        let default = entry("default", "https://github.com/org/app");
+        let path = default.app_repo_path();
+        let dir = path.trim_end_matches('/').rsplit('/').next().unwrap();
+        assert!(
+            dir.starts_with("app-default-test-app-release_1_x-"),
+            "{}",
+            dir
+        );
+        assert!(
+            default
+                .manifest_repo_path()
+                .contains("/manifest-default-test-app-")
+        );
+
+        let other_namespace = entry("staging", "https://github.com/org/app");
+        assert_ne!(other_namespace.app_repo_path(), path);
+        let other_repository = entry("default", "https://github.com/org/other");
+        assert_ne!(other_repository.app_repo_path(), path);
+        assert_eq!(
+            other_repository.manifest_repo_path(),
+            default.manifest_repo_path()
+        );
+    }
+
+    #[test]
+    fn test_codecommit_repositories_need_no_ssh_key() {
+        let annotations = |app: &str, manifests: &str| {
+            BTreeMap::from([
+                ("gitops.operator.enabled".to_string(), "true".to_string()),
+                (
+                    "gitops.operator.app_repository".to_string(),
+                    app.to_string(),
+                ),
+                (
+                    "gitops.operator.manifest_repository".to_string(),
+                    manifests.to_string(),
+                ),
+                (
+                    "gitops.operator.image_name".to_string(),
+                    "my-app".to_string(),
+                ),
+                (
+                    "gitops.operator.deployment_path".to_string(),
+                    "deployments/app.yaml".to_string(),
+                ),
+            ])
+        };
+
+        let deployment = create_test_deployment(
+            "test-app",
+            "default",
+            "my-app:1.0.0",
+            annotations(
+                "codecommit::eu-west-1://app",
+                "https://git-codecommit.eu-west-1.amazonaws.com/v1/repos/manifests",
+            ),
+        );
+        let entry = Entry::new(&deployment).unwrap();
+        assert_eq!(entry.config.ssh_key_name, "");
+        assert_eq!(entry.config.ssh_key_namespace, "");
+
+        // Any repository elsewhere still needs one.
+        let deployment = create_test_deployment(
+            "test-app",
+            "default",
+            "my-app:1.0.0",
+            annotations(
+                "codecommit::eu-west-1://app",
+                "git@github.com:org/manifests.git",
+            ),
+        );
+        assert!(Entry::new(&deployment).is_none());
+    }
+
+    #[test]
+    fn test_manifest_ssh_key_annotations() {
+        let mut annotations = BTreeMap::from([
+            ("gitops.operator.enabled".to_string(), "true".to_string()),
+            (
+                "gitops.operator.app_repository".to_string(),
+                "https://github.com/org/app.git".to_string(),
+            ),
+            (
+                "gitops.operator.manifest_repository".to_string(),
+                "git@github.com:org/manifests.git".to_string(),
+            ),
+            (
+                "gitops.operator.image_name".to_string(),
+                "my-app".to_string(),
+            ),
+            (
+                "gitops.operator.deployment_path".to_string(),
+                "deployments/app.yaml".to_string(),
+            ),
+        ]);
+        let deployment =
+            create_test_deployment("test-app", "default", "my-app:1.0.0", annotations.clone());
+        assert!(Entry::new(&deployment).is_none());
+
+        // The manifest repository's own key is enough.
+        annotations.insert(
+            "gitops.operator.manifest_ssh_key_name".to_string(),
+            "manifests-deploy-key".to_string(),
+        );
+        annotations.insert(
+            "gitops.operator.manifest_ssh_key_namespace".to_string(),
+            "keys".to_string(),
+        );
+        let deployment = create_test_deployment("test-app", "default", "my-app:1.0.0", annotations);
+        let entry = Entry::new(&deployment).unwrap();
+        assert_eq!(entry.config.ssh_key_name, "");
+        assert_eq!(
+            entry.config.manifest_ssh_key_name.as_deref(),
+            Some("manifests-deploy-key")
+        );
+        assert_eq!(
+            entry.config.manifest_ssh_key_namespace.as_deref(),
+            Some("keys")
+        );
+    }
+
     #[test]
     fn test_deployment_to_entry_with_ghcr_registry() {
         let mut annotations = BTreeMap::new();
@@ -638,6 +794,38 @@ mod tests {
         assert!(report.contains("NAMESPACE"), "{report}");
     }
 
+    #[test]
+    fn test_status_report_tells_paused_from_disabled() {
+        let mut annotations = minimal_annotations(true);
+        annotations.insert("gitops.operator.paused".to_string(), "true".to_string());
+        let paused = create_test_deployment("blog", "default", "org/app:abc1234", annotations);
+        let paused = Entry::new(&paused).expect("entry");
+        assert!(paused.config.paused);
+        assert!(paused.paused.is_some());
+        let disabled = create_test_deployment(
+            "docs",
+            "default",
+            "org/docs:abc1234",
+            minimal_annotations(false),
+        );
+        let disabled = Entry::new(&disabled).expect("entry");
+        assert_eq!(disabled.paused, None);
+
+        let report = status_report(&[paused, disabled]);
+        let row = |name: &str| {
+            report
+                .lines()
+                .find(|line| line.contains(name))
+                .unwrap()
+                .split_whitespace()
+                .nth(2)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(row("blog"), "paused");
+        assert_eq!(row("docs"), "false");
+    }
+
     #[test]
     fn test_status_report_empty() {
         let report = status_report(&[]);
@@ -656,6 +844,7 @@ mod tests {
         assert_eq!(config.image_name, "org/app");
         assert_eq!(config.observe_branch, "master"); // default
         assert_eq!(config.tag_type, "long"); // default
+        assert!(!config.force_push); // default
         assert_eq!(config.registry_url, None); // optional, absent
     }
 
@@ -680,8 +869,24 @@ mod tests {
         );
         let config = Config::from_annotations(&ann, "ns1").unwrap();
         assert_eq!(config.observe_branch, "develop");
+        assert_eq!(config.manifest_branch, "develop"); // follows observe_branch
         assert_eq!(config.tag_type, "short");
         assert_eq!(config.registry_url, Some("https://ghcr.io".to_string()));
+
+        ann.insert(
+            "gitops.operator.manifest_branch".to_string(),
+            "main".to_string(),
+        );
+        ann.insert("gitops.operator.force_push".to_string(), "true".to_string());
+        ann.insert(
+            "gitops.operator.clone_strategy".to_string(),
+            "single_branch".to_string(),
+        );
+        let config = Config::from_annotations(&ann, "ns1").unwrap();
+        assert_eq!(config.observe_branch, "develop");
+        assert_eq!(config.manifest_branch, "main");
+        assert!(config.force_push);
+        assert_eq!(config.clone_strategy, CloneStrategy::SingleBranch);
     }
 
     #[test]
@@ -693,6 +898,18 @@ mod tests {
         );
         let config = Config::from_annotations(&ann, "ns1").unwrap();
         assert_eq!(config.tag_type, "long");
+
+        for invalid in ["short:3", "short:41", "short:", "short:x"] {
+            ann.insert("gitops.operator.tag_type".to_string(), invalid.to_string());
+            let config = Config::from_annotations(&ann, "ns1").unwrap();
+            assert_eq!(config.tag_type, "long", "{}", invalid);
+        }
+        ann.insert(
+            "gitops.operator.tag_type".to_string(),
+            " short:12 ".to_string(),
+        );
+        let config = Config::from_annotations(&ann, "ns1").unwrap();
+        assert_eq!(config.tag_type, "short:12");
     }
 
     // ---- Issue #8: multi-container pods ----
@@ -737,5 +954,516 @@ mod tests {
         let entry = Entry::new(&deployment).expect("entry");
         assert_eq!(entry.container, "ghcr.io/org/app");
         assert_eq!(entry.version, "abc1234");
+
+        // Every container is listed; only the one matching image_name is tracked.
+        assert_eq!(entry.containers.len(), 2);
+        assert_eq!(entry.containers[0].name, "sidecar");
+        assert_eq!(entry.containers[0].image, "fluentd");
+        assert_eq!(entry.containers[0].tag, "v1.16");
+        assert!(!entry.containers[0].tracked);
+        assert!(entry.containers[1].tracked);
+    }
+
+    #[test]
+    fn test_deployment_path_accepts_a_list() {
+        let mut annotations = minimal_annotations(true);
+        annotations.insert(
+            "gitops.operator.deployment_path".to_string(),
+            "overlays/staging/app.yaml, overlays/prod/app.yaml,".to_string(),
+        );
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+
+        assert_eq!(config.deployment_path, "overlays/staging/app.yaml");
+        assert_eq!(
+            config.deployment_paths,
+            vec![
+                "overlays/staging/app.yaml".to_string(),
+                "overlays/prod/app.yaml".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_container_name_annotation() {
+        let config =
+            Config::from_annotations(&minimal_annotations(true), "default").expect("config");
+        assert_eq!(config.container_name, None);
+
+        let mut annotations = minimal_annotations(true);
+        annotations.insert(
+            "gitops.operator.container_name".to_string(),
+            "app".to_string(),
+        );
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert_eq!(config.container_name.as_deref(), Some("app"));
+    }
+
+    #[test]
+    fn test_image_names_annotation() {
+        let config =
+            Config::from_annotations(&minimal_annotations(true), "default").expect("config");
+        assert_eq!(config.image_names, vec!["org/app".to_string()]);
+
+        let mut annotations = minimal_annotations(true);
+        annotations.insert(
+            "gitops.operator.image_names".to_string(),
+            "org/worker, org/app,,org/migrate".to_string(),
+        );
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert_eq!(
+            config.image_names,
+            vec![
+                "org/app".to_string(),
+                "org/worker".to_string(),
+                "org/migrate".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_image_repository_strips_host_tag_and_library() {
+        assert_eq!(image_repository("kainlite/tr"), "kainlite/tr");
+        assert_eq!(
+            image_repository("ghcr.io/kainlite/tr:abc123"),
+            "kainlite/tr"
+        );
+        assert_eq!(
+            image_repository("registry.local:5000/team/app@sha256:deadbeef"),
+            "team/app"
+        );
+        assert_eq!(image_repository("localhost/app:1"), "app");
+        assert_eq!(image_repository("docker.io/library/nginx:1.27"), "nginx");
+        assert_eq!(
+            image_repository("harbor.example.com/project/nested/app:v1"),
+            "project/nested/app"
+        );
+    }
+
+    #[test]
+    fn test_vars_annotation_is_parsed_and_exposed_to_templates() {
+        let mut annotations = minimal_annotations(true);
+        annotations.insert(
+            "gitops.operator.vars".to_string(),
+            r#"{"team": "payments", "tier": 1, "app": "spoofed"}"#.to_string(),
+        );
+
+        let deployment = create_test_deployment("checkout", "shop", "org/app:abc1234", annotations);
+        let entry = Entry::new(&deployment).expect("entry");
+
+        assert_eq!(entry.config.vars.get("team").unwrap(), "payments");
+        assert_eq!(entry.config.vars.get("tier").unwrap(), "1");
+
+        let vars = entry.template_vars();
+        assert_eq!(vars.get("team").unwrap(), "payments");
+        assert_eq!(vars.get("app").unwrap(), "checkout");
+        assert_eq!(vars.get("namespace").unwrap(), "shop");
+        assert_eq!(vars.get("branch").unwrap(), "master");
+    }
+
+    #[test]
+    fn test_invalid_vars_annotation_is_ignored() {
+        let mut annotations = minimal_annotations(true);
+        annotations.insert("gitops.operator.vars".to_string(), "not json".to_string());
+
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert!(config.vars.is_empty());
+    }
+
+    #[test]
+    fn test_author_domains_allowlist() {
+        let mut annotations = minimal_annotations(true);
+        annotations.insert(
+            "gitops.operator.author_domains".to_string(),
+            " Example.com, @corp.example.org ,".to_string(),
+        );
+
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert_eq!(
+            config.author_domains,
+            vec!["example.com", "corp.example.org"]
+        );
+
+        assert!(config.allows_author("dev@example.com"));
+        assert!(config.allows_author("dev@EXAMPLE.COM"));
+        assert!(config.allows_author("ops@corp.example.org"));
+        assert!(!config.allows_author("dev@evil-example.com"));
+        assert!(!config.allows_author("dev@sub.example.com"));
+        assert!(!config.allows_author("example.com"));
+
+        let config =
+            Config::from_annotations(&minimal_annotations(true), "default").expect("config");
+        assert!(!config.allows_author("dev@example.com"));
+    }
+
+    #[test]
+    fn test_image_host_annotation() {
+        let config =
+            Config::from_annotations(&minimal_annotations(true), "default").expect("config");
+        assert_eq!(config.image_host, ImageHost::Preserve);
+
+        let mut annotations = minimal_annotations(true);
+        annotations.insert(
+            "gitops.operator.image_host".to_string(),
+            "rewrite".to_string(),
+        );
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert_eq!(config.image_host, ImageHost::Rewrite);
+    }
+
+    #[test]
+    fn test_manifest_format_helm_values() {
+        let config =
+            Config::from_annotations(&minimal_annotations(true), "default").expect("config");
+        assert_eq!(config.values_path, None);
+
+        let mut annotations = minimal_annotations(true);
+        annotations.insert(
+            "gitops.operator.manifest_format".to_string(),
+            "helm-values".to_string(),
+        );
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert_eq!(config.values_path.as_deref(), Some("image.tag"));
+
+        annotations.insert(
+            "gitops.operator.values_path".to_string(),
+            "app.image.tag".to_string(),
+        );
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert_eq!(config.values_path.as_deref(), Some("app.image.tag"));
+    }
+
+    #[test]
+    fn test_patch_expression_takes_precedence_over_values_path() {
+        let mut annotations = minimal_annotations(true);
+        annotations.insert(
+            "gitops.operator.manifest_format".to_string(),
+            "helm-values".to_string(),
+        );
+        annotations.insert(
+            "gitops.operator.patch_expression".to_string(),
+            ".spec.template.spec.containers[0].image".to_string(),
+        );
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert_eq!(
+            config.patch_expression.as_deref(),
+            Some(".spec.template.spec.containers[0].image")
+        );
+
+        let entry = Entry {
+            container: "org/app".to_string(),
+            name: "app".to_string(),
+            namespace: "default".to_string(),
+            resource_version: None,
+            generation: None,
+            annotations,
+            version: "latest".to_string(),
+            containers: vec![],
+            config,
+            paused: None,
+        };
+        assert_eq!(
+            entry.container_target("ghcr.io").key_path.as_deref(),
+            Some(".spec.template.spec.containers[0].image")
+        );
+    }
+
+    #[test]
+    fn test_tag_policy_annotations() {
+        let config =
+            Config::from_annotations(&minimal_annotations(true), "default").expect("config");
+        assert!(config.tag_policy().is_none());
+        assert_eq!(config.tag_sort, TagSort::Alphabetical);
+
+        let mut annotations = minimal_annotations(true);
+        annotations.insert(
+            "gitops.operator.tag_pattern".to_string(),
+            r"^main-(\d+)$".to_string(),
+        );
+        annotations.insert(
+            "gitops.operator.tag_sort".to_string(),
+            "numeric".to_string(),
+        );
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert_eq!(config.tag_sort, TagSort::Numeric);
+        let policy = config.tag_policy().unwrap().unwrap();
+        assert_eq!(policy.newest(["main-9", "main-10"]), Some("main-10"));
+
+        // semver takes precedence over the pattern.
+        annotations.insert("gitops.operator.semver".to_string(), "^1".to_string());
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        let policy = config.tag_policy().unwrap().unwrap();
+        assert_eq!(policy.describe(), "semver ^1");
+
+        // Invalid sorts fall back to alphabetical, invalid patterns surface
+        // when the policy is built.
+        annotations.remove("gitops.operator.semver");
+        annotations.insert("gitops.operator.tag_sort".to_string(), "newest".to_string());
+        annotations.insert("gitops.operator.tag_pattern".to_string(), "(".to_string());
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert_eq!(config.tag_sort, TagSort::Alphabetical);
+        assert!(config.tag_policy().unwrap().is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_tag_suffix_annotation_overrides_operator_default() {
+        unsafe { std::env::remove_var("GITOPS_TAG_SUFFIX") };
+        let config =
+            Config::from_annotations(&minimal_annotations(true), "default").expect("config");
+        assert_eq!(config.tag_suffix, "");
+
+        unsafe { std::env::set_var("GITOPS_TAG_SUFFIX", "-amd64") };
+        let config =
+            Config::from_annotations(&minimal_annotations(true), "default").expect("config");
+        assert_eq!(config.tag_suffix, "-amd64");
+
+        let mut annotations = minimal_annotations(true);
+        annotations.insert(
+            "gitops.operator.tag_suffix".to_string(),
+            "-arm64".to_string(),
+        );
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert_eq!(config.tag_suffix, "-arm64");
+
+        // An empty override selects the manifest list; an invalid one is ignored.
+        annotations.insert("gitops.operator.tag_suffix".to_string(), "".to_string());
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert_eq!(config.tag_suffix, "");
+        annotations.insert(
+            "gitops.operator.tag_suffix".to_string(),
+            ":latest".to_string(),
+        );
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert_eq!(config.tag_suffix, "");
+        unsafe { std::env::remove_var("GITOPS_TAG_SUFFIX") };
+    }
+
+    #[test]
+    fn test_notifications_provider_annotation() {
+        let config =
+            Config::from_annotations(&minimal_annotations(true), "default").expect("config");
+        assert_eq!(config.notifications_provider, None);
+
+        let mut annotations = minimal_annotations(true);
+        annotations.insert(
+            "gitops.operator.notifications_provider".to_string(),
+            "discord".to_string(),
+        );
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert_eq!(
+            config.notifications_provider,
+            Some(NotificationProvider::Discord)
+        );
+
+        // An unknown provider leaves it to the webhook URL.
+        annotations.insert(
+            "gitops.operator.notifications_provider".to_string(),
+            "irc".to_string(),
+        );
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert_eq!(config.notifications_provider, None);
+    }
+
+    #[test]
+    #[serial]
+    fn test_image_tags_follow_tag_type_and_tag_template() {
+        unsafe { std::env::remove_var("GITOPS_TAG_SUFFIX") };
+        let sha = "3c0a88249fb61a0a4f4a65295f42b2dee3963c28";
+        let entry = |extra: &[(&str, &str)]| {
+            let mut annotations = minimal_annotations(true);
+            for (key, value) in extra {
+                annotations.insert(format!("gitops.operator.{}", key), value.to_string());
+            }
+            let deployment =
+                create_test_deployment("test-app", "default", "my-app:1.0.0", annotations);
+            Entry::new(&deployment).expect("entry")
+        };
+
+        assert_eq!(entry(&[]).tag_for(sha, None), sha);
+        assert_eq!(
+            entry(&[("tag_type", "short")]).tag_for(sha, None),
+            "3c0a882"
+        );
+        assert_eq!(
+            entry(&[("tag_type", "short:12")]).tag_for(sha, None),
+            "3c0a88249fb6"
+        );
+        assert_eq!(
+            entry(&[("tag_type", "short:12"), ("tag_template", "v-{sha_short}")])
+                .tag_for(sha, None),
+            "v-3c0a88249fb6"
+        );
+        assert_eq!(entry(&[]).tag_for(sha, Some("v1.4.2")), "v1.4.2");
+
+        let templated = entry(&[
+            ("tag_template", "{branch}-{sha_short}-{build}"),
+            ("tag_type", "short"),
+            ("vars", r#"{"build": "ci"}"#),
+            ("tag_suffix", "-arm64"),
+        ]);
+        assert_eq!(templated.tag_for(sha, None), "master-3c0a882-ci-arm64");
+        let templated = entry(&[("tag_template", "{tag}-{sha}")]);
+        assert_eq!(
+            templated.tag_for(sha, Some("v1.4.2")),
+            format!("v1.4.2-{}", sha)
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_checkouts_live_in_the_repo_cache_dir() {
+        let deployment = create_test_deployment(
+            "test-app",
+            "default",
+            "my-app:1.0.0",
+            minimal_annotations(true),
+        );
+        let entry = Entry::new(&deployment).expect("entry");
+
+        unsafe { std::env::set_var("GITOPS_REPO_CACHE_DIR", "/var/cache/gitops") };
+        assert!(
+            entry
+                .app_repo_path()
+                .starts_with("/var/cache/gitops/app-default-test-app-master-")
+        );
+        assert!(
+            entry
+                .manifest_repo_path()
+                .starts_with("/var/cache/gitops/manifest-default-test-app-master-")
+        );
+
+        unsafe { std::env::remove_var("GITOPS_REPO_CACHE_DIR") };
+        let default_dir = std::env::temp_dir().join("app-default-test-app-master-");
+        assert!(
+            entry
+                .app_repo_path()
+                .starts_with(&default_dir.display().to_string())
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_commit_message_annotation_overrides_operator_default() {
+        unsafe { std::env::remove_var("GITOPS_COMMIT_MESSAGE") };
+        let config =
+            Config::from_annotations(&minimal_annotations(true), "default").expect("config");
+        assert_eq!(config.commit_message, None);
+
+        unsafe { std::env::set_var("GITOPS_COMMIT_MESSAGE", "deploy({app}): {new_sha}") };
+        let config =
+            Config::from_annotations(&minimal_annotations(true), "default").expect("config");
+        assert_eq!(
+            config.commit_message.as_deref(),
+            Some("deploy({app}): {new_sha}")
+        );
+
+        let mut annotations = minimal_annotations(true);
+        annotations.insert(
+            "gitops.operator.commit_message".to_string(),
+            "release {app} {old_sha}..{new_sha}".to_string(),
+        );
+        let config = Config::from_annotations(&annotations, "default").expect("config");
+        assert_eq!(
+            config.commit_message.as_deref(),
+            Some("release {app} {old_sha}..{new_sha}")
+        );
+        unsafe { std::env::remove_var("GITOPS_COMMIT_MESSAGE") };
+    }
+
+    fn result(status: Status, error: Option<ErrorKind>) -> ReconcileResult {
+        ReconcileResult {
+            deployment: "app".to_string(),
+            namespace: "default".to_string(),
+            action: Action::Failed,
+            from_sha: None,
+            to_sha: None,
+            status,
+            message: String::new(),
+            resource_version: None,
+            generation: None,
+            error,
+            git_retries: 0,
+        }
+    }
+
+    #[test]
+    fn test_exit_code_maps_the_error_taxonomy() {
+        assert_eq!(exit_code(&[]), 0);
+        assert_eq!(exit_code(&[result(Status::Success, None)]), 0);
+        // Disabled deployments are skipped without an error.
+        assert_eq!(exit_code(&[result(Status::Skipped, None)]), 0);
+
+        assert_eq!(
+            exit_code(&[result(Status::Failure, Some(ErrorKind::Auth))]),
+            10
+        );
+        assert_eq!(
+            exit_code(&[result(Status::Failure, Some(ErrorKind::Network))]),
+            11
+        );
+        assert_eq!(
+            exit_code(&[result(Status::Skipped, Some(ErrorKind::Conflict))]),
+            12
+        );
+        assert_eq!(
+            exit_code(&[result(Status::Skipped, Some(ErrorKind::Policy))]),
+            13
+        );
+        assert_eq!(
+            exit_code(&[result(Status::Failure, Some(ErrorKind::Other))]),
+            1
+        );
+
+        // The most specific error wins when everything failed...
+        assert_eq!(
+            exit_code(&[
+                result(Status::Failure, Some(ErrorKind::Other)),
+                result(Status::Failure, Some(ErrorKind::Network)),
+                result(Status::Skipped, Some(ErrorKind::Policy)),
+            ]),
+            11
+        );
+        // ...and a mix of successes and failures is a partial success.
+        assert_eq!(
+            exit_code(&[
+                result(Status::Success, None),
+                result(Status::Failure, Some(ErrorKind::Auth)),
+            ]),
+            EXIT_PARTIAL_SUCCESS
+        );
+    }
+
+    #[test]
+    fn test_git_errors_are_classified() {
+        use git2::{Error, ErrorClass, ErrorCode};
+
+        let auth = Error::new(ErrorCode::Auth, ErrorClass::Ssh, "authentication required");
+        assert_eq!(ErrorKind::from_git(&auth), ErrorKind::Auth);
+        let ssh_auth = Error::new(
+            ErrorCode::GenericError,
+            ErrorClass::Ssh,
+            "Failed to authenticate SSH session: Unable to extract public key from private key file",
+        );
+        assert_eq!(ErrorKind::from_git(&ssh_auth), ErrorKind::Auth);
+        let unreachable = Error::new(
+            ErrorCode::GenericError,
+            ErrorClass::Net,
+            "failed to resolve address for github.com",
+        );
+        assert_eq!(ErrorKind::from_git(&unreachable), ErrorKind::Network);
+        let rejected = Error::new(
+            ErrorCode::NotFastForward,
+            ErrorClass::Reference,
+            "refs/heads/master rejected by remote: fetch first",
+        );
+        assert_eq!(ErrorKind::from_git(&rejected), ErrorKind::Conflict);
+        let unrebasable = Error::new(
+            ErrorCode::MergeConflict,
+            ErrorClass::Merge,
+            "Cannot rebase abc onto origin/master (def): conflicting changes",
+        );
+        assert_eq!(ErrorKind::from_git(&unrebasable), ErrorKind::Conflict);
+        let missing = Error::from_str("Could not find master branch in any expected location");
+        assert_eq!(ErrorKind::from_git(&missing), ErrorKind::Other);
     }
 }
@@ -0,0 +1,37 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::dependencies::parse_rules;
+
+    #[test]
+    fn test_parse_rules_applies_defaults() {
+        let rules = parse_rules(
+            r#"
+- source: default/base
+  manifest_repository: git@github.com:org/b-manifests.git
+  paths: [app-b/deployment.yaml]
+- source: default/sidecar
+  manifest_repository: git@github.com:org/b-manifests.git
+  branch: main
+  paths: [app-b/deployment.yaml, app-b/overlays/prod.yaml]
+  image: org/log-shipper
+  container_name: shipper
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].branch, "master");
+        assert_eq!(rules[0].target("org/base").image_names, vec!["org/base"]);
+        assert_eq!(rules[0].target("org/base").container_name, None);
+
+        assert_eq!(rules[1].branch, "main");
+        let target = rules[1].target("org/sidecar");
+        assert_eq!(target.image_names, vec!["org/log-shipper"]);
+        assert_eq!(target.container_name.as_deref(), Some("shipper"));
+    }
+
+    #[test]
+    fn test_parse_rules_rejects_missing_fields() {
+        assert!(parse_rules("- source: default/base\n").is_err());
+    }
+}
@@ -0,0 +1,186 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::configuration::ContainerImage;
+    use gitops_operator::drift::{
+        DEFAULT_DRIFT_GRACE, DriftBoard, DriftUpdate, RunningImage, detect, detect_digests,
+        digest_of, drift_grace, drift_notifications, verify_digests,
+    };
+    use serial_test::serial;
+    use std::time::Duration;
+
+    fn container(name: &str, tag: &str, tracked: bool) -> ContainerImage {
+        ContainerImage {
+            name: name.to_string(),
+            image: format!("kainlite/{}", name),
+            tag: tag.to_string(),
+            tracked,
+        }
+    }
+
+    #[test]
+    fn test_detect_compares_tracked_containers() {
+        let containers = [
+            container("blog", "hotfix", true),
+            container("worker", "abc", true),
+            container("proxy", "1.30", false),
+        ];
+
+        let drift = detect(&containers, "abc");
+        assert_eq!(drift.len(), 1);
+        assert_eq!(drift[0].container, "blog");
+        assert_eq!(drift[0].image, "kainlite/blog");
+        assert_eq!(drift[0].live_tag, "hotfix");
+        assert_eq!(drift[0].manifest_tag, "abc");
+
+        assert!(detect(&containers[1..], "abc").is_empty());
+    }
+
+    #[test]
+    fn test_lasting_drift_is_told_once_and_resolved() {
+        let board = DriftBoard::new(Duration::ZERO);
+        let drift = detect(&[container("blog", "hotfix", true)], "abc");
+
+        let DriftUpdate::Lasting(report) = board.update("default", "blog", drift.clone(), vec![])
+        else {
+            panic!("drift past the grace period should be told");
+        };
+        assert_eq!(report.containers, drift);
+        assert_eq!(
+            board.update("default", "blog", drift, vec![]),
+            DriftUpdate::Unchanged
+        );
+        assert_eq!(board.list().len(), 1);
+
+        assert!(matches!(
+            board.update("default", "blog", vec![], vec![]),
+            DriftUpdate::Resolved(_)
+        ));
+        assert!(board.list().is_empty());
+        assert_eq!(
+            board.update("default", "blog", vec![], vec![]),
+            DriftUpdate::Unchanged
+        );
+    }
+
+    #[test]
+    fn test_drift_within_the_grace_period_is_quiet() {
+        let board = DriftBoard::new(Duration::from_secs(3600));
+        let drift = detect(&[container("blog", "hotfix", true)], "abc");
+
+        assert_eq!(
+            board.update("default", "blog", drift, vec![]),
+            DriftUpdate::Unchanged
+        );
+        assert_eq!(board.list()[0].deployment, "blog");
+
+        // Drift that was never told resolves quietly too.
+        assert_eq!(
+            board.update("default", "blog", vec![], vec![]),
+            DriftUpdate::Unchanged
+        );
+        assert!(board.list().is_empty());
+    }
+
+    fn running(pod: &str, tag: &str, digest: &str) -> RunningImage {
+        RunningImage {
+            pod: pod.to_string(),
+            container: "blog".to_string(),
+            image: format!("kainlite/blog:{}", tag),
+            image_id: format!("docker-pullable://kainlite/blog@{}", digest),
+        }
+    }
+
+    #[test]
+    fn test_digest_of_image_ids() {
+        let digest = format!("sha256:{}", "0f".repeat(32));
+        assert_eq!(
+            digest_of(&format!("docker-pullable://kainlite/blog@{}", digest)),
+            Some(digest.as_str())
+        );
+        assert_eq!(
+            digest_of(&format!("ghcr.io/kainlite/blog@{}", digest)),
+            Some(digest.as_str())
+        );
+        assert_eq!(digest_of(&digest), Some(digest.as_str()));
+        assert_eq!(digest_of("kainlite/blog:abc"), None);
+        assert_eq!(digest_of("sha256:not-hex"), None);
+        assert_eq!(digest_of(""), None);
+    }
+
+    #[test]
+    fn test_detect_digests_groups_pods_by_digest() {
+        let (served, stale) = (
+            format!("sha256:{}", "aa".repeat(32)),
+            format!("sha256:{}", "bb".repeat(32)),
+        );
+        let pods = [
+            running("blog-1", "abc", &served),
+            running("blog-2", "abc", &stale),
+            running("blog-3", "abc", &stale),
+            // Still on the previous tag: tag drift, not digest drift.
+            running("blog-4", "old", &stale),
+            RunningImage {
+                container: "proxy".to_string(),
+                ..running("blog-1", "abc", &stale)
+            },
+        ];
+
+        let drift = detect_digests(&pods, &container("blog", "abc", true), &served);
+        assert_eq!(drift.len(), 1);
+        assert_eq!(drift[0].container, "blog");
+        assert_eq!(drift[0].tag, "abc");
+        assert_eq!(drift[0].manifest_digest, served);
+        assert_eq!(drift[0].running_digest, stale);
+        assert_eq!(drift[0].pods, vec!["blog-2", "blog-3"]);
+
+        assert!(detect_digests(&pods[..1], &container("blog", "abc", true), &served).is_empty());
+    }
+
+    #[test]
+    fn test_digest_drift_alone_is_drift() {
+        let board = DriftBoard::new(Duration::ZERO);
+        let digest = format!("sha256:{}", "aa".repeat(32));
+        let pods = [running(
+            "blog-1",
+            "abc",
+            &format!("sha256:{}", "bb".repeat(32)),
+        )];
+        let digests = detect_digests(&pods, &container("blog", "abc", true), &digest);
+
+        let DriftUpdate::Lasting(report) = board.update("default", "blog", vec![], digests.clone())
+        else {
+            panic!("digest drift past the grace period should be told");
+        };
+        assert!(report.containers.is_empty());
+        assert_eq!(report.digests, digests);
+        assert!(matches!(
+            board.update("default", "blog", vec![], vec![]),
+            DriftUpdate::Resolved(_)
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_drift_settings_from_env() {
+        unsafe { std::env::remove_var("GITOPS_DRIFT_GRACE_SECS") };
+        unsafe { std::env::remove_var("GITOPS_DRIFT_NOTIFICATIONS") };
+        unsafe { std::env::remove_var("GITOPS_VERIFY_DIGESTS") };
+        assert_eq!(drift_grace(), DEFAULT_DRIFT_GRACE);
+        assert!(!drift_notifications());
+        assert!(!verify_digests());
+
+        unsafe { std::env::set_var("GITOPS_DRIFT_GRACE_SECS", "60") };
+        unsafe { std::env::set_var("GITOPS_DRIFT_NOTIFICATIONS", "true") };
+        unsafe { std::env::set_var("GITOPS_VERIFY_DIGESTS", "true") };
+        assert_eq!(drift_grace(), Duration::from_secs(60));
+        assert!(drift_notifications());
+        assert!(verify_digests());
+
+        unsafe { std::env::set_var("GITOPS_DRIFT_GRACE_SECS", "soon") };
+        assert_eq!(drift_grace(), DEFAULT_DRIFT_GRACE);
+
+        unsafe { std::env::remove_var("GITOPS_DRIFT_GRACE_SECS") };
+        unsafe { std::env::remove_var("GITOPS_DRIFT_NOTIFICATIONS") };
+        unsafe { std::env::remove_var("GITOPS_VERIFY_DIGESTS") };
+    }
+}
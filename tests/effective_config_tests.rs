@@ -0,0 +1,145 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::admission::AnnotationDefaults;
+    use gitops_operator::effective_config::{ConfigSource, EffectiveValue, effective_config};
+    use std::collections::BTreeMap;
+
+    fn map(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn required() -> BTreeMap<String, String> {
+        map(&[
+            ("gitops.operator.enabled", "true"),
+            (
+                "gitops.operator.app_repository",
+                "git@github.com:org/app.git",
+            ),
+            (
+                "gitops.operator.manifest_repository",
+                "git@github.com:org/manifests.git",
+            ),
+            ("gitops.operator.image_name", "org/app"),
+            ("gitops.operator.deployment_path", "app/deployment.yaml"),
+            ("gitops.operator.ssh_key_name", "ssh-key"),
+            ("gitops.operator.ssh_key_namespace", "gitops-operator"),
+        ])
+    }
+
+    fn defaults(namespace: &str, pairs: &[(&str, &str)]) -> AnnotationDefaults {
+        AnnotationDefaults {
+            namespace: namespace.to_string(),
+            selector: BTreeMap::new(),
+            annotations: map(pairs),
+        }
+    }
+
+    fn setting<'a>(values: &'a [EffectiveValue], name: &str) -> &'a EffectiveValue {
+        let key = format!("gitops.operator.{}", name);
+        values
+            .iter()
+            .find(|value| value.key == key)
+            .unwrap_or_else(|| panic!("no {}", key))
+    }
+
+    #[test]
+    fn test_sources_of_each_setting() {
+        let mut annotations = required();
+        annotations.insert("gitops.operator.tag_type".into(), "short".into());
+        let rules = [defaults(
+            "default",
+            &[("gitops.operator.ssh_key_name", "ssh-key")],
+        )];
+
+        let config = effective_config("default", "blog", None, Some(&annotations), &rules);
+        assert!(config.tracked);
+
+        let key = setting(&config.values, "ssh_key_name");
+        assert_eq!(key.source, ConfigSource::NamespaceDefault);
+        assert!(key.notes.is_empty());
+
+        let tag_type = setting(&config.values, "tag_type");
+        assert_eq!(tag_type.source, ConfigSource::Annotation);
+        assert_eq!(tag_type.value.as_deref(), Some("short"));
+        assert_eq!(tag_type.notes, ["overrides the operator default"]);
+
+        let branch = setting(&config.values, "observe_branch");
+        assert_eq!(branch.source, ConfigSource::OperatorDefault);
+        assert_eq!(branch.value.as_deref(), Some("master"));
+
+        let policy = setting(&config.values, "policy");
+        assert_eq!(policy.source, ConfigSource::Unset);
+        assert_eq!(policy.value, None);
+    }
+
+    #[test]
+    fn test_overrides_and_conflicts_are_flagged() {
+        let mut annotations = required();
+        annotations.insert("gitops.operator.observe_branch".into(), "develop".into());
+        annotations.insert("gitops.operator.obsrve_branch".into(), "main".into());
+        annotations.insert("gitops.operator.last-synced-sha".into(), "abc".into());
+        let labels = map(&[("team", "web")]);
+        let mut web = defaults(
+            "default",
+            &[
+                ("gitops.operator.observe_branch", "main"),
+                ("gitops.operator.tag_type", "short"),
+            ],
+        );
+        web.selector = labels.clone();
+        let rules = [
+            web,
+            defaults("default", &[("gitops.operator.tag_type", "long")]),
+            defaults("other", &[("gitops.operator.policy", "false")]),
+        ];
+
+        let config = effective_config("default", "blog", Some(&labels), Some(&annotations), &rules);
+
+        let branch = setting(&config.values, "observe_branch");
+        assert_eq!(branch.source, ConfigSource::Annotation);
+        assert_eq!(branch.namespace_default.as_deref(), Some("main"));
+        assert_eq!(branch.notes, ["overrides the namespace default"]);
+
+        let tag_type = setting(&config.values, "tag_type");
+        assert_eq!(tag_type.source, ConfigSource::OperatorDefault);
+        assert_eq!(tag_type.notes.len(), 2, "{:?}", tag_type.notes);
+        assert!(tag_type.notes[0].contains("'short', 'long'"));
+        assert!(tag_type.notes[1].contains("predates"));
+
+        // Rules for other namespaces don't apply.
+        assert!(
+            setting(&config.values, "policy")
+                .namespace_default
+                .is_none()
+        );
+
+        let typo = setting(&config.values, "obsrve_branch");
+        assert_eq!(typo.notes, ["unknown setting, ignored"]);
+        assert!(!config.values.iter().any(|v| v.key.contains("last-synced")));
+
+        let diff = config.diff();
+        let keys: Vec<&str> = diff.values.iter().map(|v| v.key.as_str()).collect();
+        assert!(keys.contains(&"gitops.operator.observe_branch"));
+        assert!(keys.contains(&"gitops.operator.tag_type"));
+        assert!(keys.contains(&"gitops.operator.enabled"));
+        assert!(!keys.contains(&"gitops.operator.tag_sort"));
+    }
+
+    #[test]
+    fn test_missing_required_settings_are_flagged() {
+        let mut annotations = required();
+        annotations.remove("gitops.operator.image_name");
+
+        let config = effective_config("default", "blog", None, Some(&annotations), &[]);
+        assert!(!config.tracked);
+        let image = setting(&config.values, "image_name");
+        assert_eq!(image.source, ConfigSource::Unset);
+        assert!(image.notes[0].contains("not tracked"));
+
+        let config = effective_config("default", "blog", None, None, &[]);
+        assert!(!config.tracked);
+    }
+}
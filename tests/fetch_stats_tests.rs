@@ -0,0 +1,68 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::fetch_stats::{FetchSample, FetchStats, MAX_SAMPLES};
+    use std::time::Duration;
+
+    fn sample(ms: u64, bytes: u64, clone: bool, ok: bool) -> FetchSample {
+        FetchSample::new(Duration::from_millis(ms), bytes, bytes / 100, clone, ok)
+    }
+
+    #[test]
+    fn test_slow_repos_rank_by_total_fetch_time() {
+        let stats = FetchStats::new();
+        stats.record(
+            "git@github.com:org/app.git",
+            sample(100, 1_000, false, true),
+        );
+        stats.record(
+            "git@github.com:org/app.git",
+            sample(300, 3_000, false, false),
+        );
+        stats.record(
+            "git@github.com:org/monorepo.git",
+            sample(5_000, 900_000, true, true),
+        );
+        stats.record(
+            "git@github.com:org/manifests.git",
+            sample(50, 500, false, true),
+        );
+
+        let report = stats.slow_repos(10);
+        let order: Vec<&str> = report.iter().map(|r| r.repository.as_str()).collect();
+        assert_eq!(
+            order,
+            [
+                "git@github.com:org/monorepo.git",
+                "git@github.com:org/app.git",
+                "git@github.com:org/manifests.git",
+            ]
+        );
+
+        let app = &report[1];
+        assert_eq!(app.fetches, 2);
+        assert_eq!(app.clones, 0);
+        assert_eq!(app.failures, 1);
+        assert_eq!(app.total_ms, 400);
+        assert_eq!(app.mean_ms, 200);
+        assert_eq!(app.max_ms, 300);
+        assert_eq!(app.received_bytes, 4_000);
+        assert_eq!(app.mean_bytes, 2_000);
+        assert_eq!(app.max_bytes, 3_000);
+        assert_eq!(report[0].clones, 1);
+
+        assert_eq!(stats.slow_repos(1).len(), 1);
+    }
+
+    #[test]
+    fn test_only_recent_samples_are_kept() {
+        let stats = FetchStats::new();
+        for ms in 0..(MAX_SAMPLES as u64 + 10) {
+            stats.record("repo", sample(ms, 0, false, true));
+        }
+
+        let samples = stats.samples("repo");
+        assert_eq!(samples.len(), MAX_SAMPLES);
+        assert_eq!(samples[0].duration_ms, 10);
+        assert!(stats.samples("other").is_empty());
+    }
+}
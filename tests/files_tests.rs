@@ -1,6 +1,10 @@
 #[cfg(test)]
 mod tests {
-    use gitops_operator::files::{current_image_tag, needs_patching, patch_deployment};
+    use gitops_operator::files::{
+        ContainerTarget, ImageHost, PathSegment, current_image_tag, current_tag, find_manifests,
+        image_matches, images_need_patching, needs_patching, parse_key_path, patch_deployment,
+        patch_deployment_with, patch_images,
+    };
     use std::fs;
     use tempfile::TempDir;
 
@@ -203,4 +207,507 @@ spec:
             "sidecar container must be left unchanged"
         );
     }
+
+    #[test]
+    fn test_patch_statefulset() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("statefulset.yaml");
+
+        let yaml_content = r#"
+apiVersion: apps/v1
+kind: StatefulSet
+metadata:
+  name: db
+spec:
+  serviceName: db
+  selector:
+    matchLabels:
+      app: db
+  template:
+    spec:
+      containers:
+      - name: db
+        image: test-image:old-sha"#;
+        fs::write(&file_path, yaml_content).unwrap();
+        let path = file_path.to_str().unwrap();
+
+        assert!(needs_patching(path, "new-sha").unwrap());
+        patch_deployment(path, "test-image", "new-sha").unwrap();
+
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert!(content.contains("kind: StatefulSet"));
+        assert!(content.contains("serviceName: db"));
+        assert_eq!(
+            current_image_tag(path, "test-image").unwrap().as_deref(),
+            Some("new-sha")
+        );
+    }
+
+    #[test]
+    fn test_patch_daemonset() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("daemonset.yaml");
+
+        let yaml_content = r#"
+apiVersion: apps/v1
+kind: DaemonSet
+metadata:
+  name: log-shipper
+spec:
+  selector:
+    matchLabels:
+      app: log-shipper
+  template:
+    spec:
+      containers:
+      - name: shipper
+        image: test-image:old-sha"#;
+        fs::write(&file_path, yaml_content).unwrap();
+        let path = file_path.to_str().unwrap();
+
+        assert!(needs_patching(path, "new-sha").unwrap());
+        patch_deployment(path, "test-image", "new-sha").unwrap();
+
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert!(content.contains("kind: DaemonSet"));
+        assert_eq!(
+            current_image_tag(path, "test-image").unwrap().as_deref(),
+            Some("new-sha")
+        );
+    }
+
+    #[test]
+    fn test_patch_cronjob() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("cronjob.yaml");
+
+        let yaml_content = r#"
+apiVersion: batch/v1
+kind: CronJob
+metadata:
+  name: nightly-report
+spec:
+  schedule: "0 2 * * *"
+  jobTemplate:
+    spec:
+      template:
+        spec:
+          restartPolicy: OnFailure
+          containers:
+          - name: report
+            image: test-image:old-sha"#;
+        fs::write(&file_path, yaml_content).unwrap();
+        let path = file_path.to_str().unwrap();
+
+        assert!(needs_patching(path, "new-sha").unwrap());
+        patch_deployment(path, "test-image", "new-sha").unwrap();
+        assert!(!needs_patching(path, "new-sha").unwrap());
+
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert!(content.contains("kind: CronJob"));
+        assert!(content.contains("schedule: 0 2 * * *"));
+        assert_eq!(
+            current_image_tag(path, "test-image").unwrap().as_deref(),
+            Some("new-sha")
+        );
+    }
+
+    #[test]
+    fn test_patch_unsupported_kind() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("service.yaml");
+
+        fs::write(
+            &file_path,
+            "apiVersion: v1\nkind: Service\nmetadata:\n  name: web\n",
+        )
+        .unwrap();
+
+        let err = patch_deployment(file_path.to_str().unwrap(), "test-image", "new-sha")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("Unsupported manifest kind 'Service'"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_image_matches_ignores_registry_host() {
+        assert!(image_matches(
+            "harbor.example.com/project/nested/app:v1",
+            "project/nested/app"
+        ));
+        assert!(image_matches(
+            "project/nested/app:v1",
+            "harbor.example.com/project/nested/app"
+        ));
+        assert!(image_matches("registry.local:5000/team/app:v1", "team/app"));
+        assert!(!image_matches(
+            "harbor.example.com/project/nested/myapp:v1",
+            "app"
+        ));
+        assert!(!image_matches(
+            "harbor.example.com/project/nested/app:v1",
+            "nested"
+        ));
+    }
+
+    #[test]
+    fn test_patch_preserves_or_rewrites_registry_host() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("deployment.yaml");
+        let path = file_path.to_str().unwrap();
+
+        // Harbor-style nested project path; image_name omits the host.
+        fs::write(
+            &file_path,
+            create_test_deployment("harbor.example.com/project/nested/app:old-sha"),
+        )
+        .unwrap();
+        assert_eq!(
+            current_image_tag(path, "project/nested/app")
+                .unwrap()
+                .as_deref(),
+            Some("old-sha")
+        );
+
+        patch_deployment_with(path, "project/nested/app", "new-sha", ImageHost::Preserve).unwrap();
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert!(content.contains("image: harbor.example.com/project/nested/app:new-sha"));
+
+        patch_deployment_with(
+            path,
+            "registry.example.com/project/nested/app",
+            "newer-sha",
+            ImageHost::Rewrite,
+        )
+        .unwrap();
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert!(content.contains("image: registry.example.com/project/nested/app:newer-sha"));
+    }
+
+    #[test]
+    fn test_patch_does_not_match_image_name_substring() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("deployment.yaml");
+
+        fs::write(
+            &file_path,
+            create_test_deployment("harbor.example.com/project/my-test-image:old-sha"),
+        )
+        .unwrap();
+
+        let result = patch_deployment(file_path.to_str().unwrap(), "test-image", "new-sha");
+        assert!(
+            result.is_err(),
+            "a substring of the repository must not match"
+        );
+    }
+
+    #[test]
+    fn test_patch_images_patches_every_matching_container() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("deployment.yaml");
+        let path = file_path.to_str().unwrap();
+
+        // Two tracked images (one already at the new tag) and an untracked sidecar.
+        fs::write(
+            &file_path,
+            r#"
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: test-app
+spec:
+  template:
+    spec:
+      containers:
+      - name: app
+        image: ghcr.io/org/app:old-sha
+      - name: worker
+        image: ghcr.io/org/worker:new-sha
+      - name: sidecar
+        image: fluentd:v1.16
+"#,
+        )
+        .unwrap();
+        let images = ContainerTarget {
+            image_names: vec!["org/app".to_string(), "org/worker".to_string()],
+            container_name: None,
+            key_path: None,
+        };
+
+        assert!(images_need_patching(path, &images, "new-sha").unwrap());
+        assert_eq!(
+            patch_images(path, &images, "new-sha", ImageHost::Preserve).unwrap(),
+            1
+        );
+
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert!(content.contains("image: ghcr.io/org/app:new-sha"));
+        assert!(content.contains("image: ghcr.io/org/worker:new-sha"));
+        assert!(content.contains("image: fluentd:v1.16"));
+
+        assert!(!images_need_patching(path, &images, "new-sha").unwrap());
+        assert!(patch_images(path, &images, "new-sha", ImageHost::Preserve).is_err());
+    }
+
+    #[test]
+    fn test_container_name_targets_only_the_named_container() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("deployment.yaml");
+        let path = file_path.to_str().unwrap();
+
+        // Both containers run the same image; only "app" may be patched.
+        fs::write(
+            &file_path,
+            r#"
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: test-app
+spec:
+  template:
+    spec:
+      containers:
+      - name: migrate
+        image: ghcr.io/org/app:old-sha
+      - name: app
+        image: ghcr.io/org/app:old-sha
+"#,
+        )
+        .unwrap();
+        let target = ContainerTarget {
+            image_names: vec!["org/app".to_string()],
+            container_name: Some("app".to_string()),
+            key_path: None,
+        };
+
+        assert_eq!(
+            patch_images(path, &target, "new-sha", ImageHost::Preserve).unwrap(),
+            1
+        );
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert!(content.contains("image: ghcr.io/org/app:old-sha"));
+        assert!(content.contains("image: ghcr.io/org/app:new-sha"));
+        assert_eq!(
+            current_tag(path, &target).unwrap().as_deref(),
+            Some("new-sha")
+        );
+
+        let missing = ContainerTarget {
+            container_name: Some("web".to_string()),
+            ..target
+        };
+        let err = patch_images(path, &missing, "newer-sha", ImageHost::Preserve).unwrap_err();
+        assert!(err.to_string().contains("container 'web'"));
+    }
+
+    #[test]
+    fn test_find_manifests_walks_directory_for_matching_workloads() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("overlays/prod")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+
+        fs::write(
+            root.join("base.yaml"),
+            create_test_deployment("test-image:old-sha"),
+        )
+        .unwrap();
+        fs::write(
+            root.join("overlays/prod/deployment.yml"),
+            create_test_deployment("registry.example.com/test-image:old-sha"),
+        )
+        .unwrap();
+        // Not targeted: another image, a kustomization, a Service, not YAML.
+        fs::write(
+            root.join("other.yaml"),
+            create_test_deployment("other-image:v1"),
+        )
+        .unwrap();
+        fs::write(
+            root.join("overlays/prod/kustomization.yaml"),
+            "resources:\n- ../../base.yaml\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("service.yaml"),
+            "apiVersion: v1\nkind: Service\nmetadata:\n  name: app\n",
+        )
+        .unwrap();
+        fs::write(root.join("README.md"), "test-image:old-sha").unwrap();
+        fs::write(
+            root.join(".git/config.yaml"),
+            create_test_deployment("test-image:old-sha"),
+        )
+        .unwrap();
+
+        let found = find_manifests(
+            root.to_str().unwrap(),
+            &ContainerTarget::image("test-image"),
+        )
+        .unwrap();
+        let relative: Vec<String> = found
+            .iter()
+            .map(|path| {
+                path.strip_prefix(root.to_str().unwrap())
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            relative,
+            vec!["/base.yaml", "/overlays/prod/deployment.yml"]
+        );
+    }
+
+    #[test]
+    fn test_helm_values_patching_follows_values_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("prod")).unwrap();
+        let values = root.join("values.yaml");
+        let path = values.to_str().unwrap();
+        fs::write(
+            &values,
+            r#"
+replicaCount: 2
+image:
+  repository: ghcr.io/org/app
+  tag: old-sha
+workers:
+- name: queue
+  tag: 1234
+"#,
+        )
+        .unwrap();
+        fs::write(root.join("prod/values.yaml"), "image:\n  tag: old-sha\n").unwrap();
+        fs::write(root.join("Chart.yaml"), "name: app\nversion: 0.1.0\n").unwrap();
+
+        let target = ContainerTarget {
+            key_path: Some("image.tag".to_string()),
+            ..ContainerTarget::image("org/app")
+        };
+        assert_eq!(
+            current_tag(path, &target).unwrap().as_deref(),
+            Some("old-sha")
+        );
+        assert!(images_need_patching(path, &target, "new-sha").unwrap());
+        assert_eq!(
+            patch_images(path, &target, "new-sha", ImageHost::Preserve).unwrap(),
+            1
+        );
+
+        let content = fs::read_to_string(&values).unwrap();
+        assert!(content.contains("tag: new-sha"));
+        assert!(content.contains("repository: ghcr.io/org/app"));
+        assert!(content.contains("replicaCount: 2"));
+        assert!(!images_need_patching(path, &target, "new-sha").unwrap());
+        assert!(patch_images(path, &target, "new-sha", ImageHost::Preserve).is_err());
+
+        // List indexes and numeric tags.
+        let worker = ContainerTarget {
+            key_path: Some("workers.0.tag".to_string()),
+            ..target.clone()
+        };
+        assert_eq!(current_tag(path, &worker).unwrap().as_deref(), Some("1234"));
+
+        // A path that isn't there is an error, not a new key.
+        let typo = ContainerTarget {
+            key_path: Some("image.tags".to_string()),
+            ..target.clone()
+        };
+        let err = patch_images(path, &typo, "new-sha", ImageHost::Preserve).unwrap_err();
+        assert!(err.to_string().contains("No key image.tags"));
+
+        let found = find_manifests(root.to_str().unwrap(), &target).unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(found[0].ends_with("/prod/values.yaml"));
+        assert!(found[1].ends_with("/values.yaml"));
+    }
+
+    #[test]
+    fn test_parse_key_path() {
+        use PathSegment::{Index, Key};
+        let key = |k: &str| Key(k.to_string());
+
+        assert_eq!(
+            parse_key_path(".spec.template.spec.containers[0].image").unwrap(),
+            vec![
+                key("spec"),
+                key("template"),
+                key("spec"),
+                key("containers"),
+                Index(0),
+                key("image")
+            ]
+        );
+        assert_eq!(
+            parse_key_path("image.tag").unwrap(),
+            vec![key("image"), key("tag")]
+        );
+        assert_eq!(
+            parse_key_path(".[1][2].tag").unwrap(),
+            vec![Index(1), Index(2), key("tag")]
+        );
+        assert!(parse_key_path(".spec..image").is_err());
+        assert!(parse_key_path(".containers[x]").is_err());
+        assert!(parse_key_path(".containers[0").is_err());
+    }
+
+    #[test]
+    fn test_patch_expression_patches_arbitrary_documents() {
+        let temp_dir = TempDir::new().unwrap();
+        let rollout = temp_dir.path().join("rollout.yaml");
+        let path = rollout.to_str().unwrap();
+        fs::write(
+            &rollout,
+            r#"
+apiVersion: argoproj.io/v1alpha1
+kind: Rollout
+metadata:
+  name: app
+spec:
+  strategy:
+    canary:
+      steps:
+      - setWeight: 20
+  template:
+    spec:
+      containers:
+      - name: app
+        image: registry.example.com:5000/org/app:old-sha
+      - name: sidecar
+        image: envoy:v1
+"#,
+        )
+        .unwrap();
+
+        let target = ContainerTarget {
+            key_path: Some(".spec.template.spec.containers[0].image".to_string()),
+            ..ContainerTarget::image("org/app")
+        };
+        assert_eq!(
+            current_tag(path, &target).unwrap().as_deref(),
+            Some("old-sha")
+        );
+        assert!(images_need_patching(path, &target, "new-sha").unwrap());
+        patch_images(path, &target, "new-sha", ImageHost::Preserve).unwrap();
+
+        let content = fs::read_to_string(&rollout).unwrap();
+        assert!(content.contains("image: registry.example.com:5000/org/app:new-sha"));
+        assert!(content.contains("image: envoy:v1"));
+        assert!(content.contains("setWeight: 20"));
+        assert!(!images_need_patching(path, &target, "new-sha").unwrap());
+
+        let invalid = ContainerTarget {
+            key_path: Some(".spec.containers[first]".to_string()),
+            ..target.clone()
+        };
+        let err = patch_images(path, &invalid, "new-sha", ImageHost::Preserve).unwrap_err();
+        assert!(err.to_string().contains("Invalid key path"));
+    }
 }
@@ -1,9 +1,18 @@
 #[cfg(test)]
 mod tests {
     use git2::Repository;
+    use gitops_operator::fetch_stats::fetch_stats;
+    use gitops_operator::files::ContainerTarget;
     use gitops_operator::git::{
-        clone_or_update_repo, create_signature, get_latest_commit, stage_and_push_changes,
+        CloneStrategy, CommitAuthor, GitBackend, GitCliProvider, GitCredentials, GitRetryPolicy,
+        GitoxideProvider, HttpsToken, clone_or_update_repo, clone_or_update_repo_with,
+        create_signature, force_commit_changes, get_commit_author, get_latest_commit,
+        get_source_commit, image_tag_history, is_ambiguous, is_protected_branch, is_transient,
+        mirror_path, newest_tag, retry_transient, sha_length, stage_and_push_changes,
+        stage_and_push_changes_as, take_git_retries, touches_paths,
     };
+    use gitops_operator::traits::GitProvider;
+    use gitops_operator::versions::GitTagPattern;
     use std::fs;
     use std::path::Path;
     use std::process::Command;
@@ -101,6 +110,24 @@ mod tests {
         assert_eq!(signature.email().unwrap(), "kainlite+gitops@gmail.com");
     }
 
+    #[test]
+    fn test_credentials_identity_tells_credentials_apart() {
+        let key = GitCredentials::ssh("secret-key");
+        assert_eq!(key.identity(), GitCredentials::ssh("secret-key").identity());
+        assert!(!key.identity().contains("secret"));
+        assert_ne!(key.identity(), GitCredentials::ssh("other-key").identity());
+        assert_ne!(key.identity(), GitCredentials::default().identity());
+
+        let token = GitCredentials {
+            https_token: Some(HttpsToken {
+                username: None,
+                token: "ghp_secret".to_string(),
+            }),
+            ..GitCredentials::default()
+        };
+        assert_ne!(token.identity(), GitCredentials::default().identity());
+    }
+
     #[test]
     fn test_stage_and_push_skips_when_no_changes() {
         // Regression: when patch_deployment produces no diff (e.g. image_name
@@ -122,7 +149,7 @@ mod tests {
             &test_repo.repo,
             "should not create empty commit",
             "master",
-            "aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==",
+            &GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ=="),
         );
 
         let head_after = test_repo
@@ -164,7 +191,7 @@ mod tests {
             &test_repo.repo,
             "Test commit",
             "master",
-            "aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==",
+            &GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ=="),
         );
 
         std::thread::sleep(Duration::from_millis(1));
@@ -175,6 +202,59 @@ mod tests {
         assert_eq!(commit.message().unwrap(), "Test commit");
     }
 
+    #[test]
+    fn test_stage_and_push_changes_as_credits_author() {
+        let test_repo = TestRepo::new();
+        let _bare = test_repo.create_bare_clone();
+
+        fs::write(test_repo.dir.path().join("new-file.txt"), "content").unwrap();
+
+        let author = CommitAuthor {
+            name: "Jane Dev".to_string(),
+            email: "jane@example.com".to_string(),
+        };
+        let _ = stage_and_push_changes_as(
+            &test_repo.repo,
+            "Test commit",
+            "master",
+            &GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ=="),
+            Some(&author),
+        );
+
+        let commit = test_repo.repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(commit.author().name().unwrap(), "Jane Dev");
+        assert_eq!(commit.author().email().unwrap(), "jane@example.com");
+        assert_eq!(
+            commit.committer().name().unwrap(),
+            create_signature().unwrap().name().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_get_commit_author() {
+        let test_repo = TestRepo::new();
+        let _bare = test_repo.create_bare_clone();
+
+        let author = get_commit_author(test_repo.dir.path(), "master").unwrap();
+        assert_eq!(author.name, "test");
+        assert_eq!(author.email, "test@local");
+    }
+
+    #[test]
+    fn test_get_source_commit() {
+        let test_repo = TestRepo::new();
+        let head = test_repo.repo.head().unwrap().peel_to_commit().unwrap();
+        let sha = head.id().to_string();
+
+        let commit = get_source_commit(test_repo.dir.path(), &sha[..7]).unwrap();
+        assert_eq!(commit.sha, sha);
+        assert_eq!(commit.summary, "Initial commit");
+        assert_eq!(commit.author.name, "test");
+        assert_eq!(commit.author.email, "test@local");
+
+        assert!(get_source_commit(test_repo.dir.path(), "0000000").is_err());
+    }
+
     #[test]
     fn test_stage_and_push_changes_non_master_branch() {
         // Regression: the push refspec and fast-forward ref were hardcoded to
@@ -217,7 +297,7 @@ mod tests {
             &repo,
             "commit on develop",
             "develop",
-            "aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==",
+            &GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ=="),
         )
         .expect("push to develop should succeed");
 
@@ -230,6 +310,174 @@ mod tests {
         assert_eq!(commit.message().unwrap(), "commit on develop");
     }
 
+    /// Two clones of the same remote, as two deployments sharing a manifest
+    /// repository have.
+    fn clone_twice(bare_dir: &TempDir) -> (TempDir, Repository, TempDir, Repository) {
+        let repo_url = format!("file://{}", bare_dir.path().to_str().unwrap());
+        let credentials =
+            GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==");
+        let clone = || {
+            let dir = TempDir::new().unwrap();
+            let repo = clone_or_update_repo(
+                &repo_url,
+                dir.path().join("checkout"),
+                "master",
+                &credentials,
+            )
+            .unwrap();
+            (dir, repo)
+        };
+        let (first_dir, first) = clone();
+        let (second_dir, second) = clone();
+        (first_dir, first, second_dir, second)
+    }
+
+    #[test]
+    fn test_non_fast_forward_push_is_rebased_and_retried() {
+        let source_repo = TestRepo::new();
+        let bare_dir = source_repo.create_bare_clone();
+        let (_first_dir, first, _second_dir, second) = clone_twice(&bare_dir);
+        let credentials =
+            GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==");
+
+        fs::write(first.workdir().unwrap().join("first.yaml"), "first").unwrap();
+        stage_and_push_changes(&first, "first change", "master", &credentials).unwrap();
+
+        // The second clone is now behind: its push has to be rebased.
+        fs::write(second.workdir().unwrap().join("second.yaml"), "second").unwrap();
+        let author = CommitAuthor {
+            name: "Jane Dev".to_string(),
+            email: "jane@example.com".to_string(),
+        };
+        stage_and_push_changes_as(
+            &second,
+            "second change",
+            "master",
+            &credentials,
+            Some(&author),
+        )
+        .expect("rebased push should succeed");
+
+        let bare = Repository::open_bare(bare_dir.path()).unwrap();
+        let tip = bare
+            .find_reference("refs/heads/master")
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!(tip.message().unwrap(), "second change");
+        assert_eq!(tip.author().name().unwrap(), "Jane Dev");
+        assert_eq!(tip.parent(0).unwrap().message().unwrap(), "first change");
+        let tree = tip.tree().unwrap();
+        assert!(tree.get_name("first.yaml").is_some());
+        assert!(tree.get_name("second.yaml").is_some());
+        assert_eq!(
+            second.head().unwrap().peel_to_commit().unwrap().id(),
+            tip.id()
+        );
+    }
+
+    #[test]
+    fn test_conflicting_non_fast_forward_push_fails() {
+        let source_repo = TestRepo::new();
+        let bare_dir = source_repo.create_bare_clone();
+        let (_first_dir, first, _second_dir, second) = clone_twice(&bare_dir);
+        let credentials =
+            GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==");
+
+        fs::write(first.workdir().unwrap().join("README.md"), "first").unwrap();
+        stage_and_push_changes(&first, "first change", "master", &credentials).unwrap();
+
+        fs::write(second.workdir().unwrap().join("README.md"), "second").unwrap();
+        let err = stage_and_push_changes(&second, "second change", "master", &credentials)
+            .expect_err("conflicting changes can't be rebased");
+        assert_eq!(err.code(), git2::ErrorCode::MergeConflict);
+
+        let bare = Repository::open_bare(bare_dir.path()).unwrap();
+        let tip = bare
+            .find_reference("refs/heads/master")
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!(tip.message().unwrap(), "first change");
+    }
+
+    #[test]
+    fn test_force_push_replaces_a_rewritten_branch() {
+        let source_repo = TestRepo::new();
+        let bare_dir = source_repo.create_bare_clone();
+        TestRepo::git_command(&["push", "origin", "master:preview"], &source_repo.dir);
+        let repo_url = format!("file://{}", bare_dir.path().to_str().unwrap());
+        let credentials =
+            GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==");
+        let checkout = TempDir::new().unwrap();
+        let path = checkout.path().join("preview");
+        let repo = clone_or_update_repo(&repo_url, path.clone(), "preview", &credentials).unwrap();
+        assert!(!is_protected_branch(&repo, "preview"));
+
+        // Someone rewrites the preview branch under the checkout.
+        source_repo.add_and_commit_file("rewritten.yaml", "rewritten", "Rewritten history");
+        TestRepo::git_command(
+            &["push", "-f", "origin", "master:preview"],
+            &source_repo.dir,
+        );
+
+        fs::write(path.join("preview.yaml"), "preview").unwrap();
+        force_commit_changes(
+            path.to_str().unwrap(),
+            "preview",
+            "preview change",
+            &credentials,
+            None,
+        )
+        .expect("force push should succeed");
+
+        let bare = Repository::open_bare(bare_dir.path()).unwrap();
+        let tip = bare
+            .find_reference("refs/heads/preview")
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!(
+            tip.id(),
+            repo.head().unwrap().peel_to_commit().unwrap().id()
+        );
+        assert!(tip.tree().unwrap().get_name("rewritten.yaml").is_none());
+    }
+
+    #[test]
+    fn test_force_push_never_applies_to_protected_branches() {
+        let source_repo = TestRepo::new();
+        let bare_dir = source_repo.create_bare_clone();
+        let (_first_dir, first, _second_dir, second) = clone_twice(&bare_dir);
+        let credentials =
+            GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==");
+        assert!(is_protected_branch(&first, "master"));
+        assert!(is_protected_branch(&first, "main"));
+
+        fs::write(first.workdir().unwrap().join("README.md"), "first").unwrap();
+        stage_and_push_changes(&first, "first change", "master", &credentials).unwrap();
+
+        fs::write(second.workdir().unwrap().join("README.md"), "second").unwrap();
+        assert!(
+            force_commit_changes(
+                second.workdir().unwrap().to_str().unwrap(),
+                "master",
+                "second change",
+                &credentials,
+                None,
+            )
+            .is_err()
+        );
+
+        let bare = Repository::open_bare(bare_dir.path()).unwrap();
+        let tip = bare
+            .find_reference("refs/heads/master")
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!(tip.message().unwrap(), "first change");
+    }
+
     #[test]
     fn test_clone_or_update_repo_new() {
         // Setup source repository
@@ -249,7 +497,7 @@ mod tests {
             &repo_url,
             target_dir.path().to_path_buf(),
             "master",
-            "aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==",
+            &GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ=="),
         );
 
         std::thread::sleep(Duration::from_millis(1));
@@ -271,6 +519,78 @@ mod tests {
         fs::remove_dir_all(target_dir.path()).unwrap();
     }
 
+    #[test]
+    fn test_clone_strategies() {
+        let source_repo = TestRepo::new();
+        source_repo.add_and_commit_file("second.txt", "second", "Second commit");
+        let bare_dir = source_repo.create_bare_clone();
+        TestRepo::git_command(&["tag", "v1"], &source_repo.dir);
+        TestRepo::git_command(&["push", "origin", "v1", "master:other"], &source_repo.dir);
+        let repo_url = format!("file://{}", bare_dir.path().to_str().unwrap());
+        let credentials =
+            GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==");
+
+        let clone = |strategy| {
+            let dir = TempDir::new().unwrap();
+            let repo = clone_or_update_repo_with(
+                &repo_url,
+                dir.path().join("checkout"),
+                "master",
+                &credentials,
+                strategy,
+            )
+            .unwrap();
+            let has = |name: &str| repo.find_reference(name).is_ok();
+            let found = (has("refs/tags/v1"), has("refs/remotes/origin/other"));
+            (dir, repo, found)
+        };
+
+        let (_dir, repo, found) = clone(CloneStrategy::SingleBranch);
+        assert_eq!(found, (false, false));
+        assert!(repo.workdir().unwrap().join("second.txt").exists());
+        assert_eq!(
+            repo.head().unwrap().target(),
+            repo.refname_to_id("refs/remotes/origin/master").ok()
+        );
+
+        // Later fetches keep to the branch too.
+        source_repo.add_and_commit_file("third.txt", "third", "Third commit");
+        TestRepo::git_command(&["tag", "v2"], &source_repo.dir);
+        TestRepo::git_command(&["push", "origin", "v2", "master"], &source_repo.dir);
+        let path = repo.workdir().unwrap().to_path_buf();
+        let repo = clone_or_update_repo_with(
+            &repo_url,
+            path,
+            "master",
+            &credentials,
+            CloneStrategy::SingleBranch,
+        )
+        .unwrap();
+        assert!(repo.workdir().unwrap().join("third.txt").exists());
+        assert!(repo.find_reference("refs/tags/v2").is_err());
+
+        // Both checkouts are worktrees of the remote's mirror, which a full
+        // clone fetches everything into.
+        let (_dir, full, found) = clone(CloneStrategy::Full);
+        assert_eq!(found, (true, true));
+        assert!(full.is_worktree());
+        assert_eq!(
+            full.commondir().canonicalize().unwrap(),
+            mirror_path(&repo_url).canonicalize().unwrap()
+        );
+        assert!(repo.find_reference("refs/tags/v2").is_ok());
+
+        assert_eq!(
+            "shallow".parse::<CloneStrategy>().unwrap(),
+            CloneStrategy::Shallow
+        );
+        assert_eq!(
+            " single_branch ".parse::<CloneStrategy>().unwrap(),
+            CloneStrategy::SingleBranch
+        );
+        assert!("blobless".parse::<CloneStrategy>().is_err());
+    }
+
     #[test]
     fn test_clone_or_update_repo_existing() {
         // Setup source repository
@@ -288,7 +608,7 @@ mod tests {
             &repo_url,
             target_dir.path().to_path_buf(),
             "master",
-            "aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==",
+            &GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ=="),
         )
         .unwrap();
 
@@ -301,7 +621,7 @@ mod tests {
             &repo_url,
             target_dir.path().to_path_buf(),
             "master",
-            "aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==",
+            &GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ=="),
         );
 
         // Verify update
@@ -314,6 +634,15 @@ mod tests {
             content, "new content",
             "Updated content should match source"
         );
+        // Fast-forwarded with a detached HEAD, like a fresh checkout.
+        let updated = Repository::open(target_dir.path()).unwrap();
+        assert!(updated.head_detached().unwrap());
+
+        // Both transfers are timed for the slow-repo report.
+        let samples = fetch_stats().samples(&repo_url);
+        assert_eq!(samples.len(), 2);
+        assert!(samples[0].clone && !samples[1].clone);
+        assert!(samples.iter().all(|sample| sample.ok));
         fs::remove_dir_all(target_dir.path()).unwrap();
     }
 
@@ -324,7 +653,7 @@ mod tests {
             "file:///nonexistent/repo",
             target_dir.path().to_path_buf(),
             "master",
-            "aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==",
+            &GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ=="),
         );
         assert!(result.is_err(), "Should fail with invalid repository URL");
     }
@@ -381,14 +710,22 @@ mod tests {
             repo_path,
             "master",
             "short",
-            "aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==",
+            &GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ=="),
         )
         .unwrap();
+        let twelve = get_latest_commit(
+            repo_path,
+            "master",
+            "short:12",
+            &GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ=="),
+        )
+        .unwrap();
+        assert_eq!(twelve, commit_oid.to_string()[..12]);
         let long_commit_id = get_latest_commit(
             repo_path,
             "master",
             "long",
-            "aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==",
+            &GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ=="),
         )
         .unwrap();
 
@@ -406,4 +743,373 @@ mod tests {
             "Long commit ID should be 40 characters long"
         );
     }
+
+    fn manifest_with_image(image: &str) -> String {
+        format!(
+            "apiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: app\nspec:\n  template:\n    spec:\n      containers:\n      - name: app\n        image: {}\n",
+            image
+        )
+    }
+
+    #[test]
+    fn test_image_tag_history_collapses_unchanged_commits() {
+        let test_repo = TestRepo::new();
+        test_repo.add_and_commit_file("app.yaml", &manifest_with_image("org/app:aaa"), "one");
+        test_repo.add_and_commit_file("README.md", "unrelated change", "two");
+        test_repo.add_and_commit_file("app.yaml", &manifest_with_image("org/app:bbb"), "three");
+        test_repo.add_and_commit_file("app.yaml", &manifest_with_image("org/app:ccc"), "four");
+
+        let history = image_tag_history(
+            test_repo.dir.path(),
+            "app.yaml",
+            &ContainerTarget::image("org/app"),
+            10,
+        )
+        .unwrap();
+        let tags: Vec<_> = history.iter().map(|r| r.tag.as_str()).collect();
+        assert_eq!(tags, vec!["ccc", "bbb", "aaa"]);
+
+        // "aaa" is attributed to the commit that introduced it, not the later
+        // README commit that kept it unchanged.
+        let first = test_repo
+            .repo
+            .revparse_single("HEAD~3")
+            .unwrap()
+            .id()
+            .to_string();
+        assert_eq!(history[2].commit, first);
+        assert!(history[2].date.ends_with('Z'), "{}", history[2].date);
+
+        let limited = image_tag_history(
+            test_repo.dir.path(),
+            "app.yaml",
+            &ContainerTarget::image("org/app"),
+            2,
+        )
+        .unwrap();
+        assert_eq!(limited.len(), 2);
+        assert_eq!(limited[1].tag, "bbb");
+    }
+
+    #[test]
+    fn test_git_retry_delay_backs_off_with_jitter() {
+        let policy = GitRetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(100),
+            jitter: Duration::ZERO,
+        };
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(400));
+
+        let policy = GitRetryPolicy {
+            jitter: Duration::from_millis(50),
+            ..policy
+        };
+        for _ in 0..20 {
+            let delay = policy.delay(1);
+            assert!(delay >= Duration::from_millis(200), "{:?}", delay);
+            assert!(delay < Duration::from_millis(250), "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn test_only_network_failures_are_transient() {
+        use git2::{Error, ErrorClass, ErrorCode};
+
+        let net = Error::new(ErrorCode::GenericError, ErrorClass::Net, "connection reset");
+        let timeout = Error::new(ErrorCode::Timeout, ErrorClass::Os, "timed out");
+        let unavailable = Error::new(
+            ErrorCode::GenericError,
+            ErrorClass::Http,
+            "unexpected http status code: 503",
+        );
+        assert!(is_transient(&net));
+        assert!(is_transient(&timeout));
+        assert!(is_transient(&unavailable));
+
+        let auth = Error::new(ErrorCode::Auth, ErrorClass::Http, "authentication required");
+        let missing = Error::new(
+            ErrorCode::GenericError,
+            ErrorClass::Http,
+            "unexpected http status code: 404",
+        );
+        let rejected = Error::new(ErrorCode::NotFastForward, ErrorClass::Reference, "rejected");
+        let ssh_login = Error::new(
+            ErrorCode::GenericError,
+            ErrorClass::Ssh,
+            "Failed to authenticate SSH session",
+        );
+        assert!(!is_transient(&auth));
+        assert!(!is_transient(&missing));
+        assert!(!is_transient(&rejected));
+        assert!(!is_transient(&ssh_login));
+    }
+
+    #[test]
+    fn test_transient_failures_are_retried_and_counted() {
+        use git2::{Error, ErrorClass, ErrorCode};
+
+        let policy = GitRetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(1),
+            jitter: Duration::ZERO,
+        };
+        let checkout = TempDir::new().unwrap();
+        let blip = || Error::new(ErrorCode::GenericError, ErrorClass::Net, "connection reset");
+
+        let mut calls = 0;
+        let fetched = retry_transient(&policy, "fetch", checkout.path(), || {
+            calls += 1;
+            if calls < 3 { Err(blip()) } else { Ok(calls) }
+        });
+        assert_eq!(fetched.unwrap(), 3);
+        // Counted against the checkout however its path is spelled.
+        let spelled = format!("{}/", checkout.path().display());
+        assert_eq!(take_git_retries(Path::new(&spelled)), 2);
+        assert_eq!(take_git_retries(checkout.path()), 0);
+
+        // Gives up after `max_retries`.
+        let mut calls = 0;
+        let fetched: Result<(), _> = retry_transient(&policy, "fetch", checkout.path(), || {
+            calls += 1;
+            Err(blip())
+        });
+        assert!(fetched.is_err());
+        assert_eq!(calls, 4);
+        assert_eq!(take_git_retries(checkout.path()), 3);
+
+        // Other failures are returned at once.
+        let mut calls = 0;
+        let pushed: Result<(), _> = retry_transient(&policy, "push", checkout.path(), || {
+            calls += 1;
+            Err(Error::new(
+                ErrorCode::NotFastForward,
+                ErrorClass::Reference,
+                "rejected",
+            ))
+        });
+        assert!(pushed.is_err());
+        assert_eq!(calls, 1);
+        assert_eq!(take_git_retries(checkout.path()), 0);
+    }
+
+    #[test]
+    fn test_git_backends() {
+        assert_eq!(
+            "libgit2".parse::<GitBackend>().unwrap(),
+            GitBackend::LibGit2
+        );
+        assert_eq!(
+            " gitoxide ".parse::<GitBackend>().unwrap(),
+            GitBackend::Gitoxide
+        );
+        assert_eq!("git".parse::<GitBackend>().unwrap(), GitBackend::Cli);
+        assert!("svn".parse::<GitBackend>().is_err());
+        assert_eq!(GitBackend::default(), GitBackend::LibGit2);
+        assert_eq!(GitBackend::LibGit2.provider().name(), "libgit2");
+        assert_eq!(GitBackend::Gitoxide.provider().name(), "gitoxide");
+        assert_eq!(GitBackend::Cli.provider().name(), "git");
+    }
+
+    #[test]
+    fn test_gitoxide_clones_fetches_commits_and_pushes() {
+        let source = TestRepo::new();
+        source.add_and_commit_file("stale.txt", "stale", "Add a file to delete");
+        let bare_dir = source.create_bare_clone();
+        let bare = Repository::open(bare_dir.path()).unwrap();
+        let url = format!("file://{}", bare_dir.path().display());
+        let checkout = TempDir::new().unwrap();
+        let path = checkout.path().join("manifests");
+        let credentials = GitCredentials::default();
+        let git = GitoxideProvider;
+        let master = |repo: &Repository| repo.refname_to_id("refs/heads/master").unwrap();
+
+        git.sync(&url, &path, "master", &credentials, CloneStrategy::Full)
+            .unwrap();
+        assert!(path.join("stale.txt").exists());
+        assert_eq!(
+            git.resolve(&path, "master").unwrap(),
+            master(&bare).to_string()
+        );
+
+        // Fetching moves the checkout, removing what the new commit deleted.
+        source.add_and_commit_file("new.txt", "new content", "Add a file");
+        TestRepo::git_command(&["rm", "-q", "stale.txt"], &source.dir);
+        TestRepo::git_command(&["commit", "-qm", "Delete a file"], &source.dir);
+        TestRepo::git_command(&["push", "origin", "master"], &source.dir);
+        git.sync(&url, &path, "master", &credentials, CloneStrategy::Full)
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(path.join("new.txt")).unwrap(),
+            "new content"
+        );
+        assert!(!path.join("stale.txt").exists());
+        let local = Repository::open(&path).unwrap();
+        assert!(local.head_detached().unwrap());
+        assert_eq!(local.head().unwrap().target().unwrap(), master(&bare));
+
+        // Nothing to commit in a clean checkout.
+        assert!(!git.commit(&path, "Nothing", None).unwrap());
+
+        fs::write(path.join("new.txt"), "changed").unwrap();
+        fs::write(path.join("added.txt"), "added").unwrap();
+        fs::remove_file(path.join("README.md")).unwrap();
+        let author = CommitAuthor {
+            name: "Dev".to_string(),
+            email: "dev@example.com".to_string(),
+        };
+        assert!(git.commit(&path, "Update files", Some(&author)).unwrap());
+        assert!(!git.commit(&path, "Nothing", None).unwrap());
+        git.push(&path, "master", &credentials, false).unwrap();
+
+        let pushed = bare.find_commit(master(&bare)).unwrap();
+        assert_eq!(pushed.message().unwrap(), "Update files");
+        assert_eq!(pushed.author().email().unwrap(), "dev@example.com");
+        let tree = pushed.tree().unwrap();
+        let names: Vec<_> = tree
+            .iter()
+            .filter_map(|e| e.name().ok().map(String::from))
+            .collect();
+        assert_eq!(names, ["added.txt", "new.txt"]);
+    }
+
+    #[test]
+    fn test_git_cli_clones_fetches_commits_and_pushes() {
+        let source = TestRepo::new();
+        source.add_and_commit_file("stale.txt", "stale", "Add a file to delete");
+        let bare_dir = source.create_bare_clone();
+        let bare = Repository::open(bare_dir.path()).unwrap();
+        let url = format!("file://{}", bare_dir.path().display());
+        let checkout = TempDir::new().unwrap();
+        let path = checkout.path().join("manifests");
+        let credentials = GitCredentials::default();
+        let git = GitCliProvider;
+        let master = |repo: &Repository| repo.refname_to_id("refs/heads/master").unwrap();
+
+        git.sync(&url, &path, "master", &credentials, CloneStrategy::Shallow)
+            .unwrap();
+        assert!(path.join("stale.txt").exists());
+        assert_eq!(
+            git.resolve(&path, "master").unwrap(),
+            master(&bare).to_string()
+        );
+
+        source.add_and_commit_file("new.txt", "new content", "Add a file");
+        TestRepo::git_command(&["rm", "-q", "stale.txt"], &source.dir);
+        TestRepo::git_command(&["commit", "-qm", "Delete a file"], &source.dir);
+        TestRepo::git_command(&["push", "origin", "master"], &source.dir);
+        git.sync(&url, &path, "master", &credentials, CloneStrategy::Shallow)
+            .unwrap();
+        assert!(path.join("new.txt").exists());
+        assert!(!path.join("stale.txt").exists());
+        let local = Repository::open(&path).unwrap();
+        assert!(local.head_detached().unwrap());
+        assert_eq!(local.head().unwrap().target().unwrap(), master(&bare));
+
+        assert!(!git.commit(&path, "Nothing", None).unwrap());
+        fs::write(path.join("added.txt"), "added").unwrap();
+        let author = CommitAuthor {
+            name: "Dev".to_string(),
+            email: "dev@example.com".to_string(),
+        };
+        assert!(git.commit(&path, "Add added.txt", Some(&author)).unwrap());
+
+        // Someone else pushed in between: the push rebases onto it.
+        source.add_and_commit_file("other.txt", "other", "Concurrent change");
+        TestRepo::git_command(&["push", "origin", "master"], &source.dir);
+        git.push(&path, "master", &credentials, false).unwrap();
+
+        let pushed = bare.find_commit(master(&bare)).unwrap();
+        assert_eq!(pushed.message().unwrap().trim(), "Add added.txt");
+        assert_eq!(pushed.author().email().unwrap(), "dev@example.com");
+        assert_eq!(
+            pushed.parent(0).unwrap().message().unwrap().trim(),
+            "Concurrent change"
+        );
+    }
+
+    #[test]
+    fn test_newest_tag() {
+        let repo = TestRepo::new();
+        TestRepo::git_command(&["tag", "v1.2.0"], &repo.dir);
+        repo.add_and_commit_file("next.txt", "next", "Next release");
+        TestRepo::git_command(&["tag", "-a", "v1.10.0", "-m", "Release 1.10"], &repo.dir);
+        let head = Repository::open(repo.dir.path())
+            .unwrap()
+            .head()
+            .unwrap()
+            .target()
+            .unwrap();
+
+        let newest = newest_tag(repo.dir.path(), &GitTagPattern::parse("v*").unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(newest.name, "v1.10.0");
+        // Annotated tags resolve to their commit.
+        assert_eq!(newest.sha, head.to_string());
+        assert!(
+            newest_tag(repo.dir.path(), &GitTagPattern::parse("^2").unwrap())
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_touches_paths() {
+        let repo = TestRepo::new();
+        let head = || {
+            Repository::open(repo.dir.path())
+                .unwrap()
+                .head()
+                .unwrap()
+                .target()
+                .unwrap()
+                .to_string()
+        };
+        let base = head();
+        fs::create_dir_all(repo.dir.path().join("services/api")).unwrap();
+        repo.add_and_commit_file("services/api/main.rs", "fn main() {}", "Add the API");
+        let api = head();
+        repo.add_and_commit_file("docs.md", "docs", "Add docs");
+        let docs = head();
+
+        let paths = |paths: &[&str]| paths.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        let touches = |from: &str, to: &str, watched: &[&str]| {
+            touches_paths(repo.dir.path(), from, to, &paths(watched)).unwrap()
+        };
+        assert!(touches(&base, &docs, &["services/api"]));
+        assert!(!touches(&api, &docs, &["services/api", "libs"]));
+        assert!(touches(&api, &docs, &["*.md"]));
+        assert!(touches_paths(repo.dir.path(), "missing", &docs, &paths(&["libs"])).is_err());
+    }
+
+    #[test]
+    fn test_sha_lengths() {
+        assert_eq!(sha_length("long"), Some(40));
+        assert_eq!(sha_length("short"), Some(7));
+        assert_eq!(sha_length("short:12"), Some(12));
+        assert_eq!(sha_length("short:4"), Some(4));
+        assert_eq!(sha_length("short:3"), None);
+        assert_eq!(sha_length("short:41"), None);
+        assert_eq!(sha_length("medium"), None);
+    }
+
+    #[test]
+    fn test_is_ambiguous() {
+        let repo = TestRepo::new();
+        let git = Repository::open(repo.dir.path()).unwrap();
+        let head = git.head().unwrap().target().unwrap().to_string();
+        assert!(!is_ambiguous(repo.dir.path(), &head[..12]).unwrap());
+
+        // Write blobs until two share their first four characters.
+        let mut seen = std::collections::HashMap::new();
+        let prefix = (0..)
+            .find_map(|i| {
+                let id = git.blob(format!("blob {}", i).as_bytes()).unwrap();
+                let prefix = id.to_string()[..4].to_string();
+                seen.insert(prefix.clone(), id).map(|_| prefix)
+            })
+            .unwrap();
+        assert!(is_ambiguous(repo.dir.path(), &prefix).unwrap());
+    }
 }
@@ -0,0 +1,153 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::gitea::*;
+    use gitops_operator::pull_requests::{ChangeRequest, Forge};
+    use gitops_operator::traits::PullRequestOpener;
+
+    use serde_json::json;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_json, header, method, path, query_param},
+    };
+
+    const PULLS: &str = "/repos/homelab/manifests/pulls";
+
+    fn change_request() -> ChangeRequest {
+        ChangeRequest {
+            forge: Forge::Gitea,
+            repository: "git@forgejo.lan:homelab/manifests.git".to_string(),
+            head: "gitops-operator/default/blog-abc123".to_string(),
+            base: "main".to_string(),
+            title: "chore(refs): update blog to abc123".to_string(),
+            body: "Opened by gitops-operator".to_string(),
+            labels: vec!["deploy".to_string(), "missing".to_string()],
+            assignees: vec!["alice".to_string()],
+        }
+    }
+
+    fn pull(number: u64, head: &str) -> serde_json::Value {
+        json!({
+            "number": number,
+            "html_url": format!("https://forgejo.lan/homelab/manifests/pulls/{}", number),
+            "head": {"ref": head},
+            "base": {"ref": "main"}
+        })
+    }
+
+    #[test]
+    fn test_parse_gitea_repository() {
+        assert_eq!(
+            parse_gitea_repository("git@codeberg.org:homelab/manifests.git"),
+            Some((
+                "https://codeberg.org/api/v1".to_string(),
+                "homelab/manifests".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_gitea_repository("https://forgejo.lan:3000/homelab/manifests"),
+            Some((
+                "https://forgejo.lan:3000/api/v1".to_string(),
+                "homelab/manifests".to_string()
+            ))
+        );
+        // Gitea has no subgroups.
+        assert_eq!(
+            parse_gitea_repository("git@forgejo.lan:homelab/infra/manifests.git"),
+            None
+        );
+        assert_eq!(parse_gitea_repository("file:///tmp/manifests"), None);
+    }
+
+    #[tokio::test]
+    async fn test_open_pull_request_creates_one() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(PULLS))
+            .and(query_param("state", "open"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!([pull(2, "gitops-operator/default/other-def456")])),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/homelab/manifests/labels"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                {"id": 5, "name": "bug"},
+                {"id": 7, "name": "deploy"}
+            ])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(PULLS))
+            .and(header("authorization", "token forgejo-test"))
+            .and(body_json(json!({
+                "head": "gitops-operator/default/blog-abc123",
+                "base": "main",
+                "title": "chore(refs): update blog to abc123",
+                "body": "Opened by gitops-operator",
+                "labels": [7],
+                "assignees": ["alice"]
+            })))
+            .respond_with(
+                ResponseTemplate::new(201)
+                    .set_body_json(pull(8, "gitops-operator/default/blog-abc123")),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let opener = GiteaPullRequests::with_api_base(mock_server.uri());
+        let pull_request = opener
+            .open_pull_request(&change_request(), "forgejo-test")
+            .await
+            .unwrap();
+        assert_eq!(pull_request.number, 8);
+        assert!(pull_request.url.ends_with("/pulls/8"));
+    }
+
+    #[tokio::test]
+    async fn test_open_pull_request_reuses_an_open_one() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(PULLS))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!([pull(3, "gitops-operator/default/blog-abc123")])),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let opener = GiteaPullRequests::with_api_base(mock_server.uri());
+        let pull_request = opener
+            .open_pull_request(&change_request(), "forgejo-test")
+            .await
+            .unwrap();
+        assert_eq!(pull_request.number, 3);
+    }
+
+    #[tokio::test]
+    async fn test_open_pull_request_reports_refusals() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(PULLS))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&mock_server)
+            .await;
+
+        let opener = GiteaPullRequests::with_api_base(mock_server.uri());
+        let error = opener
+            .open_pull_request(&change_request(), "forgejo-bad")
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("403"));
+    }
+}
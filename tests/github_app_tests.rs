@@ -0,0 +1,138 @@
+#[cfg(test)]
+mod tests {
+    use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+    use gitops_operator::github_app::*;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::sign::Verifier;
+    use serde_json::{Value, json};
+    use std::time::{Duration, UNIX_EPOCH};
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{header_regex, method, path},
+    };
+
+    fn app(installation_id: Option<u64>) -> (GitHubApp, Rsa<openssl::pkey::Private>) {
+        let rsa = Rsa::generate(2048).unwrap();
+        let app = GitHubApp {
+            app_id: "123456".to_string(),
+            private_key: String::from_utf8(rsa.private_key_to_pem().unwrap()).unwrap(),
+            installation_id,
+        };
+        (app, rsa)
+    }
+
+    #[test]
+    fn test_app_jwt_is_signed_with_the_private_key() {
+        let (app, rsa) = app(None);
+        let now = UNIX_EPOCH + Duration::from_secs(1_792_143_000);
+
+        let jwt = app_jwt(&app, now).unwrap();
+        let parts: Vec<&str> = jwt.split('.').collect();
+        assert_eq!(parts.len(), 3);
+
+        let decode = |part: &str| -> Value {
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(part).unwrap()).unwrap()
+        };
+        assert_eq!(decode(parts[0]), json!({"alg": "RS256", "typ": "JWT"}));
+        assert_eq!(
+            decode(parts[1]),
+            json!({"iat": 1_792_142_940u64, "exp": 1_792_143_540u64, "iss": "123456"})
+        );
+
+        let key = PKey::from_rsa(rsa).unwrap();
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &key).unwrap();
+        verifier
+            .update(format!("{}.{}", parts[0], parts[1]).as_bytes())
+            .unwrap();
+        assert!(
+            verifier
+                .verify(&URL_SAFE_NO_PAD.decode(parts[2]).unwrap())
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_app_jwt_rejects_invalid_keys() {
+        let app = GitHubApp {
+            app_id: "123456".to_string(),
+            private_key: "not a key".to_string(),
+            installation_id: None,
+        };
+        let error = app_jwt(&app, UNIX_EPOCH).unwrap_err();
+        assert!(error.to_string().contains("private key"));
+    }
+
+    #[tokio::test]
+    async fn test_token_finds_the_installation_and_caches_the_token() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/repos/org/manifests/installation"))
+            .and(header_regex(
+                "authorization",
+                r"^Bearer [\w-]+\.[\w-]+\.[\w-]+$",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"id": 42})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/app/installations/42/access_tokens"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "token": "ghs_installation",
+                "expires_at": "2099-01-01T00:00:00Z"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let tokens = GitHubAppTokens::with_api_base(mock_server.uri());
+        let (app, _) = app(None);
+        assert_eq!(
+            tokens.token(&app, "org/manifests").await.unwrap(),
+            "ghs_installation"
+        );
+        assert_eq!(
+            tokens.token(&app, "org/manifests").await.unwrap(),
+            "ghs_installation"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_token_is_minted_again_when_about_to_expire() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/app/installations/7/access_tokens"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "token": "ghs_short_lived",
+                "expires_at": "2000-01-01T00:00:00Z"
+            })))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let tokens = GitHubAppTokens::with_api_base(mock_server.uri());
+        let (app, _) = app(Some(7));
+        tokens.token(&app, "org/manifests").await.unwrap();
+        tokens.token(&app, "org/manifests").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_token_reports_missing_installations() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/repos/org/manifests/installation"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+
+        let tokens = GitHubAppTokens::with_api_base(mock_server.uri());
+        let (app, _) = app(None);
+        let error = tokens.token(&app, "org/manifests").await.unwrap_err();
+        assert!(error.to_string().contains("not installed on org/manifests"));
+    }
+}
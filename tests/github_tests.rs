@@ -1,13 +1,14 @@
 #[cfg(test)]
 mod tests {
     use gitops_operator::github::*;
-    use gitops_operator::traits::{BuildStatus, BuildStatusChecker};
+    use gitops_operator::pull_requests::{ChangeRequest, Forge};
+    use gitops_operator::traits::{BuildStatus, BuildStatusChecker, PullRequestOpener};
 
     use serde_json::json;
     use tracing_subscriber::{EnvFilter, fmt};
     use wiremock::{
         Mock, MockServer, ResponseTemplate,
-        matchers::{header, method, path, query_param},
+        matchers::{body_partial_json, header, method, path, query_param},
     };
 
     fn init_logging() {
@@ -309,4 +310,167 @@ mod tests {
             .unwrap();
         assert_eq!(status, BuildStatus::Running);
     }
+
+    fn change_request(repository: &str) -> ChangeRequest {
+        ChangeRequest {
+            forge: Forge::GitHub,
+            repository: repository.to_string(),
+            head: "gitops-operator/default/blog-abc123".to_string(),
+            base: "master".to_string(),
+            title: "chore(refs): update blog to abc123".to_string(),
+            body: "Opened by gitops-operator".to_string(),
+            labels: vec![],
+            assignees: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_open_pull_request_creates_one() {
+        init_logging();
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/repos/kainlite/manifests/pulls"))
+            .and(query_param(
+                "head",
+                "kainlite:gitops-operator/default/blog-abc123",
+            ))
+            .and(query_param("base", "master"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/kainlite/manifests/pulls"))
+            .and(header("authorization", "Bearer test-token"))
+            .and(body_partial_json(json!({
+                "head": "gitops-operator/default/blog-abc123",
+                "base": "master",
+                "title": "chore(refs): update blog to abc123"
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "number": 42,
+                "html_url": "https://github.com/kainlite/manifests/pull/42"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let opener = GitHubPullRequests::with_api_base(mock_server.uri());
+        let pull_request = opener
+            .open_pull_request(
+                &change_request("git@github.com:kainlite/manifests.git"),
+                "test-token",
+            )
+            .await
+            .unwrap();
+        assert_eq!(pull_request.number, 42);
+        assert_eq!(
+            pull_request.url,
+            "https://github.com/kainlite/manifests/pull/42"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_open_pull_request_labels_and_assigns() {
+        init_logging();
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/repos/kainlite/manifests/pulls"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/kainlite/manifests/pulls"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "number": 42,
+                "html_url": "https://github.com/kainlite/manifests/pull/42"
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/kainlite/manifests/issues/42"))
+            .and(body_partial_json(json!({
+                "labels": ["deploy"],
+                "assignees": ["kainlite"]
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let opener = GitHubPullRequests::with_api_base(mock_server.uri());
+        let change = ChangeRequest {
+            labels: vec!["deploy".to_string()],
+            assignees: vec!["kainlite".to_string()],
+            ..change_request("git@github.com:kainlite/manifests.git")
+        };
+        let pull_request = opener
+            .open_pull_request(&change, "test-token")
+            .await
+            .unwrap();
+        assert_eq!(pull_request.number, 42);
+    }
+
+    #[tokio::test]
+    async fn test_open_pull_request_reuses_an_open_one() {
+        init_logging();
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/repos/kainlite/manifests/pulls"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+                "number": 7,
+                "html_url": "https://github.com/kainlite/manifests/pull/7"
+            }])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let opener = GitHubPullRequests::with_api_base(mock_server.uri());
+        let pull_request = opener
+            .open_pull_request(
+                &change_request("https://github.com/kainlite/manifests"),
+                "test-token",
+            )
+            .await
+            .unwrap();
+        assert_eq!(pull_request.number, 7);
+    }
+
+    #[tokio::test]
+    async fn test_open_pull_request_reports_refusals() {
+        init_logging();
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/repos/kainlite/manifests/pulls"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(422).set_body_string("No commits between"))
+            .mount(&mock_server)
+            .await;
+
+        let opener = GitHubPullRequests::with_api_base(mock_server.uri());
+        let error = opener
+            .open_pull_request(
+                &change_request("git@github.com:kainlite/manifests.git"),
+                "test-token",
+            )
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("422"));
+
+        let error = opener
+            .open_pull_request(&change_request("file:///tmp/manifests"), "test-token")
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("not a GitHub repository"));
+    }
 }
@@ -0,0 +1,145 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::gitlab::*;
+    use gitops_operator::pull_requests::{ChangeRequest, Forge};
+    use gitops_operator::traits::PullRequestOpener;
+
+    use serde_json::json;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_partial_json, header, method, path, query_param},
+    };
+
+    const MERGE_REQUESTS: &str = "/projects/platform%2Fdeploy%2Fmanifests/merge_requests";
+
+    fn change_request() -> ChangeRequest {
+        ChangeRequest {
+            forge: Forge::GitLab,
+            repository: "git@gitlab.example.com:platform/deploy/manifests.git".to_string(),
+            head: "gitops-operator/default/blog-abc123".to_string(),
+            base: "main".to_string(),
+            title: "chore(refs): update blog to abc123".to_string(),
+            body: "Opened by gitops-operator".to_string(),
+            labels: vec!["deploy".to_string(), "automated".to_string()],
+            assignees: vec!["alice".to_string(), "nobody".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_parse_gitlab_project() {
+        assert_eq!(
+            parse_gitlab_project("git@gitlab.example.com:platform/deploy/manifests.git"),
+            Some((
+                "https://gitlab.example.com/api/v4".to_string(),
+                "platform/deploy/manifests".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_gitlab_project("https://git.internal:8443/team/manifests"),
+            Some((
+                "https://git.internal:8443/api/v4".to_string(),
+                "team/manifests".to_string()
+            ))
+        );
+        assert_eq!(parse_gitlab_project("file:///tmp/manifests"), None);
+    }
+
+    #[tokio::test]
+    async fn test_open_merge_request_creates_one() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(MERGE_REQUESTS))
+            .and(query_param(
+                "source_branch",
+                "gitops-operator/default/blog-abc123",
+            ))
+            .and(query_param("target_branch", "main"))
+            .and(query_param("state", "opened"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/users"))
+            .and(query_param("username", "alice"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{"id": 12}])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/users"))
+            .and(query_param("username", "nobody"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(MERGE_REQUESTS))
+            .and(header("private-token", "glpat-test"))
+            .and(body_partial_json(json!({
+                "source_branch": "gitops-operator/default/blog-abc123",
+                "target_branch": "main",
+                "title": "chore(refs): update blog to abc123",
+                "description": "Opened by gitops-operator",
+                "labels": "deploy,automated",
+                "assignee_ids": [12]
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "iid": 9,
+                "web_url": "https://gitlab.example.com/platform/deploy/manifests/-/merge_requests/9"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let opener = GitLabMergeRequests::with_api_base(mock_server.uri());
+        let merge_request = opener
+            .open_pull_request(&change_request(), "glpat-test")
+            .await
+            .unwrap();
+        assert_eq!(merge_request.number, 9);
+        assert!(merge_request.url.ends_with("/merge_requests/9"));
+    }
+
+    #[tokio::test]
+    async fn test_open_merge_request_reuses_an_open_one() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(MERGE_REQUESTS))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+                "iid": 3,
+                "web_url": "https://gitlab.example.com/platform/deploy/manifests/-/merge_requests/3"
+            }])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let opener = GitLabMergeRequests::with_api_base(mock_server.uri());
+        let merge_request = opener
+            .open_pull_request(&change_request(), "glpat-test")
+            .await
+            .unwrap();
+        assert_eq!(merge_request.number, 3);
+    }
+
+    #[tokio::test]
+    async fn test_open_merge_request_reports_refusals() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(MERGE_REQUESTS))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&mock_server)
+            .await;
+
+        let opener = GitLabMergeRequests::with_api_base(mock_server.uri());
+        let error = opener
+            .open_pull_request(&change_request(), "glpat-bad")
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("401"));
+    }
+}
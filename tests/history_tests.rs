@@ -0,0 +1,272 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::configuration::{Action, ReconcileResult, Status};
+    use gitops_operator::history::{HistoryRetention, HistoryStore, compare_runs, now_rfc3339};
+    use serial_test::serial;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn result(
+        namespace: &str,
+        deployment: &str,
+        action: Action,
+        status: Status,
+    ) -> ReconcileResult {
+        ReconcileResult {
+            deployment: deployment.to_string(),
+            namespace: namespace.to_string(),
+            action,
+            from_sha: Some("aaa".to_string()),
+            to_sha: Some("bbb".to_string()),
+            status,
+            message: format!("{} reconciled", deployment),
+            resource_version: Some("1234".to_string()),
+            generation: Some(3),
+            error: None,
+            git_retries: 0,
+        }
+    }
+
+    #[test]
+    fn test_record_and_list_newest_first() {
+        let store = HistoryStore::in_memory().unwrap();
+        let started_at = now_rfc3339();
+
+        store
+            .record(
+                "run-1",
+                &started_at,
+                &[result("default", "blog", Action::Patched, Status::Success)],
+            )
+            .unwrap();
+        store
+            .record(
+                "run-2",
+                &started_at,
+                &[
+                    result("default", "blog", Action::UpToDate, Status::Success),
+                    result("shop", "api", Action::Failed, Status::Failure),
+                ],
+            )
+            .unwrap();
+
+        let all = store.list(10).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].deployment, "api");
+        assert_eq!(all[0].action, "failed");
+        assert_eq!(all[0].status, "failure");
+        assert_eq!(all[0].run_id, "run-2");
+        assert_eq!(all[2].action, "patched");
+        assert_eq!(all[2].from_sha.as_deref(), Some("aaa"));
+        assert_eq!(all[2].started_at, started_at);
+
+        assert_eq!(store.list(1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_for_deployment_filters_by_namespace_and_name() {
+        let store = HistoryStore::in_memory().unwrap();
+        store
+            .record(
+                "run-1",
+                &now_rfc3339(),
+                &[
+                    result("default", "blog", Action::Patched, Status::Success),
+                    result("staging", "blog", Action::Patched, Status::Success),
+                    result("default", "api", Action::Skipped, Status::Skipped),
+                ],
+            )
+            .unwrap();
+
+        let blog = store.for_deployment("default", "blog", 10).unwrap();
+        assert_eq!(blog.len(), 1);
+        assert_eq!(blog[0].namespace, "default");
+        assert!(
+            store
+                .for_deployment("default", "nope", 10)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_history_persists_across_reopen() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("history.db");
+        let path = path.to_str().unwrap();
+
+        HistoryStore::open(path)
+            .unwrap()
+            .record(
+                "run-1",
+                &now_rfc3339(),
+                &[result("default", "blog", Action::Patched, Status::Success)],
+            )
+            .unwrap();
+
+        let reopened = HistoryStore::open(path).unwrap();
+        assert_eq!(reopened.list(10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_older_databases_gain_the_resource_version_columns() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("history.db");
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE reconcile_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                run_id TEXT NOT NULL,
+                namespace TEXT NOT NULL,
+                deployment TEXT NOT NULL,
+                action TEXT NOT NULL,
+                status TEXT NOT NULL,
+                from_sha TEXT,
+                to_sha TEXT,
+                message TEXT NOT NULL,
+                started_at TEXT NOT NULL,
+                finished_at TEXT NOT NULL
+            );
+            INSERT INTO reconcile_history
+                (run_id, namespace, deployment, action, status, message, started_at, finished_at)
+                VALUES ('run-0', 'default', 'blog', 'patched', 'success', 'old', 'a', 'b');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let store = HistoryStore::open(path.to_str().unwrap()).unwrap();
+        store
+            .record(
+                "run-1",
+                &now_rfc3339(),
+                &[result("default", "blog", Action::Patched, Status::Success)],
+            )
+            .unwrap();
+
+        let records = store.list(10).unwrap();
+        assert_eq!(records[0].resource_version.as_deref(), Some("1234"));
+        assert_eq!(records[0].generation, Some(3));
+        assert_eq!(records[1].resource_version, None);
+    }
+
+    #[test]
+    fn test_retention_prunes_old_and_excess_records() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("history.db");
+        let path = path.to_str().unwrap();
+        let blog = [result("default", "blog", Action::Patched, Status::Success)];
+
+        let store = HistoryStore::open(path).unwrap();
+        store.record("run-0", &now_rfc3339(), &blog).unwrap();
+        rusqlite::Connection::open(path)
+            .unwrap()
+            .execute_batch("UPDATE reconcile_history SET finished_at = '2020-01-01T00:00:00Z'")
+            .unwrap();
+
+        let store = HistoryStore::open(path)
+            .unwrap()
+            .with_retention(HistoryRetention {
+                max_age: Some(Duration::from_secs(24 * 3600)),
+                max_rows: Some(2),
+            });
+        for run in ["run-1", "run-2", "run-3"] {
+            store.record(run, &now_rfc3339(), &blog).unwrap();
+        }
+
+        let runs: Vec<_> = store
+            .list(10)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.run_id)
+            .collect();
+        assert_eq!(runs, ["run-3", "run-2"]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_retention_from_env() {
+        unsafe {
+            std::env::remove_var("GITOPS_HISTORY_RETENTION_DAYS");
+            std::env::set_var("GITOPS_HISTORY_RETENTION_ROWS", "0");
+        }
+        let retention = HistoryRetention::from_env();
+        assert_eq!(retention.max_age, Some(Duration::from_secs(90 * 24 * 3600)));
+        assert_eq!(retention.max_rows, None);
+        unsafe {
+            std::env::remove_var("GITOPS_HISTORY_RETENTION_ROWS");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocking_runs_off_the_runtime() {
+        let store = Arc::new(HistoryStore::in_memory().unwrap());
+        store
+            .blocking(|history| {
+                history.record(
+                    "run-1",
+                    &now_rfc3339(),
+                    &[result("default", "blog", Action::Patched, Status::Success)],
+                )
+            })
+            .await
+            .unwrap();
+
+        let records = store.blocking(|history| history.list(10)).await.unwrap();
+        assert_eq!(records.len(), 1);
+    }
+
+    #[test]
+    fn test_compare_runs_reports_changes_and_failures() {
+        let store = HistoryStore::in_memory().unwrap();
+        store
+            .record(
+                "run-1",
+                &now_rfc3339(),
+                &[
+                    result("default", "blog", Action::Failed, Status::Failure),
+                    result("default", "api", Action::UpToDate, Status::Success),
+                    result("default", "web", Action::UpToDate, Status::Success),
+                    result("default", "old", Action::UpToDate, Status::Success),
+                ],
+            )
+            .unwrap();
+        store
+            .record(
+                "run-2",
+                &now_rfc3339(),
+                &[
+                    result("default", "blog", Action::Patched, Status::Success),
+                    result("default", "api", Action::Failed, Status::Failure),
+                    result("default", "web", Action::UpToDate, Status::Success),
+                    result("default", "new", Action::Failed, Status::Failure),
+                ],
+            )
+            .unwrap();
+
+        let a = store.for_run("run-1").unwrap();
+        let b = store.for_run("run-2").unwrap();
+        assert_eq!(a.len(), 4);
+        assert!(store.for_run("run-3").unwrap().is_empty());
+
+        let comparison = compare_runs("run-1", &a, "run-2", &b);
+        assert_eq!(comparison.run_a, "run-1");
+        assert_eq!(comparison.unchanged, 1);
+
+        let names = |changes: &[gitops_operator::history::OutcomeChange]| {
+            changes
+                .iter()
+                .map(|c| c.deployment.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&comparison.changed), ["api", "blog", "new", "old"]);
+        assert_eq!(names(&comparison.new_failures), ["api", "new"]);
+        assert_eq!(names(&comparison.resolved_failures), ["blog"]);
+
+        let blog = &comparison.resolved_failures[0];
+        assert_eq!(blog.before.as_ref().unwrap().action, "failed");
+        assert_eq!(blog.after.as_ref().unwrap().action, "patched");
+        let old = comparison.changed.iter().find(|c| c.deployment == "old");
+        assert!(old.unwrap().after.is_none());
+    }
+}
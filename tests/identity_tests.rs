@@ -0,0 +1,130 @@
+#[cfg(test)]
+mod tests {
+    use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+    use gitops_operator::identity::{
+        OperatorIdentity, deployment_from_pod_name, service_account_from_token,
+    };
+    use k8s_openapi::api::apps::v1::{Deployment, DeploymentSpec};
+    use k8s_openapi::api::core::v1::{PodSpec, PodTemplateSpec};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+    use serial_test::serial;
+
+    fn deployment(namespace: &str, name: &str, service_account: Option<&str>) -> Deployment {
+        Deployment {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                namespace: Some(namespace.to_string()),
+                ..ObjectMeta::default()
+            },
+            spec: Some(DeploymentSpec {
+                template: PodTemplateSpec {
+                    spec: Some(PodSpec {
+                        service_account_name: service_account.map(String::from),
+                        ..PodSpec::default()
+                    }),
+                    ..PodTemplateSpec::default()
+                },
+                ..DeploymentSpec::default()
+            }),
+            ..Deployment::default()
+        }
+    }
+
+    fn identity(service_account: &str) -> OperatorIdentity {
+        OperatorIdentity {
+            namespace: Some("gitops-operator".to_string()),
+            deployment: Some("gitops-operator".to_string()),
+            service_account: Some(service_account.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_deployment_from_pod_name() {
+        assert_eq!(
+            deployment_from_pod_name("gitops-operator-7d9f8b6c4-x2kqz").as_deref(),
+            Some("gitops-operator")
+        );
+        assert_eq!(
+            deployment_from_pod_name("blog-5c6d7e8f9-abcde").as_deref(),
+            Some("blog")
+        );
+        assert_eq!(deployment_from_pod_name("laptop"), None);
+        assert_eq!(deployment_from_pod_name("my-laptop"), None);
+    }
+
+    #[test]
+    fn test_service_account_from_token() {
+        let claims = URL_SAFE_NO_PAD.encode(
+            r#"{"iss":"kubernetes","sub":"system:serviceaccount:gitops-operator:operator"}"#,
+        );
+        let token = format!("eyJhbGciOiJSUzI1NiJ9.{}.signature\n", claims);
+        assert_eq!(
+            service_account_from_token(&token),
+            Some(("gitops-operator".to_string(), "operator".to_string()))
+        );
+
+        let claims = URL_SAFE_NO_PAD.encode(r#"{"sub":"alice"}"#);
+        assert_eq!(
+            service_account_from_token(&format!("header.{}.signature", claims)),
+            None
+        );
+        assert_eq!(service_account_from_token("not a token"), None);
+    }
+
+    #[test]
+    fn test_is_self() {
+        let operator = identity("operator");
+
+        assert!(operator.is_self(&deployment("gitops-operator", "gitops-operator", None)));
+        // A renamed copy still runs as the operator's service account.
+        assert!(operator.is_self(&deployment(
+            "gitops-operator",
+            "operator-canary",
+            Some("operator")
+        )));
+        assert!(!operator.is_self(&deployment("gitops-operator", "blog", None)));
+        assert!(!operator.is_self(&deployment("default", "gitops-operator", Some("operator"))));
+
+        // Sharing the namespace's default service account proves nothing.
+        let operator = identity("default");
+        assert!(!operator.is_self(&deployment("gitops-operator", "blog", None)));
+        assert!(!operator.is_self(&deployment("gitops-operator", "blog", Some("default"))));
+
+        assert!(!OperatorIdentity::none().is_self(&deployment(
+            "gitops-operator",
+            "gitops-operator",
+            None
+        )));
+    }
+
+    #[test]
+    #[serial]
+    fn test_identity_from_env() {
+        unsafe { std::env::set_var("GITOPS_OPERATOR_NAMESPACE", "ops") };
+        unsafe { std::env::set_var("POD_NAME", "gitops-operator-7d9f8b6c4-x2kqz") };
+        unsafe { std::env::set_var("GITOPS_OPERATOR_SERVICE_ACCOUNT", "operator") };
+        let identity = OperatorIdentity::from_env();
+        assert_eq!(identity.namespace.as_deref(), Some("ops"));
+        assert_eq!(identity.deployment.as_deref(), Some("gitops-operator"));
+        assert_eq!(identity.service_account.as_deref(), Some("operator"));
+
+        unsafe { std::env::set_var("GITOPS_OPERATOR_DEPLOYMENT", "operator") };
+        assert_eq!(
+            OperatorIdentity::from_env().deployment.as_deref(),
+            Some("operator")
+        );
+
+        unsafe { std::env::set_var("GITOPS_SKIP_SELF", "false") };
+        assert_eq!(OperatorIdentity::from_env(), OperatorIdentity::none());
+
+        for name in [
+            "GITOPS_SKIP_SELF",
+            "GITOPS_OPERATOR_NAMESPACE",
+            "GITOPS_OPERATOR_DEPLOYMENT",
+            "GITOPS_OPERATOR_SERVICE_ACCOUNT",
+            "POD_NAME",
+        ] {
+            unsafe { std::env::remove_var(name) };
+        }
+    }
+}
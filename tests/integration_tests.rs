@@ -2,10 +2,36 @@
 mod integration_tests {
     use anyhow::Result;
     use async_trait::async_trait;
-    use gitops_operator::configuration::{Action, DeploymentProcessor, Entry, Status};
-    use gitops_operator::git::{clone_repo, get_latest_commit};
+    use gitops_operator::approvals::{ApprovalQueue, ApprovalStatus};
+    use gitops_operator::backups::BackupStore;
+    use gitops_operator::configuration::{
+        Action, DeploymentProcessor, Entry, ErrorKind, LAST_SYNCED_AT_ANNOTATION,
+        LAST_SYNCED_SHA_ANNOTATION, Status,
+    };
+    use gitops_operator::dependencies::parse_rules;
+    use gitops_operator::drift::{DriftBoard, RunningImage};
+    use gitops_operator::egress::EgressPolicy;
+    use gitops_operator::fetch_stats::fetch_stats;
+    use gitops_operator::git::{
+        CloneStrategy, CommitAuthor, GitBackend, GitCredentials, HttpsToken, LibGit2Provider,
+        clone_repo, commit_changes, get_latest_commit,
+    };
+    use gitops_operator::github_app::{GitHubApp, GitHubAppTokens};
+    use gitops_operator::maintenance_windows::MaintenanceWindow;
+    use gitops_operator::notifications::NotificationEndpoint;
+    use gitops_operator::operator::Operator;
+    use gitops_operator::pauses::{PAUSED_ANNOTATION, PauseSource, Pauses};
+    use gitops_operator::policy::Policy;
+    use gitops_operator::pull_requests::{ChangeRequest, Forge, PullRequest};
+    use gitops_operator::registries::{DEFAULT_REGISTRY_URL, RegistryMap};
+    use gitops_operator::registry::RateLimited;
+    use gitops_operator::rollouts::{RolloutStatus, Rollouts};
+    use gitops_operator::suspension::{SUSPENDED_AT_ANNOTATION, SUSPENDED_REASON_ANNOTATION};
+    use gitops_operator::timeline::{Timeline, TimelineKind};
     use gitops_operator::traits::{
-        ImageChecker, ImageCheckerFactory, NotificationSender, SecretProvider,
+        ClusterReporter, EventSeverity, GitProvider, ImageChecker, ImageCheckerFactory,
+        MaintenanceWindowSource, NotificationSender, PolicySource, PullRequestOpener,
+        SecretProvider,
     };
     use k8s_openapi::api::apps::v1::Deployment;
     use k8s_openapi::api::core::v1::Container;
@@ -17,11 +43,22 @@ mod integration_tests {
     use std::fs;
     use std::path::Path;
     use std::process::Command;
-    use std::sync::Arc;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
     use tempfile::TempDir;
 
     // Mock implementations for testing
 
+    /// A throwaway GitHub App private key, generated once per run
+    fn test_app_key() -> String {
+        static KEY: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+        KEY.get_or_init(|| {
+            let rsa = openssl::rsa::Rsa::generate(2048).unwrap();
+            String::from_utf8(rsa.private_key_to_pem().unwrap()).unwrap()
+        })
+        .clone()
+    }
+
     /// Mock secret provider that returns predefined values
     struct MockSecretProvider {
         ssh_key: String,
@@ -41,14 +78,41 @@ mod integration_tests {
             Ok(self.ssh_key.clone())
         }
 
-        async fn get_notification_endpoint(&self, _name: &str, _namespace: &str) -> Result<String> {
-            Ok(String::new()) // No notifications in tests
+        async fn get_notification_endpoint(
+            &self,
+            _name: &str,
+            _namespace: &str,
+        ) -> Result<NotificationEndpoint> {
+            Ok(NotificationEndpoint::default()) // No notifications in tests
         }
 
         async fn get_github_token(&self, _name: &str, _namespace: &str) -> Result<String> {
             Ok("ghp_test_token".to_string())
         }
 
+        async fn get_forge_token(&self, _name: &str, _namespace: &str) -> Result<String> {
+            Ok("glpat_test_token".to_string())
+        }
+
+        async fn get_https_token(&self, _name: &str, _namespace: &str) -> Result<HttpsToken> {
+            Ok(HttpsToken {
+                username: None,
+                token: "https_test_token".to_string(),
+            })
+        }
+
+        async fn get_github_app(&self, _name: &str, _namespace: &str) -> Result<GitHubApp> {
+            Ok(GitHubApp {
+                app_id: "123456".to_string(),
+                private_key: test_app_key(),
+                installation_id: None,
+            })
+        }
+
+        async fn get_known_hosts(&self, _name: &str, _namespace: &str) -> Result<String> {
+            Ok("github.com ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl\n".to_string())
+        }
+
         async fn get_registry_auth(
             &self,
             _secret_name: &str,
@@ -59,6 +123,20 @@ mod integration_tests {
         }
     }
 
+    /// Tags every mock registry lists for every image
+    const REGISTRY_TAGS: [&str; 6] = [
+        "v1.3.9",
+        "v1.4.0",
+        "v1.4.2",
+        "v1.5.0-rc.1",
+        "v1.5.0",
+        "latest",
+    ];
+
+    /// Digest every mock registry serves for every tag
+    const REGISTRY_DIGEST: &str =
+        "sha256:aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+
     /// Mock image checker that always returns true (image exists)
     struct MockImageChecker;
 
@@ -67,6 +145,14 @@ mod integration_tests {
         async fn check_image(&self, _image: &str, _tag: &str) -> Result<bool> {
             Ok(true) // Always claim image exists
         }
+
+        async fn list_tags(&self, _image: &str) -> Result<Vec<String>> {
+            Ok(REGISTRY_TAGS.iter().map(|tag| tag.to_string()).collect())
+        }
+
+        async fn image_digest(&self, _image: &str, _tag: &str) -> Result<Option<String>> {
+            Ok(Some(REGISTRY_DIGEST.to_string()))
+        }
     }
 
     /// Mock image checker factory
@@ -83,16 +169,388 @@ mod integration_tests {
         }
     }
 
+    /// Image checker factory whose checkers find every image and record the
+    /// tags they were asked about, and the registries and credentials they
+    /// were created for
+    #[derive(Default)]
+    struct RecordingImageCheckerFactory {
+        tags: Arc<Mutex<Vec<String>>>,
+        registries: Arc<Mutex<Vec<CheckerRegistry>>>,
+    }
+
+    /// Registry URL and credentials an image checker was created for
+    type CheckerRegistry = (String, Option<String>);
+
+    struct RecordingImageChecker {
+        tags: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl ImageChecker for RecordingImageChecker {
+        async fn check_image(&self, _image: &str, tag: &str) -> Result<bool> {
+            self.tags.lock().unwrap().push(tag.to_string());
+            Ok(true)
+        }
+
+        async fn list_tags(&self, _image: &str) -> Result<Vec<String>> {
+            Ok(REGISTRY_TAGS.iter().map(|tag| tag.to_string()).collect())
+        }
+    }
+
+    #[async_trait]
+    impl ImageCheckerFactory for RecordingImageCheckerFactory {
+        async fn create(
+            &self,
+            registry_url: &str,
+            auth_token: Option<String>,
+        ) -> Result<Box<dyn ImageChecker>> {
+            self.registries
+                .lock()
+                .unwrap()
+                .push((registry_url.to_string(), auth_token));
+            Ok(Box::new(RecordingImageChecker {
+                tags: self.tags.clone(),
+            }))
+        }
+    }
+
+    /// Image checker factory whose checkers list build-number tags, the
+    /// image of `build-10` labelled with `revision`
+    struct LabelledImageCheckerFactory {
+        revision: String,
+    }
+
+    struct LabelledImageChecker {
+        revision: String,
+    }
+
+    #[async_trait]
+    impl ImageChecker for LabelledImageChecker {
+        async fn check_image(&self, _image: &str, _tag: &str) -> Result<bool> {
+            Ok(true)
+        }
+
+        async fn list_tags(&self, _image: &str) -> Result<Vec<String>> {
+            Ok(["build-9", "build-10", "build-11", "latest"]
+                .iter()
+                .map(|tag| tag.to_string())
+                .collect())
+        }
+
+        async fn image_labels(&self, _image: &str, tag: &str) -> Result<BTreeMap<String, String>> {
+            let revision = match tag {
+                "build-10" => self.revision.clone(),
+                _ => "0000000000000000000000000000000000000000".to_string(),
+            };
+            Ok(BTreeMap::from([(
+                "org.opencontainers.image.revision".to_string(),
+                revision,
+            )]))
+        }
+    }
+
+    #[async_trait]
+    impl ImageCheckerFactory for LabelledImageCheckerFactory {
+        async fn create(
+            &self,
+            _registry_url: &str,
+            _auth_token: Option<String>,
+        ) -> Result<Box<dyn ImageChecker>> {
+            Ok(Box::new(LabelledImageChecker {
+                revision: self.revision.clone(),
+            }))
+        }
+    }
+
+    /// Image checker factory whose checkers find the registry rate limiting
+    /// them
+    struct RateLimitedImageCheckerFactory;
+
+    struct RateLimitedImageChecker;
+
+    #[async_trait]
+    impl ImageChecker for RateLimitedImageChecker {
+        async fn check_image(&self, _image: &str, _tag: &str) -> Result<bool> {
+            Err(RateLimited {
+                registry: "index.docker.io".to_string(),
+                retry_after: Duration::from_secs(600),
+            }
+            .into())
+        }
+        async fn list_tags(&self, _image: &str) -> Result<Vec<String>> {
+            Ok(vec![])
+        }
+    }
+
+    #[async_trait]
+    impl ImageCheckerFactory for RateLimitedImageCheckerFactory {
+        async fn create(
+            &self,
+            _registry_url: &str,
+            _auth_token: Option<String>,
+        ) -> Result<Box<dyn ImageChecker>> {
+            Ok(Box::new(RateLimitedImageChecker))
+        }
+    }
+
+    /// Image checker factory whose primary registry is down: only checkers for
+    /// URLs containing `dr` find images
+    struct PrimaryDownImageCheckerFactory;
+
+    struct RegistryImageChecker {
+        up: bool,
+    }
+
+    #[async_trait]
+    impl ImageChecker for RegistryImageChecker {
+        async fn check_image(&self, _image: &str, _tag: &str) -> Result<bool> {
+            if self.up {
+                Ok(true)
+            } else {
+                anyhow::bail!("connection refused")
+            }
+        }
+
+        async fn list_tags(&self, _image: &str) -> Result<Vec<String>> {
+            Ok(vec![])
+        }
+    }
+
+    #[async_trait]
+    impl ImageCheckerFactory for PrimaryDownImageCheckerFactory {
+        async fn create(
+            &self,
+            registry_url: &str,
+            _auth_token: Option<String>,
+        ) -> Result<Box<dyn ImageChecker>> {
+            Ok(Box::new(RegistryImageChecker {
+                up: registry_url.contains("dr"),
+            }))
+        }
+    }
+
     /// Mock notification sender that does nothing
     struct MockNotificationSender;
 
     #[async_trait]
     impl NotificationSender for MockNotificationSender {
-        async fn send(&self, _message: &str, _endpoint: &str) -> Result<()> {
+        async fn send(&self, _message: &str, _endpoint: &NotificationEndpoint) -> Result<()> {
             Ok(()) // Do nothing
         }
     }
 
+    /// Cluster reporter that records the events and annotations it was asked
+    /// to publish, and serves `live` as the live Deployment and `running` as
+    /// the images of its pods
+    #[derive(Default)]
+    struct RecordingClusterReporter {
+        events: Mutex<Vec<(EventSeverity, String)>>,
+        annotations: Mutex<BTreeMap<String, String>>,
+        live: Mutex<Option<Deployment>>,
+        running: Mutex<Vec<RunningImage>>,
+    }
+
+    #[async_trait]
+    impl ClusterReporter for RecordingClusterReporter {
+        async fn publish_event(
+            &self,
+            _namespace: &str,
+            _name: &str,
+            severity: EventSeverity,
+            reason: &str,
+            _note: &str,
+        ) -> Result<()> {
+            self.events
+                .lock()
+                .unwrap()
+                .push((severity, reason.to_string()));
+            Ok(())
+        }
+
+        async fn annotate(
+            &self,
+            _namespace: &str,
+            _name: &str,
+            annotations: &BTreeMap<String, String>,
+        ) -> Result<()> {
+            self.annotations.lock().unwrap().extend(annotations.clone());
+            Ok(())
+        }
+
+        async fn remove_annotations(
+            &self,
+            _namespace: &str,
+            _name: &str,
+            keys: &[String],
+        ) -> Result<()> {
+            let mut annotations = self.annotations.lock().unwrap();
+            for key in keys {
+                annotations.remove(key);
+            }
+            Ok(())
+        }
+
+        async fn live_deployment(
+            &self,
+            _namespace: &str,
+            _name: &str,
+        ) -> Result<Option<Deployment>> {
+            Ok(self.live.lock().unwrap().clone())
+        }
+
+        async fn running_images(&self, _namespace: &str, _name: &str) -> Result<Vec<RunningImage>> {
+            Ok(self.running.lock().unwrap().clone())
+        }
+    }
+
+    /// Git provider doing what libgit2 does, recording the branches pushed to
+    #[derive(Default)]
+    struct RecordingGitProvider {
+        pushes: Mutex<Vec<String>>,
+    }
+
+    impl GitProvider for RecordingGitProvider {
+        fn name(&self) -> &'static str {
+            "recording"
+        }
+
+        fn clone_repo(
+            &self,
+            url: &str,
+            path: &Path,
+            branch: &str,
+            credentials: &GitCredentials,
+            strategy: CloneStrategy,
+        ) -> Result<(), git2::Error> {
+            LibGit2Provider.clone_repo(url, path, branch, credentials, strategy)
+        }
+
+        fn fetch(
+            &self,
+            url: &str,
+            path: &Path,
+            branch: &str,
+            credentials: &GitCredentials,
+            strategy: CloneStrategy,
+        ) -> Result<(), git2::Error> {
+            LibGit2Provider.fetch(url, path, branch, credentials, strategy)
+        }
+
+        fn resolve(&self, path: &Path, branch: &str) -> Result<String, git2::Error> {
+            LibGit2Provider.resolve(path, branch)
+        }
+
+        fn commit(
+            &self,
+            path: &Path,
+            message: &str,
+            author: Option<&CommitAuthor>,
+        ) -> Result<bool, git2::Error> {
+            LibGit2Provider.commit(path, message, author)
+        }
+
+        fn push(
+            &self,
+            path: &Path,
+            branch: &str,
+            credentials: &GitCredentials,
+            force: bool,
+        ) -> Result<(), git2::Error> {
+            self.pushes.lock().unwrap().push(branch.to_string());
+            LibGit2Provider.push(path, branch, credentials, force)
+        }
+    }
+
+    /// Maintenance window source serving a fixed list of windows
+    struct StaticMaintenanceWindows(Mutex<Vec<MaintenanceWindow>>);
+
+    #[async_trait]
+    impl MaintenanceWindowSource for StaticMaintenanceWindows {
+        async fn windows(&self) -> Result<Vec<MaintenanceWindow>> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+    }
+
+    /// Policy source serving a fixed list of policies
+    struct StaticPolicies(Mutex<Vec<Policy>>);
+
+    #[async_trait]
+    impl PolicySource for StaticPolicies {
+        async fn policies(&self) -> Result<Vec<Policy>> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+    }
+
+    /// Secret provider whose SSH key lookup always fails
+    struct FailingSecretProvider;
+
+    #[async_trait]
+    impl SecretProvider for FailingSecretProvider {
+        async fn get_ssh_key(&self, name: &str, _namespace: &str) -> Result<String> {
+            Err(anyhow::anyhow!("secret {} not found", name))
+        }
+
+        async fn get_notification_endpoint(
+            &self,
+            _name: &str,
+            _namespace: &str,
+        ) -> Result<NotificationEndpoint> {
+            Ok(NotificationEndpoint::default())
+        }
+
+        async fn get_github_token(&self, _name: &str, _namespace: &str) -> Result<String> {
+            Ok(String::new())
+        }
+
+        async fn get_forge_token(&self, _name: &str, _namespace: &str) -> Result<String> {
+            Ok(String::new())
+        }
+
+        async fn get_https_token(&self, name: &str, _namespace: &str) -> Result<HttpsToken> {
+            Err(anyhow::anyhow!("secret {} not found", name))
+        }
+
+        async fn get_github_app(&self, name: &str, _namespace: &str) -> Result<GitHubApp> {
+            Err(anyhow::anyhow!("secret {} not found", name))
+        }
+
+        async fn get_known_hosts(&self, name: &str, _namespace: &str) -> Result<String> {
+            Err(anyhow::anyhow!("secret {} not found", name))
+        }
+
+        async fn get_registry_auth(
+            &self,
+            _secret_name: &str,
+            _namespace: &str,
+            _registry_url: &str,
+        ) -> Result<String> {
+            Ok(String::new())
+        }
+    }
+
+    /// Pull request opener that records the changes proposed and the tokens
+    /// used, numbering the pull requests from 1
+    #[derive(Default)]
+    struct RecordingPullRequestOpener {
+        opened: Mutex<Vec<(ChangeRequest, String)>>,
+    }
+
+    #[async_trait]
+    impl PullRequestOpener for RecordingPullRequestOpener {
+        async fn open_pull_request(
+            &self,
+            change: &ChangeRequest,
+            token: &str,
+        ) -> Result<PullRequest> {
+            let mut opened = self.opened.lock().unwrap();
+            opened.push((change.clone(), token.to_string()));
+            Ok(PullRequest {
+                number: opened.len() as u64,
+                url: format!("https://github.com/example/manifests/pull/{}", opened.len()),
+            })
+        }
+    }
+
     /// Create a mock DeploymentProcessor for testing
     fn create_mock_processor(ssh_key: &str) -> DeploymentProcessor {
         DeploymentProcessor::new(
@@ -156,10 +614,11 @@ mod integration_tests {
 
             // If manifest repo, create deployment file
             if repo_type == "manifest" {
-                fs::create_dir_all(dir.path().join("deployments")).unwrap();
-                fs::write(
-                    dir.path().join("deployments/app.yaml"),
-                    r#"apiVersion: apps/v1
+                fs::create_dir_all(dir.path().join("deployments/overlays")).unwrap();
+                for path in ["deployments/app.yaml", "deployments/overlays/prod.yaml"] {
+                    fs::write(
+                        dir.path().join(path),
+                        r#"apiVersion: apps/v1
 kind: Deployment
 metadata:
   name: test-app
@@ -171,8 +630,9 @@ spec:
       - name: test-app
         image: test-app:cdea6a753ce3867ab4938088f538338d1e025d7d
 "#,
-                )
-                .unwrap();
+                    )
+                    .unwrap();
+                }
             }
 
             // Initial commit
@@ -313,25 +773,35 @@ spec:
         assert_eq!(entry.config.namespace, "default");
 
         // Clone repositories for verification
-        let app_link_path = format!("/tmp/app-{}-master", entry.name);
-        let manifest_link_path = format!("/tmp/manifest-{}-master", entry.name);
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
 
         // Clean up possible lingering directories
         fs::remove_dir_all(&app_link_path).ok();
         fs::remove_dir_all(&manifest_link_path).ok();
 
         // Clone both repositories to verify setup
-        clone_repo(&repos.get_app_url(), &app_link_path, "master", ssh_key);
+        clone_repo(
+            &repos.get_app_url(),
+            &app_link_path,
+            "master",
+            &GitCredentials::ssh(ssh_key),
+        );
         clone_repo(
             &repos.get_manifest_url(),
             &manifest_link_path,
             "master",
-            ssh_key,
+            &GitCredentials::ssh(ssh_key),
         );
 
         // Get latest commit
-        let latest_commit = get_latest_commit(Path::new(&app_link_path), "master", "long", ssh_key)
-            .expect("Failed to get latest commit");
+        let latest_commit = get_latest_commit(
+            Path::new(&app_link_path),
+            "master",
+            "long",
+            &GitCredentials::ssh(ssh_key),
+        )
+        .expect("Failed to get latest commit");
         dbg!(&latest_commit);
 
         // Verify we got a valid commit hash
@@ -392,25 +862,35 @@ spec:
         assert_eq!(entry.config.namespace, "default");
 
         // Clone repositories for verification
-        let app_link_path = format!("/tmp/app-{}-master", entry.name);
-        let manifest_link_path = format!("/tmp/manifest-{}-master", entry.name);
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
 
         // Clean up possible lingering directories
         fs::remove_dir_all(&app_link_path).ok();
         fs::remove_dir_all(&manifest_link_path).ok();
 
         // Clone both repositories
-        clone_repo(&repos.get_app_url(), &app_link_path, "master", ssh_key);
+        clone_repo(
+            &repos.get_app_url(),
+            &app_link_path,
+            "master",
+            &GitCredentials::ssh(ssh_key),
+        );
         clone_repo(
             &repos.get_manifest_url(),
             &manifest_link_path,
             "master",
-            ssh_key,
+            &GitCredentials::ssh(ssh_key),
         );
 
         // Get latest commit
-        let latest_commit = get_latest_commit(Path::new(&app_link_path), "master", "long", ssh_key)
-            .expect("Failed to get latest commit");
+        let latest_commit = get_latest_commit(
+            Path::new(&app_link_path),
+            "master",
+            "long",
+            &GitCredentials::ssh(ssh_key),
+        )
+        .expect("Failed to get latest commit");
 
         // Verify we got a valid commit hash
         assert_eq!(latest_commit.len(), 40, "Should get full commit hash");
@@ -467,8 +947,8 @@ spec:
 
         let entry = Entry::new(&deployment).expect("Failed to create entry");
 
-        let app_link_path = format!("/tmp/app-{}-master", entry.name);
-        let manifest_link_path = format!("/tmp/manifest-{}-master", entry.name);
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
         fs::remove_dir_all(&app_link_path).ok();
         fs::remove_dir_all(&manifest_link_path).ok();
 
@@ -488,14 +968,2289 @@ spec:
     }
 
     #[tokio::test]
-    async fn test_entry_creation() {
-        let deployment = create_test_deployment();
+    #[serial]
+    async fn test_rollback_to_previous_tag() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
         let entry = Entry::new(&deployment).expect("Failed to create entry");
 
-        assert_eq!(entry.name, "test-app");
-        assert_eq!(entry.namespace, "default");
-        assert!(entry.config.enabled);
-        assert_eq!(entry.config.image_name, "test-app");
-        assert_eq!(entry.config.deployment_path, "deployments/app.yaml");
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let policies = Arc::new(StaticPolicies(Mutex::new(vec![])));
+        let processor = create_mock_processor(ssh_key).with_policies(policies.clone());
+        let patched = entry.process_deployment_with(&processor).await;
+        assert_eq!(patched.action, Action::Patched, "{}", patched.message);
+
+        let result = processor.rollback(&entry, "previous").await;
+        assert_eq!(result.status, Status::Success, "{}", result.message);
+        assert_eq!(result.action, Action::RolledBack);
+        assert_eq!(result.from_sha, patched.to_sha);
+        assert_eq!(
+            result.to_sha.as_deref(),
+            Some("cdea6a753ce3867ab4938088f538338d1e025d7d")
+        );
+
+        // The rollback pauses the entry, so the next pass doesn't undo it and
+        // another rollback waits for a resume too.
+        assert!(result.message.contains("paused until resumed"));
+        let held = entry.process_deployment_with(&processor).await;
+        assert_eq!(held.status, Status::Skipped);
+        assert!(held.message.contains("is paused since"), "{}", held.message);
+        let held = processor.rollback(&entry, "previous").await;
+        assert_eq!(held.status, Status::Skipped, "{}", held.message);
+
+        processor.resume(&entry).await.unwrap();
+        let promoted = entry.process_deployment_with(&processor).await;
+        assert_eq!(promoted.action, Action::Patched, "{}", promoted.message);
+
+        // Policies hold rollbacks back like promotions.
+        policies.0.lock().unwrap().push(Policy {
+            object: "ConfigMap platform/pinned".to_string(),
+            expression: "!new_tag.startsWith('cdea')".to_string(),
+            namespaces: vec![],
+        });
+        let held = processor.rollback(&entry, "previous").await;
+        assert_eq!(held.action, Action::Deferred, "{}", held.message);
+        assert!(held.message.contains("ConfigMap platform/pinned"));
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_releases_lists_tags_newest_first() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let processor = create_mock_processor(ssh_key);
+        let patched = entry.process_deployment_with(&processor).await;
+        assert_eq!(patched.action, Action::Patched, "{}", patched.message);
+
+        let releases = processor
+            .releases(&entry, 10)
+            .await
+            .expect("Failed to read releases");
+        let tags: Vec<&str> = releases.iter().map(|r| r.tag.as_str()).collect();
+        assert_eq!(
+            tags,
+            vec![
+                patched.to_sha.as_deref().unwrap(),
+                "cdea6a753ce3867ab4938088f538338d1e025d7d"
+            ]
+        );
+        assert!(
+            releases
+                .iter()
+                .all(|r| !r.commit.is_empty() && !r.date.is_empty())
+        );
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_reconcile_patches_every_deployment_path_in_one_commit() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.deployment_path".to_string(),
+            "deployments/app.yaml, deployments/overlays/prod.yaml".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let processor = create_mock_processor(ssh_key);
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+        let new_sha = result.to_sha.unwrap();
+
+        for path in ["deployments/app.yaml", "deployments/overlays/prod.yaml"] {
+            let content = fs::read_to_string(Path::new(&manifest_link_path).join(path)).unwrap();
+            assert!(content.contains(&new_sha), "{} was not patched", path);
+        }
+        let log = Command::new("git")
+            .args(["log", "--format=%s", "--name-only", "-1"])
+            .current_dir(&manifest_link_path)
+            .output()
+            .unwrap();
+        let log = String::from_utf8_lossy(&log.stdout);
+        assert!(log.contains("deployments/app.yaml"));
+        assert!(log.contains("deployments/overlays/prod.yaml"));
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_manifests_are_pushed_to_manifest_branch() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        // main moved the image on from master: patching a checkout of master
+        // instead would conflict with it.
+        let bare = git2::Repository::open(repos.manifest_bare.path()).unwrap();
+        let master = bare.refname_to_id("refs/heads/master").unwrap();
+        let work = TempDir::new().unwrap();
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args(args)
+                .current_dir(work.path())
+                .output()
+                .unwrap();
+        };
+        git(&["clone", repos.manifest_bare.path().to_str().unwrap(), "."]);
+        git(&["checkout", "-b", "main"]);
+        let app = work.path().join("deployments/app.yaml");
+        let manifest = fs::read_to_string(&app).unwrap();
+        fs::write(&app, manifest.replace("cdea6a753ce", "0123456789a")).unwrap();
+        git(&[
+            "-c",
+            "user.name=test",
+            "-c",
+            "user.email=test@local",
+            "commit",
+            "-am",
+            "Only on main",
+        ]);
+        git(&["push", "origin", "main"]);
+        let ahead = bare.refname_to_id("refs/heads/main").unwrap();
+        assert_ne!(ahead, master);
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.name = Some("test-app-manifest-branch".to_string());
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.manifest_branch".to_string(),
+            "main".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        assert_eq!(entry.config.observe_branch, "master");
+        assert_eq!(entry.config.manifest_branch, "main");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let processor = create_mock_processor(ssh_key);
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+
+        // The commit lands on main; master of the manifests is untouched.
+        assert_eq!(bare.refname_to_id("refs/heads/master").unwrap(), master);
+        let main = bare
+            .find_reference("refs/heads/main")
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!(main.parent_id(0).unwrap(), ahead);
+        // Without a template, the default message names the deployment and tag.
+        assert_eq!(
+            main.message().unwrap().lines().next().unwrap(),
+            format!(
+                "chore(refs): gitops-operator updating test-app-manifest-branch to {}",
+                result.to_sha.unwrap()
+            )
+        );
+        let local = git2::Repository::open(&manifest_link_path).unwrap();
+        assert_eq!(
+            local.head().unwrap().peel_to_commit().unwrap().id(),
+            main.id()
+        );
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_commit_message_template_is_rendered() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.name = Some("test-app-commit-message".to_string());
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.commit_message".to_string(),
+            "deploy({namespace}/{app}): {branch} -> {new_sha}".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let processor = create_mock_processor(ssh_key);
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+        let new_sha = result.to_sha.unwrap();
+
+        let local = git2::Repository::open(&manifest_link_path).unwrap();
+        let commit = local.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(
+            commit.message().unwrap().lines().next().unwrap(),
+            format!(
+                "deploy(default/test-app-commit-message): master -> {}",
+                new_sha
+            )
+        );
+
+        // The body credits the app commit being shipped.
+        let message = commit.message().unwrap();
+        assert!(
+            message.contains(&format!("@{}: ", &new_sha[..7])),
+            "{}",
+            message
+        );
+        assert!(message.contains("\nAuthor: "), "{}", message);
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_active_maintenance_window_defers_promotion() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let window = MaintenanceWindow::from_fields(
+            "ConfigMap ops/node-upgrade".to_string(),
+            None,
+            None,
+            Some(&entry.namespace),
+        )
+        .unwrap();
+        let windows = Arc::new(StaticMaintenanceWindows(Mutex::new(vec![window])));
+        let processor = DeploymentProcessor::new(
+            Arc::new(MockSecretProvider::new(ssh_key)),
+            Arc::new(MockImageCheckerFactory),
+            Arc::new(MockNotificationSender),
+        )
+        .with_maintenance_windows(windows.clone());
+
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Deferred, "{}", result.message);
+        assert_eq!(result.status, Status::Skipped);
+        assert!(result.message.contains("ConfigMap ops/node-upgrade"));
+        let content =
+            fs::read_to_string(Path::new(&manifest_link_path).join("deployments/app.yaml"))
+                .unwrap();
+        assert!(!content.contains(result.to_sha.as_deref().unwrap()));
+
+        // Once the window clears the promotion goes through.
+        windows.0.lock().unwrap().clear();
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_freeze_windows_defer_promotion() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let set_windows = |deployment: &mut Deployment, windows: &str| {
+            deployment.metadata.annotations.as_mut().unwrap().insert(
+                "gitops.operator.freeze_windows".to_string(),
+                windows.to_string(),
+            );
+        };
+        // Every minute starts a day-long freeze, so one is always active.
+        set_windows(
+            &mut deployment,
+            "2000-01-01T00:00:00Z/2000-01-02T00:00:00Z; * * * * * for 1d",
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let processor = create_mock_processor(ssh_key);
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Deferred, "{}", result.message);
+        assert_eq!(result.status, Status::Skipped);
+        assert!(result.message.contains("* * * * *"), "{}", result.message);
+
+        set_windows(&mut deployment, "every weekend");
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.status, Status::Failure, "{}", result.message);
+
+        // Windows in the past don't hold anything back.
+        set_windows(&mut deployment, "2000-01-01T00:00:00Z/2000-01-02T00:00:00Z");
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_policies_hold_back_promotions_they_refuse() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.policy".to_string(),
+            "new_tag.startsWith('zzz')".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let policies = Arc::new(StaticPolicies(Mutex::new(vec![])));
+        let processor = create_mock_processor(ssh_key).with_policies(policies.clone());
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Deferred, "{}", result.message);
+        assert_eq!(result.status, Status::Skipped);
+        assert!(result.message.contains("annotation gitops.operator.policy"));
+        let content =
+            fs::read_to_string(Path::new(&manifest_link_path).join("deployments/app.yaml"))
+                .unwrap();
+        assert!(!content.contains(result.to_sha.as_deref().unwrap()));
+
+        // Cluster policies apply to the namespaces they name.
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.policy".to_string(),
+            "new_tag != old_tag".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        policies.0.lock().unwrap().push(Policy {
+            object: "ConfigMap platform/freeze".to_string(),
+            expression: "branch == 'release'".to_string(),
+            namespaces: vec![entry.namespace.clone()],
+        });
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Deferred, "{}", result.message);
+        assert!(result.message.contains("ConfigMap platform/freeze"));
+
+        // A policy that can't be evaluated refuses as a failure.
+        policies.0.lock().unwrap()[0].expression = "branch ==".to_string();
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.status, Status::Failure, "{}", result.message);
+
+        policies.0.lock().unwrap()[0].namespaces = vec!["elsewhere".to_string()];
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_promotions_needing_approval_wait_for_it() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        // The namespace requires approval though the deployment doesn't ask for it.
+        let queue = Arc::new(ApprovalQueue::new());
+        let processor = create_mock_processor(ssh_key)
+            .with_approvals(queue.clone(), vec![entry.namespace.clone()]);
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Deferred, "{}", result.message);
+        assert_eq!(result.status, Status::Skipped);
+        let pending = queue.list().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].status, ApprovalStatus::Pending);
+        assert_eq!(
+            pending[0].to_sha.as_str(),
+            result.to_sha.as_deref().unwrap()
+        );
+        assert!(
+            result.message.contains(&pending[0].id),
+            "{}",
+            result.message
+        );
+
+        // Still held on the next pass, and once rejected.
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Deferred, "{}", result.message);
+        queue.decide(&pending[0].id, false, None).unwrap();
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Deferred, "{}", result.message);
+        assert!(result.message.contains("rejected"), "{}", result.message);
+
+        // Once the rejection is cleared the change is queued again, and approving it
+        // lets the promotion through.
+        queue.complete(&entry.namespace, &entry.name).unwrap();
+        entry.process_deployment_with(&processor).await;
+        let pending = queue.list().unwrap();
+        queue.decide(&pending[0].id, true, Some("alice")).unwrap();
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+        assert!(queue.list().unwrap().is_empty());
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_unchanged_deployments_skip_the_pipeline_until_forced() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let processor = create_mock_processor(ssh_key).with_change_ttl(Duration::from_secs(60));
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::UpToDate, "{}", result.message);
+        assert!(result.message.contains("unchanged"), "{}", result.message);
+
+        // Forcing looks at the manifests again.
+        let forced = processor.with_force(true);
+        let result = entry.process_deployment_with(&forced).await;
+        assert_eq!(result.action, Action::UpToDate, "{}", result.message);
+        assert!(
+            result.message.contains("is up to date"),
+            "{}",
+            result.message
+        );
+        let processor = forced.with_force(false);
+
+        // So does a configuration change.
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.vars".to_string(),
+            r#"{"team": "web"}"#.to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        let result = entry.process_deployment_with(&processor).await;
+        assert!(
+            result.message.contains("is up to date"),
+            "{}",
+            result.message
+        );
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_entries_sharing_an_app_repository_fetch_it_once() {
+        let repos = TestRepos::new();
+        let other = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let first =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let mut second =
+            create_test_deployment_with_repos(&repos.get_app_url(), &other.get_manifest_url());
+        second.metadata.name = Some("test-app-b".to_string());
+        let entries = vec![
+            Entry::new(&first).expect("Failed to create entry"),
+            Entry::new(&second).expect("Failed to create entry"),
+        ];
+        let checkouts: Vec<String> = entries
+            .iter()
+            .flat_map(|entry| [entry.app_repo_path(), entry.manifest_repo_path()])
+            .collect();
+        for path in &checkouts {
+            fs::remove_dir_all(path).ok();
+        }
+
+        let processor = create_mock_processor(ssh_key);
+        let results = Entry::reconcile_entries_with(entries, &processor).await;
+        assert_eq!(results.len(), 2);
+        for result in &results {
+            assert_eq!(result.action, Action::Patched, "{}", result.message);
+        }
+        assert!(results[0].to_sha.is_some());
+        assert_eq!(results[0].to_sha, results[1].to_sha);
+
+        // One entry's clone of the app repository served both.
+        let samples = fetch_stats().samples(&repos.get_app_url());
+        assert_eq!(samples.len(), 1);
+        assert!(samples[0].clone);
+
+        for path in &checkouts {
+            fs::remove_dir_all(path).ok();
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_gitoxide_backend_reconciles() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.name = Some("test-app-gitoxide".to_string());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let processor =
+            create_mock_processor(ssh_key).with_git_provider(GitBackend::Gitoxide.provider());
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+
+        // Checkouts are clones of their own, and the commit reached the remote.
+        assert!(Path::new(&manifest_link_path).join(".git").is_dir());
+        let bare = git2::Repository::open(repos.manifest_bare.path()).unwrap();
+        let master = bare.refname_to_id("refs/heads/master").unwrap();
+        let local = git2::Repository::open(&manifest_link_path).unwrap();
+        assert_eq!(local.head().unwrap().target().unwrap(), master);
+        let manifest = bare
+            .find_commit(master)
+            .unwrap()
+            .tree()
+            .unwrap()
+            .get_path(Path::new("deployments/app.yaml"))
+            .unwrap()
+            .to_object(&bare)
+            .unwrap()
+            .peel_to_blob()
+            .unwrap();
+        let tag = result.to_sha.unwrap();
+        assert!(
+            String::from_utf8_lossy(manifest.content()).contains(&tag),
+            "{}",
+            tag
+        );
+
+        // The next pass fetches into the checkouts and finds nothing to do.
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::UpToDate, "{}", result.message);
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_observed_tags_are_promoted() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+        for tag in ["v1.2.0", "v1.10.0", "other-9"] {
+            Command::new("git")
+                .args(["tag", tag, "master"])
+                .current_dir(repos.app_bare.path())
+                .output()
+                .unwrap();
+        }
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.name = Some("test-app-tags".to_string());
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.observe_tag_pattern".to_string(),
+            "v*".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let processor = create_mock_processor(ssh_key);
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+        assert_eq!(result.to_sha.as_deref(), Some("v1.10.0"));
+        let manifest =
+            fs::read_to_string(Path::new(&manifest_link_path).join("deployments/app.yaml"))
+                .unwrap();
+        assert!(manifest.contains(":v1.10.0"), "{}", manifest);
+
+        // No matching tag fails the pass.
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.observe_tag_pattern".to_string(),
+            "release-*".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.status, Status::Failure, "{}", result.message);
+        assert!(result.message.contains("release-*"), "{}", result.message);
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_watch_paths_filter_promotions() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+        let work = TempDir::new().unwrap();
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .args(args)
+                .current_dir(work.path())
+                .output()
+                .unwrap();
+        };
+        git(&["clone", repos.app_bare.path().to_str().unwrap(), "."]);
+        let commit = |path: &str| {
+            let file = work.path().join(path);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, uuid::Uuid::new_v4().to_string()).unwrap();
+            git(&["add", "-A"]);
+            git(&[
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@local",
+                "commit",
+                "-m",
+                path,
+            ]);
+            git(&["push", "origin", "master"]);
+        };
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.name = Some("test-app-watch-paths".to_string());
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.watch_paths".to_string(),
+            "services/api, libs".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        // The manifest's tag isn't an app commit: nothing to diff against.
+        let processor = create_mock_processor(ssh_key);
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+        let synced = result.to_sha.unwrap();
+
+        commit("services/web/index.html");
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::UpToDate, "{}", result.message);
+        assert_eq!(result.to_sha.as_deref(), Some(synced.as_str()));
+        assert!(
+            result.message.contains("services/api"),
+            "{}",
+            result.message
+        );
+
+        commit("libs/common.rs");
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+        assert_ne!(result.to_sha.unwrap(), synced);
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_images_are_found_on_fallback_registries() {
+        let repos = TestRepos::new();
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.name = Some("test-app-fallback-registry".to_string());
+        let annotations = deployment.metadata.annotations.as_mut().unwrap();
+        annotations.insert(
+            "gitops.operator.registry_secret_url".to_string(),
+            "https://registry.example.com".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let processor = DeploymentProcessor::new(
+            Arc::new(MockSecretProvider::new("dummy-ssh-key-for-file-protocol")),
+            Arc::new(PrimaryDownImageCheckerFactory),
+            Arc::new(MockNotificationSender),
+        );
+        // Without a fallback, the primary being down fails the promotion.
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.status, Status::Failure, "{}", result.message);
+
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.fallback_registry_urls".to_string(),
+            "https://registry-dr.example.com".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+        assert!(
+            result
+                .message
+                .ends_with("(image found on fallback registry https://registry-dr.example.com)"),
+            "{}",
+            result.message
+        );
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_rate_limits_fail_the_image_check() {
+        let repos = TestRepos::new();
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.name = Some("test-app-rate-limited".to_string());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let processor = DeploymentProcessor::new(
+            Arc::new(MockSecretProvider::new("dummy-ssh-key-for-file-protocol")),
+            Arc::new(RateLimitedImageCheckerFactory),
+            Arc::new(MockNotificationSender),
+        );
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.status, Status::Failure, "{}", result.message);
+        assert_eq!(result.error, Some(ErrorKind::Network));
+        assert!(
+            result
+                .message
+                .contains("index.docker.io rate limit reached, checks resume in 600s"),
+            "{}",
+            result.message
+        );
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_tags_are_found_by_revision_label() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+        let head = git2::Repository::open(repos.app_bare.path())
+            .unwrap()
+            .refname_to_id("refs/heads/master")
+            .unwrap()
+            .to_string();
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.name = Some("test-app-revision-label".to_string());
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.tag_source".to_string(),
+            "revision_label".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let processor = DeploymentProcessor::new(
+            Arc::new(MockSecretProvider::new(ssh_key)),
+            Arc::new(LabelledImageCheckerFactory {
+                revision: head.clone(),
+            }),
+            Arc::new(MockNotificationSender),
+        );
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+        assert_eq!(result.to_sha.as_deref(), Some("build-10"));
+
+        // No image built from the commit yet.
+        let processor = DeploymentProcessor::new(
+            Arc::new(MockSecretProvider::new(ssh_key)),
+            Arc::new(LabelledImageCheckerFactory {
+                revision: "1111111".to_string(),
+            }),
+            Arc::new(MockNotificationSender),
+        );
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.status, Status::Failure, "{}", result.message);
+        assert!(
+            result.message.contains("none of its 4 most recent tags"),
+            "{}",
+            result.message
+        );
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_patched_manifests_are_backed_up_first() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let backup_dir =
+            std::env::temp_dir().join(format!("gitops-backups-{}", uuid::Uuid::new_v4()));
+        let backups = Arc::new(BackupStore::open(&backup_dir, 10).unwrap());
+        let processor = create_mock_processor(ssh_key).with_backups(backups.clone());
+
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+
+        let records = backups.records(Some(&entry.key()), 10);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].path, "deployments/app.yaml");
+        let original = backups
+            .content(&entry.key(), &records[0].hash)
+            .unwrap()
+            .unwrap();
+        let original = String::from_utf8(original).unwrap();
+        assert!(original.contains(result.from_sha.as_deref().unwrap()));
+        assert!(!original.contains(result.to_sha.as_deref().unwrap()));
+
+        fs::remove_dir_all(&backup_dir).ok();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_reconcile_writes_and_checks_the_architecture_tag() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.tag_suffix".to_string(),
+            "-arm64".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let checker = Arc::new(RecordingImageCheckerFactory::default());
+        let tags = checker.tags.clone();
+        let processor = DeploymentProcessor::new(
+            Arc::new(MockSecretProvider::new(ssh_key)),
+            checker,
+            Arc::new(MockNotificationSender),
+        );
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+
+        let new_tag = result.to_sha.unwrap();
+        assert!(new_tag.ends_with("-arm64"), "{}", new_tag);
+        assert_eq!(*tags.lock().unwrap(), vec![new_tag.clone()]);
+        let content =
+            fs::read_to_string(Path::new(&manifest_link_path).join("deployments/app.yaml"))
+                .unwrap();
+        assert!(content.contains(&format!("test-app:{}", new_tag)));
+
+        // The suffixed tag counts as up to date on the next pass.
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::UpToDate, "{}", result.message);
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_registry_map_resolves_credentials_by_image_prefix() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let checker = Arc::new(RecordingImageCheckerFactory::default());
+        let registries = checker.registries.clone();
+        let map =
+            RegistryMap::parse("- prefix: docker.io/library/test-\n  auth: anonymous\n").unwrap();
+        let processor = DeploymentProcessor::new(
+            Arc::new(MockSecretProvider::new(ssh_key)),
+            checker,
+            Arc::new(MockNotificationSender),
+        )
+        .with_registries(map);
+
+        // The map's entry for the image applies without any registry annotation.
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+        assert_eq!(
+            registries.lock().unwrap().pop(),
+            Some((DEFAULT_REGISTRY_URL.to_string(), None))
+        );
+
+        // A secret named on the deployment still wins.
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.registry_secret_name".to_string(),
+            "private".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        entry.process_deployment_with(&processor).await;
+        assert_eq!(
+            registries.lock().unwrap().pop(),
+            Some((
+                DEFAULT_REGISTRY_URL.to_string(),
+                Some("Basic dGVzdDp0ZXN0".to_string())
+            ))
+        );
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_semver_mode_promotes_the_newest_tag_in_range() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment
+            .metadata
+            .annotations
+            .as_mut()
+            .unwrap()
+            .insert("gitops.operator.semver".to_string(), "~1.4".to_string());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let checker = Arc::new(RecordingImageCheckerFactory::default());
+        let tags = checker.tags.clone();
+        let processor = DeploymentProcessor::new(
+            Arc::new(MockSecretProvider::new(ssh_key)),
+            checker,
+            Arc::new(MockNotificationSender),
+        );
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+        assert_eq!(result.to_sha.as_deref(), Some("v1.4.2"));
+        // A listed tag needs no wait for its build.
+        assert!(tags.lock().unwrap().is_empty());
+        let content =
+            fs::read_to_string(Path::new(&manifest_link_path).join("deployments/app.yaml"))
+                .unwrap();
+        assert!(content.contains("test-app:v1.4.2"));
+
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::UpToDate, "{}", result.message);
+
+        // Nothing within the range is a failure, not a promotion.
+        deployment
+            .metadata
+            .annotations
+            .as_mut()
+            .unwrap()
+            .insert("gitops.operator.semver".to_string(), "^2".to_string());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.status, Status::Failure, "{}", result.message);
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_reconcile_directory_mode_patches_every_manifest() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.deployment_path".to_string(),
+            "deployments".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let processor = create_mock_processor(ssh_key);
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+        let new_sha = result.to_sha.clone().unwrap();
+
+        for path in ["deployments/app.yaml", "deployments/overlays/prod.yaml"] {
+            let content = fs::read_to_string(Path::new(&manifest_link_path).join(path)).unwrap();
+            assert!(content.contains(&new_sha), "{} was not patched", path);
+        }
+
+        // Rollback resolves "previous" from the first manifest found.
+        let rollback = processor.rollback(&entry, "previous").await;
+        assert_eq!(rollback.action, Action::RolledBack, "{}", rollback.message);
+        assert_eq!(
+            rollback.to_sha.as_deref(),
+            Some("cdea6a753ce3867ab4938088f538338d1e025d7d")
+        );
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_reconcile_propagates_tag_to_dependent_repository() {
+        let repos = TestRepos::new();
+        let dependent = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        let dependency_path = "/tmp/dependency-default-test-app-0-master";
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+        fs::remove_dir_all(dependency_path).ok();
+
+        let rules = parse_rules(&format!(
+            r#"
+- source: default/test-app
+  manifest_repository: {}
+  paths: [deployments/app.yaml, deployments/overlays/prod.yaml]
+"#,
+            dependent.get_manifest_url()
+        ))
+        .unwrap();
+        let processor = create_mock_processor(ssh_key).with_dependency_rules(rules);
+
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+        assert!(
+            result.message.contains("also pinned in"),
+            "{}",
+            result.message
+        );
+        let new_sha = result.to_sha.unwrap();
+
+        // The dependent repository got a single commit touching both paths.
+        let verify = TempDir::new().unwrap();
+        let verify_path = verify.path().join("checkout");
+        clone_repo(
+            &dependent.get_manifest_url(),
+            verify_path.to_str().unwrap(),
+            "master",
+            &GitCredentials::ssh(ssh_key),
+        );
+        for path in ["deployments/app.yaml", "deployments/overlays/prod.yaml"] {
+            let content = fs::read_to_string(verify_path.join(path)).unwrap();
+            assert!(content.contains(&new_sha), "{} was not pinned", path);
+        }
+        let log = Command::new("git")
+            .args(["log", "--format=%s", "-1"])
+            .current_dir(&verify_path)
+            .output()
+            .unwrap();
+        assert!(String::from_utf8_lossy(&log.stdout).contains("pinning test-app"));
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+        fs::remove_dir_all(dependency_path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_rollback_rejects_invalid_target() {
+        let entry = Entry::new(&create_test_deployment()).expect("Failed to create entry");
+        let processor = create_mock_processor("unused");
+
+        let result = processor.rollback(&entry, "abc:latest; rm -rf").await;
+        assert_eq!(result.status, Status::Failure);
+        assert!(result.message.contains("Invalid rollback target"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_reconcile_records_manifest_patched_event_and_sync_annotations() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let reporter = Arc::new(RecordingClusterReporter::default());
+        let processor = create_mock_processor(ssh_key).with_cluster_reporter(reporter.clone());
+        let result = entry.process_deployment_with(&processor).await;
+
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+        assert_eq!(
+            *reporter.events.lock().unwrap(),
+            vec![(EventSeverity::Normal, "ManifestPatched".to_string())]
+        );
+
+        let annotations = reporter.annotations.lock().unwrap();
+        assert_eq!(
+            annotations.get(LAST_SYNCED_SHA_ANNOTATION),
+            result.to_sha.as_ref()
+        );
+        assert!(annotations.contains_key(LAST_SYNCED_AT_ANNOTATION));
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_repeated_push_failures_suspend_until_resumed() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+        // A stale ref lock in the remote makes every push fail.
+        fs::write(
+            repos.manifest_bare.path().join("refs/heads/master.lock"),
+            "",
+        )
+        .unwrap();
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let reporter = Arc::new(RecordingClusterReporter::default());
+        let processor = create_mock_processor(ssh_key)
+            .with_cluster_reporter(reporter.clone())
+            .with_failure_threshold(2);
+
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.status, Status::Failure);
+        assert!(result.message.contains("Failed to commit changes"));
+        assert!(!result.message.contains("suspended"));
+
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.status, Status::Failure);
+        assert!(
+            result
+                .message
+                .contains("suspended after 2 consecutive failures"),
+            "{}",
+            result.message
+        );
+        assert!(
+            reporter
+                .events
+                .lock()
+                .unwrap()
+                .contains(&(EventSeverity::Warning, "Suspended".to_string()))
+        );
+        let annotations = reporter.annotations.lock().unwrap().clone();
+        assert!(annotations.contains_key(SUSPENDED_AT_ANNOTATION));
+        assert!(annotations[SUSPENDED_REASON_ANNOTATION].contains("2 consecutive push failures"));
+
+        // The reflector picks up the annotations: the entry is skipped from now on.
+        deployment
+            .metadata
+            .annotations
+            .as_mut()
+            .unwrap()
+            .extend(annotations);
+        let suspended = Entry::new(&deployment).expect("Failed to create entry");
+        let result = suspended.process_deployment_with(&processor).await;
+        assert_eq!(result.status, Status::Skipped);
+        assert!(result.message.contains("is suspended"));
+
+        processor.resume(&suspended).await.unwrap();
+        let annotations = reporter.annotations.lock().unwrap();
+        assert!(!annotations.contains_key(SUSPENDED_AT_ANNOTATION));
+        assert!(!annotations.contains_key(SUSPENDED_REASON_ANNOTATION));
+        assert_eq!(
+            reporter.events.lock().unwrap().last(),
+            Some(&(EventSeverity::Normal, "Resumed".to_string()))
+        );
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_paused_deployments_wait_until_resumed() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let reporter = Arc::new(RecordingClusterReporter::default());
+        let processor = create_mock_processor(ssh_key).with_cluster_reporter(reporter.clone());
+
+        let paused = processor.pause(&entry, Some("alice".to_string()), true);
+        assert!(paused.await.unwrap());
+        assert!(!processor.pause(&entry, None, false).await.unwrap());
+        assert_eq!(
+            reporter.annotations.lock().unwrap()[PAUSED_ANNOTATION],
+            "true"
+        );
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.status, Status::Skipped);
+        assert!(
+            result.message.contains("is paused since"),
+            "{}",
+            result.message
+        );
+
+        // Resuming lifts the pause and removes the annotation.
+        processor.resume(&entry).await.unwrap();
+        assert!(
+            !reporter
+                .annotations
+                .lock()
+                .unwrap()
+                .contains_key(PAUSED_ANNOTATION)
+        );
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+
+        // The annotation pauses a deployment on its own, without disabling it.
+        deployment
+            .metadata
+            .annotations
+            .as_mut()
+            .unwrap()
+            .insert(PAUSED_ANNOTATION.to_string(), "true".to_string());
+        let annotated = Entry::new(&deployment).expect("Failed to create entry");
+        assert!(annotated.config.enabled);
+        assert_eq!(
+            annotated.paused.as_ref().map(|pause| pause.source),
+            Some(PauseSource::Annotation)
+        );
+        let result = annotated.process_deployment_with(&processor).await;
+        assert_eq!(result.status, Status::Skipped);
+        assert!(
+            result.message.contains(PAUSED_ANNOTATION),
+            "{}",
+            result.message
+        );
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_manifest_commits_go_on_the_timeline() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let timeline = Arc::new(Timeline::new());
+        let processor = create_mock_processor(ssh_key).with_timeline(timeline.clone());
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+
+        let events = timeline.events(&entry.key());
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, TimelineKind::ManifestCommit);
+        assert_eq!(events[0].to, result.to_sha);
+        let head = get_latest_commit(
+            Path::new(&manifest_link_path),
+            "master",
+            "long",
+            &GitCredentials::ssh(ssh_key),
+        )
+        .expect("Failed to read manifest head");
+        assert_eq!(
+            events[0].summary,
+            format!("Pushed manifest commit {}", head)
+        );
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_drift_between_the_cluster_and_the_manifest() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        // The cluster runs 1.0.0 while the manifest declares a commit tag.
+        let board = Arc::new(DriftBoard::new(Duration::ZERO));
+        let processor = create_mock_processor(ssh_key).with_drift(board.clone(), false);
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+
+        let drifted = board.list();
+        assert_eq!(drifted.len(), 1);
+        assert_eq!(drifted[0].deployment, entry.name);
+        assert_eq!(drifted[0].containers[0].live_tag, "1.0.0");
+        assert_eq!(
+            drifted[0].containers[0].manifest_tag,
+            "cdea6a753ce3867ab4938088f538338d1e025d7d"
+        );
+
+        // Once the cluster runs what the manifest declares, the drift is gone.
+        let tag = result.to_sha.expect("patched without a tag");
+        deployment
+            .spec
+            .as_mut()
+            .unwrap()
+            .template
+            .spec
+            .as_mut()
+            .unwrap()
+            .containers[0]
+            .image = Some(format!("test-app:{}", tag));
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        entry.process_deployment_with(&processor).await;
+        assert!(board.list().is_empty());
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_pushed_images_are_verified_rolling_out() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.verify_rollout".to_string(),
+            "true".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let reporter = Arc::new(RecordingClusterReporter {
+            live: Mutex::new(Some(deployment.clone())),
+            ..Default::default()
+        });
+        let rollouts = Arc::new(Rollouts::new());
+        let processor = create_mock_processor(ssh_key)
+            .with_cluster_reporter(reporter.clone())
+            .with_rollouts(
+                rollouts.clone(),
+                Duration::from_secs(5),
+                Duration::from_millis(10),
+            );
+
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+
+        // The check runs in the background, until the cluster rolls the new
+        // tag out to its one replica.
+        let tag = result.to_sha.expect("patched without a tag");
+        let mut live = deployment.clone();
+        live.spec
+            .as_mut()
+            .unwrap()
+            .template
+            .spec
+            .as_mut()
+            .unwrap()
+            .containers[0]
+            .image = Some(format!("test-app:{}", tag));
+        live.status = Some(k8s_openapi::api::apps::v1::DeploymentStatus {
+            replicas: Some(1),
+            updated_replicas: Some(1),
+            available_replicas: Some(1),
+            ..Default::default()
+        });
+        *reporter.live.lock().unwrap() = Some(live);
+        let key = entry.key();
+        let mut rollout = None;
+        for _ in 0..200 {
+            rollout = rollouts
+                .get(&key)
+                .unwrap()
+                .filter(|r| r.status.is_finished());
+            if rollout.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let rollout = rollout
+            .unwrap_or_else(|| panic!("rollout never finished: {:?}", rollouts.get(&key).unwrap()));
+        assert_eq!(rollout.status, RolloutStatus::Complete);
+        assert_eq!(rollout.tag, tag);
+        assert!(rollout.finished_at.is_some());
+        assert!(
+            reporter
+                .events
+                .lock()
+                .unwrap()
+                .contains(&(EventSeverity::Normal, "RolloutComplete".to_string()))
+        );
+
+        // A cluster still running the old image times out.
+        *reporter.live.lock().unwrap() = Some(deployment.clone());
+        let processor =
+            processor.with_rollouts(rollouts.clone(), Duration::ZERO, Duration::from_millis(10));
+        let rollout = processor.verify_rollout(&entry, &tag, None, &None).await;
+        assert_eq!(rollout.status, RolloutStatus::TimedOut);
+        assert!(
+            rollout.message.contains("waiting for test-container"),
+            "{}",
+            rollout.message
+        );
+        assert_eq!(rollouts.get(&key).unwrap(), Some(rollout));
+        assert!(
+            reporter
+                .events
+                .lock()
+                .unwrap()
+                .contains(&(EventSeverity::Warning, "RolloutFailed".to_string()))
+        );
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_failed_rollouts_are_reverted() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let annotations = deployment.metadata.annotations.as_mut().unwrap();
+        annotations.insert(
+            "gitops.operator.verify_rollout".to_string(),
+            "true".to_string(),
+        );
+        annotations.insert(
+            "gitops.operator.auto_revert".to_string(),
+            "true".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let reporter = Arc::new(RecordingClusterReporter {
+            live: Mutex::new(Some(deployment.clone())),
+            ..Default::default()
+        });
+        let rollouts = Arc::new(Rollouts::new());
+        let pauses = Arc::new(Pauses::new());
+        let processor = create_mock_processor(ssh_key)
+            .with_cluster_reporter(reporter.clone())
+            .with_pauses(pauses.clone())
+            .with_rollouts(
+                rollouts.clone(),
+                Duration::from_secs(5),
+                Duration::from_millis(10),
+            );
+
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Patched, "{}", result.message);
+        let previous_tag = result.from_sha.expect("patched from an unknown tag");
+        let tag = result.to_sha.expect("patched without a tag");
+
+        // The new pods never become ready.
+        let mut live = deployment.clone();
+        live.spec
+            .as_mut()
+            .unwrap()
+            .template
+            .spec
+            .as_mut()
+            .unwrap()
+            .containers[0]
+            .image = Some(format!("test-app:{}", tag));
+        live.status = Some(k8s_openapi::api::apps::v1::DeploymentStatus {
+            replicas: Some(2),
+            updated_replicas: Some(1),
+            conditions: Some(vec![k8s_openapi::api::apps::v1::DeploymentCondition {
+                type_: "Progressing".to_string(),
+                status: "False".to_string(),
+                reason: Some("ProgressDeadlineExceeded".to_string()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        });
+        *reporter.live.lock().unwrap() = Some(live);
+
+        let key = entry.key();
+        let mut rollout = None;
+        for _ in 0..500 {
+            rollout = rollouts.get(&key).unwrap().filter(|r| r.reverted);
+            if rollout.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let rollout = rollout
+            .unwrap_or_else(|| panic!("rollout never reverted: {:?}", rollouts.get(&key).unwrap()));
+        assert_eq!(rollout.status, RolloutStatus::Failed);
+        assert_eq!(rollout.previous_tag.as_deref(), Some(previous_tag.as_str()));
+
+        // The manifest is back on the previous tag, and stays there.
+        let releases = processor.releases(&entry, 1).await.unwrap();
+        assert_eq!(releases[0].tag, previous_tag);
+        assert!(pauses.get(&key).is_some());
+        let events = reporter.events.lock().unwrap().clone();
+        for reason in ["RolloutFailed", "RolledBack", "Paused"] {
+            assert!(
+                events.contains(&(EventSeverity::Warning, reason.to_string()))
+                    || events.contains(&(EventSeverity::Normal, reason.to_string())),
+                "no {} event in {:?}",
+                reason,
+                events
+            );
+        }
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_digest_drift_after_a_tag_is_pushed_again() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        // The cluster runs the manifest's tag, but pulled it before the tag
+        // was pushed again.
+        let tag = "cdea6a753ce3867ab4938088f538338d1e025d7d";
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment
+            .spec
+            .as_mut()
+            .unwrap()
+            .template
+            .spec
+            .as_mut()
+            .unwrap()
+            .containers[0]
+            .image = Some(format!("test-app:{}", tag));
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let stale = format!("sha256:{}", "b".repeat(64));
+        let reporter = Arc::new(RecordingClusterReporter {
+            running: Mutex::new(vec![
+                RunningImage {
+                    pod: "test-app-5d8f9-abcde".to_string(),
+                    container: "test-container".to_string(),
+                    image: format!("test-app:{}", tag),
+                    image_id: format!("docker.io/library/test-app@{}", stale),
+                },
+                RunningImage {
+                    pod: "test-app-5d8f9-fghij".to_string(),
+                    container: "test-container".to_string(),
+                    image: format!("test-app:{}", tag),
+                    image_id: format!("docker.io/library/test-app@{}", REGISTRY_DIGEST),
+                },
+            ]),
+            ..Default::default()
+        });
+        let board = Arc::new(DriftBoard::new(Duration::ZERO));
+        let processor = create_mock_processor(ssh_key)
+            .with_cluster_reporter(reporter.clone())
+            .with_drift(board.clone(), false)
+            .with_digest_verification(true);
+        entry.process_deployment_with(&processor).await;
+
+        let drifted = board.list();
+        assert_eq!(drifted.len(), 1);
+        assert!(drifted[0].containers.is_empty());
+        let digests = &drifted[0].digests;
+        assert_eq!(digests.len(), 1);
+        assert_eq!(digests[0].pods, vec!["test-app-5d8f9-abcde"]);
+        assert_eq!(digests[0].running_digest, stale);
+        assert_eq!(digests[0].manifest_digest, REGISTRY_DIGEST);
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_missing_ssh_key_records_warning_event() {
+        let entry = Entry::new(&create_test_deployment()).expect("Failed to create entry");
+
+        let reporter = Arc::new(RecordingClusterReporter::default());
+        let processor = DeploymentProcessor::new(
+            Arc::new(FailingSecretProvider),
+            Arc::new(MockImageCheckerFactory),
+            Arc::new(MockNotificationSender),
+        )
+        .with_cluster_reporter(reporter.clone());
+
+        let result = entry.process_deployment_with(&processor).await;
+
+        assert_eq!(result.status, Status::Failure);
+        assert_eq!(
+            *reporter.events.lock().unwrap(),
+            vec![(EventSeverity::Warning, "SshKeyUnavailable".to_string())]
+        );
+        assert!(reporter.annotations.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_missing_https_token_records_warning_event() {
+        let mut deployment = create_test_deployment();
+        let annotations = deployment.metadata.annotations.as_mut().unwrap();
+        annotations.remove("gitops.operator.ssh_key_name");
+        annotations.remove("gitops.operator.ssh_key_namespace");
+        annotations.insert(
+            "gitops.operator.app_repository".to_string(),
+            "https://github.com/org/app.git".to_string(),
+        );
+        annotations.insert(
+            "gitops.operator.manifest_repository".to_string(),
+            "https://github.com/org/manifests.git".to_string(),
+        );
+        annotations.insert(
+            "gitops.operator.https_token_secret_name".to_string(),
+            "git-token".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("HTTPS repositories need no SSH key");
+
+        let reporter = Arc::new(RecordingClusterReporter::default());
+        let processor = DeploymentProcessor::new(
+            Arc::new(FailingSecretProvider),
+            Arc::new(MockImageCheckerFactory),
+            Arc::new(MockNotificationSender),
+        )
+        .with_cluster_reporter(reporter.clone());
+
+        let result = entry.process_deployment_with(&processor).await;
+
+        assert_eq!(result.status, Status::Failure);
+        assert_eq!(result.error, Some(ErrorKind::Auth));
+        assert!(result.message.contains("Failed to get HTTPS token"));
+        assert_eq!(
+            *reporter.events.lock().unwrap(),
+            vec![(EventSeverity::Warning, "HttpsTokenUnavailable".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_manifest_ssh_key_records_warning_event() {
+        let mut deployment = create_test_deployment();
+        let annotations = deployment.metadata.annotations.as_mut().unwrap();
+        annotations.remove("gitops.operator.ssh_key_name");
+        annotations.remove("gitops.operator.ssh_key_namespace");
+        annotations.insert(
+            "gitops.operator.app_repository".to_string(),
+            "https://github.com/org/app.git".to_string(),
+        );
+        annotations.insert(
+            "gitops.operator.manifest_repository".to_string(),
+            "git@github.com:org/manifests.git".to_string(),
+        );
+        annotations.insert(
+            "gitops.operator.manifest_ssh_key_name".to_string(),
+            "manifests-deploy-key".to_string(),
+        );
+        let entry = Entry::new(&deployment).unwrap();
+
+        let reporter = Arc::new(RecordingClusterReporter::default());
+        let processor = DeploymentProcessor::new(
+            Arc::new(FailingSecretProvider),
+            Arc::new(MockImageCheckerFactory),
+            Arc::new(MockNotificationSender),
+        )
+        .with_cluster_reporter(reporter.clone());
+
+        let result = entry.process_deployment_with(&processor).await;
+
+        assert_eq!(result.status, Status::Failure);
+        assert_eq!(result.error, Some(ErrorKind::Auth));
+        assert!(result.message.contains("Failed to get manifest SSH key"));
+        assert!(result.message.contains("manifests-deploy-key"));
+        assert_eq!(
+            *reporter.events.lock().unwrap(),
+            vec![(EventSeverity::Warning, "SshKeyUnavailable".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_known_hosts_records_warning_event() {
+        let entry = Entry::new(&create_test_deployment()).unwrap();
+
+        let reporter = Arc::new(RecordingClusterReporter::default());
+        let processor = create_mock_processor("unused")
+            .with_cluster_reporter(reporter.clone())
+            .with_known_hosts(Some("/nonexistent/known_hosts".to_string()), true);
+
+        let result = entry.process_deployment_with(&processor).await;
+
+        assert_eq!(result.status, Status::Failure);
+        assert_eq!(result.error, Some(ErrorKind::Auth));
+        assert!(
+            result
+                .message
+                .contains("Failed to read known_hosts from /nonexistent/known_hosts")
+        );
+        assert_eq!(
+            *reporter.events.lock().unwrap(),
+            vec![(EventSeverity::Warning, "KnownHostsUnavailable".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_uninstalled_github_app_records_warning_event() {
+        let mock_server = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::path(
+            "/repos/org/manifests/installation",
+        ))
+        .respond_with(wiremock::ResponseTemplate::new(404))
+        .mount(&mock_server)
+        .await;
+
+        let mut deployment = create_test_deployment();
+        let annotations = deployment.metadata.annotations.as_mut().unwrap();
+        annotations.remove("gitops.operator.ssh_key_name");
+        annotations.remove("gitops.operator.ssh_key_namespace");
+        annotations.insert(
+            "gitops.operator.app_repository".to_string(),
+            "https://github.com/org/app.git".to_string(),
+        );
+        annotations.insert(
+            "gitops.operator.manifest_repository".to_string(),
+            "https://github.com/org/manifests.git".to_string(),
+        );
+        annotations.insert(
+            "gitops.operator.github_app_secret_name".to_string(),
+            "github-app".to_string(),
+        );
+        let entry = Entry::new(&deployment).unwrap();
+
+        let reporter = Arc::new(RecordingClusterReporter::default());
+        let processor = create_mock_processor("unused")
+            .with_cluster_reporter(reporter.clone())
+            .with_github_app_tokens(Arc::new(GitHubAppTokens::with_api_base(mock_server.uri())));
+
+        let result = entry.process_deployment_with(&processor).await;
+
+        assert_eq!(result.status, Status::Failure);
+        assert!(result.message.contains("not installed on org/manifests"));
+        assert_eq!(
+            *reporter.events.lock().unwrap(),
+            vec![(
+                EventSeverity::Warning,
+                "GitHubAppTokenUnavailable".to_string()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_manifests_are_not_pushed() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        // Break the base manifest in the repository, in a way that doesn't
+        // stop the operator from patching it.
+        let seed = TempDir::new().unwrap();
+        let seed_path = seed.path().join("manifests");
+        let seed_path = seed_path.to_str().unwrap();
+        clone_repo(
+            &repos.get_manifest_url(),
+            seed_path,
+            "master",
+            &GitCredentials::ssh(ssh_key),
+        );
+        let app_yaml = format!("{}/deployments/app.yaml", seed_path);
+        let content = fs::read_to_string(&app_yaml).unwrap();
+        fs::write(
+            &app_yaml,
+            content.replace(
+                "      containers:",
+                "      initContainers:\n      - name: migrate\n        image: ''\n      containers:",
+            ),
+        )
+        .unwrap();
+        commit_changes(
+            seed_path,
+            "master",
+            "Add migrations",
+            &GitCredentials::ssh(ssh_key),
+            None,
+        )
+        .unwrap();
+        let broken_head = get_latest_commit(
+            Path::new(seed_path),
+            "master",
+            "long",
+            &GitCredentials::ssh(ssh_key),
+        )
+        .expect("Failed to read manifest head");
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.annotations.as_mut().unwrap().insert(
+            "gitops.operator.validate_manifests".to_string(),
+            "true".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let reporter = Arc::new(RecordingClusterReporter::default());
+        let processor = create_mock_processor(ssh_key).with_cluster_reporter(reporter.clone());
+        let result = entry.process_deployment_with(&processor).await;
+
+        assert_eq!(result.status, Status::Failure, "{}", result.message);
+        assert!(
+            result.message.contains(
+                "deployments/app.yaml: document 1 (Deployment test-app): spec.template.spec.initContainers[0].image: must not be empty"
+            ),
+            "{}",
+            result.message
+        );
+        assert!(
+            !result.message.contains("overlays/prod.yaml"),
+            "{}",
+            result.message
+        );
+        assert_eq!(
+            *reporter.events.lock().unwrap(),
+            vec![(EventSeverity::Warning, "InvalidManifest".to_string())]
+        );
+
+        // Nothing was pushed on top of the broken commit.
+        fs::remove_dir_all(&manifest_link_path).ok();
+        clone_repo(
+            &repos.get_manifest_url(),
+            &manifest_link_path,
+            "master",
+            &GitCredentials::ssh(ssh_key),
+        );
+        let head = get_latest_commit(
+            Path::new(&manifest_link_path),
+            "master",
+            "long",
+            &GitCredentials::ssh(ssh_key),
+        )
+        .expect("Failed to read manifest head");
+        assert_eq!(head, broken_head);
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_egress_policy_refuses_unlisted_repositories() {
+        let deployment = create_test_deployment_with_repos(
+            "git@github.com:kainlite/tr.git",
+            "git@attacker.example.com:loot/manifests.git",
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        // Refused before the SSH key is even read.
+        let reporter = Arc::new(RecordingClusterReporter::default());
+        let processor = DeploymentProcessor::new(
+            Arc::new(FailingSecretProvider),
+            Arc::new(MockImageCheckerFactory),
+            Arc::new(MockNotificationSender),
+        )
+        .with_cluster_reporter(reporter.clone())
+        .with_egress(EgressPolicy::parse("git: [github.com]").unwrap());
+        let result = entry.process_deployment_with(&processor).await;
+
+        assert_eq!(result.status, Status::Failure);
+        assert_eq!(result.error, Some(ErrorKind::Policy));
+        assert!(
+            result.message.contains("attacker.example.com"),
+            "{}",
+            result.message
+        );
+        assert_eq!(
+            *reporter.events.lock().unwrap(),
+            vec![(EventSeverity::Warning, "EgressDenied".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_results_carry_the_observed_resource_version() {
+        let mut cached = create_test_deployment();
+        cached.metadata.resource_version = Some("100".to_string());
+        cached.metadata.generation = Some(4);
+        let entry = Entry::new(&cached).expect("Failed to create entry");
+
+        let processor = DeploymentProcessor::new(
+            Arc::new(FailingSecretProvider),
+            Arc::new(MockImageCheckerFactory),
+            Arc::new(MockNotificationSender),
+        );
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.resource_version.as_deref(), Some("100"));
+        assert_eq!(result.generation, Some(4));
+
+        // With refetching on, the live object's version is the one acted on.
+        let mut live = cached.clone();
+        live.metadata.resource_version = Some("105".to_string());
+        live.metadata.generation = Some(5);
+        let reporter = Arc::new(RecordingClusterReporter::default());
+        *reporter.live.lock().unwrap() = Some(live);
+        let processor = DeploymentProcessor::new(
+            Arc::new(FailingSecretProvider),
+            Arc::new(MockImageCheckerFactory),
+            Arc::new(MockNotificationSender),
+        )
+        .with_cluster_reporter(reporter.clone())
+        .with_refetch(true);
+
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.resource_version.as_deref(), Some("105"));
+        assert_eq!(result.generation, Some(5));
+
+        // A Deployment deleted since it was cached is left alone.
+        *reporter.live.lock().unwrap() = None;
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Skipped);
+        assert!(
+            result.message.contains("no longer exists"),
+            "{}",
+            result.message
+        );
+    }
+
+    #[tokio::test]
+    async fn test_entry_creation() {
+        let deployment = create_test_deployment();
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        assert_eq!(entry.name, "test-app");
+        assert_eq!(entry.namespace, "default");
+        assert!(entry.config.enabled);
+        assert_eq!(entry.config.image_name, "test-app");
+        assert_eq!(entry.config.deployment_path, "deployments/app.yaml");
+    }
+
+    #[tokio::test]
+    async fn test_pull_request_strategy_pushes_to_a_branch() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let annotations = deployment.metadata.annotations.as_mut().unwrap();
+        annotations.insert(
+            "gitops.operator.strategy".to_string(),
+            "pull_request".to_string(),
+        );
+        annotations.insert(
+            "gitops.operator.github_token_secret_name".to_string(),
+            "github-token".to_string(),
+        );
+        annotations.insert(
+            "gitops.operator.pr_title".to_string(),
+            "Deploy {app} {new_sha}".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let bare = git2::Repository::open(repos.manifest_bare.path()).unwrap();
+        let master = bare.refname_to_id("refs/heads/master").unwrap();
+
+        let opener = Arc::new(RecordingPullRequestOpener::default());
+        let processor = create_mock_processor(ssh_key).with_pull_request_opener(opener.clone());
+
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Proposed, "{}", result.message);
+        assert!(result.message.contains("pull request #1"));
+        let tag = result.to_sha.expect("proposed without a tag");
+
+        // The observed branch is untouched; the change waits on its own branch.
+        assert_eq!(bare.refname_to_id("refs/heads/master").unwrap(), master);
+        let head = format!("gitops-operator/{}/{}-{}", entry.namespace, entry.name, tag);
+        let proposed = bare
+            .find_reference(&format!("refs/heads/{}", head))
+            .expect("no pull request branch")
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!(proposed.parent_id(0).unwrap(), master);
+
+        let opened = opener.opened.lock().unwrap().clone();
+        assert_eq!(opened.len(), 1);
+        let (change, token) = &opened[0];
+        assert_eq!(token, "ghp_test_token");
+        assert_eq!(change.head, head);
+        assert_eq!(change.base, "master");
+        assert_eq!(change.title, format!("Deploy {} {}", entry.name, tag));
+
+        // The local checkout keeps following master.
+        let local = git2::Repository::open(&manifest_link_path).unwrap();
+        assert_eq!(local.head().unwrap().peel_to_commit().unwrap().id(), master);
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_pull_request_strategy_proposes_rollbacks() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        let annotations = deployment.metadata.annotations.as_mut().unwrap();
+        annotations.insert(
+            "gitops.operator.strategy".to_string(),
+            "pull_request".to_string(),
+        );
+        annotations.insert(
+            "gitops.operator.github_token_secret_name".to_string(),
+            "github-token".to_string(),
+        );
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let bare = git2::Repository::open(repos.manifest_bare.path()).unwrap();
+        let master = bare.refname_to_id("refs/heads/master").unwrap();
+
+        let opener = Arc::new(RecordingPullRequestOpener::default());
+        let processor = create_mock_processor(ssh_key).with_pull_request_opener(opener.clone());
+
+        let target = "0123456789abcdef0123456789abcdef01234567";
+        let result = processor.rollback(&entry, target).await;
+        assert_eq!(result.action, Action::Proposed, "{}", result.message);
+        assert!(result.message.contains("pull request #1"));
+
+        // Nothing went to the observed branch; the rollback waits for review.
+        assert_eq!(bare.refname_to_id("refs/heads/master").unwrap(), master);
+        let head = format!(
+            "gitops-operator/{}/{}-{}",
+            entry.namespace, entry.name, target
+        );
+        let proposed = bare
+            .find_reference(&format!("refs/heads/{}", head))
+            .expect("no pull request branch")
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!(proposed.parent_id(0).unwrap(), master);
+        let opened = opener.opened.lock().unwrap().clone();
+        assert_eq!(opened.len(), 1);
+        assert_eq!(opened[0].0.head, head);
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_merge_requests_use_the_forge_token() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.name = Some("test-app-gitlab".to_string());
+        let annotations = deployment.metadata.annotations.as_mut().unwrap();
+        for (key, value) in [
+            ("strategy", "pull_request"),
+            ("forge", "gitlab"),
+            ("forge_token_secret_name", "gitlab-token"),
+            ("pr_labels", "deploy, automated"),
+            ("pr_assignees", "alice"),
+        ] {
+            annotations.insert(format!("gitops.operator.{}", key), value.to_string());
+        }
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let opener = Arc::new(RecordingPullRequestOpener::default());
+        let processor = create_mock_processor(ssh_key).with_pull_request_opener(opener.clone());
+
+        let result = entry.process_deployment_with(&processor).await;
+        assert_eq!(result.action, Action::Proposed, "{}", result.message);
+        assert!(result.message.contains("merge request !1"));
+
+        let opened = opener.opened.lock().unwrap().clone();
+        let (change, token) = &opened[0];
+        assert_eq!(token, "glpat_test_token");
+        assert_eq!(change.forge, Forge::GitLab);
+        assert_eq!(change.labels, vec!["deploy", "automated"]);
+        assert_eq!(change.assignees, vec!["alice"]);
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_embedded_operator_reconciles_once() {
+        let repos = TestRepos::new();
+        let ssh_key = "dummy-ssh-key-for-file-protocol";
+
+        let mut deployment =
+            create_test_deployment_with_repos(&repos.get_app_url(), &repos.get_manifest_url());
+        deployment.metadata.name = Some("test-app-embedded".to_string());
+        let mut unannotated = deployment.clone();
+        unannotated.metadata.name = Some("unannotated".to_string());
+        unannotated.metadata.annotations = None;
+
+        let entry = Entry::new(&deployment).expect("Failed to create entry");
+        let app_link_path = entry.app_repo_path();
+        let manifest_link_path = entry.manifest_repo_path();
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
+
+        let reporter = Arc::new(RecordingClusterReporter::default());
+        let git = Arc::new(RecordingGitProvider::default());
+        let operator = Operator::builder()
+            .secret_provider(Arc::new(MockSecretProvider::new(ssh_key)))
+            .git_provider(git.clone())
+            .image_checker_factory(Arc::new(MockImageCheckerFactory))
+            .notification_sender(Arc::new(MockNotificationSender))
+            .cluster_reporter(reporter.clone())
+            .maintenance_windows(Arc::new(StaticMaintenanceWindows(Mutex::new(vec![]))))
+            .policies(Arc::new(StaticPolicies(Mutex::new(vec![]))))
+            .build();
+
+        let results = operator.reconcile_once(&[deployment, unannotated]).await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].deployment, "test-app-embedded");
+        assert_eq!(results[0].action, Action::Patched, "{}", results[0].message);
+        assert!(
+            reporter
+                .events
+                .lock()
+                .unwrap()
+                .contains(&(EventSeverity::Normal, "ManifestPatched".to_string()))
+        );
+        assert_eq!(*git.pushes.lock().unwrap(), ["master"]);
+
+        fs::remove_dir_all(&app_link_path).ok();
+        fs::remove_dir_all(&manifest_link_path).ok();
     }
 }
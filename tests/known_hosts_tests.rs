@@ -0,0 +1,156 @@
+#[cfg(test)]
+mod tests {
+    use base64::{Engine as _, engine::general_purpose::STANDARD};
+    use gitops_operator::known_hosts::*;
+    use openssl::hash::{MessageDigest, hash};
+    use openssl::pkey::PKey;
+    use openssl::sign::Signer;
+
+    const GITHUB_KEY: &[u8] = b"github ed25519 host key";
+    const GITLAB_KEY: &[u8] = b"gitlab ed25519 host key";
+
+    fn line(hosts: &str, key: &[u8]) -> String {
+        format!("{} ssh-ed25519 {}", hosts, STANDARD.encode(key))
+    }
+
+    fn hashed(host: &str) -> String {
+        let salt = b"0123456789abcdefghij";
+        let key = PKey::hmac(salt).unwrap();
+        let mut signer = Signer::new(MessageDigest::sha1(), &key).unwrap();
+        signer.update(host.as_bytes()).unwrap();
+        format!(
+            "|1|{}|{}",
+            STANDARD.encode(salt),
+            STANDARD.encode(signer.sign_to_vec().unwrap())
+        )
+    }
+
+    #[test]
+    fn test_plain_host_names() {
+        let known_hosts = KnownHosts::parse(&format!(
+            "# comment\n\n{}\n{}\n",
+            line("github.com,140.82.121.4", GITHUB_KEY),
+            line("gitlab.com", GITLAB_KEY)
+        ));
+
+        assert_eq!(
+            known_hosts.check("github.com", "ssh-ed25519", GITHUB_KEY),
+            HostKeyStatus::Match
+        );
+        assert_eq!(
+            known_hosts.check("140.82.121.4", "ssh-ed25519", GITHUB_KEY),
+            HostKeyStatus::Match
+        );
+        assert_eq!(
+            known_hosts.check("GitHub.com", "ssh-ed25519", GITHUB_KEY),
+            HostKeyStatus::Match
+        );
+        assert_eq!(
+            known_hosts.check("github.com", "ssh-ed25519", GITLAB_KEY),
+            HostKeyStatus::Mismatch
+        );
+        assert_eq!(
+            known_hosts.check("github.com", "ssh-rsa", GITHUB_KEY),
+            HostKeyStatus::Mismatch
+        );
+        assert_eq!(
+            known_hosts.check("bitbucket.org", "ssh-ed25519", GITHUB_KEY),
+            HostKeyStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn test_hashed_host_names() {
+        let known_hosts = KnownHosts::parse(&line(&hashed("github.com"), GITHUB_KEY));
+
+        assert_eq!(
+            known_hosts.check("github.com", "ssh-ed25519", GITHUB_KEY),
+            HostKeyStatus::Match
+        );
+        assert_eq!(
+            known_hosts.check("gitlab.com", "ssh-ed25519", GITHUB_KEY),
+            HostKeyStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn test_host_names_with_ports() {
+        let known_hosts = KnownHosts::parse(&line("[git.example.com]:2222", GITHUB_KEY));
+
+        assert_eq!(
+            known_hosts.check("git.example.com", "ssh-ed25519", GITHUB_KEY),
+            HostKeyStatus::Match
+        );
+    }
+
+    #[test]
+    fn test_revoked_keys_are_refused_for_every_host() {
+        let known_hosts = KnownHosts::parse(&format!(
+            "@revoked * ssh-ed25519 {}\n{}\n",
+            STANDARD.encode(GITHUB_KEY),
+            line("github.com", GITHUB_KEY)
+        ));
+
+        assert_eq!(
+            known_hosts.check("github.com", "ssh-ed25519", GITHUB_KEY),
+            HostKeyStatus::Revoked
+        );
+    }
+
+    #[test]
+    fn test_wildcards_and_cert_authorities_are_ignored() {
+        let known_hosts = KnownHosts::parse(&format!(
+            "{}\n@cert-authority github.com ssh-ed25519 {}\nnot a valid line\n",
+            line("*.example.com", GITHUB_KEY),
+            STANDARD.encode(GITLAB_KEY)
+        ));
+
+        assert_eq!(
+            known_hosts.check("git.example.com", "ssh-ed25519", GITHUB_KEY),
+            HostKeyStatus::Unknown
+        );
+        assert_eq!(
+            known_hosts.check("github.com", "ssh-ed25519", GITLAB_KEY),
+            HostKeyStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn test_rendered_for_openssh() {
+        let content = format!(
+            "{}\n@revoked * ssh-ed25519 {}\n{}\n",
+            line(&format!("github.com,{}", hashed("gitlab.com")), GITHUB_KEY),
+            STANDARD.encode(GITLAB_KEY),
+            line("*.example.com", GITHUB_KEY)
+        );
+        let known_hosts = KnownHosts::parse(&content);
+
+        // Unsupported patterns are dropped, and with them lines left without any.
+        let rendered = known_hosts.to_openssh();
+        assert_eq!(
+            rendered,
+            format!(
+                "{}\n@revoked * ssh-ed25519 {}\n",
+                line(&format!("github.com,{}", hashed("gitlab.com")), GITHUB_KEY),
+                STANDARD.encode(GITLAB_KEY)
+            )
+        );
+        assert_eq!(KnownHosts::parse(&rendered).to_openssh(), rendered);
+    }
+
+    #[test]
+    fn test_check_by_sha256_digest() {
+        let known_hosts = KnownHosts::parse(&line("github.com", GITHUB_KEY));
+        let digest = hash(MessageDigest::sha256(), GITHUB_KEY).unwrap();
+        let other = hash(MessageDigest::sha256(), GITLAB_KEY).unwrap();
+
+        assert_eq!(
+            known_hosts.check_sha256("github.com", &digest),
+            HostKeyStatus::Match
+        );
+        assert_eq!(
+            known_hosts.check_sha256("github.com", &other),
+            HostKeyStatus::Mismatch
+        );
+    }
+}
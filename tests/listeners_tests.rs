@@ -0,0 +1,50 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::listeners::{bind_all, parse_listen_addrs, with_port};
+    use std::net::{SocketAddr, TcpStream};
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_listen_addrs() {
+        assert_eq!(
+            parse_listen_addrs("0.0.0.0:8000, [::]:8000 0.0.0.0:8000").unwrap(),
+            vec![addr("0.0.0.0:8000"), addr("[::]:8000")]
+        );
+        assert!(parse_listen_addrs(" , ").is_err());
+        assert!(parse_listen_addrs("localhost:8000").is_err());
+    }
+
+    #[test]
+    fn test_with_port_keeps_hosts() {
+        let addrs = vec![
+            addr("0.0.0.0:8000"),
+            addr("[::]:8000"),
+            addr("0.0.0.0:9000"),
+        ];
+        assert_eq!(
+            with_port(&addrs, 8443),
+            vec![addr("0.0.0.0:8443"), addr("[::]:8443")]
+        );
+    }
+
+    #[test]
+    fn test_bind_all_listens_on_both_families_on_one_port() {
+        // Find a free port, then bind it on both families at once.
+        let port = bind_all(&[addr("0.0.0.0:0")]).unwrap()[0]
+            .local_addr()
+            .unwrap()
+            .port();
+        let addrs = vec![
+            SocketAddr::new("0.0.0.0".parse().unwrap(), port),
+            SocketAddr::new("::".parse().unwrap(), port),
+        ];
+
+        let listeners = bind_all(&addrs).unwrap();
+        assert_eq!(listeners.len(), 2);
+        TcpStream::connect(("127.0.0.1", port)).unwrap();
+        TcpStream::connect(("::1", port)).unwrap();
+    }
+}
@@ -0,0 +1,76 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::changes::change_cache;
+    use gitops_operator::locks::{EntryLocks, PathLocks, reset_in_progress};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_lock_is_exclusive_and_released_on_drop() {
+        let locks = EntryLocks::new();
+
+        let guard = locks.try_acquire("default/blog").expect("first acquire");
+        assert!(locks.try_acquire("default/blog").is_none());
+        assert!(locks.try_acquire("default/api").is_some());
+
+        drop(guard);
+        assert!(locks.try_acquire("default/blog").is_some());
+    }
+
+    #[test]
+    fn test_reap_releases_locks_past_deadline() {
+        let locks = EntryLocks::new();
+        let orphan = locks.try_acquire("default/blog").unwrap();
+
+        assert!(locks.reap(Duration::from_secs(3600)).is_empty());
+
+        let reaped = locks.reap(Duration::ZERO);
+        assert_eq!(reaped.len(), 1);
+        assert_eq!(reaped[0].key, "default/blog");
+        assert!(locks.held().is_empty());
+
+        // A new reconcile can take the key, and the orphaned guard finishing
+        // late must not release the new holder's lock.
+        let _fresh = locks.try_acquire("default/blog").unwrap();
+        drop(orphan);
+        assert_eq!(locks.held(), vec!["default/blog".to_string()]);
+    }
+
+    #[test]
+    fn test_reset_in_progress_makes_the_next_pass_do_the_full_work() {
+        let ttl = Duration::from_secs(3600);
+        change_cache().record("reaped/blog", "fingerprint".to_string());
+        assert!(change_cache().unchanged("reaped/blog", "fingerprint", ttl));
+
+        reset_in_progress("reaped/blog");
+        assert!(!change_cache().unchanged("reaped/blog", "fingerprint", ttl));
+    }
+
+    #[tokio::test]
+    async fn test_path_locks_wait_for_the_holder() {
+        let locks = Arc::new(PathLocks::new());
+        let held = locks
+            .lock(&["/tmp/app-blog-main/", "/tmp/manifest-blog-main/"])
+            .await;
+
+        // Same checkouts, spelled and ordered differently.
+        let waiting = {
+            let locks = locks.clone();
+            tokio::spawn(async move {
+                let _guards = locks
+                    .lock(&["/tmp/manifest-blog-main", "/tmp/app-blog-main"])
+                    .await;
+            })
+        };
+        // Other checkouts aren't held up.
+        assert_eq!(locks.lock(&["/tmp/app-api-main/"]).await.len(), 1);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        drop(held);
+        tokio::time::timeout(Duration::from_secs(5), waiting)
+            .await
+            .expect("lock released")
+            .unwrap();
+    }
+}
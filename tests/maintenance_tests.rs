@@ -0,0 +1,235 @@
+#[cfg(test)]
+mod tests {
+    use git2::{Repository, Signature};
+    use gitops_operator::git::{GitCredentials, clone_or_update_repo, mirror_path};
+    use gitops_operator::locks::path_locks;
+    use gitops_operator::maintenance::{
+        GcSettings, ObjectStats, cache_size, dir_size, evict_lru, is_idle, object_stats,
+        remove_checkout, remove_orphaned_checkouts, repack,
+    };
+    use std::collections::HashSet;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn commit_file(repo: &Repository, content: &str, message: &str) -> git2::Oid {
+        let workdir = repo.workdir().unwrap();
+        std::fs::write(workdir.join("deployment.yaml"), content).unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("deployment.yaml")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        let parent = repo.head().ok().map(|h| h.peel_to_commit().unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_repack_packs_loose_objects_and_keeps_history() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        for i in 0..3 {
+            commit_file(
+                &repo,
+                &format!("image: org/app:{}\n", i),
+                &format!("v{}", i),
+            );
+        }
+        let head = repo.head().unwrap().target().unwrap();
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        let tag = repo
+            .tag(
+                "v2",
+                &repo.find_object(head, None).unwrap(),
+                &signature,
+                "v2",
+                false,
+            )
+            .unwrap();
+
+        let stats = object_stats(dir.path()).unwrap();
+        assert_eq!(stats.packs, 0);
+        assert!(stats.loose_objects >= 10);
+
+        let (before, after) = repack(dir.path()).unwrap();
+        assert_eq!(before, stats);
+        assert_eq!(after.loose_objects, 0);
+        assert_eq!(after.packs, 1);
+
+        // A second repack replaces the pack instead of adding one.
+        let (_, after) = repack(dir.path()).unwrap();
+        assert_eq!(after.packs, 1);
+
+        let repo = Repository::open(dir.path()).unwrap();
+        let mut walk = repo.revwalk().unwrap();
+        walk.push_head().unwrap();
+        assert_eq!(walk.count(), 3);
+        assert!(repo.find_tag(tag).is_ok());
+        let blob = repo
+            .head()
+            .unwrap()
+            .peel_to_tree()
+            .unwrap()
+            .get_name("deployment.yaml")
+            .unwrap()
+            .to_object(&repo)
+            .unwrap();
+        assert_eq!(blob.as_blob().unwrap().content(), b"image: org/app:2\n");
+    }
+
+    #[test]
+    fn test_repacking_a_worktree_keeps_the_other_checkouts() {
+        let source = TempDir::new().unwrap();
+        let repo = Repository::init(source.path()).unwrap();
+        commit_file(&repo, "image: org/app:0\n", "v0");
+        let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+        let url = format!("file://{}", source.path().display());
+        let credentials =
+            GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==");
+
+        let dir = TempDir::new().unwrap();
+        let first =
+            clone_or_update_repo(&url, dir.path().join("first"), &branch, &credentials).unwrap();
+        clone_or_update_repo(&url, dir.path().join("second"), &branch, &credentials).unwrap();
+        // Not pushed anywhere: only the first checkout's HEAD reaches it.
+        let unpushed = commit_file(&first, "image: org/app:1\n", "v1");
+
+        let (_, after) = repack(&dir.path().join("second")).unwrap();
+        assert_eq!(after.loose_objects, 0);
+        assert_eq!(object_stats(&dir.path().join("first")).unwrap(), after);
+        let first = Repository::open(dir.path().join("first")).unwrap();
+        assert_eq!(first.head().unwrap().target(), Some(unpushed));
+        assert!(first.find_commit(unpushed).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_orphaned_and_idle_checkouts_are_removed() {
+        let source = TempDir::new().unwrap();
+        let repo = Repository::init(source.path()).unwrap();
+        commit_file(&repo, "image: org/app:0\n", "v0");
+        let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+        let url = format!("file://{}", source.path().display());
+        let credentials =
+            GitCredentials::ssh("aHR0cHM6Ly93d3cueW91dHViZS5jb20vd2F0Y2g/dj1kUXc0dzlXZ1hjUQ==");
+
+        let cache = TempDir::new().unwrap();
+        let live = cache.path().join("app-live-main");
+        let orphan = cache.path().join("manifest-deleted-main");
+        clone_or_update_repo(&url, live.clone(), &branch, &credentials).unwrap();
+        clone_or_update_repo(&url, orphan.clone(), &branch, &credentials).unwrap();
+        // Not a checkout, whatever its name.
+        std::fs::create_dir(cache.path().join("app-unrelated")).unwrap();
+
+        let removed =
+            remove_orphaned_checkouts(cache.path(), || HashSet::from([live.clone()])).await;
+        assert_eq!(removed, vec![orphan.clone()]);
+        assert!(!orphan.exists());
+        assert!(live.exists());
+        assert!(cache.path().join("app-unrelated").exists());
+        assert!(mirror_path(&url).exists());
+
+        assert!(!is_idle(&live, None));
+        assert!(!is_idle(&live, Some(Duration::from_secs(3600))));
+
+        // A checkout that became live while waiting for its lock stays.
+        let claimed = cache.path().join("app-claimed-main");
+        clone_or_update_repo(&url, claimed.clone(), &branch, &credentials).unwrap();
+        let guard = path_locks().lock(&[&claimed.display().to_string()]).await;
+        let asked = AtomicUsize::new(0);
+        let removal = remove_orphaned_checkouts(cache.path(), || {
+            // Only the live checkout at first, the claimed one as well once
+            // its lock is taken.
+            let mut live = HashSet::from([live.clone()]);
+            if asked.fetch_add(1, Ordering::SeqCst) > 0 {
+                live.insert(claimed.clone());
+            }
+            live
+        });
+        let release = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(guard);
+        };
+        let (removed, ()) = tokio::join!(removal, release);
+        assert!(removed.is_empty());
+        assert!(claimed.exists());
+        remove_checkout(&claimed).unwrap();
+
+        // The mirror goes with its last checkout.
+        remove_checkout(&live).unwrap();
+        assert!(!live.exists());
+        assert!(!mirror_path(&url).exists());
+    }
+
+    #[test]
+    fn test_least_recently_used_checkouts_are_evicted_first() {
+        let cache = TempDir::new().unwrap();
+        let checkouts: Vec<_> = ["app-a-main", "manifest-b-main", "dependency-ns-c-0-main"]
+            .iter()
+            .map(|name| {
+                let path = cache.path().join(name);
+                let repo = Repository::init(&path).unwrap();
+                commit_file(&repo, &format!("image: org/{}:0\n", name), "v0");
+                std::thread::sleep(Duration::from_millis(20));
+                path
+            })
+            .collect();
+        // Other files in the cache directory (e.g. in /tmp) aren't counted.
+        std::fs::write(cache.path().join("unrelated"), vec![0u8; 1 << 20]).unwrap();
+
+        let size = cache_size(cache.path());
+        assert_eq!(
+            size,
+            checkouts.iter().map(|path| dir_size(path)).sum::<u64>()
+        );
+
+        let evicted = evict_lru(cache.path(), &checkouts, size - 1);
+        assert_eq!(evicted, vec![checkouts[0].clone()]);
+        assert!(cache_size(cache.path()) < size);
+
+        let evicted = evict_lru(cache.path(), &checkouts, 0);
+        assert_eq!(evicted, checkouts[1..].to_vec());
+        assert_eq!(cache_size(cache.path()), 0);
+        assert!(cache.path().join("unrelated").exists());
+    }
+
+    #[test]
+    fn test_needs_gc_thresholds() {
+        let settings = GcSettings {
+            interval: Duration::from_secs(3600),
+            max_packs: 5,
+            max_loose_objects: 100,
+            max_size_bytes: None,
+            max_idle: None,
+            max_cache_bytes: None,
+        };
+        let stats = ObjectStats {
+            size_bytes: 10 * 1024 * 1024,
+            packs: 5,
+            loose_objects: 100,
+        };
+        assert!(!settings.needs_gc(&stats));
+        assert!(settings.needs_gc(&ObjectStats { packs: 6, ..stats }));
+        assert!(settings.needs_gc(&ObjectStats {
+            loose_objects: 101,
+            ..stats
+        }));
+
+        let settings = GcSettings {
+            max_size_bytes: Some(1024 * 1024),
+            ..settings
+        };
+        assert!(settings.needs_gc(&stats));
+    }
+}
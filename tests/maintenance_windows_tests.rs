@@ -0,0 +1,138 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::maintenance_windows::{
+        FreezeWindow, MaintenanceWindow, active_freeze, blocking_window, parse_freeze_windows,
+        parse_resources,
+    };
+    use k8s_openapi::jiff::Timestamp;
+    use kube::core::GroupVersionKind;
+
+    fn at(time: &str) -> Timestamp {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn test_window_blocks_between_start_and_end() {
+        let window = MaintenanceWindow::from_fields(
+            "ConfigMap ops/node-upgrade".to_string(),
+            Some("2026-10-16T10:00:00Z"),
+            Some("2026-10-16T12:00:00Z"),
+            None,
+        )
+        .unwrap();
+
+        assert!(!window.blocks("default", at("2026-10-16T09:59:59Z")));
+        assert!(window.blocks("default", at("2026-10-16T10:00:00Z")));
+        assert!(window.blocks("payments", at("2026-10-16T11:30:00Z")));
+        assert!(!window.blocks("default", at("2026-10-16T12:00:00Z")));
+        assert_eq!(
+            window.describe(),
+            "ConfigMap ops/node-upgrade (until 2026-10-16T12:00:00Z)"
+        );
+    }
+
+    #[test]
+    fn test_window_without_bounds_is_scoped_by_namespace() {
+        let window = MaintenanceWindow::from_fields(
+            "MaintenanceWindow ops/db".to_string(),
+            None,
+            None,
+            Some("payments, billing"),
+        )
+        .unwrap();
+        let now = Timestamp::now();
+
+        assert!(window.blocks("billing", now));
+        assert!(!window.blocks("default", now));
+        assert_eq!(
+            blocking_window(std::slice::from_ref(&window), "payments", now),
+            Some(&window)
+        );
+        assert_eq!(blocking_window(&[window], "default", now), None);
+    }
+
+    #[test]
+    fn test_invalid_timestamps_are_rejected() {
+        let err = MaintenanceWindow::from_fields(
+            "ConfigMap ops/bad".to_string(),
+            Some("tomorrow"),
+            None,
+            None,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Invalid start in ConfigMap ops/bad")
+        );
+    }
+
+    #[test]
+    fn test_parse_resources() {
+        assert_eq!(
+            parse_resources("maintenance.example.com/v1/MaintenanceWindow, v1/ConfigMap").unwrap(),
+            vec![
+                GroupVersionKind::gvk("maintenance.example.com", "v1", "MaintenanceWindow"),
+                GroupVersionKind::gvk("", "v1", "ConfigMap"),
+            ]
+        );
+        assert!(parse_resources("MaintenanceWindow").is_err());
+    }
+
+    #[test]
+    fn test_interval_freeze_window() {
+        let window =
+            FreezeWindow::parse("2026-12-18T00:00:00Z/2027-01-04T00:00:00+00:00[UTC]").unwrap();
+
+        assert_eq!(window.active_until(at("2026-12-17T23:59:59Z")), None);
+        assert_eq!(
+            window.active_until(at("2026-12-25T12:00:00Z")),
+            Some(at("2027-01-04T00:00:00Z"))
+        );
+        assert_eq!(window.active_until(at("2027-01-04T00:00:00Z")), None);
+    }
+
+    #[test]
+    fn test_recurring_freeze_window() {
+        // Friday 18:00 UTC to Monday 08:00 UTC.
+        let window = FreezeWindow::parse("0 18 * * 5 for 2d14h").unwrap();
+
+        // 2026-10-16 is a Friday.
+        assert_eq!(window.active_until(at("2026-10-16T17:59:00Z")), None);
+        assert_eq!(
+            window.active_until(at("2026-10-16T18:00:00Z")),
+            Some(at("2026-10-19T08:00:00Z"))
+        );
+        assert_eq!(
+            window.active_until(at("2026-10-18T23:00:00Z")),
+            Some(at("2026-10-19T08:00:00Z"))
+        );
+        assert_eq!(window.active_until(at("2026-10-19T08:00:00Z")), None);
+        assert_eq!(window.active_until(at("2026-10-21T12:00:00Z")), None);
+    }
+
+    #[test]
+    fn test_parse_freeze_windows() {
+        let windows =
+            parse_freeze_windows("0 0 * * 6 for 48h; 2026-12-18T00:00:00Z/2027-01-04T00:00:00Z;")
+                .unwrap();
+        assert_eq!(windows.len(), 2);
+
+        let (window, end) = active_freeze(&windows, at("2026-12-21T10:00:00Z")).unwrap();
+        assert_eq!(window, &windows[1]);
+        assert_eq!(end, at("2027-01-04T00:00:00Z"));
+        assert!(active_freeze(&windows, at("2026-10-21T10:00:00Z")).is_none());
+        assert!(parse_freeze_windows("").unwrap().is_empty());
+
+        for invalid in [
+            "weekends",
+            "0 0 * * 6 for",
+            "0 0 * * 6 for 2w",
+            "0 0 * * 6 for 0h",
+            "0 0 * * for 2d",
+            "tomorrow/2027-01-04T00:00:00Z",
+            "2027-01-04T00:00:00Z/2026-12-18T00:00:00Z",
+        ] {
+            assert!(parse_freeze_windows(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
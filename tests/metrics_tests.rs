@@ -0,0 +1,45 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::metrics::{METRICS, grafana_dashboard, prometheus_rules};
+
+    #[test]
+    fn test_dashboard_has_a_panel_per_metric() {
+        let dashboard = grafana_dashboard();
+        let panels = dashboard["panels"].as_array().unwrap();
+
+        assert_eq!(panels.len(), METRICS.len());
+        for (panel, metric) in panels.iter().zip(METRICS) {
+            assert_eq!(panel["title"], metric.name);
+            assert!(
+                panel["targets"][0]["expr"]
+                    .as_str()
+                    .unwrap()
+                    .contains(metric.name)
+            );
+        }
+    }
+
+    #[test]
+    fn test_prometheus_rules_cover_metric_alerts() {
+        let rules: serde_yaml::Value = serde_yaml::from_str(&prometheus_rules()).unwrap();
+        let alerts = rules["groups"][0]["rules"].as_sequence().unwrap();
+
+        let expected: Vec<_> = METRICS.iter().filter_map(|m| m.alert).collect();
+        assert_eq!(alerts.len(), expected.len());
+        for alert in &expected {
+            assert!(
+                alerts
+                    .iter()
+                    .any(|a| a["alert"].as_str() == Some(alert.name))
+            );
+        }
+
+        // Alert expressions must query the metric they belong to, so renaming a
+        // metric can't silently break its alert.
+        for metric in METRICS {
+            if let Some(alert) = metric.alert {
+                assert!(alert.expr.contains(metric.name), "{}", alert.name);
+            }
+        }
+    }
+}
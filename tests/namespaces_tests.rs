@@ -0,0 +1,56 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::namespaces::{NAMESPACE_OPT_IN_LABEL, NamespacePolicy};
+    use k8s_openapi::api::core::v1::Namespace;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+    use kube::runtime::{reflector, watcher};
+    use std::collections::BTreeMap;
+
+    fn namespace(name: &str, opted_in: bool) -> Namespace {
+        let labels = opted_in
+            .then(|| BTreeMap::from([(NAMESPACE_OPT_IN_LABEL.to_string(), "true".to_string())]));
+        Namespace {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                labels,
+                ..ObjectMeta::default()
+            },
+            ..Namespace::default()
+        }
+    }
+
+    #[test]
+    fn test_denied_namespaces_are_never_allowed() {
+        let policy = NamespacePolicy::new(vec!["kube-system".to_string()], false);
+
+        assert!(!policy.allows("kube-system"));
+        assert!(policy.allows("default"));
+    }
+
+    #[test]
+    fn test_opt_in_label_required() {
+        let policy = NamespacePolicy::new(vec!["kube-system".to_string()], true);
+        let opted_in = BTreeMap::from([(NAMESPACE_OPT_IN_LABEL.to_string(), "true".to_string())]);
+
+        assert!(policy.allows_labels("apps", Some(&opted_in)));
+        assert!(!policy.allows_labels("apps", None));
+        // The deny-list wins over the label.
+        assert!(!policy.allows_labels("kube-system", Some(&opted_in)));
+    }
+
+    #[test]
+    fn test_opt_in_label_looked_up_in_namespace_store() {
+        let policy = NamespacePolicy::new(vec![], true);
+        // No store yet: nothing is allowed.
+        assert!(!policy.allows("apps"));
+
+        let (reader, mut writer) = reflector::store();
+        writer.apply_watcher_event(&watcher::Event::Apply(namespace("apps", true)));
+        writer.apply_watcher_event(&watcher::Event::Apply(namespace("default", false)));
+        policy.watch_namespaces(reader);
+
+        assert!(policy.allows("apps"));
+        assert!(!policy.allows("default"));
+        assert!(!policy.allows("unknown"));
+    }
+}
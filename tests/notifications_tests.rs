@@ -1,7 +1,15 @@
 #[cfg(test)]
 mod tests {
-    use gitops_operator::notifications::send;
-    use wiremock::matchers::{body_json_string, header, method};
+    use gitops_operator::notifications::{
+        FallbackNotificationSender, HttpNotificationSender, Notification, NotificationEndpoint,
+        NotificationProvider, discord_payload, send, teams_payload,
+    };
+    use gitops_operator::traits::NotificationSender;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use wiremock::matchers::{
+        body_json_string, body_partial_json, body_string_contains, header, method, path,
+    };
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[tokio::test]
@@ -53,4 +61,396 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_sender_fails_on_error_status() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+
+        let result = HttpNotificationSender::new()
+            .send(
+                "test message",
+                &NotificationEndpoint::new(mock_server.uri()),
+            )
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fallback_takes_what_the_primary_webhook_fails_to_deliver() {
+        let mock_server = MockServer::start().await;
+        let primary = NotificationEndpoint::new(format!("{}/primary", mock_server.uri()));
+        let fallback = format!("{}/fallback", mock_server.uri());
+
+        let primary_mock = Mock::given(method("POST"))
+            .and(path("/primary"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(2)
+            .mount_as_scoped(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/fallback"))
+            .and(body_string_contains("deploy failed"))
+            .and(body_string_contains("failed 2 time(s) in a row"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/fallback"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+
+        let sender =
+            FallbackNotificationSender::new(Arc::new(HttpNotificationSender::new()), &fallback);
+        sender.send("first", &primary).await.unwrap();
+        sender.send("deploy failed", &primary).await.unwrap();
+        assert_eq!(sender.consecutive_failures(&primary.url), 2);
+        drop(primary_mock);
+
+        // Once the primary webhook works again it is used on its own.
+        Mock::given(method("POST"))
+            .and(path("/primary"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        sender.send("recovered", &primary).await.unwrap();
+        assert_eq!(sender.consecutive_failures(&primary.url), 0);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_failing_too_is_an_error() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&mock_server)
+            .await;
+
+        let sender = FallbackNotificationSender::new(
+            Arc::new(HttpNotificationSender::new()),
+            &format!("{}/fallback", mock_server.uri()),
+        );
+        let result = sender
+            .send(
+                "test message",
+                &NotificationEndpoint::new(format!("{}/primary", mock_server.uri())),
+            )
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sender_sends_the_endpoint_headers() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(header("authorization", "Bearer s3cret"))
+            .and(header("x-team", "platform"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let endpoint = NotificationEndpoint::new(mock_server.uri())
+            .with_header("Authorization", "Bearer s3cret")
+            .with_header("X-Team", "platform");
+        HttpNotificationSender::new()
+            .send("test message", &endpoint)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_provider_is_detected_from_the_url() {
+        for (url, provider) in [
+            (
+                "https://hooks.slack.com/services/T000/B000/XXXX",
+                NotificationProvider::Slack,
+            ),
+            (
+                "HTTPS://Hooks.Slack.com/workflows/T000",
+                NotificationProvider::Slack,
+            ),
+            (
+                "https://example.com/hooks.slack.com",
+                NotificationProvider::Webhook,
+            ),
+            (
+                "https://hooks.slack.com.example.com/x",
+                NotificationProvider::Webhook,
+            ),
+            (
+                "https://discord.com/api/webhooks/123/abc",
+                NotificationProvider::Discord,
+            ),
+            (
+                "https://discordapp.com/api/webhooks/123/abc",
+                NotificationProvider::Discord,
+            ),
+            (
+                "https://contoso.webhook.office.com/webhookb2/abc@def/IncomingWebhook/123/456",
+                NotificationProvider::Teams,
+            ),
+            ("http://localhost:8080/hook", NotificationProvider::Webhook),
+        ] {
+            assert_eq!(NotificationProvider::detect(url), provider, "{}", url);
+            assert_eq!(NotificationEndpoint::new(url).provider, provider, "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_provider_names() {
+        assert_eq!(
+            "slack".parse::<NotificationProvider>().unwrap(),
+            NotificationProvider::Slack
+        );
+        assert_eq!(
+            " discord ".parse::<NotificationProvider>().unwrap(),
+            NotificationProvider::Discord
+        );
+        assert_eq!(
+            "webhook".parse::<NotificationProvider>().unwrap(),
+            NotificationProvider::Webhook
+        );
+        assert_eq!(
+            "teams".parse::<NotificationProvider>().unwrap(),
+            NotificationProvider::Teams
+        );
+        assert!("Slack!".parse::<NotificationProvider>().is_err());
+    }
+
+    #[test]
+    fn test_discord_payload() {
+        let notification = Notification {
+            app: "blog".to_string(),
+            namespace: "default".to_string(),
+            ..Notification::success("Deployment blog patched successfully to version def456")
+                .with_tags(Some("abc123"), "def456")
+                .with_commit_url(Some(
+                    "https://github.com/kainlite/blog/commit/def456".to_string(),
+                ))
+        };
+        assert_eq!(
+            discord_payload(&notification),
+            serde_json::json!({
+                "embeds": [{
+                    "description": "Deployment blog patched successfully to version def456",
+                    "color": 0x2EB67D,
+                    "fields": [
+                        { "name": "App", "value": "blog", "inline": true },
+                        { "name": "Namespace", "value": "default", "inline": true },
+                        { "name": "Tag", "value": "`abc123` → `def456`", "inline": true },
+                        {
+                            "name": "Commit",
+                            "value": "[View commit](https://github.com/kainlite/blog/commit/def456)",
+                            "inline": true,
+                        },
+                    ],
+                }],
+            })
+        );
+        let failure = discord_payload(&Notification::failure(":x: Build failed"));
+        assert_eq!(failure["embeds"][0]["color"], 0xE01E5A);
+        assert_eq!(failure["embeds"][0]["fields"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_teams_payload() {
+        let notification = Notification {
+            app: "blog".to_string(),
+            namespace: "default".to_string(),
+            ..Notification::failure(":x: Rollout of deployment blog to version def456 failed")
+                .with_tags(Some("abc123"), "def456")
+                .with_commit_url(Some(
+                    "https://github.com/kainlite/blog/commit/def456".to_string(),
+                ))
+        };
+        let payload = teams_payload(&notification);
+        assert_eq!(payload["type"], "message");
+        let attachment = &payload["attachments"][0];
+        assert_eq!(
+            attachment["contentType"],
+            "application/vnd.microsoft.card.adaptive"
+        );
+        let card = &attachment["content"];
+        assert_eq!(card["type"], "AdaptiveCard");
+        assert_eq!(card["body"][0]["style"], "attention");
+        assert_eq!(card["body"][0]["items"][0]["text"], notification.text);
+        assert_eq!(
+            card["body"][1]["facts"],
+            serde_json::json!([
+                { "title": "App", "value": "blog" },
+                { "title": "Namespace", "value": "default" },
+                { "title": "Tag", "value": "abc123 → def456" },
+            ])
+        );
+        assert_eq!(
+            card["actions"][0]["url"],
+            "https://github.com/kainlite/blog/commit/def456"
+        );
+
+        let info = teams_payload(&Notification::info("Build pending"));
+        let card = &info["attachments"][0]["content"];
+        assert_eq!(card["body"].as_array().unwrap().len(), 1);
+        assert!(card.get("actions").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_notifications_are_formatted_for_the_provider() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/slack"))
+            .and(body_partial_json(serde_json::json!({
+                "text": "Deployment blog patched",
+                "attachments": [{ "color": "#2EB67D" }],
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/webhook"))
+            .and(body_json_string(
+                serde_json::json!({ "text": "Deployment blog patched" }).to_string(),
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/discord"))
+            .and(body_partial_json(serde_json::json!({
+                "embeds": [{ "description": "Deployment blog patched", "color": 0x2EB67D }],
+            })))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/teams"))
+            .and(body_partial_json(serde_json::json!({
+                "type": "message",
+                "attachments": [{ "contentType": "application/vnd.microsoft.card.adaptive" }],
+            })))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        // Discord refuses `text`; plain messages go out as `content`.
+        Mock::given(method("POST"))
+            .and(path("/discord"))
+            .and(body_json_string(
+                serde_json::json!({ "content": "Build pending" }).to_string(),
+            ))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let notification =
+            Notification::success("Deployment blog patched").with_tags(Some("abc123"), "def456");
+        let sender = HttpNotificationSender::new();
+        sender
+            .send_notification(
+                &notification,
+                &NotificationEndpoint::new(format!("{}/slack", mock_server.uri()))
+                    .with_provider(NotificationProvider::Slack),
+            )
+            .await
+            .unwrap();
+        sender
+            .send_notification(
+                &notification,
+                &NotificationEndpoint::new(format!("{}/webhook", mock_server.uri())),
+            )
+            .await
+            .unwrap();
+        let discord = NotificationEndpoint::new(format!("{}/discord", mock_server.uri()))
+            .with_provider(NotificationProvider::Discord);
+        sender
+            .send_notification(&notification, &discord)
+            .await
+            .unwrap();
+        sender.send("Build pending", &discord).await.unwrap();
+        let teams = NotificationEndpoint::new(format!("{}/teams", mock_server.uri()))
+            .with_provider(NotificationProvider::Teams);
+        sender
+            .send_notification(&notification, &teams)
+            .await
+            .unwrap();
+        sender.send("Build pending", &teams).await.unwrap();
+    }
+
+    fn secret(fields: &[(&str, &str)]) -> BTreeMap<String, Vec<u8>> {
+        fields
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.as_bytes().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_endpoint_from_secret_data() {
+        let endpoint = NotificationEndpoint::from_secret_data(&secret(&[(
+            "webhook-url",
+            "https://hooks.slack.com/services/x",
+        )]))
+        .unwrap();
+        assert_eq!(
+            endpoint,
+            NotificationEndpoint::new("https://hooks.slack.com/services/x")
+        );
+
+        let endpoint = NotificationEndpoint::from_secret_data(&secret(&[
+            ("webhook-url", "https://proxy.internal/teams"),
+            ("auth-token", "s3cret\n"),
+            ("headers", "X-Team: platform\n\nX-Env:  prod \n"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            endpoint,
+            NotificationEndpoint::new("https://proxy.internal/teams")
+                .with_header("Authorization", "Bearer s3cret")
+                .with_header("X-Team", "platform")
+                .with_header("X-Env", "prod")
+        );
+        // Header values are credentials and stay out of the logs.
+        assert!(!format!("{:?}", endpoint).contains("s3cret"));
+
+        // Explicit headers win over the token.
+        let endpoint = NotificationEndpoint::from_secret_data(&secret(&[
+            ("webhook-url", "https://proxy.internal/teams"),
+            ("auth-token", "s3cret"),
+            ("headers", "Authorization: Basic dXNlcjpwYXNz"),
+        ]))
+        .unwrap();
+        assert_eq!(endpoint.headers["Authorization"], "Basic dXNlcjpwYXNz");
+    }
+
+    #[test]
+    fn test_endpoint_from_invalid_secret_data() {
+        assert!(NotificationEndpoint::from_secret_data(&secret(&[("auth-token", "x")])).is_err());
+        assert!(
+            NotificationEndpoint::from_secret_data(&secret(&[
+                ("webhook-url", "https://hooks.slack.com/services/x"),
+                ("headers", "no colon here"),
+            ]))
+            .is_err()
+        );
+        assert!(
+            NotificationEndpoint::from_secret_data(&secret(&[
+                ("webhook-url", "https://hooks.slack.com/services/x"),
+                ("headers", "Bad Name: value"),
+            ]))
+            .is_err()
+        );
+    }
 }
@@ -0,0 +1,83 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::configuration::{Action, Status};
+    use gitops_operator::operator::{DEFAULT_RECONCILE_INTERVAL, Operator};
+    use k8s_openapi::api::apps::v1::{Deployment, DeploymentSpec};
+    use k8s_openapi::api::core::v1::{Container, PodSpec, PodTemplateSpec};
+    use kube::api::ObjectMeta;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    fn deployment(name: &str, enabled: &str) -> Deployment {
+        let annotations = BTreeMap::from(
+            [
+                ("enabled", enabled),
+                ("app_repository", "git@github.com:org/app.git"),
+                ("manifest_repository", "git@github.com:org/manifests.git"),
+                ("image_name", "org/app"),
+                ("deployment_path", "app/deployment.yaml"),
+                ("ssh_key_name", "ssh-key"),
+                ("ssh_key_namespace", "gitops-operator"),
+            ]
+            .map(|(key, value)| (format!("gitops.operator.{}", key), value.to_string())),
+        );
+        Deployment {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                namespace: Some("default".to_string()),
+                annotations: Some(annotations),
+                ..Default::default()
+            },
+            spec: Some(DeploymentSpec {
+                template: PodTemplateSpec {
+                    spec: Some(PodSpec {
+                        containers: vec![Container {
+                            name: "app".to_string(),
+                            image: Some("org/app:abc123".to_string()),
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_builder_defaults_and_interval() {
+        assert_eq!(
+            Operator::builder().build().interval(),
+            DEFAULT_RECONCILE_INTERVAL
+        );
+        assert_eq!(
+            Operator::builder()
+                .interval(Duration::from_secs(30))
+                .build()
+                .interval(),
+            Duration::from_secs(30)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_once_skips_disabled_and_unannotated_deployments() {
+        let unannotated = Deployment {
+            metadata: ObjectMeta {
+                name: Some("plain".to_string()),
+                namespace: Some("default".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let results = Operator::builder()
+            .build()
+            .reconcile_once(&[deployment("disabled", "false"), unannotated])
+            .await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].action, Action::Skipped);
+        assert_eq!(results[0].status, Status::Skipped);
+    }
+}
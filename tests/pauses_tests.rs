@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::pauses::{Pause, PauseSource, Pauses};
+    use gitops_operator::state::MemoryState;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_pause_keeps_the_first_pause() {
+        let pauses = Pauses::new();
+        assert_eq!(pauses.get("default/blog"), None);
+
+        let (pause, created) = pauses
+            .pause("default/blog", Some("alice".to_string()))
+            .unwrap();
+        assert!(created);
+        assert_eq!(pause.source, PauseSource::Api);
+        assert_eq!(pause.by.as_deref(), Some("alice"));
+        assert!(pause.since.is_some());
+
+        let (again, created) = pauses.pause("default/blog", None).unwrap();
+        assert!(!created);
+        assert_eq!(again, pause);
+        assert_eq!(pauses.get("default/blog"), Some(pause));
+        assert_eq!(pauses.get("default/other"), None);
+    }
+
+    #[test]
+    fn test_resume_lifts_the_pause() {
+        let pauses = Pauses::new();
+        assert_eq!(pauses.resume("default/blog").unwrap(), None);
+
+        let (pause, _) = pauses.pause("default/blog", None).unwrap();
+        assert_eq!(pauses.resume("default/blog").unwrap(), Some(pause));
+        assert_eq!(pauses.get("default/blog"), None);
+    }
+
+    #[test]
+    fn test_pauses_live_in_the_store() {
+        let store = Arc::new(MemoryState::new());
+        let (pause, _) = Pauses::with_store(store.clone())
+            .pause("default/blog", None)
+            .unwrap();
+
+        // Another handle on the same store, e.g. after a restart.
+        assert_eq!(Pauses::with_store(store).get("default/blog"), Some(pause));
+    }
+
+    #[test]
+    fn test_annotation_pause_serialization() {
+        assert_eq!(
+            serde_json::to_value(Pause::annotation()).unwrap(),
+            serde_json::json!({ "source": "annotation" })
+        );
+    }
+}
@@ -0,0 +1,144 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::policy::{Policy, PolicyExpression, PolicyInput, Value};
+    use k8s_openapi::jiff::Timestamp;
+    use std::collections::BTreeMap;
+
+    fn input(old_tag: Option<&str>, new_tag: &str, now: &str) -> PolicyInput {
+        PolicyInput {
+            namespace: "production".to_string(),
+            name: "blog".to_string(),
+            branch: "main".to_string(),
+            old_tag: old_tag.map(String::from),
+            new_tag: new_tag.to_string(),
+            now: now.parse::<Timestamp>().unwrap(),
+        }
+    }
+
+    fn allows(expression: &str, input: &PolicyInput) -> bool {
+        PolicyExpression::parse(expression)
+            .unwrap()
+            .allows(&input.variables())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_variables_describe_the_promotion() {
+        // 2026-10-16 is a Friday.
+        let vars = input(None, "v1.2.0", "2026-10-16T14:05:00Z").variables();
+        assert_eq!(vars["old_tag"], Value::String(String::new()));
+        assert_eq!(vars["new_tag"], Value::String("v1.2.0".to_string()));
+        assert_eq!(vars["branch"], Value::String("main".to_string()));
+        assert_eq!(vars["hour"], Value::Int(14));
+        assert_eq!(vars["minute"], Value::Int(5));
+        assert_eq!(vars["weekday"], Value::Int(5));
+    }
+
+    #[test]
+    fn test_business_hours_policy() {
+        let policy = "hour >= 9 && hour < 17 && !(weekday in [0, 6])";
+        assert!(allows(policy, &input(None, "abc", "2026-10-16T10:00:00Z")));
+        assert!(!allows(policy, &input(None, "abc", "2026-10-16T18:00:00Z")));
+        assert!(!allows(policy, &input(None, "abc", "2026-10-17T10:00:00Z")));
+    }
+
+    #[test]
+    fn test_string_functions_and_operators() {
+        let promotion = input(Some("v1.4.2"), "v1.5.0", "2026-10-16T10:00:00Z");
+        for expression in [
+            "new_tag.startsWith('v1.') && !new_tag.endsWith('-rc')",
+            r#"new_tag.matches("^v[0-9]+\\.[0-9]+\\.[0-9]+$")"#,
+            "old_tag != new_tag && old_tag.contains('1.4')",
+            "size(new_tag) == 6 && new_tag.size() == 6",
+            "branch in ['main', 'release'] || namespace == 'staging'",
+            "int('42') % 5 == 2 && string(-3 + 5) == '2'",
+            "(namespace + '/' + name).lowerAscii() == 'production/blog'",
+            "namespace == 'production' ? branch == 'main' : true",
+            "old_tag < new_tag",
+        ] {
+            assert!(allows(expression, &promotion), "{}", expression);
+        }
+        assert!(!allows("new_tag.endsWith('.0') && false", &promotion));
+    }
+
+    #[test]
+    fn test_short_circuits_like_cel() {
+        let vars = BTreeMap::new();
+        let allows = |source: &str| PolicyExpression::parse(source).unwrap().allows(&vars);
+        assert!(!allows("false && missing").unwrap());
+        assert!(allows("true || missing").unwrap());
+        assert!(allows("missing").is_err());
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        for source in [
+            "",
+            "hour >=",
+            "hour = 9",
+            "(true",
+            "'unterminated",
+            "true true",
+            "new_tag.",
+        ] {
+            assert!(PolicyExpression::parse(source).is_err(), "{:?}", source);
+        }
+
+        // Nesting is bounded, however it is spelled.
+        for nested in [
+            format!("{}true{}", "(".repeat(10_000), ")".repeat(10_000)),
+            format!("{}true", "!".repeat(10_000)),
+            format!("{}1 > 0", "-".repeat(10_000)),
+            format!("{}1]", "[".repeat(10_000)),
+            vec!["1"; 10_000].join(" + "),
+            vec!["true"; 10_000].join(" || "),
+            format!("{}1", "true ? 1 : ".repeat(10_000)),
+        ] {
+            let error = PolicyExpression::parse(&nested).unwrap_err();
+            assert!(format!("{:#}", error).contains("nested"), "{:#}", error);
+        }
+        assert!(
+            PolicyExpression::parse(&format!("{}true{}", "(".repeat(20), ")".repeat(20))).is_ok()
+        );
+
+        let promotion = input(None, "abc", "2026-10-16T10:00:00Z");
+        for source in [
+            "hour",
+            "hour < 'nine'",
+            "new_tag.nope()",
+            "unknown == 1",
+            "1 in 'abc'",
+            "new_tag.matches('(')",
+            "-(-9223372036854775807 - 1) > 0",
+        ] {
+            let expression = PolicyExpression::parse(source).unwrap();
+            assert!(
+                expression.allows(&promotion.variables()).is_err(),
+                "{:?}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_policy_scope() {
+        let policy = Policy {
+            object: "ConfigMap platform/freeze".to_string(),
+            expression: "false".to_string(),
+            namespaces: vec!["production".to_string()],
+        };
+        assert!(policy.applies_to("production"));
+        assert!(!policy.applies_to("staging"));
+        assert!(
+            !policy
+                .allows(&input(None, "abc", "2026-10-16T10:00:00Z"))
+                .unwrap()
+        );
+
+        let everywhere = Policy {
+            namespaces: vec![],
+            ..policy
+        };
+        assert!(everywhere.applies_to("staging"));
+    }
+}
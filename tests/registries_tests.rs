@@ -0,0 +1,206 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::registries::{
+        DEFAULT_REGISTRY_URL, RegistryAuth, RegistryMap, RegistryMirror, RegistryMirrors,
+        ResolvedRegistry, qualified_image, registry_map_from_env,
+    };
+    use serial_test::serial;
+    use std::io::Write;
+
+    const MAP: &str = r#"
+- prefix: ghcr.io/acme/
+  secret_name: ghcr-acme
+- prefix: ghcr.io/acme/public-
+  auth: anonymous
+- prefix: registry.internal:5000/
+  url: http://registry.internal:5000
+  secret_name: internal
+  secret_namespace: infra
+- prefix: docker.io/library/
+  auth: anonymous
+- prefix: 123456789012.dkr.ecr.eu-west-1.amazonaws.com/
+  auth: ecr
+"#;
+
+    fn resolve(image: &str) -> ResolvedRegistry {
+        ResolvedRegistry::resolve(&RegistryMap::parse(MAP).unwrap(), image, None, None, None)
+    }
+
+    fn secret(name: &str, namespace: &str) -> Option<(String, String)> {
+        Some((name.to_string(), namespace.to_string()))
+    }
+
+    #[test]
+    fn test_qualified_image() {
+        assert_eq!(qualified_image("nginx"), "docker.io/library/nginx");
+        assert_eq!(qualified_image("kainlite/tr"), "docker.io/kainlite/tr");
+        assert_eq!(
+            qualified_image("ghcr.io/kainlite/tr"),
+            "ghcr.io/kainlite/tr"
+        );
+        assert_eq!(qualified_image("localhost/app"), "localhost/app");
+        assert_eq!(
+            qualified_image("registry.internal:5000/app"),
+            "registry.internal:5000/app"
+        );
+    }
+
+    #[test]
+    fn test_longest_prefix_wins() {
+        let map = RegistryMap::parse(MAP).unwrap();
+        assert_eq!(
+            map.lookup("ghcr.io/acme/api").unwrap().prefix,
+            "ghcr.io/acme/"
+        );
+        assert_eq!(
+            map.lookup("ghcr.io/acme/public-docs").unwrap().auth,
+            RegistryAuth::Anonymous
+        );
+        assert!(map.lookup("ghcr.io/other/api").is_none());
+        assert!(map.lookup("kainlite/tr").is_none());
+        assert_eq!(map.lookup("nginx").unwrap().prefix, "docker.io/library/");
+    }
+
+    #[test]
+    fn test_resolve_from_map() {
+        assert_eq!(
+            resolve("ghcr.io/acme/api"),
+            ResolvedRegistry {
+                url: "https://ghcr.io".to_string(),
+                auth: RegistryAuth::Dockerconfig,
+                secret: secret("ghcr-acme", "gitops-operator"),
+            }
+        );
+        assert_eq!(
+            resolve("ghcr.io/acme/public-docs"),
+            ResolvedRegistry {
+                url: "https://ghcr.io".to_string(),
+                auth: RegistryAuth::Anonymous,
+                secret: None,
+            }
+        );
+        assert_eq!(
+            resolve("registry.internal:5000/app"),
+            ResolvedRegistry {
+                url: "http://registry.internal:5000".to_string(),
+                auth: RegistryAuth::Dockerconfig,
+                secret: secret("internal", "infra"),
+            }
+        );
+        assert_eq!(
+            resolve("123456789012.dkr.ecr.eu-west-1.amazonaws.com/api"),
+            ResolvedRegistry {
+                url: "https://123456789012.dkr.ecr.eu-west-1.amazonaws.com".to_string(),
+                auth: RegistryAuth::Ecr,
+                secret: None,
+            }
+        );
+        assert_eq!(
+            resolve("library/nginx"),
+            ResolvedRegistry {
+                url: DEFAULT_REGISTRY_URL.to_string(),
+                auth: RegistryAuth::Anonymous,
+                secret: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_resolve_without_an_entry_uses_the_defaults() {
+        assert_eq!(
+            resolve("kainlite/tr"),
+            ResolvedRegistry {
+                url: DEFAULT_REGISTRY_URL.to_string(),
+                auth: RegistryAuth::Dockerconfig,
+                secret: secret("regcred", "gitops-operator"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_annotations_override_the_map() {
+        let map = RegistryMap::parse(MAP).unwrap();
+        let resolved = ResolvedRegistry::resolve(
+            &map,
+            "registry.internal:5000/app",
+            Some("https://other.example.com"),
+            None,
+            Some("team"),
+        );
+        assert_eq!(resolved.url, "https://other.example.com");
+        assert_eq!(resolved.secret, secret("internal", "team"));
+
+        // Naming a secret turns an anonymous entry into an authenticated one.
+        let resolved =
+            ResolvedRegistry::resolve(&map, "ghcr.io/acme/public-docs", None, Some("mine"), None);
+        assert_eq!(resolved.secret, secret("mine", "gitops-operator"));
+        let resolved = ResolvedRegistry::resolve(
+            &map,
+            "123456789012.dkr.ecr.eu-west-1.amazonaws.com/api",
+            None,
+            Some("mine"),
+            None,
+        );
+        assert_eq!(resolved.auth, RegistryAuth::Dockerconfig);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_maps() {
+        assert!(RegistryMap::parse("- prefix: ''\n").is_err());
+        assert!(RegistryMap::parse("- prefix: ghcr.io/\n  auth: token\n").is_err());
+        assert!(RegistryMap::parse("prefix: ghcr.io/\n").is_err());
+        assert!(RegistryMap::parse("[]").unwrap().is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn test_registry_map_from_env() {
+        unsafe { std::env::remove_var("GITOPS_REGISTRIES_PATH") };
+        assert!(registry_map_from_env().is_empty());
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(MAP.as_bytes()).unwrap();
+        unsafe { std::env::set_var("GITOPS_REGISTRIES_PATH", file.path()) };
+        assert_eq!(registry_map_from_env(), RegistryMap::parse(MAP).unwrap());
+
+        // An invalid file is ignored rather than stopping the operator.
+        unsafe { std::env::set_var("GITOPS_REGISTRIES_PATH", "/nonexistent/registries.yaml") };
+        assert!(registry_map_from_env().is_empty());
+
+        unsafe { std::env::remove_var("GITOPS_REGISTRIES_PATH") };
+    }
+
+    #[test]
+    fn test_registry_mirrors() {
+        let mirrors = RegistryMirrors::parse(
+            "docker.io=https://harbor.internal/dockerhub/, ghcr.io=http://ghcr.internal:5000",
+        )
+        .unwrap();
+        let dockerhub = RegistryMirror {
+            url: "https://harbor.internal".to_string(),
+            prefix: Some("dockerhub".to_string()),
+        };
+        assert_eq!(mirrors.lookup(DEFAULT_REGISTRY_URL), Some(&dockerhub));
+        assert_eq!(
+            mirrors.lookup("https://registry-1.docker.io"),
+            Some(&dockerhub)
+        );
+        assert_eq!(
+            mirrors.lookup("https://ghcr.io").cloned(),
+            Some(RegistryMirror {
+                url: "http://ghcr.internal:5000".to_string(),
+                prefix: None,
+            })
+        );
+        assert!(mirrors.lookup("https://quay.io").is_none());
+        assert_eq!(
+            dockerhub.repository("library/nginx"),
+            "dockerhub/library/nginx"
+        );
+
+        assert!(RegistryMirrors::parse("").unwrap().is_empty());
+        assert!(RegistryMirrors::parse("docker.io").is_err());
+        assert!(RegistryMirrors::parse("docker.io=harbor.internal").is_err());
+        assert!(RegistryMirrors::parse("=https://harbor.internal").is_err());
+    }
+}
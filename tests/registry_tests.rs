@@ -1,12 +1,15 @@
 #[cfg(test)]
 mod tests {
+    use gitops_operator::registries::RegistryMirror;
     use gitops_operator::registry::*;
+    use gitops_operator::retry::{RetryPolicy, with_policy};
+    use gitops_operator::traits::ImageChecker;
 
     use serde_json::json;
     use tracing_subscriber::{EnvFilter, fmt};
     use wiremock::{
         Mock, MockServer, ResponseTemplate,
-        matchers::{header, method, path, query_param},
+        matchers::{body_json, header, header_exists, method, path, query_param},
     };
 
     #[test]
@@ -401,4 +404,479 @@ mod tests {
         let token = checker.get_bearer_token(&challenge).await;
         assert!(token.is_err());
     }
+
+    #[tokio::test]
+    async fn test_check_images_requests_one_multi_scope_token() {
+        init_logging();
+        let mock_server = MockServer::start().await;
+
+        // Authorized requests are mounted first so they take precedence over
+        // the unauthenticated challenge below.
+        for repo in ["org/api", "org/worker"] {
+            Mock::given(method("HEAD"))
+                .and(path(format!("/v2/{}/manifests/abc123", repo)))
+                .and(header("authorization", "Bearer fleet-token"))
+                .respond_with(ResponseTemplate::new(200))
+                .mount(&mock_server)
+                .await;
+        }
+
+        Mock::given(method("HEAD"))
+            .and(path("/v2/org/worker/manifests/missing"))
+            .and(header("authorization", "Bearer fleet-token"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("HEAD"))
+            .respond_with(
+                ResponseTemplate::new(401).insert_header(
+                    "www-authenticate",
+                    format!(
+                        r#"Bearer realm="{}/token",service="registry.test.com",scope="repository:org/api:pull""#,
+                        mock_server.uri()
+                    ),
+                ),
+            )
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/token"))
+            .and(query_param("scope", "repository:org/api:pull"))
+            .and(query_param("scope", "repository:org/worker:pull"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "token": "fleet-token",
+                "expires_in": 300
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let checker = RegistryChecker::new(mock_server.uri(), None).await.unwrap();
+
+        let images = vec![
+            ("org/api".to_string(), "abc123".to_string()),
+            ("org/worker".to_string(), "abc123".to_string()),
+            ("org/worker".to_string(), "missing".to_string()),
+        ];
+        let results: Vec<bool> = checker
+            .check_images(&images)
+            .await
+            .into_iter()
+            .map(|r| r.unwrap())
+            .collect();
+
+        assert_eq!(results, vec![true, true, false]);
+    }
+
+    #[test]
+    fn test_next_page_url_from_link_header() {
+        let current = "https://registry.test.com/v2/org/app/tags/list";
+        assert_eq!(
+            next_page_url(
+                current,
+                r#"</v2/org/app/tags/list?last=v1.2.0&n=100>; rel="next""#
+            )
+            .as_deref(),
+            Some("https://registry.test.com/v2/org/app/tags/list?last=v1.2.0&n=100")
+        );
+        assert_eq!(
+            next_page_url(current, r#"<https://mirror.test.com/v2/x>; rel=next"#).as_deref(),
+            Some("https://mirror.test.com/v2/x")
+        );
+        assert_eq!(next_page_url(current, r#"</v2/first>; rel="prev""#), None);
+    }
+
+    #[tokio::test]
+    async fn test_list_tags_follows_pagination() {
+        init_logging();
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/v2/org/app/tags/list"))
+            .and(query_param("last", "v1.1.0"))
+            .and(header("authorization", "Bearer tags-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "name": "org/app",
+                "tags": ["v1.2.0"]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/v2/org/app/tags/list"))
+            .and(header("authorization", "Bearer tags-token"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header(
+                        "link",
+                        r#"</v2/org/app/tags/list?last=v1.1.0&n=2>; rel="next""#,
+                    )
+                    .set_body_json(json!({
+                        "name": "org/app",
+                        "tags": ["v1.0.0", "v1.1.0"]
+                    })),
+            )
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/v2/org/app/tags/list"))
+            .respond_with(
+                ResponseTemplate::new(401).insert_header(
+                    "www-authenticate",
+                    format!(
+                        r#"Bearer realm="{}/token",service="registry.test.com",scope="repository:org/app:pull""#,
+                        mock_server.uri()
+                    ),
+                ),
+            )
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "token": "tags-token",
+                "expires_in": 300
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let checker = RegistryChecker::new(mock_server.uri(), None).await.unwrap();
+        let tags = checker.list_tags("org/app").await.unwrap();
+        assert_eq!(tags, vec!["v1.0.0", "v1.1.0", "v1.2.0"]);
+    }
+
+    #[tokio::test]
+    async fn test_image_digest_reads_the_content_digest() {
+        init_logging();
+        let mock_server = MockServer::start().await;
+        let digest = format!("sha256:{}", "ab".repeat(32));
+
+        Mock::given(method("HEAD"))
+            .and(path("/v2/org/digest-app/manifests/v1.0.0"))
+            .and(header("authorization", "Basic dXNlcjpwYXNz"))
+            .respond_with(
+                ResponseTemplate::new(200).insert_header("docker-content-digest", &digest),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(path("/v2/org/digest-app/manifests/missing"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+
+        let checker =
+            RegistryChecker::new(mock_server.uri(), Some("Basic dXNlcjpwYXNz".to_string()))
+                .await
+                .unwrap();
+        assert_eq!(
+            checker
+                .image_digest("org/digest-app", "v1.0.0")
+                .await
+                .unwrap(),
+            Some(digest)
+        );
+        assert_eq!(
+            checker
+                .image_digest("org/digest-app", "missing")
+                .await
+                .unwrap(),
+            None
+        );
+
+        // The runtime pulls indexes too, so they must be accepted.
+        let requests = mock_server.received_requests().await.unwrap();
+        let accept = requests[0].headers.get("accept").unwrap().to_str().unwrap();
+        assert!(
+            accept.contains("application/vnd.oci.image.index.v1+json"),
+            "{}",
+            accept
+        );
+    }
+
+    #[tokio::test]
+    async fn test_image_labels_follow_indexes_to_the_image_config() {
+        init_logging();
+        let mock_server = MockServer::start().await;
+        let amd64 = format!("sha256:{}", "a".repeat(64));
+        let config = format!("sha256:{}", "c".repeat(64));
+
+        Mock::given(method("GET"))
+            .and(path("/v2/org/app/manifests/build-10"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "mediaType": "application/vnd.oci.image.index.v1+json",
+                "manifests": [
+                    {"digest": format!("sha256:{}", "b".repeat(64)),
+                     "platform": {"os": "linux", "architecture": "arm64"}},
+                    {"digest": amd64, "platform": {"os": "linux", "architecture": "amd64"}},
+                    {"digest": format!("sha256:{}", "d".repeat(64)),
+                     "platform": {"os": "unknown", "architecture": "unknown"}}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/v2/org/app/manifests/{}", amd64)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "config": {"digest": config}
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/v2/org/app/blobs/{}", config)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "architecture": "amd64",
+                "config": {"Labels": {"org.opencontainers.image.revision": "3c0a882"}}
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/org/app/manifests/missing"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+
+        let checker = RegistryChecker::new(mock_server.uri(), None).await.unwrap();
+        let labels = checker.image_labels("org/app", "build-10").await.unwrap();
+        assert_eq!(
+            labels
+                .get("org.opencontainers.image.revision")
+                .map(String::as_str),
+            Some("3c0a882")
+        );
+        assert!(checker.image_labels("org/app", "missing").await.is_err());
+    }
+
+    #[test]
+    fn test_ecr_registries_are_recognized() {
+        assert_eq!(
+            EcrRegistry::from_url("https://123456789012.dkr.ecr.eu-west-1.amazonaws.com"),
+            Some(EcrRegistry {
+                account: "123456789012".to_string(),
+                region: "eu-west-1".to_string(),
+            })
+        );
+        assert_eq!(
+            EcrRegistry::from_url("123456789012.dkr.ecr.cn-north-1.amazonaws.com.cn/")
+                .map(|r| r.region),
+            Some("cn-north-1".to_string())
+        );
+        assert!(EcrRegistry::from_url("https://ghcr.io").is_none());
+        assert!(EcrRegistry::from_url("https://public.ecr.aws").is_none());
+        assert!(EcrRegistry::from_url("https://acme.dkr.ecr.eu-west-1.amazonaws.com").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ecr_tokens_are_cached_until_they_expire() {
+        init_logging();
+        let mock_server = MockServer::start().await;
+        unsafe {
+            std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDEXAMPLE");
+            std::env::set_var("AWS_SECRET_ACCESS_KEY", "secret");
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64();
+
+        Mock::given(method("POST"))
+            .and(path("/"))
+            .and(header(
+                "x-amz-target",
+                "AmazonEC2ContainerRegistry_V20150921.GetAuthorizationToken",
+            ))
+            .and(header_exists("authorization"))
+            .and(body_json(json!({"registryIds": ["123456789012"]})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "authorizationData": [{
+                    "authorizationToken": "QVdTOnBhc3N3b3Jk",
+                    "expiresAt": now + 12.0 * 3600.0,
+                    "proxyEndpoint": "https://123456789012.dkr.ecr.eu-west-1.amazonaws.com"
+                }]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        // Expires within the refresh margin: exchanged on every use.
+        Mock::given(method("POST"))
+            .and(body_json(json!({"registryIds": ["210987654321"]})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "authorizationData": [{
+                    "authorizationToken": "QVdTOnN0YWxl",
+                    "expiresAt": now + 60.0
+                }]
+            })))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let auth = EcrAuth::with_endpoint(format!("{}/", mock_server.uri()));
+        let registry = "https://123456789012.dkr.ecr.eu-west-1.amazonaws.com";
+        for _ in 0..2 {
+            assert_eq!(
+                auth.auth_token(registry).await.unwrap(),
+                "Basic QVdTOnBhc3N3b3Jk"
+            );
+        }
+        let registry = "https://210987654321.dkr.ecr.us-east-1.amazonaws.com";
+        for _ in 0..2 {
+            assert_eq!(
+                auth.auth_token(registry).await.unwrap(),
+                "Basic QVdTOnN0YWxl"
+            );
+        }
+        assert!(auth.auth_token("https://ghcr.io").await.is_err());
+
+        unsafe {
+            std::env::remove_var("AWS_ACCESS_KEY_ID");
+            std::env::remove_var("AWS_SECRET_ACCESS_KEY");
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_remaining_header() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(rate_limit_remaining(&headers), None);
+        headers.insert("ratelimit-remaining", "76;w=21600".parse().unwrap());
+        assert_eq!(rate_limit_remaining(&headers), Some(76));
+        headers.insert("ratelimit-remaining", "0".parse().unwrap());
+        assert_eq!(rate_limit_remaining(&headers), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_registries_are_left_alone() {
+        init_logging();
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("HEAD"))
+            .and(path("/v2/library/nginx/manifests/1.27"))
+            .respond_with(
+                ResponseTemplate::new(200).insert_header("ratelimit-remaining", "1;w=21600"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(path("/v2/library/nginx/manifests/1.28"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("retry-after", "120")
+                    .insert_header("ratelimit-remaining", "0;w=21600"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mut checker = RegistryChecker::new(mock_server.uri(), None).await.unwrap();
+        checker.client = with_policy(
+            reqwest::Client::new(),
+            RetryPolicy {
+                max_retries: 0,
+                ..RetryPolicy::default()
+            },
+        );
+        assert!(checker.check_image("library/nginx", "1.27").await.unwrap());
+
+        let error = checker
+            .check_image("library/nginx", "1.28")
+            .await
+            .unwrap_err();
+        let limited = error.downcast_ref::<RateLimited>().unwrap();
+        assert_eq!(limited.retry_after, std::time::Duration::from_secs(120));
+
+        // Until Retry-After has passed, the registry isn't asked at all.
+        let error = checker
+            .check_image("library/nginx", "1.27")
+            .await
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("rate limit reached"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_checks_go_to_the_mirror() {
+        init_logging();
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("HEAD"))
+            .and(path("/v2/dockerhub/library/nginx/manifests/1.27"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/dockerhub/library/nginx/tags/list"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"name": "dockerhub/library/nginx", "tags": ["1.27"]})),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let checker = RegistryChecker::new("https://index.docker.io/v1/".to_string(), None)
+            .await
+            .unwrap()
+            .with_mirror(Some(
+                RegistryMirror::parse(&format!("{}/dockerhub", mock_server.uri())).unwrap(),
+            ));
+        assert!(checker.check_image("library/nginx", "1.27").await.unwrap());
+        assert_eq!(
+            checker.list_tags("library/nginx").await.unwrap(),
+            vec!["1.27".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fallback_registries_answer_in_order() {
+        init_logging();
+        let primary = MockServer::start().await;
+        let dr = MockServer::start().await;
+
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&primary)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(path("/v2/org/app/manifests/abc123"))
+            .respond_with(
+                ResponseTemplate::new(200).insert_header("docker-content-digest", "sha256:dr"),
+            )
+            .mount(&dr)
+            .await;
+
+        let no_retries = || {
+            with_policy(
+                reqwest::Client::new(),
+                RetryPolicy {
+                    max_retries: 0,
+                    ..RetryPolicy::default()
+                },
+            )
+        };
+        let mut checkers: Vec<(String, Box<dyn ImageChecker>)> = vec![];
+        for server in [&primary, &dr] {
+            let mut checker = RegistryChecker::new(server.uri(), None).await.unwrap();
+            checker.client = no_retries();
+            checkers.push((server.uri(), Box::new(checker)));
+        }
+        let checker = FallbackImageChecker::new(checkers);
+
+        assert_eq!(checker.answered_by(), None);
+        assert!(checker.check_image("org/app", "abc123").await.unwrap());
+        assert_eq!(checker.answered_by(), Some(dr.uri()));
+        assert_eq!(
+            checker.image_digest("org/app", "abc123").await.unwrap(),
+            Some("sha256:dr".to_string())
+        );
+        assert!(!checker.check_image("org/app", "missing").await.unwrap());
+    }
 }
@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::configuration::{Action, ReconcileResult, Status};
+    use gitops_operator::results::ResultBoard;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn result(action: Action, to_sha: &str) -> ReconcileResult {
+        ReconcileResult {
+            deployment: "blog".to_string(),
+            namespace: "default".to_string(),
+            action,
+            from_sha: None,
+            to_sha: Some(to_sha.to_string()),
+            status: Status::Success,
+            message: format!("at {}", to_sha),
+            resource_version: None,
+            generation: None,
+            error: None,
+            git_retries: 0,
+        }
+    }
+
+    #[test]
+    fn test_only_changed_results_bump_the_revision() {
+        let board = ResultBoard::new();
+        assert_eq!(board.latest("default/blog"), None);
+
+        board.publish(&[result(Action::UpToDate, "aaa")]);
+        board.publish(&[result(Action::UpToDate, "aaa")]);
+        assert_eq!(board.latest("default/blog").unwrap().revision, 1);
+
+        board.publish(&[result(Action::Patched, "bbb")]);
+        let latest = board.latest("default/blog").unwrap();
+        assert_eq!(latest.revision, 2);
+        assert_eq!(latest.result.action, Action::Patched);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_change_returns_the_next_result() {
+        let board = Arc::new(ResultBoard::new());
+        board.publish(&[result(Action::UpToDate, "aaa")]);
+
+        let waiter = {
+            let board = board.clone();
+            tokio::spawn(async move {
+                board
+                    .wait_for_change("default/blog", None, Duration::from_secs(5))
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        board.publish(&[result(Action::Patched, "bbb")]);
+
+        let changed = waiter.await.unwrap().unwrap();
+        assert_eq!(changed.revision, 2);
+        assert_eq!(changed.result.to_sha.as_deref(), Some("bbb"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_change_with_stale_revision_returns_at_once() {
+        let board = ResultBoard::new();
+        board.publish(&[result(Action::Patched, "bbb")]);
+
+        let latest = board
+            .wait_for_change("default/blog", Some(0), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(latest.revision, 1);
+
+        // Already seen: nothing changes before the timeout.
+        assert_eq!(
+            board
+                .wait_for_change("default/blog", Some(1), Duration::from_millis(20))
+                .await,
+            None
+        );
+    }
+}
@@ -0,0 +1,143 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::notifications::{HttpNotificationSender, NotificationEndpoint};
+    use gitops_operator::retry::{RetryPolicy, is_retryable_status, retry_after, with_policy};
+    use gitops_operator::traits::NotificationSender;
+    use reqwest::StatusCode;
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use std::time::{Duration, SystemTime};
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn fast_policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(50),
+        }
+    }
+
+    #[test]
+    fn test_delay_backs_off_exponentially_and_honors_retry_after() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+        };
+
+        assert_eq!(policy.delay(0, None), Duration::from_millis(100));
+        assert_eq!(policy.delay(2, None), Duration::from_millis(400));
+        assert_eq!(policy.delay(10, None), Duration::from_secs(1));
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_millis(700))),
+            Duration::from_millis(700)
+        );
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(120))),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn test_retry_after_parses_seconds_and_dates() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+
+        let date = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(60));
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(&date).unwrap());
+        let wait = retry_after(&headers).unwrap();
+        assert!(wait > Duration::from_secs(50) && wait <= Duration::from_secs(60));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_retryable_statuses() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable_status(StatusCode::REQUEST_TIMEOUT));
+        assert!(!is_retryable_status(StatusCode::NOT_FOUND));
+        assert!(!is_retryable_status(StatusCode::UNAUTHORIZED));
+    }
+
+    #[tokio::test]
+    async fn test_get_is_retried_until_it_succeeds() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503).insert_header("Retry-After", "0"))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let client = with_policy(reqwest::Client::new(), fast_policy(3));
+        let response = client.get(server.uri()).send().await.unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retries_stop_at_the_limit() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(429))
+            .mount(&server)
+            .await;
+
+        let client = with_policy(reqwest::Client::new(), fast_policy(2));
+        let response = client.get(server.uri()).send().await.unwrap();
+
+        assert_eq!(response.status(), 429);
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_post_without_idempotency_key_is_sent_once() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        let client = with_policy(reqwest::Client::new(), fast_policy(3));
+        let response = client.post(server.uri()).send().await.unwrap();
+
+        assert_eq!(response.status(), 503);
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_notification_retries_reuse_one_idempotency_key() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(502).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        HttpNotificationSender::new()
+            .send("deployed", &NotificationEndpoint::new(server.uri()))
+            .await
+            .unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        let keys: Vec<_> = requests
+            .iter()
+            .map(|r| r.headers.get("idempotency-key").unwrap().clone())
+            .collect();
+        assert_eq!(keys[0], keys[1]);
+    }
+}
@@ -0,0 +1,153 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::rollouts::{
+        DEFAULT_ROLLOUT_TIMEOUT, RolloutStatus, Rollouts, rollout_progress, rollout_timeout,
+        started,
+    };
+    use k8s_openapi::api::apps::v1::{
+        Deployment, DeploymentCondition, DeploymentSpec, DeploymentStatus,
+    };
+    use k8s_openapi::api::core::v1::{Container, PodSpec, PodTemplateSpec};
+    use kube::api::ObjectMeta;
+    use serial_test::serial;
+    use std::time::Duration;
+
+    fn deployment(image: &str, replicas: i32, status: DeploymentStatus) -> Deployment {
+        Deployment {
+            metadata: ObjectMeta {
+                name: Some("blog".to_string()),
+                namespace: Some("default".to_string()),
+                generation: Some(2),
+                ..Default::default()
+            },
+            spec: Some(DeploymentSpec {
+                replicas: Some(replicas),
+                template: PodTemplateSpec {
+                    spec: Some(PodSpec {
+                        containers: vec![
+                            Container {
+                                name: "blog".to_string(),
+                                image: Some(image.to_string()),
+                                ..Default::default()
+                            },
+                            Container {
+                                name: "proxy".to_string(),
+                                image: Some("envoy:1.30".to_string()),
+                                ..Default::default()
+                            },
+                        ],
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            status: Some(status),
+        }
+    }
+
+    fn status(observed: i64, total: i32, updated: i32, available: i32) -> DeploymentStatus {
+        DeploymentStatus {
+            observed_generation: Some(observed),
+            replicas: Some(total),
+            updated_replicas: Some(updated),
+            available_replicas: Some(available),
+            ..Default::default()
+        }
+    }
+
+    fn tracked() -> Vec<String> {
+        vec!["blog".to_string()]
+    }
+
+    #[test]
+    fn test_rollout_waits_for_the_new_tag() {
+        let live = deployment("kainlite/blog:old", 2, status(2, 2, 2, 2));
+        let (status, message) = rollout_progress(&live, &tracked(), "abc");
+        assert_eq!(status, RolloutStatus::Progressing);
+        assert_eq!(message, "waiting for blog to be updated to abc");
+    }
+
+    #[test]
+    fn test_rollout_progress_follows_the_replicas() {
+        let image = "registry.example.com:5000/kainlite/blog:abc";
+        let cases = [
+            (
+                status(1, 2, 2, 2),
+                "waiting for the new spec to be observed",
+            ),
+            (status(2, 2, 1, 2), "1 of 2 replicas updated"),
+            (status(2, 3, 2, 2), "1 old replicas pending termination"),
+            (status(2, 2, 2, 1), "1 of 2 updated replicas available"),
+        ];
+        for (status, expected) in cases {
+            let live = deployment(image, 2, status);
+            assert_eq!(
+                rollout_progress(&live, &tracked(), "abc"),
+                (RolloutStatus::Progressing, expected.to_string())
+            );
+        }
+
+        let live = deployment(image, 2, status(2, 2, 2, 2));
+        assert_eq!(
+            rollout_progress(&live, &tracked(), "abc"),
+            (
+                RolloutStatus::Complete,
+                "2 replica(s) running abc".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_rollout_fails_past_the_progress_deadline() {
+        let mut stuck = status(2, 3, 1, 2);
+        stuck.conditions = Some(vec![DeploymentCondition {
+            type_: "Progressing".to_string(),
+            status: "False".to_string(),
+            reason: Some("ProgressDeadlineExceeded".to_string()),
+            message: Some("ReplicaSet \"blog-7d9f\" has timed out progressing.".to_string()),
+            ..Default::default()
+        }]);
+        let live = deployment("kainlite/blog:abc", 2, stuck);
+
+        let (status, message) = rollout_progress(&live, &tracked(), "abc");
+        assert_eq!(status, RolloutStatus::Failed);
+        assert!(message.contains("timed out progressing"), "{}", message);
+    }
+
+    #[test]
+    fn test_rollouts_keep_the_latest_per_deployment() {
+        let rollouts = Rollouts::new();
+        rollouts
+            .record(&started("default", "blog", "abc", None))
+            .unwrap();
+        let mut done = started("default", "blog", "def", Some("abc"));
+        done.status = RolloutStatus::Complete;
+        done.finished_at = Some("2026-10-16T09:31:14Z".to_string());
+        rollouts.record(&done).unwrap();
+        rollouts
+            .record(&started("prod", "api", "abc", None))
+            .unwrap();
+
+        assert_eq!(rollouts.get("default/blog").unwrap(), Some(done));
+        assert_eq!(rollouts.get("default/missing").unwrap(), None);
+        let list = rollouts.list().unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[1].deployment, "api");
+        assert_eq!(list[1].status, RolloutStatus::Progressing);
+    }
+
+    #[test]
+    #[serial]
+    fn test_rollout_timeout_from_env() {
+        unsafe { std::env::remove_var("GITOPS_ROLLOUT_TIMEOUT_SECS") };
+        assert_eq!(rollout_timeout(), DEFAULT_ROLLOUT_TIMEOUT);
+
+        unsafe { std::env::set_var("GITOPS_ROLLOUT_TIMEOUT_SECS", "120") };
+        assert_eq!(rollout_timeout(), Duration::from_secs(120));
+
+        unsafe { std::env::set_var("GITOPS_ROLLOUT_TIMEOUT_SECS", "soon") };
+        assert_eq!(rollout_timeout(), DEFAULT_ROLLOUT_TIMEOUT);
+        unsafe { std::env::remove_var("GITOPS_ROLLOUT_TIMEOUT_SECS") };
+    }
+}
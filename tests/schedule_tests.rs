@@ -0,0 +1,122 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::schedule::{CronSchedule, ScheduleSettings, reconcile_cronjob};
+    use k8s_openapi::jiff::Timestamp;
+
+    fn at(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    fn next(expression: &str, after: &str) -> Option<String> {
+        CronSchedule::parse(expression)
+            .unwrap()
+            .next_after(at(after))
+            .map(|t| t.to_string())
+    }
+
+    #[test]
+    fn test_next_after_steps_ranges_and_lists() {
+        assert_eq!(
+            next("*/5 * * * *", "2026-03-10T10:02:30Z").as_deref(),
+            Some("2026-03-10T10:05:00Z")
+        );
+        // Strictly after: a tick exactly at `after` is not repeated.
+        assert_eq!(
+            next("*/5 * * * *", "2026-03-10T10:05:00Z").as_deref(),
+            Some("2026-03-10T10:10:00Z")
+        );
+        assert_eq!(
+            next("0 9-17/4 * * *", "2026-03-10T14:00:00Z").as_deref(),
+            Some("2026-03-10T17:00:00Z")
+        );
+        assert_eq!(
+            next("30 2 1,15 * *", "2026-03-02T00:00:00Z").as_deref(),
+            Some("2026-03-15T02:30:00Z")
+        );
+    }
+
+    #[test]
+    fn test_next_after_names_macros_and_weekdays() {
+        // 2026-03-10 is a Tuesday.
+        assert_eq!(
+            next("0 8 * * mon-fri", "2026-03-13T09:00:00Z").as_deref(),
+            Some("2026-03-16T08:00:00Z")
+        );
+        // 7 is Sunday too.
+        assert_eq!(
+            next("0 0 * * 7", "2026-03-10T00:00:00Z").as_deref(),
+            Some("2026-03-15T00:00:00Z")
+        );
+        assert_eq!(
+            next("@monthly", "2026-03-10T00:00:00Z").as_deref(),
+            Some("2026-04-01T00:00:00Z")
+        );
+        assert_eq!(
+            next("0 0 1 jan *", "2026-03-10T00:00:00Z").as_deref(),
+            Some("2027-01-01T00:00:00Z")
+        );
+        // Both day fields restricted: either one matches.
+        assert_eq!(
+            next("0 0 20 * sun", "2026-03-10T00:00:00Z").as_deref(),
+            Some("2026-03-15T00:00:00Z")
+        );
+        assert_eq!(next("0 0 30 2 *", "2026-03-10T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_expressions() {
+        for expression in [
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "@often",
+        ] {
+            assert!(
+                CronSchedule::parse(expression).is_err(),
+                "{} should be rejected",
+                expression
+            );
+        }
+        assert_eq!(
+            CronSchedule::parse(" @hourly ").unwrap().expression(),
+            "@hourly"
+        );
+    }
+
+    #[test]
+    fn test_reconcile_cronjob_is_suspended_and_carries_schedule() {
+        let settings = ScheduleSettings {
+            schedule: CronSchedule::parse("*/10 * * * *").unwrap(),
+            cronjob_name: "gitops-operator-reconcile".to_string(),
+            namespace: "gitops-operator".to_string(),
+            reconcile_url: "http://gitops-operator.gitops-operator.svc:8000/reconcile".to_string(),
+        };
+
+        let cronjob = reconcile_cronjob(&settings, &settings.schedule);
+        assert_eq!(
+            cronjob.metadata.name.as_deref(),
+            Some("gitops-operator-reconcile")
+        );
+        assert_eq!(
+            cronjob.metadata.namespace.as_deref(),
+            Some("gitops-operator")
+        );
+        assert_eq!(cronjob.spec.schedule, "*/10 * * * *");
+        assert_eq!(cronjob.spec.suspend, Some(true));
+
+        let pod = cronjob
+            .spec
+            .job_template
+            .spec
+            .unwrap()
+            .template
+            .spec
+            .unwrap();
+        let command = pod.containers[0].command.clone().unwrap();
+        assert_eq!(
+            command.last().map(String::as_str),
+            Some(settings.reconcile_url.as_str())
+        );
+    }
+}
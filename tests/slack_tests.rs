@@ -0,0 +1,190 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::notifications::{
+        HttpNotificationSender, Notification, NotificationEndpoint,
+    };
+    use gitops_operator::slack::{
+        ApprovalClick, approval_payload, notification_payload, parse_interaction,
+        replacement_payload, verify_signature,
+    };
+    use gitops_operator::traits::NotificationSender;
+    use serial_test::serial;
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // The example request from Slack's "Verifying requests from Slack" guide.
+    const SECRET: &str = "8f742231b10e8888abcd99yyyzzz85a5";
+    const TIMESTAMP: &str = "1531420618";
+    const BODY: &str = "token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner&command=%2Fwebhook-collect&text=&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J%2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN&trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c";
+    const SIGNATURE: &str = "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503";
+
+    #[test]
+    fn test_verify_signature() {
+        let now = 1531420618 + 60;
+        verify_signature(SECRET, TIMESTAMP, BODY.as_bytes(), SIGNATURE, now).unwrap();
+
+        let tampered = BODY.replace("roadrunner", "coyote");
+        assert!(verify_signature(SECRET, TIMESTAMP, tampered.as_bytes(), SIGNATURE, now).is_err());
+        assert!(verify_signature("other", TIMESTAMP, BODY.as_bytes(), SIGNATURE, now).is_err());
+        assert!(verify_signature(SECRET, TIMESTAMP, BODY.as_bytes(), "v0=abc", now).is_err());
+        assert!(verify_signature(SECRET, "soon", BODY.as_bytes(), SIGNATURE, now).is_err());
+
+        // Replays of old requests are refused.
+        let err =
+            verify_signature(SECRET, TIMESTAMP, BODY.as_bytes(), SIGNATURE, now + 600).unwrap_err();
+        assert!(err.to_string().contains("too old"), "{}", err);
+    }
+
+    fn form(payload: serde_json::Value) -> Vec<u8> {
+        serde_urlencoded::to_string([("payload", payload.to_string())])
+            .unwrap()
+            .into_bytes()
+    }
+
+    #[test]
+    fn test_parse_interaction() {
+        let body = form(serde_json::json!({
+            "type": "block_actions",
+            "user": {"id": "U123", "username": "alice"},
+            "response_url": "https://hooks.slack.com/actions/T1/1/abc",
+            "actions": [{"action_id": "reject", "block_id": "approval-4f1c", "value": "4f1c2a9be0d3"}],
+        }));
+        assert_eq!(
+            parse_interaction(&body).unwrap(),
+            Some(ApprovalClick {
+                change_id: "4f1c2a9be0d3".to_string(),
+                approve: false,
+                user: "alice".to_string(),
+                response_url: Some("https://hooks.slack.com/actions/T1/1/abc".to_string()),
+            })
+        );
+
+        let body = form(serde_json::json!({
+            "type": "block_actions",
+            "user": {"id": "U123"},
+            "actions": [{"action_id": "approve", "value": "4f1c2a9be0d3"}],
+        }));
+        let click = parse_interaction(&body).unwrap().unwrap();
+        assert!(click.approve);
+        assert_eq!(click.user, "U123");
+        assert_eq!(click.response_url, None);
+
+        // Other interactions are left alone.
+        let body = form(serde_json::json!({"type": "view_submission"}));
+        assert_eq!(parse_interaction(&body).unwrap(), None);
+        let body = form(serde_json::json!({
+            "type": "block_actions",
+            "actions": [{"action_id": "something_else", "value": "x"}],
+        }));
+        assert_eq!(parse_interaction(&body).unwrap(), None);
+
+        assert!(parse_interaction(b"payload=not-json").is_err());
+        assert!(parse_interaction(b"token=abc").is_err());
+    }
+
+    #[test]
+    fn test_payloads() {
+        let payload = approval_payload("Promote blog?", "4f1c2a9be0d3");
+        assert_eq!(payload["text"], "Promote blog?");
+        let buttons = payload["blocks"][1]["elements"].as_array().unwrap();
+        assert_eq!(buttons.len(), 2);
+        assert_eq!(buttons[0]["action_id"], "approve");
+        assert_eq!(buttons[1]["action_id"], "reject");
+        assert!(buttons.iter().all(|b| b["value"] == "4f1c2a9be0d3"));
+
+        let replacement = replacement_payload("approved");
+        assert_eq!(replacement["replace_original"], true);
+        assert_eq!(replacement["text"], "approved");
+    }
+
+    #[test]
+    fn test_notification_payload() {
+        let notification = Notification {
+            app: "blog".to_string(),
+            namespace: "default".to_string(),
+            ..Notification::failure(":x: Rollout of deployment blog to version def456 failed")
+                .with_tags(Some("abc123"), "def456")
+                .with_commit_url(Some(
+                    "https://github.com/kainlite/blog/commit/def456".to_string(),
+                ))
+        };
+        let payload = notification_payload(&notification);
+        assert_eq!(payload["text"], notification.text);
+        let attachment = &payload["attachments"][0];
+        assert_eq!(attachment["color"], "#E01E5A");
+        assert_eq!(attachment["blocks"][0]["text"]["text"], notification.text);
+        let fields: Vec<&str> = attachment["blocks"][1]["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| field["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                "*App*\nblog",
+                "*Namespace*\ndefault",
+                "*Tag*\n`abc123` → `def456`",
+                "*Commit*\n<https://github.com/kainlite/blog/commit/def456|View commit>",
+            ]
+        );
+
+        // Nothing to lay out next to a bare message.
+        let payload = notification_payload(&Notification::info("hello"));
+        assert_eq!(payload["attachments"][0]["color"], "#439FE0");
+        assert_eq!(
+            payload["attachments"][0]["blocks"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_approval_requests_carry_buttons_once_interactivity_is_configured() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                serde_json::json!({"text": "Promote blog?"}),
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        unsafe {
+            std::env::remove_var("GITOPS_SLACK_SIGNING_SECRET");
+        }
+        HttpNotificationSender::new()
+            .send_approval_request(
+                "Promote blog?",
+                "4f1c2a9be0d3",
+                &NotificationEndpoint::new(server.uri()),
+            )
+            .await
+            .unwrap();
+
+        unsafe {
+            std::env::set_var("GITOPS_SLACK_SIGNING_SECRET", SECRET);
+        }
+        HttpNotificationSender::new()
+            .send_approval_request(
+                "Promote blog?",
+                "4f1c2a9be0d3",
+                &NotificationEndpoint::new(server.uri()),
+            )
+            .await
+            .unwrap();
+        unsafe {
+            std::env::remove_var("GITOPS_SLACK_SIGNING_SECRET");
+        }
+
+        let requests = server.received_requests().await.unwrap();
+        let plain: serde_json::Value = requests[0].body_json().unwrap();
+        let interactive: serde_json::Value = requests[1].body_json().unwrap();
+        assert!(plain.get("blocks").is_none());
+        assert_eq!(interactive["blocks"][1]["type"], "actions");
+    }
+}
@@ -0,0 +1,279 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::configuration::{Action, ReconcileResult, Status};
+    use gitops_operator::history::{HistoryRetention, HistoryStore, now_rfc3339};
+    use gitops_operator::state::{
+        MemoryState, SqliteState, StateBackend, StateSettings, blocking, decode_buckets,
+        encode_buckets,
+    };
+    use gitops_operator::suspension::FailureCounter;
+    use gitops_operator::traits::StateStore;
+    use serial_test::serial;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn exercise(store: &dyn StateStore) {
+        assert_eq!(store.get("failures", "default/blog").unwrap(), None);
+
+        store.put("failures", "default/blog", "1").unwrap();
+        store.put("failures", "default/api", "2").unwrap();
+        store.put("failures", "default/blog", "3").unwrap();
+        store.put("other", "default/blog", "x").unwrap();
+
+        assert_eq!(
+            store.get("failures", "default/blog").unwrap().as_deref(),
+            Some("3")
+        );
+        assert_eq!(
+            store.entries("failures").unwrap(),
+            [
+                ("default/api".to_string(), "2".to_string()),
+                ("default/blog".to_string(), "3".to_string()),
+            ]
+        );
+
+        store.remove("failures", "default/blog").unwrap();
+        store.remove("failures", "missing").unwrap();
+        assert_eq!(store.get("failures", "default/blog").unwrap(), None);
+        assert_eq!(store.entries("other").unwrap().len(), 1);
+        assert!(store.entries("empty").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_memory_state() {
+        exercise(&MemoryState::new());
+    }
+
+    #[test]
+    fn test_sqlite_state_persists_across_reopen() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.db").display().to_string();
+
+        exercise(&SqliteState::open(&path).unwrap());
+
+        let reopened = SqliteState::open(&path).unwrap();
+        assert_eq!(
+            reopened.get("failures", "default/api").unwrap().as_deref(),
+            Some("2")
+        );
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_blocking_runs_off_the_runtime() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.db").display().to_string();
+        let state = Arc::new(SqliteState::open(&path).unwrap());
+
+        let runtime_thread = std::thread::current().id();
+        let thread = blocking(&state, |state| {
+            state.put("failures", "default/blog", "1").unwrap();
+            std::thread::current().id()
+        })
+        .await;
+        assert_ne!(thread, runtime_thread);
+
+        let failures = Arc::new(FailureCounter::with_store(state.clone()));
+        assert_eq!(
+            blocking(&failures, |failures| failures
+                .record_failure("default/blog"))
+            .await,
+            2
+        );
+    }
+
+    #[test]
+    fn test_sqlite_state_shares_the_history_database() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("history.db").display().to_string();
+
+        let history = HistoryStore::open(&path).unwrap();
+        let state = SqliteState::open(&path).unwrap();
+        state.put("failures", "default/blog", "1").unwrap();
+        history.record("run-1", &now_rfc3339(), &[]).unwrap();
+
+        assert!(history.list(10).unwrap().is_empty());
+        assert_eq!(state.entries("failures").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_configmap_data_round_trips() {
+        let mut buckets = BTreeMap::new();
+        buckets.insert(
+            "failures".to_string(),
+            BTreeMap::from([("default/blog".to_string(), "2".to_string())]),
+        );
+        buckets.insert("empty".to_string(), BTreeMap::new());
+
+        let data = encode_buckets(&buckets);
+        assert_eq!(data.len(), 1);
+        assert_eq!(data["failures"], r#"{"default/blog":"2"}"#);
+
+        buckets.remove("empty");
+        assert_eq!(decode_buckets(&data), buckets);
+
+        let mut broken = data.clone();
+        broken.insert("garbage".to_string(), "not json".to_string());
+        assert_eq!(decode_buckets(&broken), buckets);
+    }
+
+    #[test]
+    fn test_backend_parsing() {
+        assert_eq!(
+            "memory".parse::<StateBackend>().unwrap(),
+            StateBackend::Memory
+        );
+        assert_eq!(
+            " SQLite ".parse::<StateBackend>().unwrap(),
+            StateBackend::Sqlite
+        );
+        assert_eq!(
+            "configmap".parse::<StateBackend>().unwrap(),
+            StateBackend::Kubernetes
+        );
+        assert_eq!(
+            "kubernetes".parse::<StateBackend>().unwrap(),
+            StateBackend::Kubernetes
+        );
+        assert!("etcd".parse::<StateBackend>().is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_settings_from_env() {
+        unsafe {
+            std::env::remove_var("GITOPS_STATE_BACKEND");
+            std::env::remove_var("GITOPS_STATE_PATH");
+            std::env::remove_var("GITOPS_STATE_CONFIGMAP");
+            std::env::set_var("GITOPS_HISTORY_PATH", "/data/history.db");
+        }
+        let settings = StateSettings::from_env();
+        assert_eq!(settings.backend, StateBackend::Sqlite);
+        assert_eq!(settings.path, "/data/history.db");
+        assert_eq!(settings.configmap, "gitops-operator-state");
+
+        unsafe {
+            std::env::set_var("GITOPS_STATE_BACKEND", "kubernetes");
+            std::env::set_var("GITOPS_STATE_CONFIGMAP", "state");
+        }
+        let settings = StateSettings::from_env();
+        assert_eq!(settings.backend, StateBackend::Kubernetes);
+        assert_eq!(settings.configmap, "state");
+
+        unsafe {
+            std::env::set_var("GITOPS_STATE_BACKEND", "etcd");
+        }
+        assert_eq!(StateSettings::from_env().backend, StateBackend::Sqlite);
+
+        unsafe {
+            std::env::remove_var("GITOPS_STATE_BACKEND");
+            std::env::remove_var("GITOPS_STATE_CONFIGMAP");
+            std::env::remove_var("GITOPS_HISTORY_PATH");
+        }
+    }
+
+    #[test]
+    fn test_failure_streaks_survive_a_new_counter() {
+        let store: Arc<dyn StateStore> = Arc::new(MemoryState::new());
+
+        let counter = FailureCounter::with_store(store.clone());
+        assert_eq!(counter.record_failure("default/blog"), 1);
+        assert_eq!(counter.record_failure("default/blog"), 2);
+
+        let restarted = FailureCounter::with_store(store.clone());
+        assert_eq!(restarted.count("default/blog"), 2);
+        assert_eq!(restarted.record_failure("default/blog"), 3);
+
+        restarted.reset("default/blog");
+        assert!(store.entries("failures").unwrap().is_empty());
+    }
+
+    fn result(deployment: &str, action: Action, status: Status) -> ReconcileResult {
+        ReconcileResult {
+            deployment: deployment.to_string(),
+            namespace: "default".to_string(),
+            action,
+            from_sha: None,
+            to_sha: Some("bbb".to_string()),
+            status,
+            message: format!("{} reconciled", deployment),
+            resource_version: None,
+            generation: None,
+            error: None,
+            git_retries: 0,
+        }
+    }
+
+    #[test]
+    fn test_history_on_state_keeps_the_newest_records() {
+        let store: Arc<dyn StateStore> = Arc::new(MemoryState::new());
+        let history = HistoryStore::on_state(store.clone(), 3);
+
+        history
+            .record(
+                "run-1",
+                &now_rfc3339(),
+                &[
+                    result("blog", Action::Patched, Status::Success),
+                    result("api", Action::Failed, Status::Failure),
+                ],
+            )
+            .unwrap();
+        history
+            .record(
+                "run-2",
+                &now_rfc3339(),
+                &[
+                    result("blog", Action::UpToDate, Status::Success),
+                    result("api", Action::Patched, Status::Success),
+                ],
+            )
+            .unwrap();
+
+        let all = history.list(10).unwrap();
+        assert_eq!(
+            all.iter().map(|r| r.id).collect::<Vec<_>>(),
+            [4, 3, 2],
+            "the oldest record is evicted"
+        );
+        assert_eq!(all[0].action, "patched");
+        assert_eq!(all[2].status, "failure");
+        assert_eq!(store.entries("history").unwrap().len(), 3);
+
+        assert_eq!(history.list(1).unwrap().len(), 1);
+        assert_eq!(history.for_run("run-2").unwrap().len(), 2);
+        let api = history.for_deployment("default", "api", 10).unwrap();
+        assert_eq!(api.len(), 2);
+        assert!(
+            history
+                .for_deployment("other", "api", 10)
+                .unwrap()
+                .is_empty()
+        );
+
+        // A new store over the same state carries on numbering.
+        HistoryStore::on_state(store.clone(), 3)
+            .record(
+                "run-3",
+                &now_rfc3339(),
+                &[result("blog", Action::Skipped, Status::Skipped)],
+            )
+            .unwrap();
+        assert_eq!(history.list(1).unwrap()[0].id, 5);
+
+        // A tighter retention wins over the store's own limit.
+        let retention = HistoryRetention {
+            max_age: None,
+            max_rows: Some(1),
+        };
+        HistoryStore::on_state(store.clone(), 3)
+            .with_retention(retention)
+            .record(
+                "run-4",
+                &now_rfc3339(),
+                &[result("blog", Action::UpToDate, Status::Success)],
+            )
+            .unwrap();
+        assert_eq!(store.entries("history").unwrap().len(), 1);
+    }
+}
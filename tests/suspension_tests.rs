@@ -0,0 +1,19 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::suspension::FailureCounter;
+
+    #[test]
+    fn test_failure_counter_counts_streaks_per_key() {
+        let counter = FailureCounter::new();
+
+        assert_eq!(counter.record_failure("default/a"), 1);
+        assert_eq!(counter.record_failure("default/a"), 2);
+        assert_eq!(counter.record_failure("default/b"), 1);
+        assert_eq!(counter.count("default/a"), 2);
+
+        counter.reset("default/a");
+        assert_eq!(counter.count("default/a"), 0);
+        assert_eq!(counter.count("default/b"), 1);
+        assert_eq!(counter.record_failure("default/a"), 1);
+    }
+}
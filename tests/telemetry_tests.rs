@@ -1,9 +1,16 @@
 #[cfg(test)]
 mod tests {
-    use gitops_operator::telemetry::{init_subscriber, otlp_endpoint, resource};
-    use opentelemetry::global;
+    use gitops_operator::telemetry::{
+        RECONCILE_ACTION_ATTRIBUTE, RECONCILE_STATUS_ATTRIBUTE, ReloadableSpanProcessor,
+        SamplingRules, TailSamplingProcessor, Telemetry, TelemetrySettings, init_subscriber,
+        otlp_endpoint, resource, telemetry,
+    };
+    use opentelemetry::trace::{Span as _, Tracer as _, TracerProvider as _};
+    use opentelemetry::{Context, KeyValue, global};
+    use opentelemetry_sdk::error::OTelSdkResult;
+    use opentelemetry_sdk::trace::{SdkTracerProvider, Span, SpanData, SpanProcessor};
     use serial_test::serial;
-    use std::sync::Once;
+    use std::sync::{Arc, Mutex, Once};
     use std::time::Duration;
     use tokio::time::timeout;
 
@@ -162,4 +169,171 @@ mod tests {
         // Log an event within the span
         tracing::info!(event = "test_event", "Testing telemetry configuration");
     }
+
+    /// Span processor that keeps the names of the spans it receives
+    #[derive(Debug, Clone, Default)]
+    struct RecordingProcessor {
+        spans: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl SpanProcessor for RecordingProcessor {
+        fn on_start(&self, _span: &mut Span, _cx: &Context) {}
+
+        fn on_end(&self, span: SpanData) {
+            self.spans.lock().unwrap().push(span.name.to_string());
+        }
+
+        fn force_flush(&self) -> OTelSdkResult {
+            Ok(())
+        }
+
+        fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+            Ok(())
+        }
+    }
+
+    /// Run one trace with a root span and a reconcile child span carrying the
+    /// given action/status, returning the span names that were exported.
+    fn sample_trace(rules: SamplingRules, action: &str, status: &str) -> Vec<String> {
+        let recorder = RecordingProcessor::default();
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(TailSamplingProcessor::new(rules, recorder.clone()))
+            .build();
+        let tracer = provider.tracer("test");
+
+        tracer.in_span("http_request", |_cx| {
+            let mut child = tracer.start("process_deployment");
+            child.set_attribute(KeyValue::new(
+                RECONCILE_ACTION_ATTRIBUTE,
+                action.to_string(),
+            ));
+            child.set_attribute(KeyValue::new(
+                RECONCILE_STATUS_ATTRIBUTE,
+                status.to_string(),
+            ));
+            child.end();
+        });
+
+        recorder.spans.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn test_tail_sampling_drops_up_to_date_traces() {
+        let rules = SamplingRules {
+            ratio: 1.0,
+            up_to_date_ratio: 0.0,
+        };
+
+        assert!(sample_trace(rules, "up_to_date", "success").is_empty());
+        assert_eq!(
+            sample_trace(rules, "patched", "success"),
+            vec!["process_deployment", "http_request"]
+        );
+    }
+
+    #[test]
+    fn test_tail_sampling_always_keeps_failures() {
+        let rules = SamplingRules {
+            ratio: 0.0,
+            up_to_date_ratio: 0.0,
+        };
+
+        assert_eq!(
+            sample_trace(rules, "failed", "failure"),
+            vec!["process_deployment", "http_request"]
+        );
+        assert!(sample_trace(rules, "patched", "success").is_empty());
+    }
+
+    #[test]
+    fn test_reloadable_processor_follows_the_current_pipeline() {
+        let spans = ReloadableSpanProcessor::new();
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(spans.clone())
+            .build();
+        let tracer = provider.tracer("test");
+
+        // Nothing is exported before a pipeline is in place.
+        tracer.start("dropped").end();
+        assert!(!spans.is_active());
+
+        let first = RecordingProcessor::default();
+        assert!(spans.replace(Some(Box::new(first.clone()))).is_none());
+        tracer.start("first").end();
+
+        let second = RecordingProcessor::default();
+        assert!(spans.replace(Some(Box::new(second.clone()))).is_some());
+        tracer.start("second").end();
+
+        assert_eq!(*first.spans.lock().unwrap(), vec!["first"]);
+        assert_eq!(*second.spans.lock().unwrap(), vec!["second"]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_telemetry_settings_from_env() {
+        unsafe {
+            std::env::set_var(OTLP_ENV, "http://collector:4317");
+            std::env::set_var("GITOPS_OTEL_METRICS", "false");
+            std::env::remove_var("GITOPS_OTEL_TRACES");
+        }
+
+        assert_eq!(
+            TelemetrySettings::from_env(),
+            TelemetrySettings {
+                endpoint: "http://collector:4317".to_string(),
+                traces: true,
+                metrics: false,
+            }
+        );
+
+        unsafe {
+            std::env::remove_var(OTLP_ENV);
+            std::env::remove_var("GITOPS_OTEL_METRICS");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_telemetry_reload_switches_and_validates() {
+        let spans = ReloadableSpanProcessor::new();
+        let pipelines = Telemetry::new("test-telemetry".into(), spans.clone());
+
+        let settings = TelemetrySettings {
+            endpoint: "http://localhost:4317".to_string(),
+            traces: true,
+            metrics: true,
+        };
+        pipelines.reload(settings.clone()).unwrap();
+        assert!(spans.is_active());
+        assert_eq!(pipelines.settings(), settings);
+
+        let quiet = TelemetrySettings {
+            traces: false,
+            metrics: false,
+            ..settings.clone()
+        };
+        pipelines.reload(quiet.clone()).unwrap();
+        assert!(!spans.is_active());
+        assert_eq!(pipelines.settings(), quiet);
+
+        // A bad endpoint leaves the current pipelines alone.
+        let error = pipelines
+            .reload(TelemetrySettings {
+                endpoint: "collector:4317".to_string(),
+                ..settings
+            })
+            .unwrap_err();
+        assert!(error.to_string().contains("collector:4317"));
+        assert_eq!(pipelines.settings(), quiet);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[serial]
+    async fn test_init_subscriber_registers_the_pipelines() {
+        setup_test_environment().await;
+
+        let settings = telemetry().expect("telemetry not initialized").settings();
+        assert!(settings.traces);
+        assert!(settings.metrics);
+    }
 }
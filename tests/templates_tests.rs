@@ -0,0 +1,34 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::templates::render;
+    use std::collections::BTreeMap;
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_render_substitutes_known_placeholders() {
+        let out = render(
+            "deploy {app} to {namespace} ({team})",
+            &vars(&[("app", "blog"), ("namespace", "prod"), ("team", "web")]),
+        );
+        assert_eq!(out, "deploy blog to prod (web)");
+    }
+
+    #[test]
+    fn test_render_leaves_unknown_placeholders() {
+        let out = render("{app} {missing}", &vars(&[("app", "blog")]));
+        assert_eq!(out, "blog {missing}");
+    }
+
+    #[test]
+    fn test_render_escaped_and_unbalanced_braces() {
+        let out = render("{{literal}} {app} { }", &vars(&[("app", "blog")]));
+        assert_eq!(out, "{literal} blog { }");
+        assert_eq!(render("trailing {", &vars(&[])), "trailing {");
+    }
+}
@@ -0,0 +1,150 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::history::HistoryRecord;
+    use gitops_operator::timeline::{MAX_EVENTS, Timeline, TimelineEvent, TimelineKind, merge};
+    use k8s_openapi::api::apps::v1::{Deployment, DeploymentSpec};
+    use k8s_openapi::api::core::v1::{Container, PodSpec, PodTemplateSpec};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+    use std::collections::BTreeMap;
+
+    fn deployment(image: &str, annotated: bool) -> Deployment {
+        let mut annotations = BTreeMap::new();
+        if annotated {
+            annotations.insert("gitops.operator.enabled".to_string(), "true".to_string());
+        }
+        Deployment {
+            metadata: ObjectMeta {
+                name: Some("blog".to_string()),
+                namespace: Some("default".to_string()),
+                annotations: Some(annotations),
+                ..ObjectMeta::default()
+            },
+            spec: Some(DeploymentSpec {
+                template: PodTemplateSpec {
+                    spec: Some(PodSpec {
+                        containers: vec![
+                            Container {
+                                name: "blog".to_string(),
+                                image: Some(image.to_string()),
+                                ..Container::default()
+                            },
+                            Container {
+                                name: "proxy".to_string(),
+                                image: Some("envoy:1.30".to_string()),
+                                ..Container::default()
+                            },
+                        ],
+                        ..PodSpec::default()
+                    }),
+                    ..PodTemplateSpec::default()
+                },
+                ..DeploymentSpec::default()
+            }),
+            ..Deployment::default()
+        }
+    }
+
+    fn event(at: &str, summary: &str) -> TimelineEvent {
+        TimelineEvent {
+            at: at.to_string(),
+            ..TimelineEvent::new(TimelineKind::Notification, summary)
+        }
+    }
+
+    fn record(id: i64, finished_at: &str) -> HistoryRecord {
+        HistoryRecord {
+            id,
+            run_id: "run".to_string(),
+            namespace: "default".to_string(),
+            deployment: "blog".to_string(),
+            action: "patched".to_string(),
+            status: "success".to_string(),
+            from_sha: Some("abc".to_string()),
+            to_sha: Some("def".to_string()),
+            message: format!("pass {}", id),
+            started_at: finished_at.to_string(),
+            finished_at: finished_at.to_string(),
+            resource_version: None,
+            generation: None,
+        }
+    }
+
+    #[test]
+    fn test_observe_records_image_changes() {
+        let timeline = Timeline::new();
+
+        // The first sighting is the baseline; seeing it again changes nothing.
+        timeline.observe(&deployment("kainlite/blog:abc", true));
+        timeline.observe(&deployment("kainlite/blog:abc", true));
+        assert!(timeline.events("default/blog").is_empty());
+
+        timeline.observe(&deployment("kainlite/blog:def", true));
+        let events = timeline.events("default/blog");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, TimelineKind::ImageObserved);
+        assert_eq!(
+            events[0].summary,
+            "Images changed in the cluster: blog=kainlite/blog:def"
+        );
+        assert_eq!(
+            events[0].from.as_deref(),
+            Some("blog=kainlite/blog:abc,proxy=envoy:1.30")
+        );
+        assert_eq!(
+            events[0].to.as_deref(),
+            Some("blog=kainlite/blog:def,proxy=envoy:1.30")
+        );
+    }
+
+    #[test]
+    fn test_observe_ignores_untracked_deployments() {
+        let timeline = Timeline::new();
+        timeline.observe(&deployment("kainlite/blog:abc", false));
+        timeline.observe(&deployment("kainlite/blog:def", false));
+        assert!(timeline.events("default/blog").is_empty());
+    }
+
+    #[test]
+    fn test_record_keeps_the_newest_events() {
+        let timeline = Timeline::new();
+        for i in 0..MAX_EVENTS + 5 {
+            timeline.record(
+                "default/blog",
+                event("2026-10-16T09:30:00Z", &i.to_string()),
+            );
+        }
+
+        let events = timeline.events("default/blog");
+        assert_eq!(events.len(), MAX_EVENTS);
+        assert_eq!(events[0].summary, "5");
+        assert!(timeline.events("default/other").is_empty());
+    }
+
+    #[test]
+    fn test_merge_orders_events_and_history() {
+        // The history comes newest first.
+        let history = [
+            record(2, "2026-10-16T09:35:00Z"),
+            record(1, "2026-10-16T09:30:00Z"),
+        ];
+        let events = vec![
+            event("2026-10-16T09:30:00Z", "patched"),
+            event("2026-10-16T09:32:00Z", "observed"),
+        ];
+
+        let merged = merge(&history, events.clone(), 10);
+        let summaries: Vec<&str> = merged.iter().map(|e| e.summary.as_str()).collect();
+        assert_eq!(
+            summaries,
+            ["patched", "patched: pass 1", "observed", "patched: pass 2"]
+        );
+        assert_eq!(merged[1].kind, TimelineKind::Reconcile);
+        assert_eq!(merged[1].status.as_deref(), Some("success"));
+        assert_eq!(merged[1].to.as_deref(), Some("def"));
+
+        // The limit keeps the most recent.
+        let merged = merge(&history, events, 2);
+        assert_eq!(merged[0].summary, "observed");
+        assert_eq!(merged.len(), 2);
+    }
+}
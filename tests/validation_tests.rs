@@ -0,0 +1,132 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::validation::{
+        validate_file, validate_manifest, validate_manifests_default,
+    };
+    use serial_test::serial;
+    use std::io::Write;
+
+    const DEPLOYMENT: &str = r#"apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: blog
+spec:
+  replicas: 2
+  selector:
+    matchLabels:
+      app: blog
+  template:
+    metadata:
+      labels:
+        app: blog
+    spec:
+      containers:
+      - name: blog
+        image: kainlite/blog:abc
+"#;
+
+    #[test]
+    fn test_valid_manifests() {
+        assert!(validate_manifest(DEPLOYMENT).is_empty());
+
+        let multi = format!(
+            "{}---\napiVersion: v1\nkind: Service\nmetadata:\n  name: blog\nspec:\n  ports:\n  - port: 80\n---\n",
+            DEPLOYMENT
+        );
+        assert!(validate_manifest(&multi).is_empty());
+
+        // Helm values and other plain YAML only have to parse.
+        assert!(validate_manifest("image:\n  repository: kainlite/blog\n  tag: abc\n").is_empty());
+
+        // Custom resources get the common checks only.
+        let rollout = "apiVersion: argoproj.io/v1alpha1\nkind: Rollout\nmetadata:\n  name: blog\nspec:\n  anything: goes\n";
+        assert!(validate_manifest(rollout).is_empty());
+    }
+
+    #[test]
+    fn test_wrong_field_types_are_reported() {
+        let errors = validate_manifest(&DEPLOYMENT.replace("replicas: 2", "replicas: two"));
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(
+            errors[0].starts_with("document 1 (Deployment blog): "),
+            "{}",
+            errors[0]
+        );
+        assert!(errors[0].contains("invalid type"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn test_containers_are_checked() {
+        let errors = validate_manifest(
+            &DEPLOYMENT.replace("image: kainlite/blog:abc", "image: \"kainlite/blog:\""),
+        );
+        assert_eq!(
+            errors,
+            [
+                "document 1 (Deployment blog): spec.template.spec.containers[0].image: missing tag in 'kainlite/blog:'"
+            ]
+        );
+
+        let errors =
+            validate_manifest(&DEPLOYMENT.replace("image: kainlite/blog:abc", "image: ''"));
+        assert_eq!(
+            errors,
+            [
+                "document 1 (Deployment blog): spec.template.spec.containers[0].image: must not be empty"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_metadata_is_required() {
+        let errors = validate_manifest("kind: ConfigMap\ndata:\n  key: value\n");
+        assert_eq!(
+            errors,
+            [
+                "document 1: apiVersion: required",
+                "document 1: metadata.name: required",
+            ]
+        );
+
+        let second = format!("{}---\napiVersion: v1\nmetadata:\n  name: x\n", DEPLOYMENT);
+        assert_eq!(validate_manifest(&second), ["document 2: kind: required"]);
+    }
+
+    #[test]
+    fn test_broken_yaml_is_reported() {
+        let errors = validate_manifest("apiVersion: v1\nkind: [ConfigMap\n");
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].starts_with("document 1: invalid YAML: "),
+            "{}",
+            errors[0]
+        );
+
+        assert_eq!(
+            validate_manifest("- a\n- b\n"),
+            ["document 1: expected a mapping"]
+        );
+    }
+
+    #[test]
+    fn test_validate_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(DEPLOYMENT.as_bytes()).unwrap();
+        assert!(
+            validate_file(file.path().to_str().unwrap())
+                .unwrap()
+                .is_empty()
+        );
+        assert!(validate_file("/nonexistent/app.yaml").is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_validate_manifests_default_from_env() {
+        unsafe { std::env::remove_var("GITOPS_VALIDATE_MANIFESTS") };
+        assert!(!validate_manifests_default());
+        unsafe { std::env::set_var("GITOPS_VALIDATE_MANIFESTS", "true") };
+        assert!(validate_manifests_default());
+        unsafe { std::env::remove_var("GITOPS_VALIDATE_MANIFESTS") };
+    }
+}
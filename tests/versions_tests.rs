@@ -0,0 +1,218 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::versions::{
+        GitTagPattern, PatternPolicy, SemverPolicy, TagPolicy, TagSort, TagSource, natural_cmp,
+        parse_tag, parse_timestamp, same_revision,
+    };
+
+    const TAGS: [&str; 8] = [
+        "v1.3.9",
+        "v1.4.0",
+        "1.4.10",
+        "v1.4.2",
+        "v1.5.0-rc.1",
+        "v1.5.0",
+        "latest",
+        "3c0a88249fb61a0a4f4a65295f42b2dee3963c28",
+    ];
+
+    #[test]
+    fn test_parse_tag_accepts_a_leading_v() {
+        assert_eq!(parse_tag("v1.4.2").unwrap().to_string(), "1.4.2");
+        assert_eq!(parse_tag("1.4.2").unwrap().to_string(), "1.4.2");
+        assert!(parse_tag("latest").is_none());
+        assert!(parse_tag("1.4").is_none());
+    }
+
+    #[test]
+    fn test_newest_tag_within_range() {
+        let tilde = SemverPolicy::parse("~1.4").unwrap();
+        // Versions are compared numerically, and the tag is kept as written.
+        assert_eq!(tilde.newest(TAGS), Some("1.4.10"));
+        assert!(tilde.matches("v1.4.0"));
+        assert!(!tilde.matches("v1.5.0"));
+
+        let caret = SemverPolicy::parse("^1").unwrap();
+        assert_eq!(caret.newest(TAGS), Some("v1.5.0"));
+
+        let range = SemverPolicy::parse(">=1.3, <1.4").unwrap();
+        assert_eq!(range.newest(TAGS), Some("v1.3.9"));
+
+        assert_eq!(SemverPolicy::parse("^2").unwrap().newest(TAGS), None);
+    }
+
+    #[test]
+    fn test_prereleases_need_a_prerelease_range() {
+        let tags = ["v1.4.2", "v1.5.0-rc.1"];
+        assert_eq!(
+            SemverPolicy::parse(">=1.4").unwrap().newest(tags),
+            Some("v1.4.2")
+        );
+        assert_eq!(
+            SemverPolicy::parse(">=1.5.0-rc.0").unwrap().newest(tags),
+            Some("v1.5.0-rc.1")
+        );
+    }
+
+    #[test]
+    fn test_invalid_range_is_an_error() {
+        let err = SemverPolicy::parse("one point four").unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid semver range"));
+    }
+
+    #[test]
+    fn test_pattern_policy_sorts_build_numbers_numerically() {
+        let tags = [
+            "main-3c0a882-9",
+            "main-e4f5a6b-10",
+            "feature-1a2b3c4-11",
+            "main-latest",
+        ];
+        let numeric = PatternPolicy::parse(r"^main-[0-9a-f]+-(\d+)$", TagSort::Numeric).unwrap();
+        assert_eq!(numeric.newest(tags), Some("main-e4f5a6b-10"));
+
+        // Alphabetically "9" sorts after "10".
+        let alphabetical =
+            PatternPolicy::parse(r"^main-[0-9a-f]+-(\d+)$", TagSort::Alphabetical).unwrap();
+        assert_eq!(alphabetical.newest(tags), Some("main-3c0a882-9"));
+
+        // A named group wins over the first one.
+        let named = PatternPolicy::parse(
+            r"^(main|feature)-[0-9a-f]+-(?P<sort>\d+)$",
+            TagSort::Numeric,
+        )
+        .unwrap();
+        assert_eq!(named.newest(tags), Some("feature-1a2b3c4-11"));
+
+        // Without a group the whole tag is the key.
+        let whole = PatternPolicy::parse(r"^main-", TagSort::Alphabetical).unwrap();
+        assert_eq!(whole.newest(tags), Some("main-latest"));
+
+        assert!(PatternPolicy::parse("main-(", TagSort::Numeric).is_err());
+    }
+
+    #[test]
+    fn test_pattern_policy_sorts_timestamps() {
+        let tags = [
+            "build-20240115",
+            "build-20240114T235959Z",
+            "build-20240116093000",
+            "build-1705000000",
+            "build-soon",
+        ];
+        let policy = PatternPolicy::parse(r"^build-(.+)$", TagSort::Timestamp).unwrap();
+        assert_eq!(policy.newest(tags), Some("build-20240116093000"));
+
+        assert_eq!(
+            parse_timestamp("20240115").unwrap().to_string(),
+            "2024-01-15T00:00:00Z"
+        );
+        assert_eq!(
+            parse_timestamp("20240114T235959Z").unwrap().to_string(),
+            "2024-01-14T23:59:59Z"
+        );
+        assert_eq!(
+            parse_timestamp("1705000000").unwrap().to_string(),
+            "2024-01-11T19:06:40Z"
+        );
+        assert!(parse_timestamp("soon").is_none());
+    }
+
+    #[test]
+    fn test_tag_sort_parsing_and_policy_description() {
+        assert_eq!("numeric".parse::<TagSort>().unwrap(), TagSort::Numeric);
+        assert_eq!(
+            " timestamp ".parse::<TagSort>().unwrap(),
+            TagSort::Timestamp
+        );
+        assert!("newest".parse::<TagSort>().is_err());
+
+        let policy = TagPolicy::Pattern(PatternPolicy::parse(r"^\d+$", TagSort::Numeric).unwrap());
+        assert_eq!(policy.describe(), r"/^\d+$/");
+        let policy = TagPolicy::Semver(SemverPolicy::parse("~1.4").unwrap());
+        assert_eq!(policy.describe(), "semver ~1.4");
+    }
+
+    #[test]
+    fn test_git_tag_patterns() {
+        let glob = GitTagPattern::parse("v*").unwrap();
+        assert!(glob.matches("v1.4.2"));
+        assert!(!glob.matches("release-1"));
+        let single = GitTagPattern::parse("release-?").unwrap();
+        assert!(single.matches("release-1"));
+        assert!(!single.matches("release-10"));
+        let dots = GitTagPattern::parse("v1.*").unwrap();
+        assert!(!dots.matches("v1x"));
+
+        let range = GitTagPattern::parse("~1.4").unwrap();
+        assert!(range.matches("v1.4.2"));
+        assert!(!range.matches("v1.5.0"));
+        assert!(GitTagPattern::parse(" ").is_err());
+    }
+
+    #[test]
+    fn test_newest_git_tag() {
+        // Versions are compared as versions, whatever their commits' times.
+        let tags = [("v1.2.0", 300), ("v1.10.0", 100), ("v1.9.1", 200)];
+        assert_eq!(
+            GitTagPattern::parse("v*").unwrap().newest(tags),
+            Some("v1.10.0")
+        );
+        assert_eq!(
+            GitTagPattern::parse("<1.10").unwrap().newest(tags),
+            Some("v1.9.1")
+        );
+
+        // Anything else by the time of its commit.
+        let tags = [("release-b", 100), ("release-a", 200), ("v2.0.0", 300)];
+        assert_eq!(
+            GitTagPattern::parse("release-*").unwrap().newest(tags),
+            Some("release-a")
+        );
+        assert_eq!(
+            GitTagPattern::parse("nightly-*").unwrap().newest(tags),
+            None
+        );
+    }
+
+    #[test]
+    fn test_natural_order() {
+        let mut tags = vec![
+            "build-100",
+            "build-99",
+            "20240101.2",
+            "20240101.10",
+            "b",
+            "a",
+        ];
+        tags.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            tags,
+            [
+                "20240101.2",
+                "20240101.10",
+                "a",
+                "b",
+                "build-99",
+                "build-100"
+            ]
+        );
+        assert_eq!(natural_cmp("v007", "v7"), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn test_revision_labels() {
+        let sha = "3c0a88249fb61a0a4f4a65295f42b2dee3963c28";
+        assert!(same_revision(sha, sha));
+        assert!(same_revision(" 3C0A88249FB6 ", sha));
+        assert!(!same_revision("3c0a88", sha));
+        assert!(!same_revision("4c0a88249fb6", sha));
+
+        assert_eq!("sha".parse::<TagSource>().unwrap(), TagSource::Sha);
+        assert_eq!(
+            "revision_label".parse::<TagSource>().unwrap(),
+            TagSource::RevisionLabel
+        );
+        assert!("label".parse::<TagSource>().is_err());
+    }
+}
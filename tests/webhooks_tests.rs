@@ -0,0 +1,134 @@
+#[cfg(test)]
+mod tests {
+    use gitops_operator::configuration::{Config, Entry};
+    use gitops_operator::webhooks::{RegistrySource, parse_registry_event};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    fn entry_for_image(image_name: &str) -> Entry {
+        let annotations: BTreeMap<String, String> = [
+            ("gitops.operator.enabled", "true"),
+            (
+                "gitops.operator.app_repository",
+                "git@github.com:org/app.git",
+            ),
+            (
+                "gitops.operator.manifest_repository",
+                "git@github.com:org/manifests.git",
+            ),
+            ("gitops.operator.image_name", image_name),
+            ("gitops.operator.deployment_path", "app/deployment.yaml"),
+            ("gitops.operator.ssh_key_name", "ssh-key"),
+            ("gitops.operator.ssh_key_namespace", "gitops-operator"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        Entry {
+            container: image_name.to_string(),
+            name: "app".to_string(),
+            namespace: "default".to_string(),
+            resource_version: None,
+            generation: None,
+            version: "latest".to_string(),
+            containers: vec![],
+            config: Config::from_annotations(&annotations, "default").unwrap(),
+            annotations,
+            paused: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_docker_hub_push() {
+        let payload = json!({
+            "push_data": { "tag": "abc1234", "pusher": "kainlite" },
+            "repository": { "repo_name": "kainlite/blog", "namespace": "kainlite", "name": "blog" }
+        });
+
+        let events = parse_registry_event(&payload).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].source, RegistrySource::DockerHub);
+        assert_eq!(events[0].repository, "kainlite/blog");
+        assert_eq!(events[0].tag.as_deref(), Some("abc1234"));
+    }
+
+    #[test]
+    fn test_parse_harbor_push_with_multiple_resources() {
+        let payload = json!({
+            "type": "PUSH_ARTIFACT",
+            "event_data": {
+                "resources": [
+                    { "tag": "v1", "resource_url": "harbor.example.com/team/app:v1" },
+                    { "tag": "v1-arm64", "resource_url": "harbor.example.com/team/app:v1-arm64" }
+                ],
+                "repository": { "name": "app", "namespace": "team", "repo_full_name": "team/app" }
+            }
+        });
+
+        let events = parse_registry_event(&payload).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.source == RegistrySource::Harbor));
+        assert!(events.iter().all(|e| e.repository == "team/app"));
+        assert_eq!(events[1].tag.as_deref(), Some("v1-arm64"));
+    }
+
+    #[test]
+    fn test_parse_harbor_non_push_event_is_empty() {
+        let payload = json!({
+            "type": "DELETE_ARTIFACT",
+            "event_data": { "repository": { "repo_full_name": "team/app" } }
+        });
+
+        assert_eq!(parse_registry_event(&payload), Some(vec![]));
+    }
+
+    #[test]
+    fn test_parse_ecr_eventbridge_push() {
+        let payload = json!({
+            "detail-type": "ECR Image Action",
+            "source": "aws.ecr",
+            "detail": {
+                "result": "SUCCESS",
+                "repository-name": "services/api",
+                "image-digest": "sha256:7f5b2640fe6fb4f46592dfd3410c4a79dac4f89e4782432e0378abcd1234",
+                "action-type": "PUSH",
+                "image-tag": "4f2c1e0"
+            }
+        });
+
+        let events = parse_registry_event(&payload).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].source, RegistrySource::Ecr);
+        assert_eq!(events[0].repository, "services/api");
+        assert_eq!(events[0].tag.as_deref(), Some("4f2c1e0"));
+    }
+
+    #[test]
+    fn test_parse_ecr_failed_push_is_empty() {
+        let payload = json!({
+            "detail-type": "ECR Image Action",
+            "detail": { "result": "FAILURE", "action-type": "PUSH", "repository-name": "api" }
+        });
+
+        assert_eq!(parse_registry_event(&payload), Some(vec![]));
+    }
+
+    #[test]
+    fn test_parse_unrecognised_payload() {
+        assert!(parse_registry_event(&json!({ "hello": "world" })).is_none());
+    }
+
+    #[test]
+    fn test_event_matches_entry_regardless_of_registry_host() {
+        let payload = json!({
+            "push_data": { "tag": "abc1234" },
+            "repository": { "repo_name": "kainlite/blog" }
+        });
+        let events = parse_registry_event(&payload).unwrap();
+
+        assert!(events[0].matches(&entry_for_image("kainlite/blog")));
+        assert!(events[0].matches(&entry_for_image("ghcr.io/kainlite/blog")));
+        assert!(!events[0].matches(&entry_for_image("kainlite/blog-api")));
+    }
+}